@@ -652,8 +652,6 @@ fn make_button_row(
         let text = ns_string::get_static_str(env, title_text);
         () = msg![env; button setTitle:text forState:UIControlStateNormal];
         () = msg![env; button setFrame:button_frame];
-        // FIXME: manually calling layoutSubviews shouldn't be needed?
-        () = msg![env; button layoutSubviews];
 
         if let Some(font_size) = font_size {
             let label: id = msg![env; button titleLabel];
@@ -158,6 +158,53 @@ impl Bundle {
         Ok(image)
     }
 
+    /// URL schemes the app has registered to handle (`CFBundleURLTypes`).
+    pub fn url_schemes(&self) -> impl Iterator<Item = &str> {
+        self.plist
+            .get("CFBundleURLTypes")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|url_type| url_type.as_dictionary()?.get("CFBundleURLSchemes"))
+            .filter_map(|schemes| schemes.as_array())
+            .flatten()
+            .filter_map(|scheme| scheme.as_string())
+    }
+
+    /// Font files the app wants to be available at launch (`UIAppFonts`),
+    /// relative to the bundle.
+    pub fn app_fonts(&self) -> impl Iterator<Item = &str> {
+        self.plist
+            .get("UIAppFonts")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|font| font.as_string())
+    }
+
+    /// Whether the status bar is initially hidden (`UIStatusBarHidden`).
+    pub fn status_bar_hidden(&self) -> bool {
+        self.plist
+            .get("UIStatusBarHidden")
+            .and_then(|v| v.as_boolean())
+            .unwrap_or(false)
+    }
+
+    /// The initial status bar style (`UIStatusBarStyle`), e.g.
+    /// `UIStatusBarStyleBlackOpaque`.
+    pub fn status_bar_style(&self) -> Option<&str> {
+        self.plist
+            .get("UIStatusBarStyle")
+            .and_then(|v| v.as_string())
+    }
+
+    /// The name of the `UIApplication` subclass to use, if the app has one.
+    pub fn principal_class(&self) -> Option<&str> {
+        self.plist
+            .get("NSPrincipalClass")
+            .and_then(|v| v.as_string())
+    }
+
     pub fn main_nib_file_path(&self) -> Option<GuestPathBuf> {
         self.plist.get("NSMainNibFile").map(|filename| {
             let filename = filename.as_string().unwrap();
@@ -0,0 +1,303 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! On-screen overlay with performance statistics, for debugging and for
+//! reporting performance problems. See the `--debug-overlay` option. It can be
+//! toggled at any time with the F7 key.
+//!
+//! The overlay is drawn by [crate::gles::present::present_frame] on top of the
+//! app's output, so the guest never sees it. The statistics shown are:
+//!
+//! - The number of frames presented per second.
+//! - The guest frame time: the average time between the end of presenting one
+//!   frame and the start of presenting the next, i.e. the time the app spent
+//!   producing a frame, excluding time spent waiting for the framerate limit.
+//!   When the app's UI is composited by Core Animation, which happens at a
+//!   fixed rate, this includes time the app spent idle.
+//! - The number of live Objective-C objects.
+//! - The number of outstanding guest memory allocations and their total size.
+
+use crate::font::{Font, TextAlignment};
+use crate::mem::Mem;
+use crate::objc::ObjC;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames presented within this long of the latest frame are used to calculate
+/// the framerate and frame time.
+const FRAME_WINDOW: Duration = Duration::from_secs(1);
+
+/// Counting objects and allocations isn't free, so it's only done this often.
+const STATS_INTERVAL: Duration = Duration::from_millis(500);
+
+const FONT_SIZE: f32 = 12.0;
+const PADDING: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayStats {
+    pub fps: f32,
+    pub frame_time: Duration,
+    pub live_objects: usize,
+    pub guest_allocations: usize,
+    pub guest_bytes: u64,
+}
+impl OverlayStats {
+    fn lines(&self) -> [String; 4] {
+        [
+            format!("FPS: {:.1}", self.fps),
+            format!(
+                "Frame time: {:.2}ms",
+                self.frame_time.as_secs_f64() * 1000.0
+            ),
+            format!("Objects: {}", self.live_objects),
+            format!(
+                "Guest memory: {:.2}MiB in {} allocations",
+                self.guest_bytes as f64 / (1024.0 * 1024.0),
+                self.guest_allocations
+            ),
+        ]
+    }
+}
+
+/// The overlay's text, rasterized as RGBA8 pixels. The first row is the top.
+pub struct OverlayImage {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct DebugOverlay {
+    visible: bool,
+    /// When recent frames were presented, and their guest frame times. Oldest
+    /// first.
+    frames: VecDeque<(Instant, Duration)>,
+    last_present_end: Option<Instant>,
+    stats: Option<OverlayStats>,
+    stats_updated: Option<Instant>,
+    /// Loaded on first use.
+    font: Option<Font>,
+    /// Cached until the stats change.
+    image: Option<OverlayImage>,
+}
+
+impl DebugOverlay {
+    pub fn new(visible: bool) -> DebugOverlay {
+        DebugOverlay {
+            visible,
+            frames: VecDeque::new(),
+            last_present_end: None,
+            stats: None,
+            stats_updated: None,
+            font: None,
+            image: None,
+        }
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        echo!(
+            "Debug overlay {}.",
+            if self.visible { "shown" } else { "hidden" }
+        );
+    }
+
+    /// Call this when the app asks for a frame to be presented.
+    pub fn begin_present(&mut self, now: Instant) {
+        if let Some(last_present_end) = self.last_present_end {
+            let frame_time = now.saturating_duration_since(last_present_end);
+            self.frames.push_back((now, frame_time));
+        }
+        while let Some(&(time, _)) = self.frames.front() {
+            if now.saturating_duration_since(time) <= FRAME_WINDOW {
+                break;
+            }
+            self.frames.pop_front();
+        }
+    }
+
+    /// Call this once the frame has been presented and any framerate limiting
+    /// is done.
+    pub fn end_present(&mut self, now: Instant) {
+        self.last_present_end = Some(now);
+    }
+
+    fn fps(&self) -> f32 {
+        let (Some(&(first, _)), Some(&(last, _))) = (self.frames.front(), self.frames.back())
+        else {
+            return 0.0;
+        };
+        let span = last.saturating_duration_since(first);
+        if span.is_zero() {
+            return 0.0;
+        }
+        (self.frames.len() - 1) as f32 / span.as_secs_f32()
+    }
+
+    fn frame_time(&self) -> Duration {
+        if self.frames.is_empty() {
+            return Duration::ZERO;
+        }
+        let total: Duration = self.frames.iter().map(|&(_, frame_time)| frame_time).sum();
+        total / self.frames.len() as u32
+    }
+
+    /// Refresh the statistics, if they are due to be refreshed.
+    pub fn update_stats(&mut self, objc: &ObjC, mem: &Mem, now: Instant) {
+        if self
+            .stats_updated
+            .is_some_and(|updated| now.saturating_duration_since(updated) < STATS_INTERVAL)
+        {
+            return;
+        }
+        self.stats_updated = Some(now);
+
+        let live_objects = objc.live_objects(mem);
+        let live_objects = live_objects
+            .refcounted
+            .values()
+            .chain(live_objects.static_lifetime.values())
+            .sum();
+        let (guest_allocations, guest_bytes) = mem.outstanding_allocations();
+        let stats = OverlayStats {
+            fps: self.fps(),
+            frame_time: self.frame_time(),
+            live_objects,
+            guest_allocations,
+            guest_bytes,
+        };
+        if self.stats.as_ref() != Some(&stats) {
+            self.stats = Some(stats);
+            self.image = None;
+        }
+    }
+
+    pub fn stats(&self) -> Option<&OverlayStats> {
+        self.stats.as_ref()
+    }
+
+    /// Get the image to draw over the app's output, if the overlay is visible.
+    pub fn image(&mut self) -> Option<&OverlayImage> {
+        if !self.visible {
+            return None;
+        }
+        let stats = self.stats.as_ref()?;
+        if self.image.is_none() {
+            let font = self.font.get_or_insert_with(Font::sans_regular);
+            self.image = Some(rasterize(font, &stats.lines()));
+        }
+        self.image.as_ref()
+    }
+}
+
+/// Draw white text on a translucent black background.
+fn rasterize(font: &Font, lines: &[String]) -> OverlayImage {
+    let text = lines.join("\n");
+    let (text_width, text_height) = font.calculate_text_size(FONT_SIZE, &text, None);
+    let width = text_width.ceil() as u32 + PADDING * 2;
+    let height = text_height.ceil() as u32 + PADDING * 2;
+
+    let mut coverage = vec![0f32; (width * height) as usize];
+    font.draw(
+        FONT_SIZE,
+        &text,
+        (PADDING as f32, PADDING as f32),
+        None,
+        TextAlignment::Left,
+        |raster_glyph| {
+            let (origin_x, origin_y) = raster_glyph.origin();
+            let (glyph_width, glyph_height) = raster_glyph.dimensions();
+            for glyph_y in 0..glyph_height {
+                for glyph_x in 0..glyph_width {
+                    let x = origin_x as i32 + glyph_x;
+                    let y = origin_y as i32 + glyph_y;
+                    if !(0..width as i32).contains(&x) || !(0..height as i32).contains(&y) {
+                        continue;
+                    }
+                    let pixel = &mut coverage[y as usize * width as usize + x as usize];
+                    *pixel = pixel.max(raster_glyph.pixel_at((glyph_x, glyph_y)));
+                }
+            }
+        },
+    );
+
+    // Premultiplied alpha, to match the blend function used for drawing.
+    let background_alpha = 0.6;
+    let pixels = coverage
+        .into_iter()
+        .flat_map(|coverage| {
+            let alpha = coverage + background_alpha * (1.0 - coverage);
+            let white = (coverage * 255.0) as u8;
+            [white, white, white, (alpha * 255.0) as u8]
+        })
+        .collect();
+
+    OverlayImage {
+        pixels,
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objc::TrivialHostObject;
+
+    #[test]
+    fn plausible_stats() {
+        let mut mem = Mem::new();
+        let mut objc = ObjC::new();
+        let mut overlay = DebugOverlay::new(true);
+
+        // A simple app: it allocates some objects and memory, then renders at
+        // 60fps, spending 5ms on each frame and then waiting for the framerate
+        // limit.
+        let class = objc.get_known_class("NSObject", &mut mem);
+        for _ in 0..10 {
+            objc.alloc_object(class, Box::new(TrivialHostObject), &mut mem);
+        }
+        let (allocations_before, bytes_before) = mem.outstanding_allocations();
+        mem.alloc(1024);
+
+        let frame_interval = Duration::from_secs(1) / 60;
+        let mut now = Instant::now();
+        for _ in 0..120 {
+            now += Duration::from_millis(5);
+            overlay.begin_present(now);
+            now += frame_interval - Duration::from_millis(5);
+            overlay.end_present(now);
+        }
+        overlay.update_stats(&objc, &mem, now);
+
+        let stats = overlay.stats().unwrap();
+        assert!((59.0..=61.0).contains(&stats.fps), "{}", stats.fps);
+        assert!(
+            (Duration::from_micros(4900)..=Duration::from_micros(5100)).contains(&stats.frame_time),
+            "{:?}",
+            stats.frame_time
+        );
+        assert_eq!(stats.live_objects, 10);
+        assert_eq!(stats.guest_allocations, allocations_before + 1);
+        assert!(stats.guest_bytes >= bytes_before + 1024);
+
+        // The stats aren't recounted too often.
+        objc.alloc_object(class, Box::new(TrivialHostObject), &mut mem);
+        overlay.update_stats(&objc, &mem, now + STATS_INTERVAL / 2);
+        assert_eq!(overlay.stats().unwrap().live_objects, 10);
+        overlay.update_stats(&objc, &mem, now + STATS_INTERVAL);
+        assert_eq!(overlay.stats().unwrap().live_objects, 11);
+    }
+
+    #[test]
+    fn no_frames() {
+        let overlay = DebugOverlay::new(false);
+        assert_eq!(overlay.fps(), 0.0);
+        assert_eq!(overlay.frame_time(), Duration::ZERO);
+    }
+}
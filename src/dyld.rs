@@ -28,7 +28,7 @@ use crate::cpu::Cpu;
 use crate::frameworks::foundation::ns_string;
 use crate::mach_o::{MachO, SectionType};
 use crate::mem::{ConstVoidPtr, GuestUSize, Mem, MutPtr, Ptr};
-use crate::objc::{nil, ObjC};
+use crate::objc::{class_name_for_block_isa_symbol, nil, ObjC};
 use crate::Environment;
 use std::collections::HashMap;
 
@@ -326,6 +326,11 @@ impl Dyld {
                 objc.link_class(name, /* is_metaclass: */ true, mem)
                     .cast()
                     .cast_const()
+            } else if let Some(name) = class_name_for_block_isa_symbol(name) {
+                // Global blocks' isa
+                objc.link_class(name, /* is_metaclass: */ false, mem)
+                    .cast()
+                    .cast_const()
             } else if name == "___CFConstantStringClassReference" {
                 // See ns_string::register_constant_strings
                 nil.cast().cast_const()
@@ -391,6 +396,13 @@ impl Dyld {
                 }
             }
 
+            if let Some(name) = class_name_for_block_isa_symbol(symbol) {
+                // Stack blocks' isa
+                let class = objc.link_class(name, /* is_metaclass: */ false, mem);
+                mem.write(ptr_ptr, class.cast().cast_const());
+                continue;
+            }
+
             if let Some((symbol, _)) = search_lists(function_lists::FUNCTION_LISTS, symbol) {
                 // We want the same symbol name to always point to the same
                 // function. It could point to a specific stub entry, but it's
@@ -7,25 +7,47 @@
 //! very long and frequently-updated list.
 
 use crate::frameworks::{
-    core_foundation, core_graphics, foundation, media_player, opengles, uikit,
+    address_book, core_animation, core_foundation, core_graphics, core_location, foundation,
+    media_player, opengles, store_kit, uikit,
 };
 use crate::libc;
 
 /// All the lists of constants that the linker should search through.
 pub const CONSTANT_LISTS: &[super::ConstantExports] = &[
     libc::ctype::CONSTANTS,
+    libc::dispatch::CONSTANTS,
     libc::stdio::CONSTANTS,
+    crate::objc::CONSTANTS,
+    address_book::ab_person::CONSTANTS,
+    core_animation::ca_animation::CONSTANTS,
+    core_animation::ca_gradient_layer::CONSTANTS,
+    core_animation::ca_shape_layer::CONSTANTS,
+    core_animation::ca_transaction::CONSTANTS,
     core_foundation::cf_allocator::CONSTANTS,
+    core_foundation::cf_array::CONSTANTS,
+    core_foundation::cf_dictionary::CONSTANTS,
+    core_foundation::cf_preferences::CONSTANTS,
     core_foundation::cf_run_loop::CONSTANTS,
     core_graphics::cg_affine_transform::CONSTANTS,
     core_graphics::cg_color_space::CONSTANTS,
     core_graphics::cg_geometry::CONSTANTS,
+    core_location::cl_location::CONSTANTS,
     foundation::ns_error::CONSTANTS,
     foundation::ns_exception::CONSTANTS,
+    foundation::ns_http_cookie_storage::CONSTANTS,
     foundation::ns_keyed_unarchiver::CONSTANTS,
     foundation::ns_locale::CONSTANTS,
+    foundation::ns_object::CONSTANTS,
     foundation::ns_run_loop::CONSTANTS,
+    foundation::ns_stream::CONSTANTS,
+    foundation::ns_url_connection::CONSTANTS,
     media_player::movie_player::CONSTANTS,
     opengles::eagl::CONSTANTS,
+    store_kit::sk_payment_queue::CONSTANTS,
+    uikit::ui_application::CONSTANTS,
     uikit::ui_device::CONSTANTS,
+    uikit::ui_image_picker_controller::CONSTANTS,
+    uikit::ui_pasteboard::CONSTANTS,
+    uikit::ui_view::ui_keyboard::CONSTANTS,
+    uikit::ui_view::ui_scroll_view::ui_text_view::CONSTANTS,
 ];
@@ -7,27 +7,33 @@
 //! very long and frequently-updated list.
 
 use crate::frameworks::{
-    audio_toolbox, core_foundation, core_graphics, dnssd, foundation, openal, opengles, uikit,
+    address_book, audio_toolbox, core_foundation, core_graphics, core_location, core_text, dnssd,
+    foundation, map_kit, openal, opengles, system_configuration, uikit,
 };
 use crate::libc;
 
 /// All the lists of functions that the linker should search through.
 pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     libc::clocale::FUNCTIONS,
+    libc::common_crypto::FUNCTIONS,
     libc::ctype::FUNCTIONS,
     libc::cxxabi::FUNCTIONS,
     libc::dirent::FUNCTIONS,
+    libc::dispatch::FUNCTIONS,
     libc::dlfcn::FUNCTIONS,
     libc::errno::FUNCTIONS,
     libc::ifaddrs::FUNCTIONS,
     libc::keymgr::FUNCTIONS,
     libc::mach_thread_info::FUNCTIONS,
     libc::mach_time::FUNCTIONS,
+    libc::malloc::FUNCTIONS,
     libc::math::FUNCTIONS,
     libc::mmap::FUNCTIONS,
     libc::net::if_::FUNCTIONS,
+    libc::netdb::FUNCTIONS,
     libc::posix_io::FUNCTIONS,
     libc::posix_io::stat::FUNCTIONS,
+    libc::pthread::cond::FUNCTIONS,
     libc::pthread::key::FUNCTIONS,
     libc::pthread::mutex::FUNCTIONS,
     libc::pthread::once::FUNCTIONS,
@@ -41,13 +47,18 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     libc::stdlib::FUNCTIONS,
     libc::stdlib::qsort::FUNCTIONS,
     libc::string::FUNCTIONS,
+    libc::sys::socket::FUNCTIONS,
     libc::sys::timeb::FUNCTIONS,
     libc::sys::utsname::FUNCTIONS,
     libc::sysctl::FUNCTIONS,
     libc::time::FUNCTIONS,
     libc::unistd::FUNCTIONS,
+    libc::unwind::FUNCTIONS,
     libc::wchar::FUNCTIONS,
     crate::objc::FUNCTIONS,
+    address_book::ab_address_book::FUNCTIONS,
+    address_book::ab_multi_value::FUNCTIONS,
+    address_book::ab_person::FUNCTIONS,
     audio_toolbox::audio_components::FUNCTIONS,
     audio_toolbox::audio_file::FUNCTIONS,
     audio_toolbox::audio_queue::FUNCTIONS,
@@ -56,27 +67,39 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     core_foundation::cf_array::FUNCTIONS,
     core_foundation::cf_bundle::FUNCTIONS,
     core_foundation::cf_data::FUNCTIONS,
+    core_foundation::cf_dictionary::FUNCTIONS,
+    core_foundation::cf_preferences::FUNCTIONS,
     core_foundation::cf_run_loop::FUNCTIONS,
     core_foundation::cf_run_loop_timer::FUNCTIONS,
+    core_foundation::cf_stream::FUNCTIONS,
     core_foundation::cf_string::FUNCTIONS,
     core_foundation::cf_type::FUNCTIONS,
     core_foundation::cf_url::FUNCTIONS,
     core_foundation::time::FUNCTIONS,
     core_graphics::cg_affine_transform::FUNCTIONS,
     core_graphics::cg_bitmap_context::FUNCTIONS,
+    core_graphics::cg_color::FUNCTIONS,
     core_graphics::cg_color_space::FUNCTIONS,
     core_graphics::cg_context::FUNCTIONS,
     core_graphics::cg_data_provider::FUNCTIONS,
     core_graphics::cg_geometry::FUNCTIONS,
+    core_graphics::cg_gradient::FUNCTIONS,
     core_graphics::cg_image::FUNCTIONS,
+    core_graphics::cg_path::FUNCTIONS,
+    core_location::cl_location::FUNCTIONS,
+    core_text::ct_font_manager::FUNCTIONS,
     dnssd::FUNCTIONS,
     foundation::FUNCTIONS,
     foundation::ns_file_manager::FUNCTIONS,
+    foundation::ns_exception::FUNCTIONS,
     foundation::ns_log::FUNCTIONS,
     foundation::ns_objc_runtime::FUNCTIONS,
+    map_kit::mk_geometry::FUNCTIONS,
     openal::FUNCTIONS,
     opengles::FUNCTIONS,
+    system_configuration::sc_network_reachability::FUNCTIONS,
     uikit::ui_application::FUNCTIONS,
     uikit::ui_geometry::FUNCTIONS,
     uikit::ui_graphics::FUNCTIONS,
+    uikit::ui_image::FUNCTIONS,
 ];
@@ -9,13 +9,15 @@
 //! via the re-exports one level up.
 
 mod mutex;
+mod throttle;
 
 use crate::abi::GuestRet;
+use crate::libc::pthread::cond::pthread_cond_t;
 use crate::libc::semaphore::sem_t;
 use crate::mem::{MutPtr, MutVoidPtr};
 use crate::{
-    abi, bundle, cpu, dyld, frameworks, fs, gdb, image, libc, mach_o, mem, objc, options, stack,
-    window,
+    abi, bundle, cpu, debug_overlay, dyld, frameworks, fs, gdb, image, libc, mach_o, mem, objc,
+    options, stack, window,
 };
 use std::net::TcpListener;
 use std::time::{Duration, Instant};
@@ -94,6 +96,8 @@ pub struct Environment {
     pub framework_state: frameworks::State,
     pub mutex_state: mutex::MutexState,
     pub options: options::Options,
+    pub throttle: throttle::Throttle,
+    pub debug_overlay: debug_overlay::DebugOverlay,
     gdb_server: Option<gdb::GdbServer>,
 }
 
@@ -120,6 +124,9 @@ enum ThreadBlock {
     Mutex(MutexId),
     // Thread is waiting on a semaphore.
     Semaphore(MutPtr<sem_t>),
+    // Thread is waiting on a condition variable, and will then wait for the
+    // mutex to unlock.
+    Condition(MutPtr<pthread_cond_t>, MutexId),
     // Thread is waiting for another thread to finish (joining).
     Joining(ThreadId, MutPtr<MutVoidPtr>),
     // Deferred guest-to-host return
@@ -238,10 +245,14 @@ impl Environment {
         let mut dyld = dyld::Dyld::new();
         dyld.do_initial_linking(&bins, &mut mem, &mut objc);
 
-        let cpu = cpu::Cpu::new(match options.direct_memory_access {
-            true => Some(&mut mem),
-            false => None,
-        });
+        mem.set_strict_checking(options.strict_memory_checking);
+        // Strict checking relies on dynarmic using the memory access callbacks.
+        let cpu = cpu::Cpu::new(
+            match options.direct_memory_access && !options.strict_memory_checking {
+                true => Some(&mut mem),
+                false => None,
+            },
+        );
 
         let main_thread = Thread {
             active: true,
@@ -268,6 +279,11 @@ impl Environment {
             libc_state: Default::default(),
             mutex_state: Default::default(),
             framework_state: Default::default(),
+            throttle: throttle::Throttle::new(
+                options.cpu_speed.map(|mips| mips * 1_000_000.0),
+                options.turbo,
+            ),
+            debug_overlay: debug_overlay::DebugOverlay::new(options.debug_overlay),
             options,
             gdb_server: None,
         };
@@ -371,10 +387,14 @@ impl Environment {
         let mut dyld = dyld::Dyld::new();
         dyld.do_initial_linking_with_no_bins(&mut mem, &mut objc);
 
-        let cpu = cpu::Cpu::new(match options.direct_memory_access {
-            true => Some(&mut mem),
-            false => None,
-        });
+        mem.set_strict_checking(options.strict_memory_checking);
+        // Strict checking relies on dynarmic using the memory access callbacks.
+        let cpu = cpu::Cpu::new(
+            match options.direct_memory_access && !options.strict_memory_checking {
+                true => Some(&mut mem),
+                false => None,
+            },
+        );
 
         let main_thread = Thread {
             active: true,
@@ -401,6 +421,11 @@ impl Environment {
             libc_state: Default::default(),
             mutex_state: Default::default(),
             framework_state: Default::default(),
+            throttle: throttle::Throttle::new(
+                options.cpu_speed.map(|mips| mips * 1_000_000.0),
+                options.turbo,
+            ),
+            debug_overlay: debug_overlay::DebugOverlay::new(options.debug_overlay),
             options,
             gdb_server: None,
         };
@@ -425,6 +450,50 @@ impl Environment {
         Ok(env)
     }
 
+    /// Exit the emulator, printing the leak report first if it was requested.
+    pub fn exit(&mut self, exit_code: i32) -> ! {
+        if self.options.leak_report {
+            self.print_leak_report();
+        }
+        std::process::exit(exit_code);
+    }
+
+    /// Print a summary of the Objective-C objects and guest heap allocations
+    /// that are still alive. This helps with finding missing `release` calls.
+    fn print_leak_report(&self) {
+        let objc::LiveObjects {
+            refcounted,
+            static_lifetime,
+        } = self.objc.live_objects(&self.mem);
+
+        let print_counts = |counts: &std::collections::BTreeMap<String, usize>| {
+            let mut counts: Vec<_> = counts.iter().collect();
+            // Most common first.
+            counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+            for (class_name, count) in counts {
+                echo!("  {:6} {}", count, class_name);
+            }
+        };
+
+        echo!("Leak report:");
+        echo!(
+            "Reference-counted Objective-C objects still alive, probably leaked: {}",
+            refcounted.values().sum::<usize>()
+        );
+        print_counts(&refcounted);
+        echo!(
+            "Static-lifetime Objective-C objects (e.g. singletons), leaked on purpose: {}",
+            static_lifetime.values().sum::<usize>()
+        );
+        print_counts(&static_lifetime);
+        let (count, bytes) = self.mem.outstanding_allocations();
+        echo!(
+            "Guest heap allocations still outstanding (including the objects above and thread stacks): {} ({} bytes)",
+            count,
+            bytes
+        );
+    }
+
     /// Get a shared reference to the window. Panics if touchHLE is running in
     /// headless mode.
     pub fn window(&self) -> &window::Window {
@@ -441,7 +510,20 @@ impl Environment {
         )
     }
 
-    fn stack_trace(&self) {
+    /// Format a guest code address for debug output, with the name of the
+    /// symbol it belongs to if one can be found.
+    fn describe_code_address(&self, addr: u32) -> String {
+        for bin in &self.bins {
+            if let Some((symbol, offset)) = bin.symbol_for_address(addr) {
+                return format!("{:#x} ({}: {} + {:#x})", addr, bin.name, symbol, offset);
+            }
+        }
+        format!("{:#x}", addr)
+    }
+
+    /// Print a stack trace of the current guest thread, using the chain of
+    /// frame records.
+    pub fn stack_trace(&self) {
         if self.current_thread == 0 {
             echo!("Attempting to produce stack trace for main thread:");
         } else {
@@ -452,8 +534,8 @@ impl Environment {
         }
         let stack_range = self.threads[self.current_thread].stack.clone().unwrap();
         echo!(
-            " 0. {:#x} (PC)",
-            self.cpu.pc_with_thumb_bit().addr_with_thumb_bit()
+            " 0. {} (PC)",
+            self.describe_code_address(self.cpu.pc_with_thumb_bit().addr_with_thumb_bit())
         );
         let regs = self.cpu.regs();
         let mut lr = regs[cpu::Cpu::LR];
@@ -465,7 +547,7 @@ impl Environment {
             echo!(" 1. [thread exit] (LR)");
             return;
         } else {
-            echo!(" 1. {:#x} (LR)", lr);
+            echo!(" 1. {} (LR)", self.describe_code_address(lr));
         }
         let mut i = 2;
         let mut fp: mem::ConstPtr<u8> = mem::Ptr::from_bits(regs[abi::FRAME_POINTER]);
@@ -482,7 +564,7 @@ impl Environment {
                 echo!("{:2}. [thread exit]", i);
                 return;
             } else {
-                echo!("{:2}. {:#x}", i, lr);
+                echo!("{:2}. {}", i, self.describe_code_address(lr));
             }
             i += 1;
         }
@@ -792,8 +874,14 @@ impl Environment {
                                 self.current_thread,
                                 initial_thread
                             );
+                            let return_value = GuestRet::from_regs(self.cpu.regs());
+                            // Thread-specific data destructors are guest code,
+                            // so they must run before the thread is gone.
+                            self.threads[self.current_thread].in_host_function = true;
+                            libc::pthread::key::run_destructors(self);
+                            self.threads[self.current_thread].in_host_function = false;
                             let curr_thread = &mut self.threads[self.current_thread];
-                            curr_thread.return_value = Some(GuestRet::from_regs(self.cpu.regs()));
+                            curr_thread.return_value = Some(return_value);
                             curr_thread.active = false;
                             let stack = curr_thread.stack.take().unwrap();
                             let stack: mem::MutVoidPtr = mem::Ptr::from_bits(*stack.start());
@@ -883,6 +971,7 @@ impl Environment {
             } else {
                 100_000
             };
+            let ticks_budget = ticks;
             let mut step_and_debug = false;
             while ticks > 0 {
                 let state = self.cpu.run_or_step(
@@ -911,6 +1000,10 @@ impl Environment {
                 }
             }
 
+            if let Some(delay) = self.throttle.account(ticks_budget - ticks, Instant::now()) {
+                std::thread::sleep(delay);
+            }
+
             // To maintain responsiveness when moving the window and so on, we
             // need to poll for events occasionally, even if the app isn't
             // actively processing them.
@@ -978,6 +1071,9 @@ impl Environment {
                                 break;
                             }
                         }
+                        // These only become unblocked when the condition
+                        // is signalled (see Environment::signal_condition).
+                        ThreadBlock::Condition(..) => (),
                         ThreadBlock::Joining(joinee_thread, ptr) => {
                             if !self.threads[joinee_thread].active {
                                 log_dbg!(
@@ -8,8 +8,10 @@
 use std::collections::HashMap;
 use std::num::NonZeroU32;
 
-use super::{Environment, ThreadId};
+use super::{Environment, ThreadBlock, ThreadId};
 use crate::libc::errno::{EBUSY, EDEADLK, EPERM};
+use crate::libc::pthread::cond::pthread_cond_t;
+use crate::mem::MutPtr;
 
 /// Stores and manages mutexes. Note that all the methods for locking and
 /// unlocking mutexes are on [Environment] instead, because they interact with
@@ -232,4 +234,66 @@ impl Environment {
             Ok(lock_count.get() - 1)
         }
     }
+
+    /// Unlocks a mutex and blocks the current thread until the condition
+    /// variable is signalled with [Self::signal_condition], then relocks the
+    /// mutex. Returns an error (as errno) if the mutex couldn't be unlocked.
+    /// Similar to `pthread_cond_wait`, but for host code.
+    /// NOTE: Like [Self::lock_mutex], this only takes effect _after_ the
+    /// calling function returns to the host run loop
+    /// ([crate::Environment::run]).
+    pub fn wait_on_condition(
+        &mut self,
+        cond: MutPtr<pthread_cond_t>,
+        mutex_id: MutexId,
+    ) -> Result<(), i32> {
+        self.unlock_mutex(mutex_id)?;
+
+        // Add to the waiting count, so that the mutex isn't destroyed before
+        // it is relocked. This is subtracted in relock_unblocked_mutex.
+        self.mutex_state
+            .mutexes
+            .get_mut(&mutex_id)
+            .unwrap()
+            .waiting_count += 1;
+
+        let current_thread = self.current_thread;
+        assert!(matches!(
+            self.threads[current_thread].blocked_by,
+            ThreadBlock::NotBlocked
+        ));
+        log_dbg!(
+            "Thread {} waiting on condition {:?} with mutex #{}.",
+            current_thread,
+            cond,
+            mutex_id
+        );
+        self.threads[current_thread].blocked_by = ThreadBlock::Condition(cond, mutex_id);
+        Ok(())
+    }
+
+    /// Wakes up one of the threads waiting on a condition variable, or all of
+    /// them if `broadcast` is [true]. Each thread woken then waits for its
+    /// mutex to unlock. Similar to `pthread_cond_signal` and
+    /// `pthread_cond_broadcast`, but for host code.
+    pub fn signal_condition(&mut self, cond: MutPtr<pthread_cond_t>, broadcast: bool) {
+        for (i, thread) in self.threads.iter_mut().enumerate() {
+            let ThreadBlock::Condition(waiting_on, mutex_id) = thread.blocked_by else {
+                continue;
+            };
+            if waiting_on != cond {
+                continue;
+            }
+            log_dbg!(
+                "Thread {} woken by condition {:?}, waiting for mutex #{}.",
+                i,
+                cond,
+                mutex_id
+            );
+            thread.blocked_by = ThreadBlock::Mutex(mutex_id);
+            if !broadcast {
+                break;
+            }
+        }
+    }
 }
@@ -0,0 +1,144 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! CPU speed throttling and turbo mode.
+//!
+//! Some old games assume a particular CPU speed and misbehave if emulated code
+//! runs much faster. The throttle keeps the rate at which ticks (roughly,
+//! instructions) are executed at or below a target rate, by sleeping when
+//! emulation gets ahead of the wall clock.
+//!
+//! Turbo mode disables both this throttle and the framerate limit, which is
+//! useful for getting through loading screens quickly. Timers are still based
+//! on the wall clock, so time-based animations keep their speed.
+
+use std::time::{Duration, Instant};
+
+/// If emulation has fallen behind the target rate by more than this, the
+/// deficit is forgotten, so that the CPU doesn't then run too fast for a long
+/// time to catch up. This is similar to the framerate limiter's slop.
+const MAX_DEFICIT: Duration = Duration::from_millis(100);
+
+/// Sleeping for very short periods is inefficient, so emulation is allowed to
+/// get this far ahead before sleeping.
+const MIN_SLEEP: Duration = Duration::from_millis(1);
+
+pub struct Throttle {
+    /// Target number of ticks per second, if throttling is enabled.
+    ticks_per_second: Option<f64>,
+    turbo: bool,
+    /// When the current measurement period began.
+    period_start: Option<Instant>,
+    /// Ticks executed since the start of the current measurement period.
+    period_ticks: u64,
+}
+
+impl Throttle {
+    pub fn new(ticks_per_second: Option<f64>, turbo: bool) -> Throttle {
+        Throttle {
+            ticks_per_second,
+            turbo,
+            period_start: None,
+            period_ticks: 0,
+        }
+    }
+
+    /// Whether turbo mode is on. When it is, the framerate limit should be
+    /// ignored too.
+    pub fn turbo(&self) -> bool {
+        self.turbo
+    }
+
+    pub fn toggle_turbo(&mut self) {
+        self.turbo = !self.turbo;
+        // Don't try to make up for the time spent in turbo mode.
+        self.period_start = None;
+        echo!("Turbo mode {}.", if self.turbo { "on" } else { "off" });
+    }
+
+    /// Account for `ticks` having been executed by the time `now`, and get how
+    /// long to sleep for to stay on the target rate, if at all.
+    pub fn account(&mut self, ticks: u64, now: Instant) -> Option<Duration> {
+        let ticks_per_second = self.ticks_per_second?;
+        if self.turbo {
+            return None;
+        }
+
+        let Some(period_start) = self.period_start else {
+            self.period_start = Some(now);
+            self.period_ticks = ticks;
+            return None;
+        };
+        self.period_ticks += ticks;
+
+        let simulated = Duration::from_secs_f64(self.period_ticks as f64 / ticks_per_second);
+        let real = now.duration_since(period_start);
+        if simulated >= real + MIN_SLEEP {
+            Some(simulated - real)
+        } else {
+            if real > simulated + MAX_DEFICIT {
+                log_dbg!("CPU throttle fell too far behind, resetting.");
+                self.period_start = Some(now);
+                self.period_ticks = 0;
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulated_clock_matches_wall_clock() {
+        // 1,000,000 ticks per second, executed in slices of 100,000 ticks that
+        // take 10ms of real time each, so 10× too fast.
+        let mut throttle = Throttle::new(Some(1_000_000.0), false);
+        let start = Instant::now();
+        assert_eq!(throttle.account(0, start), None);
+
+        let mut now = start;
+        let mut total_ticks = 0;
+        for _ in 0..10 {
+            now += Duration::from_millis(10);
+            total_ticks += 100_000;
+            let sleep = throttle.account(100_000, now).unwrap();
+            now += sleep;
+            // After sleeping, the wall clock has caught up with the simulated
+            // clock.
+            let simulated = Duration::from_secs_f64(total_ticks as f64 / 1_000_000.0);
+            assert_eq!(now.duration_since(start), simulated);
+        }
+    }
+
+    #[test]
+    fn unthrottled_and_turbo() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(None, false);
+        throttle.account(0, start);
+        assert_eq!(throttle.account(1_000_000_000, start), None);
+
+        let mut throttle = Throttle::new(Some(1_000_000.0), true);
+        throttle.account(0, start);
+        assert_eq!(throttle.account(1_000_000_000, start), None);
+        throttle.toggle_turbo();
+        throttle.account(0, start);
+        assert!(throttle.account(1_000_000_000, start).is_some());
+    }
+
+    #[test]
+    fn deficit_is_forgotten() {
+        let mut throttle = Throttle::new(Some(1_000_000.0), false);
+        let start = Instant::now();
+        throttle.account(0, start);
+        // Fall far behind: 1 second of real time for 0.1 seconds of ticks.
+        let now = start + Duration::from_secs(1);
+        assert_eq!(throttle.account(100_000, now), None);
+        // Running fast now should be throttled straight away rather than
+        // catching up.
+        assert!(throttle.account(100_000, now).is_some());
+    }
+}
@@ -19,6 +19,15 @@ use std::io::Read;
 
 pub struct Font {
     font: rusttype::Font<'static>,
+    names: FontNames,
+}
+
+/// Names from a font file's `name` table.
+#[derive(Default)]
+struct FontNames {
+    family: Option<String>,
+    full: Option<String>,
+    postscript: Option<String>,
 }
 
 pub enum TextAlignment {
@@ -79,11 +88,33 @@ impl Font {
             );
         }
 
-        let Some(font) = rusttype::Font::try_from_vec(bytes) else {
+        let Ok(font) = Self::from_bytes(bytes) else {
             panic!("Couldn't parse bundled font file {:?}. This probably means the file is corrupt. Try re-downloading it.", path);
         };
+        font
+    }
+
+    /// Load a TrueType or OpenType font from the contents of a font file, e.g.
+    /// one provided by an app.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Font, String> {
+        let names = read_names(&bytes).unwrap_or_default();
+        let Some(font) = rusttype::Font::try_from_vec(bytes) else {
+            return Err("not a valid TrueType or OpenType font".to_string());
+        };
+        Ok(Font { font, names })
+    }
 
-        Font { font }
+    /// The family name, e.g. "Liberation Sans".
+    pub fn family_name(&self) -> Option<&str> {
+        self.names.family.as_deref()
+    }
+    /// The full name, e.g. "Liberation Sans Bold".
+    pub fn full_name(&self) -> Option<&str> {
+        self.names.full.as_deref()
+    }
+    /// The PostScript name, e.g. "LiberationSans-Bold".
+    pub fn postscript_name(&self) -> Option<&str> {
+        self.names.postscript.as_deref()
     }
 
     pub fn sans_regular() -> Font {
@@ -364,3 +395,80 @@ impl Font {
         }
     }
 }
+
+/// Read the names from a font file's `name` table. RustType doesn't provide
+/// access to these, so the table is parsed here.
+fn read_names(bytes: &[u8]) -> Option<FontNames> {
+    let u16_at = |offset: usize| -> Option<u16> {
+        Some(u16::from_be_bytes(
+            bytes.get(offset..offset + 2)?.try_into().unwrap(),
+        ))
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(
+            bytes.get(offset..offset + 4)?.try_into().unwrap(),
+        ))
+    };
+
+    // Find the name table in the table directory.
+    let table_count = u16_at(4)? as usize;
+    let name_table = (0..table_count)
+        .map(|i| 12 + i * 16)
+        .find(|&record| bytes.get(record..record + 4) == Some(&b"name"[..]))
+        .and_then(|record| u32_at(record + 8))? as usize;
+
+    let record_count = u16_at(name_table + 2)? as usize;
+    let strings = name_table + u16_at(name_table + 4)? as usize;
+
+    // Name IDs: 1 is the family, 4 is the full name, 6 is the PostScript
+    // name and 16 is the "typographic" family, which is preferred over the
+    // plain family if present.
+    let mut names: [Option<(u32, String)>; 17] = Default::default();
+    for i in 0..record_count {
+        let record = name_table + 6 + i * 12;
+        let platform_id = u16_at(record)?;
+        let encoding_id = u16_at(record + 2)?;
+        let language_id = u16_at(record + 4)?;
+        let name_id = u16_at(record + 6)? as usize;
+        let length = u16_at(record + 8)? as usize;
+        let offset = strings + u16_at(record + 10)? as usize;
+        if name_id >= names.len() {
+            continue;
+        }
+        let Some(data) = bytes.get(offset..offset + length) else {
+            continue;
+        };
+
+        // Lower is better: English Windows names, then any Unicode names, then
+        // Mac Roman names (decoded as if they were Latin-1).
+        let (score, string) = match (platform_id, encoding_id) {
+            (0, _) | (3, 0) | (3, 1) | (3, 10) => {
+                let units: Vec<u16> = data
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect();
+                let score = if platform_id == 3 && language_id == 0x409 {
+                    0
+                } else {
+                    1
+                };
+                (score, String::from_utf16_lossy(&units))
+            }
+            (1, 0) => (2, data.iter().map(|&b| b as char).collect()),
+            _ => continue,
+        };
+        if names[name_id]
+            .as_ref()
+            .map_or(true, |&(best_score, _)| score < best_score)
+        {
+            names[name_id] = Some((score, string));
+        }
+    }
+
+    let mut take = |name_id: usize| names[name_id].take().map(|(_, string)| string);
+    Some(FontNames {
+        family: take(16).or_else(|| take(1)),
+        full: take(4),
+        postscript: take(6),
+    })
+}
@@ -0,0 +1,14 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Address Book framework.
+//!
+//! touchHLE doesn't read the host's contacts. Instead, apps are always granted
+//! access to an address book containing the people set with the `--contacts=`
+//! option, which is empty by default.
+
+pub mod ab_address_book;
+pub mod ab_multi_value;
+pub mod ab_person;
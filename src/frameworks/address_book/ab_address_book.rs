@@ -0,0 +1,131 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABAddressBook.h`
+
+use super::ab_person::{new_person, ABRecordID};
+use crate::abi::CallFromHost;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_array::CFArrayRef;
+use crate::frameworks::core_foundation::cf_dictionary::CFDictionaryRef;
+use crate::frameworks::core_foundation::{CFIndex, CFTypeRef};
+use crate::frameworks::foundation::ns_array;
+use crate::mem::MutPtr;
+use crate::objc::{
+    get_block_invoke, id, msg_super, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub type ABAddressBookRef = CFTypeRef;
+
+type ABAuthorizationStatus = CFIndex;
+const kABAuthorizationStatusAuthorized: ABAuthorizationStatus = 3;
+
+struct ABAddressBookHostObject {
+    /// `ABRecordRef`s, strong references.
+    people: Vec<id>,
+}
+impl HostObject for ABAddressBookHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// ABAddressBook is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_ABAddressBook: NSObject
+
+- (())dealloc {
+    let people = std::mem::take(&mut env.objc.borrow_mut::<ABAddressBookHostObject>(this).people);
+    for person in people {
+        release(env, person);
+    }
+    msg_super![env; this dealloc]
+}
+
+@end
+
+};
+
+fn ABAddressBookCreate(env: &mut Environment) -> ABAddressBookRef {
+    let contacts = env.options.contacts.clone();
+    let mut people = Vec::with_capacity(contacts.len());
+    for (index, (name, phone)) in contacts.iter().enumerate() {
+        let record_id = ABRecordID::try_from(index).unwrap() + 1;
+        people.push(new_person(env, record_id, name, phone.as_deref()));
+    }
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_ABAddressBook", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(ABAddressBookHostObject { people }),
+        &mut env.mem,
+    )
+}
+
+/// iOS 6 replacement for [ABAddressBookCreate].
+fn ABAddressBookCreateWithOptions(
+    env: &mut Environment,
+    _options: CFDictionaryRef,
+    error: MutPtr<id>, // CFErrorRef*
+) -> ABAddressBookRef {
+    if !error.is_null() {
+        env.mem.write(error, nil);
+    }
+    ABAddressBookCreate(env)
+}
+
+fn ABAddressBookGetAuthorizationStatus(_env: &mut Environment) -> ABAuthorizationStatus {
+    kABAuthorizationStatusAuthorized
+}
+
+/// Access is always granted, without asking. The completion handler is called
+/// straight away rather than on another queue.
+fn ABAddressBookRequestAccessWithCompletion(
+    env: &mut Environment,
+    _address_book: ABAddressBookRef,
+    completion: id, // void (^)(bool granted, CFErrorRef error)
+) {
+    log!("App is requesting access to the address book, granting it.");
+    if completion == nil {
+        return;
+    }
+    let invoke = get_block_invoke(&env.mem, completion);
+    () = invoke.call_from_host(env, (completion, true, nil));
+}
+
+fn ABAddressBookGetPersonCount(env: &mut Environment, address_book: ABAddressBookRef) -> CFIndex {
+    let people = &env
+        .objc
+        .borrow::<ABAddressBookHostObject>(address_book)
+        .people;
+    people.len().try_into().unwrap()
+}
+
+fn ABAddressBookCopyArrayOfAllPeople(
+    env: &mut Environment,
+    address_book: ABAddressBookRef,
+) -> CFArrayRef {
+    let people = env
+        .objc
+        .borrow::<ABAddressBookHostObject>(address_book)
+        .people
+        .clone();
+    for &person in &people {
+        retain(env, person);
+    }
+    ns_array::from_vec(env, people)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ABAddressBookCreate()),
+    export_c_func!(ABAddressBookCreateWithOptions(_, _)),
+    export_c_func!(ABAddressBookGetAuthorizationStatus()),
+    export_c_func!(ABAddressBookRequestAccessWithCompletion(_, _)),
+    export_c_func!(ABAddressBookGetPersonCount(_)),
+    export_c_func!(ABAddressBookCopyArrayOfAllPeople(_)),
+];
@@ -0,0 +1,115 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABMultiValue.h`
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_array::CFArrayRef;
+use crate::frameworks::core_foundation::cf_string::CFStringRef;
+use crate::frameworks::core_foundation::{CFIndex, CFTypeRef};
+use crate::frameworks::foundation::ns_array;
+use crate::objc::{id, msg_super, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+
+pub type ABMultiValueRef = CFTypeRef;
+
+struct ABMultiValueHostObject {
+    /// Labels (`NSString*`) and values, both strong references.
+    entries: Vec<(id, id)>,
+}
+impl HostObject for ABMultiValueHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// ABMultiValue is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_ABMultiValue: NSObject
+
+- (())dealloc {
+    let entries = std::mem::take(&mut env.objc.borrow_mut::<ABMultiValueHostObject>(this).entries);
+    for (label, value) in entries {
+        release(env, label);
+        release(env, value);
+    }
+    msg_super![env; this dealloc]
+}
+
+@end
+
+};
+
+/// Create a multi-value from (label, value) pairs, which should already be
+/// "retained by" the `Vec`.
+pub(super) fn from_vec(env: &mut Environment, entries: Vec<(id, id)>) -> ABMultiValueRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_ABMultiValue", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(ABMultiValueHostObject { entries }),
+        &mut env.mem,
+    )
+}
+
+fn entry_at_index(env: &mut Environment, multi_value: ABMultiValueRef, index: CFIndex) -> (id, id) {
+    let entries = &env
+        .objc
+        .borrow::<ABMultiValueHostObject>(multi_value)
+        .entries;
+    entries[usize::try_from(index).unwrap()]
+}
+
+fn ABMultiValueGetCount(env: &mut Environment, multi_value: ABMultiValueRef) -> CFIndex {
+    let entries = &env
+        .objc
+        .borrow::<ABMultiValueHostObject>(multi_value)
+        .entries;
+    entries.len().try_into().unwrap()
+}
+
+fn ABMultiValueCopyValueAtIndex(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+    index: CFIndex,
+) -> CFTypeRef {
+    let (_label, value) = entry_at_index(env, multi_value, index);
+    retain(env, value)
+}
+
+fn ABMultiValueCopyLabelAtIndex(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+    index: CFIndex,
+) -> CFStringRef {
+    let (label, _value) = entry_at_index(env, multi_value, index);
+    retain(env, label)
+}
+
+fn ABMultiValueCopyArrayOfAllValues(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+) -> CFArrayRef {
+    let values: Vec<id> = env
+        .objc
+        .borrow::<ABMultiValueHostObject>(multi_value)
+        .entries
+        .iter()
+        .map(|&(_label, value)| value)
+        .collect();
+    for &value in &values {
+        retain(env, value);
+    }
+    ns_array::from_vec(env, values)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ABMultiValueGetCount(_)),
+    export_c_func!(ABMultiValueCopyValueAtIndex(_, _)),
+    export_c_func!(ABMultiValueCopyLabelAtIndex(_, _)),
+    export_c_func!(ABMultiValueCopyArrayOfAllValues(_)),
+];
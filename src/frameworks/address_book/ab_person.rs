@@ -0,0 +1,189 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABRecord.h` and `ABPerson.h`
+
+use super::ab_multi_value;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_foundation::cf_string::CFStringRef;
+use crate::frameworks::core_foundation::CFTypeRef;
+use crate::frameworks::foundation::ns_string;
+use crate::mem::{ConstVoidPtr, Mem};
+use crate::objc::{
+    id, msg, msg_super, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub type ABRecordRef = CFTypeRef;
+pub type ABRecordID = i32;
+pub type ABPropertyID = i32;
+
+const kABRecordInvalidID: ABRecordID = -1;
+
+// On a real device these are variables that are set up when the first address
+// book is created, but the values never change in practice.
+const kABPersonFirstNameProperty: ABPropertyID = 0;
+const kABPersonLastNameProperty: ABPropertyID = 1;
+const kABPersonPhoneProperty: ABPropertyID = 3;
+
+const kABPersonPhoneMobileLabel: &str = "_$!<Mobile>!$_";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kABPersonFirstNameProperty",
+        HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+            mem.alloc_and_write(kABPersonFirstNameProperty)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kABPersonLastNameProperty",
+        HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+            mem.alloc_and_write(kABPersonLastNameProperty)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kABPersonPhoneProperty",
+        HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+            mem.alloc_and_write(kABPersonPhoneProperty)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kABPersonPhoneMobileLabel",
+        HostConstant::NSString(kABPersonPhoneMobileLabel),
+    ),
+];
+
+struct ABPersonHostObject {
+    record_id: ABRecordID,
+    /// `NSString*` or `nil`
+    first_name: id,
+    /// `NSString*` or `nil`
+    last_name: id,
+    /// `ABMultiValueRef` of phone numbers (`NSString*`) or `nil`
+    phones: id,
+}
+impl HostObject for ABPersonHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// ABPerson is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not visible
+// anywhere.
+@implementation _touchHLE_ABPerson: NSObject
+
+- (())dealloc {
+    let &ABPersonHostObject {
+        record_id: _,
+        first_name,
+        last_name,
+        phones,
+    } = env.objc.borrow(this);
+    release(env, first_name);
+    release(env, last_name);
+    release(env, phones);
+    msg_super![env; this dealloc]
+}
+
+@end
+
+};
+
+/// Create a person from a name like "Jane Appleseed" and an optional phone
+/// number.
+pub(super) fn new_person(
+    env: &mut Environment,
+    record_id: ABRecordID,
+    name: &str,
+    phone: Option<&str>,
+) -> ABRecordRef {
+    let (first_name, last_name) = match name.split_once(' ') {
+        Some((first_name, last_name)) => (first_name, Some(last_name)),
+        None => (name, None),
+    };
+    let first_name = ns_string::from_rust_string(env, first_name.to_string());
+    let last_name = match last_name {
+        Some(last_name) => ns_string::from_rust_string(env, last_name.to_string()),
+        None => nil,
+    };
+    let phones = match phone {
+        Some(phone) => {
+            let label = ns_string::get_static_str(env, kABPersonPhoneMobileLabel);
+            let number = ns_string::from_rust_string(env, phone.to_string());
+            ab_multi_value::from_vec(env, vec![(label, number)])
+        }
+        None => nil,
+    };
+
+    let isa = env.objc.get_known_class("_touchHLE_ABPerson", &mut env.mem);
+    let host_object = Box::new(ABPersonHostObject {
+        record_id,
+        first_name,
+        last_name,
+        phones,
+    });
+    env.objc.alloc_object(isa, host_object, &mut env.mem)
+}
+
+fn ABRecordGetRecordID(env: &mut Environment, record: ABRecordRef) -> ABRecordID {
+    if record == nil {
+        return kABRecordInvalidID;
+    }
+    env.objc.borrow::<ABPersonHostObject>(record).record_id
+}
+
+fn ABRecordCopyValue(
+    env: &mut Environment,
+    record: ABRecordRef,
+    property: ABPropertyID,
+) -> CFTypeRef {
+    let &ABPersonHostObject {
+        first_name,
+        last_name,
+        phones,
+        ..
+    } = env.objc.borrow(record);
+    let value = match property {
+        kABPersonFirstNameProperty => first_name,
+        kABPersonLastNameProperty => last_name,
+        kABPersonPhoneProperty => phones,
+        _ => {
+            log!(
+                "TODO: ABRecordCopyValue() for property {}, returning NULL",
+                property
+            );
+            nil
+        }
+    };
+    retain(env, value)
+}
+
+fn ABRecordCopyCompositeName(env: &mut Environment, record: ABRecordRef) -> CFStringRef {
+    let &ABPersonHostObject {
+        first_name,
+        last_name,
+        ..
+    } = env.objc.borrow(record);
+    if last_name == nil {
+        return retain(env, first_name);
+    }
+    let separator = ns_string::get_static_str(env, " ");
+    let name: id = msg![env; first_name stringByAppendingString:separator];
+    let name: id = msg![env; name stringByAppendingString:last_name];
+    retain(env, name)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ABRecordGetRecordID(_)),
+    export_c_func!(ABRecordCopyValue(_, _)),
+    export_c_func!(ABRecordCopyCompositeName(_)),
+];
@@ -8,13 +8,21 @@
 //! Useful resources:
 //! - Apple's [Core Animation Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/CoreAnimation_guide/Introduction/Introduction.html)
 
+pub mod ca_animation;
 pub mod ca_eagl_layer;
+pub mod ca_gradient_layer;
 pub mod ca_layer;
+pub mod ca_shape_layer;
+pub mod ca_transaction;
 
+mod animation;
 mod composition;
-pub use composition::recomposite_if_necessary;
+pub mod frame_clock;
+pub use composition::{display_layers, recomposite_if_necessary};
 
 #[derive(Default)]
 pub struct State {
     composition: composition::State,
+    ca_transaction: ca_transaction::State,
+    frame_clock: frame_clock::FrameClock,
 }
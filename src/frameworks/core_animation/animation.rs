@@ -0,0 +1,406 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The implementation of layer property animations.
+//!
+//! A layer's properties always have their final ("model") values. Animations
+//! only affect the values that are displayed ("presentation" values), which
+//! the compositor gets from [presentation_value].
+
+use crate::frameworks::core_graphics::cg_path::PathElement;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::objc::id;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Properties of `CALayer` that can be animated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum AnimatableProperty {
+    Bounds,
+    Position,
+    Opacity,
+    BackgroundColor,
+    // CAShapeLayer only
+    Path,
+    StrokeStart,
+    StrokeEnd,
+    LineWidth,
+}
+impl AnimatableProperty {
+    pub(super) fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "bounds" => Some(Self::Bounds),
+            "position" => Some(Self::Position),
+            "opacity" => Some(Self::Opacity),
+            "backgroundColor" => Some(Self::BackgroundColor),
+            "path" => Some(Self::Path),
+            "strokeStart" => Some(Self::StrokeStart),
+            "strokeEnd" => Some(Self::StrokeEnd),
+            "lineWidth" => Some(Self::LineWidth),
+            _ => None,
+        }
+    }
+    pub(super) fn key(self) -> &'static str {
+        match self {
+            Self::Bounds => "bounds",
+            Self::Position => "position",
+            Self::Opacity => "opacity",
+            Self::BackgroundColor => "backgroundColor",
+            Self::Path => "path",
+            Self::StrokeStart => "strokeStart",
+            Self::StrokeEnd => "strokeEnd",
+            Self::LineWidth => "lineWidth",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum AnimatableValue {
+    Rect(CGRect),
+    Point(CGPoint),
+    Float(CGFloat),
+    /// RGBA. A nil color is transparent black.
+    Color((CGFloat, CGFloat, CGFloat, CGFloat)),
+    /// The elements of a `CGPathRef`. A null path has none.
+    Path(Rc<Vec<PathElement>>),
+}
+impl AnimatableValue {
+    /// Linear interpolation. `progress` is in the range [0, 1].
+    fn interpolate(self, to: Self, progress: CGFloat) -> Self {
+        let lerp = |a: CGFloat, b: CGFloat| a + (b - a) * progress;
+        let lerp_point = |a: CGPoint, b: CGPoint| CGPoint {
+            x: lerp(a.x, b.x),
+            y: lerp(a.y, b.y),
+        };
+        match (self, to) {
+            (Self::Rect(a), Self::Rect(b)) => Self::Rect(CGRect {
+                origin: lerp_point(a.origin, b.origin),
+                size: CGSize {
+                    width: lerp(a.size.width, b.size.width),
+                    height: lerp(a.size.height, b.size.height),
+                },
+            }),
+            (Self::Point(a), Self::Point(b)) => Self::Point(lerp_point(a, b)),
+            (Self::Float(a), Self::Float(b)) => Self::Float(lerp(a, b)),
+            (Self::Color(a), Self::Color(b)) => Self::Color((
+                lerp(a.0, b.0),
+                lerp(a.1, b.1),
+                lerp(a.2, b.2),
+                lerp(a.3, b.3),
+            )),
+            (Self::Path(a), Self::Path(b)) => {
+                // Paths can only be interpolated point by point, so they must
+                // be made of the same kinds of elements. Otherwise the result
+                // is undefined, and we just use the final path.
+                let matching = a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(a, b)| std::mem::discriminant(a) == std::mem::discriminant(b));
+                if !matching {
+                    return Self::Path(b);
+                }
+                let elements = a
+                    .iter()
+                    .zip(b.iter())
+                    .map(|(&a, &b)| match (a, b) {
+                        (PathElement::MoveTo(a), PathElement::MoveTo(b)) => {
+                            PathElement::MoveTo(lerp_point(a, b))
+                        }
+                        (PathElement::LineTo(a), PathElement::LineTo(b)) => {
+                            PathElement::LineTo(lerp_point(a, b))
+                        }
+                        (
+                            PathElement::CurveTo {
+                                control_point_1: a1,
+                                control_point_2: a2,
+                                to: a3,
+                            },
+                            PathElement::CurveTo {
+                                control_point_1: b1,
+                                control_point_2: b2,
+                                to: b3,
+                            },
+                        ) => PathElement::CurveTo {
+                            control_point_1: lerp_point(a1, b1),
+                            control_point_2: lerp_point(a2, b2),
+                            to: lerp_point(a3, b3),
+                        },
+                        (PathElement::Close, PathElement::Close) => PathElement::Close,
+                        _ => unreachable!(),
+                    })
+                    .collect();
+                Self::Path(Rc::new(elements))
+            }
+            (a, b) => panic!("Can't interpolate between {:?} and {:?}", a, b),
+        }
+    }
+}
+
+/// Cubic Bézier timing curve from (0, 0) to (1, 1), like
+/// `CAMediaTimingFunction`. The control points are `[x1, y1, x2, y2]`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) struct TimingFunction(pub(super) [CGFloat; 4]);
+impl TimingFunction {
+    pub(super) const LINEAR: Self = TimingFunction([0.0, 0.0, 1.0, 1.0]);
+
+    fn evaluate(self, t: CGFloat) -> CGFloat {
+        if self == Self::LINEAR {
+            return t;
+        }
+        let [x1, y1, x2, y2] = self.0;
+        let bezier = |p1: CGFloat, p2: CGFloat, s: CGFloat| {
+            let r = 1.0 - s;
+            3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+        };
+        // x(s) is monotonic for valid timing functions, so bisection will
+        // find the s where x(s) == t.
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..32 {
+            let mid = (low + high) / 2.0;
+            if bezier(x1, x2, mid) < t {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        bezier(y1, y2, (low + high) / 2.0)
+    }
+}
+
+pub(super) struct Animation {
+    pub(super) property: AnimatableProperty,
+    /// Values at times in the range [0, 1], in ascending order of time.
+    /// [None] means the layer's model value.
+    pub(super) keyframes: Vec<(CGFloat, Option<AnimatableValue>)>,
+    pub(super) timing_function: TimingFunction,
+    pub(super) begin: Instant,
+    /// Duration of a single iteration in seconds.
+    pub(super) duration: f64,
+    /// May be fractional or infinite.
+    pub(super) repeat_count: f32,
+    pub(super) autoreverses: bool,
+    /// If [false], the animation's final value continues to be displayed once
+    /// it has finished.
+    pub(super) removed_on_completion: bool,
+    /// For explicit animations: the key it was added with, if any, and the
+    /// `CAAnimation*` (strong reference).
+    pub(super) explicit: Option<(Option<String>, id)>,
+    /// Whether the delegate has been told that the animation finished.
+    pub(super) stopped: bool,
+}
+impl Animation {
+    /// Create an animation of the kind used for implicit animations: from the
+    /// value currently displayed to the model value.
+    pub(super) fn implicit(
+        property: AnimatableProperty,
+        from: AnimatableValue,
+        begin: Instant,
+        duration: f64,
+    ) -> Self {
+        Animation {
+            property,
+            keyframes: vec![(0.0, Some(from)), (1.0, None)],
+            timing_function: TimingFunction::LINEAR,
+            begin,
+            duration,
+            repeat_count: 0.0,
+            autoreverses: false,
+            removed_on_completion: true,
+            explicit: None,
+            stopped: false,
+        }
+    }
+
+    /// Duration of a single iteration in seconds, including reversing.
+    fn cycle_duration(&self) -> f64 {
+        if self.autoreverses {
+            self.duration * 2.0
+        } else {
+            self.duration
+        }
+    }
+
+    /// Total duration in seconds, including repeats and reversing.
+    fn active_duration(&self) -> f64 {
+        self.cycle_duration() * (self.repeat_count as f64).max(1.0)
+    }
+
+    fn elapsed(&self, now: Instant) -> f64 {
+        now.saturating_duration_since(self.begin).as_secs_f64()
+    }
+
+    pub(super) fn is_finished(&self, now: Instant) -> bool {
+        self.elapsed(now) >= self.active_duration()
+    }
+
+    /// Whether the animation affects the presentation value.
+    pub(super) fn is_in_effect(&self, now: Instant) -> bool {
+        !self.removed_on_completion || !self.is_finished(now)
+    }
+
+    /// How far through the current iteration we are, from 0 to 1, taking
+    /// repeating and reversing into account.
+    fn progress(&self, now: Instant) -> CGFloat {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        let cycle = self.cycle_duration();
+        let elapsed = self.elapsed(now).min(self.active_duration());
+        let mut in_cycle = elapsed % cycle;
+        // The end of a cycle is the end, not the beginning of the next one.
+        if in_cycle == 0.0 && elapsed > 0.0 {
+            in_cycle = cycle;
+        }
+        let progress = if in_cycle > self.duration {
+            2.0 - in_cycle / self.duration
+        } else {
+            in_cycle / self.duration
+        };
+        progress as CGFloat
+    }
+
+    fn value(&self, model_value: AnimatableValue, now: Instant) -> AnimatableValue {
+        let t = self.timing_function.evaluate(self.progress(now));
+        let value_at = |i: usize| {
+            self.keyframes[i]
+                .1
+                .clone()
+                .unwrap_or_else(|| model_value.clone())
+        };
+        let Some(next) = self.keyframes.iter().position(|&(time, _)| time > t) else {
+            return value_at(self.keyframes.len() - 1);
+        };
+        if next == 0 {
+            return value_at(0);
+        }
+        let (time_a, time_b) = (self.keyframes[next - 1].0, self.keyframes[next].0);
+        value_at(next - 1).interpolate(value_at(next), (t - time_a) / (time_b - time_a))
+    }
+}
+
+/// Get the value of a property that should be displayed at `now`. The most
+/// recently added animation of the property takes precedence.
+pub(super) fn presentation_value(
+    animations: &[Animation],
+    property: AnimatableProperty,
+    model_value: AnimatableValue,
+    now: Instant,
+) -> AnimatableValue {
+    match animations
+        .iter()
+        .rev()
+        .find(|animation| animation.property == property && animation.is_in_effect(now))
+    {
+        Some(animation) => animation.value(model_value, now),
+        None => model_value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn value_at(
+        animations: &[Animation],
+        property: AnimatableProperty,
+        begin: Instant,
+        secs: f64,
+    ) -> AnimatableValue {
+        presentation_value(
+            animations,
+            property,
+            AnimatableValue::Float(1.0),
+            begin + Duration::from_secs_f64(secs),
+        )
+    }
+
+    #[test]
+    fn presentation_value_interpolates() {
+        let begin = Instant::now();
+        let animations = [Animation::implicit(
+            AnimatableProperty::Opacity,
+            AnimatableValue::Float(0.0),
+            begin,
+            2.0,
+        )];
+        let value = |property, secs| value_at(&animations, property, begin, secs);
+        assert_eq!(
+            value(AnimatableProperty::Opacity, 0.0),
+            AnimatableValue::Float(0.0)
+        );
+        assert_eq!(
+            value(AnimatableProperty::Opacity, 0.5),
+            AnimatableValue::Float(0.25)
+        );
+        assert_eq!(
+            value(AnimatableProperty::Opacity, 2.0),
+            AnimatableValue::Float(1.0)
+        );
+        // Other properties are unaffected.
+        assert_eq!(
+            value(AnimatableProperty::Position, 0.5),
+            AnimatableValue::Float(1.0)
+        );
+    }
+
+    #[test]
+    fn keyframes_repeat_and_autoreverse() {
+        let begin = Instant::now();
+        let animations = [Animation {
+            keyframes: vec![
+                (0.0, Some(AnimatableValue::Float(0.0))),
+                (0.5, Some(AnimatableValue::Float(0.8))),
+                (1.0, Some(AnimatableValue::Float(0.4))),
+            ],
+            repeat_count: 2.0,
+            autoreverses: true,
+            ..Animation::implicit(
+                AnimatableProperty::Opacity,
+                AnimatableValue::Float(0.0),
+                begin,
+                1.0,
+            )
+        }];
+        let value = |secs| value_at(&animations, AnimatableProperty::Opacity, begin, secs);
+        assert_eq!(value(0.25), AnimatableValue::Float(0.4));
+        assert_eq!(value(0.5), AnimatableValue::Float(0.8));
+        assert_eq!(value(1.0), AnimatableValue::Float(0.4));
+        // Reversing
+        assert_eq!(value(1.75), AnimatableValue::Float(0.4));
+        // Second iteration
+        assert_eq!(value(2.5), AnimatableValue::Float(0.8));
+        assert!(!animations[0].is_finished(begin + Duration::from_secs_f64(3.9)));
+        assert!(animations[0].is_finished(begin + Duration::from_secs(4)));
+        // Removed on completion, so the model value is displayed.
+        assert_eq!(value(4.0), AnimatableValue::Float(1.0));
+    }
+
+    #[test]
+    fn path_interpolation() {
+        let point = |x, y| CGPoint { x, y };
+        let path = |x| {
+            AnimatableValue::Path(Rc::new(vec![
+                PathElement::MoveTo(point(0.0, 0.0)),
+                PathElement::LineTo(point(x, 0.0)),
+                PathElement::Close,
+            ]))
+        };
+        assert_eq!(path(0.0).interpolate(path(4.0), 0.25), path(1.0));
+        // Paths with different elements can't be interpolated.
+        let other = AnimatableValue::Path(Rc::new(vec![PathElement::MoveTo(point(1.0, 1.0))]));
+        assert_eq!(path(0.0).interpolate(other.clone(), 0.25), other);
+    }
+
+    #[test]
+    fn timing_function() {
+        let ease_in = TimingFunction([0.42, 0.0, 1.0, 1.0]);
+        assert!(ease_in.evaluate(0.0).abs() < 0.0001);
+        assert!(ease_in.evaluate(0.5) < 0.5);
+        assert!((ease_in.evaluate(1.0) - 1.0).abs() < 0.0001);
+        let linear_curve = TimingFunction([0.25, 0.25, 0.75, 0.75]);
+        assert!((linear_curve.evaluate(0.3) - 0.3).abs() < 0.0001);
+    }
+}
@@ -0,0 +1,449 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAAnimation` and its subclasses, and `CAMediaTimingFunction`.
+//!
+//! These are only descriptions of animations. When one is added to a layer,
+//! it is converted to an [Animation], which is what actually gets applied.
+
+use super::animation::{AnimatableProperty, AnimatableValue, Animation, TimingFunction};
+use super::frame_clock;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::frameworks::core_graphics::{cg_color, cg_path};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::{ns_string, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::rc::Rc;
+
+pub const kCAMediaTimingFunctionLinear: &str = "linear";
+pub const kCAMediaTimingFunctionEaseIn: &str = "easeIn";
+pub const kCAMediaTimingFunctionEaseOut: &str = "easeOut";
+pub const kCAMediaTimingFunctionEaseInEaseOut: &str = "easeInEaseOut";
+pub const kCAMediaTimingFunctionDefault: &str = "default";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCAMediaTimingFunctionLinear",
+        HostConstant::NSString(kCAMediaTimingFunctionLinear),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseIn",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseIn),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseOut",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseOut),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseInEaseOut",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseInEaseOut),
+    ),
+    (
+        "_kCAMediaTimingFunctionDefault",
+        HostConstant::NSString(kCAMediaTimingFunctionDefault),
+    ),
+];
+
+/// Duration used if an animation's duration is zero.
+const DEFAULT_DURATION: CFTimeInterval = 0.25;
+
+/// Belongs to CAAnimation and all its subclasses. Properties that don't apply
+/// to a particular subclass are left at their defaults.
+#[derive(Clone)]
+struct CAAnimationHostObject {
+    /// Strong reference, unlike most delegates.
+    delegate: id,
+    duration: CFTimeInterval,
+    repeat_count: f32,
+    autoreverses: bool,
+    removed_on_completion: bool,
+    /// `CAMediaTimingFunction*`
+    timing_function: id,
+    /// `NSString*`, for `CAPropertyAnimation`
+    key_path: id,
+    /// For `CABasicAnimation`
+    from_value: id,
+    /// For `CABasicAnimation`
+    to_value: id,
+    /// `NSArray*`, for `CAKeyframeAnimation`
+    values: id,
+    /// `NSArray*` of `NSNumber*`, for `CAKeyframeAnimation`
+    key_times: id,
+    /// `CGPathRef`, for `CAKeyframeAnimation`
+    path: id,
+}
+impl HostObject for CAAnimationHostObject {}
+impl CAAnimationHostObject {
+    /// All the objects this has strong references to, which may be nil.
+    fn objects(&self) -> [id; 8] {
+        [
+            self.delegate,
+            self.timing_function,
+            self.key_path,
+            self.from_value,
+            self.to_value,
+            self.values,
+            self.key_times,
+            self.path,
+        ]
+    }
+}
+
+struct CAMediaTimingFunctionHostObject {
+    function: TimingFunction,
+}
+impl HostObject for CAMediaTimingFunctionHostObject {}
+
+/// Replace an object-typed property of a `CAAnimation`, retaining the new
+/// value and releasing the old one.
+fn set_object(
+    env: &mut Environment,
+    this: id,
+    value: id,
+    field: fn(&mut CAAnimationHostObject) -> &mut id,
+) {
+    retain(env, value);
+    let old = std::mem::replace(field(env.objc.borrow_mut(this)), value);
+    release(env, old);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAAnimation: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(CAAnimationHostObject {
+        delegate: nil,
+        duration: 0.0,
+        repeat_count: 0.0,
+        autoreverses: false,
+        removed_on_completion: true,
+        timing_function: nil,
+        key_path: nil,
+        from_value: nil,
+        to_value: nil,
+        values: nil,
+        key_times: nil,
+        path: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)animation {
+    let new: id = msg![env; this new];
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let objects = env.objc.borrow::<CAAnimationHostObject>(this).objects();
+    for object in objects {
+        release(env, object);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let host_object = env.objc.borrow::<CAAnimationHostObject>(this).clone();
+    for object in host_object.objects() {
+        retain(env, object);
+    }
+    let class: Class = msg![env; this class];
+    env.objc.alloc_object(class, Box::new(host_object), &mut env.mem)
+}
+
+- (id)delegate {
+    env.objc.borrow::<CAAnimationHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    set_object(env, this, delegate, |host_obj| &mut host_obj.delegate);
+}
+
+- (CFTimeInterval)duration {
+    env.objc.borrow::<CAAnimationHostObject>(this).duration
+}
+- (())setDuration:(CFTimeInterval)duration {
+    env.objc.borrow_mut::<CAAnimationHostObject>(this).duration = duration;
+}
+
+- (f32)repeatCount {
+    env.objc.borrow::<CAAnimationHostObject>(this).repeat_count
+}
+- (())setRepeatCount:(f32)repeat_count {
+    env.objc.borrow_mut::<CAAnimationHostObject>(this).repeat_count = repeat_count;
+}
+
+- (bool)autoreverses {
+    env.objc.borrow::<CAAnimationHostObject>(this).autoreverses
+}
+- (())setAutoreverses:(bool)autoreverses {
+    env.objc.borrow_mut::<CAAnimationHostObject>(this).autoreverses = autoreverses;
+}
+
+- (bool)isRemovedOnCompletion {
+    env.objc.borrow::<CAAnimationHostObject>(this).removed_on_completion
+}
+- (())setRemovedOnCompletion:(bool)removed_on_completion {
+    env.objc.borrow_mut::<CAAnimationHostObject>(this).removed_on_completion =
+        removed_on_completion;
+}
+
+- (id)timingFunction {
+    env.objc.borrow::<CAAnimationHostObject>(this).timing_function
+}
+- (())setTimingFunction:(id)timing_function { // CAMediaTimingFunction*
+    set_object(env, this, timing_function, |host_obj| &mut host_obj.timing_function);
+}
+
+@end
+
+@implementation CAPropertyAnimation: CAAnimation
+
++ (id)animationWithKeyPath:(id)key_path { // NSString*
+    let new: id = msg![env; this animation];
+    () = msg![env; new setKeyPath:key_path];
+    new
+}
+
+- (id)keyPath {
+    env.objc.borrow::<CAAnimationHostObject>(this).key_path
+}
+- (())setKeyPath:(id)key_path { // NSString*
+    let key_path: id = msg![env; key_path copy];
+    set_object(env, this, key_path, |host_obj| &mut host_obj.key_path);
+    release(env, key_path);
+}
+
+@end
+
+@implementation CABasicAnimation: CAPropertyAnimation
+
+- (id)fromValue {
+    env.objc.borrow::<CAAnimationHostObject>(this).from_value
+}
+- (())setFromValue:(id)from_value {
+    set_object(env, this, from_value, |host_obj| &mut host_obj.from_value);
+}
+
+- (id)toValue {
+    env.objc.borrow::<CAAnimationHostObject>(this).to_value
+}
+- (())setToValue:(id)to_value {
+    set_object(env, this, to_value, |host_obj| &mut host_obj.to_value);
+}
+
+@end
+
+@implementation CAKeyframeAnimation: CAPropertyAnimation
+
+- (id)values {
+    env.objc.borrow::<CAAnimationHostObject>(this).values
+}
+- (())setValues:(id)values { // NSArray*
+    let values: id = msg![env; values copy];
+    set_object(env, this, values, |host_obj| &mut host_obj.values);
+    release(env, values);
+}
+
+- (id)keyTimes {
+    env.objc.borrow::<CAAnimationHostObject>(this).key_times
+}
+- (())setKeyTimes:(id)key_times { // NSArray*
+    let key_times: id = msg![env; key_times copy];
+    set_object(env, this, key_times, |host_obj| &mut host_obj.key_times);
+    release(env, key_times);
+}
+
+- (id)path {
+    env.objc.borrow::<CAAnimationHostObject>(this).path
+}
+- (())setPath:(id)path { // CGPathRef
+    set_object(env, this, path, |host_obj| &mut host_obj.path);
+}
+
+@end
+
+@implementation CAMediaTimingFunction: NSObject
+
++ (id)functionWithName:(id)name { // NSString*
+    let name = ns_string::to_rust_string(env, name);
+    // Control points from Apple's documentation.
+    let control_points = match &*name {
+        kCAMediaTimingFunctionLinear => [0.0, 0.0, 1.0, 1.0],
+        kCAMediaTimingFunctionEaseIn => [0.42, 0.0, 1.0, 1.0],
+        kCAMediaTimingFunctionEaseOut => [0.0, 0.0, 0.58, 1.0],
+        kCAMediaTimingFunctionEaseInEaseOut => [0.42, 0.0, 0.58, 1.0],
+        kCAMediaTimingFunctionDefault => [0.25, 0.1, 0.25, 1.0],
+        _ => panic!("Unknown timing function name {:?}", name),
+    };
+    let host_object = Box::new(CAMediaTimingFunctionHostObject {
+        function: TimingFunction(control_points),
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
+@end
+
+};
+
+/// Convert an object used as a value in an animation of `property`.
+fn value_from_object(
+    env: &mut Environment,
+    property: AnimatableProperty,
+    object: id,
+) -> AnimatableValue {
+    match property {
+        AnimatableProperty::Bounds => {
+            let rect: CGRect = msg![env; object CGRectValue];
+            AnimatableValue::Rect(rect)
+        }
+        AnimatableProperty::Position => {
+            let point: CGPoint = msg![env; object CGPointValue];
+            AnimatableValue::Point(point)
+        }
+        AnimatableProperty::Opacity
+        | AnimatableProperty::StrokeStart
+        | AnimatableProperty::StrokeEnd
+        | AnimatableProperty::LineWidth => {
+            let float: CGFloat = msg![env; object floatValue];
+            AnimatableValue::Float(float)
+        }
+        AnimatableProperty::BackgroundColor => AnimatableValue::Color(if object == nil {
+            (0.0, 0.0, 0.0, 0.0)
+        } else {
+            cg_color::get_rgba(&env.objc, &env.mem, object)
+        }),
+        // CGPathRef
+        AnimatableProperty::Path => AnimatableValue::Path(Rc::new(cg_path::elements(env, object))),
+    }
+}
+
+/// For use by `CALayer`: create the [Animation] described by a `CAAnimation`
+/// that has been added to a layer. `animation` should already be a copy made
+/// for the layer, which the new [Animation] will take ownership of.
+/// `current_value` is the value of the property that is currently displayed.
+/// Returns [None] if the animation isn't supported.
+pub(super) fn make_animation(
+    env: &mut Environment,
+    animation: id,
+    key: Option<String>,
+    current_value: impl FnOnce(&mut Environment, AnimatableProperty) -> AnimatableValue,
+) -> Option<Animation> {
+    let host_obj = env.objc.borrow::<CAAnimationHostObject>(animation).clone();
+
+    if host_obj.key_path == nil {
+        log!(
+            "Warning: animation {:?} has no key path, ignoring",
+            animation
+        );
+        return None;
+    }
+    let key_path = ns_string::to_rust_string(env, host_obj.key_path);
+    let Some(property) = AnimatableProperty::from_key(&key_path) else {
+        log!(
+            "TODO: animation of key path {:?} (animation {:?}), ignoring",
+            key_path,
+            animation
+        );
+        return None;
+    };
+
+    let keyframes = if host_obj.values != nil || host_obj.path != nil {
+        if host_obj.path != nil {
+            log!(
+                "TODO: CAKeyframeAnimation path (animation {:?}), using values instead",
+                animation
+            );
+        }
+        if host_obj.values == nil {
+            return None;
+        }
+        let values = host_obj.values;
+        let count: NSUInteger = msg![env; values count];
+        if count == 0 {
+            return None;
+        }
+        let key_times = host_obj.key_times;
+        let key_times_count: NSUInteger = if key_times == nil {
+            0
+        } else {
+            msg![env; key_times count]
+        };
+        let mut keyframes = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let time: CGFloat = if key_times_count == count {
+                let time: id = msg![env; key_times objectAtIndex:i];
+                msg![env; time floatValue]
+            } else if count == 1 {
+                0.0
+            } else {
+                // Evenly spaced if there are no key times.
+                i as CGFloat / (count - 1) as CGFloat
+            };
+            let value: id = msg![env; values objectAtIndex:i];
+            keyframes.push((time, Some(value_from_object(env, property, value))));
+        }
+        keyframes
+    } else {
+        // A basic animation from the current value to the model value, unless
+        // either has been overridden.
+        let from = if host_obj.from_value != nil {
+            value_from_object(env, property, host_obj.from_value)
+        } else {
+            current_value(env, property)
+        };
+        let to =
+            (host_obj.to_value != nil).then(|| value_from_object(env, property, host_obj.to_value));
+        vec![(0.0, Some(from)), (1.0, to)]
+    };
+
+    let timing_function = if host_obj.timing_function != nil {
+        env.objc
+            .borrow::<CAMediaTimingFunctionHostObject>(host_obj.timing_function)
+            .function
+    } else {
+        TimingFunction::LINEAR
+    };
+
+    let duration = if host_obj.duration > 0.0 {
+        host_obj.duration
+    } else {
+        DEFAULT_DURATION
+    };
+
+    Some(Animation {
+        property,
+        keyframes,
+        timing_function,
+        begin: frame_clock::now(env),
+        duration,
+        repeat_count: host_obj.repeat_count,
+        autoreverses: host_obj.autoreverses,
+        removed_on_completion: host_obj.removed_on_completion,
+        explicit: Some((key, animation)),
+        stopped: false,
+    })
+}
+
+/// For use by `CALayer`: tell an animation's delegate that it has stopped,
+/// either because it `finished` or because it was removed.
+pub(super) fn animation_did_stop(env: &mut Environment, animation: id, finished: bool) {
+    let delegate = env.objc.borrow::<CAAnimationHostObject>(animation).delegate;
+    if delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, "animationDidStop:finished:")
+    {
+        () = msg![env; delegate animationDidStop:animation finished:finished];
+    }
+}
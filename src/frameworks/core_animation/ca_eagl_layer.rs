@@ -80,6 +80,7 @@ pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
         if layer_host_obj.bounds.size != screen_bounds.size
             || layer_host_obj.bounds.origin != (CGPoint { x: 0.0, y: 0.0 })
             || layer_host_obj.anchor_point != (CGPoint { x: 0.5, y: 0.5 })
+            || !layer_host_obj.affine_transform.is_identity()
             || layer_host_obj.position
                 != (CGPoint {
                     x: screen_bounds.size.width / 2.0,
@@ -0,0 +1,235 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAGradientLayer`.
+//!
+//! Like `CAShapeLayer`, the gradient is drawn into the layer's bitmap, which is
+//! redrawn whenever what should be displayed changes.
+
+use super::ca_layer::{self, CALayerHostObject};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::cg_bitmap_context;
+use crate::frameworks::core_graphics::cg_context::CGContextRef;
+use crate::frameworks::core_graphics::cg_gradient::{color_at, Rgba};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::{ns_string, NSUInteger};
+use crate::objc::{id, msg, msg_super, nil, objc_classes, release, ClassExports};
+use crate::Environment;
+
+pub const kCAGradientLayerAxial: &str = "axial";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCAGradientLayerAxial",
+    HostConstant::NSString(kCAGradientLayerAxial),
+)];
+
+/// The properties of a `CAGradientLayer`, stored in its [CALayerHostObject].
+pub(super) struct GradientLayerProperties {
+    /// `NSArray*` of `CGColorRef`, possibly nil. This is a strong reference.
+    colors: id,
+    /// The colors in `colors` at the time it was set.
+    color_values: Vec<Rgba>,
+    /// `NSArray*` of `NSNumber*`, possibly nil. This is a strong reference.
+    locations: id,
+    /// The locations in `locations` at the time it was set.
+    location_values: Option<Vec<CGFloat>>,
+    /// In the unit co-ordinate space of the layer.
+    start_point: CGPoint,
+    /// In the unit co-ordinate space of the layer.
+    end_point: CGPoint,
+    /// What was last drawn into the layer's bitmap.
+    drawn: Option<GradientDrawing>,
+}
+
+/// Everything that affects what a `CAGradientLayer` looks like.
+#[derive(PartialEq)]
+struct GradientDrawing {
+    bounds: CGRect,
+    colors: Vec<Rgba>,
+    locations: Option<Vec<CGFloat>>,
+    start_point: CGPoint,
+    end_point: CGPoint,
+}
+
+fn gradient(env: &Environment, layer: id) -> &GradientLayerProperties {
+    env.objc
+        .borrow::<CALayerHostObject>(layer)
+        .gradient
+        .as_deref()
+        .unwrap()
+}
+fn gradient_mut(env: &mut Environment, layer: id) -> &mut GradientLayerProperties {
+    env.objc
+        .borrow_mut::<CALayerHostObject>(layer)
+        .gradient
+        .as_deref_mut()
+        .unwrap()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAGradientLayer: CALayer
+
+- (id)init {
+    let this: id = msg_super![env; this init];
+    env.objc.borrow_mut::<CALayerHostObject>(this).gradient = Some(Box::new(GradientLayerProperties {
+        colors: nil,
+        color_values: Vec::new(),
+        locations: nil,
+        location_values: None,
+        start_point: CGPoint { x: 0.5, y: 0.0 },
+        end_point: CGPoint { x: 0.5, y: 1.0 },
+        drawn: None,
+    }));
+    this
+}
+
+- (())dealloc {
+    if let Some(gradient) = env.objc.borrow_mut::<CALayerHostObject>(this).gradient.take() {
+        release(env, gradient.colors);
+        release(env, gradient.locations);
+    }
+    msg_super![env; this dealloc]
+}
+
+- (id)colors {
+    gradient(env, this).colors
+}
+- (())setColors:(id)colors { // NSArray* of CGColorRef
+    let colors: id = msg![env; colors copy];
+    let count: NSUInteger = msg![env; colors count];
+    let color_values = (0..count).map(|i| {
+        let color: id = msg![env; colors objectAtIndex:i];
+        ca_layer::rgba_or_transparent(&env.objc, &env.mem, color)
+    }).collect();
+    let gradient = gradient_mut(env, this);
+    gradient.color_values = color_values;
+    let old_colors = std::mem::replace(&mut gradient.colors, colors);
+    release(env, old_colors);
+}
+
+- (id)locations {
+    gradient(env, this).locations
+}
+- (())setLocations:(id)locations { // NSArray* of NSNumber*
+    let locations: id = msg![env; locations copy];
+    let location_values = if locations == nil {
+        None
+    } else {
+        let count: NSUInteger = msg![env; locations count];
+        Some((0..count).map(|i| {
+            let location: id = msg![env; locations objectAtIndex:i];
+            let location: CGFloat = msg![env; location floatValue];
+            location
+        }).collect())
+    };
+    let gradient = gradient_mut(env, this);
+    gradient.location_values = location_values;
+    let old_locations = std::mem::replace(&mut gradient.locations, locations);
+    release(env, old_locations);
+}
+
+- (CGPoint)startPoint {
+    gradient(env, this).start_point
+}
+- (())setStartPoint:(CGPoint)start_point {
+    gradient_mut(env, this).start_point = start_point;
+}
+- (CGPoint)endPoint {
+    gradient(env, this).end_point
+}
+- (())setEndPoint:(CGPoint)end_point {
+    gradient_mut(env, this).end_point = end_point;
+}
+
+- (id)type {
+    ns_string::get_static_str(env, kCAGradientLayerAxial)
+}
+- (())setType:(id)type_ { // NSString*
+    let type_ = ns_string::to_rust_string(env, type_);
+    if type_ != kCAGradientLayerAxial {
+        log!("TODO: CAGradientLayer type {:?}, drawing as axial instead", type_);
+    }
+}
+
+// The compositor calls this for every frame, not just when the layer has been
+// marked as needing display, so this decides for itself whether to redraw.
+- (())displayIfNeeded {
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let forced = std::mem::take(&mut host_obj.needs_display);
+    let bounds = host_obj.bounds;
+    let gradient = host_obj.gradient.as_deref_mut().unwrap();
+    let drawing = GradientDrawing {
+        bounds,
+        colors: gradient.color_values.clone(),
+        locations: gradient.location_values.clone(),
+        start_point: gradient.start_point,
+        end_point: gradient.end_point,
+    };
+    if !forced && gradient.drawn.as_ref() == Some(&drawing) {
+        return;
+    }
+    gradient.drawn = None;
+
+    let size = drawing.bounds.size;
+    if size.width.round() < 1.0 || size.height.round() < 1.0 {
+        // Nothing can be seen, and there's no bitmap to draw into.
+        return;
+    }
+    let context = ca_layer::prepare_bitmap(env, this);
+    draw_gradient(env, context, &drawing);
+    gradient_mut(env, this).drawn = Some(drawing);
+}
+
+@end
+
+};
+
+/// Replace the contents of a gradient layer's bitmap with its gradient.
+fn draw_gradient(env: &mut Environment, context: CGContextRef, drawing: &GradientDrawing) {
+    let (width, height, data) = cg_bitmap_context::get_data(&env.objc, context);
+    let pixels = env
+        .mem
+        .bytes_at_mut(data.cast(), width.checked_mul(height * 4).unwrap());
+
+    let (w, h) = (width as CGFloat, height as CGFloat);
+    let start = CGPoint {
+        x: drawing.start_point.x * w,
+        y: drawing.start_point.y * h,
+    };
+    let axis = CGPoint {
+        x: drawing.end_point.x * w - start.x,
+        y: drawing.end_point.y * h - start.y,
+    };
+    let axis_length_squared = axis.x * axis.x + axis.y * axis.y;
+
+    for y in 0..height {
+        for x in 0..width {
+            // Sample at the pixel center, projected onto the gradient's axis.
+            let px = x as CGFloat + 0.5 - start.x;
+            let py = y as CGFloat + 0.5 - start.y;
+            let t = if axis_length_squared == 0.0 {
+                0.0
+            } else {
+                (px * axis.x + py * axis.y) / axis_length_squared
+            };
+            let (r, g, b, a) = color_at(&drawing.colors, drawing.locations.as_deref(), t);
+
+            // The layer's co-ordinate space has its origin in the top-left, but
+            // like in CG, bitmap rows are in bottom-to-top order.
+            let row = (height - 1 - y) as usize;
+            let index = (row * width as usize + x as usize) * 4;
+            let to_byte = |c: CGFloat| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            pixels[index..index + 4].copy_from_slice(&[
+                to_byte(r * a),
+                to_byte(g * a),
+                to_byte(b * a),
+                to_byte(a),
+            ]);
+        }
+    }
+}
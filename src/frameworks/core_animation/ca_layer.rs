@@ -5,21 +5,40 @@
  */
 //! `CALayer`.
 
-use crate::frameworks::core_foundation::{CFRelease, CFRetain};
+use super::animation::{self, AnimatableProperty, AnimatableValue, Animation};
+use super::ca_gradient_layer::GradientLayerProperties;
+use super::ca_shape_layer::ShapeLayerProperties;
+use super::{ca_animation, ca_transaction, frame_clock};
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
 use crate::frameworks::core_graphics::cg_bitmap_context::{
-    CGBitmapContextCreate, CGBitmapContextGetHeight, CGBitmapContextGetWidth,
+    CGBitmapContextCreate, CGBitmapContextCreateImage, CGBitmapContextGetHeight,
+    CGBitmapContextGetWidth,
 };
+use crate::frameworks::core_graphics::cg_color::{self, CGColorRef, CGColorRelease, CGColorRetain};
 use crate::frameworks::core_graphics::cg_color_space::CGColorSpaceCreateDeviceRGB;
 use crate::frameworks::core_graphics::cg_context::{
-    CGContextClearRect, CGContextRef, CGContextRelease, CGContextTranslateCTM,
+    add_path_elements, CGContextBeginPath, CGContextClearRect, CGContextClip, CGContextClipToRect,
+    CGContextConcatCTM, CGContextDrawImage, CGContextFillPath, CGContextFillRect, CGContextRef,
+    CGContextRelease, CGContextRestoreGState, CGContextSaveGState, CGContextScaleCTM,
+    CGContextSetAlpha, CGContextSetLineWidth, CGContextSetRGBFillColor, CGContextSetRGBStrokeColor,
+    CGContextStrokePath, CGContextTranslateCTM,
 };
 use crate::frameworks::core_graphics::cg_image::{
-    kCGImageAlphaPremultipliedLast, kCGImageByteOrder32Big,
+    kCGImageAlphaPremultipliedLast, kCGImageByteOrder32Big, CGImageRelease,
+};
+use crate::frameworks::core_graphics::cg_path;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_array, ns_string};
+use crate::mem::{GuestUSize, Mem, Ptr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    ObjC,
 };
-use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
-use crate::mem::{GuestUSize, Ptr};
-use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, ObjC};
+use crate::Environment;
 use std::collections::HashMap;
+use std::time::Instant;
 
 pub(super) struct CALayerHostObject {
     /// Possibly nil, usually a UIView. This is a weak reference.
@@ -31,10 +50,26 @@ pub(super) struct CALayerHostObject {
     pub(super) bounds: CGRect,
     pub(super) position: CGPoint,
     pub(super) anchor_point: CGPoint,
+    /// Applied about the anchor point.
+    pub(super) affine_transform: CGAffineTransform,
     pub(super) hidden: bool,
+    /// Whether the contents and sublayers are clipped to the bounds (with
+    /// rounded corners, if `corner_radius` is set).
+    pub(super) masks_to_bounds: bool,
     pub(super) opaque: bool,
     pub(super) opacity: f32,
-    pub(super) background_color: id,
+    pub(super) background_color: CGColorRef,
+    pub(super) corner_radius: CGFloat,
+    pub(super) border_width: CGFloat,
+    /// [None] means the default color (opaque black), which is only created
+    /// if the app asks for it.
+    pub(super) border_color: Option<CGColorRef>,
+    pub(super) shadow_opacity: f32,
+    pub(super) shadow_radius: CGFloat,
+    pub(super) shadow_offset: CGSize,
+    /// [None] means the default color (opaque black), which is only created
+    /// if the app asks for it.
+    pub(super) shadow_color: Option<CGColorRef>,
     pub(super) needs_display: bool,
     /// `CGImageRef*`
     pub(super) contents: id,
@@ -42,14 +77,174 @@ pub(super) struct CALayerHostObject {
     pub(super) drawable_properties: id,
     /// For CAEAGLLayer only (internal state for compositor)
     pub(super) presented_pixels: Option<(Vec<u8>, u32, u32)>,
+    /// For CAShapeLayer only
+    pub(super) shape: Option<Box<ShapeLayerProperties>>,
+    /// For CAGradientLayer only
+    pub(super) gradient: Option<Box<GradientLayerProperties>>,
     /// Internal, only exposed when calling `drawLayer:inContext:`
     pub(super) cg_context: Option<CGContextRef>,
     /// Internal state for compositor
     pub(super) gles_texture: Option<crate::gles::gles11_raw::types::GLuint>,
     /// Internal state for compositor
     pub(super) gles_texture_is_up_to_date: bool,
+    /// Animations of properties, in the order they were added. Finished
+    /// animations are removed lazily.
+    pub(super) animations: Vec<Animation>,
 }
 impl HostObject for CALayerHostObject {}
+impl CALayerHostObject {
+    /// Get the transform from this layer's co-ordinate space to its
+    /// superlayer's co-ordinate space.
+    pub(super) fn transform_to_superlayer(&self) -> CGAffineTransform {
+        transform_to_superlayer(
+            self.bounds,
+            self.position,
+            self.anchor_point,
+            self.affine_transform,
+        )
+    }
+
+    /// Like [Self::transform_to_superlayer], but using the presentation
+    /// values of animated properties.
+    pub(super) fn presentation_transform_to_superlayer(&self, now: Instant) -> CGAffineTransform {
+        let AnimatableValue::Rect(bounds) =
+            self.presentation_value(AnimatableProperty::Bounds, now)
+        else {
+            unreachable!()
+        };
+        let AnimatableValue::Point(position) =
+            self.presentation_value(AnimatableProperty::Position, now)
+        else {
+            unreachable!()
+        };
+        transform_to_superlayer(bounds, position, self.anchor_point, self.affine_transform)
+    }
+
+    /// Get the current value of an animatable property. Colors must be
+    /// converted to RGBA by the caller.
+    fn model_value(&self, property: AnimatableProperty) -> AnimatableValue {
+        match property {
+            AnimatableProperty::Bounds => AnimatableValue::Rect(self.bounds),
+            AnimatableProperty::Position => AnimatableValue::Point(self.position),
+            AnimatableProperty::Opacity => AnimatableValue::Float(self.opacity),
+            AnimatableProperty::BackgroundColor => unreachable!(),
+            // These only make sense for a CAShapeLayer, but the defaults are
+            // used for other layers rather than crashing.
+            AnimatableProperty::Path => AnimatableValue::Path(
+                self.shape
+                    .as_ref()
+                    .map_or_else(Default::default, |shape| shape.path_elements.clone()),
+            ),
+            AnimatableProperty::StrokeStart => {
+                AnimatableValue::Float(self.shape.as_ref().map_or(0.0, |shape| shape.stroke_start))
+            }
+            AnimatableProperty::StrokeEnd => {
+                AnimatableValue::Float(self.shape.as_ref().map_or(1.0, |shape| shape.stroke_end))
+            }
+            AnimatableProperty::LineWidth => {
+                AnimatableValue::Float(self.shape.as_ref().map_or(1.0, |shape| shape.line_width))
+            }
+        }
+    }
+
+    /// Get the value of an animatable property that should be displayed at
+    /// `now`. See [Self::presentation_background_color] for colors.
+    pub(super) fn presentation_value(
+        &self,
+        property: AnimatableProperty,
+        now: Instant,
+    ) -> AnimatableValue {
+        animation::presentation_value(&self.animations, property, self.model_value(property), now)
+    }
+
+    /// Get the background color that should be displayed at `now`, as RGBA.
+    pub(super) fn presentation_background_color(
+        &self,
+        objc: &ObjC,
+        mem: &Mem,
+        now: Instant,
+    ) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+        let model_value =
+            AnimatableValue::Color(rgba_or_transparent(objc, mem, self.background_color));
+        let AnimatableValue::Color(rgba) = animation::presentation_value(
+            &self.animations,
+            AnimatableProperty::BackgroundColor,
+            model_value,
+            now,
+        ) else {
+            unreachable!()
+        };
+        rgba
+    }
+}
+
+fn transform_to_superlayer(
+    bounds: CGRect,
+    position: CGPoint,
+    anchor_point: CGPoint,
+    affine_transform: CGAffineTransform,
+) -> CGAffineTransform {
+    let anchor = CGPoint {
+        x: bounds.origin.x + bounds.size.width * anchor_point.x,
+        y: bounds.origin.y + bounds.size.height * anchor_point.y,
+    };
+    CGAffineTransform::make_translation(-anchor.x, -anchor.y)
+        .concat(affine_transform)
+        .concat(CGAffineTransform::make_translation(position.x, position.y))
+}
+
+pub(super) fn rgba_or_transparent(
+    objc: &ObjC,
+    mem: &Mem,
+    color: CGColorRef,
+) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    if color == nil {
+        (0.0, 0.0, 0.0, 0.0)
+    } else {
+        cg_color::get_rgba(objc, mem, color)
+    }
+}
+
+/// Like [rgba_or_transparent], but for colors that default to opaque black.
+pub(super) fn rgba_or_black(
+    objc: &ObjC,
+    mem: &Mem,
+    color: Option<CGColorRef>,
+) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    match color {
+        Some(color) => rgba_or_transparent(objc, mem, color),
+        None => (0.0, 0.0, 0.0, 1.0),
+    }
+}
+
+/// Shrink a rectangle by `inset` on each side (or grow it, if negative). The
+/// size never becomes negative.
+pub(super) fn inset_rect(rect: CGRect, inset: CGFloat) -> CGRect {
+    let width = (rect.size.width - inset * 2.0).max(0.0);
+    let height = (rect.size.height - inset * 2.0).max(0.0);
+    CGRect {
+        origin: CGPoint {
+            x: rect.origin.x + (rect.size.width - width) / 2.0,
+            y: rect.origin.y + (rect.size.height - height) / 2.0,
+        },
+        size: CGSize { width, height },
+    }
+}
+
+/// A blurred shadow is approximated by a stack of translucent copies of the
+/// layer's outline, from `radius` larger to `radius` smaller than the layer,
+/// so the edge fades out over roughly the blur radius. Returns how much each
+/// copy should be inset by, and the alpha to draw all of them with so that
+/// their combined alpha is `alpha`.
+pub(super) fn shadow_steps(radius: CGFloat, alpha: CGFloat) -> (Vec<CGFloat>, CGFloat) {
+    // Each step is about a point wide, up to a limit.
+    let count = radius.max(0.0).ceil().clamp(1.0, 8.0);
+    let step_alpha = 1.0 - (1.0 - alpha.clamp(0.0, 1.0)).powf(1.0 / count);
+    let insets = (0..(count as u32))
+        .map(|i| radius * ((2 * i + 1) as CGFloat / count - 1.0))
+        .collect();
+    (insets, step_alpha)
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -68,17 +263,29 @@ pub const CLASSES: ClassExports = objc_classes! {
         },
         position: CGPoint { x: 0.0, y: 0.0 },
         anchor_point: CGPoint { x: 0.5, y: 0.5 },
+        affine_transform: CGAffineTransformIdentity,
         hidden: false,
+        masks_to_bounds: false,
         opaque: false,
         opacity: 1.0,
         background_color: nil, // transparency
+        corner_radius: 0.0,
+        border_width: 0.0,
+        border_color: None,
+        shadow_opacity: 0.0,
+        shadow_radius: 3.0,
+        shadow_offset: CGSize { width: 0.0, height: -3.0 },
+        shadow_color: None,
         needs_display: true,
         contents: nil,
         drawable_properties: nil,
         presented_pixels: None,
+        shape: None,
+        gradient: None,
         cg_context: None,
         gles_texture: None,
         gles_texture_is_up_to_date: false,
+        animations: Vec::new(),
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -94,11 +301,15 @@ pub const CLASSES: ClassExports = objc_classes! {
         contents,
         superlayer,
         background_color,
+        border_color,
+        shadow_color,
         cg_context,
         ref mut sublayers,
+        ref mut animations,
         ..
     } = env.objc.borrow_mut(this);
     let sublayers = std::mem::take(sublayers);
+    let animations = std::mem::take(animations);
 
     if drawable_properties != nil {
         release(env, drawable_properties);
@@ -108,14 +319,24 @@ pub const CLASSES: ClassExports = objc_classes! {
         release(env, contents);
     }
 
-    if background_color != nil {
-        CFRelease(env, background_color);
+    CGColorRelease(env, background_color);
+    if let Some(border_color) = border_color {
+        CGColorRelease(env, border_color);
+    }
+    if let Some(shadow_color) = shadow_color {
+        CGColorRelease(env, shadow_color);
     }
 
     if let Some(cg_context) = cg_context {
         CGContextRelease(env, cg_context);
     }
 
+    for animation in animations {
+        if let Some((_, object)) = animation.explicit {
+            release(env, object);
+        }
+    }
+
     assert!(superlayer == nil);
     for sublayer in sublayers {
         env.objc.borrow_mut::<CALayerHostObject>(sublayer).superlayer = nil;
@@ -166,12 +387,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<CALayerHostObject>(this).bounds
 }
 - (())setBounds:(CGRect)bounds {
+    add_implicit_animation(env, this, AnimatableProperty::Bounds);
     env.objc.borrow_mut::<CALayerHostObject>(this).bounds = bounds;
 }
 - (CGPoint)position {
     env.objc.borrow::<CALayerHostObject>(this).position
 }
 - (())setPosition:(CGPoint)position {
+    add_implicit_animation(env, this, AnimatableProperty::Position);
     env.objc.borrow_mut::<CALayerHostObject>(this).position = position;
 }
 - (CGPoint)anchorPoint {
@@ -181,13 +404,25 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow_mut::<CALayerHostObject>(this).anchor_point = anchor_point;
 }
 
+- (CGAffineTransform)affineTransform {
+    env.objc.borrow::<CALayerHostObject>(this).affine_transform
+}
+- (())setAffineTransform:(CGAffineTransform)affine_transform {
+    env.objc.borrow_mut::<CALayerHostObject>(this).affine_transform = affine_transform;
+}
+
 - (CGRect)frame {
+    let host_object = env.objc.borrow::<CALayerHostObject>(this);
+    if !host_object.affine_transform.is_identity() {
+        // The frame is the bounding box of the transformed bounds.
+        return host_object.transform_to_superlayer().apply_to_rect(host_object.bounds);
+    }
     let &CALayerHostObject {
         bounds,
         position,
         anchor_point,
         ..
-    } = env.objc.borrow(this);
+    } = host_object;
     CGRect {
         origin: CGPoint {
             x: position.x - bounds.size.width * anchor_point.x,
@@ -197,16 +432,25 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 - (())setFrame:(CGRect)frame {
+    add_implicit_animation(env, this, AnimatableProperty::Position);
+    add_implicit_animation(env, this, AnimatableProperty::Bounds);
     let CALayerHostObject {
         bounds,
         position,
         anchor_point,
+        affine_transform,
         ..
     } = env.objc.borrow_mut(this);
     *position = CGPoint {
         x: frame.origin.x + frame.size.width * anchor_point.x,
         y: frame.origin.y + frame.size.height * anchor_point.y,
     };
+    // Apple's documentation says the frame is undefined when there's a
+    // transform, so there's no right answer for how to resize the layer.
+    // Only moving it seems the least surprising.
+    if !affine_transform.is_identity() {
+        return;
+    }
     *bounds = CGRect {
         origin: CGPoint { x: 0.0, y: 0.0 },
         size: frame.size,
@@ -220,6 +464,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow_mut::<CALayerHostObject>(this).hidden = hidden;
 }
 
+- (bool)masksToBounds {
+    env.objc.borrow::<CALayerHostObject>(this).masks_to_bounds
+}
+- (())setMasksToBounds:(bool)masks_to_bounds {
+    env.objc.borrow_mut::<CALayerHostObject>(this).masks_to_bounds = masks_to_bounds;
+}
+
 - (bool)isOpaque {
     env.objc.borrow::<CALayerHostObject>(this).opaque
 }
@@ -231,21 +482,81 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<CALayerHostObject>(this).opacity
 }
 - (())setOpacity:(f32)opacity {
+    add_implicit_animation(env, this, AnimatableProperty::Opacity);
     env.objc.borrow_mut::<CALayerHostObject>(this).opacity = opacity;
 }
 
-// See remarks in ui_view.rs about the type of this property
-- (id)backgroundColor {
+- (CGColorRef)backgroundColor {
     env.objc.borrow::<CALayerHostObject>(this).background_color
 }
-- (())setBackgroundColor:(id)new_color {
+- (())setBackgroundColor:(CGColorRef)new_color {
+    add_implicit_animation(env, this, AnimatableProperty::BackgroundColor);
+    CGColorRetain(env, new_color);
     let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
     let old_color = std::mem::replace(&mut host_obj.background_color, new_color);
-    if new_color != nil {
-        CFRetain(env, new_color); // CFRetain doesn't like nil
+    CGColorRelease(env, old_color);
+}
+
+- (CGFloat)cornerRadius {
+    env.objc.borrow::<CALayerHostObject>(this).corner_radius
+}
+- (())setCornerRadius:(CGFloat)corner_radius {
+    env.objc.borrow_mut::<CALayerHostObject>(this).corner_radius = corner_radius;
+}
+
+- (CGFloat)borderWidth {
+    env.objc.borrow::<CALayerHostObject>(this).border_width
+}
+- (())setBorderWidth:(CGFloat)border_width {
+    env.objc.borrow_mut::<CALayerHostObject>(this).border_width = border_width;
+}
+- (CGColorRef)borderColor {
+    if let Some(color) = env.objc.borrow::<CALayerHostObject>(this).border_color {
+        return color;
     }
-    if old_color != nil {
-        CFRelease(env, old_color); // CFRelease doesn't like nil
+    let color = cg_color::from_rgba(env, (0.0, 0.0, 0.0, 1.0));
+    env.objc.borrow_mut::<CALayerHostObject>(this).border_color = Some(color);
+    color
+}
+- (())setBorderColor:(CGColorRef)new_color {
+    CGColorRetain(env, new_color);
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
+    if let Some(old_color) = host_obj.border_color.replace(new_color) {
+        CGColorRelease(env, old_color);
+    }
+}
+
+- (f32)shadowOpacity {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_opacity
+}
+- (())setShadowOpacity:(f32)shadow_opacity {
+    env.objc.borrow_mut::<CALayerHostObject>(this).shadow_opacity = shadow_opacity;
+}
+- (CGFloat)shadowRadius {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_radius
+}
+- (())setShadowRadius:(CGFloat)shadow_radius {
+    env.objc.borrow_mut::<CALayerHostObject>(this).shadow_radius = shadow_radius;
+}
+- (CGSize)shadowOffset {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_offset
+}
+- (())setShadowOffset:(CGSize)shadow_offset {
+    env.objc.borrow_mut::<CALayerHostObject>(this).shadow_offset = shadow_offset;
+}
+- (CGColorRef)shadowColor {
+    if let Some(color) = env.objc.borrow::<CALayerHostObject>(this).shadow_color {
+        return color;
+    }
+    let color = cg_color::from_rgba(env, (0.0, 0.0, 0.0, 1.0));
+    env.objc.borrow_mut::<CALayerHostObject>(this).shadow_color = Some(color);
+    color
+}
+- (())setShadowColor:(CGColorRef)new_color {
+    CGColorRetain(env, new_color);
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
+    if let Some(old_color) = host_obj.shadow_color.replace(new_color) {
+        CGColorRelease(env, old_color);
     }
 }
 
@@ -255,7 +566,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())setNeedsDisplay {
     env.objc.borrow_mut::<CALayerHostObject>(this).needs_display = true;
 }
-// TODO: support setNeedsDisplayInRect:
+- (())setNeedsDisplayInRect:(CGRect)_rect {
+    // The whole layer is always redrawn.
+    env.objc.borrow_mut::<CALayerHostObject>(this).needs_display = true;
+}
 - (())displayIfNeeded {
     let &mut CALayerHostObject {
         ref mut needs_display,
@@ -309,50 +623,8 @@ pub const CLASSES: ClassExports = objc_classes! {
         }
     }
 
-    let &mut CALayerHostObject {
-        cg_context,
-        ref mut gles_texture_is_up_to_date,
-        bounds: CGRect { origin, size },
-        ..
-    } = env.objc.borrow_mut(this);
-
-    *gles_texture_is_up_to_date = false;
-
-    // TODO: more correctly handle non-integer sizes?
-    let int_width = size.width.round() as GuestUSize;
-    let int_height = size.height.round() as GuestUSize;
-
-    let need_new_context = cg_context.map_or(
-        true,
-        |existing| (
-            CGBitmapContextGetWidth(env, existing) != int_width ||
-            CGBitmapContextGetHeight(env, existing) != int_height
-        )
-    );
-    let cg_context = if need_new_context {
-        if let Some(old_context) = cg_context {
-            CGContextRelease(env, old_context);
-        }
-
-        // Make sure this is in sync with the code in composition.rs that
-        // uploads the texture!
-        // TODO: is this the right color space?
-        let color_space = CGColorSpaceCreateDeviceRGB(env);
-        let cg_context = CGBitmapContextCreate(
-            env,
-            Ptr::null(),
-            int_width,
-            int_height,
-            8, // bpp
-            int_width.checked_mul(4).unwrap(),
-            color_space,
-            kCGImageByteOrder32Big | kCGImageAlphaPremultipliedLast
-        );
-        env.objc.borrow_mut::<CALayerHostObject>(this).cg_context = Some(cg_context);
-        cg_context
-    } else {
-        cg_context.unwrap()
-    };
+    let cg_context = prepare_bitmap(env, this);
+    let CGRect { origin, size } = env.objc.borrow::<CALayerHostObject>(this).bounds;
 
     CGContextTranslateCTM(env, cg_context, -origin.x, -origin.y);
     // TODO: move clearing to UIKit (clearsContextBeforeDrawing)?
@@ -361,6 +633,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     CGContextTranslateCTM(env, cg_context, origin.x, origin.y);
 }
 
+- (())renderInContext:(CGContextRef)context {
+    render_in_context(env, this, context, 1.0);
+}
+
 // CGImageRef*
 - (id)contents {
     env.objc.borrow::<CALayerHostObject>(this).contents
@@ -393,52 +669,45 @@ pub const CLASSES: ClassExports = objc_classes! {
     // The idea is to walk up each layer's superlayer chain, one at a time,
     // alternating between layers until we find a match.
 
-    // Maps of layer pointers to origins in that layer's co-ordinate space.
-    let mut this_map = HashMap::from([(this, CGPoint { x: 0.0, y: 0.0 })]);
-    let mut other_map = HashMap::from([(other, CGPoint { x: 0.0, y: 0.0 })]);
+    // Maps of layer pointers to transforms from the starting layer's
+    // co-ordinate space to that layer's co-ordinate space.
+    let mut this_map = HashMap::from([(this, CGAffineTransformIdentity)]);
+    let mut other_map = HashMap::from([(other, CGAffineTransformIdentity)]);
     // Current iteration state.
     let mut this_superlayer = this;
-    let mut this_origin = CGPoint { x: 0.0, y: 0.0 };
+    let mut this_transform = CGAffineTransformIdentity;
     let mut other_superlayer = other;
-    let mut other_origin = CGPoint { x: 0.0, y: 0.0 };
-    let (common_ancestor, this_origin, other_origin) = loop {
+    let mut other_transform = CGAffineTransformIdentity;
+    let (common_ancestor, this_transform, other_transform) = loop {
         if this_superlayer != nil {
-            let next: id = msg![env; this_superlayer superlayer];
+            let host_object = env.objc.borrow::<CALayerHostObject>(this_superlayer);
+            let next = host_object.superlayer;
             if next == nil {
                 this_superlayer = nil;
             } else {
-                let bounds: CGRect = msg![env; this_superlayer bounds];
-                let frame: CGRect = msg![env; this_superlayer frame];
-                let next_origin = CGPoint {
-                    x: this_origin.x + frame.origin.x - bounds.origin.x,
-                    y: this_origin.y + frame.origin.y - bounds.origin.y,
-                };
-                if let Some(&other_origin) = other_map.get(&next) {
-                    break (next, next_origin, other_origin);
+                let next_transform = this_transform.concat(host_object.transform_to_superlayer());
+                if let Some(&other_transform) = other_map.get(&next) {
+                    break (next, next_transform, other_transform);
                 }
-                this_map.insert(next, next_origin);
+                this_map.insert(next, next_transform);
                 this_superlayer = next;
-                this_origin = next_origin;
+                this_transform = next_transform;
             }
         }
 
         if other_superlayer != nil {
-            let next: id = msg![env; other_superlayer superlayer];
+            let host_object = env.objc.borrow::<CALayerHostObject>(other_superlayer);
+            let next = host_object.superlayer;
             if next == nil {
                 other_superlayer = nil;
             } else {
-                let bounds: CGRect = msg![env; other_superlayer bounds];
-                let frame: CGRect = msg![env; other_superlayer frame];
-                let next_origin = CGPoint {
-                    x: other_origin.x + frame.origin.x - bounds.origin.x,
-                    y: other_origin.y + frame.origin.y - bounds.origin.y,
-                };
-                if let Some(&this_origin) = this_map.get(&next) {
-                    break (next, this_origin, next_origin);
+                let next_transform = other_transform.concat(host_object.transform_to_superlayer());
+                if let Some(&this_transform) = this_map.get(&next) {
+                    break (next, this_transform, next_transform);
                 }
-                other_map.insert(next, next_origin);
+                other_map.insert(next, next_transform);
                 other_superlayer = next;
-                other_origin = next_origin;
+                other_transform = next_transform;
             }
         }
 
@@ -449,12 +718,9 @@ pub const CLASSES: ClassExports = objc_classes! {
     };
 
     log_dbg!("{:?} and {:?}'s common ancestor: {:?}", this, other, common_ancestor);
-    log_dbg!("{:?}'s origin in common ancestor: {:?}", this, this_origin);
-    log_dbg!("{:?}'s origin in common ancestor: {:?}", other, other_origin);
-    let res = CGPoint {
-        x: point.x + other_origin.x - this_origin.x,
-        y: point.y + other_origin.y - this_origin.y,
-    };
+    log_dbg!("{:?}'s transform to common ancestor: {:?}", this, this_transform);
+    log_dbg!("{:?}'s transform to common ancestor: {:?}", other, other_transform);
+    let res = this_transform.invert().apply_to_point(other_transform.apply_to_point(point));
     log_dbg!("Converted {:?} from {:?} to {:?}: {:?}", point, other, this, res);
     res
 }
@@ -466,8 +732,433 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; other convertPoint:point fromLayer:this]
 }
 
+// Animation
+
+- (id)presentationLayer {
+    // This is a snapshot, not a live copy like on the real iPhone OS.
+    let now = frame_clock::now(env);
+    stop_finished_animations(env, this, now);
+    let host_obj = env.objc.borrow::<CALayerHostObject>(this);
+    let AnimatableValue::Rect(bounds) = host_obj.presentation_value(AnimatableProperty::Bounds, now)
+    else {
+        unreachable!()
+    };
+    let AnimatableValue::Point(position) =
+        host_obj.presentation_value(AnimatableProperty::Position, now)
+    else {
+        unreachable!()
+    };
+    let AnimatableValue::Float(opacity) =
+        host_obj.presentation_value(AnimatableProperty::Opacity, now)
+    else {
+        unreachable!()
+    };
+    let background_color = if host_obj.background_color == nil && !host_obj
+        .animations
+        .iter()
+        .any(|animation| animation.property == AnimatableProperty::BackgroundColor)
+    {
+        None
+    } else {
+        Some(host_obj.presentation_background_color(&env.objc, &env.mem, now))
+    };
+    let &CALayerHostObject {
+        anchor_point,
+        affine_transform,
+        hidden,
+        masks_to_bounds,
+        opaque,
+        ..
+    } = host_obj;
+
+    let background_color = match background_color {
+        Some(rgba) => cg_color::from_rgba(env, rgba),
+        None => nil,
+    };
+    let presentation: id = msg_class![env; CALayer alloc];
+    let presentation: id = msg![env; presentation init];
+    let presentation_host_obj = env.objc.borrow_mut::<CALayerHostObject>(presentation);
+    presentation_host_obj.bounds = bounds;
+    presentation_host_obj.position = position;
+    presentation_host_obj.anchor_point = anchor_point;
+    presentation_host_obj.affine_transform = affine_transform;
+    presentation_host_obj.hidden = hidden;
+    presentation_host_obj.masks_to_bounds = masks_to_bounds;
+    presentation_host_obj.opaque = opaque;
+    presentation_host_obj.opacity = opacity;
+    presentation_host_obj.background_color = background_color;
+    autorelease(env, presentation)
+}
+
+- (id)modelLayer {
+    this
+}
+
+- (())addAnimation:(id)animation // CAAnimation*
+             forKey:(id)key { // NSString*
+    let key = (key != nil).then(|| ns_string::to_rust_string(env, key).into_owned());
+    if let Some(key) = &key {
+        remove_animations(env, this, |other_key| other_key == Some(key.as_str()));
+    }
+    // The layer's animation can't be changed by the app.
+    let animation: id = msg![env; animation copy];
+    let new_animation = ca_animation::make_animation(env, animation, key, |env, property| {
+        let now = frame_clock::now(env);
+        current_presentation_value(env, this, property, now)
+    });
+    let Some(new_animation) = new_animation else {
+        release(env, animation);
+        return;
+    };
+    env.objc
+        .borrow_mut::<CALayerHostObject>(this)
+        .animations
+        .push(new_animation);
+}
+
+- (id)animationForKey:(id)key { // NSString*
+    let now = frame_clock::now(env);
+    stop_finished_animations(env, this, now);
+    let key = ns_string::to_rust_string(env, key);
+    env.objc
+        .borrow::<CALayerHostObject>(this)
+        .animations
+        .iter()
+        .find_map(|animation| match &animation.explicit {
+            Some((Some(other_key), object)) if *other_key == key => Some(*object),
+            _ => None,
+        })
+        .unwrap_or(nil)
+}
+
+- (id)animationKeys {
+    let now = frame_clock::now(env);
+    stop_finished_animations(env, this, now);
+    let keys: Vec<String> = env
+        .objc
+        .borrow::<CALayerHostObject>(this)
+        .animations
+        .iter()
+        .filter_map(|animation| animation.explicit.as_ref()?.0.clone())
+        .collect();
+    if keys.is_empty() {
+        return nil;
+    }
+    let keys = keys
+        .into_iter()
+        .map(|key| ns_string::from_rust_string(env, key))
+        .collect();
+    let keys = ns_array::from_vec(env, keys);
+    autorelease(env, keys)
+}
+
+- (())removeAnimationForKey:(id)key { // NSString*
+    let key = ns_string::to_rust_string(env, key);
+    remove_animations(env, this, |other_key| other_key == Some(&*key));
+}
+
+- (())removeAllAnimations {
+    remove_animations(env, this, |_| true);
+    env.objc
+        .borrow_mut::<CALayerHostObject>(this)
+        .animations
+        .clear();
+}
+
 // TODO: more
 
 @end
 
 };
+
+/// Implementation of `renderInContext:`. Draws the layer and its sublayers in
+/// the layer's co-ordinate space, using the model values of its properties.
+/// `opacity` is the accumulated opacity of the superlayers.
+fn render_in_context(env: &mut Environment, layer: id, context: CGContextRef, opacity: CGFloat) {
+    if env.objc.borrow::<CALayerHostObject>(layer).hidden {
+        return;
+    }
+
+    // Make sure the layer's bitmap is up to date, as the compositor would.
+    () = msg![env; layer displayIfNeeded];
+
+    let host_obj = env.objc.borrow::<CALayerHostObject>(layer);
+    let opacity = opacity * host_obj.opacity;
+    let background_color = rgba_or_transparent(&env.objc, &env.mem, host_obj.background_color);
+    let border_color = rgba_or_black(&env.objc, &env.mem, host_obj.border_color);
+    let shadow_color = rgba_or_black(&env.objc, &env.mem, host_obj.shadow_color);
+    let &CALayerHostObject {
+        bounds,
+        masks_to_bounds,
+        corner_radius,
+        border_width,
+        shadow_opacity,
+        shadow_radius,
+        shadow_offset,
+        contents,
+        cg_context,
+        ..
+    } = host_obj;
+
+    CGContextSaveGState(env, context);
+    CGContextSetAlpha(env, context, opacity);
+
+    // The shadow has the shape of the layer's bounds rather than of what is
+    // drawn in it, so it is only drawn if there's a background or contents.
+    // Like on the real iPhone OS, masksToBounds clips the shadow away.
+    let (r, g, b, a) = shadow_color;
+    let a = a * shadow_opacity;
+    let has_content = background_color.3 != 0.0 || contents != nil || cg_context.is_some();
+    if a != 0.0 && has_content && !masks_to_bounds {
+        let (insets, step_alpha) = shadow_steps(shadow_radius, a);
+        CGContextSetRGBFillColor(env, context, r, g, b, step_alpha);
+        for inset in insets {
+            let mut rect = inset_rect(bounds, inset);
+            rect.origin.x += shadow_offset.width;
+            rect.origin.y += shadow_offset.height;
+            let radius = (corner_radius - inset).max(0.0);
+            CGContextBeginPath(env, context);
+            add_path_elements(env, context, &cg_path::rounded_rect(rect, radius));
+            CGContextFillPath(env, context);
+        }
+    }
+
+    if masks_to_bounds {
+        if corner_radius > 0.0 {
+            CGContextBeginPath(env, context);
+            add_path_elements(env, context, &cg_path::rounded_rect(bounds, corner_radius));
+            CGContextClip(env, context);
+        } else {
+            CGContextClipToRect(env, context, bounds);
+        }
+    }
+
+    let (r, g, b, a) = background_color;
+    if a != 0.0 {
+        CGContextSetRGBFillColor(env, context, r, g, b, a);
+        if corner_radius > 0.0 {
+            CGContextBeginPath(env, context);
+            add_path_elements(env, context, &cg_path::rounded_rect(bounds, corner_radius));
+            CGContextFillPath(env, context);
+        } else {
+            CGContextFillRect(env, context, bounds);
+        }
+    }
+
+    if contents != nil {
+        // Images have top-to-bottom row order, so they must be flipped to
+        // appear the right way up in the layer's co-ordinate space.
+        CGContextSaveGState(env, context);
+        CGContextTranslateCTM(
+            env,
+            context,
+            bounds.origin.x,
+            bounds.origin.y + bounds.size.height,
+        );
+        CGContextScaleCTM(env, context, 1.0, -1.0);
+        let rect = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: bounds.size,
+        };
+        CGContextDrawImage(env, context, rect, contents);
+        CGContextRestoreGState(env, context);
+    } else if let Some(cg_context) = cg_context {
+        // The layer's bitmap is in the layer's co-ordinate space already, see
+        // displayIfNeeded.
+        let image = CGBitmapContextCreateImage(env, cg_context);
+        CGContextDrawImage(env, context, bounds, image);
+        CGImageRelease(env, image);
+    }
+
+    let sublayers = env
+        .objc
+        .borrow::<CALayerHostObject>(layer)
+        .sublayers
+        .clone();
+    for sublayer in sublayers {
+        let transform = env
+            .objc
+            .borrow::<CALayerHostObject>(sublayer)
+            .transform_to_superlayer();
+        CGContextSaveGState(env, context);
+        CGContextConcatCTM(env, context, transform);
+        render_in_context(env, sublayer, context, opacity);
+        CGContextRestoreGState(env, context);
+    }
+
+    // The border is drawn inside the bounds, on top of the sublayers.
+    let (r, g, b, a) = border_color;
+    if border_width > 0.0 && a != 0.0 {
+        let rect = inset_rect(bounds, border_width / 2.0);
+        let radius = (corner_radius - border_width / 2.0).max(0.0);
+        CGContextSetRGBStrokeColor(env, context, r, g, b, a);
+        CGContextSetLineWidth(env, context, border_width);
+        CGContextBeginPath(env, context);
+        add_path_elements(env, context, &cg_path::rounded_rect(rect, radius));
+        CGContextStrokePath(env, context);
+    }
+
+    CGContextRestoreGState(env, context);
+}
+
+/// Start an implicit animation of a property that is about to be changed, if
+/// the current transaction allows it. This must be called before the new
+/// value is set.
+pub(super) fn add_implicit_animation(
+    env: &mut Environment,
+    layer: id,
+    property: AnimatableProperty,
+) {
+    let Some(duration) = ca_transaction::implicit_animation_duration(env) else {
+        return;
+    };
+
+    // The delegate can veto the animation by returning NSNull. This is how
+    // UIView stops its layer's changes from being animated.
+    let delegate = env.objc.borrow::<CALayerHostObject>(layer).delegate;
+    if delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, "actionForLayer:forKey:")
+    {
+        let key = ns_string::get_static_str(env, property.key());
+        let action: id = msg![env; delegate actionForLayer:layer forKey:key];
+        let null: id = msg_class![env; NSNull null];
+        if action == null {
+            return;
+        }
+    }
+
+    let now = frame_clock::now(env);
+    // The animation starts from whatever is currently displayed, so that
+    // interrupting an animation doesn't cause a jump.
+    let from = current_presentation_value(env, layer, property, now);
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(layer);
+    // Older implicit animations of this property are superseded.
+    host_obj.animations.retain(|animation| {
+        animation.explicit.is_some()
+            || (animation.is_in_effect(now) && animation.property != property)
+    });
+    host_obj
+        .animations
+        .push(Animation::implicit(property, from, now, duration));
+}
+
+/// Get the layer's bitmap context for drawing its contents, creating a new one
+/// if the layer's size has changed. The compositor will upload the bitmap
+/// again afterwards.
+pub(super) fn prepare_bitmap(env: &mut Environment, layer: id) -> CGContextRef {
+    let &mut CALayerHostObject {
+        cg_context,
+        ref mut gles_texture_is_up_to_date,
+        bounds: CGRect { size, .. },
+        ..
+    } = env.objc.borrow_mut(layer);
+
+    *gles_texture_is_up_to_date = false;
+
+    // TODO: more correctly handle non-integer sizes?
+    let int_width = size.width.round() as GuestUSize;
+    let int_height = size.height.round() as GuestUSize;
+
+    let need_new_context = cg_context.map_or(true, |existing| {
+        CGBitmapContextGetWidth(env, existing) != int_width
+            || CGBitmapContextGetHeight(env, existing) != int_height
+    });
+    if !need_new_context {
+        return cg_context.unwrap();
+    }
+
+    if let Some(old_context) = cg_context {
+        CGContextRelease(env, old_context);
+    }
+
+    // Make sure this is in sync with the code in composition.rs that uploads
+    // the texture!
+    // TODO: is this the right color space?
+    let color_space = CGColorSpaceCreateDeviceRGB(env);
+    let cg_context = CGBitmapContextCreate(
+        env,
+        Ptr::null(),
+        int_width,
+        int_height,
+        8, // bpp
+        int_width.checked_mul(4).unwrap(),
+        color_space,
+        kCGImageByteOrder32Big | kCGImageAlphaPremultipliedLast,
+    );
+    env.objc.borrow_mut::<CALayerHostObject>(layer).cg_context = Some(cg_context);
+    cg_context
+}
+
+/// Get the value of any animatable property that should be displayed at `now`.
+fn current_presentation_value(
+    env: &mut Environment,
+    layer: id,
+    property: AnimatableProperty,
+    now: Instant,
+) -> AnimatableValue {
+    let host_obj = env.objc.borrow::<CALayerHostObject>(layer);
+    if property == AnimatableProperty::BackgroundColor {
+        AnimatableValue::Color(host_obj.presentation_background_color(&env.objc, &env.mem, now))
+    } else {
+        host_obj.presentation_value(property, now)
+    }
+}
+
+/// Remove finished animations from a layer and tell the delegates of explicit
+/// animations that they have finished.
+pub(super) fn stop_finished_animations(env: &mut Environment, layer: id, now: Instant) {
+    let mut stopped = Vec::new();
+    let mut removed = Vec::new();
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(layer);
+    host_obj.animations.retain_mut(|animation| {
+        let Some((_, object)) = animation.explicit else {
+            return animation.is_in_effect(now);
+        };
+        if animation.stopped || !animation.is_finished(now) {
+            return true;
+        }
+        animation.stopped = true;
+        stopped.push(object);
+        if animation.removed_on_completion {
+            removed.push(object);
+            false
+        } else {
+            true
+        }
+    });
+    for animation in stopped {
+        ca_animation::animation_did_stop(env, animation, /* finished: */ true);
+    }
+    for animation in removed {
+        release(env, animation);
+    }
+}
+
+/// Remove explicit animations from a layer, telling their delegates if they
+/// hadn't finished yet. `which` selects the animations by key.
+fn remove_animations(env: &mut Environment, layer: id, which: impl Fn(Option<&str>) -> bool) {
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(layer);
+    let (to_remove, to_keep): (Vec<_>, Vec<_>) = std::mem::take(&mut host_obj.animations)
+        .into_iter()
+        .partition(|animation| match &animation.explicit {
+            Some((key, _)) => which(key.as_deref()),
+            None => false,
+        });
+    host_obj.animations = to_keep;
+    for animation in to_remove {
+        let Animation {
+            explicit: Some((_, object)),
+            stopped,
+            ..
+        } = animation
+        else {
+            unreachable!()
+        };
+        if !stopped {
+            ca_animation::animation_did_stop(env, object, /* finished: */ false);
+        }
+        release(env, object);
+    }
+}
@@ -0,0 +1,414 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAShapeLayer`.
+//!
+//! The path is drawn into the layer's bitmap, which is redrawn whenever what
+//! should be displayed changes, including while properties are animated.
+
+use super::animation::{AnimatableProperty, AnimatableValue};
+use super::ca_layer::{self, add_implicit_animation, CALayerHostObject};
+use super::frame_clock;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::cg_color::{self, CGColorRef, CGColorRelease, CGColorRetain};
+use crate::frameworks::core_graphics::cg_context::{
+    self, CGContextAddLineToPoint, CGContextBeginPath, CGContextClearRect, CGContextEOFillPath,
+    CGContextFillPath, CGContextMoveToPoint, CGContextRef, CGContextRestoreGState,
+    CGContextSaveGState, CGContextSetLineWidth, CGContextSetRGBFillColor,
+    CGContextSetRGBStrokeColor, CGContextStrokePath, CGContextTranslateCTM,
+};
+use crate::frameworks::core_graphics::cg_path::{
+    self, CGPathRef, CGPathRelease, CGPathRetain, PathElement,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::ns_string;
+use crate::objc::{id, msg_super, nil, objc_classes, ClassExports};
+use crate::Environment;
+use std::rc::Rc;
+use std::time::Instant;
+
+pub const kCALineCapButt: &str = "butt";
+pub const kCALineCapRound: &str = "round";
+pub const kCALineCapSquare: &str = "square";
+pub const kCALineJoinMiter: &str = "miter";
+pub const kCALineJoinRound: &str = "round";
+pub const kCALineJoinBevel: &str = "bevel";
+pub const kCAFillRuleNonZero: &str = "non-zero";
+pub const kCAFillRuleEvenOdd: &str = "even-odd";
+
+pub const CONSTANTS: ConstantExports = &[
+    ("_kCALineCapButt", HostConstant::NSString(kCALineCapButt)),
+    ("_kCALineCapRound", HostConstant::NSString(kCALineCapRound)),
+    (
+        "_kCALineCapSquare",
+        HostConstant::NSString(kCALineCapSquare),
+    ),
+    (
+        "_kCALineJoinMiter",
+        HostConstant::NSString(kCALineJoinMiter),
+    ),
+    (
+        "_kCALineJoinRound",
+        HostConstant::NSString(kCALineJoinRound),
+    ),
+    (
+        "_kCALineJoinBevel",
+        HostConstant::NSString(kCALineJoinBevel),
+    ),
+    (
+        "_kCAFillRuleNonZero",
+        HostConstant::NSString(kCAFillRuleNonZero),
+    ),
+    (
+        "_kCAFillRuleEvenOdd",
+        HostConstant::NSString(kCAFillRuleEvenOdd),
+    ),
+];
+
+/// The properties of a `CAShapeLayer`, stored in its [CALayerHostObject].
+pub(super) struct ShapeLayerProperties {
+    /// Possibly null. This is a strong reference.
+    path: CGPathRef,
+    /// The elements of `path` at the time it was set. This is what gets drawn
+    /// and animated.
+    pub(super) path_elements: Rc<Vec<PathElement>>,
+    /// Possibly nil. This is a strong reference.
+    fill_color: CGColorRef,
+    /// Possibly nil. This is a strong reference.
+    stroke_color: CGColorRef,
+    pub(super) line_width: CGFloat,
+    pub(super) stroke_start: CGFloat,
+    pub(super) stroke_end: CGFloat,
+    // TODO: Caps and joins are always drawn round, because that's all that
+    // CGContext stroking supports.
+    line_cap: &'static str,
+    line_join: &'static str,
+    miter_limit: CGFloat,
+    fill_rule: &'static str,
+    /// What was last drawn into the layer's bitmap.
+    drawn: Option<ShapeDrawing>,
+}
+
+/// Everything that affects what a `CAShapeLayer` looks like, with animated
+/// properties at their presentation values.
+#[derive(PartialEq)]
+struct ShapeDrawing {
+    bounds: CGRect,
+    path: Rc<Vec<PathElement>>,
+    fill_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    stroke_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    line_width: CGFloat,
+    stroke_start: CGFloat,
+    stroke_end: CGFloat,
+    even_odd: bool,
+}
+
+fn shape(env: &Environment, layer: id) -> &ShapeLayerProperties {
+    env.objc
+        .borrow::<CALayerHostObject>(layer)
+        .shape
+        .as_deref()
+        .unwrap()
+}
+fn shape_mut(env: &mut Environment, layer: id) -> &mut ShapeLayerProperties {
+    env.objc
+        .borrow_mut::<CALayerHostObject>(layer)
+        .shape
+        .as_deref_mut()
+        .unwrap()
+}
+
+/// Convert a string property to one of its known values, or `default` if it
+/// isn't one of them.
+fn known_value(
+    env: &mut Environment,
+    value: id, // NSString*
+    known: &[&'static str],
+    default: &'static str,
+) -> &'static str {
+    let value = ns_string::to_rust_string(env, value);
+    known
+        .iter()
+        .copied()
+        .find(|&known| known == value)
+        .unwrap_or_else(|| {
+            log!("Warning: unknown CAShapeLayer value {:?}, ignoring", value);
+            default
+        })
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAShapeLayer: CALayer
+
+- (id)init {
+    let this: id = msg_super![env; this init];
+    // The default fill color is opaque black.
+    let fill_color = cg_color::from_rgba(env, (0.0, 0.0, 0.0, 1.0));
+    env.objc.borrow_mut::<CALayerHostObject>(this).shape = Some(Box::new(ShapeLayerProperties {
+        path: nil,
+        path_elements: Default::default(),
+        fill_color,
+        stroke_color: nil,
+        line_width: 1.0,
+        stroke_start: 0.0,
+        stroke_end: 1.0,
+        line_cap: kCALineCapButt,
+        line_join: kCALineJoinMiter,
+        miter_limit: 10.0,
+        fill_rule: kCAFillRuleNonZero,
+        drawn: None,
+    }));
+    this
+}
+
+- (())dealloc {
+    if let Some(shape) = env.objc.borrow_mut::<CALayerHostObject>(this).shape.take() {
+        CGPathRelease(env, shape.path);
+        CGColorRelease(env, shape.fill_color);
+        CGColorRelease(env, shape.stroke_color);
+    }
+    msg_super![env; this dealloc]
+}
+
+- (CGPathRef)path {
+    shape(env, this).path
+}
+- (())setPath:(CGPathRef)path {
+    add_implicit_animation(env, this, AnimatableProperty::Path);
+    CGPathRetain(env, path);
+    let path_elements = Rc::new(cg_path::elements(env, path));
+    let shape = shape_mut(env, this);
+    shape.path_elements = path_elements;
+    let old_path = std::mem::replace(&mut shape.path, path);
+    CGPathRelease(env, old_path);
+}
+
+- (CGColorRef)fillColor {
+    shape(env, this).fill_color
+}
+- (())setFillColor:(CGColorRef)new_color {
+    CGColorRetain(env, new_color);
+    let old_color = std::mem::replace(&mut shape_mut(env, this).fill_color, new_color);
+    CGColorRelease(env, old_color);
+}
+
+- (CGColorRef)strokeColor {
+    shape(env, this).stroke_color
+}
+- (())setStrokeColor:(CGColorRef)new_color {
+    CGColorRetain(env, new_color);
+    let old_color = std::mem::replace(&mut shape_mut(env, this).stroke_color, new_color);
+    CGColorRelease(env, old_color);
+}
+
+- (CGFloat)lineWidth {
+    shape(env, this).line_width
+}
+- (())setLineWidth:(CGFloat)line_width {
+    add_implicit_animation(env, this, AnimatableProperty::LineWidth);
+    shape_mut(env, this).line_width = line_width;
+}
+
+- (CGFloat)strokeStart {
+    shape(env, this).stroke_start
+}
+- (())setStrokeStart:(CGFloat)stroke_start {
+    add_implicit_animation(env, this, AnimatableProperty::StrokeStart);
+    shape_mut(env, this).stroke_start = stroke_start;
+}
+- (CGFloat)strokeEnd {
+    shape(env, this).stroke_end
+}
+- (())setStrokeEnd:(CGFloat)stroke_end {
+    add_implicit_animation(env, this, AnimatableProperty::StrokeEnd);
+    shape_mut(env, this).stroke_end = stroke_end;
+}
+
+- (id)lineCap {
+    let line_cap = shape(env, this).line_cap;
+    ns_string::get_static_str(env, line_cap)
+}
+- (())setLineCap:(id)line_cap { // NSString*
+    let known = [kCALineCapButt, kCALineCapRound, kCALineCapSquare];
+    let line_cap = known_value(env, line_cap, &known, kCALineCapButt);
+    shape_mut(env, this).line_cap = line_cap;
+}
+
+- (id)lineJoin {
+    let line_join = shape(env, this).line_join;
+    ns_string::get_static_str(env, line_join)
+}
+- (())setLineJoin:(id)line_join { // NSString*
+    let known = [kCALineJoinMiter, kCALineJoinRound, kCALineJoinBevel];
+    let line_join = known_value(env, line_join, &known, kCALineJoinMiter);
+    shape_mut(env, this).line_join = line_join;
+}
+
+- (CGFloat)miterLimit {
+    shape(env, this).miter_limit
+}
+- (())setMiterLimit:(CGFloat)miter_limit {
+    shape_mut(env, this).miter_limit = miter_limit;
+}
+
+- (id)fillRule {
+    let fill_rule = shape(env, this).fill_rule;
+    ns_string::get_static_str(env, fill_rule)
+}
+- (())setFillRule:(id)fill_rule { // NSString*
+    let known = [kCAFillRuleNonZero, kCAFillRuleEvenOdd];
+    let fill_rule = known_value(env, fill_rule, &known, kCAFillRuleNonZero);
+    shape_mut(env, this).fill_rule = fill_rule;
+}
+
+// The compositor calls this for every frame, not just when the layer has been
+// marked as needing display, so this decides for itself whether to redraw.
+- (())displayIfNeeded {
+    let now = frame_clock::now(env);
+    let drawing = current_drawing(env, this, now);
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let forced = std::mem::take(&mut host_obj.needs_display);
+    let shape = host_obj.shape.as_deref_mut().unwrap();
+    if !forced && shape.drawn.as_ref() == Some(&drawing) {
+        return;
+    }
+    shape.drawn = None;
+
+    let size = drawing.bounds.size;
+    if size.width.round() < 1.0 || size.height.round() < 1.0 {
+        // Nothing can be seen, and there's no bitmap to draw into.
+        return;
+    }
+    let context = ca_layer::prepare_bitmap(env, this);
+    draw_shape(env, context, &drawing);
+    shape_mut(env, this).drawn = Some(drawing);
+}
+
+@end
+
+};
+
+/// Get everything needed to draw a shape layer at `now`.
+fn current_drawing(env: &Environment, layer: id, now: Instant) -> ShapeDrawing {
+    let host_obj = env.objc.borrow::<CALayerHostObject>(layer);
+    let float_value = |property| {
+        let AnimatableValue::Float(value) = host_obj.presentation_value(property, now) else {
+            unreachable!()
+        };
+        value
+    };
+    let AnimatableValue::Path(path) = host_obj.presentation_value(AnimatableProperty::Path, now)
+    else {
+        unreachable!()
+    };
+    let shape = host_obj.shape.as_deref().unwrap();
+    ShapeDrawing {
+        bounds: host_obj.bounds,
+        path,
+        fill_color: ca_layer::rgba_or_transparent(&env.objc, &env.mem, shape.fill_color),
+        stroke_color: ca_layer::rgba_or_transparent(&env.objc, &env.mem, shape.stroke_color),
+        line_width: float_value(AnimatableProperty::LineWidth),
+        stroke_start: float_value(AnimatableProperty::StrokeStart),
+        stroke_end: float_value(AnimatableProperty::StrokeEnd),
+        even_odd: shape.fill_rule == kCAFillRuleEvenOdd,
+    }
+}
+
+/// Replace the contents of a shape layer's bitmap with its path.
+fn draw_shape(env: &mut Environment, context: CGContextRef, drawing: &ShapeDrawing) {
+    let &ShapeDrawing {
+        bounds,
+        ref path,
+        fill_color: (r, g, b, a),
+        stroke_color,
+        line_width,
+        stroke_start,
+        stroke_end,
+        even_odd,
+    } = drawing;
+
+    CGContextSaveGState(env, context);
+    CGContextTranslateCTM(env, context, -bounds.origin.x, -bounds.origin.y);
+    CGContextClearRect(env, context, bounds);
+
+    if a != 0.0 {
+        CGContextSetRGBFillColor(env, context, r, g, b, a);
+        CGContextBeginPath(env, context);
+        cg_context::add_path_elements(env, context, path);
+        if even_odd {
+            CGContextEOFillPath(env, context);
+        } else {
+            CGContextFillPath(env, context);
+        }
+    }
+
+    let (r, g, b, a) = stroke_color;
+    if a != 0.0 && line_width > 0.0 && stroke_end > stroke_start {
+        CGContextSetRGBStrokeColor(env, context, r, g, b, a);
+        CGContextSetLineWidth(env, context, line_width);
+        CGContextBeginPath(env, context);
+        let polylines = trim_polylines(cg_path::to_polygons(path), stroke_start, stroke_end);
+        for polyline in polylines {
+            let CGPoint { x, y } = polyline[0];
+            CGContextMoveToPoint(env, context, x, y);
+            for &CGPoint { x, y } in &polyline[1..] {
+                CGContextAddLineToPoint(env, context, x, y);
+            }
+        }
+        CGContextStrokePath(env, context);
+    }
+
+    CGContextRestoreGState(env, context);
+}
+
+/// Cut polylines down to the part between `start` and `end`, which are
+/// fractions of their total length.
+fn trim_polylines(polylines: Vec<Vec<CGPoint>>, start: CGFloat, end: CGFloat) -> Vec<Vec<CGPoint>> {
+    let (start, end) = (start.clamp(0.0, 1.0), end.clamp(0.0, 1.0));
+    if start == 0.0 && end == 1.0 {
+        return polylines;
+    }
+
+    let distance = |a: CGPoint, b: CGPoint| ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+    let total_length: CGFloat = polylines
+        .iter()
+        .flat_map(|polyline| polyline.windows(2))
+        .map(|segment| distance(segment[0], segment[1]))
+        .sum();
+    let (start, end) = (start * total_length, end * total_length);
+
+    let mut trimmed_polylines = Vec::new();
+    let mut length_so_far = 0.0;
+    for polyline in polylines {
+        let mut trimmed = Vec::new();
+        for segment in polyline.windows(2) {
+            let (a, b) = (segment[0], segment[1]);
+            let length = distance(a, b);
+            let (segment_start, segment_end) = (length_so_far, length_so_far + length);
+            length_so_far = segment_end;
+            if length == 0.0 || segment_end < start || segment_start > end {
+                continue;
+            }
+            let point_at = |at: CGFloat| {
+                let t = (at - segment_start) / length;
+                CGPoint {
+                    x: a.x + (b.x - a.x) * t,
+                    y: a.y + (b.y - a.y) * t,
+                }
+            };
+            if trimmed.is_empty() {
+                trimmed.push(point_at(start.max(segment_start)));
+            }
+            trimmed.push(point_at(end.min(segment_end)));
+        }
+        if trimmed.len() > 1 {
+            trimmed_polylines.push(trimmed);
+        }
+    }
+    trimmed_polylines
+}
@@ -0,0 +1,154 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CATransaction`.
+//!
+//! Changes to layers are applied immediately, rather than when the
+//! transaction is committed. Transactions only control implicit animations.
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::frameworks::foundation::ns_string;
+use crate::objc::{id, msg, msg_class, nil, objc_classes, release, ClassExports};
+use crate::Environment;
+
+pub const kCATransactionAnimationDuration: &str = "animationDuration";
+pub const kCATransactionDisableActions: &str = "disableActions";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCATransactionAnimationDuration",
+        HostConstant::NSString(kCATransactionAnimationDuration),
+    ),
+    (
+        "_kCATransactionDisableActions",
+        HostConstant::NSString(kCATransactionDisableActions),
+    ),
+];
+
+/// Duration of implicit animations if no transaction overrides it.
+const DEFAULT_ANIMATION_DURATION: CFTimeInterval = 0.25;
+
+#[derive(Default)]
+pub(super) struct State {
+    /// Explicit transactions that have begun but not been committed, from
+    /// outermost to innermost.
+    stack: Vec<Transaction>,
+}
+
+struct Transaction {
+    /// `NSMutableDictionary*` of all the values set on this transaction,
+    /// including the ones with their own accessors.
+    values: id,
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CATransaction: NSObject
+
++ (())begin {
+    let values: id = msg_class![env; NSMutableDictionary new];
+    env.framework_state
+        .core_animation
+        .ca_transaction
+        .stack
+        .push(Transaction { values });
+}
+
++ (())commit {
+    let Some(transaction) = env.framework_state.core_animation.ca_transaction.stack.pop() else {
+        log!("Warning: [CATransaction commit] without matching begin, ignoring");
+        return;
+    };
+    release(env, transaction.values);
+}
+
++ (())flush {
+    // Changes are already applied immediately.
+}
+
++ (id)valueForKey:(id)key { // NSString*
+    // Values set on inner transactions override those on outer transactions.
+    let values: Vec<id> = env
+        .framework_state
+        .core_animation
+        .ca_transaction
+        .stack
+        .iter()
+        .rev()
+        .map(|transaction| transaction.values)
+        .collect();
+    for values in values {
+        let value: id = msg![env; values objectForKey:key];
+        if value != nil {
+            return value;
+        }
+    }
+    nil
+}
++ (())setValue:(id)value
+        forKey:(id)key { // NSString*
+    let Some(transaction) = env.framework_state.core_animation.ca_transaction.stack.last() else {
+        log!(
+            "Warning: [CATransaction setValue:{:?} forKey:{:?}] outside a transaction, ignoring",
+            value,
+            ns_string::to_rust_string(env, key),
+        );
+        return;
+    };
+    let values = transaction.values;
+    if value == nil {
+        () = msg![env; values removeObjectForKey:key];
+    } else {
+        () = msg![env; values setObject:value forKey:key];
+    }
+}
+
++ (bool)disableActions {
+    let key = ns_string::get_static_str(env, kCATransactionDisableActions);
+    let value: id = msg![env; this valueForKey:key];
+    value != nil && msg![env; value boolValue]
+}
++ (())setDisableActions:(bool)disable {
+    let key = ns_string::get_static_str(env, kCATransactionDisableActions);
+    let value: id = msg_class![env; NSNumber numberWithBool:disable];
+    () = msg![env; this setValue:value forKey:key];
+}
+
++ (CFTimeInterval)animationDuration {
+    let key = ns_string::get_static_str(env, kCATransactionAnimationDuration);
+    let value: id = msg![env; this valueForKey:key];
+    if value == nil {
+        DEFAULT_ANIMATION_DURATION
+    } else {
+        msg![env; value doubleValue]
+    }
+}
++ (())setAnimationDuration:(CFTimeInterval)duration {
+    let key = ns_string::get_static_str(env, kCATransactionAnimationDuration);
+    let value: id = msg_class![env; NSNumber numberWithDouble:duration];
+    () = msg![env; this setValue:value forKey:key];
+}
+
+@end
+
+};
+
+/// For use by `CALayer`: get the duration that a property change should be
+/// implicitly animated over, or [None] if it shouldn't be animated.
+pub(super) fn implicit_animation_duration(env: &mut Environment) -> Option<CFTimeInterval> {
+    let disable_actions: bool = msg_class![env; CATransaction disableActions];
+    if disable_actions {
+        return None;
+    }
+    let duration: CFTimeInterval = msg_class![env; CATransaction animationDuration];
+    if duration > 0.0 {
+        Some(duration)
+    } else {
+        None
+    }
+}
@@ -9,19 +9,26 @@
 //! I haven't attempted to reverse-engineer the details. As such, it probably
 //! diverges wildly from what the real iPhone OS does.
 
+use super::animation::{AnimatableProperty, AnimatableValue};
 use super::ca_eagl_layer::find_fullscreen_eagl_layer;
-use super::ca_layer::CALayerHostObject;
+use super::ca_layer::{self, CALayerHostObject};
+use super::frame_clock;
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
 use crate::frameworks::core_graphics::{
     cg_bitmap_context, cg_image, CGFloat, CGPoint, CGRect, CGSize,
 };
-use crate::frameworks::uikit::ui_color;
+use crate::frameworks::uikit::ui_status_bar::{self, StatusBar, StatusBarImage};
 use crate::gles::gles11_raw as gles11; // constants only
 use crate::gles::gles11_raw::types::*;
 use crate::gles::present::{present_frame, FpsCounter};
 use crate::gles::GLES;
 use crate::mem::Mem;
-use crate::objc::{id, msg, msg_class, nil, ObjC};
+use crate::objc::{id, msg, msg_class, nil, release, retain, ObjC};
+use crate::window::DeviceOrientation;
 use crate::Environment;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
 #[derive(Default)]
@@ -29,6 +36,7 @@ pub(super) struct State {
     texture_framebuffer: Option<(GLuint, GLuint)>,
     recomposite_next: Option<Instant>,
     fps_counter: Option<FpsCounter>,
+    status_bar_texture: Option<(GLuint, Rc<StatusBarImage>)>,
 }
 
 /// For use by `NSRunLoop`: call this 60 times per second. Composites the app's
@@ -68,7 +76,7 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             .count_frame(format_args!("Core Animation compositor"));
     }
 
-    let now = Instant::now();
+    let wall_now = Instant::now();
     let interval = 1.0 / 60.0; // 60Hz
     let new_recomposite_next = if let Some(recomposite_next) = env
         .framework_state
@@ -76,13 +84,16 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
         .composition
         .recomposite_next
     {
-        if recomposite_next > now {
-            log_dbg!("Not recompositing yet, wait {:?}", recomposite_next - now);
+        if recomposite_next > wall_now {
+            log_dbg!(
+                "Not recompositing yet, wait {:?}",
+                recomposite_next - wall_now
+            );
             return Some(recomposite_next);
         }
 
         // See NSTimer implementation for a discussion of what this does.
-        let overdue_by = now.duration_since(recomposite_next);
+        let overdue_by = wall_now.duration_since(recomposite_next);
         log_dbg!("Recompositing, overdue by {:?}", overdue_by);
         // TODO: Use `.div_duration_f64()` once that is stabilized.
         let advance_by = (overdue_by.as_secs_f64() / interval).max(1.0).ceil();
@@ -96,18 +107,28 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             .unwrap();
         Some(recomposite_next.checked_add(advance_by).unwrap())
     } else {
-        Some(now.checked_add(Duration::from_secs_f64(interval)).unwrap())
+        Some(
+            wall_now
+                .checked_add(Duration::from_secs_f64(interval))
+                .unwrap(),
+        )
     };
     env.framework_state
         .core_animation
         .composition
         .recomposite_next = new_recomposite_next;
 
+    // Everything in this frame is drawn as of the same animation clock time.
+    let now = frame_clock::frame_presented(env);
+
     let root_layer: id = msg![env; top_window layer];
 
     // Ensure layer bitmaps are up to date.
     display_layers(env, root_layer);
 
+    // Animations that have finished by now shouldn't be drawn.
+    stop_finished_animations(env, root_layer, now);
+
     let screen_bounds: CGRect = {
         let screen: id = msg_class![env; UIScreen mainScreen];
         msg![env; screen bounds]
@@ -115,18 +136,23 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
     let scale_hack: u32 = env.options.scale_hack.get();
     let fb_width = screen_bounds.size.width as u32 * scale_hack;
     let fb_height = screen_bounds.size.height as u32 * scale_hack;
+    env.debug_overlay.begin_present(wall_now);
+    env.debug_overlay
+        .update_stats(&env.objc, &env.mem, wall_now);
+
     let present_frame_args = (
         env.window().viewport(),
         env.window().rotation_matrix(),
+        env.window().scaling_filter(),
         env.window().virtual_cursor_visible_at(),
     );
 
-    // TODO: draw status bar if it's not hidden
+    let status_bar = ui_status_bar::current(env, scale_hack);
 
     // Initial state for layer tree traversal (see composite_layer_recursive)
-    let origin = CGPoint { x: 0.0, y: 0.0 };
+    let transform = CGAffineTransformIdentity;
     let clip_to = CGRect {
-        origin,
+        origin: CGPoint { x: 0.0, y: 0.0 },
         size: screen_bounds.size,
     };
     let opacity = 1.0;
@@ -215,14 +241,33 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             &mut env.objc,
             &env.mem,
             root_layer,
-            origin,
+            transform,
             clip_to,
             opacity,
             scale_hack,
+            fb_width,
             fb_height,
+            now,
         );
     }
 
+    // The status bar is drawn on top of everything else
+    if let Some(status_bar) = status_bar {
+        unsafe {
+            draw_status_bar(
+                gles,
+                &mut env
+                    .framework_state
+                    .core_animation
+                    .composition
+                    .status_bar_texture,
+                status_bar,
+                scale_hack,
+                fb_height,
+            );
+        }
+    }
+
     // Clean up some GL state
     unsafe {
         gles.Viewport(0, 0, fb_width as _, fb_height as _);
@@ -242,16 +287,19 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             present_frame_args.0,
             present_frame_args.1,
             present_frame_args.2,
+            present_frame_args.3,
+            env.debug_overlay.image(),
         );
     }
     env.window().swap_window();
+    env.debug_overlay.end_present(Instant::now());
 
     new_recomposite_next
 }
 
 /// Call `displayIfNeeded` on all relevant layers in the tree, so their bitmaps
 /// are up to date before compositing.
-fn display_layers(env: &mut Environment, root_layer: id) {
+pub fn display_layers(env: &mut Environment, root_layer: id) {
     // Tell layers to redraw themselves if needed.
 
     fn traverse(objc: &ObjC, layer: id, layers_needing_display: &mut Vec<id>) {
@@ -259,7 +307,9 @@ fn display_layers(env: &mut Environment, root_layer: id) {
         if host_obj.hidden {
             return;
         }
-        if host_obj.needs_display {
+        // Shape and gradient layers decide for themselves whether they need
+        // redrawing, because their properties can change what they look like.
+        if host_obj.needs_display || host_obj.shape.is_some() || host_obj.gradient.is_some() {
             layers_needing_display.push(layer);
         }
         for &layer in &host_obj.sublayers {
@@ -275,19 +325,49 @@ fn display_layers(env: &mut Environment, root_layer: id) {
     }
 }
 
-/// Traverses the layer tree and draws each layer.
+/// Remove finished animations from all layers in the tree and notify their
+/// delegates.
+fn stop_finished_animations(env: &mut Environment, root_layer: id, now: Instant) {
+    fn traverse(objc: &ObjC, layer: id, animated_layers: &mut Vec<id>) {
+        let host_obj = objc.borrow::<CALayerHostObject>(layer);
+        if !host_obj.animations.is_empty() {
+            animated_layers.push(layer);
+        }
+        for &layer in &host_obj.sublayers {
+            traverse(objc, layer, animated_layers);
+        }
+    }
+
+    let mut animated_layers = Vec::new();
+    traverse(&env.objc, root_layer, &mut animated_layers);
+
+    // Delegates might remove layers from the tree, so they need to be kept
+    // alive until we're done.
+    for &layer in &animated_layers {
+        retain(env, layer);
+    }
+    for layer in animated_layers {
+        ca_layer::stop_finished_animations(env, layer, now);
+        release(env, layer);
+    }
+}
+
+/// Traverses the layer tree and draws each layer. `transform` is the transform
+/// from the superlayer's co-ordinate space to the screen's.
 unsafe fn composite_layer_recursive(
     gles: &mut dyn GLES,
     objc: &mut ObjC,
     mem: &Mem,
     layer: id,
-    origin: CGPoint,
+    transform: CGAffineTransform,
     clip_to: CGRect,
     opacity: CGFloat,
     scale_hack: u32,
+    fb_width: u32,
     fb_height: u32,
+    now: Instant,
 ) {
-    // TODO: this can't handle zPosition, non-AABB layer transforms, rounded
+    // TODO: this can't handle zPosition, clipping sublayers to rounded
     // corners, and many other things, but none of these are supported yet :)
     // TODO: back-to-front drawing is not efficient, could we use front-to-back?
 
@@ -297,31 +377,123 @@ unsafe fn composite_layer_recursive(
         return;
     }
 
-    let opacity = opacity * host_obj.opacity;
-    let bounds = host_obj.bounds;
-    let absolute_frame = {
-        let position = host_obj.position;
-        let anchor_point = host_obj.anchor_point;
-        CGRect {
-            origin: CGPoint {
-                x: origin.x + position.x - bounds.size.width * anchor_point.x,
-                y: origin.y + position.y - bounds.size.height * anchor_point.y,
-            },
-            size: bounds.size,
-        }
+    let AnimatableValue::Float(layer_opacity) =
+        host_obj.presentation_value(AnimatableProperty::Opacity, now)
+    else {
+        unreachable!()
+    };
+    let opacity = opacity * layer_opacity;
+    let AnimatableValue::Rect(bounds) =
+        host_obj.presentation_value(AnimatableProperty::Bounds, now)
+    else {
+        unreachable!()
     };
+    let to_screen = host_obj
+        .presentation_transform_to_superlayer(now)
+        .concat(transform);
+    let corner_radius = host_obj.corner_radius.max(0.0);
+    // Layers that are only translated and scaled can be drawn with a simple
+    // viewport and scissor rectangle. Anything else (e.g. rotation or rounded
+    // corners) needs the layer's outline to be transformed.
+    let axis_aligned = corner_radius == 0.0
+        && to_screen.b == 0.0
+        && to_screen.c == 0.0
+        && to_screen.a > 0.0
+        && to_screen.d > 0.0;
+    let absolute_frame = to_screen.apply_to_rect(bounds);
     let absolute_frame_clipped = clip_rects(clip_to, absolute_frame);
+    // The background always has rounded corners, but the contents only do if
+    // they are masked.
+    let background_shape = rounded_rect_triangles(bounds, corner_radius);
+    let contents_shape = if host_obj.masks_to_bounds {
+        background_shape.clone()
+    } else {
+        rounded_rect_triangles(bounds, 0.0)
+    };
+    let (background_vertices, contents_vertices) = if axis_aligned {
+        (UNIT_QUAD_VERTICES.to_vec(), UNIT_QUAD_VERTICES.to_vec())
+    } else {
+        (
+            transformed_vertices(
+                &background_shape,
+                to_screen,
+                scale_hack,
+                fb_width,
+                fb_height,
+            ),
+            transformed_vertices(&contents_shape, to_screen, scale_hack, fb_width, fb_height),
+        )
+    };
+
+    let background_color = host_obj.presentation_background_color(objc, mem, now);
+
+    // Draw shadow, if any. See render_in_context() in ca_layer.rs for the
+    // limitations of this approximation.
+    let (r, g, b, a) = ca_layer::rgba_or_black(objc, mem, host_obj.shadow_color);
+    let a = a * host_obj.shadow_opacity * opacity;
+    let has_content = background_color.3 != 0.0
+        || host_obj.presented_pixels.is_some()
+        || host_obj.contents != nil
+        || host_obj.cg_context.is_some();
+    if a != 0.0 && has_content && !host_obj.masks_to_bounds {
+        let (insets, step_alpha) = ca_layer::shadow_steps(host_obj.shadow_radius, a);
+        for inset in insets {
+            let mut rect = ca_layer::inset_rect(bounds, inset);
+            rect.origin.x += host_obj.shadow_offset.width;
+            rect.origin.y += host_obj.shadow_offset.height;
+            let shape = rounded_rect_triangles(rect, (corner_radius - inset).max(0.0));
+            let vertices = transformed_vertices(&shape, to_screen, scale_hack, fb_width, fb_height);
+            draw_solid_triangles(
+                gles,
+                &vertices,
+                (r, g, b, step_alpha),
+                clip_to,
+                scale_hack,
+                fb_width,
+                fb_height,
+            );
+        }
+    }
 
     // Draw background color, if any
-    let have_background = if host_obj.background_color == nil {
-        false
-    } else {
-        let (r, g, b, a) = ui_color::get_rgba(objc, host_obj.background_color);
-        // TODO: fully support alpha transparency for backgrounds
+    let have_background = {
+        let (r, g, b, a) = background_color;
         if a == 0.0 || opacity == 0.0 {
             false
+        } else if !axis_aligned {
+            // glClear() can only fill rectangles, so draw triangles instead.
+            draw_solid_triangles(
+                gles,
+                &background_vertices,
+                (r, g, b, a * opacity),
+                clip_to,
+                scale_hack,
+                fb_width,
+                fb_height,
+            );
+            true
+        } else if a * opacity != 1.0 {
+            // glClear() doesn't blend, so a translucent background has to be
+            // drawn as triangles too.
+            let vertices = transformed_vertices(
+                &background_shape,
+                to_screen,
+                scale_hack,
+                fb_width,
+                fb_height,
+            );
+            draw_solid_triangles(
+                gles,
+                &vertices,
+                (r, g, b, a * opacity),
+                clip_to,
+                scale_hack,
+                fb_width,
+                fb_height,
+            );
+            true
         } else {
-            gles.ClearColor(r * opacity, g * opacity, b * opacity, a * opacity);
+            gles.ClearColor(r, g, b, a);
             let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
             gles.Scissor(x, y, w, h);
             gles.Clear(gles11::COLOR_BUFFER_BIT);
@@ -405,30 +577,50 @@ unsafe fn composite_layer_recursive(
             gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
         }
 
-        let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
-        gles.Scissor(x, y, w, h);
-        gles.Viewport(x, y, w, h);
+        if axis_aligned {
+            let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
+            gles.Scissor(x, y, w, h);
+            gles.Viewport(x, y, w, h);
+        } else {
+            let (x, y, w, h) = gl_rect_from_cg_rect(clip_to, scale_hack, fb_height);
+            gles.Scissor(x, y, w, h);
+            gles.Viewport(0, 0, fb_width as _, fb_height as _);
+        }
 
         gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
-        let vertices: [f32; 12] = [
-            -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
-        ];
         gles.EnableClientState(gles11::VERTEX_ARRAY);
-        gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+        gles.VertexPointer(
+            2,
+            gles11::FLOAT,
+            0,
+            contents_vertices.as_ptr() as *const GLvoid,
+        );
 
         // Normal images will have top-to-bottom row order, but OpenGL ES
         // expects bottom-to-top, so flip the UVs in that case.
-        let tex_coords: [f32; 12] = if host_obj.contents != nil {
-            [0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0]
-        } else {
-            [0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0]
-        };
+        let flip = host_obj.contents != nil;
+        let tex_coords: Vec<f32> = contents_shape
+            .iter()
+            .flat_map(|&point| {
+                let u = (point.x - bounds.origin.x) / bounds.size.width;
+                let v = (point.y - bounds.origin.y) / bounds.size.height;
+                [u, if flip { v } else { 1.0 - v }]
+            })
+            .collect();
         gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
         gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
         gles.Enable(gles11::TEXTURE_2D);
-        gles.DrawArrays(gles11::TRIANGLES, 0, 6);
+        gles.DrawArrays(gles11::TRIANGLES, 0, contents_shape.len() as _);
     }
 
+    // Sublayers of a rotated layer are clipped to its bounding box, which is
+    // an approximation.
+    let clip_to = if host_obj.masks_to_bounds {
+        absolute_frame_clipped
+    } else {
+        clip_to
+    };
+
     // avoid holding mutable borrow while recursing
     let sublayers = std::mem::take(&mut host_obj.sublayers);
     for &child_layer in &sublayers {
@@ -437,19 +629,119 @@ unsafe fn composite_layer_recursive(
             objc,
             mem,
             child_layer,
-            /* origin: */
-            CGPoint {
-                x: absolute_frame.origin.x - bounds.origin.x,
-                y: absolute_frame.origin.y - bounds.origin.y,
-            },
-            // TODO: clipping goes here (when masksToBounds is implemented)
+            /* transform: */ to_screen,
             clip_to,
             opacity,
             scale_hack,
+            fb_width,
             fb_height,
+            now,
         )
     }
     objc.borrow_mut::<CALayerHostObject>(layer).sublayers = sublayers;
+
+    // Draw border, if any. It's inside the bounds, on top of the sublayers.
+    let host_obj = objc.borrow::<CALayerHostObject>(layer);
+    let (r, g, b, a) = ca_layer::rgba_or_black(objc, mem, host_obj.border_color);
+    if host_obj.border_width > 0.0 && a != 0.0 && opacity != 0.0 {
+        let shape = rounded_rect_ring_triangles(bounds, corner_radius, host_obj.border_width);
+        let vertices = transformed_vertices(&shape, to_screen, scale_hack, fb_width, fb_height);
+        draw_solid_triangles(
+            gles,
+            &vertices,
+            (r, g, b, a * opacity),
+            clip_to,
+            scale_hack,
+            fb_width,
+            fb_height,
+        );
+    }
+}
+
+/// Draw triangles (in normalized device co-ordinates) in a solid color, which
+/// has straight (non-premultiplied) alpha.
+unsafe fn draw_solid_triangles(
+    gles: &mut dyn GLES,
+    vertices: &[f32],
+    (r, g, b, a): (CGFloat, CGFloat, CGFloat, CGFloat),
+    clip_to: CGRect,
+    scale_hack: u32,
+    fb_width: u32,
+    fb_height: u32,
+) {
+    let (x, y, w, h) = gl_rect_from_cg_rect(clip_to, scale_hack, fb_height);
+    gles.Scissor(x, y, w, h);
+    gles.Viewport(0, 0, fb_width as _, fb_height as _);
+    gles.Enable(gles11::BLEND);
+    gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
+    gles.Color4f(r * a, g * a, b * a, a);
+    gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+    gles.EnableClientState(gles11::VERTEX_ARRAY);
+    gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+    gles.DisableClientState(gles11::TEXTURE_COORD_ARRAY);
+    gles.Disable(gles11::TEXTURE_2D);
+    gles.DrawArrays(gles11::TRIANGLES, 0, (vertices.len() / 2) as _);
+}
+
+/// Draw the status bar, uploading its image to a texture if it has changed.
+unsafe fn draw_status_bar(
+    gles: &mut dyn GLES,
+    texture: &mut Option<(GLuint, Rc<StatusBarImage>)>,
+    status_bar: StatusBar,
+    scale_hack: u32,
+    fb_height: u32,
+) {
+    let StatusBar {
+        frame,
+        rotation,
+        image,
+    } = status_bar;
+
+    match texture {
+        Some((texture, uploaded)) => {
+            gles.BindTexture(gles11::TEXTURE_2D, *texture);
+            if !Rc::ptr_eq(uploaded, &image) {
+                upload_rgba8_pixels(gles, &image.pixels, (image.width, image.height));
+                *uploaded = image;
+            }
+        }
+        None => {
+            let mut new_texture = 0;
+            gles.GenTextures(1, &mut new_texture);
+            gles.BindTexture(gles11::TEXTURE_2D, new_texture);
+            upload_rgba8_pixels(gles, &image.pixels, (image.width, image.height));
+            *texture = Some((new_texture, image));
+        }
+    }
+
+    let (x, y, w, h) = gl_rect_from_cg_rect(frame, scale_hack, fb_height);
+    gles.Scissor(x, y, w, h);
+    gles.Viewport(x, y, w, h);
+    gles.Color4f(1.0, 1.0, 1.0, 1.0);
+    gles.Enable(gles11::BLEND);
+    gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
+
+    let vertices: [f32; 12] = [
+        -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
+    ];
+    // The image has top-to-bottom row order, and its top is wherever the top
+    // of the interface is, so in landscape it has to be rotated.
+    let tex_coords: [f32; 12] = match rotation {
+        DeviceOrientation::Portrait => [0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+        DeviceOrientation::LandscapeLeft => {
+            [1.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+        }
+        DeviceOrientation::LandscapeRight => {
+            [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0]
+        }
+    };
+    gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+    gles.EnableClientState(gles11::VERTEX_ARRAY);
+    gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+    gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
+    gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
+    gles.Enable(gles11::TEXTURE_2D);
+    gles.DrawArrays(gles11::TRIANGLES, 0, 6);
 }
 
 unsafe fn upload_rgba8_pixels(gles: &mut dyn GLES, pixels: &[u8], dimensions: (u32, u32)) {
@@ -500,6 +792,106 @@ fn clip_rects(a_clip: CGRect, b_clip: CGRect) -> CGRect {
     }
 }
 
+/// Vertices for a quad covering the whole viewport, in the same order as
+/// [rounded_rect_triangles] uses for a rectangle without rounded corners.
+const UNIT_QUAD_VERTICES: [f32; 12] = [
+    -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
+];
+
+/// Number of line segments used to approximate each rounded corner.
+const CORNER_SEGMENTS: u32 = 8;
+
+/// Get the outline of a rectangle with rounded corners, going clockwise from
+/// the left end of the top-left corner. The number of points doesn't depend
+/// on the radius, so two outlines can be joined up to make a ring.
+fn rounded_rect_outline(rect: CGRect, radius: CGFloat) -> Vec<CGPoint> {
+    use std::f32::consts::FRAC_PI_2;
+
+    let radius = radius
+        .min(rect.size.width / 2.0)
+        .min(rect.size.height / 2.0)
+        .max(0.0);
+    let (x1, y1) = (rect.origin.x + radius, rect.origin.y + radius);
+    let (x2, y2) = (
+        rect.origin.x + rect.size.width - radius,
+        rect.origin.y + rect.size.height - radius,
+    );
+    // y points down, so increasing angles go clockwise.
+    let centers = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)];
+    let mut outline = Vec::with_capacity(centers.len() * (CORNER_SEGMENTS as usize + 1));
+    for (i, &(x, y)) in centers.iter().enumerate() {
+        let start_angle = (i as CGFloat + 2.0) * FRAC_PI_2;
+        for j in 0..=CORNER_SEGMENTS {
+            let angle = start_angle + FRAC_PI_2 * j as CGFloat / CORNER_SEGMENTS as CGFloat;
+            outline.push(CGPoint {
+                x: x + radius * angle.cos(),
+                y: y + radius * angle.sin(),
+            });
+        }
+    }
+    outline
+}
+
+/// Get triangles covering a rectangle with rounded corners. If there are no
+/// rounded corners, this is the quad used when compositing, in the same order
+/// as [UNIT_QUAD_VERTICES].
+fn rounded_rect_triangles(rect: CGRect, radius: CGFloat) -> Vec<CGPoint> {
+    if radius <= 0.0 {
+        let (x1, y1) = (rect.origin.x, rect.origin.y);
+        let (x2, y2) = (x1 + rect.size.width, y1 + rect.size.height);
+        // y points up in OpenGL ES, but down in UIKit and Core Animation, so
+        // the bottom-left corner is (x1, y2).
+        return [(x1, y2), (x1, y1), (x2, y2), (x2, y2), (x1, y1), (x2, y1)]
+            .map(|(x, y)| CGPoint { x, y })
+            .to_vec();
+    }
+    let outline = rounded_rect_outline(rect, radius);
+    let center = CGPoint {
+        x: rect.origin.x + rect.size.width / 2.0,
+        y: rect.origin.y + rect.size.height / 2.0,
+    };
+    let mut triangles = Vec::with_capacity(outline.len() * 3);
+    for (i, &point) in outline.iter().enumerate() {
+        let next = outline[(i + 1) % outline.len()];
+        triangles.extend_from_slice(&[center, point, next]);
+    }
+    triangles
+}
+
+/// Get triangles covering the inside edge of a rectangle with rounded
+/// corners, `width` wide, as used for borders.
+fn rounded_rect_ring_triangles(rect: CGRect, radius: CGFloat, width: CGFloat) -> Vec<CGPoint> {
+    let outer = rounded_rect_outline(rect, radius);
+    let inner = rounded_rect_outline(ca_layer::inset_rect(rect, width), (radius - width).max(0.0));
+    let mut triangles = Vec::with_capacity(outer.len() * 6);
+    for i in 0..outer.len() {
+        let j = (i + 1) % outer.len();
+        triangles.extend_from_slice(&[outer[i], outer[j], inner[i], inner[i], outer[j], inner[j]]);
+    }
+    triangles
+}
+
+/// Transform points in a layer's co-ordinate space to normalized device
+/// co-ordinates.
+fn transformed_vertices(
+    points: &[CGPoint],
+    to_screen: CGAffineTransform,
+    scale_hack: u32,
+    fb_width: u32,
+    fb_height: u32,
+) -> Vec<f32> {
+    points
+        .iter()
+        .flat_map(|&point| {
+            let CGPoint { x, y } = to_screen.apply_to_point(point);
+            [
+                x * scale_hack as f32 / fb_width as f32 * 2.0 - 1.0,
+                1.0 - y * scale_hack as f32 / fb_height as f32 * 2.0,
+            ]
+        })
+        .collect()
+}
+
 fn gl_rect_from_cg_rect(
     rect: CGRect,
     scale_hack: u32,
@@ -0,0 +1,263 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The clock that drives layer animations.
+//!
+//! Rather than sampling the wall clock whenever an animation is evaluated, the
+//! clock is advanced once per presented frame (by the compositor or by
+//! `presentRenderbuffer:`), so everything drawn in a frame sees the same time,
+//! and a frame that takes unusually long (e.g. because the host is under load)
+//! doesn't make animations jump ahead.
+//!
+//! When no frame has been presented for longer than a frame interval, e.g.
+//! because the app has no visible window or only presents when a timer fires,
+//! the clock follows the wall clock so that animations and timers still
+//! progress.
+//! In headless mode, where nothing can be presented, the run loop simulates
+//! frames at 60Hz instead, so that things counted in frames (like input replay,
+//! see [crate::input_recording]) still work.
+//!
+//! Core Animation (`CAAnimation` begin times and layer presentation values) and
+//! `NSTimer` (and therefore `CFRunLoopTimer`) use this clock, so timers and
+//! animations advance by the same amount each frame.
+
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+/// The most a single frame can advance the clock by. Anything longer is
+/// treated as a hitch and the excess is dropped.
+pub const MAX_FRAME_DELTA: Duration = Duration::from_millis(100);
+
+/// How long the clock waits for the next frame before following the wall
+/// clock. iPhone OS apps can't present more than 60 frames per second.
+const FRAME_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub struct FrameClock {
+    /// Clock time of the most recently presented frame.
+    frame_time: Instant,
+    /// Wall-clock time at which that frame was presented.
+    frame_wall_time: Instant,
+    /// How far the clock advanced for that frame.
+    frame_delta: Duration,
+    /// The latest time given out by [FrameClock::now], so that the clock never
+    /// goes backwards.
+    latest: Instant,
+    /// Number of frames presented so far.
+    frame_count: u64,
+    /// When the next frame is due in headless mode.
+    headless_frame_next: Option<Instant>,
+}
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+impl FrameClock {
+    fn new(wall_time: Instant) -> Self {
+        FrameClock {
+            frame_time: wall_time,
+            frame_wall_time: wall_time,
+            frame_delta: Duration::ZERO,
+            latest: wall_time,
+            frame_count: 0,
+            headless_frame_next: None,
+        }
+    }
+
+    /// Advance the clock for a newly presented frame. Returns the clock time
+    /// for that frame.
+    pub fn frame_presented(&mut self, wall_time: Instant) -> Instant {
+        let elapsed = wall_time.saturating_duration_since(self.frame_wall_time);
+        let new_frame_time = (self.frame_time + elapsed.min(MAX_FRAME_DELTA)).max(self.latest);
+        self.frame_delta = new_frame_time.duration_since(self.frame_time);
+        self.frame_time = new_frame_time;
+        self.frame_wall_time = wall_time;
+        self.latest = new_frame_time;
+        self.frame_count += 1;
+        new_frame_time
+    }
+
+    /// Get the current clock time. While frames are being presented, this is
+    /// the time of the most recent frame.
+    pub fn now(&mut self, wall_time: Instant) -> Instant {
+        let elapsed = wall_time.saturating_duration_since(self.frame_wall_time);
+        let now = if elapsed < FRAME_INTERVAL {
+            self.frame_time
+        } else {
+            self.frame_time + elapsed
+        };
+        self.latest = self.latest.max(now);
+        self.latest
+    }
+
+    /// How far the clock advanced for the most recently presented frame.
+    pub fn frame_delta(&self) -> Duration {
+        self.frame_delta
+    }
+
+    /// Get the latest wall-clock time at which the clock will have reached
+    /// `clock_time`, for timing sources that need to sleep until then. It may
+    /// get there sooner if a frame is presented in the meantime.
+    pub fn wall_time_for(&self, clock_time: Instant) -> Instant {
+        let wall_due = self.frame_wall_time + clock_time.saturating_duration_since(self.frame_time);
+        // While the clock is waiting for the next frame, it doesn't advance.
+        wall_due.max(self.frame_wall_time + FRAME_INTERVAL)
+    }
+}
+
+/// Shortcut for timing sources: get the current animation clock time.
+pub fn now(env: &mut Environment) -> Instant {
+    env.framework_state
+        .core_animation
+        .frame_clock
+        .now(Instant::now())
+}
+
+/// Shortcut for timing sources: get how far the animation clock advanced for
+/// the most recently presented frame.
+pub fn frame_delta(env: &mut Environment) -> Duration {
+    env.framework_state.core_animation.frame_clock.frame_delta()
+}
+
+/// Shortcut for timing sources that need to sleep: see
+/// [FrameClock::wall_time_for].
+pub fn wall_time_for(env: &mut Environment, clock_time: Instant) -> Instant {
+    env.framework_state
+        .core_animation
+        .frame_clock
+        .wall_time_for(clock_time)
+}
+
+/// Get the number of frames presented so far.
+pub fn frame_count(env: &Environment) -> u64 {
+    env.framework_state.core_animation.frame_clock.frame_count
+}
+
+/// For use by `NSRunLoop` in headless mode: call this 60 times per second.
+/// Advances the clock as if a frame had been presented, if one is due.
+///
+/// Returns the time the next frame is due.
+pub fn headless_frame_if_necessary(env: &mut Environment) -> Option<Instant> {
+    let interval = FRAME_INTERVAL;
+    let wall_now = Instant::now();
+    let clock = &mut env.framework_state.core_animation.frame_clock;
+    if let Some(next) = clock.headless_frame_next {
+        if next > wall_now {
+            return Some(next);
+        }
+    }
+    // Unlike the compositor, this doesn't try to keep to a fixed schedule:
+    // nobody sees these frames, so skipping some doesn't matter.
+    let next = wall_now + interval;
+    clock.headless_frame_next = Some(next);
+    clock.frame_presented(wall_now);
+    Some(next)
+}
+
+/// For use when presenting a frame: advance the animation clock. Returns the
+/// clock time for the frame.
+pub fn frame_presented(env: &mut Environment) -> Instant {
+    env.framework_state
+        .core_animation
+        .frame_clock
+        .frame_presented(Instant::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::animation::{
+        presentation_value, AnimatableProperty, AnimatableValue, Animation,
+    };
+    use super::*;
+
+    #[test]
+    fn animation_tracks_frame_time() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new(start);
+        let animations = [Animation::implicit(
+            AnimatableProperty::Opacity,
+            AnimatableValue::Float(0.0),
+            clock.now(start),
+            1.0,
+        )];
+        let opacity = |now| {
+            let AnimatableValue::Float(opacity) = presentation_value(
+                &animations,
+                AnimatableProperty::Opacity,
+                AnimatableValue::Float(1.0),
+                now,
+            ) else {
+                unreachable!()
+            };
+            opacity
+        };
+
+        // Frames of varying length, including a long one which only counts
+        // for MAX_FRAME_DELTA.
+        let mut wall_time = start;
+        let mut frame_time = Duration::ZERO;
+        for frame_ms in [16, 20, 12, 500, 16, 33] {
+            wall_time += Duration::from_millis(frame_ms);
+            let now = clock.frame_presented(wall_time);
+            frame_time += Duration::from_millis(frame_ms).min(MAX_FRAME_DELTA);
+            assert_eq!(
+                clock.frame_delta(),
+                Duration::from_millis(frame_ms).min(MAX_FRAME_DELTA)
+            );
+            assert_eq!(now, start + frame_time);
+            let expected = frame_time.as_secs_f64() as f32;
+            assert!((opacity(now) - expected).abs() < 0.0001);
+        }
+        assert_eq!(clock.frame_count, 6);
+        assert!(wall_time.duration_since(start) > Duration::from_millis(500));
+        assert!(frame_time < Duration::from_millis(250));
+
+        // Between frames, the time of the last frame is used.
+        assert_eq!(
+            clock.now(wall_time + Duration::from_millis(10)),
+            start + frame_time
+        );
+    }
+
+    #[test]
+    fn follows_wall_clock_without_frames() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new(start);
+        let later = start + Duration::from_millis(500);
+        assert_eq!(clock.now(later), later);
+        // The clock never goes backwards, even if the next frame would be
+        // treated as a hitch.
+        assert_eq!(
+            clock.frame_presented(later + Duration::from_millis(10)),
+            later
+        );
+        assert!(clock.now(later + Duration::from_millis(20)) >= later);
+    }
+
+    #[test]
+    fn timer_due_time_tracks_frames() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new(start);
+        let wall_time = start + Duration::from_millis(16);
+        clock.frame_presented(wall_time);
+        let frame_time = clock.now(wall_time);
+
+        // Something due 20ms of clock time after a frame is due 20ms of wall
+        // time after it, whether or not the next frame arrives in time.
+        let due = frame_time + Duration::from_millis(20);
+        assert_eq!(
+            clock.wall_time_for(due),
+            wall_time + Duration::from_millis(20)
+        );
+        assert!(clock.now(wall_time + Duration::from_millis(10)) < due);
+        assert_eq!(clock.now(wall_time + Duration::from_millis(20)), due);
+
+        // Something due sooner than the next frame has to wait for it, or for
+        // the clock to give up waiting.
+        let due = frame_time + Duration::from_millis(5);
+        assert_eq!(clock.wall_time_for(due), wall_time + FRAME_INTERVAL);
+        assert!(clock.now(wall_time + FRAME_INTERVAL) >= due);
+    }
+}
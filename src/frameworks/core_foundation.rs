@@ -21,8 +21,10 @@ pub mod cf_array;
 pub mod cf_bundle;
 pub mod cf_data;
 pub mod cf_dictionary;
+pub mod cf_preferences;
 pub mod cf_run_loop;
 pub mod cf_run_loop_timer;
+pub mod cf_stream;
 pub mod cf_string;
 pub mod cf_type;
 pub mod cf_url;
@@ -7,30 +7,76 @@
 //!
 //! These are toll-free bridged to `NSArray` and `NSMutableArray` in Apple's
 //! implementation. Here they are the same types.
+//!
+//! Only two kinds of callbacks are supported: `NULL`, meaning the values are
+//! arbitrary pointers that aren't retained or released, and anything else,
+//! which is treated like `kCFTypeArrayCallBacks`. The functions in a custom
+//! callbacks struct are never called.
 
 use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
 use super::CFIndex;
-use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::NSUInteger;
-use crate::mem::ConstVoidPtr;
-use crate::objc::{id, msg, msg_class};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_array, NSUInteger};
+use crate::mem::{ConstPtr, ConstVoidPtr, Mem, Ptr, SafeRead};
+use crate::objc::{id, msg, msg_class, retain};
 use crate::Environment;
 
-#[allow(dead_code)]
 pub type CFArrayRef = super::CFTypeRef;
 pub type CFMutableArrayRef = super::CFTypeRef;
 
+#[allow(dead_code, non_snake_case)] // the fields are never read
+#[repr(C, packed)]
+struct CFArrayCallBacks {
+    version: CFIndex,
+    retain: ConstVoidPtr,
+    release: ConstVoidPtr,
+    copyDescription: ConstVoidPtr,
+    equal: ConstVoidPtr,
+}
+unsafe impl SafeRead for CFArrayCallBacks {}
+
+fn CFArrayCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    values: ConstPtr<ConstVoidPtr>,
+    num_values: CFIndex,
+    callbacks: ConstPtr<CFArrayCallBacks>,
+) -> CFArrayRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    let values: Vec<id> = (0..num_values.try_into().unwrap())
+        .map(|i| env.mem.read(values + i).cast().cast_mut())
+        .collect();
+    if callbacks.is_null() {
+        // There's no immutable non-retaining variant, but this is
+        // indistinguishable as long as nobody tries to mutate it.
+        let array: id = msg_class![env; _touchHLE_NSMutableArray_non_retaining new];
+        for value in values {
+            () = msg![env; array addObject:value];
+        }
+        array
+    } else {
+        for &value in &values {
+            retain(env, value);
+        }
+        ns_array::from_vec(env, values)
+    }
+}
+
 fn CFArrayCreateMutable(
     env: &mut Environment,
     allocator: CFAllocatorRef,
     capacity: CFIndex,
-    callbacks: ConstVoidPtr, // TODO, should be `const CFArrayCallBacks*`
+    callbacks: ConstPtr<CFArrayCallBacks>,
 ) -> CFMutableArrayRef {
     assert!(allocator == kCFAllocatorDefault); // unimplemented
     assert!(capacity == 0); // TODO: fixed capacity support
-    assert!(callbacks.is_null()); // TODO: support retaining etc
 
-    msg_class![env; _touchHLE_NSMutableArray_non_retaining new]
+    if callbacks.is_null() {
+        msg_class![env; _touchHLE_NSMutableArray_non_retaining new]
+    } else {
+        msg_class![env; NSMutableArray new]
+    }
 }
 
 fn CFArrayGetCount(env: &mut Environment, array: CFArrayRef) -> CFIndex {
@@ -54,7 +100,28 @@ fn CFArrayRemoveValueAtIndex(env: &mut Environment, array: CFMutableArrayRef, id
     msg![env; array removeObjectAtIndex:idx]
 }
 
+/// Creates a callbacks struct for a constant like `kCFTypeArrayCallBacks`.
+/// Since only the pointer's nullness matters to the functions here, the
+/// function pointers are left `NULL`.
+fn alloc_callbacks_constant(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(CFArrayCallBacks {
+        version: 0,
+        retain: Ptr::null(),
+        release: Ptr::null(),
+        copyDescription: Ptr::null(),
+        equal: Ptr::null(),
+    })
+    .cast()
+    .cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCFTypeArrayCallBacks",
+    HostConstant::Custom(alloc_callbacks_constant),
+)];
+
 pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFArrayCreate(_, _, _, _)),
     export_c_func!(CFArrayCreateMutable(_, _, _)),
     export_c_func!(CFArrayGetCount(_)),
     export_c_func!(CFArrayGetValueAtIndex(_, _)),
@@ -9,8 +9,10 @@
 //! but here it is the same type.
 
 use super::cf_array::CFArrayRef;
+use super::cf_dictionary::CFDictionaryRef;
 use super::cf_string::CFStringRef;
 use super::cf_url::CFURLRef;
+use super::CFTypeRef;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::foundation::ns_bundle::NSBundleHostObject;
 use crate::frameworks::foundation::{ns_array, ns_string, NSUInteger};
@@ -23,6 +25,22 @@ fn CFBundleGetMainBundle(env: &mut Environment) -> CFBundleRef {
     msg_class![env; NSBundle mainBundle]
 }
 
+fn CFBundleGetInfoDictionary(env: &mut Environment, bundle: CFBundleRef) -> CFDictionaryRef {
+    msg![env; bundle infoDictionary]
+}
+
+fn CFBundleGetValueForInfoDictionaryKey(
+    env: &mut Environment,
+    bundle: CFBundleRef,
+    key: CFStringRef,
+) -> CFTypeRef {
+    msg![env; bundle objectForInfoDictionaryKey:key]
+}
+
+fn CFBundleGetIdentifier(env: &mut Environment, bundle: CFBundleRef) -> CFStringRef {
+    msg![env; bundle bundleIdentifier]
+}
+
 fn CFBundleGetVersionNumber(env: &mut Environment, bundle: CFBundleRef) -> u32 {
     let dict: id = msg![env; bundle infoDictionary];
     let version_key: id = ns_string::get_static_str(env, "CFBundleVersion");
@@ -74,6 +92,20 @@ fn CFBundleCopyResourceURL(
     msg![env; url copy]
 }
 
+/// Used by the `CFCopyLocalizedString` family of macros.
+fn CFBundleCopyLocalizedString(
+    env: &mut Environment,
+    bundle: CFBundleRef,
+    key: CFStringRef,
+    value: CFStringRef,
+    table_name: CFStringRef,
+) -> CFStringRef {
+    let string: CFStringRef = msg![env; bundle localizedStringForKey:key
+                                                            value:value
+                                                            table:table_name];
+    retain(env, string)
+}
+
 pub fn CFBundleCopyBundleLocalizations(env: &mut Environment, bundle: CFBundleRef) -> CFArrayRef {
     let bundle_localizations = env
         .objc
@@ -140,10 +172,14 @@ pub fn CFBundleCopyPreferredLocalizationsFromArray(
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFBundleGetMainBundle()),
+    export_c_func!(CFBundleGetInfoDictionary(_)),
+    export_c_func!(CFBundleGetValueForInfoDictionaryKey(_, _)),
+    export_c_func!(CFBundleGetIdentifier(_)),
     export_c_func!(CFBundleGetVersionNumber(_)),
     export_c_func!(CFBundleCopyBundleURL(_)),
     export_c_func!(CFBundleCopyResourcesDirectoryURL(_)),
     export_c_func!(CFBundleCopyResourceURL(_, _, _, _)),
+    export_c_func!(CFBundleCopyLocalizedString(_, _, _, _)),
     export_c_func!(CFBundleCopyBundleLocalizations(_)),
     export_c_func!(CFBundleCopyPreferredLocalizationsFromArray(_)),
 ];
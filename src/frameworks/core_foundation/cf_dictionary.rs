@@ -7,7 +7,182 @@
 //!
 //! These are toll-free bridged to `NSDictionary` and `NSMutableDictionary` in
 //! Apple's implementation. Here they are the same types.
+//!
+//! Keys and values must be Objective-C objects and are always retained, as if
+//! `kCFTypeDictionaryKeyCallBacks` and `kCFTypeDictionaryValueCallBacks` were
+//! used. Keys are also copied, like with `NSDictionary`. The functions in a
+//! custom callbacks struct are never called.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::CFIndex;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_dictionary, NSUInteger};
+use crate::mem::{ConstPtr, ConstVoidPtr, Mem, Ptr, SafeRead};
+use crate::objc::{id, msg, msg_class};
+use crate::Environment;
 
 pub type CFDictionaryRef = super::CFTypeRef;
-#[allow(dead_code)]
 pub type CFMutableDictionaryRef = super::CFTypeRef;
+
+#[allow(dead_code, non_snake_case)] // the fields are never read
+#[repr(C, packed)]
+struct CFDictionaryKeyCallBacks {
+    version: CFIndex,
+    retain: ConstVoidPtr,
+    release: ConstVoidPtr,
+    copyDescription: ConstVoidPtr,
+    equal: ConstVoidPtr,
+    hash: ConstVoidPtr,
+}
+unsafe impl SafeRead for CFDictionaryKeyCallBacks {}
+
+#[allow(dead_code, non_snake_case)] // the fields are never read
+#[repr(C, packed)]
+struct CFDictionaryValueCallBacks {
+    version: CFIndex,
+    retain: ConstVoidPtr,
+    release: ConstVoidPtr,
+    copyDescription: ConstVoidPtr,
+    equal: ConstVoidPtr,
+}
+unsafe impl SafeRead for CFDictionaryValueCallBacks {}
+
+fn check_callbacks(
+    key_callbacks: ConstPtr<CFDictionaryKeyCallBacks>,
+    value_callbacks: ConstPtr<CFDictionaryValueCallBacks>,
+) {
+    // TODO: support non-retaining dictionaries. This would need keys to be
+    // hashed and compared by pointer rather than with `hash` and `isEqual:`.
+    assert!(!key_callbacks.is_null());
+    assert!(!value_callbacks.is_null());
+}
+
+fn CFDictionaryCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    keys: ConstPtr<ConstVoidPtr>,
+    values: ConstPtr<ConstVoidPtr>,
+    num_values: CFIndex,
+    key_callbacks: ConstPtr<CFDictionaryKeyCallBacks>,
+    value_callbacks: ConstPtr<CFDictionaryValueCallBacks>,
+) -> CFDictionaryRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    check_callbacks(key_callbacks, value_callbacks);
+
+    let keys_and_values: Vec<(id, id)> = (0..num_values.try_into().unwrap())
+        .map(|i| {
+            let key: id = env.mem.read(keys + i).cast().cast_mut();
+            let value: id = env.mem.read(values + i).cast().cast_mut();
+            (key, value)
+        })
+        .collect();
+    ns_dictionary::dict_from_keys_and_objects(env, &keys_and_values)
+}
+
+fn CFDictionaryCreateMutable(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    capacity: CFIndex,
+    key_callbacks: ConstPtr<CFDictionaryKeyCallBacks>,
+    value_callbacks: ConstPtr<CFDictionaryValueCallBacks>,
+) -> CFMutableDictionaryRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    assert!(capacity == 0); // TODO: fixed capacity support
+    check_callbacks(key_callbacks, value_callbacks);
+
+    msg_class![env; NSMutableDictionary new]
+}
+
+fn CFDictionaryGetCount(env: &mut Environment, dict: CFDictionaryRef) -> CFIndex {
+    let count: NSUInteger = msg![env; dict count];
+    count.try_into().unwrap()
+}
+
+fn CFDictionaryGetValue(
+    env: &mut Environment,
+    dict: CFDictionaryRef,
+    key: ConstVoidPtr,
+) -> ConstVoidPtr {
+    let key: id = key.cast().cast_mut();
+    let value: id = msg![env; dict objectForKey:key];
+    value.cast().cast_const()
+}
+
+fn CFDictionaryContainsKey(
+    env: &mut Environment,
+    dict: CFDictionaryRef,
+    key: ConstVoidPtr,
+) -> bool {
+    !CFDictionaryGetValue(env, dict, key).is_null()
+}
+
+fn CFDictionarySetValue(
+    env: &mut Environment,
+    dict: CFMutableDictionaryRef,
+    key: ConstVoidPtr,
+    value: ConstVoidPtr,
+) {
+    let key: id = key.cast().cast_mut();
+    let value: id = value.cast().cast_mut();
+    msg![env; dict setObject:value forKey:key]
+}
+
+fn CFDictionaryRemoveValue(env: &mut Environment, dict: CFMutableDictionaryRef, key: ConstVoidPtr) {
+    let key: id = key.cast().cast_mut();
+    msg![env; dict removeObjectForKey:key]
+}
+
+fn CFDictionaryRemoveAllValues(env: &mut Environment, dict: CFMutableDictionaryRef) {
+    msg![env; dict removeAllObjects]
+}
+
+// Since only the pointers' nullness matters to the functions here, the
+// function pointers in these constants are left `NULL`.
+fn alloc_key_callbacks_constant(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(CFDictionaryKeyCallBacks {
+        version: 0,
+        retain: Ptr::null(),
+        release: Ptr::null(),
+        copyDescription: Ptr::null(),
+        equal: Ptr::null(),
+        hash: Ptr::null(),
+    })
+    .cast()
+    .cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFTypeDictionaryKeyCallBacks",
+        HostConstant::Custom(alloc_key_callbacks_constant),
+    ),
+    (
+        "_kCFCopyStringDictionaryKeyCallBacks",
+        HostConstant::Custom(alloc_key_callbacks_constant),
+    ),
+    (
+        "_kCFTypeDictionaryValueCallBacks",
+        HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+            mem.alloc_and_write(CFDictionaryValueCallBacks {
+                version: 0,
+                retain: Ptr::null(),
+                release: Ptr::null(),
+                copyDescription: Ptr::null(),
+                equal: Ptr::null(),
+            })
+            .cast()
+            .cast_const()
+        }),
+    ),
+];
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFDictionaryCreate(_, _, _, _, _, _)),
+    export_c_func!(CFDictionaryCreateMutable(_, _, _, _)),
+    export_c_func!(CFDictionaryGetCount(_)),
+    export_c_func!(CFDictionaryGetValue(_, _)),
+    export_c_func!(CFDictionaryContainsKey(_, _)),
+    export_c_func!(CFDictionarySetValue(_, _, _)),
+    export_c_func!(CFDictionaryRemoveValue(_, _)),
+    export_c_func!(CFDictionaryRemoveAllValues(_)),
+];
@@ -0,0 +1,113 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFPreferences`.
+//!
+//! Only the current application's own preferences are supported. These are
+//! the same store as `NSUserDefaults`' application domain.
+
+use super::cf_array::CFArrayRef;
+use super::cf_string::CFStringRef;
+use super::CFTypeRef;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_array, ns_string, ns_user_defaults};
+use crate::objc::{id, msg, msg_class, nil, retain};
+use crate::Environment;
+
+pub const kCFPreferencesAnyApplication: &str = "kCFPreferencesAnyApplication";
+pub const kCFPreferencesCurrentApplication: &str = "kCFPreferencesCurrentApplication";
+pub const kCFPreferencesAnyHost: &str = "kCFPreferencesAnyHost";
+pub const kCFPreferencesCurrentHost: &str = "kCFPreferencesCurrentHost";
+pub const kCFPreferencesAnyUser: &str = "kCFPreferencesAnyUser";
+pub const kCFPreferencesCurrentUser: &str = "kCFPreferencesCurrentUser";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFPreferencesAnyApplication",
+        HostConstant::NSString(kCFPreferencesAnyApplication),
+    ),
+    (
+        "_kCFPreferencesCurrentApplication",
+        HostConstant::NSString(kCFPreferencesCurrentApplication),
+    ),
+    (
+        "_kCFPreferencesAnyHost",
+        HostConstant::NSString(kCFPreferencesAnyHost),
+    ),
+    (
+        "_kCFPreferencesCurrentHost",
+        HostConstant::NSString(kCFPreferencesCurrentHost),
+    ),
+    (
+        "_kCFPreferencesAnyUser",
+        HostConstant::NSString(kCFPreferencesAnyUser),
+    ),
+    (
+        "_kCFPreferencesCurrentUser",
+        HostConstant::NSString(kCFPreferencesCurrentUser),
+    ),
+];
+
+/// Check that `application_id` refers to the current application, since other
+/// applications' preferences aren't supported.
+fn check_application_id(env: &mut Environment, application_id: CFStringRef) {
+    let application_id = ns_string::to_rust_string(env, application_id);
+    if application_id != kCFPreferencesCurrentApplication
+        && application_id != env.bundle.bundle_identifier()
+    {
+        log!(
+            "Warning: CFPreferences for {:?} are not supported, using the current app's.",
+            application_id
+        );
+    }
+}
+
+fn CFPreferencesCopyAppValue(
+    env: &mut Environment,
+    key: CFStringRef,
+    application_id: CFStringRef,
+) -> CFTypeRef {
+    check_application_id(env, application_id);
+    let defaults: id = msg_class![env; NSUserDefaults standardUserDefaults];
+    let value: id = msg![env; defaults objectForKey:key];
+    retain(env, value)
+}
+
+fn CFPreferencesSetAppValue(
+    env: &mut Environment,
+    key: CFStringRef,
+    value: CFTypeRef,
+    application_id: CFStringRef,
+) {
+    check_application_id(env, application_id);
+    let app_domain = ns_user_defaults::app_domain(env);
+    if value == nil {
+        () = msg![env; app_domain removeObjectForKey:key];
+    } else {
+        () = msg![env; app_domain setObject:value forKey:key];
+    }
+}
+
+fn CFPreferencesAppSynchronize(env: &mut Environment, application_id: CFStringRef) -> bool {
+    check_application_id(env, application_id);
+    ns_user_defaults::synchronize(env)
+}
+
+fn CFPreferencesCopyApplicationList(
+    env: &mut Environment,
+    _user_name: CFStringRef,
+    _host_name: CFStringRef,
+) -> CFArrayRef {
+    let bundle_id = env.bundle.bundle_identifier().to_string();
+    let bundle_id = ns_string::from_rust_string(env, bundle_id);
+    ns_array::from_vec(env, vec![bundle_id])
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFPreferencesCopyAppValue(_, _)),
+    export_c_func!(CFPreferencesSetAppValue(_, _, _)),
+    export_c_func!(CFPreferencesAppSynchronize(_)),
+    export_c_func!(CFPreferencesCopyApplicationList(_, _)),
+];
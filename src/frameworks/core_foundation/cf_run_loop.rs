@@ -6,14 +6,91 @@
 //! `CFRunLoop`.
 //!
 //! This is not even toll-free bridged to `NSRunLoop` in Apple's implementation,
-//! but here it is the same type.
+//! but here it is the same type, so timers and sources added to either are run
+//! by the same loop.
+//!
+//! Also includes `CFRunLoopSource`, of which only version 0 sources (signalled
+//! manually by the app) are supported.
 
+use super::cf_allocator::CFAllocatorRef;
+use super::time::CFTimeInterval;
+use super::CFIndex;
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
-use crate::objc::msg_class;
+use crate::frameworks::foundation::ns_run_loop::{self, RunLoopExit};
+use crate::mem::{ConstPtr, ConstVoidPtr, SafeRead};
+use crate::objc::{id, msg_class, objc_classes, release, retain, ClassExports, HostObject};
 use crate::Environment;
+use std::time::{Duration, Instant};
 
 pub type CFRunLoopRef = super::CFTypeRef;
 pub type CFRunLoopMode = super::cf_string::CFStringRef;
+pub type CFRunLoopSourceRef = super::CFTypeRef;
+
+type CFRunLoopRunResult = i32;
+const kCFRunLoopRunStopped: CFRunLoopRunResult = 2;
+const kCFRunLoopRunTimedOut: CFRunLoopRunResult = 3;
+const kCFRunLoopRunHandledSource: CFRunLoopRunResult = 4;
+
+#[repr(C, packed)]
+struct CFRunLoopSourceContext {
+    version: CFIndex,
+    info: ConstVoidPtr,
+    /// `const void *(*retain)(const void *info)`
+    retain: GuestFunction,
+    /// `void (*release)(const void *info)`
+    release: GuestFunction,
+    copy_description: GuestFunction,
+    equal: GuestFunction,
+    hash: GuestFunction,
+    /// `void (*schedule)(void *info, CFRunLoopRef rl, CFRunLoopMode mode)`
+    schedule: GuestFunction,
+    /// `void (*cancel)(void *info, CFRunLoopRef rl, CFRunLoopMode mode)`
+    cancel: GuestFunction,
+    /// `void (*perform)(void *info)`
+    perform: GuestFunction,
+}
+unsafe impl SafeRead for CFRunLoopSourceContext {}
+
+struct CFRunLoopSourceHostObject {
+    context: CFRunLoopSourceContext,
+    /// Weak reference to the run loop the source was added to, if any, and
+    /// the mode it was added with.
+    run_loop: Option<(CFRunLoopRef, CFRunLoopMode)>,
+    signalled: bool,
+    valid: bool,
+}
+impl HostObject for CFRunLoopSourceHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFRunLoopSource is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CFRunLoopSource: NSObject
+
+- (())dealloc {
+    let release_callback = env
+        .objc
+        .borrow::<CFRunLoopSourceHostObject>(this)
+        .context
+        .release;
+    let info = env
+        .objc
+        .borrow::<CFRunLoopSourceHostObject>(this)
+        .context
+        .info;
+    if !release_callback.to_ptr().is_null() {
+        () = release_callback.call_from_host(env, (info,));
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
 
 fn CFRunLoopGetCurrent(env: &mut Environment) -> CFRunLoopRef {
     msg_class![env; NSRunLoop currentRunLoop]
@@ -23,6 +100,154 @@ pub fn CFRunLoopGetMain(env: &mut Environment) -> CFRunLoopRef {
     msg_class![env; NSRunLoop mainRunLoop]
 }
 
+fn CFRunLoopRun(env: &mut Environment) {
+    let run_loop = CFRunLoopGetCurrent(env);
+    ns_run_loop::run_run_loop(env, run_loop, /* until: */ None, false);
+}
+
+fn CFRunLoopRunInMode(
+    env: &mut Environment,
+    mode: CFRunLoopMode,
+    seconds: CFTimeInterval,
+    return_after_source_handled: bool,
+) -> CFRunLoopRunResult {
+    // TODO: handle modes
+    log_dbg!(
+        "CFRunLoopRunInMode({:?}, {}, {})",
+        mode,
+        seconds,
+        return_after_source_handled
+    );
+    let run_loop = CFRunLoopGetCurrent(env);
+    let until = Instant::now() + Duration::from_secs_f64(seconds.max(0.0));
+    match ns_run_loop::run_run_loop(env, run_loop, Some(until), return_after_source_handled) {
+        RunLoopExit::Stopped => kCFRunLoopRunStopped,
+        RunLoopExit::TimedOut => kCFRunLoopRunTimedOut,
+        RunLoopExit::HandledSource => kCFRunLoopRunHandledSource,
+    }
+}
+
+fn CFRunLoopStop(env: &mut Environment, run_loop: CFRunLoopRef) {
+    ns_run_loop::stop_run_loop(env, run_loop);
+}
+
+fn CFRunLoopWakeUp(_env: &mut Environment, _run_loop: CFRunLoopRef) {
+    // The run loop polls for work, so it doesn't need to be woken up.
+}
+
+fn CFRunLoopSourceCreate(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    _order: CFIndex,
+    context: ConstPtr<CFRunLoopSourceContext>,
+) -> CFRunLoopSourceRef {
+    let mut context = env.mem.read(context);
+    let version = context.version;
+    // Version 1 sources are based on Mach ports.
+    assert_eq!(version, 0); // TODO
+    let retain_callback = context.retain;
+    if !retain_callback.to_ptr().is_null() {
+        context.info = retain_callback.call_from_host(env, (context.info,));
+    }
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CFRunLoopSource", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CFRunLoopSourceHostObject {
+            context,
+            run_loop: None,
+            signalled: false,
+            valid: true,
+        }),
+        &mut env.mem,
+    )
+}
+
+/// Call a source's `schedule` or `cancel` callback, if it has one.
+fn call_schedule_or_cancel(
+    env: &mut Environment,
+    callback: GuestFunction,
+    info: ConstVoidPtr,
+    run_loop: CFRunLoopRef,
+    mode: CFRunLoopMode,
+) {
+    if !callback.to_ptr().is_null() {
+        () = callback.call_from_host(env, (info, run_loop, mode));
+    }
+}
+
+fn CFRunLoopAddSource(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    source: CFRunLoopSourceRef,
+    mode: CFRunLoopMode,
+) {
+    // TODO: handle modes
+    let valid = env.objc.borrow::<CFRunLoopSourceHostObject>(source).valid;
+    if !valid || !ns_run_loop::add_source(env, run_loop, source) {
+        return;
+    }
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    assert!(host_object.run_loop.is_none()); // TODO: multiple run loops
+    host_object.run_loop = Some((run_loop, mode));
+    let (schedule, info) = (host_object.context.schedule, host_object.context.info);
+    call_schedule_or_cancel(env, schedule, info, run_loop, mode);
+}
+
+fn CFRunLoopRemoveSource(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    source: CFRunLoopSourceRef,
+    mode: CFRunLoopMode,
+) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    host_object.run_loop = None;
+    let (cancel, info) = (host_object.context.cancel, host_object.context.info);
+    // Removing the source might release the last reference to it, but it's
+    // still needed for the callback.
+    retain(env, source);
+    if ns_run_loop::remove_source(env, run_loop, source) {
+        call_schedule_or_cancel(env, cancel, info, run_loop, mode);
+    }
+    release(env, source);
+}
+
+fn CFRunLoopSourceSignal(env: &mut Environment, source: CFRunLoopSourceRef) {
+    env.objc
+        .borrow_mut::<CFRunLoopSourceHostObject>(source)
+        .signalled = true;
+}
+
+fn CFRunLoopSourceInvalidate(env: &mut Environment, source: CFRunLoopSourceRef) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    host_object.valid = false;
+    if let Some((run_loop, mode)) = host_object.run_loop {
+        CFRunLoopRemoveSource(env, run_loop, source, mode);
+    }
+}
+
+fn CFRunLoopSourceIsValid(env: &mut Environment, source: CFRunLoopSourceRef) -> bool {
+    env.objc.borrow::<CFRunLoopSourceHostObject>(source).valid
+}
+
+/// For use by `NSRunLoop`: perform a source if it has been signalled.
+/// Returns [true] if it was performed.
+pub fn handle_source(env: &mut Environment, source: CFRunLoopSourceRef) -> bool {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    if !std::mem::take(&mut host_object.signalled) {
+        return false;
+    }
+    let (perform, info) = (host_object.context.perform, host_object.context.info);
+    log_dbg!("Performing run loop source {:?}", source);
+    if !perform.to_ptr().is_null() {
+        let pool: id = msg_class![env; NSAutoreleasePool new];
+        () = perform.call_from_host(env, (info,));
+        release(env, pool);
+    }
+    true
+}
+
 pub const kCFRunLoopCommonModes: &str = "kCFRunLoopCommonModes";
 pub const kCFRunLoopDefaultMode: &str = "kCFRunLoopDefaultMode";
 pub const kCFBundleExecutableKey: &str = "kCFBundleExecutableKey";
@@ -50,4 +275,14 @@ pub const CONSTANTS: ConstantExports = &[
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFRunLoopGetCurrent()),
     export_c_func!(CFRunLoopGetMain()),
+    export_c_func!(CFRunLoopRun()),
+    export_c_func!(CFRunLoopRunInMode(_, _, _)),
+    export_c_func!(CFRunLoopStop(_)),
+    export_c_func!(CFRunLoopWakeUp(_)),
+    export_c_func!(CFRunLoopSourceCreate(_, _, _)),
+    export_c_func!(CFRunLoopAddSource(_, _, _)),
+    export_c_func!(CFRunLoopRemoveSource(_, _, _)),
+    export_c_func!(CFRunLoopSourceSignal(_)),
+    export_c_func!(CFRunLoopSourceInvalidate(_)),
+    export_c_func!(CFRunLoopSourceIsValid(_)),
 ];
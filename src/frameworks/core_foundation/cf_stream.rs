@@ -0,0 +1,107 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFReadStream` and `CFWriteStream`.
+//!
+//! These are toll-free bridged to `NSInputStream` and `NSOutputStream` in
+//! Apple's implementation. Here they are the same types.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_string::CFStringRef;
+use super::CFIndex;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::foundation::ns_stream::{
+    new_socket_streams, NSStreamStatus, NSStreamStatusError,
+};
+use crate::frameworks::foundation::{ns_string, NSInteger, NSUInteger};
+use crate::mem::{ConstPtr, MutPtr};
+use crate::objc::{msg, release};
+use crate::Environment;
+
+pub type CFReadStreamRef = super::CFTypeRef;
+pub type CFWriteStreamRef = super::CFTypeRef;
+
+fn CFStreamCreatePairWithSocketToHost(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    host: CFStringRef,
+    port: u32,
+    read_stream: MutPtr<CFReadStreamRef>,
+    write_stream: MutPtr<CFWriteStreamRef>,
+) {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let host = ns_string::to_rust_string(env, host).into_owned();
+    let (input, output) = new_socket_streams(env, host, port.try_into().unwrap());
+    if read_stream.is_null() {
+        release(env, input);
+    } else {
+        env.mem.write(read_stream, input);
+    }
+    if write_stream.is_null() {
+        release(env, output);
+    } else {
+        env.mem.write(write_stream, output);
+    }
+}
+
+fn CFReadStreamOpen(env: &mut Environment, stream: CFReadStreamRef) -> bool {
+    () = msg![env; stream open];
+    stream_opened(env, stream)
+}
+fn CFWriteStreamOpen(env: &mut Environment, stream: CFWriteStreamRef) -> bool {
+    () = msg![env; stream open];
+    stream_opened(env, stream)
+}
+fn stream_opened(env: &mut Environment, stream: CFReadStreamRef) -> bool {
+    let status: NSStreamStatus = msg![env; stream streamStatus];
+    status != NSStreamStatusError
+}
+
+fn CFReadStreamClose(env: &mut Environment, stream: CFReadStreamRef) {
+    msg![env; stream close]
+}
+fn CFWriteStreamClose(env: &mut Environment, stream: CFWriteStreamRef) {
+    msg![env; stream close]
+}
+
+fn CFReadStreamRead(
+    env: &mut Environment,
+    stream: CFReadStreamRef,
+    buffer: MutPtr<u8>,
+    length: CFIndex,
+) -> CFIndex {
+    let length: NSUInteger = length.try_into().unwrap();
+    let read: NSInteger = msg![env; stream read:buffer maxLength:length];
+    read
+}
+fn CFWriteStreamWrite(
+    env: &mut Environment,
+    stream: CFWriteStreamRef,
+    buffer: ConstPtr<u8>,
+    length: CFIndex,
+) -> CFIndex {
+    let length: NSUInteger = length.try_into().unwrap();
+    let written: NSInteger = msg![env; stream write:buffer maxLength:length];
+    written
+}
+
+fn CFReadStreamHasBytesAvailable(env: &mut Environment, stream: CFReadStreamRef) -> bool {
+    msg![env; stream hasBytesAvailable]
+}
+fn CFWriteStreamCanAcceptBytes(env: &mut Environment, stream: CFWriteStreamRef) -> bool {
+    msg![env; stream hasSpaceAvailable]
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFStreamCreatePairWithSocketToHost(_, _, _, _, _)),
+    export_c_func!(CFReadStreamOpen(_)),
+    export_c_func!(CFWriteStreamOpen(_)),
+    export_c_func!(CFReadStreamClose(_)),
+    export_c_func!(CFWriteStreamClose(_)),
+    export_c_func!(CFReadStreamRead(_, _, _)),
+    export_c_func!(CFWriteStreamWrite(_, _, _)),
+    export_c_func!(CFReadStreamHasBytesAvailable(_)),
+    export_c_func!(CFWriteStreamCanAcceptBytes(_)),
+];
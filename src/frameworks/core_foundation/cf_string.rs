@@ -14,7 +14,7 @@ use crate::abi::{DotDotDot, VaList};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::{kCFNotFound, CFIndex, CFOptionFlags, CFRange};
 use crate::frameworks::foundation::{ns_string, NSNotFound, NSRange, NSUInteger};
-use crate::mem::{ConstPtr, MutPtr};
+use crate::mem::{ConstPtr, MutPtr, Ptr};
 use crate::objc::{id, msg, msg_class};
 use crate::Environment;
 
@@ -139,6 +139,37 @@ fn CFStringGetCString(
     msg![env; a getCString:buffer maxLength:buffer_size encoding:encoding]
 }
 
+fn CFStringGetCStringPtr(
+    _env: &mut Environment,
+    _string: CFStringRef,
+    _encoding: CFStringEncoding,
+) -> ConstPtr<u8> {
+    // This is allowed to fail "if the internal storage of the string does not
+    // allow this to be returned efficiently", and that is always the case
+    // here: a pointer from e.g. `UTF8String` wouldn't live as long as the
+    // string. Apps must fall back to CFStringGetCString().
+    Ptr::null()
+}
+
+fn CFStringGetLength(env: &mut Environment, string: CFStringRef) -> CFIndex {
+    let length: NSUInteger = msg![env; string length];
+    length.try_into().unwrap()
+}
+
+fn CFStringGetCharacterAtIndex(env: &mut Environment, string: CFStringRef, idx: CFIndex) -> u16 {
+    let idx: NSUInteger = idx.try_into().unwrap();
+    msg![env; string characterAtIndex:idx]
+}
+
+fn CFStringCreateCopy(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    string: CFStringRef,
+) -> CFStringRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    msg![env; string copy]
+}
+
 fn CFStringFind(
     env: &mut Environment,
     string: CFStringRef,
@@ -168,5 +199,9 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFStringCreateWithFormatAndArguments(_, _, _, _)),
     export_c_func!(CFStringCompare(_, _, _)),
     export_c_func!(CFStringGetCString(_, _, _, _)),
+    export_c_func!(CFStringGetCStringPtr(_, _)),
+    export_c_func!(CFStringGetLength(_)),
+    export_c_func!(CFStringGetCharacterAtIndex(_, _)),
+    export_c_func!(CFStringCreateCopy(_, _)),
     export_c_func!(CFStringFind(_, _, _)),
 ];
@@ -7,10 +7,10 @@
 
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::CFTypeRef;
-use crate::frameworks::foundation::NSTimeInterval;
-use crate::libc::time::{time_t, timestamp_to_calendar_date};
+use crate::frameworks::foundation::{ns_time_zone, NSTimeInterval};
+use crate::libc::time::{time_t, timestamp_to_calendar_date, wall_clock_now};
 use crate::mem::SafeRead;
-use crate::objc::nil;
+use crate::objc::{msg_class, retain};
 use crate::{impl_GuestRet_for_large_struct, Environment};
 use std::ops::Add;
 use std::time::{Duration, SystemTime};
@@ -38,28 +38,39 @@ impl_GuestRet_for_large_struct!(CFGregorianDate);
 
 /// Absolute time is measured in seconds relative to the absolute reference date
 /// of Jan 1 2001 00:00:00 GMT.
-fn CFAbsoluteTimeGetCurrent(_env: &mut Environment) -> CFAbsoluteTime {
-    SystemTime::now()
+fn CFAbsoluteTimeGetCurrent(env: &mut Environment) -> CFAbsoluteTime {
+    wall_clock_now(env)
         .duration_since(apple_epoch())
         .unwrap()
         .as_secs_f64()
 }
 
-type CFTimeZoneRef = CFTypeRef;
+/// This is toll-free bridged to `NSTimeZone`.
+pub type CFTimeZoneRef = CFTypeRef;
 
-fn CFTimeZoneCopySystem(_env: &mut Environment) -> CFTimeZoneRef {
-    // TODO: implement (nil seems to correspond to GMT)
-    nil
+fn CFTimeZoneCopySystem(env: &mut Environment) -> CFTimeZoneRef {
+    let time_zone = msg_class![env; NSTimeZone systemTimeZone];
+    retain(env, time_zone)
 }
 
+fn CFTimeZoneCopyDefault(env: &mut Environment) -> CFTimeZoneRef {
+    let time_zone = msg_class![env; NSTimeZone defaultTimeZone];
+    retain(env, time_zone)
+}
+
+/// `tz` may be `NULL`, which means GMT.
 pub fn CFAbsoluteTimeGetGregorianDate(
-    _env: &mut Environment,
+    env: &mut Environment,
     at: CFAbsoluteTime,
     tz: CFTimeZoneRef,
 ) -> CFGregorianDate {
-    assert!(tz.is_null());
+    let offset = if tz.is_null() {
+        0
+    } else {
+        ns_time_zone::seconds_from_gmt(env, tz, at)
+    };
     let time64 = apple_epoch()
-        .add(Duration::from_secs_f64(at))
+        .add(Duration::from_secs_f64(at + f64::from(offset)))
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
@@ -78,5 +89,6 @@ pub fn CFAbsoluteTimeGetGregorianDate(
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFAbsoluteTimeGetCurrent()),
     export_c_func!(CFTimeZoneCopySystem()),
+    export_c_func!(CFTimeZoneCopyDefault()),
     export_c_func!(CFAbsoluteTimeGetGregorianDate(_, _)),
 ];
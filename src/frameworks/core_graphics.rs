@@ -10,11 +10,14 @@
 
 pub mod cg_affine_transform;
 pub mod cg_bitmap_context;
+pub mod cg_color;
 pub mod cg_color_space;
 pub mod cg_context;
 pub mod cg_data_provider;
 pub mod cg_geometry;
+pub mod cg_gradient;
 pub mod cg_image;
+pub mod cg_path;
 
 pub type CGFloat = f32;
 
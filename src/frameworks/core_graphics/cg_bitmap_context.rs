@@ -5,11 +5,11 @@
  */
 //! `CGBitmapContext.h`
 
-use super::cg_affine_transform::{CGAffineTransform, CGAffineTransformIdentity};
+use super::cg_affine_transform::CGAffineTransform;
 use super::cg_color_space::{
     kCGColorSpaceGenericGray, kCGColorSpaceGenericRGB, CGColorSpaceHostObject, CGColorSpaceRef,
 };
-use super::cg_context::{CGContextHostObject, CGContextRef, CGContextSubclass};
+use super::cg_context::{CGContextGState, CGContextHostObject, CGContextRef, CGContextSubclass};
 use super::cg_image::{
     self, kCGBitmapAlphaInfoMask, kCGBitmapByteOrderMask, kCGImageAlphaFirst, kCGImageAlphaLast,
     kCGImageAlphaNone, kCGImageAlphaNoneSkipFirst, kCGImageAlphaNoneSkipLast, kCGImageAlphaOnly,
@@ -22,6 +22,7 @@ use crate::image::{gamma_decode, gamma_encode, Image};
 use crate::mem::{GuestUSize, Mem, MutVoidPtr};
 use crate::objc::ObjC;
 use crate::Environment;
+use std::rc::Rc;
 
 #[derive(Copy, Clone)]
 pub(super) struct CGBitmapContextData {
@@ -80,9 +81,9 @@ pub fn CGBitmapContextCreate(
             color_space,
             alpha_info: bitmap_info & kCGBitmapAlphaInfoMask,
         }),
-        // TODO: is this the correct default?
-        rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
-        transform: CGAffineTransformIdentity,
+        gstate: Default::default(),
+        saved_gstates: Vec::new(),
+        path: Vec::new(),
     };
     let isa = env
         .objc
@@ -351,7 +352,10 @@ fn put_pixel(
 pub struct CGBitmapContextDrawer<'a> {
     bitmap_info: CGBitmapContextData,
     rgb_fill_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    rgb_stroke_color: (CGFloat, CGFloat, CGFloat, CGFloat),
     transform: CGAffineTransform,
+    clip: Option<Rc<Vec<bool>>>,
+    alpha: CGFloat,
     pixels: &'a mut [u8],
 }
 impl CGBitmapContextDrawer<'_> {
@@ -360,18 +364,29 @@ impl CGBitmapContextDrawer<'_> {
         mem: &'a mut Mem,
         context: CGContextRef,
     ) -> CGBitmapContextDrawer<'a> {
-        let &CGContextHostObject {
+        let CGContextHostObject {
             subclass: CGContextSubclass::CGBitmapContext(bitmap_info),
-            rgb_fill_color,
-            transform,
-        } = objc.borrow(context);
+            gstate:
+                CGContextGState {
+                    rgb_fill_color,
+                    rgb_stroke_color,
+                    transform,
+                    ref clip,
+                    alpha,
+                    ..
+                },
+            ..
+        } = *objc.borrow(context);
 
         let pixels = get_pixels(&bitmap_info, mem);
 
         CGBitmapContextDrawer {
             bitmap_info,
             rgb_fill_color,
+            rgb_stroke_color,
             transform,
+            clip: clip.clone(),
+            alpha,
             pixels,
         }
     }
@@ -385,18 +400,26 @@ impl CGBitmapContextDrawer<'_> {
     /// Get the current fill color. The returned color is linear RGB, not sRGB.
     /// It has premultiplied alpha if the context does.
     pub fn rgb_fill_color(&self) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+        self.decode_color(self.rgb_fill_color)
+    }
+    /// Get the current stroke color. See [Self::rgb_fill_color].
+    pub fn rgb_stroke_color(&self) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+        self.decode_color(self.rgb_stroke_color)
+    }
+    fn decode_color(
+        &self,
+        color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    ) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
         let multiply_by = match self.bitmap_info.alpha_info {
-            kCGImageAlphaPremultipliedLast | kCGImageAlphaPremultipliedFirst => {
-                self.rgb_fill_color.3
-            }
+            kCGImageAlphaPremultipliedLast | kCGImageAlphaPremultipliedFirst => color.3,
             _ => 1.0,
         };
         // Multiplying before decoding matches the Simulator's output.
         (
-            gamma_decode(self.rgb_fill_color.0 * multiply_by),
-            gamma_decode(self.rgb_fill_color.1 * multiply_by),
-            gamma_decode(self.rgb_fill_color.2 * multiply_by),
-            self.rgb_fill_color.3, // alpha is always linear
+            gamma_decode(color.0 * multiply_by),
+            gamma_decode(color.1 * multiply_by),
+            gamma_decode(color.2 * multiply_by),
+            color.3, // alpha is always linear
         )
     }
     /// Set the pixel at `coords` to `color`. `color` must be linear RGB, not
@@ -408,6 +431,29 @@ impl CGBitmapContextDrawer<'_> {
         color: (CGFloat, CGFloat, CGFloat, CGFloat),
         blend: bool,
     ) {
+        if let Some(ref clip) = self.clip {
+            let (x, y) = coords;
+            if x < 0 || y < 0 || x as GuestUSize >= self.width() {
+                return;
+            }
+            let index = y as usize * self.width() as usize + x as usize;
+            if !clip.get(index).copied().unwrap_or(false) {
+                return;
+            }
+        }
+        let color = if blend && self.alpha != 1.0 {
+            match self.bitmap_info.alpha_info {
+                kCGImageAlphaPremultipliedLast | kCGImageAlphaPremultipliedFirst => (
+                    color.0 * self.alpha,
+                    color.1 * self.alpha,
+                    color.2 * self.alpha,
+                    color.3 * self.alpha,
+                ),
+                _ => (color.0, color.1, color.2, color.3 * self.alpha),
+            }
+        } else {
+            color
+        };
         put_pixel(&self.bitmap_info, self.pixels, coords, color, blend)
     }
 
@@ -478,7 +524,10 @@ fn test_iter_transformed_pixels() {
                 alpha_info: 0,
             },
             rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
+            rgb_stroke_color: (0.0, 0.0, 0.0, 0.0),
             transform,
+            clip: None,
+            alpha: 1.0,
             pixels: &mut [],
         }
     }
@@ -596,16 +645,11 @@ pub(super) fn draw_image(
     //  &drawer.pixels
     //);
 
-    let (image_width, image_height) = image.dimensions();
-
     // TODO: non-nearest-neighbour filtering? (what does CG actually do?)
 
-    for ((x, y), (texel_x, texel_y)) in drawer.iter_transformed_pixels(rect) {
-        let texel_x = (image_width as f32 * texel_x) as i32;
-        // Image is in top-to-bottom order, but the bitmap is bottom-to-top
-        let texel_y = (image_height as f32 * (1.0 - texel_y)) as i32;
+    for ((x, y), texel_coords) in drawer.iter_transformed_pixels(rect) {
         // FIXME: might need alpha format conversion here
-        if let Some(color) = image.get_pixel((texel_x, texel_y)) {
+        if let Some(color) = sample_image(image, texel_coords) {
             drawer.put_pixel((x, y), color, /* blend: */ true)
         }
     }
@@ -620,6 +664,228 @@ pub(super) fn draw_image(
     //);
 }
 
+/// Implementation of `CGContextDrawTiledImage` for `CGBitmapContext`. `rect`
+/// is the size and position of one tile; the tiles cover the whole context.
+pub(super) fn draw_tiled_image(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+    image: CGImageRef,
+) {
+    if rect.size.width <= 0.0 || rect.size.height <= 0.0 {
+        return;
+    }
+
+    let image = cg_image::borrow_image(&env.objc, image);
+
+    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+
+    let inverse_transform = drawer.transform.invert();
+    for y in 0..drawer.height() {
+        for x in 0..drawer.width() {
+            let untransformed = inverse_transform.apply_to_point(CGPoint {
+                x: x as f32 + 0.5,
+                y: y as f32 + 0.5,
+            });
+            let texel_coords = (
+                ((untransformed.x - rect.origin.x) / rect.size.width).rem_euclid(1.0),
+                ((untransformed.y - rect.origin.y) / rect.size.height).rem_euclid(1.0),
+            );
+            if let Some(color) = sample_image(image, texel_coords) {
+                drawer.put_pixel((x as i32, y as i32), color, /* blend: */ true)
+            }
+        }
+    }
+}
+
+/// Fill the whole clipping area using a function that gives the color (not yet
+/// gamma-decoded or premultiplied) for a point in user space, or [None] to
+/// leave that pixel alone. Used for gradients.
+pub(super) fn draw_shading<F>(env: &mut Environment, context: CGContextRef, color_at: F)
+where
+    F: Fn(CGPoint) -> Option<(CGFloat, CGFloat, CGFloat, CGFloat)>,
+{
+    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+
+    let inverse_transform = drawer.transform.invert();
+    for y in 0..drawer.height() {
+        for x in 0..drawer.width() {
+            let untransformed = inverse_transform.apply_to_point(CGPoint {
+                x: x as f32 + 0.5,
+                y: y as f32 + 0.5,
+            });
+            if let Some(color) = color_at(untransformed) {
+                let color = drawer.decode_color(color);
+                drawer.put_pixel((x as i32, y as i32), color, /* blend: */ true)
+            }
+        }
+    }
+}
+
+/// Nearest-neighbour sampling of an image, given co-ordinates from (0,0) to
+/// (1,1) with the origin in the bottom-left corner.
+fn sample_image(image: &Image, (texel_x, texel_y): (f32, f32)) -> Option<(f32, f32, f32, f32)> {
+    let (image_width, image_height) = image.dimensions();
+    let texel_x = ((image_width as f32 * texel_x) as i32).min(image_width as i32 - 1);
+    // Image is in top-to-bottom order, but the bitmap is bottom-to-top
+    let texel_y = ((image_height as f32 * (1.0 - texel_y)) as i32).min(image_height as i32 - 1);
+    image.get_pixel((texel_x, texel_y))
+}
+
+/// Implementation of `CGContextClip` and `CGContextClipToRect` for
+/// `CGBitmapContext`: intersect the clipping area with a path in device space.
+pub(super) fn clip_to_path(env: &mut Environment, context: CGContextRef, path: &[Vec<CGPoint>]) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    let CGContextSubclass::CGBitmapContext(bitmap_data) = host_obj.subclass;
+    let (width, height) = (bitmap_data.width as usize, bitmap_data.height as usize);
+    let mut clip = match host_obj.gstate.clip.take() {
+        Some(clip) => Rc::try_unwrap(clip).unwrap_or_else(|clip| (*clip).clone()),
+        None => vec![true; width * height],
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * width + x;
+            if clip[index] {
+                let center = CGPoint {
+                    x: x as CGFloat + 0.5,
+                    y: y as CGFloat + 0.5,
+                };
+                clip[index] = winding_number(path, center) != 0;
+            }
+        }
+    }
+    host_obj.gstate.clip = Some(Rc::new(clip));
+}
+
+/// Get the integer pixel bounds `(x_start, y_start, x_end, y_end)` of a path in
+/// device space, expanded by `margin` and clamped to the bitmap.
+fn path_pixel_bounds(
+    drawer: &CGBitmapContextDrawer,
+    path: &[Vec<CGPoint>],
+    margin: CGFloat,
+) -> (u32, u32, u32, u32) {
+    let points = path.iter().flatten();
+    let min_x = points
+        .clone()
+        .map(|p| p.x)
+        .fold(CGFloat::INFINITY, CGFloat::min);
+    let min_y = points
+        .clone()
+        .map(|p| p.y)
+        .fold(CGFloat::INFINITY, CGFloat::min);
+    let max_x = points
+        .clone()
+        .map(|p| p.x)
+        .fold(CGFloat::NEG_INFINITY, CGFloat::max);
+    let max_y = points
+        .map(|p| p.y)
+        .fold(CGFloat::NEG_INFINITY, CGFloat::max);
+    if min_x > max_x {
+        return (0, 0, 0, 0);
+    }
+    (
+        (min_x - margin).floor().max(0.0) as u32,
+        (min_y - margin).floor().max(0.0) as u32,
+        ((max_x + margin).ceil().max(0.0) as u32).min(drawer.width()),
+        ((max_y + margin).ceil().max(0.0) as u32).min(drawer.height()),
+    )
+}
+
+/// Implementation of `CGContextFillPath` and `CGContextEOFillPath` for
+/// `CGBitmapContext`. The path is in device space.
+pub(super) fn fill_path(
+    env: &mut Environment,
+    context: CGContextRef,
+    path: &[Vec<CGPoint>],
+    even_odd: bool,
+) {
+    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+    let color = drawer.rgb_fill_color();
+    let (x_start, y_start, x_end, y_end) = path_pixel_bounds(&drawer, path, 0.0);
+    // TODO: anti-aliasing
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let center = CGPoint {
+                x: x as CGFloat + 0.5,
+                y: y as CGFloat + 0.5,
+            };
+            let winding = winding_number(path, center);
+            let inside = if even_odd {
+                winding % 2 != 0
+            } else {
+                winding != 0
+            };
+            if inside {
+                drawer.put_pixel((x as i32, y as i32), color, /* blend: */ true);
+            }
+        }
+    }
+}
+
+/// Implementation of `CGContextStrokePath` for `CGBitmapContext`. The path is
+/// in device space, and each subpath is a polyline rather than a polygon.
+/// `line_width` is in device space too.
+pub(super) fn stroke_path(
+    env: &mut Environment,
+    context: CGContextRef,
+    path: &[Vec<CGPoint>],
+    line_width: CGFloat,
+) {
+    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+    let color = drawer.rgb_stroke_color();
+    let half_width = line_width / 2.0;
+    let (x_start, y_start, x_end, y_end) = path_pixel_bounds(&drawer, path, half_width);
+    // TODO: anti-aliasing, non-round caps and joins
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let center = CGPoint {
+                x: x as CGFloat + 0.5,
+                y: y as CGFloat + 0.5,
+            };
+            let on_line = path.iter().any(|subpath| {
+                subpath.windows(2).any(|segment| {
+                    distance_to_segment(center, segment[0], segment[1]) <= half_width
+                })
+            });
+            if on_line {
+                drawer.put_pixel((x as i32, y as i32), color, /* blend: */ true);
+            }
+        }
+    }
+}
+
+fn distance_to_segment(point: CGPoint, a: CGPoint, b: CGPoint) -> CGFloat {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point.x - a.x) * dx + (point.y - a.y) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (closest_x, closest_y) = (a.x + t * dx, a.y + t * dy);
+    ((point.x - closest_x).powi(2) + (point.y - closest_y).powi(2)).sqrt()
+}
+
+/// Non-zero winding number of a point with respect to a path made of closed
+/// polygons.
+pub(super) fn winding_number(path: &[Vec<CGPoint>], point: CGPoint) -> i32 {
+    let mut winding = 0;
+    for subpath in path {
+        for (i, &a) in subpath.iter().enumerate() {
+            let b = subpath[(i + 1) % subpath.len()];
+            let cross = (b.x - a.x) * (point.y - a.y) - (point.x - a.x) * (b.y - a.y);
+            if a.y <= point.y {
+                if b.y > point.y && cross > 0.0 {
+                    winding += 1;
+                }
+            } else if b.y <= point.y && cross < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+    winding
+}
+
 #[allow(rustdoc::broken_intra_doc_links)] // https://github.com/rust-lang/rust/issues/83049
 /// Shortcut for [crate::frameworks::core_animation::composition]. This is a
 /// workaround for not having a `&mut Environment` that should eventually be
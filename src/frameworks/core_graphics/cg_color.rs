@@ -0,0 +1,188 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGColor.h`
+
+use super::cg_color_space::{
+    kCGColorSpaceGenericGray, kCGColorSpaceGenericRGB, CGColorSpaceCreateDeviceRGB,
+    CGColorSpaceGetNumberOfComponents, CGColorSpaceHostObject, CGColorSpaceRef,
+    CGColorSpaceRelease, CGColorSpaceRetain,
+};
+use super::CGFloat;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem, MutPtr};
+use crate::objc::{objc_classes, ClassExports, HostObject, ObjC};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGColor is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not visible
+// anywhere.
+@implementation _touchHLE_CGColor: NSObject
+
+- (())dealloc {
+    let &CGColorHostObject {
+        color_space,
+        components,
+        ..
+    } = env.objc.borrow(this);
+    CGColorSpaceRelease(env, color_space);
+    env.mem.free(components.cast());
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CGColorHostObject {
+    color_space: CGColorSpaceRef,
+    /// The color components followed by alpha, in guest memory so that
+    /// [CGColorGetComponents] can return them.
+    components: MutPtr<CGFloat>,
+    /// Number of components, including alpha.
+    count: GuestUSize,
+}
+impl HostObject for CGColorHostObject {}
+
+pub type CGColorRef = CFTypeRef;
+
+pub fn CGColorCreate(
+    env: &mut Environment,
+    color_space: CGColorSpaceRef,
+    components: ConstPtr<CGFloat>,
+) -> CGColorRef {
+    let count = CGColorSpaceGetNumberOfComponents(env, color_space) + 1;
+    let components: Vec<CGFloat> = (0..count).map(|i| env.mem.read(components + i)).collect();
+    create_color(env, color_space, &components)
+}
+
+fn CGColorCreateGenericRGB(
+    env: &mut Environment,
+    red: CGFloat,
+    green: CGFloat,
+    blue: CGFloat,
+    alpha: CGFloat,
+) -> CGColorRef {
+    let color_space = CGColorSpaceCreateDeviceRGB(env);
+    let color = create_color(env, color_space, &[red, green, blue, alpha]);
+    CGColorSpaceRelease(env, color_space);
+    color
+}
+
+fn create_color(
+    env: &mut Environment,
+    color_space: CGColorSpaceRef,
+    components: &[CGFloat],
+) -> CGColorRef {
+    let count: GuestUSize = components.len().try_into().unwrap();
+    let guest_components: MutPtr<CGFloat> =
+        env.mem.alloc(count * guest_size_of::<CGFloat>()).cast();
+    for (i, &component) in components.iter().enumerate() {
+        env.mem
+            .write(guest_components + i.try_into().unwrap(), component);
+    }
+    CGColorSpaceRetain(env, color_space);
+    let isa = env.objc.get_known_class("_touchHLE_CGColor", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGColorHostObject {
+            color_space,
+            components: guest_components,
+            count,
+        }),
+        &mut env.mem,
+    )
+}
+
+/// Shortcut for host code: create a `CGColorRef` from RGBA components.
+pub fn from_rgba(
+    env: &mut Environment,
+    (r, g, b, a): (CGFloat, CGFloat, CGFloat, CGFloat),
+) -> CGColorRef {
+    CGColorCreateGenericRGB(env, r, g, b, a)
+}
+
+pub fn CGColorRetain(env: &mut Environment, color: CGColorRef) -> CGColorRef {
+    if !color.is_null() {
+        CFRetain(env, color)
+    } else {
+        color
+    }
+}
+pub fn CGColorRelease(env: &mut Environment, color: CGColorRef) {
+    if !color.is_null() {
+        CFRelease(env, color);
+    }
+}
+
+fn CGColorGetNumberOfComponents(env: &mut Environment, color: CGColorRef) -> GuestUSize {
+    env.objc.borrow::<CGColorHostObject>(color).count
+}
+fn CGColorGetComponents(env: &mut Environment, color: CGColorRef) -> ConstPtr<CGFloat> {
+    env.objc
+        .borrow::<CGColorHostObject>(color)
+        .components
+        .cast_const()
+}
+fn CGColorGetAlpha(env: &mut Environment, color: CGColorRef) -> CGFloat {
+    let &CGColorHostObject {
+        components, count, ..
+    } = env.objc.borrow(color);
+    env.mem.read(components + (count - 1))
+}
+fn CGColorGetColorSpace(env: &mut Environment, color: CGColorRef) -> CGColorSpaceRef {
+    // Not retained, this is a "Get" function.
+    env.objc.borrow::<CGColorHostObject>(color).color_space
+}
+
+fn CGColorEqualToColor(env: &mut Environment, color1: CGColorRef, color2: CGColorRef) -> bool {
+    if color1 == color2 {
+        return true;
+    }
+    if color1.is_null() || color2.is_null() {
+        return false;
+    }
+    let color_space1 = CGColorGetColorSpace(env, color1);
+    let color_space2 = CGColorGetColorSpace(env, color2);
+    env.objc.borrow::<CGColorSpaceHostObject>(color_space1).name
+        == env.objc.borrow::<CGColorSpaceHostObject>(color_space2).name
+        && components(&env.objc, &env.mem, color1) == components(&env.objc, &env.mem, color2)
+}
+
+fn components(objc: &ObjC, mem: &Mem, color: CGColorRef) -> Vec<CGFloat> {
+    let &CGColorHostObject {
+        components, count, ..
+    } = objc.borrow(color);
+    (0..count).map(|i| mem.read(components + i)).collect()
+}
+
+/// Shortcut for host code, e.g. Core Animation's compositor: get the RGBA
+/// values for a `CGColorRef`, converting from gray if necessary.
+pub fn get_rgba(objc: &ObjC, mem: &Mem, color: CGColorRef) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    let &CGColorHostObject { color_space, .. } = objc.borrow(color);
+    let components = components(objc, mem, color);
+    match objc.borrow::<CGColorSpaceHostObject>(color_space).name {
+        kCGColorSpaceGenericRGB => (components[0], components[1], components[2], components[3]),
+        kCGColorSpaceGenericGray => (components[0], components[0], components[0], components[1]),
+        _ => unimplemented!(),
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGColorCreate(_, _)),
+    export_c_func!(CGColorCreateGenericRGB(_, _, _, _)),
+    export_c_func!(CGColorRetain(_)),
+    export_c_func!(CGColorRelease(_)),
+    export_c_func!(CGColorGetNumberOfComponents(_)),
+    export_c_func!(CGColorGetComponents(_)),
+    export_c_func!(CGColorGetAlpha(_)),
+    export_c_func!(CGColorGetColorSpace(_)),
+    export_c_func!(CGColorEqualToColor(_, _)),
+];
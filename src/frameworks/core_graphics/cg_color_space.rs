@@ -9,6 +9,7 @@ use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant}
 use crate::frameworks::core_foundation::cf_string::CFStringRef;
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
 use crate::frameworks::foundation::ns_string;
+use crate::mem::GuestUSize;
 use crate::objc::{msg, objc_classes, ClassExports, HostObject};
 use crate::Environment;
 
@@ -114,6 +115,15 @@ pub fn CGColorSpaceGetModel(env: &mut Environment, cs: CGColorSpaceRef) -> CGCol
     }
 }
 
+/// Number of color components, not including alpha.
+pub fn CGColorSpaceGetNumberOfComponents(env: &mut Environment, cs: CGColorSpaceRef) -> GuestUSize {
+    match CGColorSpaceGetModel(env, cs) {
+        kCGColorSpaceModelMonochrome => 1,
+        kCGColorSpaceModelRGB => 3,
+        _ => unreachable!(),
+    }
+}
+
 pub const kCGColorSpaceGenericRGB: &str = "kCGColorSpaceGenericRGB";
 pub const kCGColorSpaceGenericGray: &str = "kCGColorSpaceGenericGray";
 
@@ -135,4 +145,5 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGColorSpaceRetain(_)),
     export_c_func!(CGColorSpaceRelease(_)),
     export_c_func!(CGColorSpaceGetModel(_)),
+    export_c_func!(CGColorSpaceGetNumberOfComponents(_)),
 ];
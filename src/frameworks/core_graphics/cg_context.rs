@@ -5,13 +5,15 @@
  */
 //! `CGContext.h`
 
-use super::cg_affine_transform::CGAffineTransform;
+use super::cg_affine_transform::{CGAffineTransform, CGAffineTransformIdentity};
 use super::cg_image::CGImageRef;
-use super::{cg_bitmap_context, CGFloat, CGRect};
+use super::cg_path::{self, CGPathRef, PathElement};
+use super::{cg_bitmap_context, CGFloat, CGPoint, CGRect};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
 use crate::objc::{objc_classes, ClassExports, HostObject};
 use crate::Environment;
+use std::rc::Rc;
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -38,11 +40,44 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 pub(super) struct CGContextHostObject {
     pub(super) subclass: CGContextSubclass,
+    pub(super) gstate: CGContextGState,
+    /// Graphics states saved by `CGContextSaveGState`.
+    pub(super) saved_gstates: Vec<CGContextGState>,
+    /// The current path, as a list of subpaths (polygons). The current
+    /// transform is applied when points are added, so these are in device
+    /// space.
+    pub(super) path: Vec<Vec<CGPoint>>,
+}
+impl HostObject for CGContextHostObject {}
+
+/// The graphics state that `CGContextSaveGState` and `CGContextRestoreGState`
+/// save and restore.
+#[derive(Clone)]
+pub(super) struct CGContextGState {
     pub(super) rgb_fill_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    pub(super) rgb_stroke_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    pub(super) line_width: CGFloat,
     /// Current transform.
     pub(super) transform: CGAffineTransform,
+    /// Clipping mask with one entry per pixel, or [None] if nothing is clipped.
+    /// This is shared between saved states, since it's rarely modified.
+    pub(super) clip: Option<Rc<Vec<bool>>>,
+    /// Global alpha, applied to everything that is drawn.
+    pub(super) alpha: CGFloat,
+}
+impl Default for CGContextGState {
+    fn default() -> Self {
+        CGContextGState {
+            // TODO: is this the correct default?
+            rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
+            rgb_stroke_color: (0.0, 0.0, 0.0, 1.0),
+            line_width: 1.0,
+            transform: CGAffineTransformIdentity,
+            clip: None,
+            alpha: 1.0,
+        }
+    }
 }
-impl HostObject for CGContextHostObject {}
 
 pub(super) enum CGContextSubclass {
     CGBitmapContext(cg_bitmap_context::CGBitmapContextData),
@@ -50,6 +85,9 @@ pub(super) enum CGContextSubclass {
 
 pub type CGContextRef = CFTypeRef;
 
+pub type CGBlendMode = i32;
+pub const kCGBlendModeNormal: CGBlendMode = 0;
+
 pub fn CGContextRelease(env: &mut Environment, c: CGContextRef) {
     if !c.is_null() {
         CFRelease(env, c);
@@ -74,6 +112,7 @@ pub fn CGContextSetRGBFillColor(
     let color = (red, green, blue, alpha);
     env.objc
         .borrow_mut::<CGContextHostObject>(context)
+        .gstate
         .rgb_fill_color = color;
 }
 
@@ -86,9 +125,220 @@ fn CGContextSetGrayFillColor(
     let color = (gray, gray, gray, alpha);
     env.objc
         .borrow_mut::<CGContextHostObject>(context)
+        .gstate
         .rgb_fill_color = color;
 }
 
+pub fn CGContextSetRGBStrokeColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    red: CGFloat,
+    green: CGFloat,
+    blue: CGFloat,
+    alpha: CGFloat,
+) {
+    let color = (red, green, blue, alpha);
+    env.objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .gstate
+        .rgb_stroke_color = color;
+}
+
+fn CGContextSetGrayStrokeColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    gray: CGFloat,
+    alpha: CGFloat,
+) {
+    let color = (gray, gray, gray, alpha);
+    env.objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .gstate
+        .rgb_stroke_color = color;
+}
+
+pub fn CGContextSetLineWidth(env: &mut Environment, context: CGContextRef, width: CGFloat) {
+    env.objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .gstate
+        .line_width = width;
+}
+
+pub fn CGContextSetAlpha(env: &mut Environment, context: CGContextRef, alpha: CGFloat) {
+    env.objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .gstate
+        .alpha = alpha.clamp(0.0, 1.0);
+}
+
+pub fn CGContextSaveGState(env: &mut Environment, context: CGContextRef) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    let gstate = host_obj.gstate.clone();
+    host_obj.saved_gstates.push(gstate);
+}
+pub fn CGContextRestoreGState(env: &mut Environment, context: CGContextRef) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    if let Some(gstate) = host_obj.saved_gstates.pop() {
+        host_obj.gstate = gstate;
+    } else {
+        log!(
+            "Warning: CGContextRestoreGState() on {:?} without a saved state, ignoring.",
+            context
+        );
+    }
+}
+
+pub fn CGContextBeginPath(env: &mut Environment, context: CGContextRef) {
+    env.objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .path
+        .clear();
+}
+pub fn CGContextMoveToPoint(env: &mut Environment, context: CGContextRef, x: CGFloat, y: CGFloat) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    let point = host_obj.gstate.transform.apply_to_point(CGPoint { x, y });
+    host_obj.path.push(vec![point]);
+}
+pub fn CGContextAddLineToPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    let point = host_obj.gstate.transform.apply_to_point(CGPoint { x, y });
+    if let Some(subpath) = host_obj.path.last_mut() {
+        subpath.push(point);
+    } else {
+        log!(
+            "Warning: CGContextAddLineToPoint() on {:?} without a current point, ignoring.",
+            context
+        );
+    }
+}
+pub fn CGContextAddCurveToPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    cp1x: CGFloat,
+    cp1y: CGFloat,
+    cp2x: CGFloat,
+    cp2y: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    let transform = host_obj.gstate.transform;
+    let Some(subpath) = host_obj.path.last_mut() else {
+        log!(
+            "Warning: CGContextAddCurveToPoint() on {:?} without a current point, ignoring.",
+            context
+        );
+        return;
+    };
+    // Flattening in device space means the curve is smooth at any scale.
+    let from = *subpath.last().unwrap();
+    let [cp1, cp2, to] = [(cp1x, cp1y), (cp2x, cp2y), (x, y)]
+        .map(|(x, y)| transform.apply_to_point(CGPoint { x, y }));
+    subpath.extend(flatten_curve(from, cp1, cp2, to));
+}
+pub fn CGContextClosePath(env: &mut Environment, context: CGContextRef) {
+    // Subpaths are always treated as closed polygons when filling, but not
+    // when stroking, so the first point is added again. A new subpath begins
+    // at the same point.
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    if let Some(subpath) = host_obj.path.last_mut() {
+        let first = subpath[0];
+        if subpath.len() > 1 {
+            subpath.push(first);
+        }
+        host_obj.path.push(vec![first]);
+    }
+}
+pub fn CGContextAddPath(env: &mut Environment, context: CGContextRef, path: CGPathRef) {
+    let elements = cg_path::elements(env, path);
+    add_path_elements(env, context, &elements);
+}
+/// Shortcut for host code: append path elements to the current path. They are
+/// transformed by the CTM, just like when using the functions above.
+pub fn add_path_elements(env: &mut Environment, context: CGContextRef, elements: &[PathElement]) {
+    for &element in elements {
+        match element {
+            PathElement::MoveTo(CGPoint { x, y }) => CGContextMoveToPoint(env, context, x, y),
+            PathElement::LineTo(CGPoint { x, y }) => CGContextAddLineToPoint(env, context, x, y),
+            PathElement::CurveTo {
+                control_point_1: cp1,
+                control_point_2: cp2,
+                to,
+            } => CGContextAddCurveToPoint(env, context, cp1.x, cp1.y, cp2.x, cp2.y, to.x, to.y),
+            PathElement::Close => CGContextClosePath(env, context),
+        }
+    }
+}
+fn CGContextAddRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    let subpath = rect_corners(rect)
+        .map(|point| host_obj.gstate.transform.apply_to_point(point))
+        .to_vec();
+    host_obj.path.push(subpath);
+}
+
+fn rect_corners(rect: CGRect) -> [CGPoint; 4] {
+    let CGRect { origin, size } = rect;
+    [
+        origin,
+        CGPoint {
+            x: origin.x + size.width,
+            y: origin.y,
+        },
+        CGPoint {
+            x: origin.x + size.width,
+            y: origin.y + size.height,
+        },
+        CGPoint {
+            x: origin.x,
+            y: origin.y + size.height,
+        },
+    ]
+}
+
+/// Intersect the clipping area with the current path, then clear the path.
+pub fn CGContextClip(env: &mut Environment, context: CGContextRef) {
+    let path = std::mem::take(&mut env.objc.borrow_mut::<CGContextHostObject>(context).path);
+    cg_bitmap_context::clip_to_path(env, context, &path);
+}
+pub fn CGContextClipToRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    let transform = env
+        .objc
+        .borrow::<CGContextHostObject>(context)
+        .gstate
+        .transform;
+    let subpath = rect_corners(rect)
+        .map(|point| transform.apply_to_point(point))
+        .to_vec();
+    cg_bitmap_context::clip_to_path(env, context, &[subpath]);
+}
+
+/// Fill the current path, then clear it.
+pub fn CGContextFillPath(env: &mut Environment, context: CGContextRef) {
+    let path = std::mem::take(&mut env.objc.borrow_mut::<CGContextHostObject>(context).path);
+    cg_bitmap_context::fill_path(env, context, &path, /* even_odd: */ false);
+}
+/// Fill the current path using the even-odd rule, then clear it.
+pub fn CGContextEOFillPath(env: &mut Environment, context: CGContextRef) {
+    let path = std::mem::take(&mut env.objc.borrow_mut::<CGContextHostObject>(context).path);
+    cg_bitmap_context::fill_path(env, context, &path, /* even_odd: */ true);
+}
+/// Stroke the current path, then clear it.
+pub fn CGContextStrokePath(env: &mut Environment, context: CGContextRef) {
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    let path = std::mem::take(&mut host_obj.path);
+    // The line width is in user space, so it has to be scaled too.
+    // TODO: non-uniform scaling
+    let CGAffineTransform { a, b, c, d, .. } = host_obj.gstate.transform;
+    let line_width = host_obj.gstate.line_width * (a * d - b * c).abs().sqrt();
+    cg_bitmap_context::stroke_path(env, context, &path, line_width);
+}
+
 pub fn CGContextFillRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
     cg_bitmap_context::fill_rect(env, context, rect, /* clear: */ false);
 }
@@ -97,6 +347,32 @@ pub fn CGContextClearRect(env: &mut Environment, context: CGContextRef, rect: CG
     cg_bitmap_context::fill_rect(env, context, rect, /* clear: */ true);
 }
 
+/// Shortcut for host code: approximate a cubic Bézier curve with line
+/// segments. The returned points don't include the starting point.
+pub fn flatten_curve(
+    from: CGPoint,
+    cp1: CGPoint,
+    cp2: CGPoint,
+    to: CGPoint,
+) -> impl Iterator<Item = CGPoint> {
+    const SEGMENTS: u16 = 16;
+    (1..=SEGMENTS).map(move |i| {
+        let t = CGFloat::from(i) / CGFloat::from(SEGMENTS);
+        let r = 1.0 - t;
+        let (w0, w1, w2, w3) = (r * r * r, 3.0 * r * r * t, 3.0 * r * t * t, t * t * t);
+        CGPoint {
+            x: w0 * from.x + w1 * cp1.x + w2 * cp2.x + w3 * to.x,
+            y: w0 * from.y + w1 * cp1.y + w2 * cp2.y + w3 * to.y,
+        }
+    })
+}
+
+/// Shortcut for host code: check whether a point is inside a path made of
+/// polygons, using the non-zero winding rule.
+pub fn path_contains_point(path: &[Vec<CGPoint>], point: CGPoint) -> bool {
+    cg_bitmap_context::winding_number(path, point) != 0
+}
+
 pub fn CGContextConcatCTM(
     env: &mut Environment,
     context: CGContextRef,
@@ -104,22 +380,26 @@ pub fn CGContextConcatCTM(
 ) {
     log_dbg!("CGContextConcatCTM({:?})", transform);
     let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
-    host_obj.transform = transform.concat(host_obj.transform);
+    host_obj.gstate.transform = transform.concat(host_obj.gstate.transform);
 }
 pub fn CGContextGetCTM(env: &mut Environment, context: CGContextRef) -> CGAffineTransform {
-    let res = env.objc.borrow::<CGContextHostObject>(context).transform;
+    let res = env
+        .objc
+        .borrow::<CGContextHostObject>(context)
+        .gstate
+        .transform;
     log_dbg!("CGContextGetCTM() => {:?}", res);
     res
 }
 pub fn CGContextRotateCTM(env: &mut Environment, context: CGContextRef, angle: CGFloat) {
     log_dbg!("CGContextRotateCTM({:?})", angle);
     let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
-    host_obj.transform = host_obj.transform.rotate(angle);
+    host_obj.gstate.transform = host_obj.gstate.transform.rotate(angle);
 }
 pub fn CGContextScaleCTM(env: &mut Environment, context: CGContextRef, x: CGFloat, y: CGFloat) {
     log_dbg!("CGContextScaleCTM({:?})", (x, y));
     let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
-    host_obj.transform = host_obj.transform.scale(x, y);
+    host_obj.gstate.transform = host_obj.gstate.transform.scale(x, y);
 }
 pub fn CGContextTranslateCTM(
     env: &mut Environment,
@@ -129,7 +409,7 @@ pub fn CGContextTranslateCTM(
 ) {
     log_dbg!("CGContextTranslateCTM({:?})", (tx, ty));
     let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
-    host_obj.transform = host_obj.transform.translate(tx, ty);
+    host_obj.gstate.transform = host_obj.gstate.transform.translate(tx, ty);
 }
 
 pub fn CGContextDrawImage(
@@ -140,12 +420,38 @@ pub fn CGContextDrawImage(
 ) {
     cg_bitmap_context::draw_image(env, context, rect, image);
 }
+pub fn CGContextDrawTiledImage(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+    image: CGImageRef,
+) {
+    cg_bitmap_context::draw_tiled_image(env, context, rect, image);
+}
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGContextRetain(_)),
     export_c_func!(CGContextRelease(_)),
     export_c_func!(CGContextSetRGBFillColor(_, _, _, _, _)),
     export_c_func!(CGContextSetGrayFillColor(_, _, _)),
+    export_c_func!(CGContextSetRGBStrokeColor(_, _, _, _, _)),
+    export_c_func!(CGContextSetGrayStrokeColor(_, _, _)),
+    export_c_func!(CGContextSetLineWidth(_, _)),
+    export_c_func!(CGContextSetAlpha(_, _)),
+    export_c_func!(CGContextSaveGState(_)),
+    export_c_func!(CGContextRestoreGState(_)),
+    export_c_func!(CGContextBeginPath(_)),
+    export_c_func!(CGContextMoveToPoint(_, _, _)),
+    export_c_func!(CGContextAddLineToPoint(_, _, _)),
+    export_c_func!(CGContextAddCurveToPoint(_, _, _, _, _, _, _)),
+    export_c_func!(CGContextClosePath(_)),
+    export_c_func!(CGContextAddPath(_, _)),
+    export_c_func!(CGContextAddRect(_, _)),
+    export_c_func!(CGContextClip(_)),
+    export_c_func!(CGContextClipToRect(_, _)),
+    export_c_func!(CGContextFillPath(_)),
+    export_c_func!(CGContextEOFillPath(_)),
+    export_c_func!(CGContextStrokePath(_)),
     export_c_func!(CGContextFillRect(_, _)),
     export_c_func!(CGContextClearRect(_, _)),
     export_c_func!(CGContextConcatCTM(_, _)),
@@ -154,4 +460,5 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGContextScaleCTM(_, _, _)),
     export_c_func!(CGContextTranslateCTM(_, _, _)),
     export_c_func!(CGContextDrawImage(_, _, _)),
+    export_c_func!(CGContextDrawTiledImage(_, _, _)),
 ];
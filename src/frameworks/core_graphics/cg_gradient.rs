@@ -0,0 +1,329 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGGradient.h`, and the gradient drawing functions from `CGContext.h`.
+
+use super::cg_color::{self, CGColorRef};
+use super::cg_color_space::{
+    kCGColorSpaceModelMonochrome, kCGColorSpaceModelRGB, CGColorSpaceGetModel,
+    CGColorSpaceGetNumberOfComponents, CGColorSpaceRef,
+};
+use super::cg_context::CGContextRef;
+use super::{cg_bitmap_context, CGFloat, CGPoint};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_array::CFArrayRef;
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::foundation::ns_array;
+use crate::mem::{ConstPtr, GuestUSize};
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGGradient is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not visible
+// anywhere.
+@implementation _touchHLE_CGGradient: NSObject
+@end
+
+};
+
+pub type Rgba = (CGFloat, CGFloat, CGFloat, CGFloat);
+
+struct CGGradientHostObject {
+    colors: Vec<Rgba>,
+    /// [None] if the colors are spread evenly.
+    locations: Option<Vec<CGFloat>>,
+}
+impl HostObject for CGGradientHostObject {}
+
+pub type CGGradientRef = CFTypeRef;
+
+pub type CGGradientDrawingOptions = u32;
+pub const kCGGradientDrawsBeforeStartLocation: CGGradientDrawingOptions = 1 << 0;
+pub const kCGGradientDrawsAfterEndLocation: CGGradientDrawingOptions = 1 << 1;
+
+fn create_gradient(
+    env: &mut Environment,
+    colors: Vec<Rgba>,
+    locations: ConstPtr<CGFloat>,
+) -> CGGradientRef {
+    let locations = (!locations.is_null()).then(|| {
+        (0..colors.len())
+            .map(|i| env.mem.read(locations + i.try_into().unwrap()))
+            .collect()
+    });
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGGradient", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGGradientHostObject { colors, locations }),
+        &mut env.mem,
+    )
+}
+
+fn CGGradientCreateWithColorComponents(
+    env: &mut Environment,
+    space: CGColorSpaceRef,
+    components: ConstPtr<CGFloat>,
+    locations: ConstPtr<CGFloat>,
+    count: GuestUSize,
+) -> CGGradientRef {
+    let model = CGColorSpaceGetModel(env, space);
+    // Each color's components are followed by alpha.
+    let stride = CGColorSpaceGetNumberOfComponents(env, space) + 1;
+    let colors = (0..count)
+        .map(|i| {
+            let color = components + i * stride;
+            let c = |j| env.mem.read(color + j);
+            match model {
+                kCGColorSpaceModelMonochrome => (c(0), c(0), c(0), c(1)),
+                kCGColorSpaceModelRGB => (c(0), c(1), c(2), c(3)),
+                _ => unimplemented!(),
+            }
+        })
+        .collect();
+    create_gradient(env, colors, locations)
+}
+
+fn CGGradientCreateWithColors(
+    env: &mut Environment,
+    _space: CGColorSpaceRef, // TODO: convert to the color space if not NULL
+    colors: CFArrayRef,
+    locations: ConstPtr<CGFloat>,
+) -> CGGradientRef {
+    let colors: Vec<CGColorRef> = ns_array::to_vec(env, colors);
+    let colors = colors
+        .into_iter()
+        .map(|color| cg_color::get_rgba(&env.objc, &env.mem, color))
+        .collect();
+    create_gradient(env, colors, locations)
+}
+
+fn CGGradientRetain(env: &mut Environment, gradient: CGGradientRef) -> CGGradientRef {
+    if !gradient.is_null() {
+        CFRetain(env, gradient)
+    } else {
+        gradient
+    }
+}
+fn CGGradientRelease(env: &mut Environment, gradient: CGGradientRef) {
+    if !gradient.is_null() {
+        CFRelease(env, gradient);
+    }
+}
+
+fn CGContextDrawLinearGradient(
+    env: &mut Environment,
+    context: CGContextRef,
+    gradient: CGGradientRef,
+    start_point: CGPoint,
+    end_point: CGPoint,
+    options: CGGradientDrawingOptions,
+) {
+    let axis = CGPoint {
+        x: end_point.x - start_point.x,
+        y: end_point.y - start_point.y,
+    };
+    let axis_length_squared = axis.x * axis.x + axis.y * axis.y;
+    draw_gradient(env, context, gradient, options, |point| {
+        if axis_length_squared == 0.0 {
+            return None;
+        }
+        // Project the point onto the gradient's axis.
+        let (px, py) = (point.x - start_point.x, point.y - start_point.y);
+        Some((px * axis.x + py * axis.y) / axis_length_squared)
+    });
+}
+
+fn CGContextDrawRadialGradient(
+    env: &mut Environment,
+    context: CGContextRef,
+    gradient: CGGradientRef,
+    start_center: CGPoint,
+    start_radius: CGFloat,
+    end_center: CGPoint,
+    end_radius: CGFloat,
+    options: CGGradientDrawingOptions,
+) {
+    let allowed = |t: CGFloat| {
+        (t >= 0.0 || options & kCGGradientDrawsBeforeStartLocation != 0)
+            && (t <= 1.0 || options & kCGGradientDrawsAfterEndLocation != 0)
+            && start_radius + t * (end_radius - start_radius) >= 0.0
+    };
+    draw_gradient(env, context, gradient, options, |point| {
+        radial_gradient_position(
+            point,
+            (start_center, start_radius),
+            (end_center, end_radius),
+            &allowed,
+        )
+    });
+}
+
+/// Find the position `t` along a radial gradient for a point, i.e. the largest
+/// `t` for which the point lies on the circle interpolated between the start
+/// (`t == 0`) and end (`t == 1`) circles, and which `allowed` accepts.
+fn radial_gradient_position(
+    point: CGPoint,
+    (start_center, start_radius): (CGPoint, CGFloat),
+    (end_center, end_radius): (CGPoint, CGFloat),
+    allowed: impl Fn(CGFloat) -> bool,
+) -> Option<CGFloat> {
+    // Solve |point - center(t)| = radius(t) for t, which is the quadratic
+    // a*t^2 - 2*b*t + c = 0.
+    let (cdx, cdy) = (end_center.x - start_center.x, end_center.y - start_center.y);
+    let dr = end_radius - start_radius;
+    let (pdx, pdy) = (point.x - start_center.x, point.y - start_center.y);
+    let a = cdx * cdx + cdy * cdy - dr * dr;
+    let b = pdx * cdx + pdy * cdy + start_radius * dr;
+    let c = pdx * pdx + pdy * pdy - start_radius * start_radius;
+
+    if a.abs() < CGFloat::EPSILON {
+        if b == 0.0 {
+            return None;
+        }
+        let t = c / (2.0 * b);
+        return allowed(t).then_some(t);
+    }
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (t1, t2) = ((b + root) / a, (b - root) / a);
+    let (larger, smaller) = if t1 > t2 { (t1, t2) } else { (t2, t1) };
+    if allowed(larger) {
+        Some(larger)
+    } else if allowed(smaller) {
+        Some(smaller)
+    } else {
+        None
+    }
+}
+
+/// Shared part of the gradient drawing functions. `position_at` maps a point
+/// in user space to a position along the gradient, where 0 is the start and 1
+/// is the end, or [None] if nothing should be drawn there.
+fn draw_gradient<F>(
+    env: &mut Environment,
+    context: CGContextRef,
+    gradient: CGGradientRef,
+    options: CGGradientDrawingOptions,
+    position_at: F,
+) where
+    F: Fn(CGPoint) -> Option<CGFloat>,
+{
+    let CGGradientHostObject { colors, locations } =
+        env.objc.borrow::<CGGradientHostObject>(gradient);
+    // The host object can't be borrowed while drawing.
+    let (colors, locations) = (colors.clone(), locations.clone());
+    let draws_before = options & kCGGradientDrawsBeforeStartLocation != 0;
+    let draws_after = options & kCGGradientDrawsAfterEndLocation != 0;
+    cg_bitmap_context::draw_shading(env, context, |point| {
+        let t = position_at(point)?;
+        if (t < 0.0 && !draws_before) || (t > 1.0 && !draws_after) {
+            return None;
+        }
+        Some(color_at(&colors, locations.as_deref(), t))
+    });
+}
+
+/// Get the color at `t` along a gradient, where 0 is the start and 1 is the
+/// end. If `locations` is missing or doesn't match `colors`, the colors are
+/// spread evenly. Outside the locations, the nearest color is used.
+pub fn color_at(colors: &[Rgba], locations: Option<&[CGFloat]>, t: CGFloat) -> Rgba {
+    match colors.len() {
+        0 => return (0.0, 0.0, 0.0, 0.0),
+        1 => return colors[0],
+        _ => (),
+    }
+    let location = |i: usize| match locations {
+        Some(locations) if locations.len() == colors.len() => locations[i],
+        _ => i as CGFloat / (colors.len() - 1) as CGFloat,
+    };
+
+    if t <= location(0) {
+        return colors[0];
+    }
+    for i in 1..colors.len() {
+        let (start, end) = (location(i - 1), location(i));
+        if t > end {
+            continue;
+        }
+        let f = if end > start {
+            (t - start) / (end - start)
+        } else {
+            1.0
+        };
+        let (a, b) = (colors[i - 1], colors[i]);
+        return (
+            a.0 + (b.0 - a.0) * f,
+            a.1 + (b.1 - a.1) * f,
+            a.2 + (b.2 - a.2) * f,
+            a.3 + (b.3 - a.3) * f,
+        );
+    }
+    colors[colors.len() - 1]
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGGradientCreateWithColorComponents(_, _, _, _)),
+    export_c_func!(CGGradientCreateWithColors(_, _, _)),
+    export_c_func!(CGGradientRetain(_)),
+    export_c_func!(CGGradientRelease(_)),
+    export_c_func!(CGContextDrawLinearGradient(_, _, _, _, _)),
+    export_c_func!(CGContextDrawRadialGradient(_, _, _, _, _, _, _)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_stop_interpolation() {
+        let colors = [
+            (1.0, 0.0, 0.0, 1.0),
+            (0.0, 1.0, 0.0, 1.0),
+            (0.0, 0.0, 1.0, 1.0),
+        ];
+        let locations: &[CGFloat] = &[0.0, 0.25, 1.0];
+        assert_eq!(color_at(&colors, Some(locations), -1.0), colors[0]);
+        assert_eq!(
+            color_at(&colors, Some(locations), 0.125),
+            (0.5, 0.5, 0.0, 1.0)
+        );
+        assert_eq!(
+            color_at(&colors, Some(locations), 0.625),
+            (0.0, 0.5, 0.5, 1.0)
+        );
+        assert_eq!(color_at(&colors, Some(locations), 2.0), colors[2]);
+        // Without locations, the colors are evenly spaced.
+        assert_eq!(color_at(&colors, None, 0.25), (0.5, 0.5, 0.0, 1.0));
+    }
+
+    #[test]
+    fn radial_position() {
+        let origin = CGPoint { x: 0.0, y: 0.0 };
+        let any = |_| true;
+        // Concentric circles: the position is proportional to the distance.
+        let t = radial_gradient_position(
+            CGPoint { x: 3.0, y: 0.0 },
+            (origin, 2.0),
+            (origin, 6.0),
+            any,
+        );
+        assert_eq!(t, Some(0.25));
+        // Inside the start circle, only reachable by extending backwards.
+        let inside = CGPoint { x: 1.0, y: 0.0 };
+        let t = radial_gradient_position(inside, (origin, 2.0), (origin, 6.0), any);
+        assert_eq!(t, Some(-0.25));
+        let t = radial_gradient_position(inside, (origin, 2.0), (origin, 6.0), |t| t >= 0.0);
+        assert_eq!(t, None);
+    }
+}
@@ -0,0 +1,568 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPath.h`
+//!
+//! Paths are kept as a list of elements, with any transform passed when adding
+//! them already applied, so they can later be replayed into a `CGContext`
+//! (which applies its CTM) or flattened into polygons for host code.
+
+use super::cg_affine_transform::{CGAffineTransform, CGAffineTransformIdentity};
+use super::{cg_bitmap_context, cg_context, CGFloat, CGPoint, CGRect, CGSize};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::ConstPtr;
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPath is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not visible
+// anywhere.
+@implementation _touchHLE_CGPath: NSObject
+@end
+
+};
+
+/// Distance of the control points from the ends of a cubic Bézier curve that
+/// approximates a quarter of a circle of radius 1.
+pub const KAPPA: CGFloat = 0.5522848;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathElement {
+    MoveTo(CGPoint),
+    LineTo(CGPoint),
+    CurveTo {
+        control_point_1: CGPoint,
+        control_point_2: CGPoint,
+        to: CGPoint,
+    },
+    Close,
+}
+impl PathElement {
+    fn apply_transform(self, transform: CGAffineTransform) -> Self {
+        match self {
+            PathElement::MoveTo(point) => PathElement::MoveTo(transform.apply_to_point(point)),
+            PathElement::LineTo(point) => PathElement::LineTo(transform.apply_to_point(point)),
+            PathElement::CurveTo {
+                control_point_1,
+                control_point_2,
+                to,
+            } => PathElement::CurveTo {
+                control_point_1: transform.apply_to_point(control_point_1),
+                control_point_2: transform.apply_to_point(control_point_2),
+                to: transform.apply_to_point(to),
+            },
+            PathElement::Close => PathElement::Close,
+        }
+    }
+}
+
+/// Approximate a list of path elements as polygons, like the `CGContext` path
+/// engine does. As with `CGContextClosePath`, closed subpaths end with their
+/// first point again, so the result can also be stroked as polylines.
+pub fn to_polygons(elements: &[PathElement]) -> Vec<Vec<CGPoint>> {
+    let mut polygons: Vec<Vec<CGPoint>> = Vec::new();
+    for &element in elements {
+        match element {
+            PathElement::MoveTo(point) => polygons.push(vec![point]),
+            PathElement::LineTo(point) => polygons.last_mut().unwrap().push(point),
+            PathElement::CurveTo {
+                control_point_1,
+                control_point_2,
+                to,
+            } => {
+                let polygon = polygons.last_mut().unwrap();
+                let from = *polygon.last().unwrap();
+                polygon.extend(cg_context::flatten_curve(
+                    from,
+                    control_point_1,
+                    control_point_2,
+                    to,
+                ));
+            }
+            PathElement::Close => {
+                let polygon = polygons.last_mut().unwrap();
+                let first = polygon[0];
+                if polygon.len() > 1 {
+                    polygon.push(first);
+                }
+                polygons.push(vec![first]);
+            }
+        }
+    }
+    polygons
+}
+
+/// Shortcut for host code: get the elements of a rectangle with rounded
+/// corners (or an ellipse, if `radius` is large enough), going clockwise.
+/// Curves are only used if `radius` is not zero.
+pub fn rounded_rect(rect: CGRect, radius: CGFloat) -> Vec<PathElement> {
+    let CGRect { origin, size } = rect;
+    let (left, top) = (origin.x, origin.y);
+    let (right, bottom) = (origin.x + size.width, origin.y + size.height);
+    let radius_x = radius.min(size.width / 2.0);
+    let radius_y = radius.min(size.height / 2.0);
+    let point = |x, y| CGPoint { x, y };
+    // Each side is followed by the corner at its end, going clockwise.
+    let corners = [
+        (point(right, top), (-1.0, 0.0), (0.0, 1.0)),
+        (point(right, bottom), (0.0, -1.0), (-1.0, 0.0)),
+        (point(left, bottom), (1.0, 0.0), (0.0, -1.0)),
+        (point(left, top), (0.0, 1.0), (1.0, 0.0)),
+    ];
+    let offset = |corner: CGPoint, (dx, dy): (CGFloat, CGFloat), factor: CGFloat| CGPoint {
+        x: corner.x + dx * radius_x * factor,
+        y: corner.y + dy * radius_y * factor,
+    };
+    let (last_corner, _, last_exit) = corners[3];
+    let mut elements = vec![PathElement::MoveTo(offset(last_corner, last_exit, 1.0))];
+    for (corner, entry, exit) in corners {
+        elements.push(PathElement::LineTo(offset(corner, entry, 1.0)));
+        if radius_x > 0.0 && radius_y > 0.0 {
+            elements.push(PathElement::CurveTo {
+                control_point_1: offset(corner, entry, 1.0 - KAPPA),
+                control_point_2: offset(corner, exit, 1.0 - KAPPA),
+                to: offset(corner, exit, 1.0),
+            });
+        }
+    }
+    elements.push(PathElement::Close);
+    elements
+}
+
+#[derive(Default)]
+struct CGPathHostObject {
+    elements: Vec<PathElement>,
+}
+impl HostObject for CGPathHostObject {}
+
+pub type CGPathRef = CFTypeRef;
+pub type CGMutablePathRef = CFTypeRef;
+
+fn create_path(env: &mut Environment, elements: Vec<PathElement>) -> CGMutablePathRef {
+    let isa = env.objc.get_known_class("_touchHLE_CGPath", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(CGPathHostObject { elements }), &mut env.mem)
+}
+
+/// Shortcut for host code: get the elements of a path. A null path has none.
+pub fn elements(env: &Environment, path: CGPathRef) -> Vec<PathElement> {
+    if path.is_null() {
+        return Vec::new();
+    }
+    env.objc.borrow::<CGPathHostObject>(path).elements.clone()
+}
+
+fn CGPathCreateMutable(env: &mut Environment) -> CGMutablePathRef {
+    create_path(env, Vec::new())
+}
+fn CGPathCreateCopy(env: &mut Environment, path: CGPathRef) -> CGPathRef {
+    let elements = elements(env, path);
+    create_path(env, elements)
+}
+fn CGPathCreateMutableCopy(env: &mut Environment, path: CGPathRef) -> CGMutablePathRef {
+    CGPathCreateCopy(env, path)
+}
+
+pub fn CGPathRetain(env: &mut Environment, path: CGPathRef) -> CGPathRef {
+    if !path.is_null() {
+        CFRetain(env, path)
+    } else {
+        path
+    }
+}
+pub fn CGPathRelease(env: &mut Environment, path: CGPathRef) {
+    if !path.is_null() {
+        CFRelease(env, path);
+    }
+}
+
+/// Read the optional transform passed to most path functions.
+fn read_transform(env: &Environment, m: ConstPtr<CGAffineTransform>) -> CGAffineTransform {
+    if m.is_null() {
+        CGAffineTransformIdentity
+    } else {
+        env.mem.read(m)
+    }
+}
+
+fn current_point(elements: &[PathElement]) -> Option<CGPoint> {
+    let mut subpath_start = None;
+    let mut current = None;
+    for &element in elements {
+        match element {
+            PathElement::MoveTo(point) => {
+                subpath_start = Some(point);
+                current = Some(point);
+            }
+            PathElement::LineTo(point) | PathElement::CurveTo { to: point, .. } => {
+                current = Some(point)
+            }
+            PathElement::Close => current = subpath_start,
+        }
+    }
+    current
+}
+
+/// Append an element that has already been transformed.
+fn add_element(env: &mut Environment, path: CGMutablePathRef, element: PathElement) {
+    let host_obj = env.objc.borrow_mut::<CGPathHostObject>(path);
+    if !matches!(element, PathElement::MoveTo(_)) && host_obj.elements.is_empty() {
+        log!(
+            "Warning: CGPath {:?} has no current point, ignoring element",
+            path
+        );
+        return;
+    }
+    host_obj.elements.push(element);
+}
+
+fn CGPathMoveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let point = read_transform(env, m).apply_to_point(CGPoint { x, y });
+    add_element(env, path, PathElement::MoveTo(point));
+}
+fn CGPathAddLineToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let point = read_transform(env, m).apply_to_point(CGPoint { x, y });
+    add_element(env, path, PathElement::LineTo(point));
+}
+fn CGPathAddCurveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    cp1x: CGFloat,
+    cp1y: CGFloat,
+    cp2x: CGFloat,
+    cp2y: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let transform = read_transform(env, m);
+    let element = PathElement::CurveTo {
+        control_point_1: CGPoint { x: cp1x, y: cp1y },
+        control_point_2: CGPoint { x: cp2x, y: cp2y },
+        to: CGPoint { x, y },
+    };
+    add_element(env, path, element.apply_transform(transform));
+}
+fn CGPathAddQuadCurveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    cpx: CGFloat,
+    cpy: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let transform = read_transform(env, m);
+    let control_point = transform.apply_to_point(CGPoint { x: cpx, y: cpy });
+    let to = transform.apply_to_point(CGPoint { x, y });
+    let Some(from) = current_point(&env.objc.borrow::<CGPathHostObject>(path).elements) else {
+        add_element(env, path, PathElement::LineTo(to)); // will log a warning
+        return;
+    };
+    // A quadratic curve is a cubic curve with control points two thirds of the
+    // way to the quadratic control point.
+    let lerp = |a: CGPoint, b: CGPoint| CGPoint {
+        x: a.x + (b.x - a.x) * 2.0 / 3.0,
+        y: a.y + (b.y - a.y) * 2.0 / 3.0,
+    };
+    add_element(
+        env,
+        path,
+        PathElement::CurveTo {
+            control_point_1: lerp(from, control_point),
+            control_point_2: lerp(to, control_point),
+            to,
+        },
+    );
+}
+
+fn CGPathCloseSubpath(env: &mut Environment, path: CGMutablePathRef) {
+    add_element(env, path, PathElement::Close);
+}
+
+fn CGPathAddRect(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    rect: CGRect,
+) {
+    let transform = read_transform(env, m);
+    let CGRect {
+        origin: CGPoint { x, y },
+        size: CGSize { width, height },
+    } = rect;
+    let corners = [
+        CGPoint { x, y },
+        CGPoint { x: x + width, y },
+        CGPoint {
+            x: x + width,
+            y: y + height,
+        },
+        CGPoint { x, y: y + height },
+    ];
+    add_element(
+        env,
+        path,
+        PathElement::MoveTo(transform.apply_to_point(corners[0])),
+    );
+    for corner in &corners[1..] {
+        add_element(
+            env,
+            path,
+            PathElement::LineTo(transform.apply_to_point(*corner)),
+        );
+    }
+    add_element(env, path, PathElement::Close);
+}
+
+fn CGPathAddEllipseInRect(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    rect: CGRect,
+) {
+    // The ellipse is a unit circle scaled to fit the rectangle, drawn
+    // clockwise starting from the rightmost point.
+    let transform = CGAffineTransform::make_scale(rect.size.width / 2.0, rect.size.height / 2.0)
+        .concat(CGAffineTransform::make_translation(
+            rect.origin.x + rect.size.width / 2.0,
+            rect.origin.y + rect.size.height / 2.0,
+        ))
+        .concat(read_transform(env, m));
+    add_element(
+        env,
+        path,
+        PathElement::MoveTo(transform.apply_to_point(CGPoint { x: 1.0, y: 0.0 })),
+    );
+    add_arc_curves(env, path, transform, 0.0, -2.0 * std::f32::consts::PI);
+    add_element(env, path, PathElement::Close);
+}
+
+fn CGPathAddArc(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+    radius: CGFloat,
+    start_angle: CGFloat,
+    end_angle: CGFloat,
+    clockwise: bool,
+) {
+    use std::f32::consts::PI;
+
+    // Angles increase counterclockwise, in the unflipped co-ordinate system.
+    let sweep = if clockwise {
+        -(start_angle - end_angle).rem_euclid(2.0 * PI)
+    } else {
+        (end_angle - start_angle).rem_euclid(2.0 * PI)
+    };
+    // A full circle was requested, not an empty arc.
+    let sweep = if sweep == 0.0 && start_angle != end_angle {
+        if clockwise {
+            -2.0 * PI
+        } else {
+            2.0 * PI
+        }
+    } else {
+        sweep
+    };
+
+    let transform = CGAffineTransform::make_scale(radius, radius)
+        .concat(CGAffineTransform::make_translation(x, y))
+        .concat(read_transform(env, m));
+    let start = transform.apply_to_point(CGPoint {
+        x: start_angle.cos(),
+        y: start_angle.sin(),
+    });
+    // The arc is connected to the current point with a line, if there is one.
+    let element = if env
+        .objc
+        .borrow::<CGPathHostObject>(path)
+        .elements
+        .is_empty()
+    {
+        PathElement::MoveTo(start)
+    } else {
+        PathElement::LineTo(start)
+    };
+    add_element(env, path, element);
+    add_arc_curves(env, path, transform, start_angle, sweep);
+}
+
+/// Append curves approximating an arc of the unit circle, transformed by
+/// `transform`. The current point must already be the start of the arc.
+fn add_arc_curves(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    transform: CGAffineTransform,
+    start_angle: CGFloat,
+    sweep: CGFloat,
+) {
+    use std::f32::consts::FRAC_PI_2;
+
+    // Each curve covers at most a quarter of the circle.
+    let count = (sweep.abs() / FRAC_PI_2).ceil().max(1.0);
+    let step = sweep / count;
+    // Control point distance for an arc of this angle. For a quarter circle
+    // this is KAPPA.
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    for i in 0..(count as u32) {
+        let a0 = start_angle + step * i as CGFloat;
+        let a1 = a0 + step;
+        let (sin0, cos0) = a0.sin_cos();
+        let (sin1, cos1) = a1.sin_cos();
+        let element = PathElement::CurveTo {
+            control_point_1: CGPoint {
+                x: cos0 - k * sin0,
+                y: sin0 + k * cos0,
+            },
+            control_point_2: CGPoint {
+                x: cos1 + k * sin1,
+                y: sin1 - k * cos1,
+            },
+            to: CGPoint { x: cos1, y: sin1 },
+        };
+        add_element(env, path, element.apply_transform(transform));
+    }
+}
+
+fn CGPathAddPath(
+    env: &mut Environment,
+    path1: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    path2: CGPathRef,
+) {
+    let transform = read_transform(env, m);
+    let elements = elements(env, path2);
+    env.objc
+        .borrow_mut::<CGPathHostObject>(path1)
+        .elements
+        .extend(elements.into_iter().map(|e| e.apply_transform(transform)));
+}
+
+fn CGPathIsEmpty(env: &mut Environment, path: CGPathRef) -> bool {
+    elements(env, path).is_empty()
+}
+
+fn CGPathGetCurrentPoint(env: &mut Environment, path: CGPathRef) -> CGPoint {
+    current_point(&elements(env, path)).unwrap_or(CGPoint { x: 0.0, y: 0.0 })
+}
+
+fn CGPathEqualToPath(env: &mut Environment, path1: CGPathRef, path2: CGPathRef) -> bool {
+    path1 == path2 || elements(env, path1) == elements(env, path2)
+}
+
+fn CGPathContainsPoint(
+    env: &mut Environment,
+    path: CGPathRef,
+    m: ConstPtr<CGAffineTransform>,
+    point: CGPoint,
+    eo_fill: bool,
+) -> bool {
+    let point = read_transform(env, m).apply_to_point(point);
+    let polygons = to_polygons(&elements(env, path));
+    let winding = cg_bitmap_context::winding_number(&polygons, point);
+    if eo_fill {
+        winding % 2 != 0
+    } else {
+        winding != 0
+    }
+}
+
+/// Bounding box of some points, or `CGRectNull` if there are none.
+fn bounding_box(points: impl Iterator<Item = CGPoint>) -> CGRect {
+    let mut min = CGPoint {
+        x: CGFloat::INFINITY,
+        y: CGFloat::INFINITY,
+    };
+    let mut max = CGPoint {
+        x: CGFloat::NEG_INFINITY,
+        y: CGFloat::NEG_INFINITY,
+    };
+    for point in points {
+        min.x = min.x.min(point.x);
+        min.y = min.y.min(point.y);
+        max.x = max.x.max(point.x);
+        max.y = max.y.max(point.y);
+    }
+    if min.x > max.x {
+        // CGRectNull
+        return CGRect {
+            origin: min,
+            size: CGSize {
+                width: 0.0,
+                height: 0.0,
+            },
+        };
+    }
+    CGRect {
+        origin: min,
+        size: CGSize {
+            width: max.x - min.x,
+            height: max.y - min.y,
+        },
+    }
+}
+
+/// The bounding box of all the points, including control points.
+fn CGPathGetBoundingBox(env: &mut Environment, path: CGPathRef) -> CGRect {
+    let elements = elements(env, path);
+    bounding_box(elements.into_iter().flat_map(|element| match element {
+        PathElement::MoveTo(point) | PathElement::LineTo(point) => vec![point],
+        PathElement::CurveTo {
+            control_point_1,
+            control_point_2,
+            to,
+        } => vec![control_point_1, control_point_2, to],
+        PathElement::Close => vec![],
+    }))
+}
+
+/// The bounding box of the path itself, not including control points.
+fn CGPathGetPathBoundingBox(env: &mut Environment, path: CGPathRef) -> CGRect {
+    let polygons = to_polygons(&elements(env, path));
+    bounding_box(polygons.into_iter().flatten())
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPathCreateMutable()),
+    export_c_func!(CGPathCreateCopy(_)),
+    export_c_func!(CGPathCreateMutableCopy(_)),
+    export_c_func!(CGPathRetain(_)),
+    export_c_func!(CGPathRelease(_)),
+    export_c_func!(CGPathMoveToPoint(_, _, _, _)),
+    export_c_func!(CGPathAddLineToPoint(_, _, _, _)),
+    export_c_func!(CGPathAddCurveToPoint(_, _, _, _, _, _, _, _)),
+    export_c_func!(CGPathAddQuadCurveToPoint(_, _, _, _, _, _)),
+    export_c_func!(CGPathCloseSubpath(_)),
+    export_c_func!(CGPathAddRect(_, _, _)),
+    export_c_func!(CGPathAddEllipseInRect(_, _, _)),
+    export_c_func!(CGPathAddArc(_, _, _, _, _, _, _, _)),
+    export_c_func!(CGPathAddPath(_, _, _)),
+    export_c_func!(CGPathIsEmpty(_)),
+    export_c_func!(CGPathGetCurrentPoint(_)),
+    export_c_func!(CGPathEqualToPath(_, _)),
+    export_c_func!(CGPathContainsPoint(_, _, _, _)),
+    export_c_func!(CGPathGetBoundingBox(_)),
+    export_c_func!(CGPathGetPathBoundingBox(_)),
+];
@@ -0,0 +1,28 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Core Location framework.
+//!
+//! touchHLE doesn't use the host's location. Instead, a simulated location is
+//! reported, which can be configured with the `--location=` and
+//! `--location-speed=` options.
+
+pub mod cl_location;
+pub mod cl_location_manager;
+
+use std::time::Instant;
+
+#[derive(Default)]
+pub struct State {
+    cl_location_manager: cl_location_manager::State,
+}
+
+/// For use by `NSRunLoop`: send location updates to location managers'
+/// delegates if they are due.
+///
+/// Returns the time the next update is due, if any.
+pub fn handle_location_updates(env: &mut crate::Environment) -> Option<Instant> {
+    cl_location_manager::handle_location_updates(env)
+}
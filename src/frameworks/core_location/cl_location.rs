@@ -0,0 +1,265 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CLLocation.h` (`CLLocation`, `CLLocationCoordinate2D`, etc)
+
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::SafeRead;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+
+pub type CLLocationDegrees = f64;
+pub type CLLocationDistance = f64;
+pub type CLLocationAccuracy = f64;
+pub type CLLocationSpeed = f64;
+pub type CLLocationDirection = f64;
+
+pub const kCLLocationAccuracyBestForNavigation: CLLocationAccuracy = -2.0;
+pub const kCLLocationAccuracyBest: CLLocationAccuracy = -1.0;
+pub const kCLLocationAccuracyNearestTenMeters: CLLocationAccuracy = 10.0;
+pub const kCLLocationAccuracyHundredMeters: CLLocationAccuracy = 100.0;
+pub const kCLLocationAccuracyKilometer: CLLocationAccuracy = 1000.0;
+pub const kCLLocationAccuracyThreeKilometers: CLLocationAccuracy = 3000.0;
+pub const kCLDistanceFilterNone: CLLocationDistance = -1.0;
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCLLocationAccuracyBestForNavigation",
+        HostConstant::Custom(|mem| {
+            mem.alloc_and_write(kCLLocationAccuracyBestForNavigation)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCLLocationAccuracyBest",
+        HostConstant::Custom(|mem| {
+            mem.alloc_and_write(kCLLocationAccuracyBest)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCLLocationAccuracyNearestTenMeters",
+        HostConstant::Custom(|mem| {
+            mem.alloc_and_write(kCLLocationAccuracyNearestTenMeters)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCLLocationAccuracyHundredMeters",
+        HostConstant::Custom(|mem| {
+            mem.alloc_and_write(kCLLocationAccuracyHundredMeters)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCLLocationAccuracyKilometer",
+        HostConstant::Custom(|mem| {
+            mem.alloc_and_write(kCLLocationAccuracyKilometer)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCLLocationAccuracyThreeKilometers",
+        HostConstant::Custom(|mem| {
+            mem.alloc_and_write(kCLLocationAccuracyThreeKilometers)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCLDistanceFilterNone",
+        HostConstant::Custom(|mem| {
+            mem.alloc_and_write(kCLDistanceFilterNone)
+                .cast()
+                .cast_const()
+        }),
+    ),
+];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(C, packed)]
+pub struct CLLocationCoordinate2D {
+    pub latitude: CLLocationDegrees,
+    pub longitude: CLLocationDegrees,
+}
+unsafe impl SafeRead for CLLocationCoordinate2D {}
+impl_GuestRet_for_large_struct!(CLLocationCoordinate2D);
+impl GuestArg for CLLocationCoordinate2D {
+    const REG_COUNT: usize = 4;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        CLLocationCoordinate2D {
+            latitude: GuestArg::from_regs(&regs[0..2]),
+            longitude: GuestArg::from_regs(&regs[2..4]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.latitude.to_regs(&mut regs[0..2]);
+        self.longitude.to_regs(&mut regs[2..4]);
+    }
+}
+impl CLLocationCoordinate2D {
+    fn is_valid(self) -> bool {
+        let CLLocationCoordinate2D {
+            latitude,
+            longitude,
+        } = self;
+        (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)
+    }
+}
+
+/// Mean radius of the Earth in meters.
+const EARTH_RADIUS: CLLocationDistance = 6_371_008.8;
+
+/// Great-circle distance between two coordinates (haversine formula).
+pub fn distance(a: CLLocationCoordinate2D, b: CLLocationCoordinate2D) -> CLLocationDistance {
+    let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+}
+
+/// Initial bearing from one coordinate to another, in degrees clockwise from
+/// true north.
+pub fn bearing(from: CLLocationCoordinate2D, to: CLLocationCoordinate2D) -> CLLocationDirection {
+    let (lat_a, lat_b) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let d_lon = (to.longitude - from.longitude).to_radians();
+    let y = d_lon.sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+pub(super) struct CLLocationHostObject {
+    pub(super) coordinate: CLLocationCoordinate2D,
+    pub(super) altitude: CLLocationDistance,
+    pub(super) horizontal_accuracy: CLLocationAccuracy,
+    pub(super) vertical_accuracy: CLLocationAccuracy,
+    pub(super) course: CLLocationDirection,
+    pub(super) speed: CLLocationSpeed,
+    /// `NSDate*`
+    pub(super) timestamp: id,
+}
+impl HostObject for CLLocationHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CLLocation: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(CLLocationHostObject {
+        coordinate: CLLocationCoordinate2D::default(),
+        altitude: 0.0,
+        horizontal_accuracy: 0.0,
+        // Negative values mean the altitude, course and speed are invalid.
+        vertical_accuracy: -1.0,
+        course: -1.0,
+        speed: -1.0,
+        timestamp: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithLatitude:(CLLocationDegrees)latitude
+             longitude:(CLLocationDegrees)longitude {
+    let timestamp: id = msg_class![env; NSDate date];
+    retain(env, timestamp);
+    let host_object = env.objc.borrow_mut::<CLLocationHostObject>(this);
+    host_object.coordinate = CLLocationCoordinate2D { latitude, longitude };
+    host_object.timestamp = timestamp;
+    this
+}
+
+- (id)initWithCoordinate:(CLLocationCoordinate2D)coordinate
+                altitude:(CLLocationDistance)altitude
+      horizontalAccuracy:(CLLocationAccuracy)horizontal_accuracy
+        verticalAccuracy:(CLLocationAccuracy)vertical_accuracy
+               timestamp:(id)timestamp { // NSDate*
+    retain(env, timestamp);
+    let host_object = env.objc.borrow_mut::<CLLocationHostObject>(this);
+    host_object.coordinate = coordinate;
+    host_object.altitude = altitude;
+    host_object.horizontal_accuracy = horizontal_accuracy;
+    host_object.vertical_accuracy = vertical_accuracy;
+    host_object.timestamp = timestamp;
+    this
+}
+
+- (())dealloc {
+    let timestamp = env.objc.borrow::<CLLocationHostObject>(this).timestamp;
+    release(env, timestamp);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    // Locations are immutable.
+    retain(env, this)
+}
+
+- (CLLocationCoordinate2D)coordinate {
+    env.objc.borrow::<CLLocationHostObject>(this).coordinate
+}
+- (CLLocationDistance)altitude {
+    env.objc.borrow::<CLLocationHostObject>(this).altitude
+}
+- (CLLocationAccuracy)horizontalAccuracy {
+    env.objc.borrow::<CLLocationHostObject>(this).horizontal_accuracy
+}
+- (CLLocationAccuracy)verticalAccuracy {
+    env.objc.borrow::<CLLocationHostObject>(this).vertical_accuracy
+}
+- (CLLocationDirection)course {
+    env.objc.borrow::<CLLocationHostObject>(this).course
+}
+- (CLLocationSpeed)speed {
+    env.objc.borrow::<CLLocationHostObject>(this).speed
+}
+- (id)timestamp {
+    env.objc.borrow::<CLLocationHostObject>(this).timestamp
+}
+
+- (CLLocationDistance)distanceFromLocation:(id)other { // CLLocation*
+    let a = env.objc.borrow::<CLLocationHostObject>(this).coordinate;
+    let b = env.objc.borrow::<CLLocationHostObject>(other).coordinate;
+    distance(a, b)
+}
+// Deprecated name for the above, from iPhone OS 2.
+- (CLLocationDistance)getDistanceFrom:(id)other { // CLLocation*
+    msg![env; this distanceFromLocation:other]
+}
+
+@end
+
+};
+
+fn CLLocationCoordinate2DMake(
+    _env: &mut Environment,
+    latitude: CLLocationDegrees,
+    longitude: CLLocationDegrees,
+) -> CLLocationCoordinate2D {
+    CLLocationCoordinate2D {
+        latitude,
+        longitude,
+    }
+}
+fn CLLocationCoordinate2DIsValid(_env: &mut Environment, coord: CLLocationCoordinate2D) -> bool {
+    coord.is_valid()
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CLLocationCoordinate2DMake(_, _)),
+    export_c_func!(CLLocationCoordinate2DIsValid(_)),
+];
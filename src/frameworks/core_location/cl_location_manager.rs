@@ -0,0 +1,294 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CLLocationManager`.
+
+use super::cl_location::{
+    bearing, distance, kCLDistanceFilterNone, kCLLocationAccuracyBest, CLLocationAccuracy,
+    CLLocationCoordinate2D, CLLocationDirection, CLLocationDistance, CLLocationHostObject,
+    CLLocationSpeed,
+};
+use crate::frameworks::foundation::{ns_array, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+type CLAuthorizationStatus = NSInteger;
+#[allow(dead_code)]
+const kCLAuthorizationStatusNotDetermined: CLAuthorizationStatus = 0;
+#[allow(dead_code)]
+const kCLAuthorizationStatusRestricted: CLAuthorizationStatus = 1;
+#[allow(dead_code)]
+const kCLAuthorizationStatusDenied: CLAuthorizationStatus = 2;
+const kCLAuthorizationStatusAuthorized: CLAuthorizationStatus = 3;
+
+/// How often location updates are sent.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Accuracy reported for simulated locations, roughly what GPS would give.
+const SIMULATED_HORIZONTAL_ACCURACY: CLLocationAccuracy = 5.0;
+
+#[derive(Default)]
+pub struct State {
+    /// Location managers that are currently updating. These are weak
+    /// references, managers remove themselves when deallocated.
+    updating: Vec<id>,
+    /// When the simulated location started moving along the path.
+    simulation_start: Option<Instant>,
+    due_by: Option<Instant>,
+}
+
+struct CLLocationManagerHostObject {
+    /// Something implementing CLLocationManagerDelegate, weak reference
+    delegate: id,
+    desired_accuracy: CLLocationAccuracy,
+    distance_filter: CLLocationDistance,
+    /// The last `CLLocation*` sent to the delegate, if any
+    last_location: id,
+}
+impl HostObject for CLLocationManagerHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CLLocationManager: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(CLLocationManagerHostObject {
+        delegate: nil,
+        desired_accuracy: kCLLocationAccuracyBest,
+        distance_filter: kCLDistanceFilterNone,
+        last_location: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// The user never gets asked, permission is always granted.
++ (CLAuthorizationStatus)authorizationStatus {
+    kCLAuthorizationStatusAuthorized
+}
++ (bool)locationServicesEnabled {
+    true
+}
+// Deprecated instance method version of the above, from iPhone OS 2.
+- (bool)locationServicesEnabled {
+    true
+}
+- (())requestWhenInUseAuthorization {}
+- (())requestAlwaysAuthorization {}
+
++ (bool)headingAvailable {
+    false
+}
+
+- (())dealloc {
+    let state = &mut env.framework_state.core_location.cl_location_manager;
+    state.updating.retain(|&manager| manager != this);
+    let last_location = env.objc.borrow::<CLLocationManagerHostObject>(this).last_location;
+    release(env, last_location);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)delegate {
+    env.objc.borrow::<CLLocationManagerHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // CLLocationManagerDelegate*
+    env.objc.borrow_mut::<CLLocationManagerHostObject>(this).delegate = delegate;
+}
+
+- (CLLocationAccuracy)desiredAccuracy {
+    env.objc.borrow::<CLLocationManagerHostObject>(this).desired_accuracy
+}
+- (())setDesiredAccuracy:(CLLocationAccuracy)accuracy {
+    // The simulated location is always equally accurate, so this is ignored.
+    env.objc.borrow_mut::<CLLocationManagerHostObject>(this).desired_accuracy = accuracy;
+}
+
+- (CLLocationDistance)distanceFilter {
+    env.objc.borrow::<CLLocationManagerHostObject>(this).distance_filter
+}
+- (())setDistanceFilter:(CLLocationDistance)filter {
+    env.objc.borrow_mut::<CLLocationManagerHostObject>(this).distance_filter = filter;
+}
+
+- (())startUpdatingLocation {
+    let state = &mut env.framework_state.core_location.cl_location_manager;
+    if !state.updating.contains(&this) {
+        state.updating.push(this);
+    }
+    state.simulation_start.get_or_insert_with(Instant::now);
+    // Send the first update as soon as possible.
+    state.due_by = Some(Instant::now());
+}
+- (())stopUpdatingLocation {
+    let state = &mut env.framework_state.core_location.cl_location_manager;
+    state.updating.retain(|&manager| manager != this);
+}
+
+- (id)location {
+    if env.framework_state.core_location.cl_location_manager.simulation_start.is_none() {
+        // No location has ever been determined.
+        return nil;
+    }
+    let location = new_simulated_location(env);
+    autorelease(env, location)
+}
+
+@end
+
+};
+
+/// Get the simulated coordinate at the current time, and the speed and course
+/// of movement.
+fn simulated_position(
+    env: &mut Environment,
+) -> (CLLocationCoordinate2D, CLLocationSpeed, CLLocationDirection) {
+    let elapsed = env
+        .framework_state
+        .core_location
+        .cl_location_manager
+        .simulation_start
+        .map_or(Duration::ZERO, |start| start.elapsed());
+    let speed = env.options.location_speed;
+    let path: Vec<CLLocationCoordinate2D> = env
+        .options
+        .location_path
+        .iter()
+        .map(|&(latitude, longitude)| CLLocationCoordinate2D {
+            latitude,
+            longitude,
+        })
+        .collect();
+
+    let mut remaining = elapsed.as_secs_f64() * speed;
+    for segment in path.windows(2) {
+        let (from, to) = (segment[0], segment[1]);
+        let length = distance(from, to);
+        if remaining < length {
+            // Linear interpolation is a fine approximation over the short
+            // distances one would normally simulate.
+            let t = remaining / length;
+            let coordinate = CLLocationCoordinate2D {
+                latitude: from.latitude + (to.latitude - from.latitude) * t,
+                longitude: from.longitude + (to.longitude - from.longitude) * t,
+            };
+            return (coordinate, speed, bearing(from, to));
+        }
+        remaining -= length;
+    }
+    // Stationary at the end of the path.
+    (*path.last().unwrap(), 0.0, -1.0)
+}
+
+/// Create a new `CLLocation*` for the current simulated location (+1
+/// reference).
+fn new_simulated_location(env: &mut Environment) -> id {
+    let (coordinate, speed, course) = simulated_position(env);
+    let timestamp: id = msg_class![env; NSDate date];
+    let location: id = msg_class![env; CLLocation alloc];
+    let location: id = msg![env; location initWithCoordinate:coordinate
+                                                     altitude:0.0
+                                           horizontalAccuracy:SIMULATED_HORIZONTAL_ACCURACY
+                                             verticalAccuracy:-1.0
+                                                    timestamp:timestamp];
+    let host_object = env.objc.borrow_mut::<CLLocationHostObject>(location);
+    host_object.speed = speed;
+    host_object.course = course;
+    location
+}
+
+/// For use by `NSRunLoop` via [super::handle_location_updates]: check if a
+/// location update is due and send one to each updating location manager.
+///
+/// Returns the time the next update is due, if any.
+pub(super) fn handle_location_updates(env: &mut Environment) -> Option<Instant> {
+    let state = &mut env.framework_state.core_location.cl_location_manager;
+    if state.updating.is_empty() {
+        return None;
+    }
+    let now = Instant::now();
+    if let Some(due_by) = state.due_by {
+        if due_by > now {
+            return Some(due_by);
+        }
+    }
+    let new_due_by = now.checked_add(UPDATE_INTERVAL).unwrap();
+    state.due_by = Some(new_due_by);
+    let managers = state.updating.clone();
+
+    // CoreLocation creates and drains autorelease pools when sending updates.
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+
+    let location = new_simulated_location(env);
+    for manager in managers {
+        send_location(env, manager, location);
+    }
+    release(env, location);
+
+    release(env, pool);
+
+    Some(new_due_by)
+}
+
+fn send_location(env: &mut Environment, manager: id, location: id) {
+    let &CLLocationManagerHostObject {
+        delegate,
+        distance_filter,
+        last_location,
+        ..
+    } = env.objc.borrow(manager);
+
+    if last_location != nil && distance_filter > 0.0 {
+        let moved: CLLocationDistance = msg![env; location distanceFromLocation:last_location];
+        if moved < distance_filter {
+            return;
+        }
+    }
+
+    retain(env, location);
+    env.objc
+        .borrow_mut::<CLLocationManagerHostObject>(manager)
+        .last_location = location;
+
+    if delegate == nil {
+        // Nothing to do.
+    } else if env.objc.object_has_method_named(
+        &env.mem,
+        delegate,
+        "locationManager:didUpdateLocations:",
+    ) {
+        retain(env, location);
+        let locations = ns_array::from_vec(env, vec![location]);
+        autorelease(env, locations);
+        log_dbg!(
+            "Sending [{:?} locationManager:{:?} didUpdateLocations:{:?}]",
+            delegate,
+            manager,
+            locations,
+        );
+        () = msg![env; delegate locationManager:manager didUpdateLocations:locations];
+    } else if env.objc.object_has_method_named(
+        &env.mem,
+        delegate,
+        "locationManager:didUpdateToLocation:fromLocation:",
+    ) {
+        log_dbg!(
+            "Sending [{:?} locationManager:{:?} didUpdateToLocation:{:?} fromLocation:{:?}]",
+            delegate,
+            manager,
+            location,
+            last_location,
+        );
+        () = msg![env; delegate locationManager:manager
+                                didUpdateToLocation:location
+                                       fromLocation:last_location];
+    }
+
+    release(env, last_location);
+}
@@ -0,0 +1,11 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Core Text framework.
+//!
+//! Only font registration is implemented. Text is drawn by UIKit's `UIFont`
+//! and the `NSString` drawing methods.
+
+pub mod ct_font_manager;
@@ -0,0 +1,42 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CTFontManager`.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::frameworks::uikit::ui_font;
+use crate::mem::MutPtr;
+use crate::objc::{id, nil};
+use crate::Environment;
+
+type CTFontManagerScope = u32;
+
+fn CTFontManagerRegisterFontsForURL(
+    env: &mut Environment,
+    font_url: CFURLRef,
+    _scope: CTFontManagerScope,
+    error: MutPtr<id>, // CFErrorRef*
+) -> bool {
+    let path = to_rust_path(env, font_url).into_owned();
+    match ui_font::register_font_file(env, &path) {
+        Ok(()) => true,
+        Err(e) => {
+            log!(
+                "Warning: CTFontManagerRegisterFontsForURL() couldn't register {:?}: {}",
+                path,
+                e
+            );
+            // TODO: return a CFError
+            if !error.is_null() {
+                env.mem.write(error, nil);
+            }
+            false
+        }
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(CTFontManagerRegisterFontsForURL(_, _, _))];
@@ -18,6 +18,7 @@ use crate::Environment;
 pub mod ns_array;
 pub mod ns_autorelease_pool;
 pub mod ns_bundle;
+pub mod ns_cache;
 pub mod ns_character_set;
 pub mod ns_coder;
 pub mod ns_data;
@@ -29,6 +30,8 @@ pub mod ns_error;
 pub mod ns_exception;
 pub mod ns_file_handle;
 pub mod ns_file_manager;
+pub mod ns_http_cookie_storage;
+pub mod ns_index_set;
 pub mod ns_keyed_unarchiver;
 pub mod ns_locale;
 pub mod ns_lock;
@@ -42,10 +45,15 @@ pub mod ns_process_info;
 pub mod ns_property_list_serialization;
 pub mod ns_run_loop;
 pub mod ns_set;
+pub mod ns_stream;
 pub mod ns_string;
 pub mod ns_thread;
+pub mod ns_time_zone;
 pub mod ns_timer;
 pub mod ns_url;
+pub mod ns_url_connection;
+pub mod ns_url_request;
+pub mod ns_url_response;
 pub mod ns_user_defaults;
 pub mod ns_value;
 
@@ -53,12 +61,16 @@ pub mod ns_value;
 pub struct State {
     ns_autorelease_pool: ns_autorelease_pool::State,
     ns_bundle: ns_bundle::State,
+    ns_exception: ns_exception::State,
     ns_file_manager: ns_file_manager::State,
+    ns_http_cookie_storage: ns_http_cookie_storage::State,
     ns_locale: ns_locale::State,
     ns_notification_center: ns_notification_center::State,
     ns_null: ns_null::State,
+    ns_object: ns_object::State,
     ns_run_loop: ns_run_loop::State,
     ns_string: ns_string::State,
+    ns_time_zone: ns_time_zone::State,
     ns_user_defaults: ns_user_defaults::State,
 }
 
@@ -7,7 +7,10 @@
 
 use super::ns_enumerator::{fast_enumeration_helper, NSFastEnumerationState};
 use super::ns_property_list_serialization::deserialize_plist_from_file;
-use super::{ns_keyed_unarchiver, ns_string, ns_url, NSUInteger};
+use super::{
+    ns_exception, ns_index_set, ns_keyed_unarchiver, ns_string, ns_url, NSNotFound, NSRange,
+    NSUInteger,
+};
 use crate::fs::GuestPath;
 use crate::mem::MutPtr;
 use crate::objc::{
@@ -27,6 +30,25 @@ struct ArrayHostObject {
 }
 impl HostObject for ArrayHostObject {}
 
+/// Bounds check used by the array methods that take an index. `count` is the
+/// number of valid indices, which for insertion is one more than the length.
+/// If the index is out of bounds, this raises `NSRangeException` and returns
+/// [false], and the caller must then return immediately.
+#[must_use]
+fn check_index(env: &mut Environment, this: id, index: NSUInteger, count: usize) -> bool {
+    if (index as usize) < count {
+        return true;
+    }
+    let reason = format!(
+        "*** index {} beyond bounds [0 .. {}] of array {:?}",
+        index,
+        count as isize - 1,
+        this,
+    );
+    ns_exception::raise(env, "NSRangeException", reason);
+    false
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -97,6 +119,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     retain(env, this)
 }
 
+- (id)firstObject {
+    let size: NSUInteger = msg![env; this count];
+    if size == 0 {
+        return nil;
+    }
+    msg![env; this objectAtIndex:0u32]
+}
+
 - (id)lastObject {
     let size: NSUInteger = msg![env; this count];
     if size == 0 {
@@ -105,6 +135,80 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this objectAtIndex: (size - 1)]
 }
 
+- (NSUInteger)indexOfObject:(id)object {
+    let count: NSUInteger = msg![env; this count];
+    for i in 0..count {
+        let candidate: id = msg![env; this objectAtIndex:i];
+        if candidate == object || msg![env; candidate isEqual:object] {
+            return i;
+        }
+    }
+    NSNotFound as NSUInteger
+}
+
+- (bool)containsObject:(id)object {
+    let index: NSUInteger = msg![env; this indexOfObject:object];
+    index != NSNotFound as NSUInteger
+}
+
+- (id)arrayByAddingObject:(id)object {
+    let mut objects = to_vec(env, this);
+    objects.push(object);
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = from_vec(env, objects);
+    autorelease(env, array)
+}
+
+- (id)arrayByAddingObjectsFromArray:(id)other { // NSArray*
+    let mut objects = to_vec(env, this);
+    objects.extend(to_vec(env, other));
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = from_vec(env, objects);
+    autorelease(env, array)
+}
+
+- (id)subarrayWithRange:(NSRange)range {
+    let count: NSUInteger = msg![env; this count];
+    let NSRange { location, length } = range;
+    if location.checked_add(length).map_or(true, |end| end > count) {
+        let reason = format!(
+            "*** -[NSArray subarrayWithRange:]: range {{{}, {}}} extends beyond bounds [0 .. {}]",
+            location,
+            length,
+            count as isize - 1,
+        );
+        ns_exception::raise(env, "NSRangeException", reason);
+        return nil;
+    }
+    let mut objects = Vec::with_capacity(length as usize);
+    for i in location..location + length {
+        let object: id = msg![env; this objectAtIndex:i];
+        objects.push(retain(env, object));
+    }
+    let array = from_vec(env, objects);
+    autorelease(env, array)
+}
+
+- (id)componentsJoinedByString:(id)separator { // NSString*
+    let separator = ns_string::to_rust_string(env, separator).into_owned();
+    let count: NSUInteger = msg![env; this count];
+    let mut joined = String::new();
+    for i in 0..count {
+        if i > 0 {
+            joined.push_str(&separator);
+        }
+        let object: id = msg![env; this objectAtIndex:i];
+        let description: id = msg![env; object description];
+        joined.push_str(&ns_string::to_rust_string(env, description));
+    }
+    let joined = ns_string::from_rust_string(env, joined);
+    autorelease(env, joined)
+}
+
 @end
 
 // NSMutableArray is an abstract class. A subclass must provide everything
@@ -130,6 +234,61 @@ pub const CLASSES: ClassExports = objc_classes! {
     todo!(); // TODO: this should produce an immutable copy
 }
 
+// These are implemented in terms of the primitive methods, so that they also
+// work for subclasses.
+
+- (())removeObject:(id)object {
+    let count: NSUInteger = msg![env; this count];
+    for i in (0..count).rev() {
+        let candidate: id = msg![env; this objectAtIndex:i];
+        if candidate == object || msg![env; candidate isEqual:object] {
+            () = msg![env; this removeObjectAtIndex:i];
+        }
+    }
+}
+
+- (())addObjectsFromArray:(id)other { // NSArray*
+    // Copying first means adding an array to itself works.
+    for object in to_vec(env, other) {
+        () = msg![env; this addObject:object];
+    }
+}
+
+- (())removeAllObjects {
+    let count: NSUInteger = msg![env; this count];
+    for _ in 0..count {
+        () = msg![env; this removeLastObject];
+    }
+}
+
+- (())insertObjects:(id)objects // NSArray*
+          atIndexes:(id)indexes { // NSIndexSet*
+    let indexes = ns_index_set::to_vec(env, indexes);
+    let objects_count: NSUInteger = msg![env; objects count];
+    assert_eq!(indexes.len(), objects_count as usize);
+    // Each index is relative to the array as it is after the insertions at
+    // the preceding (lower) indexes.
+    for (i, index) in indexes.into_iter().enumerate() {
+        let i: NSUInteger = i.try_into().unwrap();
+        let object: id = msg![env; objects objectAtIndex:i];
+        () = msg![env; this insertObject:object atIndex:index];
+    }
+}
+
+- (())removeObjectsAtIndexes:(id)indexes { // NSIndexSet*
+    let indexes = ns_index_set::to_vec(env, indexes);
+    let count: NSUInteger = msg![env; this count];
+    if let Some(&last) = indexes.last() {
+        if !check_index(env, this, last, count as usize) {
+            return;
+        }
+    }
+    // Remove from the highest index downwards so the lower ones stay valid.
+    for index in indexes.into_iter().rev() {
+        () = msg![env; this removeObjectAtIndex:index];
+    }
+}
+
 @end
 
 // Our private subclass that is the single implementation of NSArray for the
@@ -201,7 +360,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<ArrayHostObject>(this).array.len().try_into().unwrap()
 }
 - (id)objectAtIndex:(NSUInteger)index {
-    // TODO: throw real exception rather than panic if out-of-bounds?
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len) {
+        return nil;
+    }
     env.objc.borrow::<ArrayHostObject>(this).array[index as usize]
 }
 
@@ -259,24 +421,65 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<ArrayHostObject>(this).array.len().try_into().unwrap()
 }
 - (id)objectAtIndex:(NSUInteger)index {
-    // TODO: throw real exception rather than panic if out-of-bounds?
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len) {
+        return nil;
+    }
     env.objc.borrow::<ArrayHostObject>(this).array[index as usize]
 }
 
-// TODO: more mutation methods
-
 - (())addObject:(id)object {
     retain(env, object);
     env.objc.borrow_mut::<ArrayHostObject>(this).array.push(object);
 }
 
+- (())insertObject:(id)object atIndex:(NSUInteger)index {
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len + 1) {
+        return;
+    }
+    retain(env, object);
+    env.objc.borrow_mut::<ArrayHostObject>(this).array.insert(index as usize, object);
+}
+
+- (())replaceObjectAtIndex:(NSUInteger)index withObject:(id)object {
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len) {
+        return;
+    }
+    // Retain before releasing, in case the object is being replaced by itself.
+    retain(env, object);
+    let old = std::mem::replace(
+        &mut env.objc.borrow_mut::<ArrayHostObject>(this).array[index as usize],
+        object,
+    );
+    release(env, old);
+}
+
+- (())exchangeObjectAtIndex:(NSUInteger)index1 withObjectAtIndex:(NSUInteger)index2 {
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index1, len) || !check_index(env, this, index2, len) {
+        return;
+    }
+    let array = &mut env.objc.borrow_mut::<ArrayHostObject>(this).array;
+    array.swap(index1 as usize, index2 as usize);
+}
+
 - (())removeObjectAtIndex:(NSUInteger)index {
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len) {
+        return;
+    }
     let object = env.objc.borrow_mut::<ArrayHostObject>(this).array.remove(index as usize);
     release(env, object)
 }
 
 - (())removeLastObject {
-    let object = env.objc.borrow_mut::<ArrayHostObject>(this).array.pop().unwrap();
+    let Some(object) = env.objc.borrow_mut::<ArrayHostObject>(this).array.pop() else {
+        let reason = format!("*** removeLastObject on empty array {:?}", this);
+        ns_exception::raise(env, "NSRangeException", reason);
+        return;
+    };
     release(env, object)
 }
 
@@ -294,10 +497,38 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow_mut::<ArrayHostObject>(this).array.push(object);
 }
 
+- (())insertObject:(id)object atIndex:(NSUInteger)index {
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len + 1) {
+        return;
+    }
+    let array = &mut env.objc.borrow_mut::<ArrayHostObject>(this).array;
+    array.insert(index as usize, object);
+}
+
+- (())replaceObjectAtIndex:(NSUInteger)index withObject:(id)object {
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len) {
+        return;
+    }
+    env.objc.borrow_mut::<ArrayHostObject>(this).array[index as usize] = object;
+}
+
 - (())removeObjectAtIndex:(NSUInteger)index {
+    let len = env.objc.borrow::<ArrayHostObject>(this).array.len();
+    if !check_index(env, this, index, len) {
+        return;
+    }
     env.objc.borrow_mut::<ArrayHostObject>(this).array.remove(index as usize);
 }
 
+- (())removeLastObject {
+    if env.objc.borrow_mut::<ArrayHostObject>(this).array.pop().is_none() {
+        let reason = format!("*** removeLastObject on empty array {:?}", this);
+        ns_exception::raise(env, "NSRangeException", reason);
+    }
+}
+
 @end
 
 };
@@ -310,3 +541,15 @@ pub fn from_vec(env: &mut Environment, objects: Vec<id>) -> id {
     env.objc.borrow_mut::<ArrayHostObject>(array).array = objects;
     array
 }
+
+/// Shortcut for host code, returns the elements of an array (or subclass) in
+/// order. The elements are not retained.
+pub fn to_vec(env: &mut Environment, array: id) -> Vec<id> {
+    let count: NSUInteger = msg![env; array count];
+    let mut objects = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let object: id = msg![env; array objectAtIndex:i];
+        objects.push(object);
+    }
+    objects
+}
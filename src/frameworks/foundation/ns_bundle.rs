@@ -5,6 +5,7 @@
  */
 //! `NSBundle`.
 
+use super::ns_property_list_serialization::deserialize_strings_file;
 use super::{ns_string, NSUInteger};
 use crate::bundle::Bundle;
 use crate::frameworks::core_foundation::cf_bundle::{
@@ -12,12 +13,12 @@ use crate::frameworks::core_foundation::cf_bundle::{
 };
 use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str, to_rust_string};
 use crate::frameworks::uikit::ui_nib::load_nib_file;
-use crate::fs::GuestPathBuf;
+use crate::fs::{GuestPath, GuestPathBuf};
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
 };
 use crate::Environment;
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 // Should be ISO 639-1 (or ISO 639-2) compliant
 // TODO: complete this list or use some crate for mapping
@@ -50,6 +51,9 @@ pub struct NSBundleHostObject {
     bundle_url: Option<id>,
     /// `NSDictionary*` for the `Info.plist` content. [None] if not created yet.
     info_dictionary: Option<id>,
+    /// `NSDictionary*` for each `.strings` table that has been looked up, or
+    /// `nil` if the table doesn't exist.
+    string_tables: HashMap<String, id>,
 }
 impl HostObject for NSBundleHostObject {}
 
@@ -70,6 +74,7 @@ pub const CLASSES: ClassExports = objc_classes! {
             bundle_path,
             bundle_url: None,
             info_dictionary: None,
+            string_tables: HashMap::new(),
         };
         let new = env.objc.alloc_object(
             this,
@@ -92,6 +97,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         bundle_path: _, // FIXME?
         bundle_url,
         info_dictionary,
+        string_tables: _,
     } = env.objc.borrow(this);
     if let Some(bundle_url) = bundle_url {
         release(env, bundle_url);
@@ -99,6 +105,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     if let Some(info_dictionary) = info_dictionary {
         release(env, info_dictionary);
     }
+    let string_tables = std::mem::take(&mut env.objc.borrow_mut::<NSBundleHostObject>(this).string_tables);
+    for (_, table) in string_tables {
+        release(env, table);
+    }
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -163,32 +173,39 @@ pub const CLASSES: ClassExports = objc_classes! {
         return path
     }
 
-    // Try preferred languages in order of preference
+    // Try preferred languages in order of preference, then the development
+    // language, then English.
     let langs: id = msg_class![env; NSLocale preferredLanguages];
     let lang_count: NSUInteger = msg![env; langs count];
-    let mut unknown_codes = HashSet::new();
+    let mut lang_codes = Vec::with_capacity(lang_count as usize + 2);
     for i in 0..lang_count {
         let lang_code: id = msg![env; langs objectAtIndex:i];
-        let lang_code = ns_string::to_rust_string(env, lang_code); // TODO: avoid copy
-        if let Some(&(_, lproj)) = LANG_ID_TO_LANG_PROJ.iter().find(|&&(code, _)| code == lang_code) {
-            let lproj: id = ns_string::get_static_str(env, lproj);
+        lang_codes.push(ns_string::to_rust_string(env, lang_code).to_string());
+    }
+    let info_dictionary: id = msg![env; this infoDictionary];
+    let development_region_key = get_static_str(env, "CFBundleDevelopmentRegion");
+    let development_region: id = msg![env; info_dictionary objectForKey:development_region_key];
+    if development_region != nil {
+        lang_codes.push(ns_string::to_rust_string(env, development_region).to_string());
+    }
+    lang_codes.push("en".to_string());
+
+    for lang_code in lang_codes {
+        // Both "en.lproj" and "English.lproj" style names are used by apps.
+        let mut lprojs = vec![format!("{}.lproj", lang_code)];
+        if let Some(&(_, long_name)) = LANG_ID_TO_LANG_PROJ.iter().find(|&&(code, _)| code == lang_code) {
+            lprojs.push(long_name.to_string());
+        }
+        for lproj in lprojs {
+            let lproj: id = ns_string::from_rust_string(env, lproj);
             let localized_path = path_for_resource_helper(env, this, name, lproj, directory, extension);
+            release(env, lproj);
             if localized_path != nil {
                 return localized_path;
             }
-        } else {
-            unknown_codes.insert(lang_code);
         }
     }
-
-    // As a last resort, fallback to English
-    // TODO: fallback to a development language (CFBundleDevelopmentRegion from
-    // Info.plist)
-    if !unknown_codes.is_empty() {
-        log!("TODO: language codes {:?} aren't mapped to a language name, falling back to English", unknown_codes);
-    }
-    let lproj: id = ns_string::get_static_str(env, "English.lproj");
-    path_for_resource_helper(env, this, name, lproj, directory, extension)
+    nil
 }
 - (id)pathForResource:(id)name // NSString*
                ofType:(id)extension { // NSString*
@@ -200,6 +217,9 @@ pub const CLASSES: ClassExports = objc_classes! {
    let path_string: id = msg![env; this pathForResource:name
                                                  ofType:extension
                                             inDirectory:subpath];
+   if path_string == nil {
+       return nil;
+   }
    let path_url: id = msg_class![env; NSURL alloc];
    let path_url: id = msg![env; path_url initFileURLWithPath:path_string];
    autorelease(env, path_url)
@@ -227,11 +247,55 @@ pub const CLASSES: ClassExports = objc_classes! {
     dict
 }
 
+- (id)objectForInfoDictionaryKey:(id)key { // NSString*
+    let info_dictionary: id = msg![env; this infoDictionary];
+    msg![env; info_dictionary objectForKey:key]
+}
+
+- (id)bundleIdentifier {
+    let key = get_static_str(env, "CFBundleIdentifier");
+    msg![env; this objectForInfoDictionaryKey:key]
+}
+
 - (id)localizations {
     let localizations = CFBundleCopyBundleLocalizations(env, this);
     autorelease(env, localizations)
 }
 
+- (id)localizedStringForKey:(id)key // NSString*
+                       value:(id)value // NSString*
+                       table:(id)table_name { // NSString*
+    let table_name = if table_name == nil {
+        "Localizable".to_string()
+    } else {
+        ns_string::to_rust_string(env, table_name).to_string()
+    };
+    let table = string_table(env, this, table_name);
+
+    let localized: id = if key != nil && table != nil {
+        msg![env; table objectForKey:key]
+    } else {
+        nil
+    };
+    if localized != nil {
+        return localized;
+    }
+
+    // Fall back to the value, or if there's no value, the key.
+    let value_length: NSUInteger = if value != nil {
+        msg![env; value length]
+    } else {
+        0
+    };
+    if value_length != 0 {
+        value
+    } else if key != nil {
+        key
+    } else {
+        get_static_str(env, "")
+    }
+}
+
 // TODO: constructors, more accessors
 
 @end
@@ -264,3 +328,33 @@ fn path_for_resource_helper(
     }
     nil
 }
+
+/// Get the `NSDictionary*` for a `.strings` table, loading it if necessary.
+/// Returns `nil` if there's no such table.
+fn string_table(env: &mut Environment, bundle: id, table_name: String) -> id {
+    if let Some(&table) = env
+        .objc
+        .borrow::<NSBundleHostObject>(bundle)
+        .string_tables
+        .get(&table_name)
+    {
+        return table;
+    }
+
+    let name = ns_string::from_rust_string(env, table_name.clone());
+    let extension = get_static_str(env, "strings");
+    let path: id = msg![env; bundle pathForResource:name ofType:extension];
+    release(env, name);
+    let table = if path != nil {
+        let path = ns_string::to_rust_string(env, path);
+        deserialize_strings_file(env, GuestPath::new(&path))
+    } else {
+        log_dbg!("No strings table {:?} found.", table_name);
+        nil
+    };
+    env.objc
+        .borrow_mut::<NSBundleHostObject>(bundle)
+        .string_tables
+        .insert(table_name, table);
+    table
+}
@@ -0,0 +1,202 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSCache`.
+//!
+//! There's no memory pressure to respond to, so objects are only evicted when
+//! the count or cost limit is exceeded, least recently used first.
+
+use super::NSUInteger;
+use crate::objc::{
+    id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+
+struct CacheEntry {
+    /// Strong reference. Unlike `NSDictionary`, keys aren't copied.
+    key: id,
+    /// Strong reference.
+    object: id,
+    cost: NSUInteger,
+}
+
+#[derive(Default)]
+struct NSCacheHostObject {
+    name: id,
+    /// Weak reference.
+    delegate: id,
+    /// Entries in order of use, least recently used first.
+    entries: Vec<CacheEntry>,
+    /// Zero means no limit.
+    count_limit: NSUInteger,
+    /// Zero means no limit.
+    total_cost_limit: NSUInteger,
+}
+impl HostObject for NSCacheHostObject {}
+impl NSCacheHostObject {
+    fn total_cost(&self) -> NSUInteger {
+        self.entries
+            .iter()
+            .fold(0, |total, entry| total.saturating_add(entry.cost))
+    }
+
+    fn over_limit(&self) -> bool {
+        (self.count_limit != 0 && self.entries.len() > self.count_limit as usize)
+            || (self.total_cost_limit != 0 && self.total_cost() > self.total_cost_limit)
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSCache: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSCacheHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    let name = host_object.name;
+    let entries = std::mem::take(&mut host_object.entries);
+    release(env, name);
+    for CacheEntry { key, object, .. } in entries {
+        release(env, key);
+        release(env, object);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)name {
+    env.objc.borrow::<NSCacheHostObject>(this).name
+}
+- (())setName:(id)name { // NSString*
+    let name: id = msg![env; name copy];
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    let old_name = std::mem::replace(&mut host_object.name, name);
+    release(env, old_name);
+}
+
+- (id)delegate {
+    env.objc.borrow::<NSCacheHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // id<NSCacheDelegate>
+    env.objc.borrow_mut::<NSCacheHostObject>(this).delegate = delegate;
+}
+
+- (NSUInteger)countLimit {
+    env.objc.borrow::<NSCacheHostObject>(this).count_limit
+}
+- (())setCountLimit:(NSUInteger)limit {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).count_limit = limit;
+    evict_over_limit(env, this);
+}
+
+- (NSUInteger)totalCostLimit {
+    env.objc.borrow::<NSCacheHostObject>(this).total_cost_limit
+}
+- (())setTotalCostLimit:(NSUInteger)limit {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).total_cost_limit = limit;
+    evict_over_limit(env, this);
+}
+
+- (bool)evictsObjectsWithDiscardedContent {
+    true
+}
+- (())setEvictsObjectsWithDiscardedContent:(bool)_evicts {
+    // NSDiscardableContent isn't supported, so this doesn't matter.
+}
+
+- (id)objectForKey:(id)key {
+    let Some(index) = find_entry(env, this, key) else {
+        return nil;
+    };
+    // Mark the entry as the most recently used.
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    let entry = host_object.entries.remove(index);
+    let object = entry.object;
+    host_object.entries.push(entry);
+    object
+}
+
+- (())setObject:(id)object forKey:(id)key {
+    let cost: NSUInteger = 0;
+    msg![env; this setObject:object forKey:key cost:cost]
+}
+- (())setObject:(id)object forKey:(id)key cost:(NSUInteger)cost {
+    if object == nil {
+        // Apple's implementation throws an exception in this case.
+        log!("Warning: [(NSCache*){:?} setObject:nil forKey:{:?}], ignoring", this, key);
+        return;
+    }
+    retain(env, object);
+    let replaced = if let Some(index) = find_entry(env, this, key) {
+        let entry = env.objc.borrow_mut::<NSCacheHostObject>(this).entries.remove(index);
+        release(env, entry.key);
+        Some(entry.object)
+    } else {
+        None
+    };
+    retain(env, key);
+    env.objc.borrow_mut::<NSCacheHostObject>(this).entries.push(CacheEntry { key, object, cost });
+    if let Some(replaced) = replaced {
+        release(env, replaced);
+    }
+    evict_over_limit(env, this);
+}
+
+- (())removeObjectForKey:(id)key {
+    if let Some(index) = find_entry(env, this, key) {
+        remove_entry(env, this, index);
+    }
+}
+
+- (())removeAllObjects {
+    while !env.objc.borrow::<NSCacheHostObject>(this).entries.is_empty() {
+        remove_entry(env, this, 0);
+    }
+}
+
+@end
+
+};
+
+fn find_entry(env: &mut Environment, cache: id, key: id) -> Option<usize> {
+    let keys: Vec<id> = env
+        .objc
+        .borrow::<NSCacheHostObject>(cache)
+        .entries
+        .iter()
+        .map(|entry| entry.key)
+        .collect();
+    keys.into_iter()
+        .position(|candidate| candidate == key || msg![env; candidate isEqual:key])
+}
+
+/// Remove an entry, telling the delegate first.
+fn remove_entry(env: &mut Environment, cache: id, index: usize) {
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(cache);
+    let delegate = host_object.delegate;
+    let CacheEntry { key, object, .. } = host_object.entries.remove(index);
+    if delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, "cache:willEvictObject:")
+    {
+        () = msg![env; delegate cache:cache willEvictObject:object];
+    }
+    release(env, key);
+    release(env, object);
+}
+
+/// Evict the least recently used entries until the cache is within its
+/// limits.
+fn evict_over_limit(env: &mut Environment, cache: id) {
+    while env.objc.borrow::<NSCacheHostObject>(cache).over_limit() {
+        remove_entry(env, cache, 0);
+    }
+}
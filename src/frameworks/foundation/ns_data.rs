@@ -5,14 +5,21 @@
  */
 //! `NSData` and `NSMutableData`.
 
-use super::ns_string::to_rust_string;
+mod base64;
+
+use super::ns_string::{from_rust_string, to_rust_string};
 use super::{NSRange, NSUInteger};
 use crate::fs::GuestPath;
-use crate::mem::{ConstVoidPtr, MutPtr, MutVoidPtr, Ptr};
+use crate::mem::{ConstVoidPtr, Mem, MutPtr, MutVoidPtr, Ptr};
 use crate::objc::{
     autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+    ObjC,
 };
 use crate::{msg_class, Environment};
+use base64::{
+    NSDataBase64DecodingIgnoreUnknownCharacters, NSDataBase64DecodingOptions,
+    NSDataBase64EncodingOptions,
+};
 
 struct NSDataHostObject {
     bytes: MutVoidPtr,
@@ -101,31 +108,58 @@ pub const CLASSES: ClassExports = objc_classes! {
         release(env, this);
         return nil;
     };
-    let size = bytes.len().try_into().unwrap();
-    let alloc = env.mem.alloc(size);
-    let slice = env.mem.bytes_at_mut(alloc.cast(), size);
-    slice.copy_from_slice(&bytes);
+    set_contents(env, this, &bytes);
+    this
+}
 
-    let host_object = env.objc.borrow_mut::<NSDataHostObject>(this);
-    host_object.bytes = alloc;
-    host_object.length = size;
+- (id)initWithBase64EncodedString:(id)string // NSString*
+                          options:(NSDataBase64DecodingOptions)options {
+    let string = to_rust_string(env, string);
+    let Some(bytes) = base64::decode(&string, options) else {
+        log_dbg!("Invalid base64 {:?}, returning nil", string);
+        release(env, this);
+        return nil;
+    };
+    set_contents(env, this, &bytes);
     this
 }
 
+// Deprecated predecessor of the above, which always ignores unknown characters.
+- (id)initWithBase64Encoding:(id)string { // NSString*
+    msg![env; this initWithBase64EncodedString:string
+                                       options:NSDataBase64DecodingIgnoreUnknownCharacters]
+}
+
+- (id)base64EncodedStringWithOptions:(NSDataBase64EncodingOptions)options {
+    let encoded = base64::encode(as_slice(&env.objc, &env.mem, this), options);
+    let encoded = from_rust_string(env, encoded);
+    autorelease(env, encoded)
+}
+
+- (id)base64Encoding {
+    msg![env; this base64EncodedStringWithOptions:(0 as NSDataBase64EncodingOptions)]
+}
+
+// The bytes in hexadecimal, grouped into 32-bit words, e.g. <0123abcd 4567>.
+- (id)description {
+    let mut description = String::from("<");
+    for (i, byte) in as_slice(&env.objc, &env.mem, this).iter().enumerate() {
+        if i != 0 && i % 4 == 0 {
+            description.push(' ');
+        }
+        description.push_str(&format!("{:02x}", byte));
+    }
+    description.push('>');
+    let description = from_rust_string(env, description);
+    autorelease(env, description)
+}
+
 // FIXME: writes should be atomic
 - (bool)writeToFile:(id)path // NSString*
          atomically:(bool)_use_aux_file {
     let file = to_rust_string(env, path);
     log_dbg!("[(NSData*){:?} writeToFile:{:?} atomically:_]", this, file);
-    let host_object = env.objc.borrow::<NSDataHostObject>(this);
-    // Mem::bytes_at() panics when the pointer is NULL, but NSData's pointer can
-    // be NULL if the length is 0.
-    let slice = if host_object.length == 0 {
-        &[]
-    } else {
-        env.mem.bytes_at(host_object.bytes.cast(), host_object.length)
-    };
-    env.fs.write(GuestPath::new(&file), slice).is_ok()
+    env.fs.write(GuestPath::new(&file), as_slice(&env.objc, &env.mem, this)).is_ok()
 }
 
 - (())dealloc {
@@ -202,6 +236,31 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Like [to_rust_slice], but allows the data to be empty.
+fn as_slice<'a>(objc: &'a ObjC, mem: &'a Mem, data: id) -> &'a [u8] {
+    let host_object = objc.borrow::<NSDataHostObject>(data);
+    // Mem::bytes_at() panics when the pointer is NULL, but NSData's pointer can
+    // be NULL if the length is 0.
+    if host_object.length == 0 {
+        &[]
+    } else {
+        mem.bytes_at(host_object.bytes.cast(), host_object.length)
+    }
+}
+
+/// Give a freshly-initialized NSData a copy of `bytes`.
+fn set_contents(env: &mut Environment, data: id, bytes: &[u8]) {
+    let size = bytes.len().try_into().unwrap();
+    let alloc = env.mem.alloc(size);
+    let slice = env.mem.bytes_at_mut(alloc.cast(), size);
+    slice.copy_from_slice(bytes);
+
+    let host_object = env.objc.borrow_mut::<NSDataHostObject>(data);
+    assert!(host_object.bytes.is_null() && host_object.length == 0);
+    host_object.bytes = alloc;
+    host_object.length = size;
+}
+
 pub fn to_rust_slice(env: &mut Environment, data: id) -> &[u8] {
     let borrowed_data = env.objc.borrow::<NSDataHostObject>(data);
     assert!(!borrowed_data.bytes.is_null() && borrowed_data.length != 0);
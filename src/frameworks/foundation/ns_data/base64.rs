@@ -0,0 +1,177 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Base64 encoding and decoding for NSData, with the options from
+//! `NSDataBase64EncodingOptions` and `NSDataBase64DecodingOptions`.
+
+use crate::frameworks::foundation::NSUInteger;
+
+pub type NSDataBase64EncodingOptions = NSUInteger;
+pub const NSDataBase64Encoding64CharacterLineLength: NSDataBase64EncodingOptions = 1 << 0;
+pub const NSDataBase64Encoding76CharacterLineLength: NSDataBase64EncodingOptions = 1 << 1;
+pub const NSDataBase64EncodingEndLineWithCarriageReturn: NSDataBase64EncodingOptions = 1 << 4;
+pub const NSDataBase64EncodingEndLineWithLineFeed: NSDataBase64EncodingOptions = 1 << 5;
+
+pub type NSDataBase64DecodingOptions = NSUInteger;
+pub const NSDataBase64DecodingIgnoreUnknownCharacters: NSDataBase64DecodingOptions = 1 << 0;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(bytes: &[u8], options: NSDataBase64EncodingOptions) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let indices = [
+            b[0] >> 2,
+            ((b[0] & 0x03) << 4) | (b[1] >> 4),
+            ((b[1] & 0x0f) << 2) | (b[2] >> 6),
+            b[2] & 0x3f,
+        ];
+        // A chunk of n bytes needs n + 1 characters, the rest is padding.
+        for (i, &index) in indices.iter().enumerate() {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    let line_length = if options & NSDataBase64Encoding64CharacterLineLength != 0 {
+        64
+    } else if options & NSDataBase64Encoding76CharacterLineLength != 0 {
+        76
+    } else {
+        return encoded;
+    };
+    let line_ending = match (
+        options & NSDataBase64EncodingEndLineWithCarriageReturn != 0,
+        options & NSDataBase64EncodingEndLineWithLineFeed != 0,
+    ) {
+        (true, false) => "\r",
+        (false, true) => "\n",
+        // CRLF is the default.
+        _ => "\r\n",
+    };
+    // The encoded string is pure ASCII, so it can be split at any byte.
+    encoded
+        .as_bytes()
+        .chunks(line_length)
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect::<Vec<_>>()
+        .join(line_ending)
+}
+
+/// Returns [None] if the string isn't valid base64. Line breaks and other
+/// characters outside the base64 alphabet are only allowed if
+/// `NSDataBase64DecodingIgnoreUnknownCharacters` is used.
+pub fn decode(string: &str, options: NSDataBase64DecodingOptions) -> Option<Vec<u8>> {
+    let ignore_unknown = options & NSDataBase64DecodingIgnoreUnknownCharacters != 0;
+
+    let mut values = Vec::with_capacity(string.len());
+    let mut padding = 0;
+    for c in string.bytes() {
+        if c == b'=' {
+            padding += 1;
+            continue;
+        }
+        let Some(value) = ALPHABET.iter().position(|&a| a == c) else {
+            if ignore_unknown {
+                continue;
+            }
+            return None;
+        };
+        // Padding is only allowed at the end.
+        if padding != 0 {
+            return None;
+        }
+        values.push(value as u8);
+    }
+    if (values.len() + padding) % 4 != 0 || padding > 2 || values.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(values.len() / 4 * 3 + 2);
+    for chunk in values.chunks(4) {
+        let v = [
+            chunk[0],
+            chunk[1],
+            chunk.get(2).copied().unwrap_or(0),
+            chunk.get(3).copied().unwrap_or(0),
+        ];
+        let bytes = [
+            (v[0] << 2) | (v[1] >> 4),
+            (v[1] << 4) | (v[2] >> 2),
+            (v[2] << 6) | v[3],
+        ];
+        // n characters encode n - 1 bytes.
+        decoded.extend_from_slice(&bytes[..chunk.len() - 1]);
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b"", 0), "");
+        assert_eq!(encode(b"f", 0), "Zg==");
+        assert_eq!(encode(b"fo", 0), "Zm8=");
+        assert_eq!(encode(b"foo", 0), "Zm9v");
+        assert_eq!(encode(b"foobar", 0), "Zm9vYmFy");
+        assert_eq!(encode(&[0xff, 0xfe, 0x00], 0), "//4A");
+
+        let long = [0u8; 60];
+        let encoded = encode(&long, NSDataBase64Encoding64CharacterLineLength);
+        assert_eq!(encoded.len(), 80 + 2);
+        assert_eq!(&encoded[64..66], "\r\n");
+        assert!(!encoded.ends_with('\n'));
+        let encoded = encode(
+            &long,
+            NSDataBase64Encoding76CharacterLineLength | NSDataBase64EncodingEndLineWithLineFeed,
+        );
+        assert_eq!(&encoded[76..77], "\n");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("", 0).as_deref(), Some(&b""[..]));
+        assert_eq!(decode("Zg==", 0).as_deref(), Some(&b"f"[..]));
+        assert_eq!(decode("Zm8=", 0).as_deref(), Some(&b"fo"[..]));
+        assert_eq!(decode("Zm9vYmFy", 0).as_deref(), Some(&b"foobar"[..]));
+        assert_eq!(decode("//4A", 0).as_deref(), Some(&[0xff, 0xfe, 0x00][..]));
+
+        // Padding is required, and must be at the end.
+        assert_eq!(decode("Zg", 0), None);
+        assert_eq!(decode("Zg=", 0), None);
+        assert_eq!(decode("Z===", 0), None);
+        assert_eq!(decode("Zg==Zg==", 0), None);
+
+        // Unknown characters, including line breaks, are an error unless they
+        // are ignored.
+        assert_eq!(decode("Zm9v\r\nYmFy", 0), None);
+        assert_eq!(
+            decode("Zm9v\r\nYm Fy", NSDataBase64DecodingIgnoreUnknownCharacters).as_deref(),
+            Some(&b"foobar"[..])
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..bytes.len() {
+            let options = NSDataBase64Encoding64CharacterLineLength;
+            let encoded = encode(&bytes[..len], options);
+            let decoded = decode(&encoded, NSDataBase64DecodingIgnoreUnknownCharacters);
+            assert_eq!(decoded.as_deref(), Some(&bytes[..len]));
+        }
+    }
+}
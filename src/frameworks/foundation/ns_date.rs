@@ -7,9 +7,8 @@
 
 use super::NSTimeInterval;
 use crate::frameworks::core_foundation::time::apple_epoch;
-use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject};
-
-use std::time::SystemTime;
+use crate::libc::time::wall_clock_now;
+use crate::objc::{autorelease, id, msg, objc_classes, ClassExports, HostObject};
 
 struct NSDateHostObject {
     time_interval: NSTimeInterval,
@@ -25,7 +24,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)date {
     // "Date objects are immutable, representing an invariant time interval
     // relative to an absolute reference date (00:00:00 UTC on 1 January 2001)."
-    let time_interval = SystemTime::now()
+    let time_interval = wall_clock_now(env)
         .duration_since(apple_epoch())
         .unwrap()
         .as_secs_f64();
@@ -36,6 +35,18 @@ pub const CLASSES: ClassExports = objc_classes! {
     log_dbg!("[NSDate date] => {:?} ({:?}s)", new, time_interval);
     autorelease(env, new)
 }
++ (id)dateWithTimeIntervalSinceReferenceDate:(NSTimeInterval)time_interval {
+    let host_object = Box::new(NSDateHostObject {
+        time_interval
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)dateWithTimeIntervalSinceNow:(NSTimeInterval)seconds {
+    let now: id = msg![env; this date];
+    let time_interval: NSTimeInterval = msg![env; now timeIntervalSinceReferenceDate];
+    msg![env; this dateWithTimeIntervalSinceReferenceDate:(time_interval + seconds)]
+}
 
 - (NSTimeInterval)timeIntervalSinceDate:(id)anotherDate {
     assert!(!anotherDate.is_null());
@@ -11,10 +11,14 @@
 
 use crate::frameworks::core_foundation::time::CFAbsoluteTimeGetGregorianDate;
 use crate::frameworks::foundation::{ns_string, NSTimeInterval};
-use crate::objc::{id, msg, nil, objc_classes, ClassExports, HostObject, NSZonePtr};
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
 
 struct NSDateFormatterHostObject {
     date_format: Option<id>,
+    /// Strong reference. If this is [nil], the default time zone is used.
+    time_zone: id,
 }
 impl HostObject for NSDateFormatterHostObject {}
 
@@ -27,10 +31,38 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(NSDateFormatterHostObject {
         date_format: None,
+        time_zone: nil,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
+- (())dealloc {
+    let &NSDateFormatterHostObject {
+        date_format,
+        time_zone,
+    } = env.objc.borrow(this);
+    if let Some(date_format) = date_format {
+        release(env, date_format);
+    }
+    release(env, time_zone);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)timeZone {
+    let time_zone = env.objc.borrow::<NSDateFormatterHostObject>(this).time_zone;
+    if time_zone == nil {
+        msg_class![env; NSTimeZone defaultTimeZone]
+    } else {
+        time_zone
+    }
+}
+- (())setTimeZone:(id)time_zone { // NSTimeZone*
+    retain(env, time_zone);
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.time_zone, time_zone);
+    release(env, old);
+}
+
 - (())setDateFormat:(id)format { // NSString *
     let date_format: id = msg![env; format copy];
     env.objc.borrow_mut::<NSDateFormatterHostObject>(this).date_format = Some(date_format);
@@ -38,13 +70,15 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (id)stringFromDate:(id)date {
     let &NSDateFormatterHostObject {
-        date_format
+        date_format,
+        ..
     } = env.objc.borrow(this);
     let mut format = ns_string::to_rust_string(env, date_format.unwrap()).to_string().clone();
     log_dbg!("date_format before: {:?}", format);
 
     let ti: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
-    let greg_date = CFAbsoluteTimeGetGregorianDate(env, ti, nil);
+    let time_zone: id = msg![env; this timeZone];
+    let greg_date = CFAbsoluteTimeGetGregorianDate(env, ti, time_zone);
     let year = greg_date.year;
     let month = greg_date.month;
     let day = greg_date.day;
@@ -5,13 +5,15 @@
  */
 //! The `NSDictionary` class cluster, including `NSMutableDictionary`.
 
+use super::ns_enumerator::{fast_enumeration_helper, NSFastEnumerationState};
 use super::ns_property_list_serialization::deserialize_plist_from_file;
-use super::{ns_string, ns_url, NSUInteger};
-use crate::abi::VaList;
+use super::{ns_array, ns_string, ns_url, NSComparisonResult, NSUInteger};
+use crate::abi::{CallFromHost, VaList};
 use crate::fs::GuestPath;
+use crate::mem::MutPtr;
 use crate::objc::{
-    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
-    NSZonePtr,
+    autorelease, get_block_invoke, id, msg, msg_class, msg_send, nil, objc_classes, release,
+    retain, ClassExports, HostObject, NSZonePtr, SEL,
 };
 use crate::Environment;
 use std::collections::HashMap;
@@ -39,7 +41,7 @@ impl DictionaryHostObject {
             return nil;
         };
         for &(candidate_key, value) in collisions {
-            if candidate_key == key || msg![env; candidate_key isEqualTo:key] {
+            if candidate_key == key || msg![env; candidate_key isEqual:key] {
                 return value;
             }
         }
@@ -61,7 +63,7 @@ impl DictionaryHostObject {
             return;
         };
         for &mut (candidate_key, ref mut existing_value) in collisions.iter_mut() {
-            if candidate_key == key || msg![env; candidate_key isEqualTo:key] {
+            if candidate_key == key || msg![env; candidate_key isEqual:key] {
                 release(env, *existing_value);
                 *existing_value = value;
                 return;
@@ -70,6 +72,29 @@ impl DictionaryHostObject {
         collisions.push((key, value));
         self.count += 1;
     }
+    pub(super) fn remove(&mut self, env: &mut Environment, key: id) {
+        let hash: Hash = msg![env; key hash];
+        let Some(collisions) = self.map.get_mut(&hash) else {
+            return;
+        };
+        let mut found = None;
+        for (i, &(candidate_key, _value)) in collisions.iter().enumerate() {
+            if candidate_key == key || msg![env; candidate_key isEqual:key] {
+                found = Some(i);
+                break;
+            }
+        }
+        let Some(i) = found else {
+            return;
+        };
+        let (existing_key, existing_value) = collisions.remove(i);
+        if collisions.is_empty() {
+            self.map.remove(&hash);
+        }
+        self.count -= 1;
+        release(env, existing_key);
+        release(env, existing_value);
+    }
     pub(super) fn release(&mut self, env: &mut Environment) {
         for collisions in self.map.values() {
             for &(key, value) in collisions {
@@ -83,6 +108,17 @@ impl DictionaryHostObject {
     }
 }
 
+/// Shortcut for host code: copy the keys of a dictionary into a new
+/// (autoreleased) array.
+fn all_keys(env: &mut Environment, host_object: &DictionaryHostObject) -> id {
+    let keys: Vec<id> = host_object.iter_keys().collect();
+    for &key in &keys {
+        retain(env, key);
+    }
+    let array = ns_array::from_vec(env, keys);
+    autorelease(env, array)
+}
+
 /// Helper to enable sharing `dictionaryWithObjectsAndKeys:` and
 /// `initWithObjectsAndKeys:`' implementations without vararg passthrough.
 pub fn init_with_objects_and_keys(
@@ -182,11 +218,147 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // NSCopying implementation
 - (id)copyWithZone:(NSZonePtr)_zone {
-    // TODO: override this once we have NSMutableString!
     retain(env, this)
 }
 
-// TODO
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)_zone {
+    let new: id = msg_class![env; NSMutableDictionary alloc];
+    let new: id = msg![env; new init];
+    () = msg![env; new addEntriesFromDictionary:this];
+    new
+}
+
+// These are implemented in terms of the primitive methods (plus allKeys), so
+// that they also work for subclasses.
+
+- (id)allValues {
+    let keys: id = msg![env; this allKeys];
+    let count: NSUInteger = msg![env; keys count];
+    let mut values = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let key: id = msg![env; keys objectAtIndex:i];
+        let value: id = msg![env; this objectForKey:key];
+        values.push(retain(env, value));
+    }
+    let array = ns_array::from_vec(env, values);
+    autorelease(env, array)
+}
+
+- (id)objectEnumerator { // NSEnumerator*
+    let values: id = msg![env; this allValues];
+    msg![env; values objectEnumerator]
+}
+
+- (id)keysSortedByValueUsingSelector:(SEL)comparator {
+    let keys: id = msg![env; this allKeys];
+    let count: NSUInteger = msg![env; keys count];
+    let mut pairs = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let key: id = msg![env; keys objectAtIndex:i];
+        let value: id = msg![env; this objectForKey:key];
+        pairs.push((key, value));
+    }
+    pairs.sort_by(|&(_, a), &(_, b)| {
+        let res: NSComparisonResult = msg_send(env, (a, comparator, b));
+        res.cmp(&0)
+    });
+    let sorted_keys = pairs.into_iter().map(|(key, _)| retain(env, key)).collect();
+    let array = ns_array::from_vec(env, sorted_keys);
+    autorelease(env, array)
+}
+
+- (())enumerateKeysAndObjectsUsingBlock:(id)block {
+    // Snapshot the keys so that the block can't invalidate the iteration.
+    let keys: id = msg![env; this allKeys];
+    let count: NSUInteger = msg![env; keys count];
+    let invoke = get_block_invoke(&env.mem, block);
+    let stop: MutPtr<bool> = env.mem.alloc_and_write(false);
+    for i in 0..count {
+        let key: id = msg![env; keys objectAtIndex:i];
+        let value: id = msg![env; this objectForKey:key];
+        () = invoke.call_from_host(env, (block, key, value, stop));
+        if env.mem.read(stop) {
+            break;
+        }
+    }
+    env.mem.free(stop.cast());
+}
+
+@end
+
+// NSMutableDictionary is an abstract class. A subclass must provide everything
+// NSDictionary provides, plus:
+// - (void)setObject:(id)object forKey:(id)key;
+// - (void)removeObjectForKey:(id)key;
+// Note that it inherits from NSDictionary, so we must ensure we override any
+// default methods that would be inappropriate for mutability.
+@implementation NSMutableDictionary: NSDictionary
+
++ (id)allocWithZone:(NSZonePtr)zone {
+    // NSDictionary might be subclassed by something which needs allocWithZone:
+    // to have the normal behaviour. Unimplemented: call superclass alloc then.
+    assert!(this == env.objc.get_known_class("NSMutableDictionary", &mut env.mem));
+    msg_class![env; _touchHLE_NSMutableDictionary allocWithZone:zone]
+}
+
++ (id)dictionaryWithCapacity:(NSUInteger)capacity {
+    let new_dict: id = msg![env; this alloc];
+    let new_dict: id = msg![env; new_dict initWithCapacity:capacity];
+    autorelease(env, new_dict)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let keys: id = msg![env; this allKeys];
+    let count: NSUInteger = msg![env; keys count];
+    let mut keys_and_objects = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let key: id = msg![env; keys objectAtIndex:i];
+        let value: id = msg![env; this objectForKey:key];
+        keys_and_objects.push((key, value));
+    }
+    dict_from_keys_and_objects(env, &keys_and_objects)
+}
+
+- (id)initWithCapacity:(NSUInteger)_capacity {
+    msg![env; this init]
+}
+
+// These are implemented in terms of the primitive methods, so that they also
+// work for subclasses.
+
+- (())addEntriesFromDictionary:(id)other { // NSDictionary*
+    // Entries from the other dictionary replace existing ones.
+    let keys: id = msg![env; other allKeys];
+    let count: NSUInteger = msg![env; keys count];
+    for i in 0..count {
+        let key: id = msg![env; keys objectAtIndex:i];
+        let value: id = msg![env; other objectForKey:key];
+        () = msg![env; this setObject:value forKey:key];
+    }
+}
+
+- (())removeObjectsForKeys:(id)keys { // NSArray*
+    let count: NSUInteger = msg![env; keys count];
+    for i in 0..count {
+        let key: id = msg![env; keys objectAtIndex:i];
+        () = msg![env; this removeObjectForKey:key];
+    }
+}
+
+- (())removeAllObjects {
+    let keys: id = msg![env; this allKeys];
+    msg![env; this removeObjectsForKeys:keys]
+}
+
+- (())setDictionary:(id)other { // NSDictionary*
+    // Keep the other dictionary alive in case it's owned by this one.
+    retain(env, other);
+    () = msg![env; this removeAllObjects];
+    () = msg![env; this addEntriesFromDictionary:other];
+    release(env, other);
+}
 
 @end
 
@@ -214,7 +386,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
-// TODO: enumeration, more init methods, etc
+// TODO: more init methods, etc
 
 - (NSUInteger)count {
     env.objc.borrow::<DictionaryHostObject>(this).count
@@ -226,6 +398,94 @@ pub const CLASSES: ClassExports = objc_classes! {
     res
 }
 
+- (id)allKeys {
+    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let res = all_keys(env, &host_obj);
+    *env.objc.borrow_mut(this) = host_obj;
+    res
+}
+- (id)keyEnumerator { // NSEnumerator*
+    let keys: id = msg![env; this allKeys];
+    msg![env; keys objectEnumerator]
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    let mut iterator = env.objc.borrow::<DictionaryHostObject>(this).iter_keys();
+    fast_enumeration_helper(&mut env.mem, this, &mut iterator, state, stackbuf, len)
+}
+
+@end
+
+// Our private subclass that is the single implementation of
+// NSMutableDictionary for the time being.
+@implementation _touchHLE_NSMutableDictionary: NSMutableDictionary
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<DictionaryHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    std::mem::take(env.objc.borrow_mut::<DictionaryHostObject>(this)).release(env);
+
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)initWithObjectsAndKeys:(id)first_object, ...dots {
+    init_with_objects_and_keys(env, this, first_object, dots.start())
+}
+
+- (id)init {
+    *env.objc.borrow_mut(this) = <DictionaryHostObject as Default>::default();
+    this
+}
+
+- (NSUInteger)count {
+    env.objc.borrow::<DictionaryHostObject>(this).count
+}
+- (id)objectForKey:(id)key {
+    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let res = host_obj.lookup(env, key);
+    *env.objc.borrow_mut(this) = host_obj;
+    res
+}
+
+- (id)allKeys {
+    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let res = all_keys(env, &host_obj);
+    *env.objc.borrow_mut(this) = host_obj;
+    res
+}
+- (id)keyEnumerator { // NSEnumerator*
+    let keys: id = msg![env; this allKeys];
+    msg![env; keys objectEnumerator]
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    let mut iterator = env.objc.borrow::<DictionaryHostObject>(this).iter_keys();
+    fast_enumeration_helper(&mut env.mem, this, &mut iterator, state, stackbuf, len)
+}
+
+- (())setObject:(id)object forKey:(id)key {
+    assert!(object != nil); // TODO: raise proper exception
+    assert!(key != nil); // TODO: raise proper exception
+    let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_obj.insert(env, key, object, /* copy_key: */ true);
+    *env.objc.borrow_mut(this) = host_obj;
+}
+
+- (())removeObjectForKey:(id)key {
+    let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_obj.remove(env, key);
+    *env.objc.borrow_mut(this) = host_obj;
+}
+
 @end
 
 };
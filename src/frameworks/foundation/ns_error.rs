@@ -52,6 +52,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.dealloc_object(this, &mut env.mem);
 }
 
+- (id)domain {
+    env.objc.borrow::<ErrorHostObject>(this).domain
+}
+- (NSInteger)code {
+    env.objc.borrow::<ErrorHostObject>(this).code
+}
+- (id)userInfo {
+    env.objc.borrow::<ErrorHostObject>(this).user_info
+}
 
 @end
 
@@ -4,10 +4,203 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use crate::dyld::{ConstantExports, HostConstant};
+//! `NSException`.
+
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string, with_format};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{ConstVoidPtr, Ptr};
+use crate::objc::{
+    alloc_ehtype, autorelease, id, msg, msg_class, nil, objc_classes, objc_exception_throw,
+    release, retain, ClassExports, HostObject, NSZonePtr, ObjC,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Set with `NSSetUncaughtExceptionHandler`.
+    uncaught_exception_handler: Option<GuestFunction>,
+}
+
+struct NSExceptionHostObject {
+    /// `NSString*`
+    name: id,
+    /// `NSString*`, may be nil.
+    reason: id,
+    /// `NSDictionary*`, may be nil.
+    user_info: id,
+}
+impl HostObject for NSExceptionHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSException: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSExceptionHostObject {
+        name: nil,
+        reason: nil,
+        user_info: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)exceptionWithName:(id)name // NSString*
+                 reason:(id)reason // NSString*
+               userInfo:(id)user_info { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithName:name reason:reason userInfo:user_info];
+    autorelease(env, new)
+}
+
++ (())raise:(id)name // NSString*
+     format:(id)format, // NSString*
+     ...args {
+    let reason = with_format(env, format, args.start());
+    let reason = from_rust_string(env, reason);
+    let exception: id = msg![env; this exceptionWithName:name reason:reason userInfo:nil];
+    release(env, reason);
+    objc_exception_throw(env, exception);
+}
+
+- (id)initWithName:(id)name // NSString*
+            reason:(id)reason // NSString*
+          userInfo:(id)user_info { // NSDictionary*
+    let name: id = msg![env; name copy];
+    let reason: id = msg![env; reason copy];
+    retain(env, user_info);
+    let host_object = env.objc.borrow_mut::<NSExceptionHostObject>(this);
+    host_object.name = name;
+    host_object.reason = reason;
+    host_object.user_info = user_info;
+    this
+}
+
+- (())dealloc {
+    let &NSExceptionHostObject { name, reason, user_info } = env.objc.borrow(this);
+    release(env, name);
+    release(env, reason);
+    release(env, user_info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (id)name {
+    env.objc.borrow::<NSExceptionHostObject>(this).name
+}
+- (id)reason {
+    env.objc.borrow::<NSExceptionHostObject>(this).reason
+}
+- (id)userInfo {
+    env.objc.borrow::<NSExceptionHostObject>(this).user_info
+}
+
+- (id)description {
+    env.objc.borrow::<NSExceptionHostObject>(this).reason
+}
+
+- (())raise {
+    objc_exception_throw(env, this);
+}
+
+@end
+
+};
+
+/// Shortcut for host code: raise an `NSException` with the given name (one of
+/// the `NSExceptionName` constants) and reason.
+///
+/// Like [objc_exception_throw], this only takes effect once the calling host
+/// function returns, so the caller must return without doing anything else.
+pub fn raise(env: &mut Environment, name: &'static str, reason: String) {
+    log_dbg!("Raising {}: {}", name, reason);
+    let name = get_static_str(env, name);
+    let reason = from_rust_string(env, reason);
+    let exception: id = msg_class![env; NSException exceptionWithName:name
+                                                               reason:reason
+                                                             userInfo:nil];
+    release(env, reason);
+    objc_exception_throw(env, exception);
+}
+
+/// Called when nothing handles an exception. Like Apple's Foundation, this
+/// reports the exception, calls the app's uncaught exception handler (if any)
+/// and then terminates the app.
+pub fn uncaught_exception(env: &mut Environment, exception: id) -> ! {
+    let ns_exception = env.objc.get_known_class("NSException", &mut env.mem);
+    let is_ns_exception = exception != nil
+        && env
+            .objc
+            .class_is_subclass_of(ObjC::read_isa(exception, &env.mem), ns_exception);
+    if is_ns_exception {
+        let &NSExceptionHostObject { name, reason, .. } = env.objc.borrow(exception);
+        let name = to_rust_string(env, name);
+        let reason = if reason == nil {
+            "(null)".into()
+        } else {
+            to_rust_string(env, reason)
+        };
+        echo!(
+            "*** Terminating app due to uncaught exception '{}', reason: '{}'",
+            name,
+            reason
+        );
+    } else {
+        echo!(
+            "*** Terminating app due to uncaught exception {:?}",
+            exception
+        );
+    }
+    // The guest stack is still intact at this point, so this shows where the
+    // exception was thrown from.
+    env.stack_trace();
+
+    if let Some(handler) = env
+        .framework_state
+        .foundation
+        .ns_exception
+        .uncaught_exception_handler
+    {
+        log_dbg!("Calling uncaught exception handler {:?}", handler);
+        () = handler.call_from_host(env, (exception,));
+    }
+
+    echo!("Exiting due to uncaught exception.");
+    env.exit(1)
+}
+
+fn NSGetUncaughtExceptionHandler(env: &mut Environment) -> ConstVoidPtr {
+    env.framework_state
+        .foundation
+        .ns_exception
+        .uncaught_exception_handler
+        .map_or(Ptr::null(), |handler| handler.to_ptr())
+}
+
+fn NSSetUncaughtExceptionHandler(env: &mut Environment, handler: GuestFunction) {
+    env.framework_state
+        .foundation
+        .ns_exception
+        .uncaught_exception_handler = (!handler.to_ptr().is_null()).then_some(handler);
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(NSGetUncaughtExceptionHandler()),
+    export_c_func!(NSSetUncaughtExceptionHandler(_)),
+];
 
-// All constants are NSExceptionName
 pub const CONSTANTS: ConstantExports = &[
+    (
+        "_OBJC_EHTYPE_$_NSException",
+        HostConstant::Custom(|mem| alloc_ehtype(mem, "NSException")),
+    ),
+    // The rest are NSExceptionName
     (
         "_NSCharacterConversionException",
         HostConstant::NSString("NSCharacterConversionException"),
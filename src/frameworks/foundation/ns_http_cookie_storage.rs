@@ -0,0 +1,791 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSHTTPCookie` and `NSHTTPCookieStorage`.
+//!
+//! The cookies are kept host-side. Cookies that aren't session-only are saved
+//! to a file in the app's sandbox directory on the host, so that logins and
+//! the like survive relaunching the app. `NSURLConnection` uses
+//! [cookie_header_for_url] and [store_cookies_from_response] to send and
+//! receive cookies.
+//!
+//! Resources:
+//! - [RFC 6265](https://www.rfc-editor.org/rfc/rfc6265)
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use super::ns_url::{network_location, NetworkLocation};
+use super::{ns_array, NSTimeInterval, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_foundation::time::apple_epoch;
+use crate::libc::time::wall_clock_now;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::paths;
+use crate::Environment;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+pub type NSHTTPCookieAcceptPolicy = NSUInteger;
+const NSHTTPCookieAcceptPolicyNever: NSHTTPCookieAcceptPolicy = 1;
+
+const NSHTTPCookieName: &str = "Name";
+const NSHTTPCookieValue: &str = "Value";
+const NSHTTPCookieOriginURL: &str = "OriginURL";
+const NSHTTPCookieDomain: &str = "Domain";
+const NSHTTPCookiePath: &str = "Path";
+const NSHTTPCookieSecure: &str = "Secure";
+const NSHTTPCookieExpires: &str = "Expires";
+const NSHTTPCookieMaximumAge: &str = "Max-Age";
+const NSHTTPCookieDiscard: &str = "Discard";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSHTTPCookieName",
+        HostConstant::NSString(NSHTTPCookieName),
+    ),
+    (
+        "_NSHTTPCookieValue",
+        HostConstant::NSString(NSHTTPCookieValue),
+    ),
+    (
+        "_NSHTTPCookieOriginURL",
+        HostConstant::NSString(NSHTTPCookieOriginURL),
+    ),
+    (
+        "_NSHTTPCookieDomain",
+        HostConstant::NSString(NSHTTPCookieDomain),
+    ),
+    (
+        "_NSHTTPCookiePath",
+        HostConstant::NSString(NSHTTPCookiePath),
+    ),
+    (
+        "_NSHTTPCookieSecure",
+        HostConstant::NSString(NSHTTPCookieSecure),
+    ),
+    (
+        "_NSHTTPCookieExpires",
+        HostConstant::NSString(NSHTTPCookieExpires),
+    ),
+    (
+        "_NSHTTPCookieMaximumAge",
+        HostConstant::NSString(NSHTTPCookieMaximumAge),
+    ),
+    (
+        "_NSHTTPCookieDiscard",
+        HostConstant::NSString(NSHTTPCookieDiscard),
+    ),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    /// Lower-case. A leading `.` means subdomains also match, otherwise only
+    /// this exact host does.
+    domain: String,
+    path: String,
+    /// Time since the reference date (see `NSDate`). [None] for session-only
+    /// cookies.
+    expires: Option<NSTimeInterval>,
+    secure: bool,
+    http_only: bool,
+}
+impl Cookie {
+    fn is_expired(&self, now: NSTimeInterval) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Whether setting `other` would replace this cookie.
+    fn same_identity(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    fn matches(&self, location: &NetworkLocation) -> bool {
+        let host_matches = match self.domain.strip_prefix('.') {
+            Some(domain) => domain_matches(&location.host, domain),
+            None => location.host == self.domain,
+        };
+        host_matches
+            && path_matches(request_path(location), &self.path)
+            && (!self.secure || location.scheme == "https")
+    }
+}
+
+/// Whether `host` is `domain` or a subdomain of it.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.ends_with('.'))
+}
+
+/// Whether `request_path` is `cookie_path` or is inside it.
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || request_path
+            .strip_prefix(cookie_path)
+            .is_some_and(|rest| cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+/// The path of a request, without the query.
+fn request_path(location: &NetworkLocation) -> &str {
+    location.path.split('?').next().unwrap()
+}
+
+#[derive(Default)]
+pub struct State {
+    /// `NSHTTPCookieStorage*`
+    shared_storage: Option<id>,
+    /// The default is `NSHTTPCookieAcceptPolicyAlways`.
+    accept_policy: NSHTTPCookieAcceptPolicy,
+    /// Loaded lazily, see [cookies].
+    cookies: Option<Vec<Cookie>>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut State {
+        &mut env.framework_state.foundation.ns_http_cookie_storage
+    }
+}
+
+#[derive(Default)]
+struct NSHTTPCookieHostObject {
+    cookie: Option<Cookie>,
+}
+impl HostObject for NSHTTPCookieHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSHTTPCookie: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSHTTPCookieHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)cookieWithProperties:(id)properties { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithProperties:properties];
+    autorelease(env, new)
+}
+
++ (id)cookiesWithResponseHeaderFields:(id)header_fields // NSDictionary*
+                               forURL:(id)url { // NSURL*
+    let location = network_location(env, url);
+    let set_cookie = header_field(env, header_fields, "Set-Cookie");
+    let now = now(env);
+    let cookies: Vec<id> = match (location, set_cookie) {
+        (Some(location), Some(set_cookie)) => split_set_cookie_header(&set_cookie)
+            .into_iter()
+            .filter_map(|header| parse_set_cookie(header, &location, now))
+            .map(|cookie| new_cookie_object(env, cookie))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let array = ns_array::from_vec(env, cookies);
+    autorelease(env, array)
+}
+
++ (id)requestHeaderFieldsWithCookies:(id)cookies { // NSArray<NSHTTPCookie*>*
+    let count: NSUInteger = msg![env; cookies count];
+    let mut pairs = Vec::new();
+    for i in 0..count {
+        let cookie: id = msg![env; cookies objectAtIndex:i];
+        let cookie = env.objc.borrow::<NSHTTPCookieHostObject>(cookie).cookie.as_ref().unwrap();
+        pairs.push(format!("{}={}", cookie.name, cookie.value));
+    }
+    if pairs.is_empty() {
+        return msg_class![env; NSDictionary dictionary];
+    }
+    let key = get_static_str(env, "Cookie");
+    let value = from_rust_string(env, pairs.join("; "));
+    let dict = dict_from_keys_and_objects(env, &[(key, value)]);
+    release(env, value);
+    autorelease(env, dict)
+}
+
+- (id)initWithProperties:(id)properties { // NSDictionary*
+    let Some(cookie) = cookie_from_properties(env, properties) else {
+        log_dbg!("[(NSHTTPCookie*){:?} initWithProperties:{:?}] => nil", this, properties);
+        release(env, this);
+        return nil;
+    };
+    env.objc.borrow_mut::<NSHTTPCookieHostObject>(this).cookie = Some(cookie);
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (id)name {
+    let name = cookie(env, this).name.clone();
+    let name = from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (id)value {
+    let value = cookie(env, this).value.clone();
+    let value = from_rust_string(env, value);
+    autorelease(env, value)
+}
+- (id)domain {
+    let domain = cookie(env, this).domain.clone();
+    let domain = from_rust_string(env, domain);
+    autorelease(env, domain)
+}
+- (id)path {
+    let path = cookie(env, this).path.clone();
+    let path = from_rust_string(env, path);
+    autorelease(env, path)
+}
+- (id)expiresDate {
+    match cookie(env, this).expires {
+        Some(expires) => msg_class![env; NSDate dateWithTimeIntervalSinceReferenceDate:expires],
+        None => nil,
+    }
+}
+- (bool)isSessionOnly {
+    cookie(env, this).expires.is_none()
+}
+- (bool)isSecure {
+    cookie(env, this).secure
+}
+- (bool)isHTTPOnly {
+    cookie(env, this).http_only
+}
+
+- (id)description {
+    let cookie = cookie(env, this);
+    let description = format!(
+        "<NSHTTPCookie {}={}; domain={}; path={}>",
+        cookie.name, cookie.value, cookie.domain, cookie.path
+    );
+    let description = from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+@implementation NSHTTPCookieStorage: NSObject
+
++ (id)sharedHTTPCookieStorage {
+    if let Some(existing) = State::get(env).shared_storage {
+        existing
+    } else {
+        let new: id = msg![env; this new];
+        State::get(env).shared_storage = Some(new);
+        new
+    }
+}
+
+- (NSHTTPCookieAcceptPolicy)cookieAcceptPolicy {
+    State::get(env).accept_policy
+}
+- (())setCookieAcceptPolicy:(NSHTTPCookieAcceptPolicy)policy {
+    State::get(env).accept_policy = policy;
+}
+
+- (id)cookies {
+    let cookies = cookies(env).clone();
+    cookie_array(env, cookies)
+}
+
+- (id)cookiesForURL:(id)url { // NSURL*
+    let cookies = match network_location(env, url) {
+        Some(location) => matching_cookies(env, &location),
+        None => Vec::new(),
+    };
+    cookie_array(env, cookies)
+}
+
+- (())setCookie:(id)cookie { // NSHTTPCookie*
+    if State::get(env).accept_policy == NSHTTPCookieAcceptPolicyNever {
+        return;
+    }
+    let cookie = env.objc.borrow::<NSHTTPCookieHostObject>(cookie).cookie.clone().unwrap();
+    set_cookie(env, cookie);
+}
+
+- (())setCookies:(id)cookies // NSArray<NSHTTPCookie*>*
+          forURL:(id)_url // NSURL*
+ mainDocumentURL:(id)_main_document_url { // NSURL*
+    let count: NSUInteger = msg![env; cookies count];
+    for i in 0..count {
+        let cookie: id = msg![env; cookies objectAtIndex:i];
+        () = msg![env; this setCookie:cookie];
+    }
+}
+
+- (())deleteCookie:(id)cookie { // NSHTTPCookie*
+    let cookie = env.objc.borrow::<NSHTTPCookieHostObject>(cookie).cookie.clone().unwrap();
+    let cookies = cookies(env);
+    let old_count = cookies.len();
+    cookies.retain(|existing| !existing.same_identity(&cookie));
+    if cookies.len() != old_count {
+        save_cookies(env);
+    }
+}
+
+@end
+
+};
+
+fn cookie(env: &mut Environment, cookie: id) -> &Cookie {
+    env.objc
+        .borrow::<NSHTTPCookieHostObject>(cookie)
+        .cookie
+        .as_ref()
+        .unwrap()
+}
+
+/// Create a new `NSHTTPCookie*` (not autoreleased).
+fn new_cookie_object(env: &mut Environment, cookie: Cookie) -> id {
+    let new: id = msg_class![env; NSHTTPCookie alloc];
+    env.objc.borrow_mut::<NSHTTPCookieHostObject>(new).cookie = Some(cookie);
+    new
+}
+
+/// Create an autoreleased `NSArray<NSHTTPCookie*>*`.
+fn cookie_array(env: &mut Environment, cookies: Vec<Cookie>) -> id {
+    let cookies = cookies
+        .into_iter()
+        .map(|cookie| new_cookie_object(env, cookie))
+        .collect();
+    let array = ns_array::from_vec(env, cookies);
+    autorelease(env, array)
+}
+
+fn now(env: &mut Environment) -> NSTimeInterval {
+    wall_clock_now(env)
+        .duration_since(apple_epoch())
+        .unwrap()
+        .as_secs_f64()
+}
+
+/// Get a value from a properties or header dictionary as a string. Keys are
+/// matched case-insensitively, like HTTP header names.
+fn header_field(env: &mut Environment, dict: id, key: &str) -> Option<String> {
+    let keys: id = msg![env; dict allKeys];
+    let count: NSUInteger = msg![env; keys count];
+    for i in 0..count {
+        let candidate: id = msg![env; keys objectAtIndex:i];
+        if to_rust_string(env, candidate).eq_ignore_ascii_case(key) {
+            let value: id = msg![env; dict objectForKey:candidate];
+            let value: id = msg![env; value description];
+            return Some(to_rust_string(env, value).into_owned());
+        }
+    }
+    None
+}
+
+fn cookie_from_properties(env: &mut Environment, properties: id) -> Option<Cookie> {
+    if properties == nil {
+        return None;
+    }
+    let name = header_field(env, properties, NSHTTPCookieName)?;
+    let value = header_field(env, properties, NSHTTPCookieValue)?;
+    let domain = match header_field(env, properties, NSHTTPCookieDomain) {
+        Some(domain) => domain.to_ascii_lowercase(),
+        None => {
+            let origin_url = header_field(env, properties, NSHTTPCookieOriginURL)?;
+            let origin_url = from_rust_string(env, origin_url);
+            let origin_url = autorelease(env, origin_url);
+            let origin_url: id = msg_class![env; NSURL URLWithString:origin_url];
+            network_location(env, origin_url)?.host
+        }
+    };
+    let path = header_field(env, properties, NSHTTPCookiePath).unwrap_or_else(|| "/".to_string());
+
+    let expires = if let Some(max_age) = header_field(env, properties, NSHTTPCookieMaximumAge) {
+        Some(now(env) + max_age.trim().parse::<f64>().ok()?)
+    } else {
+        let date_key = get_static_str(env, NSHTTPCookieExpires);
+        let date: id = msg![env; properties objectForKey:date_key];
+        let date_class = env.objc.get_known_class("NSDate", &mut env.mem);
+        if date == nil {
+            None
+        } else if msg![env; date isKindOfClass:date_class] {
+            let expires: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+            Some(expires)
+        } else {
+            let date: id = msg![env; date description];
+            Some(parse_http_date(&to_rust_string(env, date))?)
+        }
+    };
+    let discard = header_field(env, properties, NSHTTPCookieDiscard)
+        .is_some_and(|discard| discard.eq_ignore_ascii_case("TRUE"));
+    let secure = header_field(env, properties, NSHTTPCookieSecure)
+        .is_some_and(|secure| !secure.eq_ignore_ascii_case("FALSE") && secure != "0");
+
+    Some(Cookie {
+        name,
+        value,
+        domain,
+        path,
+        expires: if discard { None } else { expires },
+        secure,
+        http_only: false,
+    })
+}
+
+/// Split a `Set-Cookie` header value that may contain several cookies joined
+/// with commas. Commas also appear in `Expires` dates, so a comma only starts
+/// a new cookie if it's followed by something that looks like `name=value`.
+fn split_set_cookie_header(header: &str) -> Vec<&str> {
+    let mut cookies = Vec::new();
+    let mut start = 0;
+    for (comma, _) in header.match_indices(',') {
+        let next = &header[comma + 1..];
+        let next_attribute = next.split(|c| c == ';' || c == ',').next().unwrap();
+        if next_attribute.contains('=') {
+            cookies.push(&header[start..comma]);
+            start = comma + 1;
+        }
+    }
+    cookies.push(&header[start..]);
+    cookies
+}
+
+/// Parse a `Set-Cookie` header value received in response to a request to
+/// `location`. Returns [None] if it's malformed or not allowed to be set.
+fn parse_set_cookie(
+    header: &str,
+    location: &NetworkLocation,
+    now: NSTimeInterval,
+) -> Option<Cookie> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next().unwrap().split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut domain = None;
+    let mut path = None;
+    let mut expires = None;
+    let mut max_age = None;
+    let mut secure = false;
+    let mut http_only = false;
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "expires" => expires = parse_http_date(value).or(expires),
+            "max-age" => max_age = value.parse::<i64>().ok().or(max_age),
+            "domain" if !value.is_empty() => {
+                domain = Some(value.trim_start_matches('.').to_ascii_lowercase())
+            }
+            "path" if value.starts_with('/') => path = Some(value.to_string()),
+            "secure" => secure = true,
+            "httponly" => http_only = true,
+            _ => (),
+        }
+    }
+
+    let domain = match domain {
+        Some(domain) => {
+            // A server can't set cookies for unrelated domains.
+            if !domain_matches(&location.host, &domain) {
+                log!(
+                    "Warning: ignoring cookie {:?} for domain {:?} set by {:?}",
+                    name,
+                    domain,
+                    location.host
+                );
+                return None;
+            }
+            format!(".{}", domain)
+        }
+        None => location.host.clone(),
+    };
+    let path = path.unwrap_or_else(|| {
+        // The default path is the directory of the request path.
+        let request_path = request_path(location);
+        match request_path.rfind('/') {
+            Some(0) | None => "/".to_string(),
+            Some(last_slash) => request_path[..last_slash].to_string(),
+        }
+    });
+    // Max-Age takes priority over Expires. Zero or less means the cookie
+    // should be deleted right away.
+    let expires = match max_age {
+        Some(max_age) if max_age <= 0 => Some(f64::MIN),
+        Some(max_age) => Some(now + max_age as f64),
+        None => expires,
+    };
+
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain,
+        path,
+        expires,
+        secure,
+        http_only,
+    })
+}
+
+/// Parse an HTTP date like `Sun, 06 Nov 1994 08:49:37 GMT`, returning the time
+/// since the reference date (see `NSDate`). This is lenient in the same way
+/// as RFC 6265's algorithm, so older formats also work.
+fn parse_http_date(date: &str) -> Option<NSTimeInterval> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+    for token in date
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != ':')
+        .filter(|token| !token.is_empty())
+    {
+        if time.is_none() && token.contains(':') {
+            let parts: Vec<i64> = token
+                .split(':')
+                .filter_map(|part| part.parse().ok())
+                .collect();
+            if let &[hours, minutes, seconds] = &parts[..] {
+                time = Some((hours, minutes, seconds));
+            }
+        } else if token.bytes().all(|c| c.is_ascii_digit()) {
+            let number: i64 = token.parse().ok()?;
+            if day.is_none() && token.len() <= 2 {
+                day = Some(number);
+            } else if year.is_none() && (token.len() == 2 || token.len() == 4) {
+                year = Some(number);
+            }
+        } else if month.is_none() && token.len() >= 3 {
+            month = MONTHS
+                .iter()
+                .position(|&name| token[..3].eq_ignore_ascii_case(name))
+                .map(|index| index as i64 + 1);
+        }
+    }
+
+    let (hours, minutes, seconds) = time?;
+    let (day, month, year) = (day?, month?, year?);
+    let year = match year {
+        0..=69 => year + 2000,
+        70..=99 => year + 1900,
+        _ => year,
+    };
+    if !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+
+    // Days since the Unix epoch, using Howard Hinnant's days_from_civil
+    // algorithm.
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let unix_time = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    let apple_epoch = apple_epoch()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    Some((unix_time - apple_epoch) as NSTimeInterval)
+}
+
+fn cookies_file_path(env: &Environment) -> PathBuf {
+    paths::user_data_base_path()
+        .join(paths::SANDBOX_DIR)
+        .join(env.bundle.bundle_identifier())
+        .join("Cookies.plist")
+}
+
+/// Get the stored cookies, loading them from the host if necessary. Expired
+/// cookies are removed.
+fn cookies(env: &mut Environment) -> &mut Vec<Cookie> {
+    let now = now(env);
+    if State::get(env).cookies.is_none() {
+        let path = cookies_file_path(env);
+        let cookies = match plist::Value::from_file(&path) {
+            Ok(plist::Value::Array(array)) => array
+                .into_iter()
+                .filter_map(|cookie| {
+                    let cookie = cookie.into_dictionary()?;
+                    let string = |key: &str| Some(cookie.get(key)?.as_string()?.to_string());
+                    let boolean = |key: &str| cookie.get(key).and_then(|value| value.as_boolean());
+                    Some(Cookie {
+                        name: string("name")?,
+                        value: string("value")?,
+                        domain: string("domain")?,
+                        path: string("path")?,
+                        expires: Some(cookie.get("expires")?.as_real()?),
+                        secure: boolean("secure").unwrap_or(false),
+                        http_only: boolean("httpOnly").unwrap_or(false),
+                    })
+                })
+                .collect(),
+            Ok(_) => {
+                log!("Warning: {:?} is malformed, ignoring.", path);
+                Vec::new()
+            }
+            // Most likely the file just doesn't exist yet.
+            Err(_) => Vec::new(),
+        };
+        State::get(env).cookies = Some(cookies);
+    }
+    let cookies = State::get(env).cookies.as_mut().unwrap();
+    cookies.retain(|cookie| !cookie.is_expired(now));
+    cookies
+}
+
+/// Save the cookies that aren't session-only to the host.
+fn save_cookies(env: &mut Environment) {
+    let array: Vec<plist::Value> = cookies(env)
+        .iter()
+        .filter_map(|cookie| {
+            let mut dict = plist::Dictionary::new();
+            dict.insert("name".to_string(), cookie.name.clone().into());
+            dict.insert("value".to_string(), cookie.value.clone().into());
+            dict.insert("domain".to_string(), cookie.domain.clone().into());
+            dict.insert("path".to_string(), cookie.path.clone().into());
+            dict.insert("expires".to_string(), cookie.expires?.into());
+            dict.insert("secure".to_string(), cookie.secure.into());
+            dict.insert("httpOnly".to_string(), cookie.http_only.into());
+            Some(plist::Value::Dictionary(dict))
+        })
+        .collect();
+    let path = cookies_file_path(env);
+    if let Err(e) = plist::Value::Array(array).to_file_xml(&path) {
+        log!("Warning: couldn't save cookies to {:?}: {}", path, e);
+    }
+}
+
+/// Store a cookie, replacing any with the same name, domain and path. An
+/// expired cookie just deletes the existing one.
+fn set_cookie(env: &mut Environment, cookie: Cookie) {
+    log_dbg!("Setting cookie {:?}", cookie);
+    let cookies = cookies(env);
+    cookies.retain(|existing| !existing.same_identity(&cookie));
+    cookies.push(cookie);
+    save_cookies(env);
+}
+
+/// Get the cookies to send with a request, most specific path first.
+fn matching_cookies(env: &mut Environment, location: &NetworkLocation) -> Vec<Cookie> {
+    let mut cookies: Vec<Cookie> = cookies(env)
+        .iter()
+        .filter(|cookie| cookie.matches(location))
+        .cloned()
+        .collect();
+    cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+    cookies
+}
+
+/// For use by `NSURLConnection`: get the value of the `Cookie` header to send
+/// with a request, if there are any cookies for it.
+pub fn cookie_header_for_url(env: &mut Environment, location: &NetworkLocation) -> Option<String> {
+    let cookies = matching_cookies(env, location);
+    if cookies.is_empty() {
+        return None;
+    }
+    let pairs: Vec<String> = cookies
+        .iter()
+        .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+        .collect();
+    Some(pairs.join("; "))
+}
+
+/// For use by `NSURLConnection`: store the cookies from the `Set-Cookie`
+/// headers of a response, if the accept policy allows it.
+pub fn store_cookies_from_response(
+    env: &mut Environment,
+    location: &NetworkLocation,
+    set_cookie_headers: &[&str],
+) {
+    // TODO: NSHTTPCookieAcceptPolicyOnlyFromMainDocumentDomain
+    if State::get(env).accept_policy == NSHTTPCookieAcceptPolicyNever {
+        return;
+    }
+    let now = now(env);
+    for header in set_cookie_headers {
+        if let Some(cookie) = parse_set_cookie(header, location, now) {
+            set_cookie(env, cookie);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(url: &str) -> NetworkLocation {
+        let (scheme, rest) = url.split_once("://").unwrap();
+        let (host, path) = rest.split_at(rest.find('/').unwrap());
+        NetworkLocation {
+            scheme: scheme.to_string(),
+            host: host.to_string(),
+            port: None,
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn http_dates() {
+        // 2001-01-01 is the reference date.
+        assert_eq!(parse_http_date("Mon, 01 Jan 2001 00:00:00 GMT"), Some(0.0));
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some((784111777 - 978307200) as f64)
+        );
+        // RFC 850 format
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some((784111777 - 978307200) as f64)
+        );
+        assert_eq!(parse_http_date("not a date"), None);
+    }
+
+    #[test]
+    fn set_cookie_parsing_and_matching() {
+        let origin = location("http://www.example.com/account/login");
+        let cookie = parse_set_cookie(
+            "session=abc123; Path=/; Domain=.example.com; HttpOnly",
+            &origin,
+            0.0,
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "session");
+        assert_eq!(cookie.value, "abc123");
+        assert_eq!(cookie.domain, ".example.com");
+        assert_eq!(cookie.path, "/");
+        assert_eq!(cookie.expires, None);
+        assert!(cookie.http_only && !cookie.secure);
+        assert!(cookie.matches(&location("http://example.com/")));
+        assert!(cookie.matches(&location("http://api.example.com/x?y=z")));
+        assert!(!cookie.matches(&location("http://notexample.com/")));
+
+        // Without attributes: host-only, default path, expiry from Max-Age.
+        let cookie = parse_set_cookie("id=1; Max-Age=60", &origin, 100.0).unwrap();
+        assert_eq!(cookie.domain, "www.example.com");
+        assert_eq!(cookie.path, "/account");
+        assert_eq!(cookie.expires, Some(160.0));
+        assert!(cookie.matches(&location("http://www.example.com/account/settings")));
+        assert!(!cookie.matches(&location("http://www.example.com/accounts")));
+        assert!(!cookie.matches(&location("http://api.example.com/account")));
+
+        // Servers can't set cookies for other domains.
+        assert!(parse_set_cookie("a=b; Domain=other.com", &origin, 0.0).is_none());
+
+        assert_eq!(
+            split_set_cookie_header("a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT, b=2"),
+            ["a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", " b=2"]
+        );
+    }
+}
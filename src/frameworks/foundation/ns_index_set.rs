@@ -0,0 +1,146 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSIndexSet` and `NSMutableIndexSet`.
+
+use super::{NSNotFound, NSRange, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+#[derive(Default)]
+struct IndexSetHostObject {
+    indexes: BTreeSet<NSUInteger>,
+}
+impl HostObject for IndexSetHostObject {}
+
+fn range_to_indexes(range: NSRange) -> std::ops::Range<NSUInteger> {
+    let NSRange { location, length } = range;
+    location..location.checked_add(length).unwrap()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSIndexSet: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<IndexSetHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)indexSet {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
++ (id)indexSetWithIndex:(NSUInteger)index {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndex:index];
+    autorelease(env, new)
+}
++ (id)indexSetWithIndexesInRange:(NSRange)range {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndexesInRange:range];
+    autorelease(env, new)
+}
+
+- (id)initWithIndex:(NSUInteger)index {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).indexes.insert(index);
+    this
+}
+- (id)initWithIndexesInRange:(NSRange)range {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).indexes.extend(range_to_indexes(range));
+    this
+}
+- (id)initWithIndexSet:(id)other { // NSIndexSet*
+    let indexes = env.objc.borrow::<IndexSetHostObject>(other).indexes.clone();
+    env.objc.borrow_mut::<IndexSetHostObject>(this).indexes = indexes;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)_zone {
+    let new: id = msg_class![env; NSMutableIndexSet alloc];
+    msg![env; new initWithIndexSet:this]
+}
+
+- (NSUInteger)count {
+    env.objc.borrow::<IndexSetHostObject>(this).indexes.len().try_into().unwrap()
+}
+
+- (NSUInteger)firstIndex {
+    let host_object = env.objc.borrow::<IndexSetHostObject>(this);
+    host_object.indexes.first().copied().unwrap_or(NSNotFound as NSUInteger)
+}
+- (NSUInteger)lastIndex {
+    let host_object = env.objc.borrow::<IndexSetHostObject>(this);
+    host_object.indexes.last().copied().unwrap_or(NSNotFound as NSUInteger)
+}
+
+- (NSUInteger)indexGreaterThanIndex:(NSUInteger)index {
+    let host_object = env.objc.borrow::<IndexSetHostObject>(this);
+    host_object.indexes.range((Bound::Excluded(index), Bound::Unbounded)).next().copied()
+        .unwrap_or(NSNotFound as NSUInteger)
+}
+- (NSUInteger)indexLessThanIndex:(NSUInteger)index {
+    let host_object = env.objc.borrow::<IndexSetHostObject>(this);
+    host_object.indexes.range(..index).next_back().copied()
+        .unwrap_or(NSNotFound as NSUInteger)
+}
+
+- (bool)containsIndex:(NSUInteger)index {
+    env.objc.borrow::<IndexSetHostObject>(this).indexes.contains(&index)
+}
+
+@end
+
+@implementation NSMutableIndexSet: NSIndexSet
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let new: id = msg_class![env; NSIndexSet alloc];
+    msg![env; new initWithIndexSet:this]
+}
+
+- (())addIndex:(NSUInteger)index {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).indexes.insert(index);
+}
+- (())addIndexesInRange:(NSRange)range {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).indexes.extend(range_to_indexes(range));
+}
+
+- (())removeIndex:(NSUInteger)index {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).indexes.remove(&index);
+}
+- (())removeIndexesInRange:(NSRange)range {
+    let host_object = env.objc.borrow_mut::<IndexSetHostObject>(this);
+    for index in range_to_indexes(range) {
+        host_object.indexes.remove(&index);
+    }
+}
+- (())removeAllIndexes {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).indexes.clear();
+}
+
+@end
+
+};
+
+/// Shortcut for host code: get the indexes in an `NSIndexSet` in ascending
+/// order.
+pub fn to_vec(env: &mut Environment, index_set: id) -> Vec<NSUInteger> {
+    let host_object = env.objc.borrow::<IndexSetHostObject>(index_set);
+    host_object.indexes.iter().copied().collect()
+}
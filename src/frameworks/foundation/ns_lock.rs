@@ -3,16 +3,25 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! `NSLock`.
+//! `NSLock`, `NSCondition` and `NSConditionLock`.
+//!
+//! `NSCondition` and `NSConditionLock` don't use host mutexes, because a thread
+//! blocking on one only takes effect once the host function returns. Instead,
+//! waiting threads sleep cooperatively until they can proceed.
 
+use super::{NSInteger, NSTimeInterval};
 use crate::environment::ThreadId;
+use crate::libc::dispatch::wait_until;
 use crate::libc::pthread::mutex::{
     pthread_mutex_destroy, pthread_mutex_init, pthread_mutex_lock, pthread_mutex_t,
     pthread_mutex_unlock,
 };
 use crate::mem::{guest_size_of, MutPtr};
 use crate::msg;
-use crate::objc::{id, nil, objc_classes, ClassExports, HostObject};
+use crate::objc::{id, msg_class, nil, objc_classes, release, ClassExports, HostObject, NSZonePtr};
+use crate::Environment;
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
 struct NSLockHostObject {
     pthread_mutex_ptr: MutPtr<pthread_mutex_t>,
@@ -21,6 +30,27 @@ struct NSLockHostObject {
 }
 impl HostObject for NSLockHostObject {}
 
+#[derive(Default)]
+struct NSConditionHostObject {
+    name: id,
+    locked_by: Option<ThreadId>,
+    /// Tickets of the threads waiting to be signalled, oldest first.
+    waiting: VecDeque<u64>,
+    /// Tickets of the waiting threads that have been signalled but haven't
+    /// woken up yet.
+    signalled: HashSet<u64>,
+    next_ticket: u64,
+}
+impl HostObject for NSConditionHostObject {}
+
+#[derive(Default)]
+struct NSConditionLockHostObject {
+    name: id,
+    locked_by: Option<ThreadId>,
+    condition: NSInteger,
+}
+impl HostObject for NSConditionLockHostObject {}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -72,4 +102,213 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+@implementation NSCondition: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSConditionHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())lock {
+    let current_thread = env.current_thread;
+    assert!(env.objc.borrow::<NSConditionHostObject>(this).locked_by != Some(current_thread));
+    wait_until(env, None, |env| {
+        try_lock(&mut env.objc.borrow_mut::<NSConditionHostObject>(this).locked_by, current_thread)
+    });
+}
+
+- (())unlock {
+    let current_thread = env.current_thread;
+    let host_object = env.objc.borrow_mut::<NSConditionHostObject>(this);
+    if host_object.locked_by != Some(current_thread) {
+        echo!("*** -[NSCondition unlock]: condition (<NSCondition: {:?}> '{:?}') unlocked from thread {} which doesn't hold the lock", this, host_object.name, current_thread);
+    }
+    host_object.locked_by = None;
+}
+
+- (())wait {
+    condition_wait(env, this, None);
+}
+
+- (bool)waitUntilDate:(id)limit { // NSDate*
+    let deadline = instant_from_date(env, limit);
+    condition_wait(env, this, Some(deadline))
+}
+
+- (())signal {
+    let host_object = env.objc.borrow_mut::<NSConditionHostObject>(this);
+    if let Some(ticket) = host_object.waiting.pop_front() {
+        host_object.signalled.insert(ticket);
+    }
+}
+
+- (())broadcast {
+    let host_object = env.objc.borrow_mut::<NSConditionHostObject>(this);
+    let waiting = std::mem::take(&mut host_object.waiting);
+    host_object.signalled.extend(waiting);
+}
+
+- (())setName:(id)name { // NSString *
+    let name: id = msg![env; name copy];
+    let old_name = std::mem::replace(&mut env.objc.borrow_mut::<NSConditionHostObject>(this).name, name);
+    release(env, old_name);
+}
+- (id)name {
+    env.objc.borrow::<NSConditionHostObject>(this).name
+}
+
+- (())dealloc {
+    let name = env.objc.borrow::<NSConditionHostObject>(this).name;
+    release(env, name);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+@implementation NSConditionLock: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSConditionLockHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)init {
+    msg![env; this initWithCondition:0]
+}
+
+- (id)initWithCondition:(NSInteger)condition {
+    env.objc.borrow_mut::<NSConditionLockHostObject>(this).condition = condition;
+    this
+}
+
+- (NSInteger)condition {
+    env.objc.borrow::<NSConditionLockHostObject>(this).condition
+}
+
+- (())lock {
+    condition_lock_lock(env, this, None, None);
+}
+- (bool)lockBeforeDate:(id)limit { // NSDate*
+    let deadline = instant_from_date(env, limit);
+    condition_lock_lock(env, this, None, Some(deadline))
+}
+- (())lockWhenCondition:(NSInteger)condition {
+    condition_lock_lock(env, this, Some(condition), None);
+}
+- (bool)lockWhenCondition:(NSInteger)condition
+               beforeDate:(id)limit { // NSDate*
+    let deadline = instant_from_date(env, limit);
+    condition_lock_lock(env, this, Some(condition), Some(deadline))
+}
+
+- (bool)tryLock {
+    let current_thread = env.current_thread;
+    try_lock(&mut env.objc.borrow_mut::<NSConditionLockHostObject>(this).locked_by, current_thread)
+}
+- (bool)tryLockWhenCondition:(NSInteger)condition {
+    let current_thread = env.current_thread;
+    let host_object = env.objc.borrow_mut::<NSConditionLockHostObject>(this);
+    host_object.condition == condition && try_lock(&mut host_object.locked_by, current_thread)
+}
+
+- (())unlock {
+    let condition = env.objc.borrow::<NSConditionLockHostObject>(this).condition;
+    msg![env; this unlockWithCondition:condition]
+}
+- (())unlockWithCondition:(NSInteger)condition {
+    let current_thread = env.current_thread;
+    let host_object = env.objc.borrow_mut::<NSConditionLockHostObject>(this);
+    if host_object.locked_by != Some(current_thread) {
+        echo!("*** -[NSConditionLock unlockWithCondition:]: lock (<NSConditionLock: {:?}> '{:?}') unlocked from thread {} which doesn't hold the lock", this, host_object.name, current_thread);
+    }
+    host_object.locked_by = None;
+    host_object.condition = condition;
+}
+
+- (())setName:(id)name { // NSString *
+    let name: id = msg![env; name copy];
+    let old_name = std::mem::replace(&mut env.objc.borrow_mut::<NSConditionLockHostObject>(this).name, name);
+    release(env, old_name);
+}
+- (id)name {
+    env.objc.borrow::<NSConditionLockHostObject>(this).name
+}
+
+- (())dealloc {
+    let name = env.objc.borrow::<NSConditionLockHostObject>(this).name;
+    release(env, name);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
 };
+
+/// Take a lock for `thread` if nobody holds it.
+fn try_lock(locked_by: &mut Option<ThreadId>, thread: ThreadId) -> bool {
+    if locked_by.is_none() {
+        *locked_by = Some(thread);
+        true
+    } else {
+        false
+    }
+}
+
+fn instant_from_date(env: &mut Environment, date: id) -> Instant {
+    let now: id = msg_class![env; NSDate date];
+    let seconds: NSTimeInterval = msg![env; date timeIntervalSinceDate:now];
+    Instant::now() + Duration::from_secs_f64(seconds.max(0.0))
+}
+
+/// Implementation of `wait` and `waitUntilDate:` for `NSCondition`: release
+/// the lock, wait to be signalled, and then take the lock again. Returns
+/// [false] if the deadline passed without a signal.
+fn condition_wait(env: &mut Environment, condition: id, deadline: Option<Instant>) -> bool {
+    let current_thread = env.current_thread;
+    let host_object = env.objc.borrow_mut::<NSConditionHostObject>(condition);
+    assert!(host_object.locked_by == Some(current_thread));
+    let ticket = host_object.next_ticket;
+    host_object.next_ticket += 1;
+    host_object.waiting.push_back(ticket);
+    host_object.locked_by = None;
+
+    let signalled = wait_until(env, deadline, |env| {
+        env.objc
+            .borrow_mut::<NSConditionHostObject>(condition)
+            .signalled
+            .remove(&ticket)
+    });
+    if !signalled {
+        let host_object = env.objc.borrow_mut::<NSConditionHostObject>(condition);
+        host_object.waiting.retain(|&waiting| waiting != ticket);
+    }
+
+    // The lock must be held again when returning, even after a timeout.
+    wait_until(env, None, |env| {
+        try_lock(
+            &mut env
+                .objc
+                .borrow_mut::<NSConditionHostObject>(condition)
+                .locked_by,
+            current_thread,
+        )
+    });
+    signalled
+}
+
+/// Implementation of the locking methods of `NSConditionLock`. Returns [false]
+/// if the deadline passed without the lock being taken.
+fn condition_lock_lock(
+    env: &mut Environment,
+    lock: id,
+    condition: Option<NSInteger>,
+    deadline: Option<Instant>,
+) -> bool {
+    let current_thread = env.current_thread;
+    assert!(env.objc.borrow::<NSConditionLockHostObject>(lock).locked_by != Some(current_thread));
+    wait_until(env, deadline, |env| {
+        let host_object = env.objc.borrow_mut::<NSConditionLockHostObject>(lock);
+        condition.map_or(true, |condition| host_object.condition == condition)
+            && try_lock(&mut host_object.locked_by, current_thread)
+    })
+}
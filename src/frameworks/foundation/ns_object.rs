@@ -11,16 +11,72 @@
 //!   it calls "manual retain-release", not ARC.
 //! - Apple's [Key-Value Coding Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/KeyValueCoding/SearchImplementation.html)
 //!   explains the algorithm `setValue:forKey:` should follow.
+//! - Apple's [Key-Value Observing Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/KeyValueObserving/KeyValueObserving.html)
 //!
 //! See also: [crate::objc], especially the `objects` module.
 
-use super::ns_string::to_rust_string;
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_exception;
+use super::ns_string::{get_static_str, to_rust_string};
 use super::NSUInteger;
+use crate::dyld::{ConstantExports, HostConstant};
 use crate::mem::MutVoidPtr;
 use crate::objc::{
-    id, msg, msg_class, msg_send, objc_classes, Class, ClassExports, NSZonePtr, ObjC,
-    TrivialHostObject, SEL,
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, Class,
+    ClassExports, NSZonePtr, ObjC, TrivialHostObject, SEL,
 };
+use crate::Environment;
+
+type NSKeyValueObservingOptions = NSUInteger;
+const NSKeyValueObservingOptionNew: NSKeyValueObservingOptions = 0x01;
+const NSKeyValueObservingOptionOld: NSKeyValueObservingOptions = 0x02;
+const NSKeyValueObservingOptionInitial: NSKeyValueObservingOptions = 0x04;
+const NSKeyValueObservingOptionPrior: NSKeyValueObservingOptions = 0x08;
+
+const NSKeyValueChangeSetting: u64 = 1;
+
+const NSKeyValueChangeKindKey: &str = "kind";
+const NSKeyValueChangeNewKey: &str = "new";
+const NSKeyValueChangeOldKey: &str = "old";
+const NSKeyValueChangeNotificationIsPriorKey: &str = "notificationIsPrior";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSKeyValueChangeKindKey",
+        HostConstant::NSString(NSKeyValueChangeKindKey),
+    ),
+    (
+        "_NSKeyValueChangeNewKey",
+        HostConstant::NSString(NSKeyValueChangeNewKey),
+    ),
+    (
+        "_NSKeyValueChangeOldKey",
+        HostConstant::NSString(NSKeyValueChangeOldKey),
+    ),
+    (
+        "_NSKeyValueChangeNotificationIsPriorKey",
+        HostConstant::NSString(NSKeyValueChangeNotificationIsPriorKey),
+    ),
+];
+
+/// A key-value observation. These are stored by [crate::objc::ObjC], see
+/// [crate::objc::ObjC::key_value_observations].
+#[derive(Clone)]
+pub struct Observation {
+    /// Weak reference
+    observer: id,
+    key_path: String,
+    options: NSKeyValueObservingOptions,
+    context: MutVoidPtr,
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Values saved by `willChangeValueForKey:` for the matching
+    /// `didChangeValueForKey:`: the object, the key and the old value (strong
+    /// reference, may be [nil] if no observer wanted it).
+    pending_changes: Vec<(id, String, id)>,
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -55,11 +111,26 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (())autorelease {
     // classes are not refcounted
 }
++ (NSUInteger)retainCount {
+    NSUInteger::MAX // classes are not refcounted
+}
 
 + (bool)instancesRespondToSelector:(SEL)selector {
     env.objc.class_has_method(this, selector)
 }
 
++ (Class)superclass {
+    env.objc.get_superclass(this)
+}
++ (bool)isSubclassOfClass:(Class)class {
+    env.objc.class_is_subclass_of(this, class)
+}
+
+// NSKeyValueObserving
++ (bool)automaticallyNotifiesObserversForKey:(id)_key { // NSString*
+    true
+}
+
 - (id)init {
     this
 }
@@ -80,6 +151,9 @@ pub const CLASSES: ClassExports = objc_classes! {
     () = msg_class![env; NSAutoreleasePool addObject:this];
     this
 }
+- (NSUInteger)retainCount {
+    env.objc.get_refcount(this).map_or(NSUInteger::MAX, |count| count.get())
+}
 
 - (())dealloc {
     log_dbg!("[{:?} dealloc]", this);
@@ -97,6 +171,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     let this_class: Class = msg![env; this class];
     env.objc.class_is_subclass_of(this_class, class)
 }
+- (Class)superclass {
+    let this_class: Class = msg![env; this class];
+    env.objc.get_superclass(this_class)
+}
+- (bool)isProxy {
+    false
+}
 
 - (NSUInteger)hash {
     this.to_bits()
@@ -104,6 +185,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (bool)isEqual:(id)other {
     this == other
 }
+// Subclasses should override isEqual:, not this.
+- (bool)isEqualTo:(id)other {
+    msg![env; this isEqual:other]
+}
 
 // TODO: description and debugDescription (both the instance and class method).
 // This is not hard to add, but before adding a fallback implementation of it,
@@ -124,32 +209,185 @@ pub const CLASSES: ClassExports = objc_classes! {
 // NSKeyValueCoding
 - (())setValue:(id)value
        forKey:(id)key { // NSString*
-    let key = to_rust_string(env, key); // TODO: avoid copy?
-    assert!(key.is_ascii()); // TODO: do we have to handle non-ASCII keys?
+    let key_string = to_rust_string(env, key); // TODO: avoid copy?
+    assert!(key_string.is_ascii()); // TODO: do we have to handle non-ASCII keys?
 
     let class = msg![env; this class];
 
-    if let Some(sel) = env.objc.lookup_selector(&format!(
-        "set{}{}:",
-        key.as_bytes()[0].to_ascii_uppercase() as char,
-        &key[1..],
-    )) {
-        if env.objc.class_has_method(class, sel) {
-            return msg_send(env, (this, sel, value));
+    let capitalized = format!(
+        "{}{}",
+        key_string.as_bytes()[0].to_ascii_uppercase() as char,
+        &key_string[1..],
+    );
+    let Some(sel) = [format!("set{}:", capitalized), format!("_set{}:", capitalized)]
+        .into_iter()
+        .filter_map(|name| env.objc.lookup_selector(&name))
+        .find(|&sel| env.objc.class_has_method(class, sel))
+    else {
+        unimplemented!("TODO: object {:?} does not have simple setter method for {}, use fallback", this, key_string);
+    };
+
+    // This is what makes key-value observing work. Apple's implementation
+    // swaps the class of observed objects for a subclass that overrides the
+    // setters, so that calling them directly also notifies observers.
+    // TODO: do that too?
+    let notifies: bool = msg![env; class automaticallyNotifiesObserversForKey:key];
+    if notifies {
+        () = msg![env; this willChangeValueForKey:key];
+    }
+    () = msg_send(env, (this, sel, value));
+    if notifies {
+        () = msg![env; this didChangeValueForKey:key];
+    }
+}
+- (id)valueForKey:(id)key { // NSString*
+    let key_string = to_rust_string(env, key); // TODO: avoid copy?
+    assert!(key_string.is_ascii()); // TODO: do we have to handle non-ASCII keys?
+
+    let class = msg![env; this class];
+
+    let capitalized = format!(
+        "{}{}",
+        key_string.as_bytes()[0].to_ascii_uppercase() as char,
+        &key_string[1..],
+    );
+    for name in [
+        format!("get{}", capitalized),
+        key_string.to_string(),
+        format!("is{}", capitalized),
+        format!("_{}", key_string),
+    ] {
+        if let Some(sel) = env.objc.lookup_selector(&name) {
+            if env.objc.class_has_method(class, sel) {
+                // TODO: wrap non-object return values in NSNumber or NSValue.
+                // That needs the method's type encoding, which we don't have.
+                return msg_send(env, (this, sel));
+            }
         }
     }
 
-    if let Some(sel) = env.objc.lookup_selector(&format!(
-        "_set{}{}:",
-        key.as_bytes()[0].to_ascii_uppercase() as char,
-        &key[1..],
-    )) {
-        if env.objc.class_has_method(class, sel) {
-            return msg_send(env, (this, sel, value));
+    // TODO: look up instance variables before giving up
+    msg![env; this valueForUndefinedKey:key]
+}
+- (id)valueForUndefinedKey:(id)key { // NSString*
+    let key = to_rust_string(env, key);
+    let class: Class = msg![env; this class];
+    let reason = format!(
+        "[<{} {:?}> valueForUndefinedKey:]: this class is not key value coding-compliant for the key {}.",
+        env.objc.get_class_name(class),
+        this,
+        key,
+    );
+    ns_exception::raise(env, "NSUndefinedKeyException", reason);
+    nil
+}
+
+// NSKeyValueObserving
+- (())addObserver:(id)observer
+       forKeyPath:(id)key_path // NSString*
+          options:(NSKeyValueObservingOptions)options
+          context:(MutVoidPtr)context {
+    let key_path_string = to_rust_string(env, key_path).into_owned();
+    if key_path_string.contains('.') {
+        // TODO: key paths with more than one key
+        log!(
+            "Warning: {:?} tried to observe key path {:?} of {:?}, but key paths with more than one key are unimplemented, ignoring",
+            observer,
+            key_path_string,
+            this
+        );
+        return;
+    }
+    log_dbg!(
+        "{:?} is observing key path {:?} of {:?}, options {:#x}",
+        observer,
+        key_path_string,
+        this,
+        options
+    );
+    let observation = Observation {
+        observer,
+        key_path: key_path_string,
+        options,
+        context,
+    };
+    env.objc
+        .key_value_observations()
+        .entry(this)
+        .or_default()
+        .push(observation.clone());
+
+    if options & NSKeyValueObservingOptionInitial != 0 {
+        let new = if options & NSKeyValueObservingOptionNew != 0 {
+            value_for_change(env, this, key_path)
+        } else {
+            nil
+        };
+        send_change(env, this, key_path, &observation, nil, new, false);
+    }
+}
+- (())removeObserver:(id)observer
+          forKeyPath:(id)key_path { // NSString*
+    remove_observation(env, this, observer, key_path, None);
+}
+- (())removeObserver:(id)observer
+          forKeyPath:(id)key_path // NSString*
+             context:(MutVoidPtr)context {
+    remove_observation(env, this, observer, key_path, Some(context));
+}
+
+- (())willChangeValueForKey:(id)key { // NSString*
+    let observations = observations_for_key(env, this, key);
+    if observations.is_empty() {
+        return;
+    }
+    let wants_old = observations
+        .iter()
+        .any(|observation| observation.options & NSKeyValueObservingOptionOld != 0);
+    let old = if wants_old {
+        value_for_change(env, this, key)
+    } else {
+        nil
+    };
+    retain(env, old);
+    let key_string = to_rust_string(env, key).into_owned();
+    env.framework_state
+        .foundation
+        .ns_object
+        .pending_changes
+        .push((this, key_string, old));
+
+    for observation in observations {
+        if observation.options & NSKeyValueObservingOptionPrior != 0 {
+            send_change(env, this, key, &observation, old, nil, true);
         }
     }
+}
+- (())didChangeValueForKey:(id)key { // NSString*
+    let key_string = to_rust_string(env, key);
+    let pending_changes = &mut env.framework_state.foundation.ns_object.pending_changes;
+    // If there was no observer when the change began, there's nothing to do.
+    let Some(index) = pending_changes
+        .iter()
+        .rposition(|(object, pending_key, _)| *object == this && *pending_key == key_string)
+    else {
+        return;
+    };
+    let (_, _, old) = pending_changes.remove(index);
 
-    unimplemented!("TODO: object {:?} does not have simple setter method for {}, use fallback", this, key);
+    let observations = observations_for_key(env, this, key);
+    let wants_new = observations
+        .iter()
+        .any(|observation| observation.options & NSKeyValueObservingOptionNew != 0);
+    let new = if wants_new {
+        value_for_change(env, this, key)
+    } else {
+        nil
+    };
+    for observation in observations {
+        send_change(env, this, key, &observation, old, new, false);
+    }
+    release(env, old);
 }
 
 - (bool)respondsToSelector:(SEL)selector {
@@ -175,6 +413,119 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg_send(env, (this, sel, o1, o2))
 }
 
+- (())performSelectorInBackground:(SEL)sel
+                       withObject:(id)arg {
+    assert!(!sel.is_null());
+    // This is documented as being the same as using NSThread, which retains
+    // the receiver and argument until the thread is done.
+    msg_class![env; NSThread detachNewThreadSelector:sel
+                                            toTarget:this
+                                          withObject:arg]
+}
+
 @end
 
 };
+
+fn observations_for_key(env: &mut Environment, object: id, key: id) -> Vec<Observation> {
+    let Some(observations) = env.objc.key_value_observations().get(&object) else {
+        return Vec::new();
+    };
+    let observations = observations.clone();
+    let key = to_rust_string(env, key);
+    observations
+        .into_iter()
+        .filter(|observation| observation.key_path == key)
+        .collect()
+}
+
+fn remove_observation(
+    env: &mut Environment,
+    object: id,
+    observer: id,
+    key_path: id,
+    context: Option<MutVoidPtr>,
+) {
+    let key_path = to_rust_string(env, key_path).into_owned();
+    let observations = env.objc.key_value_observations();
+    let Some(object_observations) = observations.get_mut(&object) else {
+        log!(
+            "Warning: {:?} tried to stop observing key path {:?} of {:?}, which has no observers",
+            observer,
+            key_path,
+            object,
+        );
+        return;
+    };
+    // If the same observation was added more than once, only the most recent
+    // one is removed.
+    if let Some(index) = object_observations.iter().rposition(|observation| {
+        observation.observer == observer
+            && observation.key_path == key_path
+            && context.map_or(true, |context| observation.context == context)
+    }) {
+        object_observations.remove(index);
+    }
+    if object_observations.is_empty() {
+        observations.remove(&object);
+    }
+}
+
+/// Get the value of a key for a change dictionary, where [nil] is represented
+/// by `NSNull`.
+fn value_for_change(env: &mut Environment, object: id, key: id) -> id {
+    let value: id = msg![env; object valueForKey:key];
+    if value == nil {
+        msg_class![env; NSNull null]
+    } else {
+        value
+    }
+}
+
+/// Send `observeValueForKeyPath:ofObject:change:context:` to an observer.
+/// `old` and `new` are only included if the observer asked for them.
+fn send_change(
+    env: &mut Environment,
+    object: id,
+    key_path: id,
+    observation: &Observation,
+    old: id,
+    new: id,
+    is_prior: bool,
+) {
+    let &Observation {
+        observer,
+        options,
+        context,
+        ..
+    } = observation;
+
+    let kind_key = get_static_str(env, NSKeyValueChangeKindKey);
+    let kind: id = msg_class![env; NSNumber numberWithUnsignedLongLong:NSKeyValueChangeSetting];
+    let mut change = vec![(kind_key, kind)];
+    if options & NSKeyValueObservingOptionOld != 0 && old != nil {
+        change.push((get_static_str(env, NSKeyValueChangeOldKey), old));
+    }
+    if options & NSKeyValueObservingOptionNew != 0 && new != nil {
+        change.push((get_static_str(env, NSKeyValueChangeNewKey), new));
+    }
+    if is_prior {
+        let is_prior_key = get_static_str(env, NSKeyValueChangeNotificationIsPriorKey);
+        let yes: id = msg_class![env; NSNumber numberWithBool:true];
+        change.push((is_prior_key, yes));
+    }
+    let change = dict_from_keys_and_objects(env, &change);
+    let change = autorelease(env, change);
+
+    log_dbg!(
+        "Notifying {:?} of change to {:?} of {:?}: {:?}",
+        observer,
+        key_path,
+        object,
+        change
+    );
+    () = msg![env; observer observeValueForKeyPath:key_path
+                                          ofObject:object
+                                            change:change
+                                           context:context];
+}
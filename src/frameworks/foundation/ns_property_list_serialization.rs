@@ -1,9 +1,9 @@
 //! `NSPropertyListSerialization`.
 
-use super::{ns_array, ns_dictionary, ns_string, NSUInteger};
+use super::{ns_array, ns_data, ns_dictionary, ns_string, ns_value, NSUInteger};
 use crate::fs::GuestPath;
 use crate::mem::MutPtr;
-use crate::objc::{id, msg, msg_class, nil, release};
+use crate::objc::{id, msg, msg_class, nil, release, Class};
 use crate::Environment;
 use plist::Value;
 use std::io::Cursor;
@@ -41,7 +41,187 @@ pub(super) fn deserialize_plist_from_file(
     deserialize_plist(env, &root)
 }
 
-fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
+/// Internals of `localizedStringForKey:value:table:` on `NSBundle`: reads a
+/// `.strings` file, which may be a binary or XML property list, or more often
+/// the old text format (`"key" = "value";`). Returns `nil` on failure.
+pub(super) fn deserialize_strings_file(env: &mut Environment, path: &GuestPath) -> id {
+    log_dbg!("Reading strings file from {:?}.", path);
+    let Ok(bytes) = env.fs.read(path) else {
+        log_dbg!("Couldn't read file, returning nil.");
+        return nil;
+    };
+
+    if let Ok(root) = Value::from_reader(Cursor::new(&bytes)) {
+        if root.as_dictionary().is_none() {
+            log_dbg!("Plist root is not dictionary, returning nil.");
+            return nil;
+        }
+        return deserialize_plist(env, &root);
+    }
+
+    let Some(text) = decode_strings_text(&bytes) else {
+        log!(
+            "Warning: couldn't decode strings file {:?}, ignoring.",
+            path
+        );
+        return nil;
+    };
+    match parse_strings_text(&text) {
+        Ok(pairs) => {
+            let dict = pairs
+                .into_iter()
+                .map(|(key, value)| (key, Value::String(value)))
+                .collect::<plist::Dictionary>();
+            deserialize_plist(env, &Value::Dictionary(dict))
+        }
+        Err(e) => {
+            log!(
+                "Warning: couldn't parse strings file {:?} ({}), ignoring.",
+                path,
+                e
+            );
+            nil
+        }
+    }
+}
+
+/// `.strings` files are usually UTF-16 with a byte-order mark, but UTF-8 is
+/// also allowed.
+fn decode_strings_text(bytes: &[u8]) -> Option<String> {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| from_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).ok()
+    };
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        utf16(rest, u16::from_be_bytes)
+    } else {
+        let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+/// Parse the old text format of `.strings` files: a sequence of
+/// `"key" = "value";` entries (or just `"key";`), with C-style comments.
+fn parse_strings_text(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut chars = text.chars().peekable();
+    let mut pairs = Vec::new();
+
+    fn skip_whitespace_and_comments(
+        chars: &mut std::iter::Peekable<std::str::Chars>,
+    ) -> Result<(), String> {
+        loop {
+            match chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    chars.next();
+                }
+                Some('/') => {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    match lookahead.next() {
+                        Some('/') => {
+                            for c in chars.by_ref() {
+                                if c == '\n' {
+                                    break;
+                                }
+                            }
+                        }
+                        Some('*') => {
+                            chars.next();
+                            chars.next();
+                            let mut prev = '\0';
+                            loop {
+                                let Some(c) = chars.next() else {
+                                    return Err("unterminated comment".to_string());
+                                };
+                                if prev == '*' && c == '/' {
+                                    break;
+                                }
+                                prev = c;
+                            }
+                        }
+                        _ => return Ok(()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+        let mut string = String::new();
+        if chars.peek() != Some(&'"') {
+            // Unquoted strings are allowed if they're simple enough.
+            while let Some(&c) = chars.peek() {
+                if c.is_ascii_alphanumeric() || "_$+/:.-".contains(c) {
+                    string.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            return if string.is_empty() {
+                Err(format!("unexpected {:?}", chars.peek()))
+            } else {
+                Ok(string)
+            };
+        }
+        chars.next();
+        loop {
+            match chars.next() {
+                None => return Err("unterminated string".to_string()),
+                Some('"') => return Ok(string),
+                Some('\\') => match chars.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('U') | Some('u') => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("bad escape \\U{}", hex))?;
+                        string.push(c);
+                    }
+                    Some(c) => string.push(c),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    loop {
+        skip_whitespace_and_comments(&mut chars)?;
+        if chars.peek().is_none() {
+            return Ok(pairs);
+        }
+        let key = parse_string(&mut chars)?;
+        skip_whitespace_and_comments(&mut chars)?;
+        let value = if chars.peek() == Some(&'=') {
+            chars.next();
+            skip_whitespace_and_comments(&mut chars)?;
+            let value = parse_string(&mut chars)?;
+            skip_whitespace_and_comments(&mut chars)?;
+            value
+        } else {
+            // A key on its own maps to itself.
+            key.clone()
+        };
+        if chars.next() != Some(';') {
+            return Err(format!("missing ';' after {:?}", key));
+        }
+        pairs.push((key, value));
+    }
+}
+
+/// Convert a property list value to the equivalent Foundation objects. The
+/// result is retained.
+pub(super) fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
     match value {
         Value::Array(array) => {
             let array = array
@@ -114,3 +294,51 @@ fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
         }
     }
 }
+
+/// Convert a tree of Foundation objects to a property list value. Returns
+/// [None] if it contains objects that can't be represented in a property list.
+pub(super) fn serialize_plist(env: &mut Environment, object: id) -> Option<Value> {
+    fn is_kind_of(env: &mut Environment, object: id, class_name: &str) -> bool {
+        let class: Class = env.objc.get_known_class(class_name, &mut env.mem);
+        msg![env; object isKindOfClass:class]
+    }
+
+    if is_kind_of(env, object, "NSString") {
+        Some(Value::String(
+            ns_string::to_rust_string(env, object).into_owned(),
+        ))
+    } else if is_kind_of(env, object, "NSNumber") {
+        Some(ns_value::number_to_plist_value(env, object))
+    } else if is_kind_of(env, object, "NSData") {
+        let length: NSUInteger = msg![env; object length];
+        if length == 0 {
+            Some(Value::Data(Vec::new()))
+        } else {
+            Some(Value::Data(ns_data::to_rust_slice(env, object).to_vec()))
+        }
+    } else if is_kind_of(env, object, "NSArray") {
+        let count: NSUInteger = msg![env; object count];
+        let mut array = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let item: id = msg![env; object objectAtIndex:i];
+            array.push(serialize_plist(env, item)?);
+        }
+        Some(Value::Array(array))
+    } else if is_kind_of(env, object, "NSDictionary") {
+        let keys: id = msg![env; object allKeys];
+        let count: NSUInteger = msg![env; keys count];
+        let mut dict = plist::Dictionary::new();
+        for i in 0..count {
+            let key: id = msg![env; keys objectAtIndex:i];
+            if !is_kind_of(env, key, "NSString") {
+                return None;
+            }
+            let value: id = msg![env; object objectForKey:key];
+            let value = serialize_plist(env, value)?;
+            dict.insert(ns_string::to_rust_string(env, key).into_owned(), value);
+        }
+        Some(Value::Dictionary(dict))
+    } else {
+        None
+    }
+}
@@ -8,14 +8,15 @@
 //! Resources:
 //! - Apple's [Threading Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Multithreading/Introduction/Introduction.html)
 
-use super::{ns_string, ns_timer};
+use super::{ns_stream, ns_string, ns_timer, ns_url_connection, NSTimeInterval};
 use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::audio_toolbox::audio_queue::{handle_audio_queue, AudioQueueRef};
 use crate::frameworks::core_foundation::cf_run_loop::{
-    kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoopRef,
+    self, kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoopRef, CFRunLoopSourceRef,
 };
-use crate::frameworks::{core_animation, media_player, uikit};
-use crate::objc::{id, msg, objc_classes, release, retain, ClassExports, HostObject};
+use crate::frameworks::{core_animation, core_location, media_player, uikit};
+use crate::libc::dispatch;
+use crate::objc::{id, msg, msg_class, objc_classes, release, retain, ClassExports, HostObject};
 use crate::Environment;
 use std::time::{Duration, Instant};
 
@@ -48,6 +49,17 @@ struct NSRunLoopHostObject {
     /// Strong references to `NSTimer*` in no particular order. Timers are owned
     /// by the run loop. The timer must remove itself when invalidated.
     timers: Vec<id>,
+    /// Strong references to `CFRunLoopSourceRef`s in no particular order.
+    sources: Vec<CFRunLoopSourceRef>,
+    /// Weak references to `NSStream*`s in no particular order. Streams must
+    /// remove themselves when closed or deallocated.
+    streams: Vec<id>,
+    /// Strong references to `NSURLConnection*`s with a request in progress, in
+    /// no particular order. Connections remove themselves when they finish or
+    /// are cancelled.
+    connections: Vec<id>,
+    /// Set by `CFRunLoopStop()`, and cleared when the run loop stops.
+    stop_requested: bool,
 }
 impl HostObject for NSRunLoopHostObject {}
 
@@ -64,6 +76,10 @@ pub const CLASSES: ClassExports = objc_classes! {
         let host_object = Box::new(NSRunLoopHostObject {
             audio_queues: Vec::new(),
             timers: Vec::new(),
+            sources: Vec::new(),
+            streams: Vec::new(),
+            connections: Vec::new(),
+            stop_requested: false,
         });
         let new = env.objc.alloc_static_object(this, host_object, &mut env.mem);
         env.framework_state.foundation.ns_run_loop.main_thread_run_loop = Some(new);
@@ -110,7 +126,13 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())run {
-    run_run_loop(env, this, /* single_iteration: */ false);
+    run_run_loop(env, this, /* until: */ None, false);
+}
+- (())runUntilDate:(id)limit_date { // NSDate*
+    let now: id = msg_class![env; NSDate date];
+    let seconds: NSTimeInterval = msg![env; limit_date timeIntervalSinceDate:now];
+    let until = Instant::now() + Duration::from_secs_f64(seconds.max(0.0));
+    run_run_loop(env, this, Some(until), false);
 }
 // TODO: other run methods
 
@@ -159,16 +181,107 @@ pub(super) fn remove_timer(env: &mut Environment, run_loop: id, timer: id) {
     }
 }
 
+/// For use by `CFRunLoopAddSource`. Returns [false] if the source was already
+/// added.
+pub fn add_source(env: &mut Environment, run_loop: id, source: CFRunLoopSourceRef) -> bool {
+    let sources = &mut env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop).sources;
+    if sources.contains(&source) {
+        return false;
+    }
+    sources.push(source);
+    retain(env, source);
+    true
+}
+
+/// For use by `CFRunLoopRemoveSource`. Returns [false] if the source wasn't
+/// in the run loop.
+pub fn remove_source(env: &mut Environment, run_loop: id, source: CFRunLoopSourceRef) -> bool {
+    let sources = &mut env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop).sources;
+    let Some(source_idx) = sources.iter().position(|&item| item == source) else {
+        return false;
+    };
+    sources.swap_remove(source_idx);
+    release(env, source);
+    true
+}
+
+/// For use by `NSStream`.
+pub(super) fn add_stream(env: &mut Environment, run_loop: id, stream: id) {
+    let streams = &mut env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop).streams;
+    if !streams.contains(&stream) {
+        streams.push(stream);
+    }
+}
+
+/// For use by `NSStream`.
+pub(super) fn remove_stream(env: &mut Environment, run_loop: id, stream: id) {
+    let streams = &mut env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop).streams;
+    if let Some(stream_idx) = streams.iter().position(|&item| item == stream) {
+        streams.swap_remove(stream_idx);
+    }
+}
+
+/// For use by `NSURLConnection`.
+pub(super) fn add_connection(env: &mut Environment, run_loop: id, connection: id) {
+    let connections = &mut env
+        .objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .connections;
+    if !connections.contains(&connection) {
+        connections.push(connection);
+        retain(env, connection);
+    }
+}
+
+/// For use by `NSURLConnection`.
+pub(super) fn remove_connection(env: &mut Environment, run_loop: id, connection: id) {
+    let connections = &mut env
+        .objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .connections;
+    if let Some(connection_idx) = connections.iter().position(|&item| item == connection) {
+        connections.swap_remove(connection_idx);
+        release(env, connection);
+    }
+}
+
+/// For use by `CFRunLoopStop`. If the run loop isn't running, the next run
+/// will stop after one iteration.
+pub fn stop_run_loop(env: &mut Environment, run_loop: id) {
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .stop_requested = true;
+}
+
+/// Why [run_run_loop] returned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RunLoopExit {
+    Stopped,
+    TimedOut,
+    HandledSource,
+}
+
 /// Run the run loop for just a single iteration. This is a special mode just
-/// for the app picker, since we don't have `runMode:beforeDate:` or
-/// `runUntilDate:` yet. (TODO: implement those to replace this.)
+/// for the app picker, since we don't have `runMode:beforeDate:` yet.
+/// (TODO: implement that to replace this.)
 pub fn run_run_loop_single_iteration(env: &mut Environment, run_loop: id) {
-    run_run_loop(env, run_loop, /* single_iteration: */ true)
+    run_run_loop(env, run_loop, Some(Instant::now()), false);
 }
 
-fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
-    if single_iteration {
-        log_dbg!("Entering run loop {:?} (single iteration)", run_loop);
+/// Run the run loop until the time `until`, or indefinitely if it's [None],
+/// or until it's stopped with [stop_run_loop]. At least one iteration is
+/// always run, so a time in the past means a single iteration.
+///
+/// If `return_after_source_handled` is set, the run loop also returns once a
+/// `CFRunLoopSource` has been performed.
+pub fn run_run_loop(
+    env: &mut Environment,
+    run_loop: id,
+    until: Option<Instant>,
+    return_after_source_handled: bool,
+) -> RunLoopExit {
+    if let Some(until) = until {
+        log_dbg!("Entering run loop {:?} (until {:?})", run_loop, until);
     } else {
         log_dbg!("Entering run loop {:?} (indefinitely)", run_loop);
     }
@@ -177,6 +290,9 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
     // environment or to lock the object. Re-used each iteration for efficiency.
     let mut timers_tmp = Vec::new();
     let mut audio_queues_tmp = Vec::new();
+    let mut sources_tmp = Vec::new();
+    let mut streams_tmp = Vec::new();
+    let mut connections_tmp = Vec::new();
 
     fn limit_sleep_time(current: &mut Option<Instant>, new: Option<Instant>) {
         if let Some(new) = new {
@@ -187,19 +303,37 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
     loop {
         let mut sleep_until = None;
 
-        env.window
-            .as_mut()
-            .expect("NSRunLoop not supported in headless mode")
-            .poll_for_events(&env.options);
-
+        // There's no UI in headless mode, but everything else still works,
+        // and there can still be events from an input replay.
+        if let Some(window) = env.window.as_mut() {
+            window.poll_for_events(&env.options);
+        }
         let next_due = uikit::handle_events(env);
         limit_sleep_time(&mut sleep_until, next_due);
 
-        let next_due = core_animation::recomposite_if_necessary(env);
+        // Views are laid out and drawn even in headless mode, since apps may
+        // depend on layoutSubviews and drawRect: being called.
+        uikit::ui_view::layout_and_display_views(env);
+
+        let next_due = if env.window.is_some() {
+            core_animation::recomposite_if_necessary(env)
+        } else {
+            core_animation::frame_clock::headless_frame_if_necessary(env)
+        };
+        limit_sleep_time(&mut sleep_until, next_due);
+
+        let next_due = core_location::handle_location_updates(env);
         limit_sleep_time(&mut sleep_until, next_due);
 
+        let next_due = uikit::ui_application::handle_background_tasks(env);
+        limit_sleep_time(&mut sleep_until, next_due);
+
+        // Timers that become due while the app is suspended fire once it
+        // resumes. Repeating timers don't try to catch up on missed firings.
         assert!(timers_tmp.is_empty());
-        timers_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).timers);
+        if !uikit::ui_application::timers_suspended(env) {
+            timers_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).timers);
+        }
 
         for timer in timers_tmp.drain(..) {
             let next_due = ns_timer::handle_timer(env, timer);
@@ -217,8 +351,43 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
             handle_audio_queue(env, audio_queue);
         }
 
+        let mut handled_source = false;
+        assert!(sources_tmp.is_empty());
+        sources_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).sources);
+
+        for source in sources_tmp.drain(..) {
+            handled_source |= cf_run_loop::handle_source(env, source);
+        }
+
+        assert!(streams_tmp.is_empty());
+        streams_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).streams);
+
+        for stream in streams_tmp.drain(..) {
+            ns_stream::handle_stream(env, stream);
+        }
+
+        assert!(connections_tmp.is_empty());
+        connections_tmp
+            .extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).connections);
+
+        for connection in connections_tmp.drain(..) {
+            ns_url_connection::handle_connection(env, connection);
+        }
+
         media_player::handle_players(env);
 
+        let next_due = dispatch::handle_main_queue(env);
+        limit_sleep_time(&mut sleep_until, next_due);
+
+        let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+        if std::mem::take(&mut host_object.stop_requested) {
+            log_dbg!("Run loop {:?} was stopped", run_loop);
+            return RunLoopExit::Stopped;
+        }
+        if handled_source && return_after_source_handled {
+            return RunLoopExit::HandledSource;
+        }
+
         // Unfortunately, touchHLE has to poll for certain things repeatedly;
         // it can't just wait until the next event appears.
         //
@@ -233,14 +402,21 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
         // The compromise used here is that we will wait for a 60th of a second,
         // or until the next scheduled event, whichever is sooner. iPhone OS
         // apps can't do more than 60fps so this should be fine.
+        //
+        // If the run loop is only running until a certain time, it shouldn't
+        // oversleep.
         let limit = Duration::from_millis(1000 / 60);
+        limit_sleep_time(
+            &mut sleep_until,
+            until.filter(|&until| until > Instant::now()),
+        );
         env.sleep(
             sleep_until.map_or(limit, |i| i.duration_since(Instant::now()).min(limit)),
             false,
         );
 
-        if single_iteration {
-            break;
+        if until.is_some_and(|until| Instant::now() >= until) {
+            return RunLoopExit::TimedOut;
         }
     }
 }
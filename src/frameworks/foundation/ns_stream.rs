@@ -0,0 +1,610 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSStream`, `NSInputStream` and `NSOutputStream`.
+//!
+//! Streams can be backed by memory, files or TCP sockets. Socket streams are
+//! created in pairs by `CFStreamCreatePairWithSocketToHost`, see
+//! [crate::frameworks::core_foundation::cf_stream].
+//!
+//! Resources:
+//! - Apple's [Stream Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Streams/Streams.html)
+
+use super::{ns_run_loop, ns_string, NSInteger, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::libc::posix_io::{self, FileDescriptor, O_APPEND, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY};
+use crate::libc::sys::socket;
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::cell::RefCell;
+use std::net::ToSocketAddrs;
+use std::rc::Rc;
+
+pub type NSStreamStatus = NSUInteger;
+const NSStreamStatusNotOpen: NSStreamStatus = 0;
+const NSStreamStatusOpening: NSStreamStatus = 1;
+const NSStreamStatusOpen: NSStreamStatus = 2;
+const NSStreamStatusAtEnd: NSStreamStatus = 5;
+const NSStreamStatusClosed: NSStreamStatus = 6;
+pub const NSStreamStatusError: NSStreamStatus = 7;
+
+pub type NSStreamEvent = NSUInteger;
+const NSStreamEventOpenCompleted: NSStreamEvent = 1 << 0;
+const NSStreamEventHasBytesAvailable: NSStreamEvent = 1 << 1;
+const NSStreamEventHasSpaceAvailable: NSStreamEvent = 1 << 2;
+const NSStreamEventErrorOccurred: NSStreamEvent = 1 << 3;
+const NSStreamEventEndEncountered: NSStreamEvent = 1 << 4;
+
+pub const NSStreamDataWrittenToMemoryStreamKey: &str = "NSStreamDataWrittenToMemoryStreamKey";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_NSStreamDataWrittenToMemoryStreamKey",
+    HostConstant::NSString(NSStreamDataWrittenToMemoryStreamKey),
+)];
+
+/// A socket shared by an input and output stream. It is connected when either
+/// stream is opened, and closed once both have been closed.
+struct SharedSocket {
+    host: String,
+    port: u16,
+    fd: Option<FileDescriptor>,
+    open_streams: u32,
+}
+
+enum Backing {
+    /// For input, a copy of an `NSData`'s bytes. For output, the bytes that
+    /// have been written so far.
+    Memory {
+        bytes: Vec<u8>,
+        position: usize,
+    },
+    File {
+        /// `NSString*`
+        path: id,
+        flags: i32,
+        fd: Option<FileDescriptor>,
+    },
+    Socket(Rc<RefCell<SharedSocket>>),
+}
+
+#[derive(Default)]
+struct NSStreamHostObject {
+    backing: Option<Backing>,
+    status: NSStreamStatus,
+    /// Weak reference. If this is nil, the stream is its own delegate.
+    delegate: id,
+    /// Weak reference. Set if the stream is scheduled in a run loop.
+    run_loop: id,
+    /// Whether `NSStreamEventOpenCompleted` still needs to be sent.
+    open_completed_pending: bool,
+    /// Whether `NSStreamEventHasBytesAvailable` or
+    /// `NSStreamEventHasSpaceAvailable` can be sent. They are only sent again
+    /// once the delegate has read or written something.
+    availability_armed: bool,
+    /// Whether `NSStreamEventEndEncountered` or `NSStreamEventErrorOccurred`
+    /// still needs to be sent, once the status is at end or error.
+    end_or_error_pending: bool,
+}
+impl HostObject for NSStreamHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSStream: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSStreamHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    close_stream(env, this);
+    let run_loop = std::mem::take(&mut env.objc.borrow_mut::<NSStreamHostObject>(this).run_loop);
+    if run_loop != nil {
+        ns_run_loop::remove_stream(env, run_loop, this);
+    }
+    if let Some(Backing::File { path, .. }) = env.objc.borrow::<NSStreamHostObject>(this).backing {
+        release(env, path);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)delegate {
+    let delegate = env.objc.borrow::<NSStreamHostObject>(this).delegate;
+    if delegate == nil {
+        this
+    } else {
+        delegate
+    }
+}
+- (())setDelegate:(id)delegate { // id<NSStreamDelegate>
+    let delegate = if delegate == this { nil } else { delegate };
+    env.objc.borrow_mut::<NSStreamHostObject>(this).delegate = delegate;
+}
+
+// The default delegate method, for when the stream is its own delegate.
+- (())stream:(id)_stream handleEvent:(NSStreamEvent)_event {}
+
+- (NSStreamStatus)streamStatus {
+    env.objc.borrow::<NSStreamHostObject>(this).status
+}
+- (id)streamError {
+    // TODO: NSError for failed streams
+    nil
+}
+
+- (())open {
+    open_stream(env, this);
+}
+- (())close {
+    close_stream(env, this);
+}
+
+- (())scheduleInRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(id)_mode { // NSRunLoopMode
+    // TODO: handle run loop modes
+    let old_run_loop = std::mem::replace(
+        &mut env.objc.borrow_mut::<NSStreamHostObject>(this).run_loop,
+        run_loop,
+    );
+    if old_run_loop != nil && old_run_loop != run_loop {
+        ns_run_loop::remove_stream(env, old_run_loop, this);
+    }
+    ns_run_loop::add_stream(env, run_loop, this);
+}
+- (())removeFromRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(id)_mode { // NSRunLoopMode
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    if host_object.run_loop == run_loop {
+        host_object.run_loop = nil;
+        ns_run_loop::remove_stream(env, run_loop, this);
+    }
+}
+
+- (id)propertyForKey:(id)key { // NSString*
+    let key = ns_string::to_rust_string(env, key);
+    if key != NSStreamDataWrittenToMemoryStreamKey {
+        log!("TODO: [(NSStream*){:?} propertyForKey:{:?}]", this, key);
+        return nil;
+    }
+    let Some(Backing::Memory { bytes, .. }) = &env.objc.borrow::<NSStreamHostObject>(this).backing
+    else {
+        return nil;
+    };
+    let bytes = bytes.clone();
+    let length: NSUInteger = bytes.len().try_into().unwrap();
+    let copy = env.mem.alloc(length);
+    env.mem
+        .bytes_at_mut(copy.cast(), length)
+        .copy_from_slice(&bytes);
+    msg_class![env; NSData dataWithBytesNoCopy:copy length:length]
+}
+- (bool)setProperty:(id)_property forKey:(id)key { // NSString*
+    log!("TODO: [(NSStream*){:?} setProperty:forKey:{:?}]", this, ns_string::to_rust_string(env, key));
+    false
+}
+
+@end
+
+@implementation NSInputStream: NSStream
+
++ (id)inputStreamWithData:(id)data { // NSData*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithData:data];
+    autorelease(env, new)
+}
++ (id)inputStreamWithFileAtPath:(id)path { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithFileAtPath:path];
+    autorelease(env, new)
+}
+
+- (id)initWithData:(id)data { // NSData*
+    let bytes: ConstVoidPtr = msg![env; data bytes];
+    let length: NSUInteger = msg![env; data length];
+    let bytes = env.mem.bytes_at(bytes.cast(), length).to_vec();
+    env.objc.borrow_mut::<NSStreamHostObject>(this).backing = Some(Backing::Memory {
+        bytes,
+        position: 0,
+    });
+    this
+}
+- (id)initWithFileAtPath:(id)path { // NSString*
+    let path: id = msg![env; path copy];
+    env.objc.borrow_mut::<NSStreamHostObject>(this).backing = Some(Backing::File {
+        path,
+        flags: O_RDONLY,
+        fd: None,
+    });
+    this
+}
+
+- (NSInteger)read:(MutPtr<u8>)buffer
+        maxLength:(NSUInteger)max_length {
+    read_stream(env, this, buffer, max_length)
+}
+- (bool)getBuffer:(MutPtr<MutPtr<u8>>)_buffer
+           length:(MutPtr<NSUInteger>)_length {
+    // Apple's streams mostly don't support this either.
+    false
+}
+- (bool)hasBytesAvailable {
+    has_bytes_available(env, this)
+}
+
+@end
+
+@implementation NSOutputStream: NSStream
+
++ (id)outputStreamToMemory {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initToMemory];
+    autorelease(env, new)
+}
++ (id)outputStreamToFileAtPath:(id)path // NSString*
+                        append:(bool)append {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initToFileAtPath:path append:append];
+    autorelease(env, new)
+}
+
+- (id)initToMemory {
+    env.objc.borrow_mut::<NSStreamHostObject>(this).backing = Some(Backing::Memory {
+        bytes: Vec::new(),
+        position: 0,
+    });
+    this
+}
+- (id)initToFileAtPath:(id)path // NSString*
+                append:(bool)append {
+    let path: id = msg![env; path copy];
+    let flags = O_WRONLY | O_CREAT | if append { O_APPEND } else { O_TRUNC };
+    env.objc.borrow_mut::<NSStreamHostObject>(this).backing = Some(Backing::File {
+        path,
+        flags,
+        fd: None,
+    });
+    this
+}
+
+- (NSInteger)write:(ConstPtr<u8>)buffer
+         maxLength:(NSUInteger)max_length {
+    write_stream(env, this, buffer, max_length)
+}
+- (bool)hasSpaceAvailable {
+    has_space_available(env, this)
+}
+
+@end
+
+};
+
+/// Create a pair of streams for a TCP connection to a host, which is made when
+/// either is opened. Both streams are retained.
+pub fn new_socket_streams(env: &mut Environment, host: String, port: u16) -> (id, id) {
+    let socket = Rc::new(RefCell::new(SharedSocket {
+        host,
+        port,
+        fd: None,
+        open_streams: 0,
+    }));
+    let input: id = msg_class![env; NSInputStream alloc];
+    env.objc.borrow_mut::<NSStreamHostObject>(input).backing =
+        Some(Backing::Socket(socket.clone()));
+    let output: id = msg_class![env; NSOutputStream alloc];
+    env.objc.borrow_mut::<NSStreamHostObject>(output).backing = Some(Backing::Socket(socket));
+    (input, output)
+}
+
+fn is_input(env: &mut Environment, stream: id) -> bool {
+    let class = env.objc.get_known_class("NSInputStream", &mut env.mem);
+    msg![env; stream isKindOfClass:class]
+}
+
+fn open_stream(env: &mut Environment, stream: id) {
+    let is_input = is_input(env, stream);
+    let host_object = env.objc.borrow::<NSStreamHostObject>(stream);
+    if host_object.status != NSStreamStatusNotOpen {
+        log!("Warning: stream {:?} was already opened", stream);
+        return;
+    }
+
+    let status = match host_object.backing {
+        None => panic!("Stream {:?} wasn't initialized", stream),
+        // An empty input stream is already at its end.
+        Some(Backing::Memory { ref bytes, .. }) if bytes.is_empty() && is_input => {
+            NSStreamStatusAtEnd
+        }
+        Some(Backing::Memory { .. }) => NSStreamStatusOpen,
+        Some(Backing::File { path, flags, .. }) => {
+            let path_str: ConstPtr<u8> = msg![env; path UTF8String];
+            match posix_io::open_direct(env, path_str, flags) {
+                -1 => {
+                    log!(
+                        "Warning: stream {:?} couldn't open {:?}",
+                        stream,
+                        ns_string::to_rust_string(env, path)
+                    );
+                    NSStreamStatusError
+                }
+                new_fd => {
+                    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+                    let Some(Backing::File { ref mut fd, .. }) = host_object.backing else {
+                        unreachable!();
+                    };
+                    *fd = Some(new_fd);
+                    NSStreamStatusOpen
+                }
+            }
+        }
+        Some(Backing::Socket(ref socket)) => {
+            let socket = socket.clone();
+            let mut socket = socket.borrow_mut();
+            socket.open_streams += 1;
+            if socket.fd.is_some() {
+                NSStreamStatusOpening
+            } else {
+                // TODO: resolve the name without blocking
+                let addr = (socket.host.as_str(), socket.port)
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next());
+                if let Some(addr) = addr {
+                    socket.fd = Some(socket::connect_in_background(env, addr));
+                    NSStreamStatusOpening
+                } else {
+                    log!(
+                        "Warning: stream {:?} couldn't resolve {:?}",
+                        stream,
+                        socket.host
+                    );
+                    NSStreamStatusError
+                }
+            }
+        }
+    };
+
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+    host_object.status = status;
+    host_object.open_completed_pending = status != NSStreamStatusError;
+    host_object.availability_armed = true;
+    host_object.end_or_error_pending = true;
+}
+
+fn close_stream(env: &mut Environment, stream: id) {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+    if matches!(
+        host_object.status,
+        NSStreamStatusNotOpen | NSStreamStatusClosed
+    ) {
+        return;
+    }
+    host_object.status = NSStreamStatusClosed;
+    let run_loop = std::mem::take(&mut host_object.run_loop);
+
+    let fd_to_close = match host_object.backing {
+        Some(Backing::File { ref mut fd, .. }) => fd.take(),
+        Some(Backing::Socket(ref socket)) => {
+            let mut socket = socket.borrow_mut();
+            socket.open_streams = socket.open_streams.saturating_sub(1);
+            if socket.open_streams == 0 {
+                socket.fd.take()
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    if let Some(fd) = fd_to_close {
+        posix_io::close(env, fd);
+    }
+    if run_loop != nil {
+        ns_run_loop::remove_stream(env, run_loop, stream);
+    }
+}
+
+/// Find whether a socket is readable, writable and has an error.
+fn socket_readiness(
+    env: &mut Environment,
+    socket: &Rc<RefCell<SharedSocket>>,
+) -> (bool, bool, bool) {
+    match socket.borrow().fd {
+        Some(fd) => socket::readiness(env, fd),
+        None => (false, false, true),
+    }
+}
+
+fn has_bytes_available(env: &mut Environment, stream: id) -> bool {
+    let host_object = env.objc.borrow::<NSStreamHostObject>(stream);
+    if host_object.status != NSStreamStatusOpen {
+        return false;
+    }
+    match host_object.backing {
+        Some(Backing::Memory {
+            ref bytes,
+            position,
+        }) => position < bytes.len(),
+        // Reading a file doesn't block, so a read may as well be attempted.
+        Some(Backing::File { .. }) => true,
+        Some(Backing::Socket(ref socket)) => {
+            let socket = socket.clone();
+            socket_readiness(env, &socket).0
+        }
+        None => false,
+    }
+}
+
+fn has_space_available(env: &mut Environment, stream: id) -> bool {
+    let host_object = env.objc.borrow::<NSStreamHostObject>(stream);
+    if host_object.status != NSStreamStatusOpen {
+        return false;
+    }
+    match host_object.backing {
+        Some(Backing::Socket(ref socket)) => {
+            let socket = socket.clone();
+            socket_readiness(env, &socket).1
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+fn read_stream(
+    env: &mut Environment,
+    stream: id,
+    buffer: MutPtr<u8>,
+    max_length: NSUInteger,
+) -> NSInteger {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+    match host_object.status {
+        NSStreamStatusOpen => (),
+        NSStreamStatusAtEnd => return 0,
+        _ => return -1,
+    }
+    host_object.availability_armed = true;
+
+    let (result, at_end): (NSInteger, bool) = match host_object.backing {
+        Some(Backing::Memory {
+            ref bytes,
+            ref mut position,
+        }) => {
+            let count = (bytes.len() - *position).min(max_length as usize);
+            let chunk = bytes[*position..][..count].to_vec();
+            *position += count;
+            let at_end = *position == bytes.len();
+            let count: GuestUSize = count.try_into().unwrap();
+            env.mem.bytes_at_mut(buffer, count).copy_from_slice(&chunk);
+            (count.try_into().unwrap(), at_end)
+        }
+        Some(Backing::File { fd: Some(fd), .. }) => {
+            let read = posix_io::read(env, fd, buffer.cast(), max_length);
+            // A short read means the end of the file has been reached.
+            (read, read >= 0 && (read as NSUInteger) < max_length)
+        }
+        Some(Backing::Socket(ref socket)) => {
+            let fd = socket.borrow().fd.unwrap();
+            let read = posix_io::read(env, fd, buffer.cast(), max_length);
+            (read, read == 0)
+        }
+        _ => unreachable!(),
+    };
+
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+    if result < 0 {
+        host_object.status = NSStreamStatusError;
+    } else if at_end {
+        host_object.status = NSStreamStatusAtEnd;
+    }
+    result
+}
+
+fn write_stream(
+    env: &mut Environment,
+    stream: id,
+    buffer: ConstPtr<u8>,
+    max_length: NSUInteger,
+) -> NSInteger {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+    if host_object.status != NSStreamStatusOpen {
+        return -1;
+    }
+    host_object.availability_armed = true;
+
+    let result: NSInteger = match host_object.backing {
+        Some(Backing::Memory { ref mut bytes, .. }) => {
+            bytes.extend_from_slice(env.mem.bytes_at(buffer, max_length));
+            max_length.try_into().unwrap()
+        }
+        Some(Backing::File { fd: Some(fd), .. }) => {
+            posix_io::write(env, fd, buffer.cast(), max_length)
+        }
+        Some(Backing::Socket(ref socket)) => {
+            let fd = socket.borrow().fd.unwrap();
+            posix_io::write(env, fd, buffer.cast(), max_length)
+        }
+        _ => unreachable!(),
+    };
+
+    if result < 0 {
+        env.objc.borrow_mut::<NSStreamHostObject>(stream).status = NSStreamStatusError;
+    }
+    result
+}
+
+/// For use by `NSRunLoop`: check on a scheduled stream and send its delegate
+/// any events that have happened.
+pub(super) fn handle_stream(env: &mut Environment, stream: id) {
+    // Socket streams finish opening once the connection is made.
+    let host_object = env.objc.borrow::<NSStreamHostObject>(stream);
+    if host_object.status == NSStreamStatusOpening {
+        let Some(Backing::Socket(ref socket)) = host_object.backing else {
+            unreachable!();
+        };
+        let socket = socket.clone();
+        let (_, writable, error) = socket_readiness(env, &socket);
+        let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+        if error {
+            host_object.status = NSStreamStatusError;
+        } else if writable {
+            host_object.status = NSStreamStatusOpen;
+            host_object.open_completed_pending = true;
+        }
+    }
+
+    let mut events = Vec::new();
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+    let status = host_object.status;
+    if matches!(status, NSStreamStatusOpen | NSStreamStatusAtEnd)
+        && std::mem::take(&mut host_object.open_completed_pending)
+    {
+        events.push(NSStreamEventOpenCompleted);
+    }
+    match status {
+        NSStreamStatusAtEnd | NSStreamStatusError => {
+            if std::mem::take(&mut host_object.end_or_error_pending) {
+                events.push(if status == NSStreamStatusAtEnd {
+                    NSStreamEventEndEncountered
+                } else {
+                    NSStreamEventErrorOccurred
+                });
+            }
+        }
+        NSStreamStatusOpen if host_object.availability_armed => {
+            let is_input = is_input(env, stream);
+            let available = if is_input {
+                has_bytes_available(env, stream)
+            } else {
+                has_space_available(env, stream)
+            };
+            if available {
+                env.objc
+                    .borrow_mut::<NSStreamHostObject>(stream)
+                    .availability_armed = false;
+                events.push(if is_input {
+                    NSStreamEventHasBytesAvailable
+                } else {
+                    NSStreamEventHasSpaceAvailable
+                });
+            }
+        }
+        _ => (),
+    }
+
+    if events.is_empty() {
+        return;
+    }
+    // The delegate might close and release the stream.
+    retain(env, stream);
+    for event in events {
+        let delegate: id = msg![env; stream delegate];
+        () = msg![env; delegate stream:stream handleEvent:event];
+    }
+    release(env, stream);
+}
@@ -9,11 +9,12 @@
 //! - Apple's [String Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Strings/introStrings.html)
 
 mod path_algorithms;
+mod value_parsing;
 
-use super::ns_array;
+use super::{ns_array, ns_exception};
 use super::{
-    NSComparisonResult, NSNotFound, NSOrderedAscending, NSOrderedDescending, NSOrderedSame,
-    NSRange, NSUInteger,
+    NSComparisonResult, NSInteger, NSNotFound, NSOrderedAscending, NSOrderedDescending,
+    NSOrderedSame, NSRange, NSUInteger,
 };
 use crate::abi::VaList;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
@@ -45,6 +46,7 @@ pub type NSStringCompareOptions = NSUInteger;
 pub const NSCaseInsensitiveSearch: NSUInteger = 1;
 pub const NSLiteralSearch: NSUInteger = 2;
 pub const NSBackwardsSearch: NSUInteger = 4;
+pub const NSAnchoredSearch: NSUInteger = 8;
 pub const NSNumericSearch: NSUInteger = 64;
 
 /// Encodings that C strings (null-terminated byte strings) can use.
@@ -335,51 +337,76 @@ pub const CLASSES: ClassExports = objc_classes! {
     utf16[index as usize]
 }
 
-- (NSRange)rangeOfString:(id)search_string
-                 options:(NSStringCompareOptions)options { // NSString *
+- (NSRange)rangeOfString:(id)search_string { // NSString*
+    msg![env; this rangeOfString:search_string options:0u32]
+}
+- (NSRange)rangeOfString:(id)search_string // NSString*
+                 options:(NSStringCompareOptions)options {
+    let length: NSUInteger = msg![env; this length];
+    let range = NSRange { location: 0, length };
+    msg![env; this rangeOfString:search_string options:options range:range]
+}
+- (NSRange)rangeOfString:(id)search_string // NSString*
+                 options:(NSStringCompareOptions)options
+                   range:(NSRange)range {
     log_dbg!(
-        "[(NSString *){} rangeOfString:{} options:{}]",
-        to_rust_string(env, this), to_rust_string(env, search_string), options
+        "[{:?} rangeOfString:{:?} options:{} range:{:?}]",
+        this, search_string, options, range
     );
-    let len: NSUInteger = msg![env; this length];
-    let len_search: NSUInteger = msg![env; search_string length];
-    if len_search == 0 {
+    if search_string == nil {
+        ns_exception::raise(
+            env,
+            "NSInvalidArgumentException",
+            "-[NSString rangeOfString:options:range:]: nil argument".to_string(),
+        );
         return NSRange { location: NSNotFound as NSUInteger, length: 0 };
     }
-    // TODO: other search options
-    // TODO: OR'ing of options
-    match options {
-        // 0 is for default options, which is NSLiteralSearch
-        NSLiteralSearch | 0 => {
-            for i in 0..len {
-                if is_match_at_position(env, this, search_string, i, len, len_search, |a, b| a == b) {
-                    return NSRange { location: i, length: len_search }
-                }
-            }
-        },
-        NSCaseInsensitiveSearch => {
-            let compare = |a, b| {
-                let (Some(a_c), Some(b_c)) = (char::from_u32(a as u32), char::from_u32(b as u32)) else {
-                    panic!("Invalid chars in the strings!");
-                };
-                a_c.to_lowercase().eq(b_c.to_lowercase())
-            };
-            for i in 0..len {
-                if is_match_at_position(env, this, search_string, i, len, len_search, compare) {
-                    return NSRange { location: i, length: len_search }
-                }
-            }
-        },
-        NSBackwardsSearch => {
-            for i in (0..len).rev() {
-                if is_match_at_position(env, this, search_string, i, len, len_search, |a, b| a == b) {
-                    return NSRange { location: i, length: len_search }
-                }
-            }
+    // NSLiteralSearch only matters for composed characters, which aren't
+    // handled anyway.
+    let supported =
+        NSCaseInsensitiveSearch | NSLiteralSearch | NSBackwardsSearch | NSAnchoredSearch;
+    if options & !supported != 0 {
+        unimplemented!("options {}", options);
+    }
+
+    let string = copy_code_units(env, this);
+    let search_string = copy_code_units(env, search_string);
+
+    let range_end = range.location.checked_add(range.length);
+    if range_end.map_or(true, |end| end as usize > string.len()) {
+        let reason = format!(
+            "-[NSString rangeOfString:options:range:]: Range {{{}, {}}} out of bounds; string length {}",
+            range.location,
+            range.length,
+            string.len(),
+        );
+        ns_exception::raise(env, "NSRangeException", reason);
+        return NSRange { location: NSNotFound as NSUInteger, length: 0 };
+    }
+    let haystack = &string[range.location as usize..range_end.unwrap() as usize];
+
+    match find_code_units(haystack, &search_string, options) {
+        Some(idx) => NSRange {
+            location: range.location + idx as NSUInteger,
+            length: search_string.len() as NSUInteger,
         },
-        _ => unimplemented!("options {}", options)
+        None => NSRange { location: NSNotFound as NSUInteger, length: 0 },
     }
-    NSRange { location: NSNotFound as NSUInteger, length: 0 }
+}
+
+// Like on Apple platforms, the empty string is never a prefix or suffix.
+- (bool)hasPrefix:(id)prefix { // NSString*
+    let res: NSRange = msg![env; this rangeOfString:prefix options:NSAnchoredSearch];
+    res.location != NSNotFound as NSUInteger
+}
+- (bool)hasSuffix:(id)suffix { // NSString*
+    let options = NSAnchoredSearch | NSBackwardsSearch;
+    let res: NSRange = msg![env; this rangeOfString:suffix options:options];
+    res.location != NSNotFound as NSUInteger
+}
+- (bool)containsString:(id)other { // NSString*
+    let res: NSRange = msg![env; this rangeOfString:other];
+    res.location != NSNotFound as NSUInteger
 }
 
 - (id)description {
@@ -391,7 +418,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     // TODO: avoid copying
     super::hash_helper(&to_rust_string(env, this))
 }
-- (bool)isEqualTo:(id)other {
+- (bool)isEqual:(id)other {
     if this == other {
         return true;
     }
@@ -776,23 +803,29 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (id)stringByAppendingPathComponent:(id)component { // NSString*
     // TODO: avoid copying
-    // FIXME: check if Rust join() matches NSString (it probably doesn't)
-    let combined = GuestPath::new(&to_rust_string(env, this))
-        .join(to_rust_string(env, component));
-    let new_string = from_rust_string(env, String::from(combined));
+    let combined = path_algorithms::append_path_component(
+        &to_rust_string(env, this),
+        &to_rust_string(env, component),
+    );
+    let new_string = from_rust_string(env, combined);
     autorelease(env, new_string)
 }
 
 - (id)stringByAppendingPathExtension:(id)extension { // NSString*
-    // FIXME: handle edge cases like trailing '/' (may differ from Rust!)
-    let mut combined = to_rust_string(env, this).into_owned();
     // TODO: avoid copying
+    let path = to_rust_string(env, this);
     let extension_string = to_rust_string(env, extension);
-    if extension_string.len() > 0 {
-        combined.push('.');
-        combined.push_str(&extension_string);
-    }
-
+    let Some(combined) = path_algorithms::append_path_extension(
+        &path,
+        &extension_string,
+    ) else {
+        log!(
+            "Warning: cannot append extension {:?} to path {:?}, returning nil",
+            extension_string,
+            path,
+        );
+        return nil;
+    };
     let new_string = from_rust_string(env, combined);
     autorelease(env, new_string)
 }
@@ -903,31 +936,25 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (f32)floatValue {
-    let st = to_rust_string(env, this);
-    let st = st.trim_start();
-    let mut cutoff = st.len();
-    for (i, c) in st.char_indices() {
-        if !c.is_ascii_digit() && c != '.' && c != '+' && c != '-' {
-            cutoff = i;
-            break;
-        }
-    }
-    // TODO: handle over/underflow properly
-    st[..cutoff].parse().unwrap_or(0.0)
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    value_parsing::parse_double_prefix(&string) as f32
+}
+- (f64)doubleValue {
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    value_parsing::parse_double_prefix(&string)
 }
 
 - (i32)intValue {
-    let st = to_rust_string(env, this);
-    let st = st.trim_start();
-    let mut cutoff = st.len();
-    for (i, c) in st.char_indices() {
-        if !c.is_ascii_digit() && c != '+' && c != '-' {
-            cutoff = i;
-            break;
-        }
-    }
-    // TODO: handle over/underflow properly
-    st[..cutoff].parse().unwrap_or(0)
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    value_parsing::parse_int_prefix(&string)
+}
+- (NSInteger)integerValue {
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    value_parsing::parse_int_prefix(&string)
+}
+- (i64)longLongValue {
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    value_parsing::parse_long_long_prefix(&string)
 }
 
 @end
@@ -1238,25 +1265,71 @@ where
         });
 }
 
-/// Helper function for `rangeOfString:options:` method
-/// Note: this implementation is linear
-fn is_match_at_position<F: Fn(u16, u16) -> bool>(
-    env: &mut Environment,
-    the_string: id,
-    search_string: id,
-    start: NSUInteger,
-    len: NSUInteger,
-    len_search: NSUInteger,
-    compare_fn: F,
-) -> bool {
-    (0..len_search).all(|j| {
-        let curr: NSUInteger = start + j;
-        if curr < len {
-            let a_c: u16 = msg![env; the_string characterAtIndex:curr];
-            let b_c: u16 = msg![env; search_string characterAtIndex:j];
-            compare_fn(a_c, b_c)
+/// Copies the UTF-16 code units of a string. Foreign subclasses of NSString
+/// are supported by falling back to the `length` and `characterAtIndex:`
+/// primitive methods.
+fn copy_code_units(env: &mut Environment, string: id) -> Utf16String {
+    let class = ObjC::read_isa(string, &env.mem);
+    let is_host_string = ["_touchHLE_NSString", "_touchHLE_NSMutableString"]
+        .into_iter()
+        .any(|name| {
+            let host_class = env.objc.get_known_class(name, &mut env.mem);
+            env.objc.class_is_subclass_of(class, host_class)
+        });
+    if is_host_string {
+        return env
+            .objc
+            .borrow::<StringHostObject>(string)
+            .iter_code_units()
+            .collect();
+    }
+
+    let length: NSUInteger = msg![env; string length];
+    (0..length)
+        .map(|idx| {
+            let c: u16 = msg![env; string characterAtIndex:idx];
+            c
+        })
+        .collect()
+}
+
+/// Helper function for `rangeOfString:options:range:`: finds the index of
+/// `needle` in `haystack`, respecting `NSCaseInsensitiveSearch`,
+/// `NSBackwardsSearch` and `NSAnchoredSearch`. An empty needle is never found.
+fn find_code_units(
+    haystack: &[u16],
+    needle: &[u16],
+    options: NSStringCompareOptions,
+) -> Option<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+
+    let is_match_at = |start: usize| {
+        let candidate = &haystack[start..start + needle.len()];
+        if options & NSCaseInsensitiveSearch != 0 {
+            candidate.iter().zip(needle).all(|(&a, &b)| {
+                // Surrogates can't be case-folded on their own.
+                match (char::from_u32(a.into()), char::from_u32(b.into())) {
+                    (Some(a_c), Some(b_c)) => a_c.to_lowercase().eq(b_c.to_lowercase()),
+                    _ => a == b,
+                }
+            })
         } else {
-            false
+            candidate == needle
         }
-    })
+    };
+
+    let last_start = haystack.len() - needle.len();
+    let backwards = options & NSBackwardsSearch != 0;
+    if options & NSAnchoredSearch != 0 {
+        // Anchored searches only look at the start, or the end if searching
+        // backwards.
+        let start = if backwards { last_start } else { 0 };
+        is_match_at(start).then_some(start)
+    } else if backwards {
+        (0..=last_start).rev().find(|&start| is_match_at(start))
+    } else {
+        (0..=last_start).find(|&start| is_match_at(start))
+    }
 }
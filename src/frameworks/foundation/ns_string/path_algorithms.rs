@@ -90,6 +90,48 @@ pub fn split_path_extension(path: &str) -> (&str, &str) {
     }
 }
 
+/// Collapses runs of slashes into a single slash and removes any trailing
+/// slash, unless the path is just `/`.
+pub fn collapse_slashes(path: &str) -> String {
+    let mut collapsed = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c == '/' && collapsed.ends_with('/') {
+            continue;
+        }
+        collapsed.push(c);
+    }
+    if collapsed.len() > 1 && collapsed.ends_with('/') {
+        collapsed.pop();
+    }
+    collapsed
+}
+
+/// Returns the `stringByAppendingPathComponent:` value for a string.
+pub fn append_path_component(path: &str, component: &str) -> String {
+    if path.is_empty() {
+        collapse_slashes(component)
+    } else if component.is_empty() {
+        collapse_slashes(path)
+    } else {
+        collapse_slashes(&format!("{}/{}", path, component))
+    }
+}
+
+/// Returns the `stringByAppendingPathExtension:` value for a string, or [None]
+/// if there is nothing to append the extension to.
+pub fn append_path_extension(path: &str, extension: &str) -> Option<String> {
+    let path = trim_trailing_slashes(path);
+    if path.is_empty() || path == "/" {
+        return None;
+    }
+    let mut combined = String::from(path);
+    if !extension.is_empty() {
+        combined.push('.');
+        combined.push_str(extension);
+    }
+    Some(combined)
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -162,4 +204,40 @@ mod tests {
         assert_eq!(path_extension("/a/"), "");
         assert_eq!(path_extension("/a/a..png"), "png");
     }
+
+    #[test]
+    fn test_append_path_component() {
+        use super::append_path_component;
+
+        assert_eq!(append_path_component("/tmp", "a.png"), "/tmp/a.png");
+        assert_eq!(append_path_component("/tmp/", "a.png"), "/tmp/a.png");
+        assert_eq!(append_path_component("/", "a.png"), "/a.png");
+        assert_eq!(append_path_component("", "a.png"), "a.png");
+        assert_eq!(append_path_component("a//b/", "/c//"), "a/b/c");
+        assert_eq!(append_path_component("/tmp/", ""), "/tmp");
+        assert_eq!(append_path_component("/", ""), "/");
+        assert_eq!(append_path_component("", ""), "");
+    }
+
+    #[test]
+    fn test_append_path_extension() {
+        use super::append_path_extension;
+
+        assert_eq!(
+            append_path_extension("/tmp/a.old", "png").as_deref(),
+            Some("/tmp/a.old.png")
+        );
+        assert_eq!(
+            append_path_extension("/tmp/a.", "png").as_deref(),
+            Some("/tmp/a..png")
+        );
+        assert_eq!(
+            append_path_extension("/tmp/", "png").as_deref(),
+            Some("/tmp.png")
+        );
+        assert_eq!(append_path_extension("a", "png").as_deref(), Some("a.png"));
+        assert_eq!(append_path_extension("a", "").as_deref(), Some("a"));
+        assert_eq!(append_path_extension("", "png"), None);
+        assert_eq!(append_path_extension("/", "png"), None);
+    }
 }
@@ -0,0 +1,130 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Parsing of numbers for NSString's `intValue`, `doubleValue` etc.
+//!
+//! These are lenient: leading whitespace is skipped, and parsing stops at the
+//! first character that can't be part of the number, rather than failing.
+//! If there's no number at all, the result is zero.
+
+/// Returns the number at the start of a string for `longLongValue`. Values
+/// that don't fit saturate. Use [parse_int_prefix] for narrower types.
+pub fn parse_long_long_prefix(string: &str) -> i64 {
+    let string = string.trim_start();
+    let (negative, digits) = if let Some(rest) = string.strip_prefix('-') {
+        (true, rest)
+    } else {
+        (false, string.strip_prefix('+').unwrap_or(string))
+    };
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        let Some(digit) = c.to_digit(10) else {
+            break;
+        };
+        // Accumulating the negative value means i64::MIN can be represented.
+        value = if negative {
+            value.saturating_mul(10).saturating_sub(digit.into())
+        } else {
+            value.saturating_mul(10).saturating_add(digit.into())
+        };
+    }
+    value
+}
+
+/// Like [parse_long_long_prefix], but saturating to the range of `i32`, as used
+/// by `intValue` and (on 32-bit iPhone OS) `integerValue`.
+pub fn parse_int_prefix(string: &str) -> i32 {
+    parse_long_long_prefix(string).clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+/// Returns the number at the start of a string for `doubleValue`. Values too
+/// large to represent become infinity.
+pub fn parse_double_prefix(string: &str) -> f64 {
+    let string = string.trim_start();
+    let bytes = string.as_bytes();
+
+    let skip_digits = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+
+    let mut end = 0;
+    if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+        end += 1;
+    }
+    let mantissa_start = end;
+    end = skip_digits(end);
+    if end < bytes.len() && bytes[end] == b'.' {
+        end = skip_digits(end + 1);
+    }
+    // Just a sign and/or a decimal point isn't a number.
+    if !string[mantissa_start..end]
+        .bytes()
+        .any(|b| b.is_ascii_digit())
+    {
+        return 0.0;
+    }
+    // The exponent is only included if it has digits.
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exponent_end = end + 1;
+        if exponent_end < bytes.len()
+            && (bytes[exponent_end] == b'+' || bytes[exponent_end] == b'-')
+        {
+            exponent_end += 1;
+        }
+        let digits_start = exponent_end;
+        exponent_end = skip_digits(exponent_end);
+        if exponent_end > digits_start {
+            end = exponent_end;
+        }
+    }
+
+    string[..end].parse().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_int_prefix() {
+        assert_eq!(parse_int_prefix("42abc"), 42);
+        assert_eq!(parse_int_prefix("  \t-17 "), -17);
+        assert_eq!(parse_int_prefix("+8"), 8);
+        assert_eq!(parse_int_prefix("3.9"), 3);
+        assert_eq!(parse_int_prefix("1-2"), 1);
+        assert_eq!(parse_int_prefix("abc"), 0);
+        assert_eq!(parse_int_prefix("-"), 0);
+        assert_eq!(parse_int_prefix(""), 0);
+        assert_eq!(parse_int_prefix("99999999999"), i32::MAX);
+        assert_eq!(parse_int_prefix("-99999999999"), i32::MIN);
+    }
+
+    #[test]
+    fn test_parse_long_long_prefix() {
+        assert_eq!(parse_long_long_prefix("99999999999"), 99999999999);
+        assert_eq!(parse_long_long_prefix("-9223372036854775808"), i64::MIN);
+        assert_eq!(parse_long_long_prefix("99999999999999999999999"), i64::MAX);
+        assert_eq!(parse_long_long_prefix("-99999999999999999999999"), i64::MIN);
+    }
+
+    #[test]
+    fn test_parse_double_prefix() {
+        assert_eq!(parse_double_prefix(" -3.14"), -3.14);
+        assert_eq!(parse_double_prefix("42abc"), 42.0);
+        assert_eq!(parse_double_prefix("1.5e3x"), 1500.0);
+        assert_eq!(parse_double_prefix("1.5e"), 1.5);
+        assert_eq!(parse_double_prefix("2E-1"), 0.2);
+        assert_eq!(parse_double_prefix(".5"), 0.5);
+        assert_eq!(parse_double_prefix("5."), 5.0);
+        assert_eq!(parse_double_prefix("1-2"), 1.0);
+        assert_eq!(parse_double_prefix("-."), 0.0);
+        assert_eq!(parse_double_prefix("abc"), 0.0);
+        assert_eq!(parse_double_prefix("1e999"), f64::INFINITY);
+    }
+}
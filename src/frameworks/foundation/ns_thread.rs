@@ -13,10 +13,9 @@ use crate::libc::pthread::thread::{
     PTHREAD_CREATE_DETACHED,
 };
 use crate::mem::{guest_size_of, MutPtr};
-use crate::msg;
 use crate::objc::{
-    id, msg_send, nil, objc_classes, release, retain, Class, ClassExports, HostObject, NSZonePtr,
-    SEL,
+    id, msg, msg_class, msg_send, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject, NSZonePtr, SEL,
 };
 use crate::Environment;
 use std::time::Duration;
@@ -117,7 +116,11 @@ pub fn _touchHLE_NSThreadInvocationHelper(env: &mut Environment, ns_thread_obj:
         selector,
         object,
     } = env.objc.borrow(ns_thread_obj);
+    // The new thread has no autorelease pool yet, so anything the selector
+    // autoreleases would otherwise leak.
+    let pool: id = msg_class![env; NSAutoreleasePool new];
     () = msg_send(env, (target, selector.unwrap(), object));
+    () = msg![env; pool drain];
 
     release(env, object);
     release(env, target);
@@ -0,0 +1,203 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSTimeZone`.
+//!
+//! Named time zones come from the host's time zone database, see
+//! [tz_database]. `CFTimeZone` is toll-free bridged to this.
+
+mod tz_database;
+
+use super::{ns_string, NSInteger, NSTimeInterval};
+use crate::frameworks::core_foundation::time::apple_epoch;
+use crate::libc::time::wall_clock_now;
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+use std::time::SystemTime;
+use tz_database::{LocalTimeType, TimeZone};
+
+#[derive(Default)]
+pub struct State {
+    /// `[NSTimeZone systemTimeZone]`, loaded on first use.
+    system_time_zone: Option<id>,
+    /// Set by `[NSTimeZone setDefaultTimeZone:]`.
+    default_time_zone: Option<id>,
+}
+
+struct NSTimeZoneHostObject {
+    time_zone: TimeZone,
+}
+impl HostObject for NSTimeZoneHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSTimeZone: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSTimeZoneHostObject {
+        time_zone: TimeZone::gmt(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)timeZoneWithName:(id)name { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithName:name];
+    autorelease(env, new)
+}
+
++ (id)timeZoneForSecondsFromGMT:(NSInteger)seconds {
+    // Apple names these like "GMT+0100".
+    let minutes = seconds.abs() / 60;
+    let name = format!(
+        "GMT{}{:02}{:02}",
+        if seconds < 0 { '-' } else { '+' },
+        minutes / 60,
+        minutes % 60,
+    );
+    let host_object = Box::new(NSTimeZoneHostObject {
+        time_zone: TimeZone::fixed(name, seconds),
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
++ (id)systemTimeZone {
+    if let Some(system) = env.framework_state.foundation.ns_time_zone.system_time_zone {
+        return system;
+    }
+    let time_zone = TimeZone::load_local();
+    log_dbg!("System time zone is {:?}", time_zone.name);
+    let host_object = Box::new(NSTimeZoneHostObject { time_zone });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    env.framework_state.foundation.ns_time_zone.system_time_zone = Some(new);
+    new
+}
++ (())resetSystemTimeZone {
+    let state = &mut env.framework_state.foundation.ns_time_zone;
+    if let Some(system) = state.system_time_zone.take() {
+        release(env, system);
+    }
+}
+
++ (id)defaultTimeZone {
+    match env.framework_state.foundation.ns_time_zone.default_time_zone {
+        Some(default) => default,
+        None => msg![env; this systemTimeZone],
+    }
+}
++ (())setDefaultTimeZone:(id)time_zone { // NSTimeZone*
+    retain(env, time_zone);
+    let state = &mut env.framework_state.foundation.ns_time_zone;
+    if let Some(old) = std::mem::replace(&mut state.default_time_zone, Some(time_zone)) {
+        release(env, old);
+    }
+}
+
+// Apple's implementation returns an object that always forwards to the default
+// time zone, even if it changes later.
+// TODO: do that too?
++ (id)localTimeZone {
+    msg![env; this defaultTimeZone]
+}
+
+- (id)initWithName:(id)name { // NSString*
+    let name = ns_string::to_rust_string(env, name);
+    let Some(time_zone) = TimeZone::load(&name) else {
+        log!("Warning: unknown time zone {:?}, returning nil", name);
+        release(env, this);
+        return nil;
+    };
+    env.objc.borrow_mut::<NSTimeZoneHostObject>(this).time_zone = time_zone;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (id)name {
+    let name = env.objc.borrow::<NSTimeZoneHostObject>(this).time_zone.name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (id)description {
+    msg![env; this name]
+}
+
+- (NSInteger)secondsFromGMT {
+    local_time_type_now(env, this).offset
+}
+- (NSInteger)secondsFromGMTForDate:(id)date { // NSDate*
+    let time: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    seconds_from_gmt(env, this, time)
+}
+
+- (id)abbreviation {
+    let abbreviation = local_time_type_now(env, this).abbreviation;
+    let abbreviation = ns_string::from_rust_string(env, abbreviation);
+    autorelease(env, abbreviation)
+}
+- (id)abbreviationForDate:(id)date { // NSDate*
+    let time: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    let abbreviation = local_time_type(env, this, time).abbreviation;
+    let abbreviation = ns_string::from_rust_string(env, abbreviation);
+    autorelease(env, abbreviation)
+}
+
+- (bool)isDaylightSavingTime {
+    local_time_type_now(env, this).is_dst
+}
+- (bool)isDaylightSavingTimeForDate:(id)date { // NSDate*
+    let time: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    local_time_type(env, this, time).is_dst
+}
+
+- (bool)isEqualToTimeZone:(id)other { // NSTimeZone*
+    if other == nil {
+        return false;
+    }
+    let name = &env.objc.borrow::<NSTimeZoneHostObject>(this).time_zone.name;
+    let other_name = &env.objc.borrow::<NSTimeZoneHostObject>(other).time_zone.name;
+    name == other_name
+}
+
+@end
+
+};
+
+/// Get the local time type of a time zone at a time relative to the reference
+/// date, like an `NSDate`.
+fn local_time_type(env: &mut Environment, time_zone: id, time: NSTimeInterval) -> LocalTimeType {
+    let unix_epoch_offset = apple_epoch()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let unix_time = time.floor() as i64 + unix_epoch_offset;
+    env.objc
+        .borrow::<NSTimeZoneHostObject>(time_zone)
+        .time_zone
+        .local_time_type(unix_time)
+        .clone()
+}
+
+fn local_time_type_now(env: &mut Environment, time_zone: id) -> LocalTimeType {
+    let now = wall_clock_now(env)
+        .duration_since(apple_epoch())
+        .unwrap()
+        .as_secs_f64();
+    local_time_type(env, time_zone, now)
+}
+
+/// Shortcut for host code: the offset of a time zone from GMT at a time
+/// relative to the reference date, like `secondsFromGMTForDate:`.
+pub fn seconds_from_gmt(env: &mut Environment, time_zone: id, time: NSTimeInterval) -> NSInteger {
+    local_time_type(env, time_zone, time).offset
+}
@@ -0,0 +1,549 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Reading time zones from the host's time zone database.
+//!
+//! Unix-like hosts have the IANA time zone database in the
+//! [TZif format](https://www.rfc-editor.org/rfc/rfc8536.html), normally in
+//! `/usr/share/zoneinfo`. Other hosts (Windows, Android) don't, so only GMT
+//! and fixed offsets from it are available there.
+
+use std::path::{Path, PathBuf};
+
+const ZONEINFO_DIRS: &[&str] = &[
+    "/usr/share/zoneinfo",
+    "/usr/lib/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+];
+
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// The offset and name of local time at some moment in a time zone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalTimeType {
+    /// Seconds east of GMT.
+    pub offset: i32,
+    pub is_dst: bool,
+    pub abbreviation: String,
+}
+
+#[derive(Debug)]
+pub struct TimeZone {
+    pub name: String,
+    /// Sorted transition times (UNIX time) and the index of the local time
+    /// type used from then on.
+    transitions: Vec<(i64, usize)>,
+    /// Never empty.
+    types: Vec<LocalTimeType>,
+    /// Rule for times after the last transition.
+    rule: Option<Rule>,
+}
+
+/// A POSIX `TZ` string, e.g. `EST5EDT,M3.2.0,M11.1.0`.
+#[derive(Debug, PartialEq)]
+struct Rule {
+    standard: LocalTimeType,
+    /// Daylight saving time and the dates it starts and ends, if there is any.
+    dst: Option<(LocalTimeType, RuleDate, RuleDate)>,
+}
+
+#[derive(Debug, PartialEq)]
+struct RuleDate {
+    day: RuleDay,
+    /// Seconds since local midnight. This can be negative or more than a day.
+    time: i64,
+}
+
+#[derive(Debug, PartialEq)]
+enum RuleDay {
+    /// `Jn`: 1 to 365, where February 29 is never counted.
+    JulianWithoutLeapDay(i64),
+    /// `n`: 0 to 365, where February 29 is counted.
+    Julian(i64),
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` (5 is the last one) of
+    /// month `m`.
+    MonthWeekDay { month: i64, week: i64, weekday: i64 },
+}
+
+impl TimeZone {
+    pub fn gmt() -> TimeZone {
+        TimeZone::fixed("GMT".to_string(), 0)
+    }
+
+    /// A time zone with a fixed offset from GMT, in seconds.
+    pub fn fixed(name: String, offset: i32) -> TimeZone {
+        TimeZone {
+            types: vec![LocalTimeType {
+                offset,
+                is_dst: false,
+                abbreviation: name.clone(),
+            }],
+            name,
+            transitions: Vec::new(),
+            rule: None,
+        }
+    }
+
+    /// Load a time zone by its name in the database, e.g. `Europe/London`.
+    pub fn load(name: &str) -> Option<TimeZone> {
+        // Don't allow escaping from the database directory.
+        if name.is_empty()
+            || name.starts_with('/')
+            || name.split('/').any(|part| part.is_empty() || part == "..")
+        {
+            return None;
+        }
+        let zone = zoneinfo_dirs()
+            .into_iter()
+            .find_map(|dir| std::fs::read(dir.join(name)).ok())
+            .and_then(|data| parse_tzif(name.to_string(), &data));
+        match zone {
+            Some(zone) => Some(zone),
+            None if name == "GMT" || name == "UTC" => Some(TimeZone::fixed(name.to_string(), 0)),
+            None => None,
+        }
+    }
+
+    /// Load the host's time zone, falling back to GMT if it can't be found.
+    pub fn load_local() -> TimeZone {
+        if let Ok(tz) = std::env::var("TZ") {
+            // TODO: support POSIX TZ strings here too
+            if let Some(zone) = TimeZone::load(tz.trim_start_matches(':')) {
+                return zone;
+            }
+        }
+
+        // This is usually a link into the database, which tells us the name.
+        let path = Path::new("/etc/localtime");
+        if let Ok(data) = std::fs::read(path) {
+            let name = std::fs::read_link(path)
+                .ok()
+                .and_then(|target| {
+                    let target = target.to_str()?;
+                    Some(target.split_once("zoneinfo/")?.1.to_string())
+                })
+                .unwrap_or_else(|| "localtime".to_string());
+            if let Some(zone) = parse_tzif(name, &data) {
+                return zone;
+            }
+        }
+
+        log!("Couldn't find the host's time zone, using GMT.");
+        TimeZone::gmt()
+    }
+
+    /// Get the local time type at some UNIX time.
+    pub fn local_time_type(&self, time: i64) -> &LocalTimeType {
+        let index = self
+            .transitions
+            .partition_point(|&(transition, _)| transition <= time);
+        if index == self.transitions.len() {
+            if let Some(ref rule) = self.rule {
+                return rule.local_time_type(time);
+            }
+        }
+        // Times before the first transition use the first type.
+        match index {
+            0 => &self.types[0],
+            _ => &self.types[self.transitions[index - 1].1],
+        }
+    }
+}
+
+fn zoneinfo_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::var_os("TZDIR") {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs.extend(ZONEINFO_DIRS.iter().map(PathBuf::from));
+    dirs
+}
+
+struct Reader<'a>(&'a [u8]);
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.0.len() < count {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Some(taken)
+    }
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+    fn be_u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn be_i32(&mut self) -> Option<i32> {
+        Some(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn be_i64(&mut self) -> Option<i64> {
+        Some(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+struct TzifHeader {
+    version: u8,
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+fn parse_tzif_header(reader: &mut Reader) -> Option<TzifHeader> {
+    if reader.take(4)? != b"TZif" {
+        return None;
+    }
+    let version = reader.u8()?;
+    reader.take(15)?;
+    let mut count = || -> Option<usize> { Some(reader.be_u32()? as usize) };
+    Some(TzifHeader {
+        version,
+        isutcnt: count()?,
+        isstdcnt: count()?,
+        leapcnt: count()?,
+        timecnt: count()?,
+        typecnt: count()?,
+        charcnt: count()?,
+    })
+}
+
+fn parse_tzif(name: String, data: &[u8]) -> Option<TimeZone> {
+    let mut reader = Reader(data);
+    let mut header = parse_tzif_header(&mut reader)?;
+    let mut time_size = 4;
+    // Version 2 and later files have a second copy of the data with 64-bit
+    // times, which is the one to use.
+    if header.version >= b'2' {
+        let v1_size = header.timecnt * 5
+            + header.typecnt * 6
+            + header.charcnt
+            + header.leapcnt * 8
+            + header.isstdcnt
+            + header.isutcnt;
+        reader.take(v1_size)?;
+        header = parse_tzif_header(&mut reader)?;
+        time_size = 8;
+    }
+    if header.typecnt == 0 {
+        return None;
+    }
+
+    let mut times = Vec::with_capacity(header.timecnt);
+    for _ in 0..header.timecnt {
+        times.push(if time_size == 8 {
+            reader.be_i64()?
+        } else {
+            reader.be_i32()?.into()
+        });
+    }
+    let mut transitions = Vec::with_capacity(header.timecnt);
+    for time in times {
+        let index = reader.u8()? as usize;
+        if index >= header.typecnt {
+            return None;
+        }
+        transitions.push((time, index));
+    }
+    let mut types_raw = Vec::with_capacity(header.typecnt);
+    for _ in 0..header.typecnt {
+        types_raw.push((reader.be_i32()?, reader.u8()? != 0, reader.u8()? as usize));
+    }
+    let chars = reader.take(header.charcnt)?;
+    let types = types_raw
+        .into_iter()
+        .map(|(offset, is_dst, abbreviation_index)| {
+            let abbreviation = chars.get(abbreviation_index..)?;
+            let end = abbreviation.iter().position(|&c| c == b'\0')?;
+            Some(LocalTimeType {
+                offset,
+                is_dst,
+                abbreviation: String::from_utf8_lossy(&abbreviation[..end]).into_owned(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    reader.take(header.leapcnt * (time_size + 4) + header.isstdcnt + header.isutcnt)?;
+
+    // The footer is a POSIX TZ string between newlines.
+    let rule = if header.version >= b'2' {
+        std::str::from_utf8(reader.0)
+            .ok()
+            .and_then(|footer| footer.strip_prefix('\n')?.split('\n').next())
+            .filter(|footer| !footer.is_empty())
+            .and_then(parse_rule)
+    } else {
+        None
+    };
+
+    Some(TimeZone {
+        name,
+        transitions,
+        types,
+        rule,
+    })
+}
+
+struct RuleParser<'a>(&'a str);
+impl<'a> RuleParser<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        if let Some(rest) = self.0.strip_prefix(c) {
+            self.0 = rest;
+            true
+        } else {
+            false
+        }
+    }
+    fn number(&mut self) -> Option<i64> {
+        let end = self
+            .0
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.0.len());
+        let (digits, rest) = self.0.split_at(end);
+        self.0 = rest;
+        digits.parse().ok()
+    }
+    /// `std` or `dst`: either at least three letters, or anything in angle
+    /// brackets.
+    fn name(&mut self) -> Option<String> {
+        let name = if self.eat('<') {
+            let (name, rest) = self.0.split_once('>')?;
+            self.0 = rest;
+            name
+        } else {
+            let end = self
+                .0
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(self.0.len());
+            let (name, rest) = self.0.split_at(end);
+            self.0 = rest;
+            name
+        };
+        (name.len() >= 3).then(|| name.to_string())
+    }
+    /// `[+|-]hh[:mm[:ss]]`, in seconds.
+    fn time(&mut self) -> Option<i64> {
+        let sign = if self.eat('-') {
+            -1
+        } else {
+            self.eat('+');
+            1
+        };
+        let mut seconds = self.number()? * 60 * 60;
+        if self.eat(':') {
+            seconds += self.number()? * 60;
+            if self.eat(':') {
+                seconds += self.number()?;
+            }
+        }
+        Some(sign * seconds)
+    }
+    fn date(&mut self) -> Option<RuleDate> {
+        let day = if self.eat('J') {
+            RuleDay::JulianWithoutLeapDay(self.number()?)
+        } else if self.eat('M') {
+            let month = self.number()?;
+            if !self.eat('.') {
+                return None;
+            }
+            let week = self.number()?;
+            if !self.eat('.') {
+                return None;
+            }
+            let weekday = self.number()?;
+            if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                return None;
+            }
+            RuleDay::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            }
+        } else {
+            RuleDay::Julian(self.number()?)
+        };
+        // The default is 02:00.
+        let time = if self.eat('/') {
+            self.time()?
+        } else {
+            2 * 60 * 60
+        };
+        Some(RuleDate { day, time })
+    }
+}
+
+fn parse_rule(rule: &str) -> Option<Rule> {
+    let mut parser = RuleParser(rule);
+    // POSIX offsets are west of GMT, the opposite of everything else.
+    let standard = LocalTimeType {
+        abbreviation: parser.name()?,
+        offset: (-parser.time()?).try_into().ok()?,
+        is_dst: false,
+    };
+    if parser.0.is_empty() {
+        return Some(Rule {
+            standard,
+            dst: None,
+        });
+    }
+
+    let abbreviation = parser.name()?;
+    // The default is an hour ahead of standard time.
+    let offset = if parser.0.starts_with(',') {
+        standard.offset + 60 * 60
+    } else {
+        (-parser.time()?).try_into().ok()?
+    };
+    if !parser.eat(',') {
+        return None;
+    }
+    let start = parser.date()?;
+    if !parser.eat(',') {
+        return None;
+    }
+    let end = parser.date()?;
+    if !parser.0.is_empty() {
+        return None;
+    }
+    Some(Rule {
+        standard,
+        dst: Some((
+            LocalTimeType {
+                offset,
+                is_dst: true,
+                abbreviation,
+            },
+            start,
+            end,
+        )),
+    })
+}
+
+impl Rule {
+    fn local_time_type(&self, time: i64) -> &LocalTimeType {
+        let Some((ref dst, ref start, ref end)) = self.dst else {
+            return &self.standard;
+        };
+        let year = year_from_days((time + i64::from(self.standard.offset)).div_euclid(DAY_SECONDS));
+        // DST starts at a time in standard time and ends at a time in DST.
+        let start = start.local_time(year) - i64::from(self.standard.offset);
+        let end = end.local_time(year) - i64::from(dst.offset);
+        // In the southern hemisphere, DST spans the new year.
+        let is_dst = if start < end {
+            (start..end).contains(&time)
+        } else {
+            !(end..start).contains(&time)
+        };
+        if is_dst {
+            dst
+        } else {
+            &self.standard
+        }
+    }
+}
+
+impl RuleDate {
+    /// Seconds since the UNIX epoch, in local time, that this date and time
+    /// falls on in some year.
+    fn local_time(&self, year: i64) -> i64 {
+        let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let new_year = days_from_civil(year, 1, 1);
+        let day = match self.day {
+            RuleDay::JulianWithoutLeapDay(day) => {
+                new_year + day - 1 + i64::from(is_leap_year && day >= 60)
+            }
+            RuleDay::Julian(day) => new_year + day,
+            RuleDay::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month, 1);
+                let next_month = if month == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, month + 1, 1)
+                };
+                // 1970-01-01 was a Thursday.
+                let first_weekday = (first + 4).rem_euclid(7);
+                let mut day = first + (weekday - first_weekday).rem_euclid(7) + (week - 1) * 7;
+                // Week 5 means the last week, which might be the fourth.
+                if day >= next_month {
+                    day -= 7;
+                }
+                day
+            }
+        };
+        day * DAY_SECONDS + self.time
+    }
+}
+
+/// Days since the UNIX epoch of a date in the proleptic Gregorian calendar.
+/// The algorithm is from <https://howardhinnant.github.io/date_algorithms.html>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of [days_from_civil], but only the year.
+fn year_from_days(days: i64) -> i64 {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // This counts months from March, so January and February are in the next
+    // year.
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    era * 400 + year_of_era + i64::from(month_from_march >= 10)
+}
+
+#[cfg(test)]
+#[test]
+fn test_rules() {
+    let rule = parse_rule("EST5EDT,M3.2.0,M11.1.0").unwrap();
+    let offset_at = |time| rule.local_time_type(time).offset / 3600;
+    // 2023-01-15 12:00 UTC
+    assert_eq!(offset_at(1673784000), -5);
+    // 2023-07-15 12:00 UTC
+    assert_eq!(offset_at(1689422400), -4);
+    // DST started at 2023-03-12 07:00 UTC and ended at 2023-11-05 06:00 UTC.
+    assert_eq!(offset_at(1678604399), -5);
+    assert_eq!(offset_at(1678604400), -4);
+    assert_eq!(offset_at(1699163999), -4);
+    assert_eq!(offset_at(1699164000), -5);
+
+    // Southern hemisphere, with names in angle brackets.
+    let rule = parse_rule("<+1030>-10:30<+11>-11,M10.1.0,M4.1.0").unwrap();
+    let offset_at = |time| rule.local_time_type(time).offset;
+    assert_eq!(offset_at(1673784000), 11 * 3600);
+    assert_eq!(offset_at(1689422400), 10 * 3600 + 30 * 60);
+
+    let rule = parse_rule("<+0330>-3:30").unwrap();
+    assert_eq!(rule.local_time_type(0).offset, 3 * 3600 + 30 * 60);
+    assert_eq!(rule.local_time_type(0).abbreviation, "+0330");
+
+    assert!(parse_rule("EST5EDT,M13.2.0,M11.1.0").is_none());
+    assert!(parse_rule("E5").is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn test_days_from_civil() {
+    assert_eq!(days_from_civil(1970, 1, 1), 0);
+    assert_eq!(days_from_civil(2000, 3, 1), 11017);
+    assert_eq!(days_from_civil(1969, 12, 31), -1);
+    for days in [-1, 0, 59, 365, 11016, 11017, 19722] {
+        let year = year_from_days(days);
+        assert!(days_from_civil(year, 1, 1) <= days && days < days_from_civil(year + 1, 1, 1));
+    }
+}
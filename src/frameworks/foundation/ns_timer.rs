@@ -4,10 +4,15 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `NSTimer`.
+//!
+//! Timers are driven by the animation clock (see
+//! [crate::frameworks::core_animation::frame_clock]) rather than the wall
+//! clock, so they advance in step with animations.
 
 use super::ns_run_loop::NSDefaultRunLoopMode;
 use super::NSTimeInterval;
 use super::{ns_run_loop, ns_string};
+use crate::frameworks::core_animation::frame_clock;
 use crate::objc::{
     autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
     HostObject, SEL,
@@ -45,6 +50,7 @@ pub const CLASSES: ClassExports = objc_classes! {
                     repeats:(bool)repeats {
     let ns_interval = ns_interval.max(0.0001);
     let rust_interval = Duration::from_secs_f64(ns_interval);
+    let now = frame_clock::now(env);
 
     retain(env, target);
     retain(env, user_info);
@@ -56,7 +62,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         selector,
         user_info,
         repeats,
-        due_by: Some(Instant::now().checked_add(rust_interval).unwrap()),
+        due_by: Some(now.checked_add(rust_interval).unwrap()),
         run_loop: nil,
     });
     let new = env.objc.alloc_object(this, host_object, &mut env.mem);
@@ -118,6 +124,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<NSTimerHostObject>(this).due_by.is_some()
 }
 
+- (())setFireDate:(id)date { // NSDate*
+    let now: id = msg_class![env; NSDate date];
+    let seconds: NSTimeInterval = msg![env; date timeIntervalSinceDate:now];
+    let due_by = frame_clock::now(env) + Duration::from_secs_f64(seconds.max(0.0));
+    let host_object = env.objc.borrow_mut::<NSTimerHostObject>(this);
+    // This has no effect on invalidated timers.
+    if host_object.due_by.is_some() {
+        host_object.due_by = Some(due_by);
+    }
+}
+
 -(())invalidate {
     // Timer might already be invalid, don't try to remove it twice.
     if env.objc.borrow_mut::<NSTimerHostObject>(this).due_by.take().is_some() {
@@ -143,7 +160,7 @@ pub(super) fn set_run_loop(env: &mut Environment, timer: id, run_loop: id) {
 /// For use by `NSRunLoop`: check if a timer is due to fire and fire it if
 /// necessary.
 ///
-/// Returns the next firing time, if any.
+/// Returns the next firing time (in wall-clock time), if any.
 pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant> {
     let &NSTimerHostObject {
         ns_interval,
@@ -159,10 +176,10 @@ pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant>
     // invalidated timers should have already been removed from the run loop
     let due_by = due_by.unwrap();
 
-    let now = Instant::now();
+    let now = frame_clock::now(env);
 
     if due_by > now {
-        return Some(due_by);
+        return Some(frame_clock::wall_time_for(env, due_by));
     }
 
     let overdue_by = now.duration_since(due_by);
@@ -190,7 +207,9 @@ pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant>
         let advance_by = (overdue_by.as_secs_f64() / ns_interval).max(1.0).ceil();
         assert!(advance_by == (advance_by as u32) as f64);
         let advance_by = advance_by as u32;
-        if advance_by > 1 {
+        // Firing can be late by up to a frame, since the clock only advances
+        // when a frame is presented. That isn't lag.
+        if advance_by > 1 && overdue_by > frame_clock::frame_delta(env) {
             log_dbg!("Warning: Timer {:?} is lagging. It is overdue by {}s and has missed {} interval(s)!", timer, overdue_by.as_secs_f64(), advance_by - 1);
         }
         let advance_by = rust_interval.checked_mul(advance_by).unwrap();
@@ -216,5 +235,5 @@ pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant>
     release(env, timer);
     release(env, pool);
 
-    new_due_by
+    new_due_by.map(|new_due_by| frame_clock::wall_time_for(env, new_due_by))
 }
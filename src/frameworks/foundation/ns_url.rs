@@ -5,7 +5,7 @@
  */
 //! `NSURL`.
 
-use super::ns_string::{from_rust_string, to_rust_string, NSUTF8StringEncoding};
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string, NSUTF8StringEncoding};
 use super::NSUInteger;
 use crate::fs::{GuestPath, GuestPathBuf};
 use crate::mem::MutPtr;
@@ -136,12 +136,33 @@ pub const CLASSES: ClassExports = objc_classes! {
         NSURLHostObject::FileURL { ns_string, .. } => ns_string,
         NSURLHostObject::OtherURL { ns_string } => {
             // TODO: full RFC 1808 resolution
-            assert!(to_rust_string(env, ns_string).starts_with("http"));
+            assert!(to_rust_string(env, ns_string).contains(':'));
             ns_string
         },
     }
 }
 
+- (id)scheme {
+    let ns_string = match *env.objc.borrow(this) {
+        NSURLHostObject::FileURL { .. } => return get_static_str(env, "file"),
+        NSURLHostObject::OtherURL { ns_string } => ns_string,
+    };
+    // FIXME: This should be done when parsing the URL.
+    let url = to_rust_string(env, ns_string);
+    let Some((scheme, _)) = url.split_once(':') else {
+        return nil;
+    };
+    // RFC 3986: scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+    if !scheme.starts_with(|c: char| c.is_ascii_alphabetic()) || !scheme
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+    {
+        return nil;
+    }
+    let scheme = from_rust_string(env, scheme.to_string());
+    autorelease(env, scheme)
+}
+
 - (id)absoluteURL {
     // FIXME: don't assume URL is already absolute
     let &NSURLHostObject::OtherURL { .. } = env.objc.borrow(this) else {
@@ -176,3 +197,68 @@ pub fn to_rust_path(env: &mut Environment, url: id) -> Cow<'static, GuestPath> {
         Cow::Owned(path_buf) => Cow::Owned(path_buf.into()),
     }
 }
+
+/// The parts of a URL needed to make a request over the network, see
+/// [network_location].
+#[derive(Debug)]
+pub struct NetworkLocation {
+    /// Lower-case.
+    pub scheme: String,
+    /// Lower-case. IPv6 addresses don't have brackets.
+    pub host: String,
+    pub port: Option<u16>,
+    /// The path and query, without the fragment. Never empty.
+    pub path: String,
+}
+
+/// Shortcut for host code: split a URL like `http://host:port/path?query` into
+/// its parts. Returns [None] for nil, file URLs and URLs
+/// without a host.
+pub fn network_location(env: &mut Environment, url: id) -> Option<NetworkLocation> {
+    if url == nil {
+        return None;
+    }
+    let &NSURLHostObject::OtherURL { ns_string } = env.objc.borrow(url) else {
+        return None;
+    };
+    parse_network_location(&to_rust_string(env, ns_string))
+}
+
+fn parse_network_location(url: &str) -> Option<NetworkLocation> {
+    // FIXME: This should do unescaping.
+    let (scheme, rest) = url.split_once("://")?;
+    let rest = rest.split('#').next().unwrap();
+    let authority_end = rest.find(|c| c == '/' || c == '?').unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+    // User info isn't needed.
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_user_info, authority)| authority);
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        (host, rest.strip_prefix(':'))
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let port = match port {
+        None | Some("") => None,
+        Some(port) => Some(port.parse().ok()?),
+    };
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+    Some(NetworkLocation {
+        scheme: scheme.to_ascii_lowercase(),
+        host: host.to_ascii_lowercase(),
+        port,
+        path,
+    })
+}
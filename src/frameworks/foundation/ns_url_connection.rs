@@ -0,0 +1,623 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLConnection`.
+//!
+//! Requests are made over plain HTTP/1.1 by a separate host thread, so other
+//! guest threads can keep running while one waits for the network. HTTPS isn't
+//! supported.
+//!
+//! Asynchronous requests deliver the whole response at once: the delegate gets
+//! `connection:didReceiveResponse:`, then a single `connection:didReceiveData:`
+//! with the entire body, then `connectionDidFinishLoading:`, all from the run
+//! loop the connection is scheduled in.
+//!
+//! Cookies are sent and stored automatically unless the request says not to,
+//! see [super::ns_http_cookie_storage].
+
+use super::ns_http_cookie_storage::{cookie_header_for_url, store_cookies_from_response};
+use super::ns_string::get_static_str;
+use super::ns_url::network_location;
+use super::ns_url::NetworkLocation;
+use super::ns_url_request::NSURLRequestHostObject;
+use super::ns_url_response::new_http_response;
+use super::{ns_run_loop, NSInteger, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::libc::dispatch::wait_until;
+use crate::mem::{ConstVoidPtr, MutPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+pub const NSURLErrorDomain: &str = "NSURLErrorDomain";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_NSURLErrorDomain",
+    HostConstant::NSString(NSURLErrorDomain),
+)];
+
+const NSURLErrorTimedOut: NSInteger = -1001;
+const NSURLErrorUnsupportedURL: NSInteger = -1002;
+const NSURLErrorCannotFindHost: NSInteger = -1003;
+const NSURLErrorCannotConnectToHost: NSInteger = -1004;
+const NSURLErrorNetworkConnectionLost: NSInteger = -1005;
+const NSURLErrorBadServerResponse: NSInteger = -1011;
+const NSURLErrorDataLengthExceedsMaximum: NSInteger = -1103;
+
+/// Responses are kept in memory, so this limits how much a server can make
+/// touchHLE allocate.
+const MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+
+/// A request that has been sent (or failed before it could be).
+struct PendingRequest {
+    /// `NSURL*`, kept alive by the request.
+    url: id,
+    location: NetworkLocation,
+    should_handle_cookies: bool,
+    receiver: Receiver<Result<HttpResponse, NSInteger>>,
+}
+
+#[derive(Default)]
+struct NSURLConnectionHostObject {
+    /// `NSURLRequest*`, a copy of the one the connection was created with.
+    request: id,
+    /// Strong reference, released once the connection finishes, fails or is
+    /// cancelled.
+    delegate: id,
+    /// Weak reference. The run loop has a strong reference to the connection
+    /// while it's scheduled.
+    run_loop: id,
+    started: bool,
+    /// Errors that happen when starting the request are kept here too, so
+    /// that they can be reported to the delegate from the run loop.
+    pending: Option<Result<PendingRequest, NSInteger>>,
+}
+impl HostObject for NSURLConnectionHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLConnection: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSURLConnectionHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)connectionWithRequest:(id)request // NSURLRequest*
+                   delegate:(id)delegate {
+    let new: id = msg_class![env; NSURLConnection alloc];
+    let new: id = msg![env; new initWithRequest:request delegate:delegate];
+    autorelease(env, new)
+}
+
++ (bool)canHandleRequest:(id)request { // NSURLRequest*
+    let url: id = msg![env; request URL];
+    network_location(env, url).is_some_and(|location| location.scheme == "http")
+}
+
++ (id)sendSynchronousRequest:(id)request // NSURLRequest*
+           returningResponse:(MutPtr<id>)response // NSURLResponse**
+                       error:(MutPtr<id>)error { // NSError**
+    match send_request(env, request) {
+        Ok((new_response, data)) => {
+            if !response.is_null() {
+                env.mem.write(response, new_response);
+            }
+            data
+        }
+        Err(code) => {
+            if !response.is_null() {
+                env.mem.write(response, nil);
+            }
+            if !error.is_null() {
+                let new_error = new_error(env, code);
+                let new_error = autorelease(env, new_error);
+                env.mem.write(error, new_error);
+            }
+            nil
+        }
+    }
+}
+
+- (id)initWithRequest:(id)request // NSURLRequest*
+             delegate:(id)delegate {
+    msg![env; this initWithRequest:request delegate:delegate startImmediately:true]
+}
+
+- (id)initWithRequest:(id)request // NSURLRequest*
+             delegate:(id)delegate
+     startImmediately:(bool)start_immediately {
+    let request: id = msg![env; request copy];
+    retain(env, delegate);
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    host_object.request = request;
+    host_object.delegate = delegate;
+    if start_immediately {
+        () = msg![env; this start];
+    }
+    this
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow::<NSURLConnectionHostObject>(this);
+    // The run loop keeps scheduled connections alive, so run_loop must be nil.
+    assert!(host_object.run_loop == nil);
+    let request = host_object.request;
+    let delegate = host_object.delegate;
+    release(env, request);
+    release(env, delegate);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)originalRequest {
+    env.objc.borrow::<NSURLConnectionHostObject>(this).request
+}
+
+- (())scheduleInRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(id)_mode { // NSRunLoopMode
+    // TODO: handle run loop modes
+    let old_run_loop = std::mem::replace(
+        &mut env.objc.borrow_mut::<NSURLConnectionHostObject>(this).run_loop,
+        run_loop,
+    );
+    if old_run_loop != nil && old_run_loop != run_loop {
+        ns_run_loop::remove_connection(env, old_run_loop, this);
+    }
+    ns_run_loop::add_connection(env, run_loop, this);
+}
+- (())unscheduleFromRunLoop:(id)run_loop // NSRunLoop*
+                    forMode:(id)_mode { // NSRunLoopMode
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    if host_object.run_loop == run_loop {
+        host_object.run_loop = nil;
+        ns_run_loop::remove_connection(env, run_loop, this);
+    }
+}
+
+- (())start {
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    if std::mem::replace(&mut host_object.started, true) {
+        log!("Warning: NSURLConnection {:?} was already started", this);
+        return;
+    }
+    let request = host_object.request;
+    if host_object.run_loop == nil {
+        let run_loop: id = msg_class![env; NSRunLoop currentRunLoop];
+        () = msg![env; this scheduleInRunLoop:run_loop forMode:nil];
+    }
+    let pending = start_request(env, request);
+    env.objc.borrow_mut::<NSURLConnectionHostObject>(this).pending = Some(pending);
+}
+
+- (())cancel {
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    // The request's thread will finish on its own, nothing receives the
+    // result.
+    host_object.pending = None;
+    let delegate = std::mem::take(&mut host_object.delegate);
+    let run_loop = host_object.run_loop;
+    release(env, delegate);
+    if run_loop != nil {
+        () = msg![env; this unscheduleFromRunLoop:run_loop forMode:nil];
+    }
+}
+
+@end
+
+};
+
+struct HttpResponse {
+    status_code: NSInteger,
+    /// In the order received. Names may be repeated.
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// Create a new `NSError*` (+1 reference) in [NSURLErrorDomain].
+fn new_error(env: &mut Environment, code: NSInteger) -> id {
+    let domain = get_static_str(env, NSURLErrorDomain);
+    let new_error: id = msg_class![env; NSError alloc];
+    msg![env; new_error initWithDomain:domain code:code userInfo:nil]
+}
+
+/// Make a request, returning the (autoreleased) `NSHTTPURLResponse*` and
+/// `NSData*` of the body, or an `NSURLErrorDomain` error code.
+fn send_request(env: &mut Environment, request: id) -> Result<(id, id), NSInteger> {
+    let pending = start_request(env, request)?;
+    let mut result = None;
+    wait_until(env, None, |_| {
+        result = match pending.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(NSURLErrorNetworkConnectionLost)),
+        };
+        result.is_some()
+    });
+    let (response, data) = finish_request(env, &pending, result.unwrap())?;
+    Ok((autorelease(env, response), autorelease(env, data)))
+}
+
+/// Start making a request on a separate host thread.
+fn start_request(env: &mut Environment, request: id) -> Result<PendingRequest, NSInteger> {
+    let request_data = env.objc.borrow::<NSURLRequestHostObject>(request).clone();
+    let url = request_data.url;
+    let Some(location) = network_location(env, url) else {
+        log!(
+            "Warning: NSURLConnection can't handle URL (NSURL*){:?}",
+            url
+        );
+        return Err(NSURLErrorUnsupportedURL);
+    };
+    if location.scheme != "http" {
+        log!(
+            "TODO: NSURLConnection request with scheme {:?}",
+            location.scheme
+        );
+        return Err(NSURLErrorUnsupportedURL);
+    }
+    let port = location.port.unwrap_or(80);
+
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\n",
+        request_data.http_method, location.path
+    );
+    if request_data.header("Host").is_none() {
+        if location.host.contains(':') {
+            head.push_str(&format!("Host: [{}]", location.host));
+        } else {
+            head.push_str(&format!("Host: {}", location.host));
+        }
+        if let Some(port) = location.port {
+            head.push_str(&format!(":{}", port));
+        }
+        head.push_str("\r\n");
+    }
+    for (name, value) in &request_data.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if request_data.should_handle_cookies && request_data.header("Cookie").is_none() {
+        if let Some(cookies) = cookie_header_for_url(env, &location) {
+            head.push_str(&format!("Cookie: {}\r\n", cookies));
+        }
+    }
+    let body_length: NSUInteger = msg![env; (request_data.body) length];
+    let body = if body_length != 0 {
+        let bytes: ConstVoidPtr = msg![env; (request_data.body) bytes];
+        env.mem.bytes_at(bytes.cast(), body_length).to_vec()
+    } else {
+        Vec::new()
+    };
+    if request_data.header("Content-Length").is_none()
+        && (!body.is_empty() || request_data.http_method == "POST")
+    {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    // Each request gets its own connection, so the end of the response is
+    // always clear.
+    head.push_str("Connection: close\r\n\r\n");
+    log_dbg!("NSURLConnection request to port {}:\n{}", port, head);
+
+    let mut bytes = head.into_bytes();
+    bytes.extend_from_slice(&body);
+    let host = location.host.clone();
+    let is_head = request_data.http_method == "HEAD";
+    let timeout = if request_data.timeout_interval > 0.0 {
+        Duration::from_secs_f64(request_data.timeout_interval)
+    } else {
+        Duration::from_secs(60)
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(perform_request(&host, port, &bytes, is_head, timeout));
+    });
+    Ok(PendingRequest {
+        url,
+        location,
+        should_handle_cookies: request_data.should_handle_cookies,
+        receiver,
+    })
+}
+
+/// Handle the result of a request, returning the `NSHTTPURLResponse*` and
+/// `NSData*` of the body (both +1 references), or an `NSURLErrorDomain` error
+/// code.
+fn finish_request(
+    env: &mut Environment,
+    pending: &PendingRequest,
+    result: Result<HttpResponse, NSInteger>,
+) -> Result<(id, id), NSInteger> {
+    let response = result?;
+    log_dbg!(
+        "NSURLConnection response: status {}, headers {:?}, {} byte body",
+        response.status_code,
+        response.headers,
+        response.body.len()
+    );
+
+    if pending.should_handle_cookies {
+        let set_cookie_headers: Vec<&str> = response
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Set-Cookie"))
+            .map(|(_, value)| value.as_str())
+            .collect();
+        store_cookies_from_response(env, &pending.location, &set_cookie_headers);
+    }
+
+    // TODO: follow redirects
+    let new_response = new_http_response(env, pending.url, response.status_code, &response.headers);
+
+    let length: NSUInteger = response.body.len().try_into().unwrap();
+    let alloc = env.mem.alloc(length);
+    env.mem
+        .bytes_at_mut(alloc.cast(), length)
+        .copy_from_slice(&response.body);
+    let data: id = msg_class![env; NSData alloc];
+    let data: id = msg![env; data initWithBytesNoCopy:alloc length:length];
+
+    Ok((new_response, data))
+}
+
+/// For use by `NSRunLoop`: check if a scheduled connection's request has
+/// finished, and if so, send the delegate messages.
+pub(super) fn handle_connection(env: &mut Environment, connection: id) {
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(connection);
+    let result = match host_object.pending {
+        None => return,
+        Some(Err(code)) => Err(code),
+        Some(Ok(ref pending)) => match pending.receiver.try_recv() {
+            Ok(result) => Ok(result),
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(NSURLErrorNetworkConnectionLost),
+        },
+    };
+    let pending = host_object.pending.take().unwrap().ok();
+    let delegate = std::mem::take(&mut host_object.delegate);
+    let run_loop = host_object.run_loop;
+    let result = result.and_then(|result| finish_request(env, pending.as_ref().unwrap(), result));
+
+    // The delegate might release the connection.
+    retain(env, connection);
+    () = msg![env; connection unscheduleFromRunLoop:run_loop forMode:nil];
+    match result {
+        Ok((response, data)) => {
+            if env.objc.object_has_method_named(
+                &env.mem,
+                delegate,
+                "connection:didReceiveResponse:",
+            ) {
+                () = msg![env; delegate connection:connection didReceiveResponse:response];
+            }
+            let length: NSUInteger = msg![env; data length];
+            if length != 0
+                && env.objc.object_has_method_named(
+                    &env.mem,
+                    delegate,
+                    "connection:didReceiveData:",
+                )
+            {
+                () = msg![env; delegate connection:connection didReceiveData:data];
+            }
+            if env
+                .objc
+                .object_has_method_named(&env.mem, delegate, "connectionDidFinishLoading:")
+            {
+                () = msg![env; delegate connectionDidFinishLoading:connection];
+            }
+            release(env, response);
+            release(env, data);
+        }
+        Err(code) => {
+            log_dbg!("NSURLConnection {:?} failed with code {}", connection, code);
+            if env
+                .objc
+                .object_has_method_named(&env.mem, delegate, "connection:didFailWithError:")
+            {
+                let error = new_error(env, code);
+                () = msg![env; delegate connection:connection didFailWithError:error];
+                release(env, error);
+            }
+        }
+    }
+    release(env, delegate);
+    release(env, connection);
+}
+
+fn error_code_for_io_error(err: &std::io::Error) -> NSInteger {
+    match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => NSURLErrorTimedOut,
+        ErrorKind::UnexpectedEof | ErrorKind::InvalidData => NSURLErrorBadServerResponse,
+        ErrorKind::OutOfMemory => NSURLErrorDataLengthExceedsMaximum,
+        _ => NSURLErrorNetworkConnectionLost,
+    }
+}
+
+/// Send a request and read the response. This blocks, so it's run on a
+/// separate host thread.
+fn perform_request(
+    host: &str,
+    port: u16,
+    request: &[u8],
+    is_head: bool,
+    timeout: Duration,
+) -> Result<HttpResponse, NSInteger> {
+    let addr = (host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or(NSURLErrorCannotFindHost)?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(|err| {
+        if err.kind() == ErrorKind::TimedOut {
+            NSURLErrorTimedOut
+        } else {
+            NSURLErrorCannotConnectToHost
+        }
+    })?;
+    stream.set_read_timeout(Some(timeout)).unwrap();
+    stream.set_write_timeout(Some(timeout)).unwrap();
+    stream
+        .write_all(request)
+        .map_err(|err| error_code_for_io_error(&err))?;
+    read_response(&mut BufReader::new(stream), is_head).map_err(|err| error_code_for_io_error(&err))
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> std::io::Result<String> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+/// Append exactly `size` bytes to `body`, or fail with
+/// [ErrorKind::OutOfMemory] if that would make it larger than
+/// [MAX_BODY_SIZE]. The buffer only grows as data actually arrives, so a bogus
+/// size from the server can't cause a huge allocation up front.
+fn read_body_part<R: BufRead>(
+    reader: &mut R,
+    body: &mut Vec<u8>,
+    size: usize,
+) -> std::io::Result<()> {
+    if size > MAX_BODY_SIZE - body.len() {
+        return Err(ErrorKind::OutOfMemory.into());
+    }
+    let read = reader.by_ref().take(size as u64).read_to_end(body)?;
+    if read != size {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn read_response<R: BufRead>(reader: &mut R, is_head: bool) -> std::io::Result<HttpResponse> {
+    let invalid = || std::io::Error::from(ErrorKind::InvalidData);
+
+    // Informational (1xx) responses come before the real one.
+    let (status_code, headers) = loop {
+        // e.g. "HTTP/1.1 200 OK"
+        let status_line = read_line(reader)?;
+        let mut parts = status_line.split_whitespace();
+        if !parts
+            .next()
+            .is_some_and(|version| version.starts_with("HTTP/"))
+        {
+            return Err(invalid());
+        }
+        let status_code: NSInteger = parts
+            .next()
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(invalid)?;
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(reader)?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        if !(100..200).contains(&status_code) {
+            break (status_code, headers);
+        }
+    };
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    let mut body = Vec::new();
+    if is_head || status_code == 204 || status_code == 304 {
+        // No body.
+    } else if header("Transfer-Encoding")
+        .is_some_and(|encoding| encoding.to_ascii_lowercase().contains("chunked"))
+    {
+        loop {
+            // e.g. "1a2b;extension=value"
+            let size_line = read_line(reader)?;
+            let size = size_line.split(';').next().unwrap().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+            if size == 0 {
+                // Skip any trailer fields.
+                while !read_line(reader)?.is_empty() {}
+                break;
+            }
+            read_body_part(reader, &mut body, size)?;
+            read_line(reader)?;
+        }
+    } else if let Some(length) = header("Content-Length") {
+        let length: usize = length.parse().map_err(|_| invalid())?;
+        read_body_part(reader, &mut body, length)?;
+    } else {
+        // The body ends when the connection is closed.
+        reader
+            .by_ref()
+            .take(MAX_BODY_SIZE as u64 + 1)
+            .read_to_end(&mut body)?;
+        if body.len() > MAX_BODY_SIZE {
+            return Err(ErrorKind::OutOfMemory.into());
+        }
+    }
+
+    Ok(HttpResponse {
+        status_code,
+        headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunked_response() {
+        let response = b"HTTP/1.1 100 Continue\r\n\r\n\
+            HTTP/1.1 200 OK\r\n\
+            Set-Cookie: a=1\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Set-Cookie: b=2\r\n\
+            \r\n\
+            5\r\nhello\r\n7;x=y\r\n, world\r\n0\r\n\r\n";
+        let response = read_response(&mut &response[..], false).unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.headers.len(), 3);
+        assert_eq!(
+            response.headers[2],
+            ("Set-Cookie".to_string(), "b=2".to_string())
+        );
+        assert_eq!(response.body, b"hello, world");
+    }
+
+    #[test]
+    fn body_size_limit() {
+        // A huge Content-Length must not be allocated before the data arrives.
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\nhello",
+            usize::MAX
+        );
+        let err = read_response(&mut response.as_bytes(), false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        let err = read_response(&mut &response[..], false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            ffffffffffff\r\nhello";
+        let err = read_response(&mut &response[..], false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+    }
+}
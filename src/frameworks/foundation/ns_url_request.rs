@@ -0,0 +1,278 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLRequest` and `NSMutableURLRequest`.
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_string::{from_rust_string, to_rust_string};
+use super::{NSTimeInterval, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+
+pub type NSURLRequestCachePolicy = NSUInteger;
+const NSURLRequestUseProtocolCachePolicy: NSURLRequestCachePolicy = 0;
+
+/// The default timeout, in seconds.
+const DEFAULT_TIMEOUT_INTERVAL: NSTimeInterval = 60.0;
+
+#[derive(Clone)]
+pub(super) struct NSURLRequestHostObject {
+    /// `NSURL*`
+    pub(super) url: id,
+    pub(super) cache_policy: NSURLRequestCachePolicy,
+    pub(super) timeout_interval: NSTimeInterval,
+    pub(super) http_method: String,
+    /// Header fields in the order they were first set. No two have the same
+    /// name, ignoring case.
+    pub(super) headers: Vec<(String, String)>,
+    /// `NSData*`, may be nil.
+    pub(super) body: id,
+    pub(super) should_handle_cookies: bool,
+}
+impl HostObject for NSURLRequestHostObject {}
+impl Default for NSURLRequestHostObject {
+    fn default() -> Self {
+        NSURLRequestHostObject {
+            url: nil,
+            cache_policy: NSURLRequestUseProtocolCachePolicy,
+            timeout_interval: DEFAULT_TIMEOUT_INTERVAL,
+            http_method: "GET".to_string(),
+            headers: Vec::new(),
+            body: nil,
+            should_handle_cookies: true,
+        }
+    }
+}
+impl NSURLRequestHostObject {
+    pub(super) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLRequest: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSURLRequestHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)requestWithURL:(id)url { // NSURL*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url];
+    autorelease(env, new)
+}
+
++ (id)requestWithURL:(id)url // NSURL*
+         cachePolicy:(NSURLRequestCachePolicy)cache_policy
+     timeoutInterval:(NSTimeInterval)timeout_interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url
+                                cachePolicy:cache_policy
+                            timeoutInterval:timeout_interval];
+    autorelease(env, new)
+}
+
+- (id)initWithURL:(id)url { // NSURL*
+    msg![env; this initWithURL:url
+                   cachePolicy:NSURLRequestUseProtocolCachePolicy
+               timeoutInterval:DEFAULT_TIMEOUT_INTERVAL]
+}
+
+- (id)initWithURL:(id)url // NSURL*
+      cachePolicy:(NSURLRequestCachePolicy)cache_policy
+  timeoutInterval:(NSTimeInterval)timeout_interval {
+    let url: id = msg![env; url copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    host_object.url = url;
+    host_object.cache_policy = cache_policy;
+    host_object.timeout_interval = timeout_interval;
+    this
+}
+
+- (())dealloc {
+    let &NSURLRequestHostObject { url, body, .. } = env.objc.borrow(this);
+    release(env, url);
+    release(env, body);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)_zone {
+    copy_request(env, this, "NSMutableURLRequest")
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLRequestHostObject>(this).url
+}
+
+- (NSURLRequestCachePolicy)cachePolicy {
+    env.objc.borrow::<NSURLRequestHostObject>(this).cache_policy
+}
+
+- (NSTimeInterval)timeoutInterval {
+    env.objc.borrow::<NSURLRequestHostObject>(this).timeout_interval
+}
+
+- (id)HTTPMethod {
+    let method = env.objc.borrow::<NSURLRequestHostObject>(this).http_method.clone();
+    let method = from_rust_string(env, method);
+    autorelease(env, method)
+}
+
+- (id)allHTTPHeaderFields {
+    let headers = env.objc.borrow::<NSURLRequestHostObject>(this).headers.clone();
+    headers_to_dict(env, &headers)
+}
+
+- (id)valueForHTTPHeaderField:(id)field { // NSString*
+    let field = to_rust_string(env, field);
+    let value = env
+        .objc
+        .borrow::<NSURLRequestHostObject>(this)
+        .header(&field)
+        .map(str::to_string);
+    match value {
+        Some(value) => {
+            let value = from_rust_string(env, value);
+            autorelease(env, value)
+        }
+        None => nil,
+    }
+}
+
+- (id)HTTPBody {
+    env.objc.borrow::<NSURLRequestHostObject>(this).body
+}
+
+- (bool)HTTPShouldHandleCookies {
+    env.objc.borrow::<NSURLRequestHostObject>(this).should_handle_cookies
+}
+
+@end
+
+@implementation NSMutableURLRequest: NSURLRequest
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    copy_request(env, this, "NSURLRequest")
+}
+
+- (())setURL:(id)url { // NSURL*
+    let url: id = msg![env; url copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    let old_url = std::mem::replace(&mut host_object.url, url);
+    release(env, old_url);
+}
+
+- (())setCachePolicy:(NSURLRequestCachePolicy)cache_policy {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).cache_policy = cache_policy;
+}
+
+- (())setTimeoutInterval:(NSTimeInterval)timeout_interval {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).timeout_interval = timeout_interval;
+}
+
+- (())setHTTPMethod:(id)method { // NSString*
+    let method = if method == nil {
+        "GET".to_string()
+    } else {
+        to_rust_string(env, method).into_owned()
+    };
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).http_method = method;
+}
+
+- (())setValue:(id)value // NSString*
+forHTTPHeaderField:(id)field { // NSString*
+    let field = to_rust_string(env, field).into_owned();
+    let value = (value != nil).then(|| to_rust_string(env, value).into_owned());
+    let headers = &mut env.objc.borrow_mut::<NSURLRequestHostObject>(this).headers;
+    let existing = headers
+        .iter()
+        .position(|(existing, _)| existing.eq_ignore_ascii_case(&field));
+    match (existing, value) {
+        (Some(index), Some(value)) => headers[index].1 = value,
+        (Some(index), None) => {
+            headers.remove(index);
+        }
+        (None, Some(value)) => headers.push((field, value)),
+        (None, None) => (),
+    }
+}
+
+- (())addValue:(id)value // NSString*
+forHTTPHeaderField:(id)field { // NSString*
+    let field = to_rust_string(env, field).into_owned();
+    let value = to_rust_string(env, value).into_owned();
+    let headers = &mut env.objc.borrow_mut::<NSURLRequestHostObject>(this).headers;
+    // HTTP allows repeated header fields to be combined into one.
+    match headers
+        .iter_mut()
+        .find(|(existing, _)| existing.eq_ignore_ascii_case(&field))
+    {
+        Some((_, existing)) => {
+            existing.push_str(", ");
+            existing.push_str(&value);
+        }
+        None => headers.push((field, value)),
+    }
+}
+
+- (())setHTTPBody:(id)body { // NSData*
+    let body: id = msg![env; body copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    let old_body = std::mem::replace(&mut host_object.body, body);
+    release(env, old_body);
+}
+
+- (())setHTTPShouldHandleCookies:(bool)should_handle_cookies {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).should_handle_cookies = should_handle_cookies;
+}
+
+@end
+
+};
+
+/// Create a new request of the class `class_name` with the same contents as
+/// `request` (not autoreleased).
+fn copy_request(env: &mut Environment, request: id, class_name: &str) -> id {
+    let host_object = env.objc.borrow::<NSURLRequestHostObject>(request).clone();
+    retain(env, host_object.url);
+    retain(env, host_object.body);
+    let class = env.objc.get_known_class(class_name, &mut env.mem);
+    let new: id = msg![env; class alloc];
+    *env.objc.borrow_mut::<NSURLRequestHostObject>(new) = host_object;
+    new
+}
+
+/// Shortcut for host code: make an autoreleased `NSDictionary*` of HTTP header
+/// fields.
+pub(super) fn headers_to_dict(env: &mut Environment, headers: &[(String, String)]) -> id {
+    let mut keys_and_objects = Vec::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = from_rust_string(env, name.clone());
+        let value = from_rust_string(env, value.clone());
+        keys_and_objects.push((name, value));
+    }
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    for (name, value) in keys_and_objects {
+        release(env, name);
+        release(env, value);
+    }
+    autorelease(env, dict)
+}
@@ -0,0 +1,243 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLResponse` and `NSHTTPURLResponse`.
+
+use super::ns_string::from_rust_string;
+use super::ns_url_request::headers_to_dict;
+use super::NSInteger;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+/// `NSURLResponseUnknownLength`
+const UNKNOWN_LENGTH: i64 = -1;
+
+struct NSURLResponseHostObject {
+    /// `NSURL*`
+    url: id,
+    /// `NSString*`, may be nil.
+    mime_type: id,
+    expected_content_length: i64,
+    /// `NSString*`, may be nil.
+    text_encoding_name: id,
+    /// Only used by `NSHTTPURLResponse`.
+    status_code: NSInteger,
+    /// Only used by `NSHTTPURLResponse`.
+    headers: Vec<(String, String)>,
+}
+impl HostObject for NSURLResponseHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLResponse: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSURLResponseHostObject {
+        url: nil,
+        mime_type: nil,
+        expected_content_length: UNKNOWN_LENGTH,
+        text_encoding_name: nil,
+        status_code: 0,
+        headers: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithURL:(id)url // NSURL*
+         MIMEType:(id)mime_type // NSString*
+expectedContentLength:(NSInteger)length
+ textEncodingName:(id)text_encoding_name { // NSString*
+    let url: id = msg![env; url copy];
+    let mime_type: id = msg![env; mime_type copy];
+    let text_encoding_name: id = msg![env; text_encoding_name copy];
+    let host_object = env.objc.borrow_mut::<NSURLResponseHostObject>(this);
+    host_object.url = url;
+    host_object.mime_type = mime_type;
+    host_object.expected_content_length = length.into();
+    host_object.text_encoding_name = text_encoding_name;
+    this
+}
+
+- (())dealloc {
+    let &NSURLResponseHostObject {
+        url,
+        mime_type,
+        text_encoding_name,
+        ..
+    } = env.objc.borrow(this);
+    release(env, url);
+    release(env, mime_type);
+    release(env, text_encoding_name);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLResponseHostObject>(this).url
+}
+- (id)MIMEType {
+    env.objc.borrow::<NSURLResponseHostObject>(this).mime_type
+}
+- (i64)expectedContentLength {
+    env.objc.borrow::<NSURLResponseHostObject>(this).expected_content_length
+}
+- (id)textEncodingName {
+    env.objc.borrow::<NSURLResponseHostObject>(this).text_encoding_name
+}
+
+@end
+
+@implementation NSHTTPURLResponse: NSURLResponse
+
++ (id)localizedStringForStatusCode:(NSInteger)status_code {
+    let reason = from_rust_string(env, reason_phrase(status_code).to_lowercase());
+    autorelease(env, reason)
+}
+
+- (NSInteger)statusCode {
+    env.objc.borrow::<NSURLResponseHostObject>(this).status_code
+}
+
+- (id)allHeaderFields {
+    let headers = env.objc.borrow::<NSURLResponseHostObject>(this).headers.clone();
+    headers_to_dict(env, &headers)
+}
+
+@end
+
+};
+
+/// For use by `NSURLConnection`: create a new `NSHTTPURLResponse*` (not
+/// autoreleased). Header fields with the same name are combined, as HTTP
+/// allows.
+pub fn new_http_response(
+    env: &mut Environment,
+    url: id,
+    status_code: NSInteger,
+    headers: &[(String, String)],
+) -> id {
+    let mut combined: Vec<(String, String)> = Vec::new();
+    for (name, value) in headers {
+        match combined
+            .iter_mut()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+        {
+            Some((_, existing)) => {
+                existing.push_str(", ");
+                existing.push_str(value);
+            }
+            None => combined.push((name.clone(), value.clone())),
+        }
+    }
+
+    let header = |name: &str| {
+        combined
+            .iter()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    // e.g. "text/html; charset=utf-8"
+    let content_type = header("Content-Type").unwrap_or("");
+    let mut parameters = content_type.split(';');
+    let mime_type = parameters.next().unwrap().trim().to_ascii_lowercase();
+    let text_encoding_name = parameters.find_map(|parameter| {
+        let (key, value) = parameter.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_string())
+    });
+    let expected_content_length = header("Content-Length")
+        .and_then(|length| length.trim().parse().ok())
+        .unwrap_or(UNKNOWN_LENGTH);
+
+    let mime_type = if mime_type.is_empty() {
+        nil
+    } else {
+        from_rust_string(env, mime_type)
+    };
+    let text_encoding_name = match text_encoding_name {
+        Some(name) => from_rust_string(env, name),
+        None => nil,
+    };
+    let url: id = msg![env; url copy];
+    let response: id = msg_class![env; NSHTTPURLResponse alloc];
+    *env.objc.borrow_mut(response) = NSURLResponseHostObject {
+        url,
+        mime_type,
+        expected_content_length,
+        text_encoding_name,
+        status_code,
+        headers: combined,
+    };
+    log_dbg!(
+        "New response {:?} for (NSURL*){:?}: status {}, {:?}",
+        response,
+        url,
+        status_code,
+        env.objc.borrow::<NSURLResponseHostObject>(response).headers
+    );
+    response
+}
+
+/// The standard reason phrase for an HTTP status code.
+fn reason_phrase(status_code: NSInteger) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Request Entity Too Large",
+        414 => "Request-URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Requested Range Not Satisfiable",
+        417 => "Expectation Failed",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        100..=199 => "Informational",
+        200..=299 => "Success",
+        300..=399 => "Redirected",
+        400..=499 => "Client Error",
+        500..=599 => "Server Error",
+        _ => "Unknown",
+    }
+}
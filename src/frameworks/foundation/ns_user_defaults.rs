@@ -5,18 +5,29 @@
  */
 //! `NSUserDefaults`.
 //!
+//! Only the application domain (which is persisted) and the registration
+//! domain (which is not) are supported. The application domain is shared with
+//! `CFPreferences`.
+//!
 //! References:
 //! - Apple's [Preferences and Settings Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/UserDefaults/AboutPreferenceDomains/AboutPreferenceDomains.html).
 
 use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_property_list_serialization::{deserialize_plist, serialize_plist};
 use super::ns_string;
-use crate::objc::{id, msg_class, objc_classes, ClassExports};
+use crate::objc::{id, msg, msg_class, nil, objc_classes, release, Class, ClassExports};
+use crate::paths;
 use crate::Environment;
+use std::path::PathBuf;
 
 #[derive(Default)]
 pub struct State {
-    /// `NSDictionary*`
+    /// `NSUserDefaults*`
     standard_defaults: Option<id>,
+    /// `NSMutableDictionary*`, loaded lazily from the host.
+    app_domain: Option<id>,
+    /// `NSMutableDictionary*`
+    registration_domain: Option<id>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut State {
@@ -24,6 +35,72 @@ impl State {
     }
 }
 
+fn preferences_file_path(env: &Environment) -> PathBuf {
+    paths::user_data_base_path()
+        .join(paths::SANDBOX_DIR)
+        .join(env.bundle.bundle_identifier())
+        .join("Preferences.plist")
+}
+
+/// Get the application domain (`NSMutableDictionary*`), loading it from the
+/// host if necessary.
+pub fn app_domain(env: &mut Environment) -> id {
+    if let Some(existing) = State::get(env).app_domain {
+        return existing;
+    }
+
+    let path = preferences_file_path(env);
+    let dict = match plist::Value::from_file(&path) {
+        Ok(value @ plist::Value::Dictionary(_)) => {
+            let dict = deserialize_plist(env, &value);
+            let mutable: id = msg![env; dict mutableCopy];
+            release(env, dict);
+            mutable
+        }
+        Ok(_) => {
+            log!("Warning: {:?} is malformed, ignoring.", path);
+            msg_class![env; NSMutableDictionary new]
+        }
+        // Most likely the file just doesn't exist yet.
+        Err(_) => msg_class![env; NSMutableDictionary new],
+    };
+    State::get(env).app_domain = Some(dict);
+    dict
+}
+
+/// Write the application domain to the host. Returns `false` on failure.
+pub fn synchronize(env: &mut Environment) -> bool {
+    let Some(dict) = State::get(env).app_domain else {
+        // Nothing can have changed.
+        return true;
+    };
+    let Some(value) = serialize_plist(env, dict) else {
+        log!("Warning: user defaults contain non-property-list objects, not saving.");
+        return false;
+    };
+    let path = preferences_file_path(env);
+    if let Err(e) = value.to_file_xml(&path) {
+        log!("Warning: couldn't save user defaults to {:?}: {}", path, e);
+        return false;
+    }
+    true
+}
+
+fn registration_domain(env: &mut Environment) -> id {
+    if let Some(existing) = State::get(env).registration_domain {
+        return existing;
+    }
+
+    // TODO: Are there other default keys we need to set?
+    let langs_value: id = msg_class![env; NSLocale preferredLanguages];
+    let langs_key: id = ns_string::get_static_str(env, "AppleLanguages");
+    let dict = dict_from_keys_and_objects(env, &[(langs_key, langs_value)]);
+    let mutable: id = msg![env; dict mutableCopy];
+    release(env, dict);
+    State::get(env).registration_domain = Some(mutable);
+    mutable
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -34,16 +111,79 @@ pub const CLASSES: ClassExports = objc_classes! {
     if let Some(existing) = State::get(env).standard_defaults {
         existing
     } else {
-        // TODO: Are there other default keys we need to set?
-        let langs_value: id = msg_class![env; NSLocale preferredLanguages];
-        let langs_key: id = ns_string::get_static_str(env, "AppleLanguages");
-        let new = dict_from_keys_and_objects(env, &[(langs_key, langs_value)]);
+        let new: id = msg_class![env; NSUserDefaults new];
         State::get(env).standard_defaults = Some(new);
         new
     }
 }
 
-// TODO: plist methods etc
+- (())registerDefaults:(id)defaults { // NSDictionary*
+    let domain = registration_domain(env);
+    () = msg![env; domain addEntriesFromDictionary:defaults];
+}
+
+- (id)dictionaryRepresentation {
+    let registration = registration_domain(env);
+    let app = app_domain(env);
+    let dict: id = msg_class![env; NSMutableDictionary dictionary];
+    () = msg![env; dict addEntriesFromDictionary:registration];
+    () = msg![env; dict addEntriesFromDictionary:app];
+    dict
+}
+
+- (id)objectForKey:(id)key { // NSString*
+    let app = app_domain(env);
+    let object: id = msg![env; app objectForKey:key];
+    if object != nil {
+        return object;
+    }
+    let registration = registration_domain(env);
+    msg![env; registration objectForKey:key]
+}
+
+- (())setObject:(id)object forKey:(id)key { // NSString*
+    let app = app_domain(env);
+    if object == nil {
+        () = msg![env; app removeObjectForKey:key];
+    } else {
+        () = msg![env; app setObject:object forKey:key];
+    }
+}
+
+- (())removeObjectForKey:(id)key { // NSString*
+    let app = app_domain(env);
+    () = msg![env; app removeObjectForKey:key];
+}
+
+- (id)stringForKey:(id)key { // NSString*
+    let object: id = msg![env; this objectForKey:key];
+    let string_class: Class = env.objc.get_known_class("NSString", &mut env.mem);
+    if object != nil && msg![env; object isKindOfClass:string_class] {
+        object
+    } else {
+        nil
+    }
+}
+
+- (bool)boolForKey:(id)key { // NSString*
+    let object: id = msg![env; this objectForKey:key];
+    if object == nil {
+        false
+    } else {
+        msg![env; object boolValue]
+    }
+}
+
+- (())setBool:(bool)value forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithBool:value];
+    () = msg![env; this setObject:number forKey:key];
+}
+
+- (bool)synchronize {
+    synchronize(env)
+}
+
+// TODO: more typed accessors, other domains
 
 @end
 
@@ -6,11 +6,22 @@
 //! The `NSValue` class cluster, including `NSNumber`.
 
 use super::NSUInteger;
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
 use crate::frameworks::foundation::ns_string::from_rust_string;
 use crate::objc::{
     autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject,
     NSZonePtr,
 };
+use crate::Environment;
+
+/// Host object for `NSValue`s that aren't `NSNumber`s. Only the geometry types
+/// that UIKit adds support for are supported so far.
+enum NSValueHostObject {
+    CGPoint(CGPoint),
+    CGSize(CGSize),
+    CGRect(CGRect),
+}
+impl HostObject for NSValueHostObject {}
 
 enum NSNumberHostObject {
     Bool(bool),
@@ -20,15 +31,61 @@ enum NSNumberHostObject {
     Double(f64),
 }
 impl HostObject for NSNumberHostObject {}
+impl NSNumberHostObject {
+    fn as_f64(&self) -> f64 {
+        match *self {
+            NSNumberHostObject::Bool(value) => value as u8 as f64,
+            NSNumberHostObject::UnsignedLongLong(value) => value as f64,
+            NSNumberHostObject::LongLong(value) => value as f64,
+            NSNumberHostObject::Float(value) => value as f64,
+            NSNumberHostObject::Double(value) => value,
+        }
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
-// NSValue is an abstract class. None of the things it should provide are
+// NSValue is an abstract class. Most of the things it should provide are not
 // implemented here yet (TODO).
 @implementation NSValue: NSObject
 
+// UIKit additions (NSValue(UIGeometryKeyedCodingAdditions))
++ (id)valueWithCGPoint:(CGPoint)point {
+    let host_object = Box::new(NSValueHostObject::CGPoint(point));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithCGSize:(CGSize)size {
+    let host_object = Box::new(NSValueHostObject::CGSize(size));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithCGRect:(CGRect)rect {
+    let host_object = Box::new(NSValueHostObject::CGRect(rect));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+- (CGPoint)CGPointValue {
+    let &NSValueHostObject::CGPoint(point) = env.objc.borrow(this) else {
+        panic!("{:?} is not a CGPoint value", this);
+    };
+    point
+}
+- (CGSize)CGSizeValue {
+    let &NSValueHostObject::CGSize(size) = env.objc.borrow(this) else {
+        panic!("{:?} is not a CGSize value", this);
+    };
+    size
+}
+- (CGRect)CGRectValue {
+    let &NSValueHostObject::CGRect(rect) = env.objc.borrow(this) else {
+        panic!("{:?} is not a CGRect value", this);
+    };
+    rect
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(NSZonePtr)_zone {
     retain(env, this)
@@ -126,7 +183,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     };
     super::hash_helper(&value)
 }
-- (bool)isEqualTo:(id)other {
+- (bool)isEqual:(id)other {
     if this == other {
         return true;
     }
@@ -143,8 +200,29 @@ pub const CLASSES: ClassExports = objc_classes! {
     a == b
 }
 
-// TODO: accessors etc
+- (bool)boolValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64() != 0.0
+}
+- (f32)floatValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64() as f32
+}
+- (f64)doubleValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64()
+}
+
+// TODO: more accessors etc
 
 @end
 
 };
+
+/// Convert an `NSNumber` to a property list value, for serialization.
+pub(super) fn number_to_plist_value(env: &mut Environment, number: id) -> plist::Value {
+    match *env.objc.borrow::<NSNumberHostObject>(number) {
+        NSNumberHostObject::Bool(value) => plist::Value::Boolean(value),
+        NSNumberHostObject::UnsignedLongLong(value) => plist::Value::Integer(value.into()),
+        NSNumberHostObject::LongLong(value) => plist::Value::Integer(value.into()),
+        NSNumberHostObject::Float(value) => plist::Value::Real(value.into()),
+        NSNumberHostObject::Double(value) => plist::Value::Real(value),
+    }
+}
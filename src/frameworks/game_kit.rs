@@ -0,0 +1,123 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The GameKit framework.
+//!
+//! touchHLE doesn't connect to Game Center. The local player is always signed
+//! in (see the `--game-center-alias=` option), and scores are kept in a file in
+//! the app's sandbox directory on the host, so that leaderboards reflect them.
+
+pub mod gk_leaderboard;
+pub mod gk_local_player;
+pub mod gk_score;
+
+use crate::frameworks::foundation::NSTimeInterval;
+use crate::objc::{get_block_invoke, id};
+use crate::paths;
+use crate::Environment;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Fixed player ID of the local player.
+const LOCAL_PLAYER_ID: &str = "G:1000000001";
+
+#[derive(Default)]
+pub struct State {
+    gk_local_player: gk_local_player::State,
+    /// The local player's best score for each leaderboard category. Loaded
+    /// lazily, see [scores].
+    scores: Option<HashMap<String, StoredScore>>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct StoredScore {
+    value: i64,
+    context: u64,
+    /// Time since the reference date (see `NSDate`).
+    date: NSTimeInterval,
+}
+
+fn scores_file_path(env: &Environment) -> PathBuf {
+    paths::user_data_base_path()
+        .join(paths::SANDBOX_DIR)
+        .join(env.bundle.bundle_identifier())
+        .join("GameCenterScores.plist")
+}
+
+/// Get the stored scores, loading them from the host if necessary.
+fn scores(env: &mut Environment) -> &mut HashMap<String, StoredScore> {
+    if env.framework_state.game_kit.scores.is_none() {
+        let path = scores_file_path(env);
+        let scores = match plist::Value::from_file(&path) {
+            Ok(plist::Value::Dictionary(dict)) => dict
+                .into_iter()
+                .filter_map(|(category, score)| {
+                    let score = score.into_dictionary()?;
+                    let score = StoredScore {
+                        value: score.get("value")?.as_signed_integer()?,
+                        context: score.get("context")?.as_unsigned_integer()?,
+                        date: score.get("date")?.as_real()?,
+                    };
+                    Some((category, score))
+                })
+                .collect(),
+            Ok(_) => {
+                log!("Warning: {:?} is malformed, ignoring.", path);
+                HashMap::new()
+            }
+            // Most likely the file just doesn't exist yet.
+            Err(_) => HashMap::new(),
+        };
+        env.framework_state.game_kit.scores = Some(scores);
+    }
+    env.framework_state.game_kit.scores.as_mut().unwrap()
+}
+
+/// Record a score for the local player, if it's their best for that category,
+/// and save the scores to the host.
+fn submit_score(env: &mut Environment, category: String, score: StoredScore) {
+    let scores = scores(env);
+    // TODO: Some leaderboards rank lower scores higher, but touchHLE has no
+    // way of knowing which ones.
+    if scores
+        .get(&category)
+        .is_some_and(|best| best.value >= score.value)
+    {
+        log_dbg!("Score {:?} for {:?} isn't a new best.", score, category);
+        return;
+    }
+    scores.insert(category, score);
+
+    let dict: plist::Dictionary = scores
+        .iter()
+        .map(|(category, score)| {
+            let mut dict = plist::Dictionary::new();
+            dict.insert("value".to_string(), score.value.into());
+            dict.insert("context".to_string(), score.context.into());
+            dict.insert("date".to_string(), score.date.into());
+            (category.clone(), plist::Value::Dictionary(dict))
+        })
+        .collect();
+    let path = scores_file_path(env);
+    if let Err(e) = plist::Value::Dictionary(dict).to_file_xml(&path) {
+        log!(
+            "Warning: couldn't save Game Center scores to {:?}: {}",
+            path,
+            e
+        );
+    }
+}
+
+/// Call a completion handler block that takes one object argument (usually an
+/// `NSError*`), if there is one. GameKit calls these asynchronously, but
+/// since touchHLE doesn't need to wait for any server, they're called
+/// immediately.
+fn call_completion_handler(env: &mut Environment, handler: id, arg: id) {
+    if handler.is_null() {
+        return;
+    }
+    let invoke = get_block_invoke(&env.mem, handler);
+    () = invoke.call_from_host(env, (handler, arg));
+}
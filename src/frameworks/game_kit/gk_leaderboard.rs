@@ -0,0 +1,180 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `GKLeaderboard`.
+//!
+//! Only the local player's own scores are known, so every leaderboard has at
+//! most one entry.
+
+use super::gk_score::new_score;
+use super::scores;
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::frameworks::foundation::{ns_array, NSInteger, NSRange, NSUInteger};
+use crate::objc::{
+    autorelease, get_block_invoke, id, msg, nil, objc_classes, release, retain, ClassExports,
+    HostObject, NSZonePtr,
+};
+
+type GKLeaderboardTimeScope = NSInteger;
+const GKLeaderboardTimeScopeAllTime: GKLeaderboardTimeScope = 2;
+
+type GKLeaderboardPlayerScope = NSInteger;
+const GKLeaderboardPlayerScopeGlobal: GKLeaderboardPlayerScope = 0;
+
+struct GKLeaderboardHostObject {
+    /// `NSString*`
+    category: id,
+    time_scope: GKLeaderboardTimeScope,
+    player_scope: GKLeaderboardPlayerScope,
+    range: NSRange,
+    /// `NSArray<GKScore*>*`, set once scores have been loaded
+    scores: id,
+    /// `GKScore*`, set once scores have been loaded
+    local_player_score: id,
+}
+impl HostObject for GKLeaderboardHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation GKLeaderboard: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(GKLeaderboardHostObject {
+        category: nil,
+        time_scope: GKLeaderboardTimeScopeAllTime,
+        player_scope: GKLeaderboardPlayerScopeGlobal,
+        range: NSRange { location: 1, length: 25 },
+        scores: nil,
+        local_player_score: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithPlayerIDs:(id)_player_ids { // NSArray<NSString*>*
+    // Only the local player's scores exist anyway.
+    msg![env; this init]
+}
+
++ (())loadCategoriesWithCompletionHandler:(id)handler { // void (^)(NSArray*, NSError*)
+    let mut categories: Vec<String> = scores(env).keys().cloned().collect();
+    categories.sort();
+    let categories: Vec<id> = categories
+        .into_iter()
+        .map(|category| from_rust_string(env, category))
+        .collect();
+    // Titles aren't known, so the category names are used instead.
+    for &category in &categories {
+        retain(env, category);
+    }
+    let titles = ns_array::from_vec(env, categories.clone());
+    let categories = ns_array::from_vec(env, categories);
+    autorelease(env, titles);
+    autorelease(env, categories);
+    if handler != nil {
+        let invoke = get_block_invoke(&env.mem, handler);
+        () = invoke.call_from_host(env, (handler, categories, titles));
+    }
+}
+
+- (())dealloc {
+    let &GKLeaderboardHostObject {
+        category,
+        scores,
+        local_player_score,
+        ..
+    } = env.objc.borrow(this);
+    release(env, category);
+    release(env, scores);
+    release(env, local_player_score);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)category {
+    env.objc.borrow::<GKLeaderboardHostObject>(this).category
+}
+- (())setCategory:(id)category { // NSString*
+    let category: id = msg![env; category copy];
+    let host_object = env.objc.borrow_mut::<GKLeaderboardHostObject>(this);
+    let old = std::mem::replace(&mut host_object.category, category);
+    release(env, old);
+}
+
+- (GKLeaderboardTimeScope)timeScope {
+    env.objc.borrow::<GKLeaderboardHostObject>(this).time_scope
+}
+- (())setTimeScope:(GKLeaderboardTimeScope)time_scope {
+    env.objc.borrow_mut::<GKLeaderboardHostObject>(this).time_scope = time_scope;
+}
+- (GKLeaderboardPlayerScope)playerScope {
+    env.objc.borrow::<GKLeaderboardHostObject>(this).player_scope
+}
+- (())setPlayerScope:(GKLeaderboardPlayerScope)player_scope {
+    env.objc.borrow_mut::<GKLeaderboardHostObject>(this).player_scope = player_scope;
+}
+- (NSRange)range {
+    env.objc.borrow::<GKLeaderboardHostObject>(this).range
+}
+- (())setRange:(NSRange)range {
+    env.objc.borrow_mut::<GKLeaderboardHostObject>(this).range = range;
+}
+
+- (id)scores {
+    env.objc.borrow::<GKLeaderboardHostObject>(this).scores
+}
+- (id)localPlayerScore {
+    env.objc.borrow::<GKLeaderboardHostObject>(this).local_player_score
+}
+- (NSUInteger)maxRange {
+    let scores = env.objc.borrow::<GKLeaderboardHostObject>(this).scores;
+    if scores == nil {
+        0
+    } else {
+        msg![env; scores count]
+    }
+}
+
+- (bool)isLoading {
+    // Loading always finishes immediately.
+    false
+}
+
+- (())loadScoresWithCompletionHandler:(id)handler { // void (^)(NSArray*, NSError*)
+    let &GKLeaderboardHostObject { category, range, .. } = env.objc.borrow(this);
+    let category = if category == nil {
+        // TODO: use the app's default leaderboard
+        String::new()
+    } else {
+        to_rust_string(env, category).into_owned()
+    };
+
+    let local_player_score = match scores(env).get(&category).copied() {
+        Some(score) => new_score(env, &category, score, /* rank: */ 1),
+        None => nil,
+    };
+    // The local player is the only one on the leaderboard, so they're first.
+    let scores = if local_player_score != nil && range.location <= 1 && range.length >= 1 {
+        retain(env, local_player_score);
+        ns_array::from_vec(env, vec![local_player_score])
+    } else {
+        nil
+    };
+
+    let host_object = env.objc.borrow_mut::<GKLeaderboardHostObject>(this);
+    let old_scores = std::mem::replace(&mut host_object.scores, scores);
+    let old_local_player_score = std::mem::replace(&mut host_object.local_player_score, local_player_score);
+    release(env, old_scores);
+    release(env, old_local_player_score);
+
+    if handler != nil {
+        let invoke = get_block_invoke(&env.mem, handler);
+        () = invoke.call_from_host(env, (handler, scores, nil));
+    }
+}
+
+@end
+
+};
@@ -0,0 +1,91 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `GKLocalPlayer`.
+
+use super::{call_completion_handler, LOCAL_PLAYER_ID};
+use crate::frameworks::foundation::{ns_array, ns_string};
+use crate::objc::{
+    autorelease, get_block_invoke, id, msg, nil, objc_classes, ClassExports, TrivialHostObject,
+};
+
+#[derive(Default)]
+pub struct State {
+    /// [GKLocalPlayer localPlayer]
+    local_player: Option<id>,
+    authenticated: bool,
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// This is a singleton.
+// TODO: should extend GKPlayer.
+@implementation GKLocalPlayer: NSObject
+
++ (id)localPlayer {
+    if let Some(player) = env.framework_state.game_kit.gk_local_player.local_player {
+        player
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::new(TrivialHostObject),
+            &mut env.mem
+        );
+        env.framework_state.game_kit.gk_local_player.local_player = Some(new);
+        new
+   }
+}
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
+- (bool)isAuthenticated {
+    env.framework_state.game_kit.gk_local_player.authenticated
+}
+- (bool)isUnderage {
+    false
+}
+
+- (id)playerID {
+    ns_string::get_static_str(env, LOCAL_PLAYER_ID)
+}
+- (id)alias {
+    let alias = env.options.game_center_alias.clone();
+    let alias = ns_string::from_rust_string(env, alias);
+    autorelease(env, alias)
+}
+- (id)displayName {
+    msg![env; this alias]
+}
+
+- (())authenticateWithCompletionHandler:(id)handler { // void (^)(NSError*)
+    log!("App is authenticating with Game Center, signing in as {:?}.", env.options.game_center_alias);
+    env.framework_state.game_kit.gk_local_player.authenticated = true;
+    call_completion_handler(env, handler, nil);
+}
+// iOS 6 replacement for the above.
+- (())setAuthenticateHandler:(id)handler { // void (^)(UIViewController*, NSError*)
+    log!("App is authenticating with Game Center, signing in as {:?}.", env.options.game_center_alias);
+    env.framework_state.game_kit.gk_local_player.authenticated = true;
+    if handler != nil {
+        let invoke = get_block_invoke(&env.mem, handler);
+        () = invoke.call_from_host(env, (handler, nil, nil));
+    }
+}
+
+- (())loadFriendsWithCompletionHandler:(id)handler { // void (^)(NSArray*, NSError*)
+    if handler != nil {
+        let friends = ns_array::from_vec(env, Vec::new());
+        autorelease(env, friends);
+        let invoke = get_block_invoke(&env.mem, handler);
+        () = invoke.call_from_host(env, (handler, friends, nil));
+    }
+}
+
+@end
+
+};
@@ -0,0 +1,175 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `GKScore`.
+
+use super::{call_completion_handler, submit_score, StoredScore, LOCAL_PLAYER_ID};
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+struct GKScoreHostObject {
+    /// `NSString*`
+    category: id,
+    value: i64,
+    context: u64,
+    /// `NSDate*`
+    date: id,
+    rank: NSInteger,
+}
+impl HostObject for GKScoreHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation GKScore: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(GKScoreHostObject {
+        category: nil,
+        value: 0,
+        context: 0,
+        date: nil,
+        rank: 0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)init {
+    let date: id = msg_class![env; NSDate date];
+    retain(env, date);
+    env.objc.borrow_mut::<GKScoreHostObject>(this).date = date;
+    this
+}
+- (id)initWithCategory:(id)category { // NSString*
+    let this: id = msg![env; this init];
+    () = msg![env; this setCategory:category];
+    this
+}
+
+- (())dealloc {
+    let &GKScoreHostObject { category, date, .. } = env.objc.borrow(this);
+    release(env, category);
+    release(env, date);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)category {
+    env.objc.borrow::<GKScoreHostObject>(this).category
+}
+- (())setCategory:(id)category { // NSString*
+    let category: id = msg![env; category copy];
+    let host_object = env.objc.borrow_mut::<GKScoreHostObject>(this);
+    let old = std::mem::replace(&mut host_object.category, category);
+    release(env, old);
+}
+
+- (i64)value {
+    env.objc.borrow::<GKScoreHostObject>(this).value
+}
+- (())setValue:(i64)value {
+    env.objc.borrow_mut::<GKScoreHostObject>(this).value = value;
+}
+
+- (u64)context {
+    env.objc.borrow::<GKScoreHostObject>(this).context
+}
+- (())setContext:(u64)context {
+    env.objc.borrow_mut::<GKScoreHostObject>(this).context = context;
+}
+
+- (id)date {
+    env.objc.borrow::<GKScoreHostObject>(this).date
+}
+- (NSInteger)rank {
+    env.objc.borrow::<GKScoreHostObject>(this).rank
+}
+- (id)playerID {
+    get_static_str(env, LOCAL_PLAYER_ID)
+}
+
+- (id)formattedValue {
+    // TODO: use the leaderboard's format
+    let value = env.objc.borrow::<GKScoreHostObject>(this).value;
+    let formatted = from_rust_string(env, value.to_string());
+    autorelease(env, formatted)
+}
+
+- (())reportScoreWithCompletionHandler:(id)handler { // void (^)(NSError*)
+    report_score(env, this);
+    call_completion_handler(env, handler, nil);
+}
+// iOS 6 replacement for the above.
++ (())reportScores:(id)scores // NSArray<GKScore*>*
+withCompletionHandler:(id)handler { // void (^)(NSError*)
+    let count: NSUInteger = msg![env; scores count];
+    for i in 0..count {
+        let score: id = msg![env; scores objectAtIndex:i];
+        report_score(env, score);
+    }
+    call_completion_handler(env, handler, nil);
+}
+
+@end
+
+};
+
+fn report_score(env: &mut Environment, score: id) {
+    let &GKScoreHostObject {
+        category,
+        value,
+        context,
+        date,
+        rank: _,
+    } = env.objc.borrow(score);
+    let date: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    let category = if category == nil {
+        // TODO: use the app's default leaderboard
+        String::new()
+    } else {
+        to_rust_string(env, category).into_owned()
+    };
+    log!(
+        "App reported Game Center score {} for {:?}.",
+        value,
+        category
+    );
+    submit_score(
+        env,
+        category,
+        StoredScore {
+            value,
+            context,
+            date,
+        },
+    );
+}
+
+/// Create a new `GKScore*` for one of the local player's stored scores (+1
+/// reference).
+pub(super) fn new_score(
+    env: &mut Environment,
+    category: &str,
+    score: StoredScore,
+    rank: NSInteger,
+) -> id {
+    let date: id = msg_class![env; NSDate dateWithTimeIntervalSinceReferenceDate:(score.date)];
+    retain(env, date);
+    let category = from_rust_string(env, category.to_string());
+    let new: id = msg_class![env; GKScore alloc];
+    *env.objc.borrow_mut(new) = GKScoreHostObject {
+        category,
+        value: score.value,
+        context: score.context,
+        date,
+        rank,
+    };
+    new
+}
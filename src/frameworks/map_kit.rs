@@ -0,0 +1,14 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Map Kit framework.
+//!
+//! touchHLE has no map data, so map views only draw a placeholder grid. The
+//! region and annotations are still tracked so that apps can use them.
+
+pub mod mk_annotation_view;
+pub mod mk_geometry;
+pub mod mk_map_view;
+pub mod mk_shape;
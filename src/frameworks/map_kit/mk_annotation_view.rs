@@ -0,0 +1,291 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKAnnotationView` and `MKPinAnnotationView`.
+
+use crate::frameworks::core_graphics::cg_context::{
+    CGContextAddCurveToPoint, CGContextClosePath, CGContextFillPath, CGContextFillRect,
+    CGContextMoveToPoint, CGContextRef, CGContextSetRGBFillColor,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::NSUInteger;
+use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::frameworks::uikit::ui_view::UIViewHostObject;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_super, nil, objc_classes, release, retain,
+    ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+type MKPinAnnotationColor = NSUInteger;
+const MKPinAnnotationColorRed: MKPinAnnotationColor = 0;
+const MKPinAnnotationColorGreen: MKPinAnnotationColor = 1;
+const MKPinAnnotationColorPurple: MKPinAnnotationColor = 2;
+
+/// Size of the pin drawn by `MKPinAnnotationView`. The point of the pin is at
+/// the bottom middle.
+const PIN_SIZE: CGSize = CGSize {
+    width: 16.0,
+    height: 32.0,
+};
+/// Radius of the pin's head.
+const PIN_HEAD_RADIUS: CGFloat = 6.0;
+
+#[derive(Default)]
+struct MKAnnotationViewHostObject {
+    superclass: UIViewHostObject,
+    /// `id<MKAnnotation>`, strong reference.
+    annotation: id,
+    /// `NSString*`
+    reuse_identifier: id,
+    /// `UIImage*`
+    image: id,
+    center_offset: CGPoint,
+    can_show_callout: bool,
+}
+impl_HostObject_with_superclass!(MKAnnotationViewHostObject);
+
+#[derive(Default)]
+struct MKPinAnnotationViewHostObject {
+    superclass: MKAnnotationViewHostObject,
+    pin_color: MKPinAnnotationColor,
+    animates_drop: bool,
+}
+impl_HostObject_with_superclass!(MKPinAnnotationViewHostObject);
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MKAnnotationView: UIView
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<MKAnnotationViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithAnnotation:(id)annotation // id<MKAnnotation>
+         reuseIdentifier:(id)reuse_identifier { // NSString*
+    let frame = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: CGSize { width: 0.0, height: 0.0 },
+    };
+    let this: id = msg_super![env; this initWithFrame:frame];
+    () = msg![env; this setOpaque:false];
+    () = msg![env; this setAnnotation:annotation];
+    let reuse_identifier: id = msg![env; reuse_identifier copy];
+    env.objc.borrow_mut::<MKAnnotationViewHostObject>(this).reuse_identifier = reuse_identifier;
+    this
+}
+
+- (())dealloc {
+    let &MKAnnotationViewHostObject {
+        annotation,
+        reuse_identifier,
+        image,
+        ..
+    } = env.objc.borrow(this);
+    release(env, annotation);
+    release(env, reuse_identifier);
+    release(env, image);
+    msg_super![env; this dealloc]
+}
+
+- (id)annotation {
+    env.objc.borrow::<MKAnnotationViewHostObject>(this).annotation
+}
+- (())setAnnotation:(id)annotation { // id<MKAnnotation>
+    retain(env, annotation);
+    let host_object = env.objc.borrow_mut::<MKAnnotationViewHostObject>(this);
+    let old = std::mem::replace(&mut host_object.annotation, annotation);
+    release(env, old);
+}
+
+- (id)reuseIdentifier {
+    env.objc.borrow::<MKAnnotationViewHostObject>(this).reuse_identifier
+}
+
+- (id)image {
+    env.objc.borrow::<MKAnnotationViewHostObject>(this).image
+}
+- (())setImage:(id)image { // UIImage*
+    retain(env, image);
+    let host_object = env.objc.borrow_mut::<MKAnnotationViewHostObject>(this);
+    let old = std::mem::replace(&mut host_object.image, image);
+    release(env, old);
+
+    // The view takes the size of its image, keeping its center.
+    if image != nil {
+        let size: CGSize = msg![env; image size];
+        let bounds = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size,
+        };
+        () = msg![env; this setBounds:bounds];
+    }
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (CGPoint)centerOffset {
+    env.objc.borrow::<MKAnnotationViewHostObject>(this).center_offset
+}
+- (())setCenterOffset:(CGPoint)offset {
+    env.objc.borrow_mut::<MKAnnotationViewHostObject>(this).center_offset = offset;
+}
+
+- (bool)canShowCallout {
+    env.objc.borrow::<MKAnnotationViewHostObject>(this).can_show_callout
+}
+- (())setCanShowCallout:(bool)can_show_callout {
+    // TODO: show callouts when the annotation view is tapped
+    env.objc.borrow_mut::<MKAnnotationViewHostObject>(this).can_show_callout = can_show_callout;
+}
+
+- (())prepareForReuse {
+    // Subclasses may override this.
+}
+
+- (())drawRect:(CGRect)rect {
+    let image = env.objc.borrow::<MKAnnotationViewHostObject>(this).image;
+    if image != nil {
+        () = msg![env; image drawInRect:rect];
+    }
+}
+
+@end
+
+@implementation MKPinAnnotationView: MKAnnotationView
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<MKPinAnnotationViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithAnnotation:(id)annotation // id<MKAnnotation>
+         reuseIdentifier:(id)reuse_identifier { // NSString*
+    let this: id = msg_super![env; this initWithAnnotation:annotation
+                                           reuseIdentifier:reuse_identifier];
+    let bounds = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: PIN_SIZE,
+    };
+    () = msg![env; this setBounds:bounds];
+    // Put the point of the pin on the annotation's coordinate.
+    let offset = CGPoint {
+        x: 0.0,
+        y: -PIN_SIZE.height / 2.0,
+    };
+    () = msg![env; this setCenterOffset:offset];
+    this
+}
+
+- (MKPinAnnotationColor)pinColor {
+    env.objc.borrow::<MKPinAnnotationViewHostObject>(this).pin_color
+}
+- (())setPinColor:(MKPinAnnotationColor)pin_color {
+    env.objc.borrow_mut::<MKPinAnnotationViewHostObject>(this).pin_color = pin_color;
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (bool)animatesDrop {
+    env.objc.borrow::<MKPinAnnotationViewHostObject>(this).animates_drop
+}
+- (())setAnimatesDrop:(bool)animates_drop {
+    // The drop animation isn't implemented, pins just appear.
+    env.objc.borrow_mut::<MKPinAnnotationViewHostObject>(this).animates_drop = animates_drop;
+}
+
+- (())drawRect:(CGRect)_rect {
+    let bounds: CGRect = msg![env; this bounds];
+    let context = UIGraphicsGetCurrentContext(env);
+    let mid_x = bounds.origin.x + bounds.size.width / 2.0;
+
+    // Needle
+    CGContextSetRGBFillColor(env, context, 0.4, 0.4, 0.4, 1.0);
+    let needle = CGRect {
+        origin: CGPoint {
+            x: mid_x - 1.0,
+            y: bounds.origin.y + PIN_HEAD_RADIUS,
+        },
+        size: CGSize {
+            width: 2.0,
+            height: bounds.size.height - PIN_HEAD_RADIUS,
+        },
+    };
+    CGContextFillRect(env, context, needle);
+
+    // Head
+    let (r, g, b) = match env.objc.borrow::<MKPinAnnotationViewHostObject>(this).pin_color {
+        MKPinAnnotationColorRed => (0.9, 0.1, 0.1),
+        MKPinAnnotationColorGreen => (0.1, 0.7, 0.1),
+        MKPinAnnotationColorPurple => (0.6, 0.2, 0.8),
+        other => {
+            log!("Warning: unknown pin color {}, drawing a red pin", other);
+            (0.9, 0.1, 0.1)
+        }
+    };
+    CGContextSetRGBFillColor(env, context, r, g, b, 1.0);
+    let head_center = CGPoint {
+        x: mid_x,
+        y: bounds.origin.y + PIN_HEAD_RADIUS,
+    };
+    fill_circle(env, context, head_center, PIN_HEAD_RADIUS);
+}
+
+@end
+
+};
+
+/// Fill a circle, approximated with four Bézier curves.
+fn fill_circle(env: &mut Environment, context: CGContextRef, center: CGPoint, radius: CGFloat) {
+    // Distance of the control points from the ends of each quarter arc.
+    const KAPPA: CGFloat = 0.552_284_8;
+    let k = radius * KAPPA;
+    let CGPoint { x, y } = center;
+
+    CGContextMoveToPoint(env, context, x + radius, y);
+    CGContextAddCurveToPoint(
+        env,
+        context,
+        x + radius,
+        y + k,
+        x + k,
+        y + radius,
+        x,
+        y + radius,
+    );
+    CGContextAddCurveToPoint(
+        env,
+        context,
+        x - k,
+        y + radius,
+        x - radius,
+        y + k,
+        x - radius,
+        y,
+    );
+    CGContextAddCurveToPoint(
+        env,
+        context,
+        x - radius,
+        y - k,
+        x - k,
+        y - radius,
+        x,
+        y - radius,
+    );
+    CGContextAddCurveToPoint(
+        env,
+        context,
+        x + k,
+        y - radius,
+        x + radius,
+        y - k,
+        x + radius,
+        y,
+    );
+    CGContextClosePath(env, context);
+    CGContextFillPath(env, context);
+}
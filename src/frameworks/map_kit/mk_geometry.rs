@@ -0,0 +1,96 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKGeometry.h` (`MKCoordinateRegion` etc)
+
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_location::cl_location::{
+    CLLocationCoordinate2D, CLLocationDegrees, CLLocationDistance,
+};
+use crate::mem::SafeRead;
+use crate::Environment;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(C, packed)]
+pub struct MKCoordinateSpan {
+    pub latitudeDelta: CLLocationDegrees,
+    pub longitudeDelta: CLLocationDegrees,
+}
+unsafe impl SafeRead for MKCoordinateSpan {}
+impl_GuestRet_for_large_struct!(MKCoordinateSpan);
+impl GuestArg for MKCoordinateSpan {
+    const REG_COUNT: usize = 4;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        MKCoordinateSpan {
+            latitudeDelta: GuestArg::from_regs(&regs[0..2]),
+            longitudeDelta: GuestArg::from_regs(&regs[2..4]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.latitudeDelta.to_regs(&mut regs[0..2]);
+        self.longitudeDelta.to_regs(&mut regs[2..4]);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(C, packed)]
+pub struct MKCoordinateRegion {
+    pub center: CLLocationCoordinate2D,
+    pub span: MKCoordinateSpan,
+}
+unsafe impl SafeRead for MKCoordinateRegion {}
+impl_GuestRet_for_large_struct!(MKCoordinateRegion);
+impl GuestArg for MKCoordinateRegion {
+    const REG_COUNT: usize = 8;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        MKCoordinateRegion {
+            center: GuestArg::from_regs(&regs[0..4]),
+            span: GuestArg::from_regs(&regs[4..8]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.center.to_regs(&mut regs[0..4]);
+        self.span.to_regs(&mut regs[4..8]);
+    }
+}
+impl MKCoordinateRegion {
+    /// The region a map view shows before one is set: the whole world.
+    pub fn world() -> Self {
+        MKCoordinateRegion {
+            center: CLLocationCoordinate2D::default(),
+            span: MKCoordinateSpan {
+                latitudeDelta: 180.0,
+                longitudeDelta: 360.0,
+            },
+        }
+    }
+}
+
+/// Approximate length of a degree of latitude in meters.
+const METERS_PER_DEGREE: CLLocationDistance = 111_195.0;
+
+fn MKCoordinateRegionMakeWithDistance(
+    _env: &mut Environment,
+    center: CLLocationCoordinate2D,
+    latitudinal_meters: CLLocationDistance,
+    longitudinal_meters: CLLocationDistance,
+) -> MKCoordinateRegion {
+    // Degrees of longitude get shorter towards the poles.
+    let latitude = center.latitude;
+    let longitude_scale = latitude.to_radians().cos().max(f64::EPSILON);
+    MKCoordinateRegion {
+        center,
+        span: MKCoordinateSpan {
+            latitudeDelta: latitudinal_meters / METERS_PER_DEGREE,
+            longitudeDelta: longitudinal_meters / (METERS_PER_DEGREE * longitude_scale),
+        },
+    }
+}
+
+pub const FUNCTIONS: FunctionExports =
+    &[export_c_func!(MKCoordinateRegionMakeWithDistance(_, _, _))];
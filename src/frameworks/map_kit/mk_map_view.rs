@@ -0,0 +1,407 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKMapView`.
+//!
+//! There are no map tiles, so the map is a grid of lines of latitude and
+//! longitude. Annotations are placed using a simple equirectangular
+//! projection of the current region onto the view's bounds.
+
+use super::mk_geometry::{MKCoordinateRegion, MKCoordinateSpan};
+use crate::frameworks::core_graphics::cg_context::{CGContextFillRect, CGContextSetRGBFillColor};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::core_location::cl_location::{CLLocationCoordinate2D, CLLocationDegrees};
+use crate::frameworks::foundation::{ns_array, NSUInteger};
+use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::frameworks::uikit::ui_view::UIViewHostObject;
+use crate::objc::{
+    autorelease, id, impl_HostObject_with_superclass, msg, msg_super, nil, objc_classes, release,
+    retain, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+type MKMapType = NSUInteger;
+const MKMapTypeStandard: MKMapType = 0;
+
+/// Spacing of the placeholder grid's lines, in degrees.
+const GRID_SPACINGS: [CLLocationDegrees; 6] = [30.0, 10.0, 1.0, 0.1, 0.01, 0.001];
+
+struct MKMapViewHostObject {
+    superclass: UIViewHostObject,
+    /// Weak reference.
+    delegate: id,
+    region: MKCoordinateRegion,
+    /// Annotations (`id<MKAnnotation>`) and their views (`MKAnnotationView*`),
+    /// in the order they were added. Both are strong references.
+    annotations: Vec<(id, id)>,
+    map_type: MKMapType,
+    shows_user_location: bool,
+    zoom_enabled: bool,
+    scroll_enabled: bool,
+}
+impl_HostObject_with_superclass!(MKMapViewHostObject);
+impl Default for MKMapViewHostObject {
+    fn default() -> Self {
+        MKMapViewHostObject {
+            superclass: Default::default(),
+            delegate: nil,
+            region: MKCoordinateRegion::world(),
+            annotations: Vec::new(),
+            map_type: MKMapTypeStandard,
+            shows_user_location: false,
+            zoom_enabled: true,
+            scroll_enabled: true,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MKMapView: UIView
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<MKMapViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// TODO: initWithCoder:
+
+- (())dealloc {
+    let annotations = std::mem::take(
+        &mut env.objc.borrow_mut::<MKMapViewHostObject>(this).annotations
+    );
+    for (annotation, view) in annotations {
+        release(env, annotation);
+        release(env, view);
+    }
+    msg_super![env; this dealloc]
+}
+
+- (id)delegate {
+    env.objc.borrow::<MKMapViewHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // id<MKMapViewDelegate>
+    env.objc.borrow_mut::<MKMapViewHostObject>(this).delegate = delegate;
+}
+
+- (MKMapType)mapType {
+    env.objc.borrow::<MKMapViewHostObject>(this).map_type
+}
+- (())setMapType:(MKMapType)map_type {
+    // The placeholder looks the same for all map types.
+    env.objc.borrow_mut::<MKMapViewHostObject>(this).map_type = map_type;
+}
+
+- (bool)isZoomEnabled {
+    env.objc.borrow::<MKMapViewHostObject>(this).zoom_enabled
+}
+- (())setZoomEnabled:(bool)enabled {
+    env.objc.borrow_mut::<MKMapViewHostObject>(this).zoom_enabled = enabled;
+}
+
+- (bool)isScrollEnabled {
+    env.objc.borrow::<MKMapViewHostObject>(this).scroll_enabled
+}
+- (())setScrollEnabled:(bool)enabled {
+    env.objc.borrow_mut::<MKMapViewHostObject>(this).scroll_enabled = enabled;
+}
+
+- (bool)showsUserLocation {
+    env.objc.borrow::<MKMapViewHostObject>(this).shows_user_location
+}
+- (())setShowsUserLocation:(bool)shows {
+    // TODO: add an MKUserLocation annotation tracking Core Location
+    env.objc.borrow_mut::<MKMapViewHostObject>(this).shows_user_location = shows;
+}
+
+- (MKCoordinateRegion)region {
+    env.objc.borrow::<MKMapViewHostObject>(this).region
+}
+- (())setRegion:(MKCoordinateRegion)region {
+    msg![env; this setRegion:region animated:false]
+}
+- (())setRegion:(MKCoordinateRegion)region
+       animated:(bool)animated {
+    let delegate = env.objc.borrow::<MKMapViewHostObject>(this).delegate;
+    if delegate_responds(env, delegate, "mapView:regionWillChangeAnimated:") {
+        () = msg![env; delegate mapView:this regionWillChangeAnimated:animated];
+    }
+    // Changes are never animated.
+    env.objc.borrow_mut::<MKMapViewHostObject>(this).region = region;
+    update_annotation_views(env, this);
+    () = msg![env; this setNeedsDisplay];
+    if delegate_responds(env, delegate, "mapView:regionDidChangeAnimated:") {
+        () = msg![env; delegate mapView:this regionDidChangeAnimated:animated];
+    }
+}
+- (MKCoordinateRegion)regionThatFits:(MKCoordinateRegion)region {
+    // Without map tiles, there is no zoom level to snap to.
+    region
+}
+
+- (CLLocationCoordinate2D)centerCoordinate {
+    env.objc.borrow::<MKMapViewHostObject>(this).region.center
+}
+- (())setCenterCoordinate:(CLLocationCoordinate2D)center {
+    msg![env; this setCenterCoordinate:center animated:false]
+}
+- (())setCenterCoordinate:(CLLocationCoordinate2D)center
+                 animated:(bool)animated {
+    let mut region = env.objc.borrow::<MKMapViewHostObject>(this).region;
+    region.center = center;
+    msg![env; this setRegion:region animated:animated]
+}
+
+- (CGPoint)convertCoordinate:(CLLocationCoordinate2D)coordinate
+               toPointToView:(id)view { // UIView*
+    let point = coordinate_to_point(env, this, coordinate);
+    msg![env; this convertPoint:point toView:view]
+}
+- (CLLocationCoordinate2D)convertPoint:(CGPoint)point
+                  toCoordinateFromView:(id)view { // UIView*
+    let point: CGPoint = msg![env; this convertPoint:point fromView:view];
+    point_to_coordinate(env, this, point)
+}
+
+- (id)annotations {
+    let annotations: Vec<id> = env
+        .objc
+        .borrow::<MKMapViewHostObject>(this)
+        .annotations
+        .iter()
+        .map(|&(annotation, _view)| annotation)
+        .collect();
+    for &annotation in &annotations {
+        retain(env, annotation);
+    }
+    let array = ns_array::from_vec(env, annotations);
+    autorelease(env, array)
+}
+
+- (())addAnnotation:(id)annotation { // id<MKAnnotation>
+    add_annotations(env, this, vec![annotation]);
+}
+- (())addAnnotations:(id)annotations { // NSArray* of id<MKAnnotation>
+    let count: NSUInteger = msg![env; annotations count];
+    let mut new_annotations = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let annotation: id = msg![env; annotations objectAtIndex:i];
+        new_annotations.push(annotation);
+    }
+    add_annotations(env, this, new_annotations);
+}
+
+- (())removeAnnotation:(id)annotation { // id<MKAnnotation>
+    let host_object = env.objc.borrow_mut::<MKMapViewHostObject>(this);
+    let Some(index) = host_object
+        .annotations
+        .iter()
+        .position(|&(existing, _view)| existing == annotation)
+    else {
+        return;
+    };
+    let (annotation, view) = host_object.annotations.remove(index);
+    () = msg![env; view removeFromSuperview];
+    release(env, view);
+    release(env, annotation);
+}
+- (())removeAnnotations:(id)annotations { // NSArray* of id<MKAnnotation>
+    let count: NSUInteger = msg![env; annotations count];
+    for i in 0..count {
+        let annotation: id = msg![env; annotations objectAtIndex:i];
+        () = msg![env; this removeAnnotation:annotation];
+    }
+}
+
+- (id)viewForAnnotation:(id)annotation { // id<MKAnnotation>
+    env.objc
+        .borrow::<MKMapViewHostObject>(this)
+        .annotations
+        .iter()
+        .find(|&&(existing, _view)| existing == annotation)
+        .map_or(nil, |&(_annotation, view)| view)
+}
+
+- (id)dequeueReusableAnnotationViewWithIdentifier:(id)_identifier { // NSString*
+    // Views of removed annotations aren't kept for reuse, which apps must
+    // cope with anyway.
+    nil
+}
+
+- (())setFrame:(CGRect)frame {
+    () = msg_super![env; this setFrame:frame];
+    update_annotation_views(env, this);
+}
+- (())setBounds:(CGRect)bounds {
+    () = msg_super![env; this setBounds:bounds];
+    update_annotation_views(env, this);
+}
+- (())layoutSubviews {
+    update_annotation_views(env, this);
+}
+
+- (())drawRect:(CGRect)_rect {
+    let bounds: CGRect = msg![env; this bounds];
+    let region = env.objc.borrow::<MKMapViewHostObject>(this).region;
+    let context = UIGraphicsGetCurrentContext(env);
+
+    // Background
+    CGContextSetRGBFillColor(env, context, 0.93, 0.92, 0.88, 1.0);
+    CGContextFillRect(env, context, bounds);
+
+    // Pick the widest spacing that still puts a few lines on the screen.
+    let span = region.span.latitudeDelta.max(region.span.longitudeDelta);
+    let spacing = GRID_SPACINGS
+        .into_iter()
+        .find(|&spacing| span / spacing >= 3.0)
+        .unwrap_or(GRID_SPACINGS[GRID_SPACINGS.len() - 1]);
+
+    CGContextSetRGBFillColor(env, context, 0.75, 0.75, 0.8, 1.0);
+    let (west, north) = (
+        region.center.longitude - region.span.longitudeDelta / 2.0,
+        region.center.latitude + region.span.latitudeDelta / 2.0,
+    );
+    let mut longitude = (west / spacing).ceil() * spacing;
+    while longitude <= west + region.span.longitudeDelta {
+        let x = (longitude - west) / region.span.longitudeDelta * bounds.size.width as f64;
+        let line = CGRect {
+            origin: CGPoint { x: bounds.origin.x + x as CGFloat, y: bounds.origin.y },
+            size: CGSize { width: 1.0, height: bounds.size.height },
+        };
+        CGContextFillRect(env, context, line);
+        longitude += spacing;
+    }
+    let mut latitude = (north / spacing).floor() * spacing;
+    while latitude >= north - region.span.latitudeDelta {
+        let y = (north - latitude) / region.span.latitudeDelta * bounds.size.height as f64;
+        let line = CGRect {
+            origin: CGPoint { x: bounds.origin.x, y: bounds.origin.y + y as CGFloat },
+            size: CGSize { width: bounds.size.width, height: 1.0 },
+        };
+        CGContextFillRect(env, context, line);
+        latitude -= spacing;
+    }
+}
+
+@end
+
+};
+
+fn delegate_responds(env: &mut Environment, delegate: id, selector: &str) -> bool {
+    delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, selector)
+}
+
+/// Project a coordinate onto a point in the map view's bounds.
+fn coordinate_to_point(
+    env: &mut Environment,
+    map_view: id,
+    coordinate: CLLocationCoordinate2D,
+) -> CGPoint {
+    let bounds: CGRect = msg![env; map_view bounds];
+    let MKCoordinateRegion {
+        center,
+        span: MKCoordinateSpan {
+            latitudeDelta,
+            longitudeDelta,
+        },
+    } = env.objc.borrow::<MKMapViewHostObject>(map_view).region;
+    let x = (coordinate.longitude - center.longitude) / longitudeDelta + 0.5;
+    let y = (center.latitude - coordinate.latitude) / latitudeDelta + 0.5;
+    CGPoint {
+        x: bounds.origin.x + (x * bounds.size.width as f64) as CGFloat,
+        y: bounds.origin.y + (y * bounds.size.height as f64) as CGFloat,
+    }
+}
+
+/// Inverse of [coordinate_to_point].
+fn point_to_coordinate(
+    env: &mut Environment,
+    map_view: id,
+    point: CGPoint,
+) -> CLLocationCoordinate2D {
+    let bounds: CGRect = msg![env; map_view bounds];
+    let MKCoordinateRegion {
+        center,
+        span: MKCoordinateSpan {
+            latitudeDelta,
+            longitudeDelta,
+        },
+    } = env.objc.borrow::<MKMapViewHostObject>(map_view).region;
+    let x = ((point.x - bounds.origin.x) / bounds.size.width) as f64 - 0.5;
+    let y = ((point.y - bounds.origin.y) / bounds.size.height) as f64 - 0.5;
+    CLLocationCoordinate2D {
+        latitude: center.latitude - y * latitudeDelta,
+        longitude: center.longitude + x * longitudeDelta,
+    }
+}
+
+fn add_annotations(env: &mut Environment, map_view: id, annotations: Vec<id>) {
+    let delegate = env.objc.borrow::<MKMapViewHostObject>(map_view).delegate;
+    let asks_delegate = delegate_responds(env, delegate, "mapView:viewForAnnotation:");
+
+    let mut new_views = Vec::with_capacity(annotations.len());
+    for annotation in annotations {
+        let existing: id = msg![env; map_view viewForAnnotation:annotation];
+        if existing != nil {
+            continue;
+        }
+        retain(env, annotation);
+
+        let mut view: id = nil;
+        if asks_delegate {
+            view = msg![env; delegate mapView:map_view viewForAnnotation:annotation];
+            retain(env, view);
+        }
+        if view == nil {
+            let class = env
+                .objc
+                .get_known_class("MKPinAnnotationView", &mut env.mem);
+            view = msg![env; class alloc];
+            view = msg![env; view initWithAnnotation:annotation reuseIdentifier:nil];
+        }
+
+        env.objc
+            .borrow_mut::<MKMapViewHostObject>(map_view)
+            .annotations
+            .push((annotation, view));
+        () = msg![env; map_view addSubview:view];
+        retain(env, view);
+        new_views.push(view);
+    }
+    update_annotation_views(env, map_view);
+
+    if new_views.is_empty() {
+        return;
+    }
+    let new_views = ns_array::from_vec(env, new_views);
+    if delegate_responds(env, delegate, "mapView:didAddAnnotationViews:") {
+        () = msg![env; delegate mapView:map_view didAddAnnotationViews:new_views];
+    }
+    release(env, new_views);
+}
+
+/// Move the annotation views to where their annotations are in the region.
+fn update_annotation_views(env: &mut Environment, map_view: id) {
+    let annotations = env
+        .objc
+        .borrow::<MKMapViewHostObject>(map_view)
+        .annotations
+        .clone();
+    for (annotation, view) in annotations {
+        let coordinate: CLLocationCoordinate2D = msg![env; annotation coordinate];
+        let offset: CGPoint = msg![env; view centerOffset];
+        let point = coordinate_to_point(env, map_view, coordinate);
+        let center = CGPoint {
+            x: point.x + offset.x,
+            y: point.y + offset.y,
+        };
+        () = msg![env; view setCenter:center];
+    }
+}
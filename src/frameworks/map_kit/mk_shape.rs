@@ -0,0 +1,88 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKShape` and `MKPointAnnotation`.
+
+use crate::frameworks::core_location::cl_location::CLLocationCoordinate2D;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_super, objc_classes, release, ClassExports,
+    HostObject, NSZonePtr,
+};
+
+#[derive(Default)]
+struct MKShapeHostObject {
+    /// `NSString*`
+    title: id,
+    /// `NSString*`
+    subtitle: id,
+}
+impl HostObject for MKShapeHostObject {}
+
+#[derive(Default)]
+struct MKPointAnnotationHostObject {
+    superclass: MKShapeHostObject,
+    coordinate: CLLocationCoordinate2D,
+}
+impl_HostObject_with_superclass!(MKPointAnnotationHostObject);
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// Abstract class that implements the MKAnnotation protocol's title and
+// subtitle.
+@implementation MKShape: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<MKShapeHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &MKShapeHostObject { title, subtitle } = env.objc.borrow(this);
+    release(env, title);
+    release(env, subtitle);
+    msg_super![env; this dealloc]
+}
+
+- (id)title {
+    env.objc.borrow::<MKShapeHostObject>(this).title
+}
+- (())setTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    let host_object = env.objc.borrow_mut::<MKShapeHostObject>(this);
+    let old = std::mem::replace(&mut host_object.title, title);
+    release(env, old);
+}
+
+- (id)subtitle {
+    env.objc.borrow::<MKShapeHostObject>(this).subtitle
+}
+- (())setSubtitle:(id)subtitle { // NSString*
+    let subtitle: id = msg![env; subtitle copy];
+    let host_object = env.objc.borrow_mut::<MKShapeHostObject>(this);
+    let old = std::mem::replace(&mut host_object.subtitle, subtitle);
+    release(env, old);
+}
+
+@end
+
+@implementation MKPointAnnotation: MKShape
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<MKPointAnnotationHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (CLLocationCoordinate2D)coordinate {
+    env.objc.borrow::<MKPointAnnotationHostObject>(this).coordinate
+}
+- (())setCoordinate:(CLLocationCoordinate2D)coordinate {
+    env.objc.borrow_mut::<MKPointAnnotationHostObject>(this).coordinate = coordinate;
+}
+
+@end
+
+};
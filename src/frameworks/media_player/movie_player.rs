@@ -4,11 +4,22 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `MPMoviePlayerController` etc.
+//!
+//! Decoding isn't implemented for any format, so neither video frames nor the
+//! audio track are ever presented, and the player's view stays black. The
+//! player reads the movie's duration from its container (MPEG-4 and
+//! QuickTime) so that `duration` and seeking work, but "playback" finishes as
+//! soon as the app returns to the run loop. That's enough for apps that just
+//! wait for a cutscene to end, which is the common case.
 
 use crate::dyld::{ConstantExports, HostConstant};
-use crate::frameworks::foundation::{ns_string, ns_url, NSInteger};
+use crate::frameworks::core_graphics::CGRect;
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::{ns_string, ns_url, NSInteger, NSTimeInterval};
 use crate::frameworks::uikit::ui_device::UIDeviceOrientation;
-use crate::objc::{id, msg, msg_class, objc_classes, release, retain, ClassExports};
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
 use crate::Environment;
 use std::collections::VecDeque;
 
@@ -19,7 +30,7 @@ pub struct State {
     /// create or start a player and await some kind of notification, but can't
     /// handle it if that notification happens immediately. This queue lets us
     /// delay such notifications until the app next returns to the run loop,
-    /// which seems to be late enough.
+    /// which seems to be late enough. The players are retained while queued.
     pending_notifications: VecDeque<(&'static str, id)>,
 }
 impl State {
@@ -30,6 +41,39 @@ impl State {
 
 type MPMovieScalingMode = NSInteger;
 
+type MPMoviePlaybackState = NSInteger;
+const MPMoviePlaybackStateStopped: MPMoviePlaybackState = 0;
+const MPMoviePlaybackStatePlaying: MPMoviePlaybackState = 1;
+const MPMoviePlaybackStatePaused: MPMoviePlaybackState = 2;
+
+type MPMovieFinishReason = NSInteger;
+const MPMovieFinishReasonPlaybackEnded: MPMovieFinishReason = 0;
+
+struct MPMoviePlayerControllerHostObject {
+    /// `NSURL*`
+    content_url: id,
+    /// `UIView*`, created on first use.
+    view: id,
+    /// In seconds, if it could be read from the file.
+    duration: Option<NSTimeInterval>,
+    playback_state: MPMoviePlaybackState,
+    current_playback_time: NSTimeInterval,
+    fullscreen: bool,
+}
+impl HostObject for MPMoviePlayerControllerHostObject {}
+impl Default for MPMoviePlayerControllerHostObject {
+    fn default() -> Self {
+        MPMoviePlayerControllerHostObject {
+            content_url: nil,
+            view: nil,
+            duration: None,
+            playback_state: MPMoviePlaybackStateStopped,
+            current_playback_time: 0.0,
+            fullscreen: false,
+        }
+    }
+}
+
 // Values might not be correct, but as these are linked symbol constants, it
 // shouldn't matter.
 pub const MPMoviePlayerPlaybackDidFinishNotification: &str =
@@ -39,7 +83,10 @@ pub const MPMoviePlayerContentPreloadDidFinishNotification: &str =
     "MPMoviePlayerContentPreloadDidFinishNotification";
 // TODO: More notifications?
 
-/// `NSNotificationName` values.
+pub const MPMoviePlayerPlaybackDidFinishReasonUserInfoKey: &str =
+    "MPMoviePlayerPlaybackDidFinishReasonUserInfoKey";
+
+/// `NSNotificationName` values and user info keys.
 pub const CONSTANTS: ConstantExports = &[
     (
         "_MPMoviePlayerPlaybackDidFinishNotification",
@@ -49,6 +96,10 @@ pub const CONSTANTS: ConstantExports = &[
         "_MPMoviePlayerContentPreloadDidFinishNotification",
         HostConstant::NSString(MPMoviePlayerContentPreloadDidFinishNotification),
     ),
+    (
+        "_MPMoviePlayerPlaybackDidFinishReasonUserInfoKey",
+        HostConstant::NSString(MPMoviePlayerPlaybackDidFinishReasonUserInfoKey),
+    ),
 ];
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -59,22 +110,66 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // TODO: actual playback
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<MPMoviePlayerControllerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
 - (id)initWithContentURL:(id)url { // NSURL*
+    let path = ns_url::to_rust_path(env, url);
+    let duration = env
+        .fs
+        .read(path.as_ref())
+        .ok()
+        .and_then(|bytes| read_movie_duration(&bytes));
     log!(
-        "TODO: [(MPMoviePlayerController*){:?} initWithContentURL:{:?} ({:?})]",
+        "TODO: [(MPMoviePlayerController*){:?} initWithContentURL:{:?} ({:?})] (duration: {:?})",
         this,
         url,
-        ns_url::to_rust_path(env, url),
+        path,
+        duration,
     );
 
+    retain(env, url);
+    let host_object = env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this);
+    host_object.content_url = url;
+    host_object.duration = duration;
+
     // Act as if loading immediately completed (Spore Origins waits for this).
-    State::get(env).pending_notifications.push_back(
-        (MPMoviePlayerContentPreloadDidFinishNotification, this)
-    );
+    queue_notification(env, MPMoviePlayerContentPreloadDidFinishNotification, this);
 
     this
 }
 
+- (())dealloc {
+    let &MPMoviePlayerControllerHostObject { content_url, view, .. } = env.objc.borrow(this);
+    release(env, content_url);
+    release(env, view);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)contentURL {
+    env.objc.borrow::<MPMoviePlayerControllerHostObject>(this).content_url
+}
+
+- (id)view {
+    let view = env.objc.borrow::<MPMoviePlayerControllerHostObject>(this).view;
+    if view != nil {
+        return view;
+    }
+    // This is where the video would be drawn.
+    let frame: CGRect = {
+        let screen: id = msg_class![env; UIScreen mainScreen];
+        msg![env; screen bounds]
+    };
+    let view: id = msg_class![env; UIView alloc];
+    let view: id = msg![env; view initWithFrame:frame];
+    let black: id = msg_class![env; UIColor blackColor];
+    () = msg![env; view setBackgroundColor:black];
+    env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this).view = view;
+    view
+}
+
 - (())setBackgroundColor:(id)_color { // UIColor*
     // TODO
 }
@@ -92,9 +187,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     // the notification again.
     if env.bundle.bundle_identifier().starts_with("com.ea.spore") {
         log!("Applying game-specific hack for Spore Origins: sending MPMoviePlayerPlaybackDidFinishNotification again.");
-        State::get(env).pending_notifications.push_back(
-            (MPMoviePlayerPlaybackDidFinishNotification, this)
-        );
+        queue_notification(env, MPMoviePlayerPlaybackDidFinishNotification, this);
     }
     // As this is undocumented and we don't have real video playback yet, let's
     // ignore it otherwise.
@@ -106,27 +199,78 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 }
 
+- (bool)isFullscreen {
+    env.objc.borrow::<MPMoviePlayerControllerHostObject>(this).fullscreen
+}
+- (())setFullscreen:(bool)fullscreen {
+    () = msg![env; this setFullscreen:fullscreen animated:false];
+}
+- (())setFullscreen:(bool)fullscreen animated:(bool)_animated {
+    // TODO: show the view over the app's UI once there's something to show
+    env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this).fullscreen = fullscreen;
+}
+
+- (NSTimeInterval)duration {
+    env.objc.borrow::<MPMoviePlayerControllerHostObject>(this).duration.unwrap_or(0.0)
+}
+
 // MPMediaPlayback implementation
+- (MPMoviePlaybackState)playbackState {
+    env.objc.borrow::<MPMoviePlayerControllerHostObject>(this).playback_state
+}
+
+- (NSTimeInterval)currentPlaybackTime {
+    env.objc.borrow::<MPMoviePlayerControllerHostObject>(this).current_playback_time
+}
+- (())setCurrentPlaybackTime:(NSTimeInterval)time {
+    let host_object = env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this);
+    let duration = host_object.duration.unwrap_or(0.0);
+    host_object.current_playback_time = time.clamp(0.0, duration);
+}
+
 - (())play {
-    log!("TODO: [(MPMoviePlayerController*){:?} play]", this);
-    if let Some(old) = env.framework_state.media_player.movie_player.active_player {
+    log!(
+        "Warning: [(MPMoviePlayerController*){:?} play]: movie decoding isn't supported, playback will finish immediately.",
+        this
+    );
+    if let Some(old) = State::get(env).active_player {
+        if old == this {
+            // Resuming after a pause.
+            env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this).playback_state =
+                MPMoviePlaybackStatePlaying;
+            return;
+        }
         let _: () = msg![env; old stop];
     }
-    assert!(env.framework_state.media_player.movie_player.active_player.is_none());
+    assert!(State::get(env).active_player.is_none());
     // Movie player is retained by the runtime until it is stopped
     retain(env, this);
-    env.framework_state.media_player.movie_player.active_player = Some(this);
+    State::get(env).active_player = Some(this);
+    env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this).playback_state =
+        MPMoviePlaybackStatePlaying;
 
-    // Act as if playback immediately completed (various apps wait for this).
-    State::get(env).pending_notifications.push_back(
-        (MPMoviePlayerPlaybackDidFinishNotification, this)
-    );
+    // The video can't be decoded, so act as if playback immediately completed
+    // (various apps wait for this).
+    queue_notification(env, MPMoviePlayerPlaybackDidFinishNotification, this);
+}
+
+- (())pause {
+    let host_object = env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this);
+    if host_object.playback_state == MPMoviePlaybackStatePlaying {
+        host_object.playback_state = MPMoviePlaybackStatePaused;
+    }
 }
 
 - (())stop {
-    log!("TODO: [(MPMoviePlayerController*){:?} stop]", this);
-    assert!(this == env.framework_state.media_player.movie_player.active_player.take().unwrap());
-    release(env, this);
+    log_dbg!("[(MPMoviePlayerController*){:?} stop]", this);
+    let host_object = env.objc.borrow_mut::<MPMoviePlayerControllerHostObject>(this);
+    host_object.playback_state = MPMoviePlaybackStateStopped;
+    host_object.current_playback_time = 0.0;
+    // Apps often stop a player that has already finished.
+    if State::get(env).active_player == Some(this) {
+        State::get(env).active_player = None;
+        release(env, this);
+    }
 }
 
 @end
@@ -138,9 +282,104 @@ pub const CLASSES: ClassExports = objc_classes! {
 pub(super) fn handle_players(env: &mut Environment) {
     while let Some(notif) = State::get(env).pending_notifications.pop_front() {
         let (name, object) = notif;
+        let finished = name == MPMoviePlayerPlaybackDidFinishNotification;
+        let active = State::get(env).active_player == Some(object);
+        let user_info = if finished {
+            if active {
+                finish_playback(env, object);
+            }
+            let key =
+                ns_string::get_static_str(env, MPMoviePlayerPlaybackDidFinishReasonUserInfoKey);
+            let reason = MPMovieFinishReasonPlaybackEnded as i64;
+            let reason: id = msg_class![env; NSNumber numberWithLongLong:reason];
+            dict_from_keys_and_objects(env, &[(key, reason)])
+        } else {
+            nil
+        };
         let name = ns_string::get_static_str(env, name);
         let center: id = msg_class![env; NSNotificationCenter defaultCenter];
-        // TODO: should there be some user info attached?
-        let _: () = msg![env; center postNotificationName:name object:object];
+        let _: () = msg![env; center postNotificationName:name
+                                                   object:object
+                                                 userInfo:user_info];
+        release(env, user_info);
+
+        // The runtime's reference is only dropped now, so the player can't be
+        // deallocated before the app has heard that it finished. An observer
+        // may already have dropped it by stopping the player.
+        if finished && active && State::get(env).active_player == Some(object) {
+            State::get(env).active_player = None;
+            release(env, object);
+        }
+        release(env, object);
+    }
+}
+
+fn queue_notification(env: &mut Environment, name: &'static str, player: id) {
+    retain(env, player);
+    State::get(env)
+        .pending_notifications
+        .push_back((name, player));
+}
+
+/// Move a player to the end of the movie, as if it had played to completion.
+fn finish_playback(env: &mut Environment, player: id) {
+    let host_object = env
+        .objc
+        .borrow_mut::<MPMoviePlayerControllerHostObject>(player);
+    host_object.playback_state = MPMoviePlaybackStateStopped;
+    host_object.current_playback_time = host_object.duration.unwrap_or(0.0);
+}
+
+/// Get the duration in seconds of an MPEG-4 or QuickTime movie, from the
+/// movie header (`mvhd`) box inside the `moov` box.
+fn read_movie_duration(bytes: &[u8]) -> Option<NSTimeInterval> {
+    let moov = find_box(bytes, b"moov")?;
+    let mvhd = find_box(moov, b"mvhd")?;
+    let version = *mvhd.first()?;
+    let read_u32 = |offset: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(
+            mvhd.get(offset..offset + 4)?.try_into().unwrap(),
+        ))
+    };
+    let read_u64 = |offset: usize| -> Option<u64> {
+        Some(u64::from_be_bytes(
+            mvhd.get(offset..offset + 8)?.try_into().unwrap(),
+        ))
+    };
+    // The version and flags are followed by the creation and modification
+    // times, which are 32-bit in version 0 and 64-bit in version 1.
+    let (timescale, duration) = match version {
+        0 => (read_u32(12)?, read_u32(16)? as u64),
+        1 => (read_u32(20)?, read_u64(24)?),
+        _ => return None,
+    };
+    if timescale == 0 {
+        return None;
+    }
+    Some(duration as NSTimeInterval / timescale as NSTimeInterval)
+}
+
+/// Find the contents of the first box with a particular type in a sequence of
+/// ISO base media file format boxes.
+fn find_box<'a>(mut bytes: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    while bytes.len() >= 8 {
+        let size = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as u64;
+        let (header_size, size) = match size {
+            0 => (8, bytes.len() as u64),
+            1 => (
+                16,
+                u64::from_be_bytes(bytes.get(8..16)?.try_into().unwrap()),
+            ),
+            _ => (8, size),
+        };
+        if size < header_size || size > bytes.len() as u64 {
+            return None;
+        }
+        let (this_box, rest) = bytes.split_at(size as usize);
+        if &this_box[4..8] == box_type {
+            return Some(&this_box[header_size as usize..]);
+        }
+        bytes = rest;
     }
+    None
 }
@@ -0,0 +1,233 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MFMailComposeViewController`.
+//!
+//! touchHLE has no UI for composing mail, so when the composer appears, the
+//! message is turned into a `mailto:` URL and handed to the host's mail client,
+//! and the delegate is told straight away how that went. As on a real device,
+//! it's then up to the delegate to dismiss the composer.
+
+use crate::frameworks::foundation::ns_string::to_rust_string;
+use crate::frameworks::foundation::{NSInteger, NSUInteger};
+use crate::frameworks::uikit::ui_view_controller::UIViewControllerHostObject;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, nil, objc_classes, release, retain, ClassExports,
+    NSZonePtr,
+};
+use crate::Environment;
+
+type MFMailComposeResult = NSInteger;
+const MFMailComposeResultCancelled: MFMailComposeResult = 0;
+#[allow(dead_code)]
+const MFMailComposeResultSaved: MFMailComposeResult = 1;
+const MFMailComposeResultSent: MFMailComposeResult = 2;
+#[allow(dead_code)]
+const MFMailComposeResultFailed: MFMailComposeResult = 3;
+
+#[derive(Default)]
+pub struct MFMailComposeViewControllerHostObject {
+    superclass: UIViewControllerHostObject,
+    /// `NSObject<MFMailComposeViewControllerDelegate>*`, weak reference
+    mail_compose_delegate: id,
+    to_recipients: Vec<String>,
+    cc_recipients: Vec<String>,
+    bcc_recipients: Vec<String>,
+    subject: Option<String>,
+    body: Option<String>,
+    /// File names of attachments. `mailto:` URLs can't carry attachments, so
+    /// the data itself isn't kept.
+    attachment_names: Vec<String>,
+    /// Whether the message has been handed off already, so it isn't sent
+    /// again if the composer reappears.
+    handed_off: bool,
+}
+impl_HostObject_with_superclass!(MFMailComposeViewControllerHostObject);
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// TODO: should extend UINavigationController, which extends
+//       UIViewController.
+@implementation MFMailComposeViewController: UIViewController
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<MFMailComposeViewControllerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (bool)canSendMail {
+    // Mail is always handed off to the host's mail client.
+    true
+}
+
+- (id)mailComposeDelegate {
+    env.objc.borrow::<MFMailComposeViewControllerHostObject>(this).mail_compose_delegate
+}
+- (())setMailComposeDelegate:(id)delegate { // NSObject<MFMailComposeViewControllerDelegate>*
+    env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this).mail_compose_delegate = delegate;
+}
+
+- (())setToRecipients:(id)recipients { // NSArray<NSString*>*
+    let recipients = strings_from_array(env, recipients);
+    env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this).to_recipients = recipients;
+}
+- (())setCcRecipients:(id)recipients { // NSArray<NSString*>*
+    let recipients = strings_from_array(env, recipients);
+    env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this).cc_recipients = recipients;
+}
+- (())setBccRecipients:(id)recipients { // NSArray<NSString*>*
+    let recipients = strings_from_array(env, recipients);
+    env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this).bcc_recipients = recipients;
+}
+
+- (())setSubject:(id)subject { // NSString*
+    let subject = (subject != nil).then(|| to_rust_string(env, subject).into_owned());
+    env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this).subject = subject;
+}
+
+- (())setMessageBody:(id)body // NSString*
+              isHTML:(bool)is_html {
+    if is_html {
+        log!("TODO: HTML mail body will be sent to the host mail client as plain text");
+    }
+    let body = (body != nil).then(|| to_rust_string(env, body).into_owned());
+    env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this).body = body;
+}
+
+- (())addAttachmentData:(id)_data // NSData*
+               mimeType:(id)_mime_type // NSString*
+               fileName:(id)file_name { // NSString*
+    let file_name = to_rust_string(env, file_name).into_owned();
+    env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this).attachment_names.push(file_name);
+}
+
+- (())viewDidAppear:(bool)_animated {
+    let host_object = env.objc.borrow_mut::<MFMailComposeViewControllerHostObject>(this);
+    if !std::mem::replace(&mut host_object.handed_off, true) {
+        hand_off_to_host(env, this);
+    }
+}
+
+@end
+
+};
+
+fn strings_from_array(env: &mut Environment, array: id) -> Vec<String> {
+    if array == nil {
+        return Vec::new();
+    }
+    let count: NSUInteger = msg![env; array count];
+    (0..count)
+        .map(|i| {
+            let string: id = msg![env; array objectAtIndex:i];
+            to_rust_string(env, string).into_owned()
+        })
+        .collect()
+}
+
+/// Percent-encode a string for use in a `mailto:` URL (RFC 6068).
+fn percent_encode(string: &str) -> String {
+    let mut encoded = String::with_capacity(string.len());
+    for &byte in string.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn mailto_url(host_object: &MFMailComposeViewControllerHostObject) -> String {
+    let join = |addresses: &[String]| -> String {
+        addresses
+            .iter()
+            .map(|address| percent_encode(address))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    let mut url = format!("mailto:{}", join(&host_object.to_recipients));
+    let mut fields = Vec::new();
+    if !host_object.cc_recipients.is_empty() {
+        fields.push(format!("cc={}", join(&host_object.cc_recipients)));
+    }
+    if !host_object.bcc_recipients.is_empty() {
+        fields.push(format!("bcc={}", join(&host_object.bcc_recipients)));
+    }
+    if let Some(ref subject) = host_object.subject {
+        fields.push(format!("subject={}", percent_encode(subject)));
+    }
+    if let Some(ref body) = host_object.body {
+        fields.push(format!("body={}", percent_encode(body)));
+    }
+    if !fields.is_empty() {
+        url.push('?');
+        url.push_str(&fields.join("&"));
+    }
+    url
+}
+
+/// Hands the message to the host mail client and immediately tells the
+/// delegate the result.
+fn hand_off_to_host(env: &mut Environment, controller: id) {
+    retain(env, controller);
+
+    let host_object = env
+        .objc
+        .borrow::<MFMailComposeViewControllerHostObject>(controller);
+    let url = mailto_url(host_object);
+    let delegate = host_object.mail_compose_delegate;
+    if !host_object.attachment_names.is_empty() {
+        log!(
+            "Warning: attachments {:?} can't be passed to the host mail client, dropping them.",
+            host_object.attachment_names
+        );
+    }
+
+    let result = if env.window.is_none() {
+        // Don't launch the host mail client when running headless (e.g. in
+        // the integration tests).
+        log!(
+            "App tried to send mail ({:?}), but there's no window, cancelling.",
+            url
+        );
+        MFMailComposeResultCancelled
+    } else if let Err(e) = crate::window::open_url(&url) {
+        log!(
+            "Couldn't open mail URL {:?} on host ({}), cancelling.",
+            url,
+            e
+        );
+        MFMailComposeResultCancelled
+    } else {
+        echo!("App sent mail, opened {:?} in host mail client.", url);
+        // The message hasn't really been sent yet, but this is the most
+        // useful thing to tell the app.
+        MFMailComposeResultSent
+    };
+
+    if delegate != nil
+        && env.objc.object_has_method_named(
+            &env.mem,
+            delegate,
+            "mailComposeController:didFinishWithResult:error:",
+        )
+    {
+        () = msg![env; delegate mailComposeController:controller
+                                  didFinishWithResult:result
+                                                error:nil];
+    } else {
+        log!(
+            "Mail compose delegate {:?} doesn't implement mailComposeController:didFinishWithResult:error:, ignoring.",
+            delegate
+        );
+    }
+
+    release(env, controller);
+}
@@ -12,8 +12,12 @@
 pub mod eagl;
 mod gles_guest;
 
+use crate::gles::gles11_raw as gles11; // constants only
+use crate::image::Image;
 use crate::mem::ConstPtr;
+use crate::Environment;
 pub use gles_guest::FUNCTIONS;
+use std::time::{SystemTime, UNIX_EPOCH};
 use touchHLE_gl_bindings::gles11::types::GLenum;
 
 #[derive(Default)]
@@ -52,3 +56,102 @@ fn sync_context<'a>(
 
     gles_ctx
 }
+
+/// Capture the contents of the framebuffer the current thread's `EAGLContext`
+/// is rendering to, which could be a `CAEAGLLayer`'s renderbuffer or an
+/// offscreen framebuffer object. Only the area within the viewport is included.
+///
+/// Returns [None] if there is no current context.
+pub fn capture_framebuffer(env: &mut Environment) -> Option<Image> {
+    let window = env.window.as_mut()?;
+    let state = &mut env.framework_state.opengles;
+    state.current_ctx_for_thread(env.current_thread).as_ref()?;
+    let gles = sync_context(state, &mut env.objc, window, env.current_thread);
+
+    let (pixels, width, height) = unsafe {
+        let mut viewport = [0; 4];
+        gles.GetIntegerv(gles11::VIEWPORT, viewport.as_mut_ptr());
+        let [x, y, width, height] = viewport;
+
+        let mut old_alignment = 0;
+        gles.GetIntegerv(gles11::PACK_ALIGNMENT, &mut old_alignment);
+        gles.PixelStorei(gles11::PACK_ALIGNMENT, 1);
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        gles.ReadPixels(
+            x,
+            y,
+            width,
+            height,
+            gles11::RGBA,
+            gles11::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut _,
+        );
+        gles.PixelStorei(gles11::PACK_ALIGNMENT, old_alignment);
+
+        (pixels, width as u32, height as u32)
+    };
+
+    Some(framebuffer_pixels_to_image(&pixels, (width, height)))
+}
+
+/// Convert tightly-packed RGBA pixels read from a framebuffer into an [Image].
+fn framebuffer_pixels_to_image(pixels: &[u8], (width, height): (u32, u32)) -> Image {
+    // OpenGL ES's row order is bottom-to-top, but [Image]'s is top-to-bottom.
+    let mut pixels: Vec<u8> = pixels
+        .chunks(width as usize * 4)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+    // The alpha channel isn't displayed, so it shouldn't be in the image.
+    for rgba in pixels.chunks_exact_mut(4) {
+        rgba[3] = 255;
+    }
+    Image::from_pixel_vec(pixels, (width, height))
+}
+
+/// Save a screenshot of the app's OpenGL ES rendering as a PNG file in the
+/// user data directory. See [capture_framebuffer].
+pub fn save_screenshot(env: &mut Environment) {
+    let Some(image) = capture_framebuffer(env) else {
+        log!("Can't take a screenshot: the app has no current EAGLContext.");
+        return;
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let path =
+        crate::paths::user_data_base_path().join(format!("touchHLE_screenshot_{}.png", timestamp));
+    match std::fs::write(&path, image.to_png()) {
+        Ok(()) => echo!("Saved screenshot: {}", path.display()),
+        Err(e) => log!(
+            "Warning: Couldn't save screenshot to {}: {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framebuffer_pixels_are_flipped_and_opaque() {
+        #[rustfmt::skip]
+        let pixels = [
+            // bottom row
+            1, 2, 3, 0,  4, 5, 6, 128,
+            // top row
+            7, 8, 9, 255,  10, 11, 12, 64,
+        ];
+        let image = framebuffer_pixels_to_image(&pixels, (2, 2));
+        assert_eq!(image.dimensions(), (2, 2));
+        #[rustfmt::skip]
+        assert_eq!(image.pixels(), [
+            7, 8, 9, 255,  10, 11, 12, 255,
+            1, 2, 3, 255,  4, 5, 6, 255,
+        ]);
+    }
+}
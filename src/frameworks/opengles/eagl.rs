@@ -5,10 +5,13 @@
  */
 //! EAGL.
 
+use crate::debug_overlay::OverlayImage;
 use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::core_animation::ca_eagl_layer::{
     find_fullscreen_eagl_layer, get_pixels_vec_for_presenting, present_pixels,
 };
+use crate::frameworks::core_animation::frame_clock;
+use crate::frameworks::core_graphics::{CGFloat, CGRect};
 use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::foundation::NSUInteger;
 use crate::gles::gles11_raw as gles11; // constants only
@@ -57,11 +60,15 @@ const kEAGLRenderingAPIOpenGLES3: EAGLRenderingAPI = 3;
 
 pub(super) struct EAGLContextHostObject {
     pub(super) gles_ctx: Option<Box<dyn GLES>>,
+    api: EAGLRenderingAPI,
     /// Mapping of OpenGL ES renderbuffer names to `EAGLDrawable` instances
     /// (always `CAEAGLLayer*`). Retains the instance so it won't dangle.
     renderbuffer_drawable_bindings: HashMap<GLuint, id>,
     fps_counter: Option<FpsCounter>,
     next_frame_due: Option<Instant>,
+    /// Error found by touchHLE's own validation of an OpenGL ES call, rather
+    /// than by the driver. See `glGetError`.
+    pub(super) pending_error: Option<GLenum>,
 }
 impl HostObject for EAGLContextHostObject {}
 
@@ -74,9 +81,11 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)alloc {
     let host_object = Box::new(EAGLContextHostObject {
         gles_ctx: None,
+        api: 0,
         renderbuffer_drawable_bindings: HashMap::new(),
         fps_counter: None,
         next_frame_due: None,
+        pending_error: None,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -111,7 +120,18 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)initWithAPI:(EAGLRenderingAPI)api {
-    assert!(api == kEAGLRenderingAPIOpenGLES1);
+    if api != kEAGLRenderingAPIOpenGLES1 {
+        // Only OpenGL ES 1.1 is implemented. Like a real device that lacks
+        // support for the requested API, return nil, so that apps which can
+        // also render with OpenGL ES 1.1 will fall back to it.
+        log!(
+            "[(EAGLContext*){:?} initWithAPI:{}] Warning: unsupported API, returning nil",
+            this,
+            api,
+        );
+        release(env, this);
+        return nil;
+    }
 
     let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
     let gles1_ctx = create_gles1_ctx(window, &env.options);
@@ -125,11 +145,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.framework_state.opengles.current_ctx_thread = None;
     log!("Driver info: {}", unsafe { gles1_ctx.driver_description() });
 
-    env.objc.borrow_mut::<EAGLContextHostObject>(this).gles_ctx = Some(gles1_ctx);
+    let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
+    host_obj.gles_ctx = Some(gles1_ctx);
+    host_obj.api = api;
 
     this
 }
 
+- (EAGLRenderingAPI)API {
+    env.objc.borrow::<EAGLContextHostObject>(this).api
+}
+
 - (())dealloc {
     let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
     let bindings = std::mem::take(&mut host_obj.renderbuffer_drawable_bindings);
@@ -141,10 +167,22 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (bool)renderbufferStorage:(NSUInteger)target
                fromDrawable:(id)drawable { // EAGLDrawable (always CAEAGLayer*)
-    assert!(drawable != nil); // TODO: handle unbinding
-
     assert!(target == gles11::RENDERBUFFER_OES);
 
+    if drawable == nil {
+        // Detach the currently-bound renderbuffer from its drawable. The
+        // storage itself is left alone, since the app will usually delete the
+        // renderbuffer or attach it to a new drawable straight afterwards.
+        let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
+        let gles = super::sync_context(&mut env.framework_state.opengles, &mut env.objc, window, env.current_thread);
+        let renderbuffer: GLuint = unsafe { get_int(gles, gles11::RENDERBUFFER_BINDING_OES) as _ };
+        let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
+        if let Some(old_drawable) = host_obj.renderbuffer_drawable_bindings.remove(&renderbuffer) {
+            release(env, old_drawable);
+        }
+        return true;
+    }
+
     let props: id = msg![env; drawable drawableProperties];
 
     let format_key = get_static_str(env, kEAGLDrawablePropertyColorFormat);
@@ -160,24 +198,27 @@ pub const CLASSES: ClassExports = objc_classes! {
     // and it seems like RGB565 isn't supported, at least on a machine with
     // Intel HD Graphics 615 running macOS Monterey. I don't think RGBA8 is
     // guaranteed either, but it at least seems to work.
-    if !msg![env; format isEqualTo:format_rgba8] && !msg![env; format isEqualTo:format_rgb565] {
+    if !msg![env; format isEqual:format_rgba8] && !msg![env; format isEqual:format_rgb565] {
         log!("[renderbufferStorage:{:?} fromDrawable:{:?}] Warning: unhandled format {:?}, using RGBA8", target, drawable, format);
     }
     let internalformat = gles11::RGBA8_OES;
 
-    let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
+    let bounds: CGRect = msg![env; drawable bounds];
+    let scale_hack = env.options.scale_hack.get() as CGFloat;
+    let (width, height) = renderbuffer_size_for_layer(bounds, scale_hack).unwrap_or_else(|| {
+        // Some apps bind the renderbuffer before giving the layer a size.
+        log!("[renderbufferStorage:{:?} fromDrawable:{:?}] Warning: layer has no size, using window size", target, drawable);
+        env.window().size_unrotated_scalehacked()
+    });
 
-    // FIXME: get width and height from the layer!
-    let (width, height) = window.size_unrotated_scalehacked();
+    let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
 
     // Unclear from documentation if this method requires an appropriate context
     // to already be active, but that seems to be the case in practice?
     let gles = super::sync_context(&mut env.framework_state.opengles, &mut env.objc, window, env.current_thread);
     let renderbuffer: GLuint = unsafe {
         gles.RenderbufferStorageOES(target, internalformat, width.try_into().unwrap(), height.try_into().unwrap());
-        let mut renderbuffer = 0;
-        gles.GetIntegerv(gles11::RENDERBUFFER_BINDING_OES, &mut renderbuffer);
-        renderbuffer as _
+        get_int(gles, gles11::RENDERBUFFER_BINDING_OES) as _
     };
 
     retain(env, drawable);
@@ -197,7 +238,11 @@ pub const CLASSES: ClassExports = objc_classes! {
 
     // The presented frame should be displayed ASAP, but the next one must be
     // delayed, so this needs to be checked before returning.
-    let sleep_for = limit_framerate(&mut env.objc.borrow_mut::<EAGLContextHostObject>(this).next_frame_due, &env.options);
+    let sleep_for = if env.throttle.turbo() {
+        None
+    } else {
+        limit_framerate(&mut env.objc.borrow_mut::<EAGLContextHostObject>(this).next_frame_due, &env.options)
+    };
 
     if env.options.print_fps {
         env
@@ -236,10 +281,15 @@ pub const CLASSES: ClassExports = objc_classes! {
             drawable,
             renderbuffer,
         );
+        // Nothing is composited, but layer animations still advance per
+        // frame.
+        frame_clock::frame_presented(env);
+        env.debug_overlay.begin_present(Instant::now());
+        env.debug_overlay.update_stats(&env.objc, &env.mem, Instant::now());
         // re-borrow
         let gles = super::sync_context(&mut env.framework_state.opengles, &mut env.objc, env.window.as_mut().unwrap(), env.current_thread);
         unsafe {
-            present_renderbuffer(gles, env.window.as_mut().unwrap());
+            present_renderbuffer(gles, env.window.as_mut().unwrap(), env.debug_overlay.image());
         }
     } else {
         if fullscreen_layer != nil {
@@ -282,6 +332,10 @@ pub const CLASSES: ClassExports = objc_classes! {
         env.sleep(sleep_for, /* tail_call: */ false);
     }
 
+    if drawable == fullscreen_layer {
+        env.debug_overlay.end_present(Instant::now());
+    }
+
     true
 }
 
@@ -289,6 +343,19 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// The size in pixels of a renderbuffer for a `CAEAGLLayer` with some bounds:
+/// the size of the layer, with the scale hack applied just like it is to
+/// glViewport() etc. Returns [None] if the layer has no size yet.
+fn renderbuffer_size_for_layer(bounds: CGRect, scale_hack: CGFloat) -> Option<(u32, u32)> {
+    if bounds.size.width <= 0.0 || bounds.size.height <= 0.0 {
+        return None;
+    }
+    Some((
+        (bounds.size.width * scale_hack).round() as u32,
+        (bounds.size.height * scale_hack).round() as u32,
+    ))
+}
+
 /// Implement framerate limiting.
 ///
 /// The real iPhone OS seems to force 60Hz v-sync in `presentRenderbuffer:`.
@@ -474,7 +541,11 @@ unsafe fn read_renderbuffer(gles: &mut dyn GLES, mut pixel_buffer: Vec<u8>) -> (
 /// doing so. The front and back buffers are then swapped.
 ///
 /// The provided context must be current.
-unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
+unsafe fn present_renderbuffer(
+    gles: &mut dyn GLES,
+    window: &mut Window,
+    debug_overlay: Option<&OverlayImage>,
+) {
     // We can't directly copy the content of the renderbuffer to the default
     // framebuffer (the window), but if we attach it to a framebuffer object, we
     // can use glCopyTexImage2D() to copy it to a texture, which we can then
@@ -514,14 +585,6 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
         height,
         0,
     );
-    // The texture will not have any mip levels so we must ensure the filter
-    // does not use them, else rendering will fail.
-    gles.TexParameteri(
-        gles11::TEXTURE_2D,
-        gles11::TEXTURE_MIN_FILTER,
-        gles11::LINEAR as _,
-    );
-
     // Clean up the framebuffer object since we no longer need it.
     // This also sets the framebuffer bindings back to zero, so rendering
     // will go to the default framebuffer (the window).
@@ -596,7 +659,9 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
         gles,
         window.viewport(),
         window.rotation_matrix(),
+        window.scaling_filter(),
         window.virtual_cursor_visible_at(),
+        debug_overlay,
     );
 
     // Clean up the texture
@@ -674,3 +739,42 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
 
     //{ let err = gl21::GetError(); if err != 0 { panic!("{:#x}", err); } }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frameworks::core_graphics::{CGPoint, CGSize};
+
+    fn rect(x: CGFloat, y: CGFloat, width: CGFloat, height: CGFloat) -> CGRect {
+        CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        }
+    }
+
+    #[test]
+    fn renderbuffer_size() {
+        // The origin doesn't matter, only the size.
+        assert_eq!(
+            renderbuffer_size_for_layer(rect(10.0, 20.0, 320.0, 480.0), 1.0),
+            Some((320, 480))
+        );
+        assert_eq!(
+            renderbuffer_size_for_layer(rect(0.0, 0.0, 320.0, 480.0), 2.0),
+            Some((640, 960))
+        );
+        // Fractional sizes are rounded.
+        assert_eq!(
+            renderbuffer_size_for_layer(rect(0.0, 0.0, 100.4, 50.6), 1.0),
+            Some((100, 51))
+        );
+        assert_eq!(
+            renderbuffer_size_for_layer(rect(0.0, 0.0, 0.0, 480.0), 1.0),
+            None
+        );
+        assert_eq!(
+            renderbuffer_size_for_layer(rect(0.0, 0.0, 320.0, -1.0), 1.0),
+            None
+        );
+    }
+}
@@ -15,7 +15,7 @@
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::gles::gles11_raw as gles11; // constants only
 use crate::gles::GLES;
-use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr, Ptr};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr};
 use crate::Environment;
 
 // These types are the same size in guest code (32-bit) and host code (64-bit).
@@ -65,8 +65,31 @@ fn panic_on_gl_errors(gles: &mut dyn GLES) {
     }
 }
 
+/// Flag an error found by validation here, for calls the driver would accept
+/// but OpenGL ES 1.1 wouldn't. Like with the driver, only the first error is
+/// kept until `glGetError` is called.
+fn set_error(env: &mut Environment, error: GLenum) {
+    let state = &mut env.framework_state.opengles;
+    let current_ctx = state.current_ctx_for_thread(env.current_thread).unwrap();
+    env.objc
+        .borrow_mut::<super::eagl::EAGLContextHostObject>(current_ctx)
+        .pending_error
+        .get_or_insert(error);
+}
+
 // Generic state manipulation
 fn glGetError(env: &mut Environment) -> GLenum {
+    let state = &mut env.framework_state.opengles;
+    let current_ctx = state.current_ctx_for_thread(env.current_thread).unwrap();
+    let pending_error = env
+        .objc
+        .borrow_mut::<super::eagl::EAGLContextHostObject>(current_ctx)
+        .pending_error
+        .take();
+    if let Some(err) = pending_error {
+        log!("Warning: glGetError() returned {:#x}", err);
+        return err;
+    }
     with_ctx_and_mem(env, |gles, _mem| {
         let err = unsafe { gles.GetError() };
         if err != 0 {
@@ -149,6 +172,9 @@ fn glHint(env: &mut Environment, target: GLenum, mode: GLenum) {
 fn glFlush(env: &mut Environment) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Flush() })
 }
+fn glFinish(env: &mut Environment) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Finish() })
+}
 fn glGetString(env: &mut Environment, name: GLenum) -> ConstPtr<GLubyte> {
     let res = if let Some(&str) = env.framework_state.opengles.strings_cache.get(&name) {
         str
@@ -262,6 +288,22 @@ fn glLineWidth(env: &mut Environment, val: GLfloat) {
 fn glLineWidthx(env: &mut Environment, val: GLfixed) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.LineWidthx(val) })
 }
+fn glLogicOp(env: &mut Environment, opcode: GLenum) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.LogicOp(opcode) })
+}
+fn glStencilFunc(env: &mut Environment, func: GLenum, ref_: GLint, mask: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.StencilFunc(func, ref_, mask)
+    })
+}
+fn glStencilOp(env: &mut Environment, sfail: GLenum, dpfail: GLenum, dppass: GLenum) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.StencilOp(sfail, dpfail, dppass)
+    })
+}
+fn glStencilMask(env: &mut Environment, mask: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.StencilMask(mask) })
+}
 // Points
 fn glPointSize(env: &mut Environment, size: GLfloat) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.PointSize(size) })
@@ -741,6 +783,178 @@ fn glTranslatex(env: &mut Environment, x: GLfixed, y: GLfixed, z: GLfixed) {
 fn glPixelStorei(env: &mut Environment, pname: GLenum, param: GLint) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.PixelStorei(pname, param) })
 }
+/// Check the arguments of `glReadPixels` like OpenGL ES 1.1 does, given the
+/// implementation's own format and type pair. Returns the error to flag if
+/// they're invalid.
+fn validate_read_pixels(
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    type_: GLenum,
+    (read_format, read_type): (GLenum, GLenum),
+) -> Result<(), GLenum> {
+    if ![gles11::RGBA, read_format].contains(&format)
+        || ![gles11::UNSIGNED_BYTE, read_type].contains(&type_)
+    {
+        return Err(gles11::INVALID_ENUM);
+    }
+    if (format, type_) != (gles11::RGBA, gles11::UNSIGNED_BYTE)
+        && (format, type_) != (read_format, read_type)
+    {
+        return Err(gles11::INVALID_OPERATION);
+    }
+    if width < 0 || height < 0 {
+        return Err(gles11::INVALID_VALUE);
+    }
+    Ok(())
+}
+
+fn glReadPixels(
+    env: &mut Environment,
+    x: GLint,
+    y: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    type_: GLenum,
+    pixels: MutVoidPtr,
+) {
+    // OpenGL ES 1.1 only allows RGBA/UNSIGNED_BYTE and one pair chosen by the
+    // implementation. The host driver may accept more, so this has to be
+    // checked here, and before the guest memory is touched.
+    let read_pair = with_ctx_and_mem(env, |gles, _mem| unsafe {
+        let mut read_format = 0;
+        let mut read_type = 0;
+        gles.GetIntegerv(
+            gles11::IMPLEMENTATION_COLOR_READ_FORMAT_OES,
+            &mut read_format,
+        );
+        gles.GetIntegerv(gles11::IMPLEMENTATION_COLOR_READ_TYPE_OES, &mut read_type);
+        (read_format as GLenum, read_type as GLenum)
+    });
+    if let Err(error) = validate_read_pixels(width, height, format, type_, read_pair) {
+        set_error(env, error);
+        return;
+    }
+
+    // Like OpenGL ES on iPhone OS, the rows are in bottom-to-top order, so
+    // no flipping is needed here.
+    let factor = env.options.scale_hack.get() as GLsizei;
+    with_ctx_and_mem(env, |gles, mem| unsafe {
+        let bytes_per_pixel = image_size_estimate(1, format, type_) as usize;
+        let mut alignment = 0;
+        gles.GetIntegerv(gles11::PACK_ALIGNMENT, &mut alignment);
+        let alignment = alignment as usize;
+        let width_usize = usize::try_from(width).unwrap();
+        let height_usize = usize::try_from(height).unwrap();
+        let (stride, size) =
+            packed_pixels_layout(width_usize, height_usize, bytes_per_pixel, alignment);
+        let pixels = mem.bytes_at_mut(pixels.cast(), size.try_into().unwrap());
+
+        // apply scale hack: renderbuffers (but not textures) are larger than
+        // the app thinks, so read a larger area and pick out the pixels the
+        // app would have seen.
+        if factor == 1 || !is_renderbuffer_bound_to_framebuffer(gles) {
+            gles.ReadPixels(
+                x,
+                y,
+                width,
+                height,
+                format,
+                type_,
+                pixels.as_mut_ptr() as *mut GLvoid,
+            );
+            return;
+        }
+        let factor_usize = factor as usize;
+        let (scaled_stride, scaled_size) = packed_pixels_layout(
+            width_usize * factor_usize,
+            height_usize * factor_usize,
+            bytes_per_pixel,
+            alignment,
+        );
+        let mut scaled_pixels = vec![0u8; scaled_size];
+        gles.ReadPixels(
+            x * factor,
+            y * factor,
+            width * factor,
+            height * factor,
+            format,
+            type_,
+            scaled_pixels.as_mut_ptr() as *mut GLvoid,
+        );
+        downscale_pixels(
+            &scaled_pixels,
+            scaled_stride,
+            pixels,
+            stride,
+            width_usize * bytes_per_pixel,
+            bytes_per_pixel,
+            factor_usize,
+        );
+    })
+}
+/// Get the row stride and total size in bytes of pixel data packed with the
+/// `GL_PACK_ALIGNMENT` value `alignment`. The last row doesn't need padding.
+fn packed_pixels_layout(
+    width: usize,
+    height: usize,
+    bytes_per_pixel: usize,
+    alignment: usize,
+) -> (usize, usize) {
+    let row_size = width * bytes_per_pixel;
+    let stride = row_size.next_multiple_of(alignment);
+    let size = match height {
+        0 => 0,
+        height => stride * (height - 1) + row_size,
+    };
+    (stride, size)
+}
+/// Undo the scale hack's enlargement of a renderbuffer by picking out every
+/// `factor`th pixel of every `factor`th row of `scaled_pixels`. Only the first
+/// `row_size` bytes of each row in `pixels` are written, the padding is left
+/// untouched.
+fn downscale_pixels(
+    scaled_pixels: &[u8],
+    scaled_stride: usize,
+    pixels: &mut [u8],
+    stride: usize,
+    row_size: usize,
+    bytes_per_pixel: usize,
+    factor: usize,
+) {
+    for (row_idx, row) in pixels.chunks_mut(stride).enumerate() {
+        let scaled_row = &scaled_pixels[row_idx * factor * scaled_stride..];
+        for (pixel_idx, pixel) in row[..row_size]
+            .chunks_exact_mut(bytes_per_pixel)
+            .enumerate()
+        {
+            let scaled_pixel_idx = pixel_idx * factor * bytes_per_pixel;
+            pixel
+                .copy_from_slice(&scaled_row[scaled_pixel_idx..scaled_pixel_idx + bytes_per_pixel]);
+        }
+    }
+}
+/// Check whether the color buffer of the current framebuffer is a
+/// renderbuffer, and is therefore affected by the scale hack.
+unsafe fn is_renderbuffer_bound_to_framebuffer(gles: &mut dyn GLES) -> bool {
+    // Querying the attachments of the default framebuffer is an error, and
+    // the app has no business using it anyway.
+    let mut framebuffer = 0;
+    gles.GetIntegerv(gles11::FRAMEBUFFER_BINDING_OES, &mut framebuffer);
+    if framebuffer == 0 {
+        return false;
+    }
+
+    let mut type_ = 0;
+    gles.GetFramebufferAttachmentParameterivOES(
+        gles11::FRAMEBUFFER_OES,
+        gles11::COLOR_ATTACHMENT0_OES,
+        gles11::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE_OES,
+        &mut type_,
+    );
+    type_ as GLenum == gles11::RENDERBUFFER_OES
+}
 fn glGenTextures(env: &mut Environment, n: GLsizei, textures: MutPtr<GLuint>) {
     with_ctx_and_mem(env, |gles, mem| {
         let n_usize: GuestUSize = n.try_into().unwrap();
@@ -1079,6 +1293,18 @@ fn glGetRenderbufferParameterivOES(
         }
     })
 }
+fn glGetFramebufferAttachmentParameterivOES(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    pname: GLenum,
+    params: MutPtr<GLint>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetFramebufferAttachmentParameterivOES(target, attachment, pname, params) };
+    })
+}
 fn glCheckFramebufferStatusOES(env: &mut Environment, target: GLenum) -> GLenum {
     with_ctx_and_mem(env, |gles, _mem| unsafe {
         gles.CheckFramebufferStatusOES(target)
@@ -1147,6 +1373,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glGetTexEnviv(_, _, _)),
     export_c_func!(glHint(_, _)),
     export_c_func!(glFlush()),
+    export_c_func!(glFinish()),
     export_c_func!(glGetString(_)),
     // Other state manipulation
     export_c_func!(glAlphaFunc(_, _)),
@@ -1166,6 +1393,10 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glViewport(_, _, _, _)),
     export_c_func!(glLineWidth(_)),
     export_c_func!(glLineWidthx(_)),
+    export_c_func!(glLogicOp(_)),
+    export_c_func!(glStencilFunc(_, _, _)),
+    export_c_func!(glStencilOp(_, _, _)),
+    export_c_func!(glStencilMask(_)),
     // Points
     export_c_func!(glPointSize(_)),
     export_c_func!(glPointSizex(_)),
@@ -1236,6 +1467,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glTranslatex(_, _, _)),
     // Textures
     export_c_func!(glPixelStorei(_, _)),
+    export_c_func!(glReadPixels(_, _, _, _, _, _, _)),
     export_c_func!(glGenTextures(_, _)),
     export_c_func!(glDeleteTextures(_, _)),
     export_c_func!(glActiveTexture(_)),
@@ -1266,8 +1498,82 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glFramebufferRenderbufferOES(_, _, _, _)),
     export_c_func!(glFramebufferTexture2DOES(_, _, _, _, _)),
     export_c_func!(glGetRenderbufferParameterivOES(_, _, _)),
+    export_c_func!(glGetFramebufferAttachmentParameterivOES(_, _, _, _)),
     export_c_func!(glCheckFramebufferStatusOES(_)),
     export_c_func!(glDeleteFramebuffersOES(_, _)),
     export_c_func!(glDeleteRenderbuffersOES(_, _)),
     export_c_func!(glGenerateMipmapOES(_)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_pixels_layout_alignment() {
+        // RGB, 3 pixels wide: 9 bytes per row, padded to 12 with the default
+        // alignment of 4, but the last row isn't padded.
+        assert_eq!(packed_pixels_layout(3, 2, 3, 4), (12, 21));
+        assert_eq!(packed_pixels_layout(3, 2, 3, 1), (9, 18));
+        assert_eq!(packed_pixels_layout(3, 0, 3, 4), (12, 0));
+        assert_eq!(packed_pixels_layout(2, 3, 4, 8), (8, 24));
+    }
+
+    #[test]
+    fn read_pixels_format_and_type_validation() {
+        let read_pair = (gles11::BGRA_EXT, gles11::UNSIGNED_BYTE);
+        let validate = |format, type_| validate_read_pixels(1, 1, format, type_, read_pair);
+        assert_eq!(validate(gles11::RGBA, gles11::UNSIGNED_BYTE), Ok(()));
+        assert_eq!(validate(gles11::BGRA_EXT, gles11::UNSIGNED_BYTE), Ok(()));
+        // Valid in desktop OpenGL, but not OpenGL ES 1.1.
+        assert_eq!(
+            validate(gles11::RGB, gles11::UNSIGNED_BYTE),
+            Err(gles11::INVALID_ENUM)
+        );
+        assert_eq!(
+            validate(gles11::RGBA, gles11::UNSIGNED_SHORT_4_4_4_4),
+            Err(gles11::INVALID_ENUM)
+        );
+        // Each is allowed on its own, but not in this combination.
+        let read_pair = (gles11::RGB, gles11::UNSIGNED_SHORT_5_6_5);
+        assert_eq!(
+            validate_read_pixels(1, 1, gles11::RGB, gles11::UNSIGNED_BYTE, read_pair),
+            Err(gles11::INVALID_OPERATION)
+        );
+        assert_eq!(
+            validate_read_pixels(-1, 1, gles11::RGBA, gles11::UNSIGNED_BYTE, read_pair),
+            Err(gles11::INVALID_VALUE)
+        );
+    }
+
+    #[test]
+    fn downscale_pixels_picks_bottom_left_of_each_block() {
+        // 4x4 single-byte pixels with stride 4, scaled by 2, into a 2x2 image
+        // with the stride padded to 4.
+        #[rustfmt::skip]
+        let scaled_pixels = [
+            1, 0, 2, 0,
+            0, 0, 0, 0,
+            3, 0, 4, 0,
+            0, 0, 0, 0,
+        ];
+        let mut pixels = [0xFF; 4 + 2];
+        downscale_pixels(&scaled_pixels, 4, &mut pixels, 4, 2, 1, 2);
+        // The padding is untouched, and the row order is unchanged, since
+        // both buffers are bottom-to-top.
+        assert_eq!(pixels, [1, 2, 0xFF, 0xFF, 3, 4]);
+    }
+
+    #[test]
+    fn downscale_pixels_multi_byte() {
+        // 2x1 RGBA pixels scaled by 2, so the scaled buffer is 4x2.
+        #[rustfmt::skip]
+        let scaled_pixels = [
+            1, 2, 3, 4,  0, 0, 0, 0,  5, 6, 7, 8,  0, 0, 0, 0,
+            0, 0, 0, 0,  0, 0, 0, 0,  0, 0, 0, 0,  0, 0, 0, 0,
+        ];
+        let mut pixels = [0; 8];
+        downscale_pixels(&scaled_pixels, 16, &mut pixels, 8, 8, 4, 2);
+        assert_eq!(pixels, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+}
@@ -4,5 +4,17 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! StoreKit
+//!
+//! touchHLE doesn't connect to the App Store. Every product the app asks about
+//! exists, and purchases either all succeed or all fail, depending on the
+//! `--in-app-purchases=` option. Requests are answered immediately, since
+//! there's no server to wait for.
 
+pub mod sk_payment;
+pub mod sk_payment_queue;
 pub mod sk_product;
+
+#[derive(Default)]
+pub struct State {
+    sk_payment_queue: sk_payment_queue::State,
+}
@@ -0,0 +1,111 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKPayment` and `SKMutablePayment`.
+
+use crate::frameworks::foundation::NSInteger;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+
+struct SKPaymentHostObject {
+    /// `NSString*`
+    product_identifier: id,
+    quantity: NSInteger,
+}
+impl HostObject for SKPaymentHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation SKPayment: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(SKPaymentHostObject {
+        product_identifier: nil,
+        quantity: 1,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)paymentWithProduct:(id)product { // SKProduct*
+    let identifier: id = msg![env; product productIdentifier];
+    msg![env; this paymentWithProductIdentifier:identifier]
+}
+// Deprecated, but the only option in iPhone OS 3.0.
++ (id)paymentWithProductIdentifier:(id)identifier { // NSString*
+    let new: id = msg![env; this alloc];
+    let identifier: id = msg![env; identifier copy];
+    env.objc.borrow_mut::<SKPaymentHostObject>(new).product_identifier = identifier;
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let product_identifier = env.objc.borrow::<SKPaymentHostObject>(this).product_identifier;
+    release(env, product_identifier);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)_zone {
+    let &SKPaymentHostObject {
+        product_identifier,
+        quantity,
+    } = env.objc.borrow(this);
+    let new: id = msg_class![env; SKMutablePayment alloc];
+    retain(env, product_identifier);
+    *env.objc.borrow_mut(new) = SKPaymentHostObject {
+        product_identifier,
+        quantity,
+    };
+    new
+}
+
+- (id)productIdentifier {
+    env.objc.borrow::<SKPaymentHostObject>(this).product_identifier
+}
+- (NSInteger)quantity {
+    env.objc.borrow::<SKPaymentHostObject>(this).quantity
+}
+
+@end
+
+@implementation SKMutablePayment: SKPayment
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let &SKPaymentHostObject {
+        product_identifier,
+        quantity,
+    } = env.objc.borrow(this);
+    let new: id = msg_class![env; SKPayment alloc];
+    retain(env, product_identifier);
+    *env.objc.borrow_mut(new) = SKPaymentHostObject {
+        product_identifier,
+        quantity,
+    };
+    new
+}
+
+- (())setProductIdentifier:(id)identifier { // NSString*
+    let identifier: id = msg![env; identifier copy];
+    let host_object = env.objc.borrow_mut::<SKPaymentHostObject>(this);
+    let old = std::mem::replace(&mut host_object.product_identifier, identifier);
+    release(env, old);
+}
+- (())setQuantity:(NSInteger)quantity {
+    env.objc.borrow_mut::<SKPaymentHostObject>(this).quantity = quantity;
+}
+
+@end
+
+};
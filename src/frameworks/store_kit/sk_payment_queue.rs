@@ -0,0 +1,305 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKPaymentQueue` and `SKPaymentTransaction`.
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use crate::frameworks::foundation::{ns_array, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr, TrivialHostObject,
+};
+use crate::Environment;
+
+pub const SKErrorDomain: &str = "SKErrorDomain";
+
+pub const CONSTANTS: ConstantExports = &[("_SKErrorDomain", HostConstant::NSString(SKErrorDomain))];
+
+type SKErrorCode = NSInteger;
+const SKErrorPaymentCancelled: SKErrorCode = 2;
+
+type SKPaymentTransactionState = NSInteger;
+const SKPaymentTransactionStatePurchasing: SKPaymentTransactionState = 0;
+const SKPaymentTransactionStatePurchased: SKPaymentTransactionState = 1;
+const SKPaymentTransactionStateFailed: SKPaymentTransactionState = 2;
+const SKPaymentTransactionStateRestored: SKPaymentTransactionState = 3;
+
+#[derive(Default)]
+pub struct State {
+    /// [SKPaymentQueue defaultQueue]
+    default_queue: Option<id>,
+    /// Things implementing SKPaymentTransactionObserver, weak references
+    observers: Vec<id>,
+    /// Unfinished `SKPaymentTransaction*`s, retained.
+    transactions: Vec<id>,
+    /// Identifiers of products purchased in this session, for restoring.
+    /// TODO: persist these
+    purchased: Vec<String>,
+    transaction_count: u32,
+}
+
+struct SKPaymentTransactionHostObject {
+    /// `SKPayment*`
+    payment: id,
+    state: SKPaymentTransactionState,
+    /// `NSString*`
+    identifier: id,
+    /// `NSDate*`
+    date: id,
+    /// `NSError*`
+    error: id,
+}
+impl HostObject for SKPaymentTransactionHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// This is a singleton.
+@implementation SKPaymentQueue: NSObject
+
++ (id)defaultQueue {
+    if let Some(queue) = env.framework_state.store_kit.sk_payment_queue.default_queue {
+        queue
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::new(TrivialHostObject),
+            &mut env.mem
+        );
+        env.framework_state.store_kit.sk_payment_queue.default_queue = Some(new);
+        new
+   }
+}
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
++ (bool)canMakePayments {
+    true
+}
+
+- (())addTransactionObserver:(id)observer { // id<SKPaymentTransactionObserver>
+    let observers = &mut env.framework_state.store_kit.sk_payment_queue.observers;
+    if !observers.contains(&observer) {
+        observers.push(observer);
+    }
+}
+- (())removeTransactionObserver:(id)observer { // id<SKPaymentTransactionObserver>
+    let observers = &mut env.framework_state.store_kit.sk_payment_queue.observers;
+    observers.retain(|&other| other != observer);
+}
+
+- (id)transactions {
+    let transactions = env.framework_state.store_kit.sk_payment_queue.transactions.clone();
+    for &transaction in &transactions {
+        retain(env, transaction);
+    }
+    let transactions = ns_array::from_vec(env, transactions);
+    autorelease(env, transactions)
+}
+
+- (())addPayment:(id)payment { // SKPayment*
+    let payment: id = msg![env; payment copy];
+    let product_identifier: id = msg![env; payment productIdentifier];
+    let product_identifier = to_rust_string(env, product_identifier).into_owned();
+
+    let transaction = new_transaction(env, payment, SKPaymentTransactionStatePurchasing);
+    release(env, payment);
+    env.framework_state.store_kit.sk_payment_queue.transactions.push(transaction);
+    update_transactions(env, this, vec![transaction]);
+
+    if env.options.in_app_purchases_succeed {
+        log!("App is purchasing {:?}, simulating success.", product_identifier);
+        set_transaction_state(env, transaction, SKPaymentTransactionStatePurchased);
+        env.framework_state.store_kit.sk_payment_queue.purchased.push(product_identifier);
+    } else {
+        log!("App is purchasing {:?}, simulating cancellation. Use --in-app-purchases=succeed to change this.", product_identifier);
+        let domain = get_static_str(env, SKErrorDomain);
+        let error: id = msg_class![env; NSError alloc];
+        let error: id = msg![env; error initWithDomain:domain
+                                                  code:SKErrorPaymentCancelled
+                                              userInfo:nil];
+        env.objc.borrow_mut::<SKPaymentTransactionHostObject>(transaction).error = error;
+        set_transaction_state(env, transaction, SKPaymentTransactionStateFailed);
+    }
+    update_transactions(env, this, vec![transaction]);
+}
+
+- (())finishTransaction:(id)transaction { // SKPaymentTransaction*
+    let transactions = &mut env.framework_state.store_kit.sk_payment_queue.transactions;
+    let Some(index) = transactions.iter().position(|&other| other == transaction) else {
+        log!("Warning: app tried to finish unknown transaction {:?}, ignoring.", transaction);
+        return;
+    };
+    transactions.remove(index);
+
+    let observers = env.framework_state.store_kit.sk_payment_queue.observers.clone();
+    for observer in observers {
+        if !env.objc.object_has_method_named(&env.mem, observer, "paymentQueue:removedTransactions:") {
+            continue;
+        }
+        retain(env, transaction);
+        let transactions = ns_array::from_vec(env, vec![transaction]);
+        autorelease(env, transactions);
+        () = msg![env; observer paymentQueue:this removedTransactions:transactions];
+    }
+
+    release(env, transaction);
+}
+
+- (())restoreCompletedTransactions {
+    let purchased = env.framework_state.store_kit.sk_payment_queue.purchased.clone();
+    log!("App is restoring purchases, restoring {} purchase(s) made in this session.", purchased.len());
+    let mut transactions = Vec::new();
+    for product_identifier in purchased {
+        let product_identifier = from_rust_string(env, product_identifier);
+        let payment: id = msg_class![env; SKPayment paymentWithProductIdentifier:product_identifier];
+        release(env, product_identifier);
+        // TODO: originalTransaction
+        let transaction = new_transaction(env, payment, SKPaymentTransactionStateRestored);
+        env.framework_state.store_kit.sk_payment_queue.transactions.push(transaction);
+        transactions.push(transaction);
+    }
+    if !transactions.is_empty() {
+        update_transactions(env, this, transactions);
+    }
+
+    let observers = env.framework_state.store_kit.sk_payment_queue.observers.clone();
+    for observer in observers {
+        if env.objc.object_has_method_named(&env.mem, observer, "paymentQueueRestoreCompletedTransactionsFinished:") {
+            () = msg![env; observer paymentQueueRestoreCompletedTransactionsFinished:this];
+        }
+    }
+}
+
+@end
+
+@implementation SKPaymentTransaction: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(SKPaymentTransactionHostObject {
+        payment: nil,
+        state: SKPaymentTransactionStatePurchasing,
+        identifier: nil,
+        date: nil,
+        error: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &SKPaymentTransactionHostObject {
+        payment,
+        identifier,
+        date,
+        error,
+        ..
+    } = env.objc.borrow(this);
+    release(env, payment);
+    release(env, identifier);
+    release(env, date);
+    release(env, error);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)payment {
+    env.objc.borrow::<SKPaymentTransactionHostObject>(this).payment
+}
+- (SKPaymentTransactionState)transactionState {
+    env.objc.borrow::<SKPaymentTransactionHostObject>(this).state
+}
+- (id)transactionIdentifier {
+    env.objc.borrow::<SKPaymentTransactionHostObject>(this).identifier
+}
+- (id)transactionDate {
+    env.objc.borrow::<SKPaymentTransactionHostObject>(this).date
+}
+- (id)error {
+    env.objc.borrow::<SKPaymentTransactionHostObject>(this).error
+}
+- (id)originalTransaction {
+    nil
+}
+- (id)transactionReceipt {
+    // TODO: Apps that verify receipts won't be satisfied by this.
+    nil
+}
+
+@end
+
+};
+
+/// Create a new `SKPaymentTransaction*` (+1 reference).
+fn new_transaction(env: &mut Environment, payment: id, state: SKPaymentTransactionState) -> id {
+    retain(env, payment);
+    let transaction: id = msg_class![env; SKPaymentTransaction alloc];
+    env.objc
+        .borrow_mut::<SKPaymentTransactionHostObject>(transaction)
+        .payment = payment;
+    set_transaction_state(env, transaction, state);
+    transaction
+}
+
+fn set_transaction_state(env: &mut Environment, transaction: id, state: SKPaymentTransactionState) {
+    let (identifier, date) = if state == SKPaymentTransactionStatePurchased
+        || state == SKPaymentTransactionStateRestored
+    {
+        let queue_state = &mut env.framework_state.store_kit.sk_payment_queue;
+        queue_state.transaction_count += 1;
+        let identifier = format!("{}", 1000000000 + queue_state.transaction_count);
+        let identifier = from_rust_string(env, identifier);
+        let date: id = msg_class![env; NSDate date];
+        retain(env, date);
+        (identifier, date)
+    } else {
+        (nil, nil)
+    };
+
+    let host_object = env
+        .objc
+        .borrow_mut::<SKPaymentTransactionHostObject>(transaction);
+    host_object.state = state;
+    let old_identifier = std::mem::replace(&mut host_object.identifier, identifier);
+    let old_date = std::mem::replace(&mut host_object.date, date);
+    release(env, old_identifier);
+    release(env, old_date);
+}
+
+/// Tell all the observers about the transactions.
+fn update_transactions(env: &mut Environment, queue: id, transactions: Vec<id>) {
+    for &transaction in &transactions {
+        retain(env, transaction);
+    }
+    let transactions = ns_array::from_vec(env, transactions);
+    let observers = env
+        .framework_state
+        .store_kit
+        .sk_payment_queue
+        .observers
+        .clone();
+    for observer in observers {
+        if !env.objc.object_has_method_named(
+            &env.mem,
+            observer,
+            "paymentQueue:updatedTransactions:",
+        ) {
+            log!(
+                "Warning: transaction observer {:?} doesn't implement paymentQueue:updatedTransactions:, ignoring.",
+                observer
+            );
+            continue;
+        }
+        log_dbg!(
+            "Sending [{:?} paymentQueue:{:?} updatedTransactions:{:?}]",
+            observer,
+            queue,
+            transactions
+        );
+        () = msg![env; observer paymentQueue:queue updatedTransactions:transactions];
+    }
+    release(env, transactions);
+}
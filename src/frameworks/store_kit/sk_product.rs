@@ -3,16 +3,190 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
+//! `SKProduct`, `SKProductsRequest` and `SKProductsResponse`.
 
-use crate::objc::ClassExports;
-use crate::objc_classes;
+use crate::frameworks::foundation::ns_array;
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+/// Price of every simulated product, in the current locale's currency.
+const SIMULATED_PRICE: f64 = 0.99;
+
+struct SKProductHostObject {
+    /// `NSString*`
+    product_identifier: id,
+}
+impl HostObject for SKProductHostObject {}
+
+struct SKProductsRequestHostObject {
+    /// `NSSet<NSString*>*`
+    product_identifiers: id,
+    /// Something implementing SKProductsRequestDelegate, weak reference
+    delegate: id,
+}
+impl HostObject for SKProductsRequestHostObject {}
+
+struct SKProductsResponseHostObject {
+    /// `NSArray<SKProduct*>*`
+    products: id,
+    /// `NSArray<NSString*>*`
+    invalid_product_identifiers: id,
+}
+impl HostObject for SKProductsResponseHostObject {}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
 @implementation SKProduct: NSObject
-// TODO
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(SKProductHostObject {
+        product_identifier: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let product_identifier = env.objc.borrow::<SKProductHostObject>(this).product_identifier;
+    release(env, product_identifier);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)productIdentifier {
+    env.objc.borrow::<SKProductHostObject>(this).product_identifier
+}
+// The real title and description are unknown, so the identifier is used.
+- (id)localizedTitle {
+    env.objc.borrow::<SKProductHostObject>(this).product_identifier
+}
+- (id)localizedDescription {
+    env.objc.borrow::<SKProductHostObject>(this).product_identifier
+}
+- (id)price {
+    // TODO: should be an NSDecimalNumber
+    msg_class![env; NSNumber numberWithDouble:SIMULATED_PRICE]
+}
+- (id)priceLocale {
+    msg_class![env; NSLocale currentLocale]
+}
+
+@end
+
+@implementation SKRequest: NSObject
+@end
+
+@implementation SKProductsRequest: SKRequest
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(SKProductsRequestHostObject {
+        product_identifiers: nil,
+        delegate: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithProductIdentifiers:(id)product_identifiers { // NSSet<NSString*>*
+    let product_identifiers: id = msg![env; product_identifiers copy];
+    env.objc.borrow_mut::<SKProductsRequestHostObject>(this).product_identifiers = product_identifiers;
+    this
+}
+
+- (())dealloc {
+    let product_identifiers = env.objc.borrow::<SKProductsRequestHostObject>(this).product_identifiers;
+    release(env, product_identifiers);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)delegate {
+    env.objc.borrow::<SKProductsRequestHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // id<SKProductsRequestDelegate>
+    env.objc.borrow_mut::<SKProductsRequestHostObject>(this).delegate = delegate;
+}
+
+- (())start {
+    let &SKProductsRequestHostObject {
+        product_identifiers,
+        delegate,
+    } = env.objc.borrow(this);
+
+    let identifiers: id = msg![env; product_identifiers allObjects];
+    let count: u32 = msg![env; identifiers count];
+    let products = (0..count).map(|i| {
+        let identifier: id = msg![env; identifiers objectAtIndex:i];
+        let identifier = to_rust_string(env, identifier).into_owned();
+        new_product(env, identifier)
+    }).collect();
+    let products = ns_array::from_vec(env, products);
+    let invalid_product_identifiers = ns_array::from_vec(env, Vec::new());
+
+    let response: id = msg_class![env; SKProductsResponse alloc];
+    *env.objc.borrow_mut(response) = SKProductsResponseHostObject {
+        products,
+        invalid_product_identifiers,
+    };
+    autorelease(env, response);
+
+    // The request must not disappear while the delegate is being called, even
+    // if the delegate releases it.
+    retain(env, this);
+    if delegate != nil {
+        log_dbg!("Sending [{:?} productsRequest:{:?} didReceiveResponse:{:?}]", delegate, this, response);
+        () = msg![env; delegate productsRequest:this didReceiveResponse:response];
+        if env.objc.object_has_method_named(&env.mem, delegate, "requestDidFinish:") {
+            () = msg![env; delegate requestDidFinish:this];
+        }
+    }
+    release(env, this);
+}
+- (())cancel {
+    // Requests complete immediately, so there's nothing to cancel.
+}
+
+@end
+
+@implementation SKProductsResponse: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(SKProductsResponseHostObject {
+        products: nil,
+        invalid_product_identifiers: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &SKProductsResponseHostObject {
+        products,
+        invalid_product_identifiers,
+    } = env.objc.borrow(this);
+    release(env, products);
+    release(env, invalid_product_identifiers);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)products {
+    env.objc.borrow::<SKProductsResponseHostObject>(this).products
+}
+- (id)invalidProductIdentifiers {
+    env.objc.borrow::<SKProductsResponseHostObject>(this).invalid_product_identifiers
+}
+
 @end
 
 };
+
+/// Create a new `SKProduct*` (+1 reference).
+fn new_product(env: &mut Environment, identifier: String) -> id {
+    let product_identifier = from_rust_string(env, identifier);
+    let product: id = msg_class![env; SKProduct alloc];
+    env.objc
+        .borrow_mut::<SKProductHostObject>(product)
+        .product_identifier = product_identifier;
+    product
+}
@@ -0,0 +1,11 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The System Configuration framework.
+//!
+//! touchHLE doesn't check the host's network configuration. Instead, the
+//! connectivity set with the `--network=` option is reported.
+
+pub mod sc_network_reachability;
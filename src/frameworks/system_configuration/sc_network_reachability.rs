@@ -0,0 +1,230 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SCNetworkReachability.h`
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_allocator::CFAllocatorRef;
+use crate::frameworks::core_foundation::cf_run_loop::{CFRunLoopMode, CFRunLoopRef};
+use crate::frameworks::core_foundation::{CFIndex, CFTypeRef};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, SafeRead};
+use crate::objc::{id, msg, msg_class, nil, objc_classes, ClassExports, HostObject};
+use crate::options::NetworkConnectivity;
+use crate::Environment;
+
+type SCNetworkReachabilityRef = CFTypeRef;
+type SCNetworkReachabilityFlags = u32;
+
+const kSCNetworkReachabilityFlagsReachable: SCNetworkReachabilityFlags = 1 << 1;
+const kSCNetworkReachabilityFlagsIsDirect: SCNetworkReachabilityFlags = 1 << 17;
+const kSCNetworkReachabilityFlagsIsWWAN: SCNetworkReachabilityFlags = 1 << 18;
+
+// void (*)(SCNetworkReachabilityRef target, SCNetworkReachabilityFlags flags,
+//          void *info)
+type SCNetworkReachabilityCallBack = GuestFunction;
+
+#[repr(C, packed)]
+struct SCNetworkReachabilityContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain_callback: GuestFunction,
+    release_callback: GuestFunction,
+    copy_desc_callback: GuestFunction,
+}
+unsafe impl SafeRead for SCNetworkReachabilityContext {}
+
+const AF_INET: u8 = 2;
+
+struct SCNetworkReachabilityHostObject {
+    /// Whether the target is a link-local address (169.254.0.0/16), which apps
+    /// use to check for a local (WiFi) network specifically.
+    link_local: bool,
+    callback: Option<(SCNetworkReachabilityCallBack, MutVoidPtr)>,
+    /// `NSTimer*` (weak reference) that will deliver the initial callback,
+    /// if the target is scheduled and the callback hasn't happened yet.
+    pending_timer: id,
+}
+impl HostObject for SCNetworkReachabilityHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// SCNetworkReachability is a CFType-based type, but in our implementation
+// those are just Objective-C types, so we need a class for it, but its name is
+// not visible anywhere.
+@implementation _touchHLE_SCNetworkReachability: NSObject
+
+- (())timerFireMethod:(id)_timer { // NSTimer*
+    let host_object = env
+        .objc
+        .borrow_mut::<SCNetworkReachabilityHostObject>(this);
+    host_object.pending_timer = nil;
+    let Some((callback, info)) = host_object.callback else {
+        return;
+    };
+    let flags = flags_for_target(env, this);
+    () = callback.call_from_host(env, (this, flags, info));
+}
+
+@end
+
+};
+
+fn create_reachability(env: &mut Environment, link_local: bool) -> SCNetworkReachabilityRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_SCNetworkReachability", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(SCNetworkReachabilityHostObject {
+            link_local,
+            callback: None,
+            pending_timer: nil,
+        }),
+        &mut env.mem,
+    )
+}
+
+/// The connectivity never changes while the app is running, so the flags only
+/// depend on the option and the kind of target.
+fn flags_for_target(
+    env: &Environment,
+    target: SCNetworkReachabilityRef,
+) -> SCNetworkReachabilityFlags {
+    let link_local = env
+        .objc
+        .borrow::<SCNetworkReachabilityHostObject>(target)
+        .link_local;
+    match (env.options.network, link_local) {
+        (NetworkConnectivity::WiFi, false) => kSCNetworkReachabilityFlagsReachable,
+        (NetworkConnectivity::WiFi, true) => {
+            kSCNetworkReachabilityFlagsReachable | kSCNetworkReachabilityFlagsIsDirect
+        }
+        (NetworkConnectivity::Cellular, false) => {
+            kSCNetworkReachabilityFlagsReachable | kSCNetworkReachabilityFlagsIsWWAN
+        }
+        (NetworkConnectivity::Cellular, true) | (NetworkConnectivity::None, _) => 0,
+    }
+}
+
+fn SCNetworkReachabilityCreateWithName(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    nodename: ConstPtr<u8>,
+) -> SCNetworkReachabilityRef {
+    log_dbg!(
+        "SCNetworkReachabilityCreateWithName for {:?}",
+        env.mem.cstr_at_utf8(nodename)
+    );
+    create_reachability(env, false)
+}
+
+fn SCNetworkReachabilityCreateWithAddress(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    address: ConstPtr<u8>, // const struct sockaddr *
+) -> SCNetworkReachabilityRef {
+    // struct sockaddr_in starts with the length, the family, and the port,
+    // followed by the address in network byte order.
+    let bytes = env.mem.bytes_at(address, 8);
+    let link_local = bytes[1] == AF_INET && bytes[4..6] == [169, 254];
+    log_dbg!(
+        "SCNetworkReachabilityCreateWithAddress for {:?} (link-local: {})",
+        bytes,
+        link_local
+    );
+    create_reachability(env, link_local)
+}
+
+fn SCNetworkReachabilityGetFlags(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    flags: MutPtr<SCNetworkReachabilityFlags>,
+) -> bool {
+    let value = flags_for_target(env, target);
+    env.mem.write(flags, value);
+    true
+}
+
+fn SCNetworkReachabilitySetCallback(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    callback: SCNetworkReachabilityCallBack,
+    context: ConstPtr<SCNetworkReachabilityContext>,
+) -> bool {
+    let callback = if callback.to_ptr().is_null() {
+        None
+    } else {
+        // TODO: call the context's retain and release callbacks
+        let info = if context.is_null() {
+            MutVoidPtr::null()
+        } else {
+            env.mem.read(context).info
+        };
+        Some((callback, info))
+    };
+    env.objc
+        .borrow_mut::<SCNetworkReachabilityHostObject>(target)
+        .callback = callback;
+    true
+}
+
+/// Scheduling a target makes the callback be called once with the current
+/// flags, as happens on a real device once the status is first determined.
+fn SCNetworkReachabilityScheduleWithRunLoop(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    run_loop: CFRunLoopRef,
+    mode: CFRunLoopMode,
+) -> bool {
+    if env
+        .objc
+        .borrow::<SCNetworkReachabilityHostObject>(target)
+        .pending_timer
+        != nil
+    {
+        return true;
+    }
+    let selector = env.objc.lookup_selector("timerFireMethod:").unwrap();
+    let interval: f64 = 0.0;
+    // The timer retains the target until it fires.
+    let timer: id = msg_class![env; NSTimer timerWithTimeInterval:interval
+                                                           target:target
+                                                         selector:selector
+                                                         userInfo:nil
+                                                          repeats:false];
+    () = msg![env; run_loop addTimer:timer forMode:mode];
+    env.objc
+        .borrow_mut::<SCNetworkReachabilityHostObject>(target)
+        .pending_timer = timer;
+    true
+}
+
+fn SCNetworkReachabilityUnscheduleFromRunLoop(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    _run_loop: CFRunLoopRef,
+    _mode: CFRunLoopMode,
+) -> bool {
+    let host_object = env
+        .objc
+        .borrow_mut::<SCNetworkReachabilityHostObject>(target);
+    let timer = std::mem::replace(&mut host_object.pending_timer, nil);
+    if timer != nil {
+        () = msg![env; timer invalidate];
+    }
+    true
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(SCNetworkReachabilityCreateWithName(_, _)),
+    export_c_func!(SCNetworkReachabilityCreateWithAddress(_, _)),
+    export_c_func!(SCNetworkReachabilityGetFlags(_, _)),
+    export_c_func!(SCNetworkReachabilitySetCallback(_, _, _)),
+    export_c_func!(SCNetworkReachabilityScheduleWithRunLoop(_, _, _)),
+    export_c_func!(SCNetworkReachabilityUnscheduleFromRunLoop(_, _, _)),
+];
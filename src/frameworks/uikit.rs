@@ -9,12 +9,17 @@
 //! likely to use UIKit in very simple and limited ways, so this implementation
 //! will probably take a lot of shortcuts.
 
+use crate::frameworks::core_animation::frame_clock;
+use crate::frameworks::foundation::ns_string;
+use crate::input_recording;
+use crate::objc::{nil, release};
 use crate::{msg, Environment};
 use std::time::Instant;
 
 pub mod ui_accelerometer;
 pub mod ui_activity_indicator_view;
 pub mod ui_application;
+pub mod ui_bezier_path;
 pub mod ui_color;
 pub mod ui_device;
 pub mod ui_event;
@@ -24,8 +29,11 @@ pub mod ui_graphics;
 pub mod ui_image;
 pub mod ui_image_picker_controller;
 pub mod ui_nib;
+pub mod ui_pasteboard;
 pub mod ui_responder;
 pub mod ui_screen;
+pub mod ui_status_bar;
+pub mod ui_tab_bar_item;
 pub mod ui_touch;
 pub mod ui_view;
 pub mod ui_view_controller;
@@ -38,10 +46,34 @@ pub struct State {
     ui_device: ui_device::State,
     ui_font: ui_font::State,
     ui_graphics: ui_graphics::State,
+    ui_pasteboard: ui_pasteboard::State,
     ui_screen: ui_screen::State,
+    ui_status_bar: ui_status_bar::State,
     ui_touch: ui_touch::State,
     pub ui_view: ui_view::State,
     ui_responder: ui_responder::State,
+    input_recording: input_recording::State,
+}
+
+/// For use by `UIApplicationMain`: begin recording or replaying input, if the
+/// options ask for it. Frame numbers in recordings count from here.
+fn start_input_recording(env: &mut Environment) {
+    let frame = frame_clock::frame_count(env);
+    env.framework_state
+        .uikit
+        .input_recording
+        .start(&env.options, frame);
+}
+
+/// Get the real, simulated or replayed accelerometer output, see
+/// [input_recording::State::get_acceleration].
+fn get_acceleration(env: &mut Environment) -> (f32, f32, f32) {
+    let frame = frame_clock::frame_count(env);
+    env.framework_state.uikit.input_recording.get_acceleration(
+        env.window.as_ref().unwrap(),
+        &env.options,
+        frame,
+    )
 }
 
 /// For use by `NSRunLoop`: handles any events that have queued up.
@@ -53,8 +85,14 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
     use crate::window::TextInputEvent;
 
     loop {
-        // NSRunLoop will never call this function in headless mode.
-        let Some(event) = env.window.as_mut().unwrap().pop_event() else {
+        // In headless mode, there can still be events from an input replay.
+        let frame = frame_clock::frame_count(env);
+        let Some(event) = env
+            .framework_state
+            .uikit
+            .input_recording
+            .pop_event(env.window.as_mut(), frame)
+        else {
             break;
         };
 
@@ -89,6 +127,20 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
                 log!("Handling app-will-terminate event.");
                 ui_application::exit(env);
             }
+            Event::FocusLost => {
+                ui_application::focus_lost(env);
+            }
+            Event::FocusGained => {
+                ui_application::set_app_state(env, ui_application::AppState::Active);
+            }
+            Event::Minimized => {
+                ui_application::set_app_state(env, ui_application::AppState::Background);
+            }
+            Event::Restored => {
+                // The window normally has focus again once it's restored, and
+                // the order of the two events varies between platforms.
+                ui_application::set_app_state(env, ui_application::AppState::Active);
+            }
             Event::EnterDebugger => {
                 if env.is_debugging_enabled() {
                     log!("Handling EnterDebugger event: entering debugger.");
@@ -98,21 +150,40 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
                     log!("Ignoring EnterDebugger event: no debugger connected.");
                 }
             }
+            Event::Screenshot => {
+                crate::frameworks::opengles::save_screenshot(env);
+            }
+            Event::ToggleTurbo => {
+                env.throttle.toggle_turbo();
+            }
+            Event::ToggleDebugOverlay => {
+                env.debug_overlay.toggle();
+            }
+            Event::ToggleScalingFilter => {
+                if let Some(window) = env.window.as_mut() {
+                    window.toggle_scaling_filter();
+                }
+            }
             Event::TextInput(text_event) => {
+                // Host keyboard input goes to the first responder the same
+                // way as software keyboard input, via UIKeyInput.
                 let responder = env.framework_state.uikit.ui_responder.first_responder;
-                let class = msg![env; responder class];
-                let ui_text_field_class = env.objc.get_known_class("UITextField", &mut env.mem);
-                if !responder.is_null() && env.objc.class_is_subclass_of(class, ui_text_field_class)
-                {
+                let sel = env
+                    .objc
+                    .register_host_selector("insertText:".to_string(), &mut env.mem);
+                if responder != nil && msg![env; responder respondsToSelector:sel] {
                     match text_event {
                         TextInputEvent::Text(text) => {
-                            ui_view::ui_control::ui_text_field::handle_text(env, responder, text)
+                            let text = ns_string::from_rust_string(env, text);
+                            () = msg![env; responder insertText:text];
+                            release(env, text);
                         }
                         TextInputEvent::Backspace => {
-                            ui_view::ui_control::ui_text_field::handle_backspace(env, responder)
+                            () = msg![env; responder deleteBackward];
                         }
                         TextInputEvent::Return => {
-                            ui_view::ui_control::ui_text_field::handle_return(env, responder)
+                            let text = ns_string::get_static_str(env, "\n");
+                            () = msg![env; responder insertText:text];
                         }
                     }
                 }
@@ -120,5 +191,9 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
         }
     }
 
+    if env.window.is_none() {
+        // There's no accelerometer in headless mode.
+        return None;
+    }
     ui_accelerometer::handle_accelerometer(env)
 }
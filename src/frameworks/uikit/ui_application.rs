@@ -6,22 +6,132 @@
 //! `UIApplication` and `UIApplicationMain`.
 
 use super::ui_device::*;
-use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::{ns_array, ns_string};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger, NSTimeInterval, NSUInteger};
 use crate::frameworks::uikit::ui_nib::load_main_nib_file;
-use crate::mem::MutPtr;
+use crate::mem::{ConstVoidPtr, Mem, MutPtr};
 use crate::objc::{
-    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
-    NSZonePtr,
+    _Block_copy, _Block_release, autorelease, get_block_invoke, id, msg, msg_class, msg_send, nil,
+    objc_classes, release, retain, ClassExports, HostObject, NSZonePtr, SEL,
 };
 use crate::window::DeviceOrientation;
 use crate::Environment;
+use std::time::{Duration, Instant};
+
+pub const UIApplicationDidFinishLaunchingNotification: &str =
+    "UIApplicationDidFinishLaunchingNotification";
+pub const UIApplicationWillResignActiveNotification: &str =
+    "UIApplicationWillResignActiveNotification";
+pub const UIApplicationDidEnterBackgroundNotification: &str =
+    "UIApplicationDidEnterBackgroundNotification";
+pub const UIApplicationWillEnterForegroundNotification: &str =
+    "UIApplicationWillEnterForegroundNotification";
+pub const UIApplicationDidBecomeActiveNotification: &str =
+    "UIApplicationDidBecomeActiveNotification";
+pub const UIApplicationWillTerminateNotification: &str = "UIApplicationWillTerminateNotification";
+pub const UIApplicationWillChangeStatusBarFrameNotification: &str =
+    "UIApplicationWillChangeStatusBarFrameNotification";
+pub const UIApplicationDidChangeStatusBarFrameNotification: &str =
+    "UIApplicationDidChangeStatusBarFrameNotification";
+pub const UIApplicationStatusBarFrameUserInfoKey: &str = "UIApplicationStatusBarFrameUserInfoKey";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIApplicationDidFinishLaunchingNotification",
+        HostConstant::NSString(UIApplicationDidFinishLaunchingNotification),
+    ),
+    (
+        "_UIApplicationWillResignActiveNotification",
+        HostConstant::NSString(UIApplicationWillResignActiveNotification),
+    ),
+    (
+        "_UIApplicationDidEnterBackgroundNotification",
+        HostConstant::NSString(UIApplicationDidEnterBackgroundNotification),
+    ),
+    (
+        "_UIApplicationWillEnterForegroundNotification",
+        HostConstant::NSString(UIApplicationWillEnterForegroundNotification),
+    ),
+    (
+        "_UIApplicationDidBecomeActiveNotification",
+        HostConstant::NSString(UIApplicationDidBecomeActiveNotification),
+    ),
+    (
+        "_UIApplicationWillTerminateNotification",
+        HostConstant::NSString(UIApplicationWillTerminateNotification),
+    ),
+    (
+        "_UIApplicationWillChangeStatusBarFrameNotification",
+        HostConstant::NSString(UIApplicationWillChangeStatusBarFrameNotification),
+    ),
+    (
+        "_UIApplicationDidChangeStatusBarFrameNotification",
+        HostConstant::NSString(UIApplicationDidChangeStatusBarFrameNotification),
+    ),
+    (
+        "_UIApplicationStatusBarFrameUserInfoKey",
+        HostConstant::NSString(UIApplicationStatusBarFrameUserInfoKey),
+    ),
+    (
+        "_UIBackgroundTaskInvalid",
+        HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+            mem.alloc_and_write(UIBackgroundTaskInvalid)
+                .cast()
+                .cast_const()
+        }),
+    ),
+];
+
+type UIApplicationState = NSInteger;
+const UIApplicationStateActive: UIApplicationState = 0;
+const UIApplicationStateInactive: UIApplicationState = 1;
+const UIApplicationStateBackground: UIApplicationState = 2;
+
+pub type UIStatusBarStyle = NSInteger;
+pub const UIStatusBarStyleDefault: UIStatusBarStyle = 0;
+pub const UIStatusBarStyleBlackTranslucent: UIStatusBarStyle = 1;
+pub const UIStatusBarStyleBlackOpaque: UIStatusBarStyle = 2;
+
+type UIBackgroundTaskIdentifier = NSUInteger;
+const UIBackgroundTaskInvalid: UIBackgroundTaskIdentifier = 0;
+
+/// How long background tasks can keep running once the app has entered the
+/// background. iPhone OS allows up to ten minutes, but there's little reason
+/// for an app to use that much host CPU time while nobody is looking at it.
+const BACKGROUND_TASK_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Height of the status bar in points, or its width in landscape.
+pub const STATUS_BAR_HEIGHT: CGFloat = 20.0;
 
 #[derive(Default)]
 pub struct State {
     /// [UIApplication sharedApplication]
     shared_application: Option<id>,
-    pub(super) status_bar_hidden: bool,
+    status_bar_hidden: bool,
+    status_bar_style: UIStatusBarStyle,
+    app_state: AppState,
+    /// Set once the app stops being active, see [timers_suspended].
+    suspended: bool,
+    /// Identifiers of unfinished background tasks and their expiration
+    /// handlers (copied blocks, may be [nil]).
+    background_tasks: Vec<(UIBackgroundTaskIdentifier, id)>,
+    last_background_task: UIBackgroundTaskIdentifier,
+    /// When the background tasks' grace period ends, if the app is in the
+    /// background.
+    background_task_deadline: Option<Instant>,
+}
+
+/// The app's position in the lifecycle, see [set_app_state].
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub enum AppState {
+    /// The app is launching, or is visible but not receiving events.
+    #[default]
+    Inactive,
+    Active,
+    /// The app isn't visible.
+    Background,
 }
 
 struct UIApplicationHostObject {
@@ -55,6 +165,19 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)init {
     assert!(env.framework_state.uikit.ui_application.shared_application.is_none());
     env.framework_state.uikit.ui_application.shared_application = Some(this);
+
+    let state = &mut env.framework_state.uikit.ui_application;
+    state.status_bar_hidden = env.bundle.status_bar_hidden();
+    state.status_bar_style = match env.bundle.status_bar_style() {
+        None | Some("UIStatusBarStyleDefault") => UIStatusBarStyleDefault,
+        Some("UIStatusBarStyleBlackTranslucent") => UIStatusBarStyleBlackTranslucent,
+        Some("UIStatusBarStyleBlackOpaque") => UIStatusBarStyleBlackOpaque,
+        Some(other) => {
+            log!("Unknown UIStatusBarStyle {:?} in Info.plist, ignoring", other);
+            UIStatusBarStyleDefault
+        }
+    };
+
     this
 }
 
@@ -76,15 +199,42 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
-// TODO: statusBarHidden getter
+- (id)keyWindow {
+    env.framework_state.uikit.ui_view.ui_window.key_window
+}
+
+- (bool)isStatusBarHidden {
+    env.framework_state.uikit.ui_application.status_bar_hidden
+}
 - (())setStatusBarHidden:(bool)hidden {
-    env.framework_state.uikit.ui_application.status_bar_hidden = hidden;
+    set_status_bar_hidden(env, this, hidden);
 }
 - (())setStatusBarHidden:(bool)hidden
                 animated:(bool)_animated {
     // TODO: animation
     msg![env; this setStatusBarHidden:hidden]
 }
+- (())setStatusBarHidden:(bool)hidden
+           withAnimation:(NSInteger)_animation { // UIStatusBarAnimation
+    // TODO: animation
+    msg![env; this setStatusBarHidden:hidden]
+}
+
+- (UIStatusBarStyle)statusBarStyle {
+    env.framework_state.uikit.ui_application.status_bar_style
+}
+- (())setStatusBarStyle:(UIStatusBarStyle)style {
+    env.framework_state.uikit.ui_application.status_bar_style = style;
+}
+- (())setStatusBarStyle:(UIStatusBarStyle)style
+               animated:(bool)_animated {
+    // TODO: animation
+    msg![env; this setStatusBarStyle:style]
+}
+
+- (CGRect)statusBarFrame {
+    status_bar_frame(env)
+}
 
 - (UIInterfaceOrientation)statusBarOrientation {
     match env.window().current_rotation() {
@@ -107,6 +257,50 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this setStatusBarOrientation:orientation]
 }
 
+- (UIApplicationState)applicationState {
+    match env.framework_state.uikit.ui_application.app_state {
+        AppState::Active => UIApplicationStateActive,
+        AppState::Inactive => UIApplicationStateInactive,
+        AppState::Background => UIApplicationStateBackground,
+    }
+}
+
+- (UIBackgroundTaskIdentifier)beginBackgroundTaskWithExpirationHandler:(id)handler { // block
+    let state = &env.framework_state.uikit.ui_application;
+    if state
+        .background_task_deadline
+        .is_some_and(|deadline| deadline <= Instant::now())
+    {
+        log!("App tried to begin a background task after its grace period ended.");
+        return UIBackgroundTaskInvalid;
+    }
+    let handler = _Block_copy(env, handler);
+    let state = &mut env.framework_state.uikit.ui_application;
+    state.last_background_task += 1;
+    let identifier = state.last_background_task;
+    state.background_tasks.push((identifier, handler));
+    log_dbg!("Began background task {}", identifier);
+    identifier
+}
+- (())endBackgroundTask:(UIBackgroundTaskIdentifier)identifier {
+    let tasks = &mut env.framework_state.uikit.ui_application.background_tasks;
+    let Some(index) = tasks.iter().position(|&(task, _)| task == identifier) else {
+        log!("App tried to end unknown background task {}, ignoring.", identifier);
+        return;
+    };
+    let (_, handler) = tasks.remove(index);
+    log_dbg!("Ended background task {}", identifier);
+    _Block_release(env, handler);
+}
+- (NSTimeInterval)backgroundTimeRemaining {
+    // This is documented to be a huge number when the app is in the
+    // foreground.
+    match env.framework_state.uikit.ui_application.background_task_deadline {
+        Some(deadline) => deadline.saturating_duration_since(Instant::now()).as_secs_f64(),
+        None => f64::MAX,
+    }
+}
+
 - (bool)idleTimerDisabled {
     !env.window().is_screen_saver_enabled()
 }
@@ -114,7 +308,38 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.window_mut().set_screen_saver_enabled(!disabled);
 }
 
+- (bool)canOpenURL:(id)url { // NSURL
+    match url_scheme(env, url) {
+        Some(scheme) => {
+            HOST_URL_SCHEMES.contains(&scheme.as_str()) || app_handles_url_scheme(env, &scheme)
+        },
+        None => false,
+    }
+}
+
 - (bool)openURL:(id)url { // NSURL
+    let Some(scheme) = url_scheme(env, url) else {
+        log!("App tried to open URL {:?} without a scheme, ignoring.", url);
+        return false;
+    };
+
+    if app_handles_url_scheme(env, &scheme) {
+        // On a real device, the app would be terminated and then relaunched
+        // with the URL. Delivering the URL straight to the running app is much
+        // less disruptive and, from the app's point of view, close enough.
+        let delegate: id = msg![env; this delegate];
+        if !env.objc.object_has_method_named(&env.mem, delegate, "application:handleOpenURL:") {
+            log!("App tried to open URL {:?} with its own scheme, but its delegate doesn't implement application:handleOpenURL:, ignoring.", url);
+            return false;
+        }
+        return msg![env; delegate application:this handleOpenURL:url];
+    }
+
+    if !HOST_URL_SCHEMES.contains(&scheme.as_str()) {
+        log!("App tried to open URL {:?} with unsupported scheme {:?}, ignoring.", url, scheme);
+        return false;
+    }
+
     let ns_string = msg![env; url absoluteString];
     let url_string = ns_string::to_rust_string(env, ns_string);
     if let Err(e) = crate::window::open_url(&url_string) {
@@ -142,6 +367,74 @@ pub const CLASSES: ClassExports = objc_classes! {
     log!("TODO: ignoring endIgnoringInteractionEvents");
 }
 
+- (bool)sendAction:(SEL)action
+                to:(id)target
+              from:(id)sender
+          forEvent:(id)event { // UIEvent*
+    let target = if target != nil {
+        target
+    } else {
+        // Send the action up the responder chain, starting with the first
+        // responder.
+        let first_responder = env.framework_state.uikit.ui_responder.first_responder;
+        let start = if first_responder != nil { first_responder } else { sender };
+        let target: id = if start != nil {
+            msg![env; start targetForAction:action withSender:sender]
+        } else {
+            nil
+        };
+        if target == nil {
+            log!(
+                "Warning: no responder handled action {:?} from {:?}",
+                action.as_str(&env.mem),
+                sender,
+            );
+            return false;
+        }
+        target
+    };
+
+    let sel_str = action.as_str(&env.mem);
+    let colon_count = sel_str.bytes().filter(|&b| b == b':').count();
+    match colon_count {
+        // - (IBAction)action;
+        0 => {
+            log_dbg!(
+                "Sending {:?} ({:?}) message to {:?} (no args)",
+                action,
+                sel_str,
+                target
+            );
+            () = msg_send(env, (target, action));
+        }
+        // - (IBAction)action:(id)sender;
+        1 => {
+            log_dbg!(
+                "Sending {:?} ({:?}) message to {:?} (one arg: {:?})",
+                action,
+                sel_str,
+                target,
+                sender
+            );
+            () = msg_send(env, (target, action, sender));
+        }
+        // - (IBAction)action:(id)sender forEvent:(UIEvent*)event;
+        2 => {
+            log_dbg!(
+                "Sending {:?} ({:?}) message to {:?} (two args: {:?}, {:?})",
+                action,
+                sel_str,
+                target,
+                sender,
+                event
+            );
+            () = msg_send(env, (target, action, sender, event));
+        }
+        _ => panic!(),
+    };
+    true
+}
+
 - (id)windows {
     log!("TODO: UIApplication's windows getter is returning only visible windows");
     let visible_windows: Vec<id> = (*env
@@ -161,6 +454,25 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// URL schemes that `openURL:` can hand over to the host OS.
+const HOST_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Get the lowercase scheme of an `NSURL`, if it has one.
+fn url_scheme(env: &mut Environment, url: id) -> Option<String> {
+    let scheme: id = msg![env; url scheme];
+    if scheme == nil {
+        return None;
+    }
+    Some(ns_string::to_rust_string(env, scheme).to_lowercase())
+}
+
+/// Check whether the app registered a URL scheme in its `Info.plist`.
+fn app_handles_url_scheme(env: &Environment, scheme: &str) -> bool {
+    env.bundle
+        .url_schemes()
+        .any(|app_scheme| app_scheme.eq_ignore_ascii_case(scheme))
+}
+
 /// `UIApplicationMain`, the entry point of the application.
 ///
 /// This function should never return.
@@ -171,6 +483,8 @@ pub(super) fn UIApplicationMain(
     principal_class_name: id, // NSString*
     delegate_class_name: id,  // NSString*
 ) {
+    super::start_input_recording(env);
+
     // UIKit creates and drains autorelease pools when handling events.
     // It's not clear what granularity this should happen with, but this
     // granularity has already caught several bugs. :)
@@ -178,12 +492,18 @@ pub(super) fn UIApplicationMain(
     let ui_application = {
         let pool: id = msg_class![env; NSAutoreleasePool new];
 
-        let principal_class = if principal_class_name != nil {
-            let name = ns_string::to_rust_string(env, principal_class_name);
-            env.objc.get_known_class(&name, &mut env.mem)
+        // If no name is passed, the Info.plist can specify one instead.
+        let principal_class_name = if principal_class_name != nil {
+            ns_string::to_rust_string(env, principal_class_name).into_owned()
         } else {
-            env.objc.get_known_class("UIApplication", &mut env.mem)
+            env.bundle
+                .principal_class()
+                .unwrap_or("UIApplication")
+                .to_string()
         };
+        let principal_class = env
+            .objc
+            .get_known_class(&principal_class_name, &mut env.mem);
         let ui_application: id = msg![env; principal_class new];
 
         load_main_nib_file(env, ui_application);
@@ -197,14 +517,18 @@ pub(super) fn UIApplicationMain(
                 .borrow_mut::<UIApplicationHostObject>(ui_application)
                 .delegate_is_retained = true;
             retain(env, delegate);
-        } else {
+        } else if delegate_class_name != nil {
             // We have to construct the delegate.
-            assert!(delegate_class_name != nil);
             let name = ns_string::to_rust_string(env, delegate_class_name);
             let class = env.objc.get_known_class(&name, &mut env.mem);
             let delegate: id = msg![env; class new];
             let _: () = msg![env; ui_application setDelegate:delegate];
             assert!(delegate != nil);
+        } else {
+            // This is allowed, though the app probably won't do much.
+            log!(
+                "Warning: app has no main nib file or delegate class, running without a delegate."
+            );
         };
         // We can't hang on to the delegate, the guest app may change it at any
         // time.
@@ -219,7 +543,9 @@ pub(super) fn UIApplicationMain(
         let delegate: id = msg![env; ui_application delegate];
         // iOS 3+ apps usually use application:didFinishLaunchingWithOptions:,
         // and it seems to be prioritized over applicationDidFinishLaunching:.
-        if env.objc.object_has_method_named(
+        if delegate == nil {
+            // Nothing to tell.
+        } else if env.objc.object_has_method_named(
             &env.mem,
             delegate,
             "application:didFinishLaunchingWithOptions:",
@@ -233,33 +559,28 @@ pub(super) fn UIApplicationMain(
         ) {
             () = msg![env; delegate applicationDidFinishLaunching:ui_application];
         }
+        post_notification(
+            env,
+            ui_application,
+            UIApplicationDidFinishLaunchingNotification,
+        );
 
         let _: () = msg![env; pool drain];
     }
 
-    // Call layoutSubviews on all views in the view hierarchy.
+    // Lay out all views in the view hierarchy, even those not in a window.
     // See https://medium.com/geekculture/uiview-lifecycle-part-5-faa2d44511c9
     let views = env.framework_state.uikit.ui_view.views.clone();
     for view in views {
-        () = msg![env; view layoutSubviews];
+        let superview: id = msg![env; view superview];
+        if superview == nil {
+            () = msg![env; view layoutIfNeeded];
+        }
     }
 
     // Send applicationDidBecomeActive now that the application is ready to
     // become active.
-    {
-        let pool: id = msg_class![env; NSAutoreleasePool new];
-        let delegate: id = msg![env; ui_application delegate];
-        if env
-            .objc
-            .object_has_method_named(&env.mem, delegate, "applicationDidBecomeActive:")
-        {
-            () = msg![env; delegate applicationDidBecomeActive:ui_application];
-        }
-        let _: () = msg![env; pool drain];
-    }
-
-    // FIXME: There are more messages we should send.
-    // TODO: Send UIApplicationDidFinishLaunchingNotification?
+    set_app_state(env, AppState::Active);
 
     // TODO: It might be nicer to return from this function (even though it's
     // conceptually noreturn) and set some global flag that changes how the
@@ -270,37 +591,285 @@ pub(super) fn UIApplicationMain(
     let _: () = msg![env; run_loop run];
 }
 
-/// Tell the app it's about to quit and then exit.
-pub(super) fn exit(env: &mut Environment) {
+/// Move the app to a new lifecycle state, sending the appropriate messages to
+/// the app delegate and posting the matching notifications. Going between
+/// [AppState::Active] and [AppState::Background] passes through
+/// [AppState::Inactive], like on a real device.
+pub(super) fn set_app_state(env: &mut Environment, new_state: AppState) {
     let ui_application: id = msg_class![env; UIApplication sharedApplication];
+    if ui_application == nil {
+        // The app hasn't got as far as UIApplicationMain yet.
+        return;
+    }
+
+    loop {
+        let old_state = env.framework_state.uikit.ui_application.app_state;
+        if old_state == new_state {
+            break;
+        }
+        let (next_state, selector, notification) = match (old_state, new_state) {
+            (AppState::Active, _) => (
+                AppState::Inactive,
+                "applicationWillResignActive:",
+                UIApplicationWillResignActiveNotification,
+            ),
+            (AppState::Inactive, AppState::Active) => (
+                AppState::Active,
+                "applicationDidBecomeActive:",
+                UIApplicationDidBecomeActiveNotification,
+            ),
+            (AppState::Inactive, AppState::Background) => (
+                AppState::Background,
+                "applicationDidEnterBackground:",
+                UIApplicationDidEnterBackgroundNotification,
+            ),
+            (AppState::Background, _) => (
+                AppState::Inactive,
+                "applicationWillEnterForeground:",
+                UIApplicationWillEnterForegroundNotification,
+            ),
+            (AppState::Inactive, AppState::Inactive) => unreachable!(),
+        };
+        log_dbg!("App state change: {:?} => {:?}", old_state, next_state);
+        let state = &mut env.framework_state.uikit.ui_application;
+        state.app_state = next_state;
+        // Timers are suspended before `applicationWillResignActive:` is sent
+        // and resumed before `applicationDidBecomeActive:` is sent.
+        state.suspended = next_state != AppState::Active;
+        state.background_task_deadline = (next_state == AppState::Background)
+            .then(|| Instant::now() + BACKGROUND_TASK_GRACE_PERIOD);
+        send_lifecycle_message(env, ui_application, selector, notification);
+    }
+}
 
-    // TODO: send notifications also
+/// Whether timers should stop firing because the app has resigned active or
+/// entered the background, like it would be suspended on a real device. This
+/// stops games from using host CPU time while unfocused. Unfinished background
+/// tasks keep the app running until they end or expire.
+pub fn timers_suspended(env: &Environment) -> bool {
+    let state = &env.framework_state.uikit.ui_application;
+    state.suspended && state.background_tasks.is_empty()
+}
 
-    {
-        let pool: id = msg_class![env; NSAutoreleasePool new];
-        let delegate: id = msg![env; ui_application delegate];
-        if env
-            .objc
-            .object_has_method_named(&env.mem, delegate, "applicationWillResignActive:")
-        {
-            () = msg![env; delegate applicationWillResignActive:ui_application];
+/// For use by `NSRunLoop`: call the expiration handlers of background tasks
+/// whose grace period is over.
+///
+/// Returns the time the grace period ends, if it hasn't yet.
+pub fn handle_background_tasks(env: &mut Environment) -> Option<Instant> {
+    let state = &env.framework_state.uikit.ui_application;
+    if state.background_tasks.is_empty() {
+        return None;
+    }
+    let deadline = state.background_task_deadline?;
+    if deadline > Instant::now() {
+        return Some(deadline);
+    }
+
+    log!("Background task grace period is over, calling expiration handlers.");
+    let tasks = state.background_tasks.clone();
+    for (_, handler) in tasks {
+        if handler == nil {
+            continue;
         }
-        let _: () = msg![env; pool drain];
-    };
+        // The handler should end its task, which releases the handler, so it
+        // must be kept alive until it returns.
+        let handler = _Block_copy(env, handler);
+        let invoke = get_block_invoke(&env.mem, handler);
+        () = invoke.call_from_host(env, (handler,));
+        _Block_release(env, handler);
+    }
 
-    {
-        let pool: id = msg_class![env; NSAutoreleasePool new];
-        let delegate: id = msg![env; ui_application delegate];
-        if env
+    // A real device would kill an app that doesn't end its tasks in time.
+    let tasks = std::mem::take(&mut env.framework_state.uikit.ui_application.background_tasks);
+    for (identifier, handler) in tasks {
+        log!(
+            "Warning: background task {} wasn't ended in time, ending it.",
+            identifier
+        );
+        _Block_release(env, handler);
+    }
+    None
+}
+
+/// Send a message like `applicationDidBecomeActive:` to the app delegate, if it
+/// implements it, then post the corresponding notification.
+fn send_lifecycle_message(
+    env: &mut Environment,
+    ui_application: id,
+    selector: &str,
+    notification: &'static str,
+) {
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let delegate: id = msg![env; ui_application delegate];
+    if delegate != nil
+        && env
             .objc
-            .object_has_method_named(&env.mem, delegate, "applicationWillTerminate:")
-        {
-            () = msg![env; delegate applicationWillTerminate:ui_application];
-        }
-        let _: () = msg![env; pool drain];
+            .object_has_method_named(&env.mem, delegate, selector)
+    {
+        let selector = env.objc.lookup_selector(selector).unwrap();
+        () = msg_send(env, (delegate, selector, ui_application));
+    }
+    post_notification(env, ui_application, notification);
+    let _: () = msg![env; pool drain];
+}
+
+/// The status bar's frame in screen co-ordinates, or an empty rectangle if it
+/// is hidden.
+pub fn status_bar_frame(env: &mut Environment) -> CGRect {
+    if env.framework_state.uikit.ui_application.status_bar_hidden {
+        return CGRect::default();
+    }
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let bounds: CGRect = msg![env; screen bounds];
+    // The status bar is always at the top of the interface, so in landscape
+    // it's along one of the long sides of the screen.
+    let rotation = env
+        .window
+        .as_ref()
+        .map_or(DeviceOrientation::Portrait, |window| {
+            window.current_rotation()
+        });
+    let (origin, size) = match rotation {
+        DeviceOrientation::Portrait => (
+            bounds.origin,
+            CGSize {
+                width: bounds.size.width,
+                height: STATUS_BAR_HEIGHT,
+            },
+        ),
+        DeviceOrientation::LandscapeLeft => (
+            CGPoint {
+                x: bounds.origin.x + bounds.size.width - STATUS_BAR_HEIGHT,
+                y: bounds.origin.y,
+            },
+            CGSize {
+                width: STATUS_BAR_HEIGHT,
+                height: bounds.size.height,
+            },
+        ),
+        DeviceOrientation::LandscapeRight => (
+            bounds.origin,
+            CGSize {
+                width: STATUS_BAR_HEIGHT,
+                height: bounds.size.height,
+            },
+        ),
     };
+    CGRect { origin, size }
+}
+
+/// The status bar's style, if it is visible. Used for drawing it.
+pub fn visible_status_bar_style(env: &Environment) -> Option<UIStatusBarStyle> {
+    let state = &env.framework_state.uikit.ui_application;
+    (!state.status_bar_hidden).then_some(state.status_bar_style)
+}
+
+fn set_status_bar_hidden(env: &mut Environment, ui_application: id, hidden: bool) {
+    if env.framework_state.uikit.ui_application.status_bar_hidden == hidden {
+        return;
+    }
+
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let old_status_bar_frame = status_bar_frame(env);
+    let old_application_frame: CGRect = msg![env; screen applicationFrame];
+
+    // The "will change" notification has the new frame, and the "did change"
+    // notification has the old one.
+    env.framework_state.uikit.ui_application.status_bar_hidden = hidden;
+    let new_status_bar_frame = status_bar_frame(env);
+    env.framework_state.uikit.ui_application.status_bar_hidden = !hidden;
+    post_status_bar_frame_notification(
+        env,
+        ui_application,
+        UIApplicationWillChangeStatusBarFrameNotification,
+        new_status_bar_frame,
+    );
+
+    env.framework_state.uikit.ui_application.status_bar_hidden = hidden;
+    let new_application_frame: CGRect = msg![env; screen applicationFrame];
+
+    // Views that fill the application frame, like view controllers' views
+    // added to a window, are resized to fill the new application frame.
+    let windows = env
+        .framework_state
+        .uikit
+        .ui_view
+        .ui_window
+        .visible_windows
+        .clone();
+    for window in windows {
+        let subviews: id = msg![env; window subviews];
+        let count: NSUInteger = msg![env; subviews count];
+        for i in 0..count {
+            let view: id = msg![env; subviews objectAtIndex:i];
+            let frame: CGRect = msg![env; view frame];
+            if frame == old_application_frame {
+                () = msg![env; view setFrame:new_application_frame];
+            }
+        }
+    }
+
+    post_status_bar_frame_notification(
+        env,
+        ui_application,
+        UIApplicationDidChangeStatusBarFrameNotification,
+        old_status_bar_frame,
+    );
+}
+
+fn post_status_bar_frame_notification(
+    env: &mut Environment,
+    ui_application: id,
+    name: &'static str,
+    frame: CGRect,
+) {
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let name = ns_string::get_static_str(env, name);
+    let key = ns_string::get_static_str(env, UIApplicationStatusBarFrameUserInfoKey);
+    let value: id = msg_class![env; NSValue valueWithCGRect:frame];
+    let user_info = dict_from_keys_and_objects(env, &[(key, value)]);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    () = msg![env; center postNotificationName:name
+                                        object:ui_application
+                                      userInfo:user_info];
+    release(env, user_info);
+    () = msg![env; pool drain];
+}
+
+fn post_notification(env: &mut Environment, ui_application: id, name: &'static str) {
+    let name = ns_string::get_static_str(env, name);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    () = msg![env; center postNotificationName:name object:ui_application];
+}
+
+/// For use by [super::handle_events]: the window lost focus. Focus is also lost
+/// when the window is minimized, and depending on the platform that can come
+/// after [AppState::Background] was entered, so this only affects an active
+/// app.
+pub(super) fn focus_lost(env: &mut Environment) {
+    if env.framework_state.uikit.ui_application.app_state == AppState::Active {
+        set_app_state(env, AppState::Inactive);
+    }
+}
+
+/// Tell the app it's about to quit and then exit.
+pub(super) fn exit(env: &mut Environment) {
+    let ui_application: id = msg_class![env; UIApplication sharedApplication];
+
+    // Apps for iPhone OS versions before 4.0 don't expect to be backgrounded,
+    // they only expect to resign active before terminating.
+    if env.framework_state.uikit.ui_application.app_state == AppState::Active {
+        set_app_state(env, AppState::Inactive);
+    }
+
+    send_lifecycle_message(
+        env,
+        ui_application,
+        "applicationWillTerminate:",
+        UIApplicationWillTerminateNotification,
+    );
 
-    std::process::exit(0);
+    env.exit(0);
 }
 
 pub const FUNCTIONS: FunctionExports = &[export_c_func!(UIApplicationMain(_, _, _, _))];
@@ -0,0 +1,160 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIBezierPath`.
+//!
+//! Drawing is done by replaying the path into the current `CGContext`.
+
+use super::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::frameworks::core_graphics::cg_context::{
+    self, CGContextBeginPath, CGContextFillPath, CGContextRef, CGContextRestoreGState,
+    CGContextSaveGState, CGContextSetLineWidth, CGContextStrokePath,
+};
+use crate::frameworks::core_graphics::cg_path::{self, PathElement};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::objc::{autorelease, id, msg, nil, objc_classes, ClassExports, HostObject, NSZonePtr};
+use crate::Environment;
+
+struct UIBezierPathHostObject {
+    elements: Vec<PathElement>,
+    line_width: CGFloat,
+}
+impl HostObject for UIBezierPathHostObject {}
+
+fn add_element(env: &mut Environment, this: id, element: PathElement) {
+    let host_obj = env.objc.borrow_mut::<UIBezierPathHostObject>(this);
+    if !matches!(element, PathElement::MoveTo(_)) && host_obj.elements.is_empty() {
+        log!(
+            "Warning: UIBezierPath {:?} has no current point, ignoring element",
+            this
+        );
+        return;
+    }
+    host_obj.elements.push(element);
+}
+
+/// Append a rectangle, or an ellipse inscribed in it, to a path. Curves are
+/// used for the corners if `radius` is not zero.
+fn add_rounded_rect(env: &mut Environment, this: id, rect: CGRect, radius: CGFloat) {
+    for element in cg_path::rounded_rect(rect, radius) {
+        add_element(env, this, element);
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIBezierPath: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(UIBezierPathHostObject {
+        elements: Vec::new(),
+        line_width: 1.0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)bezierPath {
+    let new: id = msg![env; this new];
+    autorelease(env, new)
+}
+
++ (id)bezierPathWithRect:(CGRect)rect {
+    let new: id = msg![env; this bezierPath];
+    add_rounded_rect(env, new, rect, 0.0);
+    new
+}
+
++ (id)bezierPathWithOvalInRect:(CGRect)rect {
+    let new: id = msg![env; this bezierPath];
+    let radius = rect.size.width.max(rect.size.height) / 2.0;
+    add_rounded_rect(env, new, rect, radius);
+    new
+}
+
++ (id)bezierPathWithRoundedRect:(CGRect)rect
+                   cornerRadius:(CGFloat)radius {
+    let new: id = msg![env; this bezierPath];
+    add_rounded_rect(env, new, rect, radius);
+    new
+}
+
+- (())moveToPoint:(CGPoint)point {
+    add_element(env, this, PathElement::MoveTo(point));
+}
+- (())addLineToPoint:(CGPoint)point {
+    add_element(env, this, PathElement::LineTo(point));
+}
+- (())addCurveToPoint:(CGPoint)to
+        controlPoint1:(CGPoint)control_point_1
+        controlPoint2:(CGPoint)control_point_2 {
+    add_element(env, this, PathElement::CurveTo {
+        control_point_1,
+        control_point_2,
+        to,
+    });
+}
+- (())closePath {
+    add_element(env, this, PathElement::Close);
+}
+- (())removeAllPoints {
+    env.objc.borrow_mut::<UIBezierPathHostObject>(this).elements.clear();
+}
+
+- (bool)isEmpty {
+    env.objc.borrow::<UIBezierPathHostObject>(this).elements.is_empty()
+}
+
+- (CGFloat)lineWidth {
+    env.objc.borrow::<UIBezierPathHostObject>(this).line_width
+}
+- (())setLineWidth:(CGFloat)line_width {
+    env.objc.borrow_mut::<UIBezierPathHostObject>(this).line_width = line_width;
+}
+
+- (bool)containsPoint:(CGPoint)point {
+    let polygons = cg_path::to_polygons(&env.objc.borrow::<UIBezierPathHostObject>(this).elements);
+    cg_context::path_contains_point(&polygons, point)
+}
+
+- (())fill {
+    let context = UIGraphicsGetCurrentContext(env);
+    if context == nil {
+        log!("Warning: [UIBezierPath fill] without a current context, ignoring");
+        return;
+    }
+    add_path_to_context(env, this, context);
+    CGContextFillPath(env, context);
+}
+
+- (())stroke {
+    let context = UIGraphicsGetCurrentContext(env);
+    if context == nil {
+        log!("Warning: [UIBezierPath stroke] without a current context, ignoring");
+        return;
+    }
+    let line_width = env.objc.borrow::<UIBezierPathHostObject>(this).line_width;
+    CGContextSaveGState(env, context);
+    CGContextSetLineWidth(env, context, line_width);
+    add_path_to_context(env, this, context);
+    CGContextStrokePath(env, context);
+    CGContextRestoreGState(env, context);
+}
+
+@end
+
+};
+
+/// Replace the current path of a `CGContext` with a `UIBezierPath`.
+fn add_path_to_context(env: &mut Environment, this: id, context: CGContextRef) {
+    CGContextBeginPath(env, context);
+    let elements = env
+        .objc
+        .borrow::<UIBezierPathHostObject>(this)
+        .elements
+        .clone();
+    cg_context::add_path_elements(env, context, &elements);
+}
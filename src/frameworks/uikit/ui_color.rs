@@ -5,10 +5,12 @@
  */
 //! `UIColor`.
 
+use crate::frameworks::core_graphics::cg_color::{self, CGColorRef, CGColorRelease};
 use crate::frameworks::core_graphics::CGFloat;
 use crate::mem::MutPtr;
 use crate::objc::{
-    autorelease, id, msg, msg_class, objc_classes, ClassExports, HostObject, NSZonePtr, ObjC, SEL,
+    autorelease, id, msg, msg_class, nil, objc_classes, ClassExports, HostObject, NSZonePtr, ObjC,
+    SEL,
 };
 use crate::Environment;
 use std::collections::HashMap;
@@ -42,6 +44,8 @@ fn get_standard_color(
 
 struct UIColorHostObject {
     rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// Created on demand by `CGColor`, owned by this object.
+    cg_color: CGColorRef,
 }
 impl HostObject for UIColorHostObject {}
 
@@ -54,6 +58,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(UIColorHostObject {
         rgba: (0.0, 0.0, 0.0, 0.0),
+        cg_color: nil,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -73,6 +78,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)colorWithCGColor:(CGColorRef)cg_color {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCGColor:cg_color];
+    autorelease(env, new)
+}
+
 + (id)clearColor    { get_standard_color(env, _cmd, 0.0, 0.0, 0.0, 0.0) }
 + (id)blackColor    { get_standard_color(env, _cmd, 0.0, 0.0, 0.0, 1.0) }
 + (id)whiteColor    { get_standard_color(env, _cmd, 1.0, 1.0, 1.0, 1.0) }
@@ -114,6 +125,29 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithCGColor:(CGColorRef)cg_color {
+    let rgba = cg_color::get_rgba(&env.objc, &env.mem, cg_color);
+    env.objc.borrow_mut::<UIColorHostObject>(this).rgba = rgba;
+    this
+}
+
+- (())dealloc {
+    let cg_color = env.objc.borrow::<UIColorHostObject>(this).cg_color;
+    CGColorRelease(env, cg_color);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (CGColorRef)CGColor {
+    let existing = env.objc.borrow::<UIColorHostObject>(this).cg_color;
+    if existing != nil {
+        return existing;
+    }
+    let rgba = env.objc.borrow::<UIColorHostObject>(this).rgba;
+    let new = cg_color::from_rgba(env, rgba);
+    env.objc.borrow_mut::<UIColorHostObject>(this).cg_color = new;
+    new
+}
+
 - (bool)getRed:(MutPtr<CGFloat>)r
          green:(MutPtr<CGFloat>)g
           blue:(MutPtr<CGFloat>)b
@@ -4,13 +4,19 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIFont`.
+//!
+//! The system font is Helvetica on iPhone OS, which is substituted with the
+//! fonts in `touchHLE_fonts`. Apps can also provide their own fonts, either
+//! with `UIAppFonts` in `Info.plist`, or by registering them with
+//! `CTFontManagerRegisterFontsForURL`.
 
 use super::ui_graphics::UIGraphicsGetCurrentContext;
 use crate::font::{Font, TextAlignment, WrapMode};
 use crate::frameworks::core_graphics::cg_bitmap_context::CGBitmapContextDrawer;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
-use crate::frameworks::foundation::NSInteger;
-use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject};
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger};
+use crate::fs::GuestPath;
+use crate::objc::{autorelease, id, msg, objc_classes, Class, ClassExports, HostObject};
 use crate::Environment;
 use std::ops::Range;
 
@@ -21,6 +27,10 @@ pub(super) struct State {
     italic: Option<Font>,
     regular_ja: Option<Font>,
     bold_ja: Option<Font>,
+    /// Fonts provided by the app.
+    custom: Vec<Font>,
+    /// Whether the fonts listed in `UIAppFonts` have been loaded yet.
+    app_fonts_loaded: bool,
 }
 
 #[derive(Copy, Clone)]
@@ -28,8 +38,13 @@ enum FontKind {
     Regular,
     Bold,
     Italic,
+    /// Index into [State::custom].
+    Custom(usize),
 }
 
+/// The name of the system font family, which the built-in fonts stand in for.
+const SYSTEM_FAMILY_NAME: &str = "Helvetica";
+
 struct UIFontHostObject {
     size: CGFloat,
     kind: FontKind,
@@ -103,10 +118,200 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)fontWithName:(id)name // NSString*
+              size:(CGFloat)size {
+    let name = ns_string::to_rust_string(env, name);
+    match find_font(env, &name) {
+        FontKind::Regular => msg![env; this systemFontOfSize:size],
+        FontKind::Bold => msg![env; this boldSystemFontOfSize:size],
+        FontKind::Italic => msg![env; this italicSystemFontOfSize:size],
+        kind @ FontKind::Custom(_) => {
+            let host_object = UIFontHostObject { size, kind };
+            let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
+            autorelease(env, new)
+        }
+    }
+}
+
++ (id)familyNames {
+    load_app_fonts(env);
+    let mut family_names = vec![SYSTEM_FAMILY_NAME.to_string()];
+    for font in &env.framework_state.uikit.ui_font.custom {
+        if let Some(family_name) = font.family_name() {
+            if !family_names.iter().any(|name| name == family_name) {
+                family_names.push(family_name.to_string());
+            }
+        }
+    }
+    let family_names = family_names
+        .into_iter()
+        .map(|name| ns_string::from_rust_string(env, name))
+        .collect();
+    let family_names = ns_array::from_vec(env, family_names);
+    autorelease(env, family_names)
+}
+
++ (id)fontNamesForFamilyName:(id)family_name { // NSString*
+    load_app_fonts(env);
+    let family_name = ns_string::to_rust_string(env, family_name);
+    let font_names: Vec<String> = if family_name == SYSTEM_FAMILY_NAME {
+        [FontKind::Regular, FontKind::Bold, FontKind::Italic]
+            .into_iter()
+            .map(|kind| system_font_name(kind).to_string())
+            .collect()
+    } else {
+        env.framework_state
+            .uikit
+            .ui_font
+            .custom
+            .iter()
+            .filter(|font| font.family_name() == Some(&*family_name))
+            .filter_map(|font| font.postscript_name())
+            .map(|name| name.to_string())
+            .collect()
+    };
+    let font_names = font_names
+        .into_iter()
+        .map(|name| ns_string::from_rust_string(env, name))
+        .collect();
+    let font_names = ns_array::from_vec(env, font_names);
+    autorelease(env, font_names)
+}
+
+- (id)fontWithSize:(CGFloat)size {
+    let kind = env.objc.borrow::<UIFontHostObject>(this).kind;
+    let host_object = UIFontHostObject { size, kind };
+    let class: Class = msg![env; this class];
+    let new = env.objc.alloc_object(class, Box::new(host_object), &mut env.mem);
+    autorelease(env, new)
+}
+
+- (CGFloat)pointSize {
+    env.objc.borrow::<UIFontHostObject>(this).size
+}
+
+- (id)familyName {
+    let kind = env.objc.borrow::<UIFontHostObject>(this).kind;
+    let FontKind::Custom(index) = kind else {
+        return ns_string::get_static_str(env, SYSTEM_FAMILY_NAME);
+    };
+    let font = &env.framework_state.uikit.ui_font.custom[index];
+    let family_name = font.family_name().unwrap_or_default().to_string();
+    let family_name = ns_string::from_rust_string(env, family_name);
+    autorelease(env, family_name)
+}
+
+- (id)fontName {
+    let kind = env.objc.borrow::<UIFontHostObject>(this).kind;
+    let FontKind::Custom(index) = kind else {
+        return ns_string::get_static_str(env, system_font_name(kind));
+    };
+    let font = &env.framework_state.uikit.ui_font.custom[index];
+    let font_name = font.postscript_name().unwrap_or_default().to_string();
+    let font_name = ns_string::from_rust_string(env, font_name);
+    autorelease(env, font_name)
+}
+
 @end
 
 };
 
+fn system_font_name(kind: FontKind) -> &'static str {
+    match kind {
+        FontKind::Regular => "Helvetica",
+        FontKind::Bold => "Helvetica-Bold",
+        FontKind::Italic => "Helvetica-Oblique",
+        FontKind::Custom(_) => unreachable!(),
+    }
+}
+
+/// Load the fonts listed in the app's `UIAppFonts`, if that hasn't been done
+/// already. This happens lazily, but the app can't tell the difference.
+fn load_app_fonts(env: &mut Environment) {
+    if env.framework_state.uikit.ui_font.app_fonts_loaded {
+        return;
+    }
+    env.framework_state.uikit.ui_font.app_fonts_loaded = true;
+
+    let paths: Vec<_> = env
+        .bundle
+        .app_fonts()
+        .map(|name| env.bundle.bundle_path().join(name))
+        .collect();
+    for path in paths {
+        if let Err(e) = register_font_file(env, &path) {
+            log!("Warning: couldn't load app font {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Make the fonts in a font file available to `[UIFont fontWithName:size:]`.
+/// Used for `UIAppFonts` and `CTFontManagerRegisterFontsForURL`.
+pub fn register_font_file(env: &mut Environment, path: &GuestPath) -> Result<(), String> {
+    load_app_fonts(env);
+
+    let bytes = env
+        .fs
+        .read(path)
+        .map_err(|_| "couldn't read file".to_string())?;
+    let font = Font::from_bytes(bytes)?;
+    let custom = &mut env.framework_state.uikit.ui_font.custom;
+    if font.postscript_name().is_some()
+        && custom
+            .iter()
+            .any(|other| other.postscript_name() == font.postscript_name())
+    {
+        return Err(format!(
+            "a font named {:?} is already registered",
+            font.postscript_name().unwrap()
+        ));
+    }
+    log_dbg!(
+        "Registered font {:?} ({:?}) from {:?}",
+        font.postscript_name(),
+        font.family_name(),
+        path
+    );
+    custom.push(font);
+    Ok(())
+}
+
+/// Look up a font by its PostScript name, full name or family name. There are
+/// no fonts other than the system font and the app's own, so any other name
+/// gets the closest system font.
+fn find_font(env: &mut Environment, name: &str) -> FontKind {
+    load_app_fonts(env);
+
+    let custom = &env.framework_state.uikit.ui_font.custom;
+    let index = custom
+        .iter()
+        .position(|font| font.postscript_name() == Some(name) || font.full_name() == Some(name))
+        .or_else(|| {
+            custom
+                .iter()
+                .position(|font| font.family_name() == Some(name))
+        });
+    if let Some(index) = index {
+        return FontKind::Custom(index);
+    }
+
+    let kind = if name.contains("Bold") {
+        FontKind::Bold
+    } else if name.contains("Italic") || name.contains("Oblique") {
+        FontKind::Italic
+    } else {
+        FontKind::Regular
+    };
+    if !name.starts_with(SYSTEM_FAMILY_NAME) {
+        log!(
+            "App requested font {:?}, which isn't available. {:?} will be used instead.",
+            name,
+            system_font_name(kind)
+        );
+    }
+    kind
+}
+
 fn convert_line_break_mode(ui_mode: UILineBreakMode) -> WrapMode {
     match ui_mode {
         UILineBreakModeWordWrap => WrapMode::Word,
@@ -120,6 +325,11 @@ fn convert_line_break_mode(ui_mode: UILineBreakMode) -> WrapMode {
 
 #[rustfmt::skip]
 fn get_font<'a>(state: &'a mut State, kind: FontKind, text: &str) -> &'a Font {
+    // TODO: fall back to the system font for characters an app's font lacks
+    if let FontKind::Custom(index) = kind {
+        return &state.custom[index];
+    }
+
     // The default fonts (see font.rs) are the Liberation family, which are a
     // good substitute for Helvetica, the iPhone OS system font. Unfortunately,
     // there is no CJK support in these fonts. To support Super Monkey Ball in
@@ -146,6 +356,7 @@ fn get_font<'a>(state: &'a mut State, kind: FontKind, text: &str) -> &'a Font {
                     }
                     return state.bold_ja.as_ref().unwrap();
                 },
+                FontKind::Custom(_) => unreachable!(),
             }
         }
     }
@@ -154,6 +365,7 @@ fn get_font<'a>(state: &'a mut State, kind: FontKind, text: &str) -> &'a Font {
         FontKind::Regular => state.regular.as_ref().unwrap(),
         FontKind::Bold => state.bold.as_ref().unwrap(),
         FontKind::Italic => state.italic.as_ref().unwrap(),
+        FontKind::Custom(_) => unreachable!(),
     }
 }
 
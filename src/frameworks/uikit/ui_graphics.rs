@@ -6,15 +6,46 @@
 //! `UIGraphics.h`
 
 use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_graphics::cg_bitmap_context::{
+    get_data, CGBitmapContextCreate, CGBitmapContextCreateImage,
+};
+use crate::frameworks::core_graphics::cg_color_space::{
+    CGColorSpaceCreateDeviceRGB, CGColorSpaceRelease,
+};
 use crate::frameworks::core_graphics::cg_context::{
-    CGContextRef, CGContextRelease, CGContextRetain,
+    CGContextRef, CGContextRelease, CGContextRetain, CGContextScaleCTM, CGContextTranslateCTM,
+};
+use crate::frameworks::core_graphics::cg_image::{
+    kCGImageAlphaPremultipliedLast, kCGImageByteOrder32Big, CGImageRelease,
 };
-use crate::objc::nil;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string;
+use crate::fs::GuestPath;
+use crate::mem::{GuestUSize, Ptr};
+use crate::objc::{id, msg_class, nil};
 use crate::Environment;
 
 #[derive(Default)]
 pub(super) struct State {
     pub(super) context_stack: Vec<CGContextRef>,
+    /// Contexts created by `UIGraphicsBeginImageContext`, with their scales.
+    /// Each of these is also on the context stack.
+    image_contexts: Vec<(CGContextRef, CGFloat)>,
+    /// The context created by `UIGraphicsBeginPDFContextToFile`, if any.
+    pdf_context: Option<PDFContext>,
+}
+
+/// Pages of a PDF context are drawn into bitmap contexts and embedded in the
+/// PDF file as images. This means anything can be drawn, but text in the file
+/// isn't selectable.
+struct PDFContext {
+    path: String,
+    bounds: CGRect,
+    /// Finished pages: width, height, and RGB pixels from top to bottom.
+    pages: Vec<(GuestUSize, GuestUSize, Vec<u8>)>,
+    /// Bitmap context for the current page, if one has begun. This is also on
+    /// the context stack.
+    page_context: Option<CGContextRef>,
 }
 
 pub fn UIGraphicsPushContext(env: &mut Environment, context: CGContextRef) {
@@ -39,8 +70,305 @@ pub fn UIGraphicsGetCurrentContext(env: &mut Environment) -> CGContextRef {
         .unwrap_or(nil)
 }
 
+fn UIGraphicsBeginImageContext(env: &mut Environment, size: CGSize) {
+    UIGraphicsBeginImageContextWithOptions(env, size, false, 1.0);
+}
+fn UIGraphicsBeginImageContextWithOptions(
+    env: &mut Environment,
+    size: CGSize,
+    _opaque: bool,
+    scale: CGFloat,
+) {
+    // A scale of 0 means the screen's scale, which is always 1 for now.
+    let scale = if scale == 0.0 { 1.0 } else { scale };
+    let width = (size.width * scale).ceil() as GuestUSize;
+    let height = (size.height * scale).ceil() as GuestUSize;
+    let context = create_flipped_bitmap_context(env, width, height, scale);
+    UIGraphicsPushContext(env, context);
+    CGContextRelease(env, context);
+    env.framework_state
+        .uikit
+        .ui_graphics
+        .image_contexts
+        .push((context, scale));
+}
+
+/// Create an RGBA bitmap context with UIKit's co-ordinate system.
+fn create_flipped_bitmap_context(
+    env: &mut Environment,
+    width: GuestUSize,
+    height: GuestUSize,
+    scale: CGFloat,
+) -> CGContextRef {
+    let color_space = CGColorSpaceCreateDeviceRGB(env);
+    let context = CGBitmapContextCreate(
+        env,
+        Ptr::null(),
+        width,
+        height,
+        8,
+        width * 4,
+        color_space,
+        kCGImageByteOrder32Big | kCGImageAlphaPremultipliedLast,
+    );
+    CGColorSpaceRelease(env, color_space);
+
+    // UIKit's co-ordinate system has the origin in the top-left corner. The
+    // rows of the resulting image are top-to-bottom.
+    CGContextTranslateCTM(env, context, 0.0, height as CGFloat);
+    CGContextScaleCTM(env, context, scale, -scale);
+    context
+}
+fn UIGraphicsGetImageFromCurrentImageContext(env: &mut Environment) -> id {
+    let context = UIGraphicsGetCurrentContext(env);
+    let Some(&(_, scale)) = env
+        .framework_state
+        .uikit
+        .ui_graphics
+        .image_contexts
+        .iter()
+        .find(|&&(image_context, _)| image_context == context)
+    else {
+        log!("Warning: UIGraphicsGetImageFromCurrentImageContext() called when the current context is not an image context, returning nil");
+        return nil;
+    };
+    let cg_image = CGBitmapContextCreateImage(env, context);
+    let image: id = msg_class![env; UIImage imageWithCGImage:cg_image
+                                                      scale:scale
+                                                orientation:0]; // Up
+    CGImageRelease(env, cg_image);
+    image
+}
+fn UIGraphicsEndImageContext(env: &mut Environment) {
+    let context = UIGraphicsGetCurrentContext(env);
+    let image_contexts = &mut env.framework_state.uikit.ui_graphics.image_contexts;
+    let Some(index) = image_contexts
+        .iter()
+        .position(|&(image_context, _)| image_context == context)
+    else {
+        log!("Warning: UIGraphicsEndImageContext() called when the current context is not an image context, ignoring");
+        return;
+    };
+    image_contexts.remove(index);
+    UIGraphicsPopContext(env);
+}
+
+fn UIGraphicsBeginPDFContextToFile(
+    env: &mut Environment,
+    path: id, // NSString*
+    bounds: CGRect,
+    _document_info: id, // NSDictionary*
+) -> bool {
+    if env.framework_state.uikit.ui_graphics.pdf_context.is_some() {
+        log!("Warning: UIGraphicsBeginPDFContextToFile() called when a PDF context is already open, ignoring");
+        return false;
+    }
+    let path = ns_string::to_rust_string(env, path).into_owned();
+    // An empty rectangle means the default page size, US Letter.
+    let bounds = if bounds.size.width <= 0.0 || bounds.size.height <= 0.0 {
+        CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize {
+                width: 612.0,
+                height: 792.0,
+            },
+        }
+    } else {
+        bounds
+    };
+    env.framework_state.uikit.ui_graphics.pdf_context = Some(PDFContext {
+        path,
+        bounds,
+        pages: Vec::new(),
+        page_context: None,
+    });
+    true
+}
+fn UIGraphicsBeginPDFPage(env: &mut Environment) {
+    let Some(pdf_context) = &env.framework_state.uikit.ui_graphics.pdf_context else {
+        log!("Warning: UIGraphicsBeginPDFPage() called without a PDF context, ignoring");
+        return;
+    };
+    let bounds = pdf_context.bounds;
+    UIGraphicsBeginPDFPageWithInfo(env, bounds, nil);
+}
+fn UIGraphicsBeginPDFPageWithInfo(
+    env: &mut Environment,
+    bounds: CGRect,
+    _page_info: id, // NSDictionary*
+) {
+    if env.framework_state.uikit.ui_graphics.pdf_context.is_none() {
+        log!("Warning: UIGraphicsBeginPDFPageWithInfo() called without a PDF context, ignoring");
+        return;
+    }
+    end_pdf_page(env);
+
+    let width = bounds.size.width.ceil() as GuestUSize;
+    let height = bounds.size.height.ceil() as GuestUSize;
+    let context = create_flipped_bitmap_context(env, width, height, 1.0);
+    CGContextTranslateCTM(env, context, -bounds.origin.x, -bounds.origin.y);
+    UIGraphicsPushContext(env, context);
+    CGContextRelease(env, context);
+    let pdf_context = env.framework_state.uikit.ui_graphics.pdf_context.as_mut();
+    pdf_context.unwrap().page_context = Some(context);
+}
+fn UIGraphicsEndPDFContext(env: &mut Environment) {
+    if env.framework_state.uikit.ui_graphics.pdf_context.is_none() {
+        log!("Warning: UIGraphicsEndPDFContext() called without a PDF context, ignoring");
+        return;
+    }
+    end_pdf_page(env);
+    let PDFContext { path, pages, .. } = env
+        .framework_state
+        .uikit
+        .ui_graphics
+        .pdf_context
+        .take()
+        .unwrap();
+    let pdf = encode_pdf(&pages);
+    if env.fs.write(GuestPath::new(&path), &pdf).is_err() {
+        log!(
+            "Warning: UIGraphicsEndPDFContext() couldn't write to {:?}",
+            path
+        );
+    }
+}
+
+/// Finish the current page of the PDF context, if there is one, and remove its
+/// bitmap context from the context stack.
+fn end_pdf_page(env: &mut Environment) {
+    let pdf_context = env.framework_state.uikit.ui_graphics.pdf_context.as_mut();
+    let Some(context) = pdf_context.unwrap().page_context.take() else {
+        return;
+    };
+
+    // The page is composited onto white, since PDF images are opaque.
+    let (width, height, data) = get_data(&env.objc, context);
+    let rgba = env.mem.bytes_at(data.cast(), width * height * 4);
+    let rgb = rgba
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            // Alpha is premultiplied.
+            let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            [r, g, b].map(|component| component.saturating_add(255 - a))
+        })
+        .collect();
+    env.framework_state
+        .uikit
+        .ui_graphics
+        .pdf_context
+        .as_mut()
+        .unwrap()
+        .pages
+        .push((width, height, rgb));
+
+    let context_stack = &mut env.framework_state.uikit.ui_graphics.context_stack;
+    if context_stack.last() == Some(&context) {
+        UIGraphicsPopContext(env);
+    } else if let Some(index) = context_stack.iter().position(|&other| other == context) {
+        log!(
+            "Warning: PDF page context {:?} is not the current context",
+            context
+        );
+        context_stack.remove(index);
+        CGContextRelease(env, context);
+    }
+}
+
+/// Create a PDF file where each page is a single image.
+fn encode_pdf(pages: &[(GuestUSize, GuestUSize, Vec<u8>)]) -> Vec<u8> {
+    // The second line marks the file as binary.
+    let mut pdf = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    // Offset of each object, in order of object number starting from 1.
+    let mut offsets = Vec::new();
+    let mut write_object = |pdf: &mut Vec<u8>, header: String, stream: Option<&[u8]>| {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\n", offsets.len(), header).as_bytes());
+        if let Some(stream) = stream {
+            pdf.extend_from_slice(b"stream\n");
+            pdf.extend_from_slice(stream);
+            pdf.extend_from_slice(b"\nendstream\n");
+        }
+        pdf.extend_from_slice(b"endobj\n");
+    };
+
+    // Objects 1 and 2 are the catalog and page tree. Each page is then made of
+    // three objects: the page, its content stream and its image.
+    let page_object_number = |index: usize| 3 + index * 3;
+    write_object(
+        &mut pdf,
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        None,
+    );
+    let kids: Vec<String> = (0..pages.len())
+        .map(|index| format!("{} 0 R", page_object_number(index)))
+        .collect();
+    write_object(
+        &mut pdf,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+        None,
+    );
+    for (index, (width, height, rgb)) in pages.iter().enumerate() {
+        let page = page_object_number(index);
+        write_object(
+            &mut pdf,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                width,
+                height,
+                page + 2,
+                page + 1
+            ),
+            None,
+        );
+        let contents = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", width, height);
+        write_object(
+            &mut pdf,
+            format!("<< /Length {} >>", contents.len()),
+            Some(contents.as_bytes()),
+        );
+        write_object(
+            &mut pdf,
+            format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} \
+                 /ColorSpace /DeviceRGB /BitsPerComponent 8 /Length {} >>",
+                width,
+                height,
+                rgb.len()
+            ),
+            Some(rgb.as_slice()),
+        );
+    }
+
+    let xref_offset = pdf.len();
+    let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+    for offset in &offsets {
+        xref.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    xref.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        offsets.len() + 1,
+        xref_offset
+    ));
+    pdf.extend_from_slice(xref.as_bytes());
+    pdf
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(UIGraphicsPushContext(_)),
     export_c_func!(UIGraphicsPopContext()),
     export_c_func!(UIGraphicsGetCurrentContext()),
+    export_c_func!(UIGraphicsBeginImageContext(_)),
+    export_c_func!(UIGraphicsBeginImageContextWithOptions(_, _, _)),
+    export_c_func!(UIGraphicsGetImageFromCurrentImageContext()),
+    export_c_func!(UIGraphicsEndImageContext()),
+    export_c_func!(UIGraphicsBeginPDFContextToFile(_, _, _)),
+    export_c_func!(UIGraphicsBeginPDFPage()),
+    export_c_func!(UIGraphicsBeginPDFPageWithInfo(_, _)),
+    export_c_func!(UIGraphicsEndPDFContext()),
 ];
@@ -5,10 +5,16 @@
  */
 //! `UIImage`.
 
-use crate::frameworks::core_graphics::cg_context::CGContextDrawImage;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_graphics::cg_context::{
+    kCGBlendModeNormal, CGBlendMode, CGContextClipToRect, CGContextDrawImage,
+    CGContextDrawTiledImage, CGContextRef, CGContextRestoreGState, CGContextSaveGState,
+    CGContextScaleCTM, CGContextSetAlpha, CGContextTranslateCTM,
+};
 use crate::frameworks::core_graphics::cg_image::{self, CGImageRef, CGImageRelease, CGImageRetain};
-use crate::frameworks::core_graphics::{CGRect, CGSize};
-use crate::frameworks::foundation::{ns_data, ns_string, NSInteger};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_data, ns_string, NSInteger, NSUInteger};
+use crate::frameworks::uikit::ui_geometry::UIEdgeInsets;
 use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
 use crate::fs::GuestPath;
 use crate::image::Image;
@@ -16,9 +22,15 @@ use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
     NSZonePtr,
 };
+use crate::Environment;
 
 struct UIImageHostObject {
     cg_image: CGImageRef,
+    /// Number of pixels per point.
+    scale: CGFloat,
+    /// Set for images created by `resizableImageWithCapInsets:`. The insets
+    /// are in points.
+    cap_insets: Option<UIEdgeInsets>,
 }
 impl HostObject for UIImageHostObject {}
 
@@ -29,7 +41,11 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation UIImage: NSObject
 
 + (id)allocWithZone:(NSZonePtr)_zone {
-    let host_object = Box::new(UIImageHostObject { cg_image: nil });
+    let host_object = Box::new(UIImageHostObject {
+        cg_image: nil,
+        scale: 1.0,
+        cap_insets: None,
+    });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
@@ -39,6 +55,16 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)imageWithCGImage:(CGImageRef)cg_image
+                 scale:(CGFloat)scale
+           orientation:(NSInteger)orientation { // UIImageOrientation
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCGImage:cg_image
+                                          scale:scale
+                                    orientation:orientation];
+    autorelease(env, new)
+}
+
 + (id)imageNamed:(id)name { // NSString*
     // TODO: figure out whether this is actually correct in all cases
     let bundle: id = msg_class![env; NSBundle mainBundle];
@@ -63,7 +89,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())dealloc {
-    let &UIImageHostObject { cg_image } = env.objc.borrow(this);
+    let &UIImageHostObject { cg_image, .. } = env.objc.borrow(this);
     CGImageRelease(env, cg_image);
 
     env.objc.dealloc_object(this, &mut env.mem)
@@ -75,6 +101,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithCGImage:(CGImageRef)cg_image
+                scale:(CGFloat)scale
+          orientation:(NSInteger)orientation { // UIImageOrientation
+    if orientation != 0 {
+        log!("TODO: [(UIImage*){:?} initWithCGImage:{:?} scale:{} orientation:{}] ignoring orientation", this, cg_image, scale, orientation);
+    }
+    let this: id = msg![env; this initWithCGImage:cg_image];
+    env.objc.borrow_mut::<UIImageHostObject>(this).scale = scale;
+    this
+}
+
 - (id)initWithContentsOfFile:(id)path { // NSString*
     let path = ns_string::to_rust_string(env, path); // TODO: avoid copy
     let Ok(bytes) = env.fs.read(GuestPath::new(&path)) else {
@@ -113,21 +150,251 @@ pub const CLASSES: ClassExports = objc_classes! {
     0 // UIImageOrientationUp
 }
 
+- (CGFloat)scale {
+    env.objc.borrow::<UIImageHostObject>(this).scale
+}
+
 - (CGSize)size {
-    let image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
-    let (width, height) = cg_image::borrow_image(&env.objc, image).dimensions();
+    let &UIImageHostObject {
+        cg_image, scale, ..
+    } = env.objc.borrow(this);
+    let (width, height) = cg_image::borrow_image(&env.objc, cg_image).dimensions();
     CGSize {
-        width: width as _,
-        height: height as _,
+        width: width as CGFloat / scale,
+        height: height as CGFloat / scale,
     }
 }
 
+- (id)resizableImageWithCapInsets:(UIEdgeInsets)cap_insets {
+    let &UIImageHostObject {
+        cg_image, scale, ..
+    } = env.objc.borrow(this);
+    let new: id = msg_class![env; UIImage alloc];
+    let new: id = msg![env; new initWithCGImage:cg_image scale:scale orientation:0];
+    env.objc.borrow_mut::<UIImageHostObject>(new).cap_insets = Some(cap_insets);
+    autorelease(env, new)
+}
+- (id)stretchableImageWithLeftCapWidth:(NSInteger)left_cap_width
+                          topCapHeight:(NSInteger)top_cap_height {
+    // The middle part is one point wide/high, the rest are the other caps.
+    let size: CGSize = msg![env; this size];
+    let right_cap_width = if left_cap_width > 0 {
+        size.width - left_cap_width as CGFloat - 1.0
+    } else {
+        0.0
+    };
+    let bottom_cap_height = if top_cap_height > 0 {
+        size.height - top_cap_height as CGFloat - 1.0
+    } else {
+        0.0
+    };
+    let cap_insets = UIEdgeInsets {
+        top: top_cap_height as CGFloat,
+        left: left_cap_width as CGFloat,
+        bottom: bottom_cap_height.max(0.0),
+        right: right_cap_width.max(0.0),
+    };
+    msg![env; this resizableImageWithCapInsets:cap_insets]
+}
+- (UIEdgeInsets)capInsets {
+    env.objc
+        .borrow::<UIImageHostObject>(this)
+        .cap_insets
+        .unwrap_or_default()
+}
+
+- (())drawAtPoint:(CGPoint)point {
+    let size: CGSize = msg![env; this size];
+    () = msg![env; this drawInRect:(CGRect { origin: point, size })];
+}
 - (())drawInRect:(CGRect)rect {
+    let alpha: CGFloat = 1.0;
+    () = msg![env; this drawInRect:rect blendMode:kCGBlendModeNormal alpha:alpha];
+}
+- (())drawInRect:(CGRect)rect
+       blendMode:(CGBlendMode)blend_mode
+           alpha:(CGFloat)alpha {
+    if blend_mode != kCGBlendModeNormal {
+        log!("TODO: [(UIImage*){:?} drawInRect:{:?} blendMode:{} alpha:{}] ignoring blend mode", this, rect, blend_mode, alpha);
+    }
+    let context = UIGraphicsGetCurrentContext(env);
+    let size: CGSize = msg![env; this size];
+    let &UIImageHostObject {
+        cg_image,
+        cap_insets,
+        ..
+    } = env.objc.borrow(this);
+
+    CGContextSaveGState(env, context);
+    CGContextSetAlpha(env, context, alpha);
+    if let Some(cap_insets) = cap_insets {
+        draw_nine_part(env, context, cg_image, size, cap_insets, rect);
+    } else {
+        draw_upright(env, context, cg_image, rect);
+    }
+    CGContextRestoreGState(env, context);
+}
+- (())drawAsPatternInRect:(CGRect)rect {
     let context = UIGraphicsGetCurrentContext(env);
-    let image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
-    CGContextDrawImage(env, context, rect, image);
+    let size: CGSize = msg![env; this size];
+    let cg_image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
+
+    CGContextSaveGState(env, context);
+    CGContextClipToRect(env, context, rect);
+    // The pattern starts at the origin of the context. See draw_upright().
+    CGContextScaleCTM(env, context, 1.0, -1.0);
+    let tile = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size,
+    };
+    CGContextDrawTiledImage(env, context, tile, cg_image);
+    CGContextRestoreGState(env, context);
 }
 
 @end
 
 };
+
+/// Copy encoded image data into a new autoreleased `NSData`.
+fn data_from_bytes(env: &mut Environment, bytes: Vec<u8>) -> id {
+    let length: NSUInteger = bytes.len().try_into().unwrap();
+    let alloc = env.mem.alloc(length);
+    env.mem
+        .bytes_at_mut(alloc.cast(), length)
+        .copy_from_slice(&bytes);
+    msg_class![env; NSData dataWithBytesNoCopy:alloc length:length]
+}
+
+fn UIImagePNGRepresentation(env: &mut Environment, image: id) -> id {
+    if image == nil {
+        return nil;
+    }
+    let &UIImageHostObject {
+        cg_image, scale, ..
+    } = env.objc.borrow(image);
+    if cg_image == nil {
+        return nil;
+    }
+    let png = cg_image::borrow_image(&env.objc, cg_image).to_png_with_scale(scale);
+    data_from_bytes(env, png)
+}
+
+fn UIImageJPEGRepresentation(env: &mut Environment, image: id, compression_quality: CGFloat) -> id {
+    if image == nil {
+        return nil;
+    }
+    let cg_image = env.objc.borrow::<UIImageHostObject>(image).cg_image;
+    if cg_image == nil {
+        return nil;
+    }
+    // UIKit's quality is 0.0 (most compressed) to 1.0 (best quality).
+    let quality = (compression_quality.clamp(0.0, 1.0) * 100.0).round() as u8;
+    let jpeg = cg_image::borrow_image(&env.objc, cg_image).to_jpeg(quality);
+    data_from_bytes(env, jpeg)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(UIImagePNGRepresentation(_)),
+    export_c_func!(UIImageJPEGRepresentation(_, _)),
+];
+
+/// Draw an image so that it appears the right way up in UIKit's flipped
+/// co-ordinate system. `CGContextDrawImage` assumes the origin is in the
+/// bottom-left corner, so on its own it would draw the image upside-down.
+fn draw_upright(env: &mut Environment, context: CGContextRef, cg_image: CGImageRef, rect: CGRect) {
+    CGContextSaveGState(env, context);
+    CGContextTranslateCTM(
+        env,
+        context,
+        rect.origin.x,
+        rect.origin.y + rect.size.height,
+    );
+    CGContextScaleCTM(env, context, 1.0, -1.0);
+    let rect = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: rect.size,
+    };
+    CGContextDrawImage(env, context, rect, cg_image);
+    CGContextRestoreGState(env, context);
+}
+
+/// Draw a resizable image: the corners are drawn at their original size, the
+/// edges are stretched in one direction, and the middle is stretched in both.
+fn draw_nine_part(
+    env: &mut Environment,
+    context: CGContextRef,
+    cg_image: CGImageRef,
+    size: CGSize,
+    cap_insets: UIEdgeInsets,
+    rect: CGRect,
+) {
+    // Start and end of each of the three parts in one dimension, in the image
+    // and in the destination.
+    fn parts(
+        size: CGFloat,
+        start_cap: CGFloat,
+        end_cap: CGFloat,
+        dest_start: CGFloat,
+        dest_size: CGFloat,
+    ) -> [((CGFloat, CGFloat), (CGFloat, CGFloat)); 3] {
+        let dest_end = dest_start + dest_size;
+        [
+            ((0.0, start_cap), (dest_start, dest_start + start_cap)),
+            (
+                (start_cap, size - end_cap),
+                (dest_start + start_cap, dest_end - end_cap),
+            ),
+            ((size - end_cap, size), (dest_end - end_cap, dest_end)),
+        ]
+    }
+    let columns = parts(
+        size.width,
+        cap_insets.left,
+        cap_insets.right,
+        rect.origin.x,
+        rect.size.width,
+    );
+    let rows = parts(
+        size.height,
+        cap_insets.top,
+        cap_insets.bottom,
+        rect.origin.y,
+        rect.size.height,
+    );
+
+    for ((src_x0, src_x1), (dest_x0, dest_x1)) in columns {
+        for ((src_y0, src_y1), (dest_y0, dest_y1)) in rows {
+            if src_x1 <= src_x0 || src_y1 <= src_y0 || dest_x1 <= dest_x0 || dest_y1 <= dest_y0 {
+                continue;
+            }
+            // Draw the whole image, scaled and positioned so that this part
+            // of it fills the destination part, and clip to that part.
+            let scale_x = (dest_x1 - dest_x0) / (src_x1 - src_x0);
+            let scale_y = (dest_y1 - dest_y0) / (src_y1 - src_y0);
+            let whole_image = CGRect {
+                origin: CGPoint {
+                    x: dest_x0 - src_x0 * scale_x,
+                    y: dest_y0 - src_y0 * scale_y,
+                },
+                size: CGSize {
+                    width: size.width * scale_x,
+                    height: size.height * scale_y,
+                },
+            };
+            let part = CGRect {
+                origin: CGPoint {
+                    x: dest_x0,
+                    y: dest_y0,
+                },
+                size: CGSize {
+                    width: dest_x1 - dest_x0,
+                    height: dest_y1 - dest_y0,
+                },
+            };
+            CGContextSaveGState(env, context);
+            CGContextClipToRect(env, context, part);
+            draw_upright(env, context, cg_image, whole_image);
+            CGContextRestoreGState(env, context);
+        }
+    }
+}
@@ -4,11 +4,82 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIImagePickerController`
+//!
+//! touchHLE has no camera or photo library, so when the picker appears, the
+//! user immediately "picks" the image chosen with the `--image-picker=` option
+//! (or cancels), and the delegate is told straight away. As on a real device,
+//! it's then up to the delegate to dismiss the picker.
 
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::{cg_image, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::foundation::NSInteger;
-use crate::objc::{objc_classes, ClassExports};
+use crate::frameworks::uikit::ui_view_controller::UIViewControllerHostObject;
+use crate::image::Image;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_class, nil, objc_classes, release, retain,
+    ClassExports, NSZonePtr,
+};
+use crate::options::ImagePickerChoice;
+use crate::Environment;
 
 type UIImagePickerControllerSourceType = NSInteger;
+const UIImagePickerControllerSourceTypePhotoLibrary: UIImagePickerControllerSourceType = 0;
+const UIImagePickerControllerSourceTypeCamera: UIImagePickerControllerSourceType = 1;
+const UIImagePickerControllerSourceTypeSavedPhotosAlbum: UIImagePickerControllerSourceType = 2;
+
+pub const UIImagePickerControllerMediaType: &str = "UIImagePickerControllerMediaType";
+pub const UIImagePickerControllerOriginalImage: &str = "UIImagePickerControllerOriginalImage";
+pub const UIImagePickerControllerEditedImage: &str = "UIImagePickerControllerEditedImage";
+pub const UIImagePickerControllerCropRect: &str = "UIImagePickerControllerCropRect";
+
+/// Keys for the media info dictionary.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIImagePickerControllerMediaType",
+        HostConstant::NSString(UIImagePickerControllerMediaType),
+    ),
+    (
+        "_UIImagePickerControllerOriginalImage",
+        HostConstant::NSString(UIImagePickerControllerOriginalImage),
+    ),
+    (
+        "_UIImagePickerControllerEditedImage",
+        HostConstant::NSString(UIImagePickerControllerEditedImage),
+    ),
+    (
+        "_UIImagePickerControllerCropRect",
+        HostConstant::NSString(UIImagePickerControllerCropRect),
+    ),
+];
+
+/// Size of the built-in sample image, the same as the original iPhone's screen.
+const SAMPLE_IMAGE_SIZE: (u32, u32) = (320, 480);
+
+pub struct UIImagePickerControllerHostObject {
+    superclass: UIViewControllerHostObject,
+    /// `NSObject<UIImagePickerControllerDelegate,
+    /// UINavigationControllerDelegate>*`, weak reference
+    delegate: id,
+    source_type: UIImagePickerControllerSourceType,
+    allows_editing: bool,
+    /// Whether the user has picked (or cancelled) already, so it doesn't
+    /// happen again if the picker reappears.
+    finished: bool,
+}
+impl_HostObject_with_superclass!(UIImagePickerControllerHostObject);
+impl Default for UIImagePickerControllerHostObject {
+    fn default() -> Self {
+        UIImagePickerControllerHostObject {
+            superclass: Default::default(),
+            delegate: nil,
+            source_type: UIImagePickerControllerSourceTypePhotoLibrary,
+            allows_editing: false,
+            finished: false,
+        }
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -18,12 +89,184 @@ pub const CLASSES: ClassExports = objc_classes! {
 //       UIViewController.
 @implementation UIImagePickerController: UIViewController
 
-+ (bool)isSourceTypeAvailable:(UIImagePickerControllerSourceType)_type {
-    // For now, simply claim no sources are available.
-    // TODO: support some sources.
-    false
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIImagePickerControllerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (bool)isSourceTypeAvailable:(UIImagePickerControllerSourceType)type_ {
+    // There's no camera, but the "library" always has an image to pick.
+    matches!(
+        type_,
+        UIImagePickerControllerSourceTypePhotoLibrary
+            | UIImagePickerControllerSourceTypeSavedPhotosAlbum
+    )
+}
+
+- (id)delegate {
+    env.objc.borrow::<UIImagePickerControllerHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    env.objc.borrow_mut::<UIImagePickerControllerHostObject>(this).delegate = delegate;
+}
+
+- (UIImagePickerControllerSourceType)sourceType {
+    env.objc.borrow::<UIImagePickerControllerHostObject>(this).source_type
+}
+- (())setSourceType:(UIImagePickerControllerSourceType)type_ {
+    if type_ == UIImagePickerControllerSourceTypeCamera {
+        log!("App wants to take a photo, it will get the picked image instead.");
+    }
+    env.objc.borrow_mut::<UIImagePickerControllerHostObject>(this).source_type = type_;
+}
+
+- (bool)allowsEditing {
+    env.objc.borrow::<UIImagePickerControllerHostObject>(this).allows_editing
+}
+- (())setAllowsEditing:(bool)allows {
+    env.objc.borrow_mut::<UIImagePickerControllerHostObject>(this).allows_editing = allows;
+}
+// The iPhone OS 2 name for the same property.
+- (bool)allowsImageEditing {
+    msg![env; this allowsEditing]
+}
+- (())setAllowsImageEditing:(bool)allows {
+    msg![env; this setAllowsEditing:allows]
+}
+
+- (())viewDidAppear:(bool)_animated {
+    let host_object = env.objc.borrow_mut::<UIImagePickerControllerHostObject>(this);
+    if !std::mem::replace(&mut host_object.finished, true) {
+        pick(env, this);
+    }
 }
 
 @end
 
 };
+
+/// Get the `UIImage*` (autoreleased) that the user "picks", or [None] if they
+/// cancel.
+fn picked_image(env: &mut Environment) -> Option<id> {
+    let image = match env.options.image_picker.clone() {
+        ImagePickerChoice::Cancel => return None,
+        ImagePickerChoice::Sample => sample_image(),
+        ImagePickerChoice::File(path) => {
+            match std::fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Image::from_bytes(&bytes))
+            {
+                Ok(image) => image,
+                Err(e) => {
+                    log!(
+                        "Warning: couldn't load image picker image {:?} ({}), using the sample image instead.",
+                        path,
+                        e
+                    );
+                    sample_image()
+                }
+            }
+        }
+    };
+    let cg_image = cg_image::from_image(env, image);
+    let ui_image: id = msg_class![env; UIImage imageWithCGImage:cg_image];
+    cg_image::CGImageRelease(env, cg_image);
+    Some(ui_image)
+}
+
+/// A simple opaque gradient, so it's obvious when an app shows it.
+fn sample_image() -> Image {
+    let (width, height) = SAMPLE_IMAGE_SIZE;
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.extend_from_slice(&[
+                (x * 255 / width) as u8,
+                (y * 255 / height) as u8,
+                0xc0,
+                0xff,
+            ]);
+        }
+    }
+    Image::from_pixel_vec(pixels, (width, height))
+}
+
+/// Immediately tells the delegate what was picked.
+fn pick(env: &mut Environment, picker: id) {
+    retain(env, picker);
+
+    let &UIImagePickerControllerHostObject {
+        delegate,
+        allows_editing,
+        ..
+    } = env.objc.borrow(picker);
+
+    let Some(image) = picked_image(env) else {
+        log!("App presented an image picker, simulating cancellation.");
+        if delegate != nil
+            && env.objc.object_has_method_named(
+                &env.mem,
+                delegate,
+                "imagePickerControllerDidCancel:",
+            )
+        {
+            () = msg![env; delegate imagePickerControllerDidCancel:picker];
+        }
+        release(env, picker);
+        return;
+    };
+    echo!("App presented an image picker, picking an image.");
+
+    if delegate != nil
+        && env.objc.object_has_method_named(
+            &env.mem,
+            delegate,
+            "imagePickerController:didFinishPickingMediaWithInfo:",
+        )
+    {
+        let mut keys_and_objects = vec![
+            (
+                UIImagePickerControllerMediaType,
+                get_static_str(env, "public.image"),
+            ),
+            (UIImagePickerControllerOriginalImage, image),
+        ];
+        if allows_editing {
+            // The "edit" is always a no-op.
+            let size: CGSize = msg![env; image size];
+            let crop_rect = CGRect {
+                origin: CGPoint { x: 0.0, y: 0.0 },
+                size,
+            };
+            let crop_rect: id = msg_class![env; NSValue valueWithCGRect:crop_rect];
+            keys_and_objects.push((UIImagePickerControllerEditedImage, image));
+            keys_and_objects.push((UIImagePickerControllerCropRect, crop_rect));
+        }
+        let keys_and_objects: Vec<(id, id)> = keys_and_objects
+            .into_iter()
+            .map(|(key, object)| (get_static_str(env, key), object))
+            .collect();
+        let info = dict_from_keys_and_objects(env, &keys_and_objects);
+        () = msg![env; delegate imagePickerController:picker
+                        didFinishPickingMediaWithInfo:info];
+        release(env, info);
+    } else if delegate != nil
+        && env.objc.object_has_method_named(
+            &env.mem,
+            delegate,
+            "imagePickerController:didFinishPickingImage:editingInfo:",
+        )
+    {
+        // Deprecated iPhone OS 2 method.
+        () = msg![env; delegate imagePickerController:picker
+                                didFinishPickingImage:image
+                                          editingInfo:nil];
+    } else {
+        log!(
+            "Image picker delegate {:?} doesn't implement imagePickerController:didFinishPickingMediaWithInfo:, ignoring.",
+            delegate
+        );
+    }
+
+    release(env, picker);
+}
@@ -0,0 +1,283 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIPasteboard`.
+//!
+//! The general pasteboard is bridged to the host clipboard for text, so text
+//! can be copied and pasted between the app and the host OS. Everything else
+//! only exists within touchHLE.
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::ns_string::{
+    from_rust_string, get_static_str, to_rust_string, NSUTF8StringEncoding,
+};
+use crate::frameworks::foundation::{ns_array, ns_data, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::collections::HashMap;
+
+pub const UIPasteboardNameGeneral: &str = "com.apple.UIKit.pboard.general";
+pub const UIPasteboardNameFind: &str = "com.apple.UIKit.pboard.find";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIPasteboardNameGeneral",
+        HostConstant::NSString(UIPasteboardNameGeneral),
+    ),
+    (
+        "_UIPasteboardNameFind",
+        HostConstant::NSString(UIPasteboardNameFind),
+    ),
+];
+
+// Uniform Type Identifiers used by the convenience properties.
+const kUTTypeUTF8PlainText: &str = "public.utf8-plain-text";
+const kUTTypePNG: &str = "public.png";
+/// Types that `string` will accept.
+const TEXT_TYPES: &[&str] = &[kUTTypeUTF8PlainText, "public.plain-text", "public.text"];
+
+#[derive(Default)]
+pub struct State {
+    /// Pasteboards by name. These are never deallocated.
+    pasteboards: HashMap<String, id>,
+    next_unique_name: u32,
+}
+
+struct UIPasteboardHostObject {
+    /// `NSString*`
+    name: id,
+    /// Mapping of pasteboard types (UTIs) to values. The values are retained.
+    items: HashMap<String, id>,
+    change_count: NSInteger,
+    /// If this is the general pasteboard, the host clipboard text as of the
+    /// last time they were synchronized.
+    host_text: Option<Option<String>>,
+}
+impl HostObject for UIPasteboardHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIPasteboard: NSObject
+
++ (id)generalPasteboard {
+    get_pasteboard(env, UIPasteboardNameGeneral, true)
+}
++ (id)pasteboardWithName:(id)name // NSString*
+                  create:(bool)create {
+    let name = to_rust_string(env, name).into_owned();
+    get_pasteboard(env, &name, create)
+}
++ (id)pasteboardWithUniqueName {
+    let state = &mut env.framework_state.uikit.ui_pasteboard;
+    let name = format!("touchHLE.pboard.unique.{}", state.next_unique_name);
+    state.next_unique_name += 1;
+    get_pasteboard(env, &name, true)
+}
++ (())removePasteboardWithName:(id)name { // NSString*
+    // The pasteboard object is kept alive, since the app might still have a
+    // reference to it, but it won't be found by name any more.
+    let name = to_rust_string(env, name).into_owned();
+    env.framework_state.uikit.ui_pasteboard.pasteboards.remove(&name);
+}
+
+// Pasteboards are kept alive by the state.
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
+- (id)name {
+    env.objc.borrow::<UIPasteboardHostObject>(this).name
+}
+
+- (NSInteger)changeCount {
+    sync_from_host(env, this);
+    env.objc.borrow::<UIPasteboardHostObject>(this).change_count
+}
+
+- (id)pasteboardTypes {
+    sync_from_host(env, this);
+    let types: Vec<String> = env.objc.borrow::<UIPasteboardHostObject>(this).items.keys().cloned().collect();
+    let types = types.into_iter().map(|type_| from_rust_string(env, type_)).collect();
+    let types = ns_array::from_vec(env, types);
+    autorelease(env, types)
+}
+- (bool)containsPasteboardTypes:(id)types { // NSArray<NSString*>*
+    let count: u32 = msg![env; types count];
+    (0..count).any(|i| {
+        let type_: id = msg![env; types objectAtIndex:i];
+        let value: id = msg![env; this valueForPasteboardType:type_];
+        value != nil
+    })
+}
+
+- (id)valueForPasteboardType:(id)type_ { // NSString*
+    sync_from_host(env, this);
+    let type_ = to_rust_string(env, type_);
+    let host_object = env.objc.borrow::<UIPasteboardHostObject>(this);
+    host_object.items.get(&*type_).copied().unwrap_or(nil)
+}
+- (())setValue:(id)value
+forPasteboardType:(id)type_ { // NSString*
+    let type_ = to_rust_string(env, type_).into_owned();
+    // Setting an item replaces the whole content of the pasteboard.
+    let value = retain(env, value);
+    let host_object = env.objc.borrow_mut::<UIPasteboardHostObject>(this);
+    let old_items = std::mem::take(&mut host_object.items);
+    host_object.items.insert(type_.clone(), value);
+    host_object.change_count += 1;
+    for (_type, old_value) in old_items {
+        release(env, old_value);
+    }
+
+    if TEXT_TYPES.contains(&type_.as_str()) {
+        let string_class = env.objc.get_known_class("NSString", &mut env.mem);
+        if msg![env; value isKindOfClass:string_class] {
+            let text = to_rust_string(env, value).into_owned();
+            sync_to_host(env, this, text);
+        }
+    }
+}
+
+- (id)dataForPasteboardType:(id)type_ { // NSString*
+    let value: id = msg![env; this valueForPasteboardType:type_];
+    if value == nil {
+        return nil;
+    }
+    let data_class = env.objc.get_known_class("NSData", &mut env.mem);
+    let string_class = env.objc.get_known_class("NSString", &mut env.mem);
+    if msg![env; value isKindOfClass:data_class] {
+        value
+    } else if msg![env; value isKindOfClass:string_class] {
+        msg![env; value dataUsingEncoding:NSUTF8StringEncoding]
+    } else {
+        log!("TODO: [(UIPasteboard*){:?} dataForPasteboardType:{:?}] for value {:?}", this, type_, value);
+        nil
+    }
+}
+- (())setData:(id)data // NSData*
+forPasteboardType:(id)type_ { // NSString*
+    let type_str = to_rust_string(env, type_);
+    if TEXT_TYPES.contains(&&*type_str) {
+        // Store text as a string so the host clipboard can see it.
+        let text = String::from_utf8_lossy(ns_data::to_rust_slice(env, data));
+        let text = text.trim_end_matches('\0').to_string();
+        let string = from_rust_string(env, text);
+        () = msg![env; this setValue:string forPasteboardType:type_];
+        release(env, string);
+    } else {
+        () = msg![env; this setValue:data forPasteboardType:type_];
+    }
+}
+
+- (id)string {
+    for &type_ in TEXT_TYPES {
+        let type_ = get_static_str(env, type_);
+        let value: id = msg![env; this valueForPasteboardType:type_];
+        if value != nil {
+            return value;
+        }
+    }
+    nil
+}
+- (())setString:(id)string { // NSString*
+    let type_ = get_static_str(env, kUTTypeUTF8PlainText);
+    msg![env; this setValue:string forPasteboardType:type_]
+}
+
+- (id)image {
+    let type_ = get_static_str(env, kUTTypePNG);
+    msg![env; this valueForPasteboardType:type_]
+}
+- (())setImage:(id)image { // UIImage*
+    // TODO: bridge images to the host clipboard
+    let type_ = get_static_str(env, kUTTypePNG);
+    msg![env; this setValue:image forPasteboardType:type_]
+}
+
+@end
+
+};
+
+fn get_pasteboard(env: &mut Environment, name: &str, create: bool) -> id {
+    if let Some(&pasteboard) = env
+        .framework_state
+        .uikit
+        .ui_pasteboard
+        .pasteboards
+        .get(name)
+    {
+        return pasteboard;
+    }
+    if !create {
+        return nil;
+    }
+
+    let ns_name = from_rust_string(env, name.to_string());
+    let host_object = Box::new(UIPasteboardHostObject {
+        name: ns_name,
+        items: HashMap::new(),
+        change_count: 0,
+        host_text: (name == UIPasteboardNameGeneral).then_some(None),
+    });
+    let class = env.objc.get_known_class("UIPasteboard", &mut env.mem);
+    let pasteboard = env
+        .objc
+        .alloc_static_object(class, host_object, &mut env.mem);
+    env.framework_state
+        .uikit
+        .ui_pasteboard
+        .pasteboards
+        .insert(name.to_string(), pasteboard);
+    pasteboard
+}
+
+/// If this is the general pasteboard and the host clipboard's text has changed
+/// since the last check, replace the pasteboard's content with that text.
+fn sync_from_host(env: &mut Environment, pasteboard: id) {
+    let Some(window) = env.window.as_ref() else {
+        return;
+    };
+    let host_object = env.objc.borrow_mut::<UIPasteboardHostObject>(pasteboard);
+    let Some(ref mut last_host_text) = host_object.host_text else {
+        return;
+    };
+    let host_text = window.clipboard_text();
+    if *last_host_text == host_text {
+        return;
+    }
+    *last_host_text = host_text.clone();
+    let Some(host_text) = host_text else {
+        return;
+    };
+
+    log_dbg!("Host clipboard text changed, updating general pasteboard");
+    let string = from_rust_string(env, host_text);
+    let host_object = env.objc.borrow_mut::<UIPasteboardHostObject>(pasteboard);
+    let old_items = std::mem::take(&mut host_object.items);
+    host_object
+        .items
+        .insert(kUTTypeUTF8PlainText.to_string(), string);
+    host_object.change_count += 1;
+    for (_type, old_value) in old_items {
+        release(env, old_value);
+    }
+}
+
+/// If this is the general pasteboard, put the text on the host clipboard.
+fn sync_to_host(env: &mut Environment, pasteboard: id, text: String) {
+    let Some(window) = env.window.as_ref() else {
+        return;
+    };
+    let host_object = env.objc.borrow_mut::<UIPasteboardHostObject>(pasteboard);
+    let Some(ref mut last_host_text) = host_object.host_text else {
+        return;
+    };
+    window.set_clipboard_text(&text);
+    *last_host_text = Some(text);
+}
@@ -4,11 +4,18 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIResponder`.
+//!
+//! Responders form a chain, which input and action messages travel up until
+//! something handles them. For views, the next responder is the view
+//! controller whose view it is, if any, otherwise the superview. The chain ends
+//! at the window and then the application.
 
-use crate::objc::{id, objc_classes, ClassExports};
+use super::ui_view::ui_keyboard;
+use crate::objc::{id, msg, nil, objc_classes, ClassExports, SEL};
 
 #[derive(Default)]
 pub struct State {
+    /// The current first responder, if any. This is a weak reference.
     pub(crate) first_responder: id,
 }
 
@@ -18,7 +25,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UIResponder: NSObject
 
-// TODO: real responder implementation etc
+- (id)nextResponder {
+    nil
+}
 
 // These methods print debug logs because they are only likely to get called if
 // a subclass didn't override them, which might mean we delivered the event to
@@ -54,21 +63,63 @@ pub const CLASSES: ClassExports = objc_classes! {
     );
 }
 
+- (bool)isFirstResponder {
+    env.framework_state.uikit.ui_responder.first_responder == this
+}
 - (bool)canBecomeFirstResponder {
     false
 }
 - (bool)becomeFirstResponder {
-    // TODO
-    false
+    let current: id = env.framework_state.uikit.ui_responder.first_responder;
+    if current == this {
+        return true;
+    }
+    if !msg![env; this canBecomeFirstResponder] {
+        return false;
+    }
+    // There can only be one first responder at a time.
+    if current != nil {
+        env.framework_state.uikit.ui_view.ui_keyboard.switching_responders = true;
+        let resigned: bool = msg![env; current resignFirstResponder];
+        env.framework_state.uikit.ui_view.ui_keyboard.switching_responders = false;
+        if !resigned {
+            return false;
+        }
+    }
+    env.framework_state.uikit.ui_responder.first_responder = this;
+    ui_keyboard::update_for_first_responder(env);
+    true
 }
 - (bool)canResignFirstResponder {
     true
 }
 - (bool)resignFirstResponder {
-    // TODO
+    if env.framework_state.uikit.ui_responder.first_responder == this {
+        env.framework_state.uikit.ui_responder.first_responder = nil;
+        ui_keyboard::update_for_first_responder(env);
+    }
     true
 }
 
+- (bool)canPerformAction:(SEL)action
+              withSender:(id)_sender {
+    msg![env; this respondsToSelector:action]
+}
+
+// Find the first responder in the chain, starting with this one, that can
+// perform an action. Used by UIApplication when an action has no target.
+- (id)targetForAction:(SEL)action
+           withSender:(id)sender {
+    let mut responder = this;
+    while responder != nil {
+        if msg![env; responder canPerformAction:action withSender:sender] {
+            return responder;
+        }
+        responder = msg![env; responder nextResponder];
+    }
+    nil
+}
+
 @end
 
 };
@@ -5,6 +5,7 @@
  */
 //! `UIScreen`.
 
+use super::ui_application::status_bar_frame;
 use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
 use crate::objc::{id, msg, objc_classes, ClassExports, TrivialHostObject};
 
@@ -49,13 +50,19 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (CGRect)applicationFrame {
-    let mut bounds: CGRect = msg![env; this bounds];
-    const STATUS_BAR_HEIGHT: f32 = 20.0;
-    if !env.framework_state.uikit.ui_application.status_bar_hidden {
-        bounds.origin.y += STATUS_BAR_HEIGHT;
-        bounds.size.height -= STATUS_BAR_HEIGHT;
+    // The part of the screen not covered by the status bar.
+    let mut frame: CGRect = msg![env; this bounds];
+    let status_bar = status_bar_frame(env);
+    if status_bar.size.width == frame.size.width {
+        frame.origin.y += status_bar.size.height;
+        frame.size.height -= status_bar.size.height;
+    } else {
+        if status_bar.origin.x == frame.origin.x {
+            frame.origin.x += status_bar.size.width;
+        }
+        frame.size.width -= status_bar.size.width;
     }
-    bounds
+    frame
 }
 
 @end
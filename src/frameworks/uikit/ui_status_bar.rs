@@ -0,0 +1,169 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Drawing of the status bar (carrier, time and battery level).
+//!
+//! On a real device the status bar isn't drawn by the app, so there's no class
+//! for it here. Instead, the Core Animation compositor draws it on top of the
+//! app's UI. The app can only see its frame and style, via `UIApplication`.
+
+use super::ui_application::{
+    status_bar_frame, visible_status_bar_style, UIStatusBarStyle, UIStatusBarStyleBlackOpaque,
+    UIStatusBarStyleBlackTranslucent, STATUS_BAR_HEIGHT,
+};
+use crate::font::{Font, TextAlignment};
+use crate::frameworks::core_foundation::time::{apple_epoch, CFAbsoluteTimeGetGregorianDate};
+use crate::frameworks::core_graphics::CGRect;
+use crate::libc::time::wall_clock_now;
+use crate::objc::msg_class;
+use crate::window::DeviceOrientation;
+use crate::Environment;
+use std::rc::Rc;
+
+const FONT_SIZE: f32 = 12.0;
+const PADDING: f32 = 6.0;
+
+/// The status bar's contents, rasterized as RGBA8 pixels with premultiplied
+/// alpha, in the orientation of the interface. The first row is the top.
+pub struct StatusBarImage {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(PartialEq)]
+struct ImageKey {
+    style: UIStatusBarStyle,
+    clock: String,
+    width: u32,
+    scale: u32,
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Loaded on first use.
+    font: Option<Font>,
+    /// Cached until the clock or style changes.
+    image: Option<(ImageKey, Rc<StatusBarImage>)>,
+}
+
+/// What the compositor needs to draw the status bar.
+pub struct StatusBar {
+    /// Frame in screen co-ordinates.
+    pub frame: CGRect,
+    /// The image's top is at the top of the interface, which is not the top of
+    /// the screen in landscape.
+    pub rotation: DeviceOrientation,
+    /// This is only redrawn when something changes, so the compositor can
+    /// compare pointers to see if it needs to upload it again.
+    pub image: Rc<StatusBarImage>,
+}
+
+/// Get the status bar to draw, if it is visible. `scale` is the scale hack
+/// factor.
+pub fn current(env: &mut Environment, scale: u32) -> Option<StatusBar> {
+    let style = visible_status_bar_style(env)?;
+    let frame = status_bar_frame(env);
+    let rotation = env
+        .window
+        .as_ref()
+        .map_or(DeviceOrientation::Portrait, |window| {
+            window.current_rotation()
+        });
+    let width = frame.size.width.max(frame.size.height) as u32;
+
+    let now = wall_clock_now(env)
+        .duration_since(apple_epoch())
+        .unwrap()
+        .as_secs_f64();
+    let time_zone = msg_class![env; NSTimeZone systemTimeZone];
+    let date = CFAbsoluteTimeGetGregorianDate(env, now, time_zone);
+    let clock = format!("{}:{:02}", date.hours, date.minutes);
+
+    let key = ImageKey {
+        style,
+        clock,
+        width,
+        scale,
+    };
+    let state = &mut env.framework_state.uikit.ui_status_bar;
+    let image = match state.image {
+        Some((ref cached_key, ref image)) if *cached_key == key => image.clone(),
+        _ => {
+            let font = state.font.get_or_insert_with(Font::sans_bold);
+            let image = Rc::new(rasterize(font, &key));
+            state.image = Some((key, image.clone()));
+            image
+        }
+    };
+
+    Some(StatusBar {
+        frame,
+        rotation,
+        image,
+    })
+}
+
+fn rasterize(font: &Font, key: &ImageKey) -> StatusBarImage {
+    let scale = key.scale as f32;
+    let width = key.width * key.scale;
+    let height = (STATUS_BAR_HEIGHT * scale) as u32;
+    let font_size = FONT_SIZE * scale;
+    let padding = PADDING * scale;
+
+    let mut coverage = vec![0f32; (width * height) as usize];
+    let (_, text_height) = font.calculate_text_size(font_size, &key.clock, None);
+    let y = (height as f32 - text_height) / 2.0;
+    for (text, x, alignment) in [
+        ("touchHLE", padding, TextAlignment::Left),
+        (
+            key.clock.as_str(),
+            width as f32 / 2.0,
+            TextAlignment::Center,
+        ),
+        ("100%", width as f32 - padding, TextAlignment::Right),
+    ] {
+        font.draw(font_size, text, (x, y), None, alignment, |raster_glyph| {
+            let (origin_x, origin_y) = raster_glyph.origin();
+            let (glyph_width, glyph_height) = raster_glyph.dimensions();
+            for glyph_y in 0..glyph_height {
+                for glyph_x in 0..glyph_width {
+                    let x = origin_x as i32 + glyph_x;
+                    let y = origin_y as i32 + glyph_y;
+                    if !(0..width as i32).contains(&x) || !(0..height as i32).contains(&y) {
+                        continue;
+                    }
+                    let pixel = &mut coverage[y as usize * width as usize + x as usize];
+                    *pixel = pixel.max(raster_glyph.pixel_at((glyph_x, glyph_y)));
+                }
+            }
+        });
+    }
+
+    // The default style is grey with black text, the others are black with
+    // white text.
+    let (background, background_alpha, text) = match key.style {
+        UIStatusBarStyleBlackOpaque => (0.0, 1.0, 1.0),
+        UIStatusBarStyleBlackTranslucent => (0.0, 0.5, 1.0),
+        _ => (0.7, 1.0, 0.0),
+    };
+    let pixels = coverage
+        .into_iter()
+        .flat_map(|coverage| {
+            let alpha = coverage + background_alpha * (1.0 - coverage);
+            // Premultiplied alpha, to match the blend function used for
+            // drawing.
+            let color = text * coverage + background * background_alpha * (1.0 - coverage);
+            let color = (color * 255.0) as u8;
+            [color, color, color, (alpha * 255.0) as u8]
+        })
+        .collect();
+
+    StatusBarImage {
+        pixels,
+        width,
+        height,
+    }
+}
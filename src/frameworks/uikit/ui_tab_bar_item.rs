@@ -0,0 +1,123 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITabBarItem`.
+
+use crate::frameworks::foundation::NSInteger;
+use crate::objc::{
+    id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+
+struct UITabBarItemHostObject {
+    /// `NSString*`
+    title: id,
+    /// `UIImage*`
+    image: id,
+    tag: NSInteger,
+    /// `NSString*`
+    badge_value: id,
+    enabled: bool,
+}
+impl HostObject for UITabBarItemHostObject {}
+impl Default for UITabBarItemHostObject {
+    fn default() -> Self {
+        UITabBarItemHostObject {
+            title: nil,
+            image: nil,
+            tag: 0,
+            badge_value: nil,
+            enabled: true,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// TODO: should extend UIBarItem
+@implementation UITabBarItem: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UITabBarItemHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithTitle:(id)title // NSString*
+              image:(id)image // UIImage*
+                tag:(NSInteger)tag {
+    () = msg![env; this setTitle:title];
+    () = msg![env; this setImage:image];
+    () = msg![env; this setTag:tag];
+    this
+}
+
+- (())dealloc {
+    let &UITabBarItemHostObject {
+        title,
+        image,
+        badge_value,
+        ..
+    } = env.objc.borrow(this);
+    release(env, title);
+    release(env, image);
+    release(env, badge_value);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)title {
+    env.objc.borrow::<UITabBarItemHostObject>(this).title
+}
+- (())setTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    let old_title = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITabBarItemHostObject>(this).title,
+        title
+    );
+    release(env, old_title);
+}
+
+- (id)image {
+    env.objc.borrow::<UITabBarItemHostObject>(this).image
+}
+- (())setImage:(id)image { // UIImage*
+    retain(env, image);
+    let old_image = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITabBarItemHostObject>(this).image,
+        image
+    );
+    release(env, old_image);
+}
+
+- (NSInteger)tag {
+    env.objc.borrow::<UITabBarItemHostObject>(this).tag
+}
+- (())setTag:(NSInteger)tag {
+    env.objc.borrow_mut::<UITabBarItemHostObject>(this).tag = tag;
+}
+
+- (id)badgeValue {
+    env.objc.borrow::<UITabBarItemHostObject>(this).badge_value
+}
+- (())setBadgeValue:(id)badge_value { // NSString*
+    // TODO: draw the badge
+    let badge_value: id = msg![env; badge_value copy];
+    let old_badge_value = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITabBarItemHostObject>(this).badge_value,
+        badge_value
+    );
+    release(env, old_badge_value);
+}
+
+- (bool)isEnabled {
+    env.objc.borrow::<UITabBarItemHostObject>(this).enabled
+}
+- (())setEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UITabBarItemHostObject>(this).enabled = enabled;
+}
+
+@end
+
+};
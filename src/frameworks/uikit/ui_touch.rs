@@ -15,8 +15,7 @@ use crate::objc::{
 };
 use crate::window::{Coords, Event, FingerId};
 use crate::Environment;
-use std::collections::hash_map::{Entry, HashMap};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub type UITouchPhase = NSInteger;
 pub const UITouchPhaseBegan: UITouchPhase = 0;
@@ -89,6 +88,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)view {
     env.objc.borrow::<UITouchHostObject>(this).view
 }
+- (id)window {
+    env.objc.borrow::<UITouchHostObject>(this).window
+}
 
 - (NSTimeInterval)timestamp {
     env.objc.borrow::<UITouchHostObject>(this).timestamp
@@ -205,8 +207,8 @@ fn handle_touches_down(env: &mut Environment, map: HashMap<FingerId, Coords>) {
         .map(|&touch| env.objc.borrow::<UITouchHostObject>(touch).view)
         .collect();
 
-    // view to set of touches for this view
-    let mut view_touches: HashMap<id, id> = HashMap::new();
+    // views with new touches (see isMultipleTouchEnabled check below)
+    let mut views_with_new_touches: HashSet<id> = HashSet::new();
 
     let touches_arr: id = msg![env; touches allObjects];
     let touches_count: NSUInteger = msg![env; touches_arr count];
@@ -245,7 +247,7 @@ fn handle_touches_down(env: &mut Environment, map: HashMap<FingerId, Coords>) {
             // there are no other touches currently associated with it, and if
             // there are multiple new touches for this view, we can only report
             // one of them.
-            let view_has_other_new_touches = view_touches.contains_key(&view);
+            let view_has_other_new_touches = views_with_new_touches.contains(&view);
             let view_has_existing_touches = views_with_existing_touches.contains(&view);
             if view_has_other_new_touches || view_has_existing_touches {
                 log!(
@@ -265,14 +267,7 @@ fn handle_touches_down(env: &mut Environment, map: HashMap<FingerId, Coords>) {
             }
         }
 
-        // Only create the set after the isMultipleTouchEnabled checks so we
-        // won't end up with an empty set.
-        if let Entry::Vacant(e) = view_touches.entry(view) {
-            let touches: id = msg_class![env; NSMutableSet allocWithZone:(MutVoidPtr::null())];
-            e.insert(touches);
-        }
-        let touches: id = *view_touches.get(&view).unwrap();
-        let _: () = msg![env; touches addObject:touch];
+        views_with_new_touches.insert(view);
 
         retain(env, view);
         retain(env, top_window);
@@ -283,15 +278,7 @@ fn handle_touches_down(env: &mut Environment, map: HashMap<FingerId, Coords>) {
         }
     }
 
-    for (view, touches) in view_touches {
-        log_dbg!(
-            "Sending [{:?} touchesBegan:{:?} withEvent:{:?}]",
-            view,
-            touches,
-            event
-        );
-        let _: () = msg![env; view touchesBegan:touches withEvent:event];
-    }
+    send_event_to_windows(env, &[top_window], event);
 
     release(env, pool);
 }
@@ -303,8 +290,8 @@ fn handle_touches_move(env: &mut Environment, map: HashMap<FingerId, Coords>) {
 
     let touches: id = msg_class![env; NSMutableSet allocWithZone:(MutVoidPtr::null())];
 
-    // view to set of touches for this view
-    let mut view_touches: HashMap<id, id> = HashMap::new();
+    // windows the touches belong to
+    let mut windows: Vec<id> = Vec::new();
 
     for (finger_id, coords) in map {
         let Some(&touch) = env
@@ -328,7 +315,6 @@ fn handle_touches_move(env: &mut Environment, map: HashMap<FingerId, Coords>) {
             y: coords.1,
         };
 
-        let view = env.objc.borrow::<UITouchHostObject>(touch).view;
         let host_object = env.objc.borrow_mut::<UITouchHostObject>(touch);
         host_object.previous_location = host_object.location;
         host_object.location = location;
@@ -336,28 +322,18 @@ fn handle_touches_move(env: &mut Environment, map: HashMap<FingerId, Coords>) {
         assert_eq!(host_object.phase, UITouchPhaseStationary);
         host_object.phase = UITouchPhaseMoved;
 
-        let _: () = msg![env; touches addObject:touch];
-
-        if let Entry::Vacant(e) = view_touches.entry(view) {
-            let touches: id = msg_class![env; NSMutableSet allocWithZone:(MutVoidPtr::null())];
-            e.insert(touches);
+        let window = host_object.window;
+        if window != nil && !windows.contains(&window) {
+            windows.push(window);
         }
-        let touches: id = *view_touches.get(&view).unwrap();
+
         let _: () = msg![env; touches addObject:touch];
     }
 
     let event = ui_event::new_event(env, touches);
     autorelease(env, event);
 
-    for (view, touches) in view_touches {
-        log_dbg!(
-            "Sending [{:?} touchesMoved:{:?} withEvent:{:?}]",
-            view,
-            touches,
-            event
-        );
-        let _: () = msg![env; view touchesMoved:touches withEvent:event];
-    }
+    send_event_to_windows(env, &windows, event);
 
     release(env, pool);
 }
@@ -369,8 +345,8 @@ fn handle_touches_up(env: &mut Environment, map: HashMap<FingerId, Coords>) {
 
     let touches: id = msg_class![env; NSMutableSet allocWithZone:(MutVoidPtr::null())];
 
-    // view to set of touches for this view
-    let mut view_touches: HashMap<id, id> = HashMap::new();
+    // windows the touches belong to
+    let mut windows: Vec<id> = Vec::new();
 
     for (finger_id, coords) in map {
         let Some(&touch) = env
@@ -394,7 +370,6 @@ fn handle_touches_up(env: &mut Environment, map: HashMap<FingerId, Coords>) {
             y: coords.1,
         };
 
-        let view = env.objc.borrow::<UITouchHostObject>(touch).view;
         let host_object = env.objc.borrow_mut::<UITouchHostObject>(touch);
         host_object.previous_location = host_object.location;
         host_object.location = location;
@@ -402,13 +377,11 @@ fn handle_touches_up(env: &mut Environment, map: HashMap<FingerId, Coords>) {
         assert_eq!(host_object.phase, UITouchPhaseStationary);
         host_object.phase = UITouchPhaseEnded;
 
-        let _: () = msg![env; touches addObject:touch];
-
-        if let Entry::Vacant(e) = view_touches.entry(view) {
-            let touches: id = msg_class![env; NSMutableSet allocWithZone:(MutVoidPtr::null())];
-            e.insert(touches);
+        let window = host_object.window;
+        if window != nil && !windows.contains(&window) {
+            windows.push(window);
         }
-        let touches: id = *view_touches.get(&view).unwrap();
+
         let _: () = msg![env; touches addObject:touch];
 
         let _ = &env
@@ -423,15 +396,15 @@ fn handle_touches_up(env: &mut Environment, map: HashMap<FingerId, Coords>) {
     let event = ui_event::new_event(env, touches);
     autorelease(env, event);
 
-    for (view, touches) in view_touches {
-        log_dbg!(
-            "Sending [{:?} touchesEnded:{:?} withEvent:{:?}]",
-            view,
-            touches,
-            event
-        );
-        let _: () = msg![env; view touchesEnded:touches withEvent:event];
-    }
+    send_event_to_windows(env, &windows, event);
 
     release(env, pool);
 }
+
+fn send_event_to_windows(env: &mut Environment, windows: &[id], event: id) {
+    // The windows deliver the touches to their views.
+    for &window in windows {
+        log_dbg!("Sending [{:?} sendEvent:{:?}]", window, event);
+        let _: () = msg![env; window sendEvent:event];
+    }
+}
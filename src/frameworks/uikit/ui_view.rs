@@ -8,41 +8,72 @@
 //! Useful resources:
 //! - Apple's [View Programming Guide for iOS](https://developer.apple.com/library/archive/documentation/WindowsViews/Conceptual/ViewPG_iPhoneOS/Introduction/Introduction.html)
 
+pub mod ui_action_sheet;
 pub mod ui_alert_view;
 pub mod ui_control;
 pub mod ui_image_view;
+pub mod ui_keyboard;
 pub mod ui_label;
+pub mod ui_scroll_view;
+pub mod ui_tab_bar;
 pub mod ui_window;
 
-use super::ui_graphics::{UIGraphicsPopContext, UIGraphicsPushContext};
+use super::ui_graphics::{
+    UIGraphicsGetCurrentContext, UIGraphicsPopContext, UIGraphicsPushContext,
+};
+use crate::frameworks::core_animation;
 use crate::frameworks::core_graphics::cg_affine_transform::CGAffineTransform;
-use crate::frameworks::core_graphics::cg_context::{CGContextClearRect, CGContextRef};
-use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::core_graphics::cg_color::CGColorRef;
+use crate::frameworks::core_graphics::cg_context::{
+    CGContextClearRect, CGContextRef, CGContextRestoreGState, CGContextSaveGState,
+    CGContextScaleCTM, CGContextTranslateCTM,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::foundation::{ns_array, NSInteger, NSUInteger};
 use crate::objc::{
-    autorelease, id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject,
-    NSZonePtr,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject, NSZonePtr,
 };
 use crate::Environment;
 
+pub type UIViewAutoresizing = NSUInteger;
+pub const UIViewAutoresizingNone: UIViewAutoresizing = 0;
+pub const UIViewAutoresizingFlexibleLeftMargin: UIViewAutoresizing = 1 << 0;
+pub const UIViewAutoresizingFlexibleWidth: UIViewAutoresizing = 1 << 1;
+pub const UIViewAutoresizingFlexibleRightMargin: UIViewAutoresizing = 1 << 2;
+pub const UIViewAutoresizingFlexibleTopMargin: UIViewAutoresizing = 1 << 3;
+pub const UIViewAutoresizingFlexibleHeight: UIViewAutoresizing = 1 << 4;
+pub const UIViewAutoresizingFlexibleBottomMargin: UIViewAutoresizing = 1 << 5;
+
 #[derive(Default)]
 pub struct State {
     /// List of views for internal purposes. Non-retaining!
     pub(super) views: Vec<id>,
     pub ui_window: ui_window::State,
+    pub ui_keyboard: ui_keyboard::State,
 }
 
-pub(super) struct UIViewHostObject {
+pub struct UIViewHostObject {
     /// CALayer or subclass.
     layer: id,
     /// Subviews in back-to-front order. These are strong references.
     subviews: Vec<id>,
     /// The superview. This is a weak reference.
     superview: id,
+    /// The view controller this is the view of, if any. This is a weak
+    /// reference.
+    pub(super) view_controller: id,
+    /// `UIColor*`. The layer has the equivalent `CGColorRef`.
+    background_color: id,
     clears_context_before_drawing: bool,
     user_interaction_enabled: bool,
     multiple_touch_enabled: bool,
+    autoresizing_mask: UIViewAutoresizing,
+    autoresizes_subviews: bool,
+    /// Whether `layoutSubviews` should be called in the next layout pass.
+    needs_layout: bool,
+    tag: NSInteger,
 }
 impl HostObject for UIViewHostObject {}
 impl Default for UIViewHostObject {
@@ -53,9 +84,16 @@ impl Default for UIViewHostObject {
             layer: nil,
             subviews: Vec::new(),
             superview: nil,
+            view_controller: nil,
+            background_color: nil,
             clears_context_before_drawing: true,
             user_interaction_enabled: true,
             multiple_touch_enabled: false,
+            autoresizing_mask: UIViewAutoresizingNone,
+            autoresizes_subviews: true,
+            // New views are laid out before they are first drawn.
+            needs_layout: true,
+            tag: 0,
         }
     }
 }
@@ -140,18 +178,26 @@ pub const CLASSES: ClassExports = objc_classes! {
     let key_ns_string = get_static_str(env, "UIOpaque");
     let opaque: bool = msg![env; coder decodeBoolForKey:key_ns_string];
 
+    // TODO: decode UIAutoresizeSubviews (needs containsValueForKey:)
+    let key_ns_string = get_static_str(env, "UIAutoresizingMask");
+    let autoresizing_mask: NSUInteger = msg![env; coder decodeIntegerForKey:key_ns_string] as _;
+
+    let key_ns_string = get_static_str(env, "UITag");
+    let tag: NSInteger = msg![env; coder decodeIntegerForKey:key_ns_string];
+
     let key_ns_string = get_static_str(env, "UISubviews");
     let subviews: id = msg![env; coder decodeObjectForKey:key_ns_string];
     let subview_count: NSUInteger = msg![env; subviews count];
 
     log_dbg!(
-        "[(UIView*){:?} initWithCoder:{:?}] => bounds {}, center {}, hidden {}, opaque {}, {} subviews",
+        "[(UIView*){:?} initWithCoder:{:?}] => bounds {}, center {}, hidden {}, opaque {}, tag {}, {} subviews",
         this,
         coder,
         bounds,
         center,
         hidden,
         opaque,
+        tag,
         subview_count,
     );
 
@@ -159,6 +205,8 @@ pub const CLASSES: ClassExports = objc_classes! {
     () = msg![env; this setCenter:center];
     () = msg![env; this setHidden:hidden];
     () = msg![env; this setOpaque:opaque];
+    () = msg![env; this setAutoresizingMask:autoresizing_mask];
+    () = msg![env; this setTag:tag];
 
     for i in 0..subview_count {
         let subview: id = msg![env; subviews objectAtIndex:i];
@@ -186,11 +234,42 @@ pub const CLASSES: ClassExports = objc_classes! {
     // On iOS 5.1 and earlier, the default implementation of this method does
     // nothing.
 }
+- (())setNeedsLayout {
+    env.objc.borrow_mut::<UIViewHostObject>(this).needs_layout = true;
+}
+- (())layoutIfNeeded {
+    layout_if_needed(env, this);
+}
 
 - (id)superview {
     env.objc.borrow::<UIViewHostObject>(this).superview
 }
 
+- (id)window {
+    let window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
+    let mut view = this;
+    while view != nil {
+        if msg![env; view isKindOfClass:window_class] {
+            return view;
+        }
+        view = env.objc.borrow::<UIViewHostObject>(view).superview;
+    }
+    nil
+}
+
+- (id)nextResponder {
+    let &UIViewHostObject {
+        view_controller,
+        superview,
+        ..
+    } = env.objc.borrow(this);
+    if view_controller != nil {
+        view_controller
+    } else {
+        superview
+    }
+}
+
 - (id)subviews {
     let views = env.objc.borrow::<UIViewHostObject>(this).subviews.clone();
     for view in &views {
@@ -200,6 +279,29 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, subs)
 }
 
+- (NSInteger)tag {
+    env.objc.borrow::<UIViewHostObject>(this).tag
+}
+- (())setTag:(NSInteger)tag {
+    env.objc.borrow_mut::<UIViewHostObject>(this).tag = tag;
+}
+
+- (id)viewWithTag:(NSInteger)tag {
+    // Depth-first search, starting with the receiver itself.
+    let this_tag: NSInteger = msg![env; this tag];
+    if this_tag == tag {
+        return this;
+    }
+    let subviews = env.objc.borrow::<UIViewHostObject>(this).subviews.clone();
+    for subview in subviews {
+        let view: id = msg![env; subview viewWithTag:tag];
+        if view != nil {
+            return view;
+        }
+    }
+    nil
+}
+
 - (())addSubview:(id)view {
     log_dbg!("[(UIView*){:?} addSubview:{:?}] => ()", this, view);
 
@@ -219,6 +321,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         let this_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
         this_obj.subviews.push(view);
         let this_layer = this_obj.layer;
+        this_obj.needs_layout = true;
         () = msg![env; this_layer addSublayer:subview_layer];
     }
 }
@@ -271,18 +374,29 @@ pub const CLASSES: ClassExports = objc_classes! {
         layer,
         superview,
         subviews,
+        view_controller: _,
+        background_color,
         clears_context_before_drawing: _,
         user_interaction_enabled: _,
         multiple_touch_enabled: _,
+        autoresizing_mask: _,
+        autoresizes_subviews: _,
+        needs_layout: _,
+        tag: _,
     } = std::mem::take(env.objc.borrow_mut(this));
 
     release(env, layer);
+    release(env, background_color);
     assert!(superview == nil);
     for subview in subviews {
         env.objc.borrow_mut::<UIViewHostObject>(subview).superview = nil;
         release(env, subview);
     }
 
+    if env.framework_state.uikit.ui_responder.first_responder == this {
+        env.framework_state.uikit.ui_responder.first_responder = nil;
+    }
+
     env.framework_state.uikit.ui_view.views.swap_remove(
         env.framework_state.uikit.ui_view.views.iter().position(|&v| v == this).unwrap()
     );
@@ -303,6 +417,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; layer setHidden:hidden]
 }
 
+- (bool)clipsToBounds {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer masksToBounds]
+}
+- (())setClipsToBounds:(bool)clips_to_bounds {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer setMasksToBounds:clips_to_bounds]
+}
+
 - (bool)isOpaque {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer isOpaque]
@@ -321,24 +444,31 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; layer setOpacity:alpha]
 }
 
-// FIXME: CALayer's backgroundColor should be a CGColorRef, which is supposedly
-// a separate type from UIColor. For now we have not implemented it and treat
-// them as the same type (and it seems like UIKit itself maybe did this once),
-// but eventually we'll have to do this properly.
 - (id)backgroundColor {
-    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
-    msg![env; layer backgroundColor]
+    env.objc.borrow::<UIViewHostObject>(this).background_color
 }
 - (())setBackgroundColor:(id)color { // UIColor*
-    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
-    msg![env; layer setBackgroundColor:color]
+    retain(env, color);
+    let host_object = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let old_color = std::mem::replace(&mut host_object.background_color, color);
+    let layer = host_object.layer;
+    release(env, old_color);
+    let cg_color: CGColorRef = if color == nil {
+        nil
+    } else {
+        msg![env; color CGColor]
+    };
+    msg![env; layer setBackgroundColor:cg_color]
 }
 
-// TODO: support setNeedsDisplayInRect:
 - (())setNeedsDisplay {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer setNeedsDisplay]
 }
+- (())setNeedsDisplayInRect:(CGRect)rect {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer setNeedsDisplayInRect:rect]
+}
 
 - (CGRect)bounds {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
@@ -346,7 +476,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 - (())setBounds:(CGRect)bounds {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
-    msg![env; layer setBounds:bounds]
+    let old_bounds: CGRect = msg![env; layer bounds];
+    () = msg![env; layer setBounds:bounds];
+    autoresize_subviews(env, this, old_bounds.size, bounds.size);
 }
 - (CGPoint)center {
     // FIXME: what happens if [layer anchorPoint] isn't (0.5, 0.5)?
@@ -363,11 +495,46 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 - (())setFrame:(CGRect)frame {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
-    msg![env; layer setFrame:frame]
+    let old_bounds: CGRect = msg![env; layer bounds];
+    () = msg![env; layer setFrame:frame];
+    // The bounds don't change if the view has a transform.
+    let new_bounds: CGRect = msg![env; layer bounds];
+    autoresize_subviews(env, this, old_bounds.size, new_bounds.size);
+}
+
+- (CGSize)sizeThatFits:(CGSize)_size {
+    // Default implementation: subclasses with natural sizes override this.
+    let bounds: CGRect = msg![env; this bounds];
+    bounds.size
+}
+- (())sizeToFit {
+    let bounds: CGRect = msg![env; this bounds];
+    let size: CGSize = msg![env; this sizeThatFits:(bounds.size)];
+    let mut frame: CGRect = msg![env; this frame];
+    frame.size = size;
+    () = msg![env; this setFrame:frame];
+}
+
+- (UIViewAutoresizing)autoresizingMask {
+    env.objc.borrow::<UIViewHostObject>(this).autoresizing_mask
+}
+- (())setAutoresizingMask:(UIViewAutoresizing)mask {
+    env.objc.borrow_mut::<UIViewHostObject>(this).autoresizing_mask = mask;
+}
+- (bool)autoresizesSubviews {
+    env.objc.borrow::<UIViewHostObject>(this).autoresizes_subviews
+}
+- (())setAutoresizesSubviews:(bool)autoresizes {
+    env.objc.borrow_mut::<UIViewHostObject>(this).autoresizes_subviews = autoresizes;
 }
 
+- (CGAffineTransform)transform {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer affineTransform]
+}
 - (())setTransform:(CGAffineTransform)transform {
-    log!("TODO: [{:?} setTransform:{:?}]", this, transform);
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    () = msg![env; layer setAffineTransform:transform];
 }
 
 - (())setContentMode:(NSInteger)content_mode { // should be UIViewContentMode
@@ -386,6 +553,34 @@ pub const CLASSES: ClassExports = objc_classes! {
     // default implementation does nothing
 }
 
+// Snapshots the view and its subviews into the current graphics context,
+// scaled so that the view's bounds fill `rect`.
+- (bool)drawViewHierarchyInRect:(CGRect)rect
+             afterScreenUpdates:(bool)_after_updates {
+    let context = UIGraphicsGetCurrentContext(env);
+    if context == nil {
+        log!("Warning: [(UIView*){:?} drawViewHierarchyInRect:{:?}] called without a current context, ignoring", this, rect);
+        return false;
+    }
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    let bounds: CGRect = msg![env; layer bounds];
+    if bounds.size.width == 0.0 || bounds.size.height == 0.0 {
+        return false;
+    }
+    CGContextSaveGState(env, context);
+    CGContextTranslateCTM(env, context, rect.origin.x, rect.origin.y);
+    CGContextScaleCTM(
+        env,
+        context,
+        rect.size.width / bounds.size.width,
+        rect.size.height / bounds.size.height,
+    );
+    CGContextTranslateCTM(env, context, -bounds.origin.x, -bounds.origin.y);
+    () = msg![env; layer renderInContext:context];
+    CGContextRestoreGState(env, context);
+    true
+}
+
 // CALayerDelegate implementation
 - (())drawLayer:(id)layer // CALayer*
       inContext:(CGContextRef)context {
@@ -399,6 +594,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     UIGraphicsPopContext(env);
 }
 
+- (id)actionForLayer:(id)_layer // CALayer*
+              forKey:(id)_key { // NSString*
+    // Changes made to a view's layer aren't implicitly animated.
+    // TODO: return an animation inside UIView animation blocks
+    msg_class![env; NSNull null]
+}
+
 // Event handling
 
 - (bool)pointInside:(CGPoint)point
@@ -409,24 +611,21 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (id)hitTest:(CGPoint)point
     withEvent:(id)event { // UIEvent* (possibly nil)
+    // Views that are hidden, don't accept interaction, or are almost entirely
+    // transparent can't be hit, and neither can their subviews.
+    let hidden: bool = msg![env; this isHidden];
+    let alpha: CGFloat = msg![env; this alpha];
+    let interactible: bool = msg![env; this isUserInteractionEnabled];
+    if hidden || alpha < 0.01 || !interactible {
+        return nil;
+    }
     if !msg![env; this pointInside:point withEvent:event] {
         return nil;
     }
     // TODO: avoid copy somehow?
     let subviews = env.objc.borrow::<UIViewHostObject>(this).subviews.clone();
     for subview in subviews.into_iter().rev() { // later views are on top
-        let hidden: bool = msg![env; subview isHidden];
-        let alpha: CGFloat = msg![env; subview alpha];
-        let interactible: bool = msg![env; this isUserInteractionEnabled];
-        if hidden || alpha < 0.01 || !interactible {
-           continue;
-        }
-        let frame: CGRect = msg![env; subview frame];
-        let bounds: CGRect = msg![env; subview bounds];
-        let point = CGPoint {
-            x: point.x - frame.origin.x + bounds.origin.x,
-            y: point.y - frame.origin.y + bounds.origin.y,
-        };
+        let point: CGPoint = msg![env; subview convertPoint:point fromView:this];
         let subview: id = msg![env; subview hitTest:point withEvent:event];
         if subview != nil {
             return subview;
@@ -458,21 +657,223 @@ pub const CLASSES: ClassExports = objc_classes! {
 // Co-ordinate space conversion
 
 - (CGPoint)convertPoint:(CGPoint)point
-               fromView:(id)other { // UIView*
-    assert!(other != nil); // TODO
+               fromView:(id)other { // UIView* (possibly nil)
+    let other = if other == nil { window_or_root_view(env, this) } else { other };
     let this_layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     let other_layer = env.objc.borrow::<UIViewHostObject>(other).layer;
     msg![env; this_layer convertPoint:point fromLayer:other_layer]
 }
 
 - (CGPoint)convertPoint:(CGPoint)point
-                 toView:(id)other { // UIView*
-    assert!(other != nil); // TODO
+                 toView:(id)other { // UIView* (possibly nil)
+    let other = if other == nil { window_or_root_view(env, this) } else { other };
     let this_layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     let other_layer = env.objc.borrow::<UIViewHostObject>(other).layer;
     msg![env; this_layer convertPoint:point toLayer:other_layer]
 }
 
+- (CGRect)convertRect:(CGRect)rect
+             fromView:(id)other { // UIView* (possibly nil)
+    let corners = rect_corners(rect).map(|corner| -> CGPoint {
+        msg![env; this convertPoint:corner fromView:other]
+    });
+    bounding_box(corners)
+}
+
+- (CGRect)convertRect:(CGRect)rect
+               toView:(id)other { // UIView* (possibly nil)
+    let corners = rect_corners(rect).map(|corner| -> CGPoint {
+        msg![env; this convertPoint:corner toView:other]
+    });
+    bounding_box(corners)
+}
+
 @end
 
 };
+
+/// Find the window a view is in, for co-ordinate conversions to and from
+/// `nil`. If the view isn't in a window, the root of its hierarchy is used.
+fn window_or_root_view(env: &mut Environment, view: id) -> id {
+    let mut view = view;
+    loop {
+        let superview = env.objc.borrow::<UIViewHostObject>(view).superview;
+        if superview == nil {
+            return view;
+        }
+        view = superview;
+    }
+}
+
+/// Apply the subviews' autoresizing masks after a view's size has changed.
+fn autoresize_subviews(env: &mut Environment, view: id, old_size: CGSize, new_size: CGSize) {
+    if old_size == new_size {
+        return;
+    }
+    // Any change of size also means the view needs to be laid out again.
+    env.objc.borrow_mut::<UIViewHostObject>(view).needs_layout = true;
+    let &UIViewHostObject {
+        ref subviews,
+        autoresizes_subviews,
+        ..
+    } = env.objc.borrow(view);
+    if !autoresizes_subviews {
+        return;
+    }
+    for subview in subviews.clone() {
+        let mask = env
+            .objc
+            .borrow::<UIViewHostObject>(subview)
+            .autoresizing_mask;
+        if mask == UIViewAutoresizingNone {
+            continue;
+        }
+        let frame: CGRect = msg![env; subview frame];
+        let (x, width) = autoresize_axis(
+            (frame.origin.x, frame.size.width),
+            (old_size.width, new_size.width),
+            (
+                mask & UIViewAutoresizingFlexibleLeftMargin != 0,
+                mask & UIViewAutoresizingFlexibleWidth != 0,
+                mask & UIViewAutoresizingFlexibleRightMargin != 0,
+            ),
+        );
+        let (y, height) = autoresize_axis(
+            (frame.origin.y, frame.size.height),
+            (old_size.height, new_size.height),
+            (
+                mask & UIViewAutoresizingFlexibleTopMargin != 0,
+                mask & UIViewAutoresizingFlexibleHeight != 0,
+                mask & UIViewAutoresizingFlexibleBottomMargin != 0,
+            ),
+        );
+        let new_frame = CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        };
+        if new_frame != frame {
+            () = msg![env; subview setFrame:new_frame];
+        }
+    }
+}
+
+/// Resize one axis of a subview. The change in the superview's size is shared
+/// between the flexible parts (margins and size) in proportion to their
+/// current sizes, like UIKit does.
+fn autoresize_axis(
+    (origin, size): (CGFloat, CGFloat),
+    (old_super_size, new_super_size): (CGFloat, CGFloat),
+    (flexible_start, flexible_size, flexible_end): (bool, bool, bool),
+) -> (CGFloat, CGFloat) {
+    let delta = new_super_size - old_super_size;
+    let end = old_super_size - origin - size;
+    let parts = [
+        (flexible_start, origin),
+        (flexible_size, size),
+        (flexible_end, end),
+    ];
+    let flexible_count = parts.iter().filter(|&&(flexible, _)| flexible).count();
+    if flexible_count == 0 {
+        return (origin, size);
+    }
+    let flexible_total: CGFloat = parts
+        .iter()
+        .filter(|&&(flexible, _)| flexible)
+        .map(|&(_, part)| part)
+        .sum();
+    let share = |(flexible, part): (bool, CGFloat)| -> CGFloat {
+        if !flexible {
+            0.0
+        } else if flexible_total != 0.0 {
+            delta * part / flexible_total
+        } else {
+            delta / flexible_count as CGFloat
+        }
+    };
+    (origin + share(parts[0]), (size + share(parts[1])).max(0.0))
+}
+
+fn rect_corners(rect: CGRect) -> [CGPoint; 4] {
+    let CGRect { origin, size } = rect;
+    [
+        origin,
+        CGPoint {
+            x: origin.x + size.width,
+            y: origin.y,
+        },
+        CGPoint {
+            x: origin.x,
+            y: origin.y + size.height,
+        },
+        CGPoint {
+            x: origin.x + size.width,
+            y: origin.y + size.height,
+        },
+    ]
+}
+
+/// Get the smallest rectangle containing all the points. If there are
+/// transforms involved, a converted rectangle is the bounding box of its
+/// converted corners, like a frame.
+fn bounding_box(points: [CGPoint; 4]) -> CGRect {
+    let (mut min_x, mut min_y) = (CGFloat::INFINITY, CGFloat::INFINITY);
+    let (mut max_x, mut max_y) = (CGFloat::NEG_INFINITY, CGFloat::NEG_INFINITY);
+    for CGPoint { x, y } in points {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    CGRect {
+        origin: CGPoint { x: min_x, y: min_y },
+        size: CGSize {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    }
+}
+
+/// Implementation of `layoutIfNeeded`: call `layoutSubviews` on the view and
+/// then its subviews, top-down, if they need layout.
+fn layout_if_needed(env: &mut Environment, view: id) {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(view);
+    if std::mem::take(&mut host_obj.needs_layout) {
+        () = msg![env; view layoutSubviews];
+    }
+
+    // Subviews might be removed during layout, so they need to be kept alive
+    // until we're done.
+    let subviews = env.objc.borrow::<UIViewHostObject>(view).subviews.clone();
+    for &subview in &subviews {
+        retain(env, subview);
+    }
+    for subview in subviews {
+        layout_if_needed(env, subview);
+        release(env, subview);
+    }
+}
+
+/// The layout and display pass that happens before each frame (at the end of
+/// each run loop iteration on the real iPhone OS): views in visible windows
+/// that need layout get `layoutSubviews`, top-down, and then layers that need
+/// display are drawn.
+pub fn layout_and_display_views(env: &mut Environment) {
+    let windows = env
+        .framework_state
+        .uikit
+        .ui_view
+        .ui_window
+        .visible_windows
+        .clone();
+    for &window in &windows {
+        retain(env, window);
+    }
+    for &window in &windows {
+        layout_if_needed(env, window);
+    }
+    for window in windows {
+        let layer = env.objc.borrow::<UIViewHostObject>(window).layer;
+        core_animation::display_layers(env, layer);
+        release(env, window);
+    }
+}
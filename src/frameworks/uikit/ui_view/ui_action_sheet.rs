@@ -0,0 +1,376 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIActionSheet`.
+//!
+//! Useful resources:
+//! - [UIActionSheetDelegate overview](https://developer.apple.com/documentation/uikit/uiactionsheetdelegate?language=objc)
+
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::NSInteger;
+use crate::frameworks::uikit::ui_font::UITextAlignmentCenter;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes, release,
+    retain, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+type UIActionSheetStyle = NSInteger;
+
+const MARGIN: CGFloat = 10.0;
+const TITLE_HEIGHT: CGFloat = 30.0;
+const BUTTON_HEIGHT: CGFloat = 44.0;
+const BUTTON_SPACING: CGFloat = 8.0;
+
+struct UIActionSheetHostObject {
+    superclass: super::UIViewHostObject,
+    /// Weak reference.
+    delegate: id,
+    /// `NSString*`
+    title: id,
+    /// `NSString*`s
+    button_titles: Vec<id>,
+    cancel_button_index: NSInteger,
+    destructive_button_index: NSInteger,
+    first_other_button_index: NSInteger,
+    /// Frame of each button, by index, while the sheet is shown.
+    button_frames: Vec<CGRect>,
+}
+impl_HostObject_with_superclass!(UIActionSheetHostObject);
+impl Default for UIActionSheetHostObject {
+    fn default() -> Self {
+        UIActionSheetHostObject {
+            superclass: Default::default(),
+            delegate: nil,
+            title: nil,
+            button_titles: Vec::new(),
+            cancel_button_index: -1,
+            destructive_button_index: -1,
+            first_other_button_index: -1,
+            button_frames: Vec::new(),
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIActionSheet: UIView
+
+// TODO: sliding animation, landscape, popovers
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIActionSheetHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithTitle:(id)title // NSString*
+           delegate:(id)delegate
+  cancelButtonTitle:(id)cancel_title // NSString*
+destructiveButtonTitle:(id)destructive_title // NSString*
+  otherButtonTitles:(id)first_other_title, ...other_titles { // NSString*
+    let this: id = msg![env; this initWithFrame:(CGRect::default())];
+    () = msg![env; this setTitle:title];
+    () = msg![env; this setDelegate:delegate];
+
+    // The destructive button goes first and the cancel button goes last.
+    if destructive_title != nil {
+        let index: NSInteger = msg![env; this addButtonWithTitle:destructive_title];
+        env.objc.borrow_mut::<UIActionSheetHostObject>(this).destructive_button_index = index;
+    }
+    if first_other_title != nil {
+        let index: NSInteger = msg![env; this addButtonWithTitle:first_other_title];
+        env.objc.borrow_mut::<UIActionSheetHostObject>(this).first_other_button_index = index;
+        let mut varargs = other_titles.start();
+        loop {
+            let other_title: id = varargs.next(env);
+            if other_title == nil {
+                break;
+            }
+            let _: NSInteger = msg![env; this addButtonWithTitle:other_title];
+        }
+    }
+    if cancel_title != nil {
+        let index: NSInteger = msg![env; this addButtonWithTitle:cancel_title];
+        env.objc.borrow_mut::<UIActionSheetHostObject>(this).cancel_button_index = index;
+    }
+
+    let background: id = msg_class![env; UIColor colorWithWhite:(0.1 as CGFloat)
+                                                          alpha:(0.8 as CGFloat)];
+    () = msg![env; this setBackgroundColor:background];
+
+    this
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<UIActionSheetHostObject>(this);
+    let title = host_object.title;
+    let button_titles = std::mem::take(&mut host_object.button_titles);
+    release(env, title);
+    for button_title in button_titles {
+        release(env, button_title);
+    }
+    msg_super![env; this dealloc]
+}
+
+// weak/non-retaining
+- (id)delegate {
+    env.objc.borrow::<UIActionSheetHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing UIActionSheetDelegate
+    env.objc.borrow_mut::<UIActionSheetHostObject>(this).delegate = delegate;
+}
+
+- (id)title {
+    env.objc.borrow::<UIActionSheetHostObject>(this).title
+}
+- (())setTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    let old_title = std::mem::replace(
+        &mut env.objc.borrow_mut::<UIActionSheetHostObject>(this).title,
+        title
+    );
+    release(env, old_title);
+}
+
+- (())setActionSheetStyle:(UIActionSheetStyle)_style {
+    // TODO: styles other than the default
+}
+
+- (NSInteger)addButtonWithTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    let button_titles = &mut env.objc.borrow_mut::<UIActionSheetHostObject>(this).button_titles;
+    button_titles.push(title);
+    (button_titles.len() - 1).try_into().unwrap()
+}
+- (NSInteger)numberOfButtons {
+    env.objc.borrow::<UIActionSheetHostObject>(this).button_titles.len().try_into().unwrap()
+}
+- (id)buttonTitleAtIndex:(NSInteger)index {
+    let button_titles = &env.objc.borrow::<UIActionSheetHostObject>(this).button_titles;
+    usize::try_from(index).ok().and_then(|index| button_titles.get(index)).copied().unwrap_or(nil)
+}
+
+- (NSInteger)cancelButtonIndex {
+    env.objc.borrow::<UIActionSheetHostObject>(this).cancel_button_index
+}
+- (())setCancelButtonIndex:(NSInteger)index {
+    env.objc.borrow_mut::<UIActionSheetHostObject>(this).cancel_button_index = index;
+}
+- (NSInteger)destructiveButtonIndex {
+    env.objc.borrow::<UIActionSheetHostObject>(this).destructive_button_index
+}
+- (())setDestructiveButtonIndex:(NSInteger)index {
+    env.objc.borrow_mut::<UIActionSheetHostObject>(this).destructive_button_index = index;
+}
+- (NSInteger)firstOtherButtonIndex {
+    env.objc.borrow::<UIActionSheetHostObject>(this).first_other_button_index
+}
+
+- (bool)isVisible {
+    let superview: id = msg![env; this superview];
+    superview != nil
+}
+
+- (())showInView:(id)view { // UIView*
+    show(env, this, view);
+}
+- (())showFromToolbar:(id)toolbar { // UIToolbar*
+    show(env, this, toolbar);
+}
+- (())showFromTabBar:(id)tab_bar { // UITabBar*
+    show(env, this, tab_bar);
+}
+
+- (())dismissWithClickedButtonIndex:(NSInteger)index animated:(bool)_animated {
+    dismiss(env, this, index);
+}
+
+- (())touchesBegan:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    // Buttons are pressed when the touch ends.
+}
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    let location: CGPoint = msg![env; touch locationInView:this];
+    let _: NSInteger = msg![env; this pressButtonAtPoint:location];
+}
+
+// Not public API. Returns the index of the button that was pressed, or -1 if
+// there was no button at the point.
+- (NSInteger)pressButtonAtPoint:(CGPoint)point {
+    let index = env
+        .objc
+        .borrow::<UIActionSheetHostObject>(this)
+        .button_frames
+        .iter()
+        .position(|&frame| rect_contains_point(frame, point));
+    let Some(index) = index else {
+        return -1;
+    };
+    let index: NSInteger = index.try_into().unwrap();
+    log_dbg!("Action sheet {:?} button {} pressed", this, index);
+
+    let delegate = env.objc.borrow::<UIActionSheetHostObject>(this).delegate;
+    if delegate_implements(env, delegate, "actionSheet:clickedButtonAtIndex:") {
+        () = msg![env; delegate actionSheet:this clickedButtonAtIndex:index];
+    }
+    dismiss(env, this, index);
+    index
+}
+
+@end
+
+};
+
+fn rect_contains_point(rect: CGRect, point: CGPoint) -> bool {
+    (rect.origin.x..rect.origin.x + rect.size.width).contains(&point.x)
+        && (rect.origin.y..rect.origin.y + rect.size.height).contains(&point.y)
+}
+
+fn delegate_implements(env: &mut Environment, delegate: id, selector: &str) -> bool {
+    delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, selector)
+}
+
+/// Lay out the sheet's title and buttons, and put it at the bottom of the
+/// view's window (or the root of its hierarchy, if it's not in a window).
+fn show(env: &mut Environment, sheet: id, view: id) {
+    let mut container = view;
+    loop {
+        let superview: id = msg![env; container superview];
+        if superview == nil {
+            break;
+        }
+        container = superview;
+    }
+    let container_bounds: CGRect = msg![env; container bounds];
+
+    let &UIActionSheetHostObject {
+        delegate,
+        title,
+        cancel_button_index,
+        destructive_button_index,
+        ..
+    } = env.objc.borrow(sheet);
+    let button_titles = env
+        .objc
+        .borrow::<UIActionSheetHostObject>(sheet)
+        .button_titles
+        .clone();
+
+    let subviews = env
+        .objc
+        .borrow::<super::UIViewHostObject>(sheet)
+        .subviews
+        .clone();
+    for subview in subviews {
+        () = msg![env; subview removeFromSuperview];
+    }
+
+    let width = container_bounds.size.width;
+    let title_height = if title != nil { TITLE_HEIGHT } else { 0.0 };
+    let height = MARGIN * 2.0
+        + title_height
+        + button_titles.len() as CGFloat * (BUTTON_HEIGHT + BUTTON_SPACING)
+        - if button_titles.is_empty() {
+            0.0
+        } else {
+            BUTTON_SPACING
+        };
+    let frame = CGRect {
+        origin: CGPoint {
+            x: container_bounds.origin.x,
+            y: container_bounds.origin.y + container_bounds.size.height - height,
+        },
+        size: CGSize { width, height },
+    };
+    () = msg![env; sheet setFrame:frame];
+
+    let add_label = |env: &mut Environment, frame: CGRect, text: id, background: id| -> id {
+        let label: id = msg_class![env; UILabel alloc];
+        let label: id = msg![env; label initWithFrame:frame];
+        () = msg![env; label setText:text];
+        () = msg![env; label setTextAlignment:UITextAlignmentCenter];
+        () = msg![env; label setBackgroundColor:background];
+        // Touches should go to the sheet itself.
+        () = msg![env; label setUserInteractionEnabled:false];
+        () = msg![env; sheet addSubview:label];
+        release(env, label);
+        label
+    };
+
+    let mut y = MARGIN;
+    if title != nil {
+        let frame = CGRect {
+            origin: CGPoint { x: MARGIN, y },
+            size: CGSize {
+                width: width - MARGIN * 2.0,
+                height: TITLE_HEIGHT,
+            },
+        };
+        let background: id = msg_class![env; UIColor clearColor];
+        let label = add_label(env, frame, title, background);
+        let white: id = msg_class![env; UIColor whiteColor];
+        () = msg![env; label setTextColor:white];
+        y += TITLE_HEIGHT;
+    }
+
+    let mut button_frames = Vec::with_capacity(button_titles.len());
+    for (index, &button_title) in button_titles.iter().enumerate() {
+        let index = index as NSInteger;
+        let frame = CGRect {
+            origin: CGPoint { x: MARGIN, y },
+            size: CGSize {
+                width: width - MARGIN * 2.0,
+                height: BUTTON_HEIGHT,
+            },
+        };
+        let background: id = if index == destructive_button_index {
+            msg_class![env; UIColor redColor]
+        } else if index == cancel_button_index {
+            msg_class![env; UIColor grayColor]
+        } else {
+            msg_class![env; UIColor whiteColor]
+        };
+        let _ = add_label(env, frame, button_title, background);
+        button_frames.push(frame);
+        y += BUTTON_HEIGHT + BUTTON_SPACING;
+    }
+    env.objc
+        .borrow_mut::<UIActionSheetHostObject>(sheet)
+        .button_frames = button_frames;
+
+    if delegate_implements(env, delegate, "willPresentActionSheet:") {
+        () = msg![env; delegate willPresentActionSheet:sheet];
+    }
+    // The sheet stays alive while it's shown, even if the app releases it.
+    retain(env, sheet);
+    () = msg![env; container addSubview:sheet];
+    if delegate_implements(env, delegate, "didPresentActionSheet:") {
+        () = msg![env; delegate didPresentActionSheet:sheet];
+    }
+}
+
+fn dismiss(env: &mut Environment, sheet: id, index: NSInteger) {
+    let superview: id = msg![env; sheet superview];
+    if superview == nil {
+        return;
+    }
+
+    let delegate = env.objc.borrow::<UIActionSheetHostObject>(sheet).delegate;
+    if delegate_implements(env, delegate, "actionSheet:willDismissWithButtonIndex:") {
+        () = msg![env; delegate actionSheet:sheet willDismissWithButtonIndex:index];
+    }
+    () = msg![env; sheet removeFromSuperview];
+    if delegate_implements(env, delegate, "actionSheet:didDismissWithButtonIndex:") {
+        () = msg![env; delegate actionSheet:sheet didDismissWithButtonIndex:index];
+    }
+    release(env, sheet);
+}
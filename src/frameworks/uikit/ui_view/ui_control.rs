@@ -12,10 +12,10 @@ pub mod ui_button;
 pub mod ui_text_field;
 
 use crate::frameworks::core_graphics::CGPoint;
-use crate::frameworks::foundation::NSUInteger;
+use crate::frameworks::foundation::{ns_array, ns_string, NSUInteger};
 use crate::objc::{
-    id, impl_HostObject_with_superclass, msg, msg_send, msg_super, nil, objc_classes, release,
-    retain, ClassExports, NSZonePtr, SEL,
+    autorelease, id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes,
+    release, retain, ClassExports, NSZonePtr, SEL,
 };
 use crate::Environment;
 
@@ -38,7 +38,8 @@ struct UIControlHostObject {
     tracked_touch: id,
     tracking: bool,
     /// See `addTarget:action:forControlEvents:`. The target is a weak
-    /// reference!
+    /// reference, and may be [nil] (meaning the responder chain is searched).
+    /// No two entries have the same target and action.
     action_targets: Vec<(id, SEL, UIControlEvents)>,
 }
 impl_HostObject_with_superclass!(UIControlHostObject);
@@ -80,8 +81,6 @@ fn send_actions(env: &mut Environment, this: id, event: id, control_event: UICon
         .collect();
 
     for (target, action) in action_targets {
-        assert!(target != nil); // TODO
-
         () = msg![env; this sendAction:action to:target forEvent:event];
     }
 }
@@ -261,74 +260,95 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())addTarget:(id)target
          action:(SEL)action
 forControlEvents:(UIControlEvents)events {
-    if target == nil {
-        // TODO: when the target is nil, the responder chain is searched for
-        // a suitable target
-        log!(
-            "TODO: [{:?} addTarget:nil action:{:?} forControlEvents:{:?}] (ignored)",
-            target,
-            action,
-            events,
-        );
-        return;
-    }
-    // The target is a *weak* reference!
+    // The target is a *weak* reference! If it's nil, the action is sent up the
+    // responder chain (see UIApplication's sendAction:to:from:forEvent:).
 
     // The selector must be for a method with zero to two arguments
     let sel_str = action.as_str(&env.mem);
     let colon_count = sel_str.bytes().filter(|&b| b == b':').count();
     assert!([0, 1, 2].contains(&colon_count));
 
-    env.objc.borrow_mut::<UIControlHostObject>(this).action_targets.push((target, action, events));
+    let action_targets = &mut env.objc.borrow_mut::<UIControlHostObject>(this).action_targets;
+    // Adding an existing target-action pair adds to its events.
+    match action_targets
+        .iter_mut()
+        .find(|&&mut (existing_target, existing_action, _)| {
+            existing_target == target && existing_action == action
+        })
+    {
+        Some((_, _, existing_events)) => *existing_events |= events,
+        None => action_targets.push((target, action, events)),
+    }
+}
+
+- (())removeTarget:(id)target
+            action:(SEL)action
+  forControlEvents:(UIControlEvents)events {
+    // A nil target or NULL action matches all targets or actions.
+    let action_targets = &mut env.objc.borrow_mut::<UIControlHostObject>(this).action_targets;
+    for (existing_target, existing_action, existing_events) in action_targets.iter_mut() {
+        if (target == nil || *existing_target == target)
+            && (action.is_null() || *existing_action == action)
+        {
+            *existing_events &= !events;
+        }
+    }
+    action_targets.retain(|&(_, _, events)| events != 0);
+}
+
+- (id)allTargets {
+    let action_targets = env.objc.borrow::<UIControlHostObject>(this).action_targets.clone();
+    let targets: id = msg_class![env; NSMutableSet new];
+    for (target, _, _) in action_targets {
+        // NSNull stands in for the nil target.
+        let target = if target == nil { msg_class![env; NSNull null] } else { target };
+        () = msg![env; targets addObject:target];
+    }
+    autorelease(env, targets)
+}
+
+- (UIControlEvents)allControlEvents {
+    env.objc
+        .borrow::<UIControlHostObject>(this)
+        .action_targets
+        .iter()
+        .fold(0, |all_events, &(_, _, events)| all_events | events)
+}
+
+- (id)actionsForTarget:(id)target
+       forControlEvent:(UIControlEvents)event {
+    let actions: Vec<SEL> = env
+        .objc
+        .borrow::<UIControlHostObject>(this)
+        .action_targets
+        .iter()
+        .filter(|&&(existing_target, _, events)| existing_target == target && events & event != 0)
+        .map(|&(_, action, _)| action)
+        .collect();
+    if actions.is_empty() {
+        return nil;
+    }
+    let actions = actions.into_iter().map(|action| {
+        let action = action.as_str(&env.mem).to_string();
+        ns_string::from_rust_string(env, action)
+    }).collect();
+    let actions = ns_array::from_vec(env, actions);
+    autorelease(env, actions)
+}
+
+- (())sendActionsForControlEvents:(UIControlEvents)events {
+    send_actions(env, this, nil, events);
 }
 
 - (())sendAction:(SEL)action
               to:(id)target
         forEvent:(id)event { // UIEvent*
-    assert!(target != nil); // TODO
-
-    let sel_str = action.as_str(&env.mem);
-    let colon_count = sel_str.bytes().filter(|&b| b == b':').count();
-    match colon_count {
-        // - (IBAction)action;
-        0 => {
-            log_dbg!(
-                "Sending {:?} ({:?}) message to {:?} (no args)",
-                action,
-                sel_str,
-                target
-            );
-            () = msg_send(env, (target, action));
-        }
-        // - (IBAction)action:(id)sender;
-        1 => {
-            log_dbg!(
-                "Sending {:?} ({:?}) message to {:?} (one arg: {:?})",
-                action,
-                sel_str,
-                target,
-                this
-            );
-            () = msg_send(env, (target, action, this));
-        }
-        // - (IBAction)action:(id)sender forEvent:(UIEvent*)event;
-        2 => {
-            log_dbg!(
-                "Sending {:?} ({:?}) message to {:?} (two args: {:?}, {:?})",
-                action,
-                sel_str,
-                target,
-                this,
-                event
-            );
-            () = msg_send(env, (target, action, this, event));
-        }
-        _ => panic!(),
-    };
+    // UIApplication does the actual sending, including finding a target in
+    // the responder chain if there is none.
+    let app: id = msg_class![env; UIApplication sharedApplication];
+    let _: bool = msg![env; app sendAction:action to:target from:this forEvent:event];
 }
 
-// TODO: more triggers/targets/actions stuff
-
 @end
 
 };
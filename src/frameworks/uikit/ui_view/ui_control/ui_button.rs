@@ -6,9 +6,10 @@
 //! `UIButton`.
 
 use super::{UIControlState, UIControlStateNormal};
-use crate::frameworks::core_graphics::{CGPoint, CGRect};
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
 use crate::frameworks::foundation::NSInteger;
 use crate::frameworks::uikit::ui_font::UITextAlignmentCenter;
+use crate::frameworks::uikit::ui_geometry::UIEdgeInsets;
 use crate::objc::{
     autorelease, id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes,
     release, retain, ClassExports, NSZonePtr,
@@ -45,6 +46,7 @@ pub struct UIButtonHostObject {
     images_for_states: HashMap<UIControlState, id>,
     /// Values are `UIImage*`
     background_images_for_states: HashMap<UIControlState, id>,
+    content_edge_insets: UIEdgeInsets,
 }
 impl_HostObject_with_superclass!(UIButtonHostObject);
 impl Default for UIButtonHostObject {
@@ -59,10 +61,43 @@ impl Default for UIButtonHostObject {
             title_colors_for_states: HashMap::new(),
             images_for_states: HashMap::new(),
             background_images_for_states: HashMap::new(),
+            content_edge_insets: Default::default(),
         }
     }
 }
 
+/// Get the value for a state, falling back to the value for the normal state
+/// if there is none.
+fn value_for_state(values: &HashMap<UIControlState, id>, state: UIControlState) -> id {
+    match values.get(&state) {
+        Some(&value) if value != nil => value,
+        _ => values.get(&UIControlStateNormal).copied().unwrap_or(nil),
+    }
+}
+
+/// Get the sizes of the current image and title, which make up the content.
+fn content_sizes(env: &mut Environment, this: id) -> (CGSize, CGSize) {
+    let zero = CGSize {
+        width: 0.0,
+        height: 0.0,
+    };
+    let image: id = msg![env; this currentImage];
+    let image_size = if image != nil {
+        msg![env; image size]
+    } else {
+        zero
+    };
+    let title: id = msg![env; this currentTitle];
+    let title_size = if title != nil {
+        let title_label: id = msg![env; this titleLabel];
+        let font: id = msg![env; title_label font];
+        msg![env; title sizeWithFont:font]
+    } else {
+        zero
+    };
+    (image_size, title_size)
+}
+
 fn update(env: &mut Environment, this: id) {
     let title_label: id = msg![env; this titleLabel];
     let title: id = msg![env; this currentTitle];
@@ -77,6 +112,9 @@ fn update(env: &mut Environment, this: id) {
     let background_image_view: id = msg![env; this backgroundImageView];
     let background_image: id = msg![env; this currentBackgroundImage];
     () = msg![env; background_image_view setImage:background_image];
+
+    // The content's size may have changed.
+    () = msg![env; this setNeedsLayout];
 }
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -158,7 +196,8 @@ pub const CLASSES: ClassExports = objc_classes! {
         titles_for_states,
         title_colors_for_states,
         images_for_states,
-        background_images_for_states
+        background_images_for_states,
+        content_edge_insets: _,
     } = std::mem::take(env.objc.borrow_mut(this));
 
     release(env, title_label);
@@ -180,14 +219,71 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())layoutSubviews {
-    let label = env.objc.borrow_mut::<UIButtonHostObject>(this).title_label;
-    let background_image_view = env.objc.borrow_mut::<UIButtonHostObject>(this).background_image_view;
+    let &UIButtonHostObject {
+        title_label,
+        image_view,
+        background_image_view,
+        content_edge_insets: insets,
+        ..
+    } = env.objc.borrow(this);
     let bounds: CGRect = msg![env; this bounds];
 
     () = msg![env; background_image_view setFrame:bounds];
-    () = msg![env; label setFrame:bounds];
-    // TODO: layout for image
 
+    // The image and title are centered side by side within the content rect.
+    let content = CGRect {
+        origin: CGPoint {
+            x: bounds.origin.x + insets.left,
+            y: bounds.origin.y + insets.top,
+        },
+        size: CGSize {
+            width: bounds.size.width - insets.left - insets.right,
+            height: bounds.size.height - insets.top - insets.bottom,
+        },
+    };
+    let (image_size, title_size) = content_sizes(env, this);
+    let title_width = title_size
+        .width
+        .min((content.size.width - image_size.width).max(0.0));
+    let x = content.origin.x
+        + ((content.size.width - image_size.width - title_width) / 2.0).max(0.0);
+    let image_frame = CGRect {
+        origin: CGPoint {
+            x,
+            y: content.origin.y + (content.size.height - image_size.height) / 2.0,
+        },
+        size: image_size,
+    };
+    () = msg![env; image_view setFrame:image_frame];
+    // UILabel centers the text vertically by itself.
+    let title_frame = CGRect {
+        origin: CGPoint {
+            x: x + image_size.width,
+            y: content.origin.y,
+        },
+        size: CGSize {
+            width: title_width,
+            height: content.size.height,
+        },
+    };
+    () = msg![env; title_label setFrame:title_frame];
+}
+
+- (CGSize)sizeThatFits:(CGSize)_size {
+    let insets = env.objc.borrow::<UIButtonHostObject>(this).content_edge_insets;
+    let (image_size, title_size) = content_sizes(env, this);
+    CGSize {
+        width: image_size.width + title_size.width + insets.left + insets.right,
+        height: image_size.height.max(title_size.height) + insets.top + insets.bottom,
+    }
+}
+
+- (UIEdgeInsets)contentEdgeInsets {
+    env.objc.borrow::<UIButtonHostObject>(this).content_edge_insets
+}
+- (())setContentEdgeInsets:(UIEdgeInsets)insets {
+    env.objc.borrow_mut::<UIButtonHostObject>(this).content_edge_insets = insets;
+    () = msg![env; this setNeedsLayout];
 }
 
 - (UIButtonType)buttonType {
@@ -224,10 +320,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this titleForState:state]
 }
 - (id)titleForState:(UIControlState)state {
-    let host_obj = env.objc.borrow::<UIButtonHostObject>(this);
-    host_obj.titles_for_states.get(&state).or_else(|| {
-        host_obj.titles_for_states.get(&UIControlStateNormal)
-    }).copied().unwrap()
+    value_for_state(&env.objc.borrow::<UIButtonHostObject>(this).titles_for_states, state)
 }
 - (())setTitle:(id)title // NSString*
       forState:(UIControlState)state {
@@ -244,10 +337,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this backgroundImageForState:state]
 }
 - (id)backgroundImageForState:(UIControlState)state {
-    let host_obj = env.objc.borrow::<UIButtonHostObject>(this);
-    host_obj.background_images_for_states.get(&state).or_else(|| {
-        host_obj.background_images_for_states.get(&UIControlStateNormal)
-    }).copied().unwrap()
+    value_for_state(&env.objc.borrow::<UIButtonHostObject>(this).background_images_for_states, state)
 }
 - (())setBackgroundImage:(id)image forState:(UIControlState)state {
     retain(env,image);
@@ -263,10 +353,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this titleColorForState:state]
 }
 - (id)titleColorForState:(UIControlState)state {
-    let host_obj = env.objc.borrow::<UIButtonHostObject>(this);
-    host_obj.title_colors_for_states.get(&state).or_else(|| {
-        host_obj.title_colors_for_states.get(&UIControlStateNormal)
-    }).copied().unwrap()
+    value_for_state(&env.objc.borrow::<UIButtonHostObject>(this).title_colors_for_states, state)
 }
 - (())setTitleColor:(id)color // UIColor*
       forState:(UIControlState)state {
@@ -283,10 +370,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this imageForState:state]
 }
 - (id)imageForState:(UIControlState)state {
-    let host_obj = env.objc.borrow::<UIButtonHostObject>(this);
-    host_obj.images_for_states.get(&state).or_else(|| {
-        host_obj.images_for_states.get(&UIControlStateNormal)
-    }).copied().unwrap()
+    value_for_state(&env.objc.borrow::<UIButtonHostObject>(this).images_for_states, state)
 }
 - (())setImage:(id)image // UIImage*
       forState:(UIControlState)state {
@@ -298,12 +382,11 @@ pub const CLASSES: ClassExports = objc_classes! {
     update(env, this);
 }
 
-// TODO: actions, etc
-
 - (id)hitTest:(CGPoint)point
     withEvent:(id)event { // UIEvent* (possibly nil)
     // Hide subviews from hit testing so event goes straight to this control
-    if msg![env; this pointInside:point withEvent:event] {
+    let hit_view: id = msg_super![env; this hitTest:point withEvent:event];
+    if hit_view != nil {
         this
     } else {
         nil
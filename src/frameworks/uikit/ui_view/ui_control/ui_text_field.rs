@@ -13,6 +13,7 @@ use sdl2_sys::{SDL_StartTextInput, SDL_StopTextInput};
 use crate::frameworks::core_graphics::CGRect;
 use crate::frameworks::foundation::{ns_string, NSInteger, NSRange, NSUInteger};
 use crate::frameworks::uikit::ui_font::UITextAlignmentLeft;
+use crate::frameworks::uikit::ui_view::ui_keyboard::{UIKeyboardType, UIKeyboardTypeDefault};
 use crate::impl_HostObject_with_superclass;
 use crate::objc::{
     id, msg, msg_class, msg_super, nil, objc_classes, release, ClassExports, NSZonePtr, SEL,
@@ -20,7 +21,6 @@ use crate::objc::{
 use crate::Environment;
 
 type UIKeyboardAppearance = NSInteger;
-type UIKeyboardType = NSInteger;
 type UIReturnKeyType = NSInteger;
 type UITextAutocapitalizationType = NSInteger;
 type UITextAutocorrectionType = NSInteger;
@@ -30,6 +30,7 @@ struct UITextFieldHostObject {
     delegate: id,
     editing: bool,
     text_label: id,
+    keyboard_type: UIKeyboardType,
 }
 impl_HostObject_with_superclass!(UITextFieldHostObject);
 impl Default for UITextFieldHostObject {
@@ -39,6 +40,7 @@ impl Default for UITextFieldHostObject {
             delegate: nil,
             editing: false,
             text_label: nil,
+            keyboard_type: UIKeyboardTypeDefault,
         }
     }
 }
@@ -162,8 +164,32 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())setKeyboardAppearance:(UIKeyboardAppearance)appearance {
     log!("TODO: setKeyboardAppearance:{}", appearance);
 }
+- (UIKeyboardType)keyboardType {
+    env.objc.borrow::<UITextFieldHostObject>(this).keyboard_type
+}
 - (())setKeyboardType:(UIKeyboardType)type_ {
-    log!("TODO: setKeyboardType:{}", type_);
+    env.objc.borrow_mut::<UITextFieldHostObject>(this).keyboard_type = type_;
+}
+
+// UIKeyInput implementation
+- (bool)hasText {
+    let text: id = msg![env; this text];
+    let length: NSUInteger = msg![env; text length];
+    length != 0
+}
+- (())insertText:(id)text { // NSString*
+    let is_return: bool = {
+        let newline = ns_string::get_static_str(env, "\n");
+        msg![env; text isEqualToString:newline]
+    };
+    if is_return {
+        handle_return(env, this);
+    } else {
+        handle_text(env, this, text);
+    }
+}
+- (())deleteBackward {
+    handle_backspace(env, this);
 }
 - (())setBorderStyle:(NSInteger)style {
     log!("TODO: setBorderStyle:{}", style);
@@ -178,6 +204,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<UITextFieldHostObject>(this).editing
 }
 
+- (bool)canBecomeFirstResponder {
+    true
+}
+
 - (bool)becomeFirstResponder {
     log_dbg!("becomeFirstResponder");
 
@@ -192,6 +222,11 @@ pub const CLASSES: ClassExports = objc_classes! {
         return false;
     }
 
+    // This resigns the previous first responder, if any.
+    if !msg_super![env; this becomeFirstResponder] {
+        return false;
+    }
+
     // If text is nil, it becomes an empty string
     // on becoming the first responder.
     // This behaviour was validated on the Aspen Simulator
@@ -205,7 +240,6 @@ pub const CLASSES: ClassExports = objc_classes! {
         () = msg![env; text_label setText:empty];
     }
 
-    env.framework_state.uikit.ui_responder.first_responder = this;
     unsafe { SDL_StartTextInput(); }
 
     let sel: SEL = env.objc.register_host_selector("textFieldDidBeginEditing:".to_string(), &mut env.mem);
@@ -229,7 +263,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         return false;
     }
 
-    env.framework_state.uikit.ui_responder.first_responder = nil;
+    let _: bool = msg_super![env; this resignFirstResponder];
     unsafe { SDL_StopTextInput(); }
 
     let sel: SEL = env.objc.register_host_selector("textFieldDidEndEditing:".to_string(), &mut env.mem);
@@ -247,11 +281,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
-pub fn handle_text(env: &mut Environment, text_field: id, text: String) {
-    let txt = ns_string::from_rust_string(env, text);
-    let txt_len: NSUInteger = msg![env; txt length];
-    assert_eq!(txt_len, 1);
-
+fn handle_text(env: &mut Environment, text_field: id, txt: id) {
     let text_label = env
         .objc
         .borrow_mut::<UITextFieldHostObject>(text_field)
@@ -287,10 +317,9 @@ pub fn handle_text(env: &mut Environment, text_field: id, text: String) {
         () = msg![env; text_field setNeedsDisplay];
         release(env, new_text);
     }
-    release(env, txt);
 }
 
-pub fn handle_backspace(env: &mut Environment, text_field: id) {
+fn handle_backspace(env: &mut Environment, text_field: id) {
     let text_label = env
         .objc
         .borrow_mut::<UITextFieldHostObject>(text_field)
@@ -332,7 +361,7 @@ pub fn handle_backspace(env: &mut Environment, text_field: id) {
     }
 }
 
-pub fn handle_return(env: &mut Environment, text_field: id) {
+fn handle_return(env: &mut Environment, text_field: id) {
     let delegate: id = env
         .objc
         .borrow::<UITextFieldHostObject>(text_field)
@@ -0,0 +1,593 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIKeyboard`, the on-screen software keyboard.
+//!
+//! This isn't a public class, but apps can see it in the view hierarchy, and
+//! the notifications it posts are public. The keyboard is shown whenever the
+//! first responder is something that accepts text input (i.e. implements
+//! `UIKeyInput`) and is hidden again when it resigns. Key presses are sent to
+//! the first responder with `insertText:` and `deleteBackward`, just like
+//! input from the host keyboard.
+//!
+//! The layout is a much simplified version of the iPhone OS keyboard's, and
+//! there is no shift key, autocapitalization or autocorrection.
+
+use super::UIViewHostObject;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str};
+use crate::frameworks::foundation::NSInteger;
+use crate::frameworks::uikit::ui_font::UITextAlignmentCenter;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes, release,
+    Class, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+pub type UIKeyboardType = NSInteger;
+pub const UIKeyboardTypeDefault: UIKeyboardType = 0;
+pub const UIKeyboardTypeASCIICapable: UIKeyboardType = 1;
+pub const UIKeyboardTypeNumbersAndPunctuation: UIKeyboardType = 2;
+pub const UIKeyboardTypeURL: UIKeyboardType = 3;
+pub const UIKeyboardTypeNumberPad: UIKeyboardType = 4;
+pub const UIKeyboardTypePhonePad: UIKeyboardType = 5;
+pub const UIKeyboardTypeNamePhonePad: UIKeyboardType = 6;
+pub const UIKeyboardTypeEmailAddress: UIKeyboardType = 7;
+pub const UIKeyboardTypeDecimalPad: UIKeyboardType = 8;
+
+pub const UIKeyboardWillShowNotification: &str = "UIKeyboardWillShowNotification";
+pub const UIKeyboardDidShowNotification: &str = "UIKeyboardDidShowNotification";
+pub const UIKeyboardWillHideNotification: &str = "UIKeyboardWillHideNotification";
+pub const UIKeyboardDidHideNotification: &str = "UIKeyboardDidHideNotification";
+
+pub const UIKeyboardFrameBeginUserInfoKey: &str = "UIKeyboardFrameBeginUserInfoKey";
+pub const UIKeyboardFrameEndUserInfoKey: &str = "UIKeyboardFrameEndUserInfoKey";
+pub const UIKeyboardBoundsUserInfoKey: &str = "UIKeyboardBoundsUserInfoKey";
+pub const UIKeyboardCenterBeginUserInfoKey: &str = "UIKeyboardCenterBeginUserInfoKey";
+pub const UIKeyboardCenterEndUserInfoKey: &str = "UIKeyboardCenterEndUserInfoKey";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIKeyboardWillShowNotification",
+        HostConstant::NSString(UIKeyboardWillShowNotification),
+    ),
+    (
+        "_UIKeyboardDidShowNotification",
+        HostConstant::NSString(UIKeyboardDidShowNotification),
+    ),
+    (
+        "_UIKeyboardWillHideNotification",
+        HostConstant::NSString(UIKeyboardWillHideNotification),
+    ),
+    (
+        "_UIKeyboardDidHideNotification",
+        HostConstant::NSString(UIKeyboardDidHideNotification),
+    ),
+    (
+        "_UIKeyboardFrameBeginUserInfoKey",
+        HostConstant::NSString(UIKeyboardFrameBeginUserInfoKey),
+    ),
+    (
+        "_UIKeyboardFrameEndUserInfoKey",
+        HostConstant::NSString(UIKeyboardFrameEndUserInfoKey),
+    ),
+    (
+        "_UIKeyboardBoundsUserInfoKey",
+        HostConstant::NSString(UIKeyboardBoundsUserInfoKey),
+    ),
+    (
+        "_UIKeyboardCenterBeginUserInfoKey",
+        HostConstant::NSString(UIKeyboardCenterBeginUserInfoKey),
+    ),
+    (
+        "_UIKeyboardCenterEndUserInfoKey",
+        HostConstant::NSString(UIKeyboardCenterEndUserInfoKey),
+    ),
+];
+
+/// Height of the portrait keyboard on the iPhone.
+const KEYBOARD_HEIGHT: CGFloat = 216.0;
+/// Gap between keys.
+const KEY_SPACING: CGFloat = 3.0;
+
+#[derive(Default)]
+pub struct State {
+    /// The keyboard view, created on first use. This is a strong reference.
+    keyboard: Option<id>,
+    /// Set while the first responder changes from one responder to another, so
+    /// the keyboard isn't needlessly hidden and shown again.
+    pub(in crate::frameworks::uikit) switching_responders: bool,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum Layout {
+    #[default]
+    Letters,
+    NumbersAndPunctuation,
+    Url,
+    EmailAddress,
+    NumberPad,
+    PhonePad,
+    DecimalPad,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Key {
+    Text(&'static str),
+    Space,
+    Return,
+    Backspace,
+    /// Switches between letters and numbers/punctuation.
+    SwitchLayout,
+}
+
+/// Get the layout used by default for a keyboard type, and the layout the
+/// [Key::SwitchLayout] key switches back to from numbers and punctuation.
+fn layouts_for_keyboard_type(keyboard_type: UIKeyboardType) -> (Layout, Layout) {
+    match keyboard_type {
+        UIKeyboardTypeNumbersAndPunctuation => (Layout::NumbersAndPunctuation, Layout::Letters),
+        UIKeyboardTypeURL => (Layout::Url, Layout::Url),
+        UIKeyboardTypeNumberPad => (Layout::NumberPad, Layout::NumberPad),
+        UIKeyboardTypePhonePad => (Layout::PhonePad, Layout::PhonePad),
+        UIKeyboardTypeEmailAddress => (Layout::EmailAddress, Layout::EmailAddress),
+        UIKeyboardTypeDecimalPad => (Layout::DecimalPad, Layout::DecimalPad),
+        // The name/phone pad's letters layout is close enough.
+        UIKeyboardTypeDefault | UIKeyboardTypeASCIICapable | UIKeyboardTypeNamePhonePad => {
+            (Layout::Letters, Layout::Letters)
+        }
+        _ => {
+            log!(
+                "TODO: keyboard type {}, using default layout",
+                keyboard_type
+            );
+            (Layout::Letters, Layout::Letters)
+        }
+    }
+}
+
+/// One key for each character in the string.
+fn text_keys(chars: &'static str) -> impl Iterator<Item = Key> {
+    chars
+        .char_indices()
+        .map(move |(i, c)| Key::Text(&chars[i..i + c.len_utf8()]))
+}
+
+/// Get the rows of keys for a layout, top to bottom.
+fn rows_for_layout(layout: Layout) -> Vec<Vec<Key>> {
+    let letters = || {
+        vec![
+            text_keys("qwertyuiop").collect(),
+            text_keys("asdfghjkl").collect(),
+            text_keys("zxcvbnm")
+                .chain(std::iter::once(Key::Backspace))
+                .collect(),
+        ]
+    };
+    let keypad = |bottom_row: &[Key]| {
+        vec![
+            text_keys("123").collect(),
+            text_keys("456").collect(),
+            text_keys("789").collect(),
+            bottom_row.to_vec(),
+        ]
+    };
+    match layout {
+        Layout::Letters => {
+            let mut rows = letters();
+            rows.push(vec![Key::SwitchLayout, Key::Space, Key::Return]);
+            rows
+        }
+        Layout::NumbersAndPunctuation => vec![
+            text_keys("1234567890").collect(),
+            text_keys("-/:;()$&@\"").collect(),
+            text_keys(".,?!'")
+                .chain(std::iter::once(Key::Backspace))
+                .collect(),
+            vec![Key::SwitchLayout, Key::Space, Key::Return],
+        ],
+        Layout::Url => {
+            let mut rows = letters();
+            rows.push(vec![
+                Key::SwitchLayout,
+                Key::Text("."),
+                Key::Text("/"),
+                Key::Text(".com"),
+                Key::Return,
+            ]);
+            rows
+        }
+        Layout::EmailAddress => {
+            let mut rows = letters();
+            rows.push(vec![
+                Key::SwitchLayout,
+                Key::Space,
+                Key::Text("@"),
+                Key::Text("."),
+                Key::Return,
+            ]);
+            rows
+        }
+        Layout::NumberPad => keypad(&[Key::Text("0"), Key::Backspace]),
+        Layout::PhonePad => keypad(&[
+            Key::Text("*"),
+            Key::Text("0"),
+            Key::Text("#"),
+            Key::Backspace,
+        ]),
+        Layout::DecimalPad => keypad(&[Key::Text("."), Key::Text("0"), Key::Backspace]),
+    }
+}
+
+/// How wide a key is relative to a character key.
+fn key_weight(key: Key) -> CGFloat {
+    match key {
+        Key::Text(_) => 1.0,
+        Key::Backspace | Key::SwitchLayout => 1.5,
+        Key::Return => 2.0,
+        Key::Space => 5.0,
+    }
+}
+
+/// Lay out the rows of keys to fill the bounds.
+fn key_frames(rows: &[Vec<Key>], bounds: CGRect) -> Vec<(CGRect, Key)> {
+    let row_height = bounds.size.height / rows.len() as CGFloat;
+    let mut frames = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let total_weight: CGFloat = row.iter().map(|&key| key_weight(key)).sum();
+        let mut x = bounds.origin.x;
+        for &key in row {
+            let width = bounds.size.width * key_weight(key) / total_weight;
+            let frame = CGRect {
+                origin: CGPoint {
+                    x,
+                    y: bounds.origin.y + row_height * i as CGFloat,
+                },
+                size: CGSize {
+                    width,
+                    height: row_height,
+                },
+            };
+            frames.push((frame, key));
+            x += width;
+        }
+    }
+    frames
+}
+
+#[derive(Default)]
+struct UIKeyboardHostObject {
+    superclass: UIViewHostObject,
+    layout: Layout,
+    /// The layout the [Key::SwitchLayout] key returns to.
+    base_layout: Layout,
+    /// Keys and their frames in the keyboard's co-ordinate space.
+    keys: Vec<(CGRect, Key)>,
+}
+impl_HostObject_with_superclass!(UIKeyboardHostObject);
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIKeyboard: UIView
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIKeyboardHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithFrame:(CGRect)frame {
+    let this: id = msg_super![env; this initWithFrame:frame];
+    let background: id = msg_class![env; UIColor darkGrayColor];
+    () = msg![env; this setBackgroundColor:background];
+    set_layout(env, this, Layout::Letters);
+    this
+}
+
+- (())touchesBegan:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    // Keys are pressed when the touch ends.
+}
+- (())touchesMoved:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+}
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    let location: CGPoint = msg![env; touch locationInView:this];
+    let _: bool = msg![env; this pressKeyAtPoint:location];
+}
+
+// Not public API. Returns whether there was a key at the point.
+- (bool)pressKeyAtPoint:(CGPoint)point {
+    let key = env
+        .objc
+        .borrow::<UIKeyboardHostObject>(this)
+        .keys
+        .iter()
+        .find(|&&(frame, _)| rect_contains_point(frame, point))
+        .map(|&(_, key)| key);
+    let Some(key) = key else {
+        return false;
+    };
+    log_dbg!("Software keyboard key pressed: {:?}", key);
+
+    if key == Key::SwitchLayout {
+        let &UIKeyboardHostObject {
+            layout,
+            base_layout,
+            ..
+        } = env.objc.borrow(this);
+        let new_layout = if layout == Layout::NumbersAndPunctuation {
+            base_layout
+        } else {
+            Layout::NumbersAndPunctuation
+        };
+        set_layout(env, this, new_layout);
+        return true;
+    }
+
+    let responder = env.framework_state.uikit.ui_responder.first_responder;
+    if responder == nil {
+        return true;
+    }
+    match key {
+        Key::Text(text) => {
+            let text = get_static_str(env, text);
+            () = msg![env; responder insertText:text];
+        }
+        Key::Space => {
+            let text = get_static_str(env, " ");
+            () = msg![env; responder insertText:text];
+        }
+        Key::Return => {
+            let text = get_static_str(env, "\n");
+            () = msg![env; responder insertText:text];
+        }
+        Key::Backspace => {
+            () = msg![env; responder deleteBackward];
+        }
+        Key::SwitchLayout => unreachable!(),
+    }
+    true
+}
+
+@end
+
+};
+
+fn rect_contains_point(rect: CGRect, point: CGPoint) -> bool {
+    (rect.origin.x..rect.origin.x + rect.size.width).contains(&point.x)
+        && (rect.origin.y..rect.origin.y + rect.size.height).contains(&point.y)
+}
+
+/// Replace the keyboard's key views with those for a new layout.
+fn set_layout(env: &mut Environment, keyboard: id, layout: Layout) {
+    let subviews = env
+        .objc
+        .borrow::<UIViewHostObject>(keyboard)
+        .subviews
+        .clone();
+    for subview in subviews {
+        () = msg![env; subview removeFromSuperview];
+    }
+
+    let bounds: CGRect = msg![env; keyboard bounds];
+    let keys = key_frames(&rows_for_layout(layout), bounds);
+
+    let key_color: id = msg_class![env; UIColor lightGrayColor];
+    for &(frame, key) in &keys {
+        let title = match key {
+            Key::Text(text) => text,
+            Key::Space => "space",
+            Key::Return => "return",
+            Key::Backspace => "del",
+            Key::SwitchLayout if layout == Layout::NumbersAndPunctuation => "ABC",
+            Key::SwitchLayout => "123",
+        };
+        let frame = CGRect {
+            origin: CGPoint {
+                x: frame.origin.x + KEY_SPACING / 2.0,
+                y: frame.origin.y + KEY_SPACING / 2.0,
+            },
+            size: CGSize {
+                width: frame.size.width - KEY_SPACING,
+                height: frame.size.height - KEY_SPACING,
+            },
+        };
+        let label: id = msg_class![env; UILabel alloc];
+        let label: id = msg![env; label initWithFrame:frame];
+        let title = from_rust_string(env, title.to_string());
+        () = msg![env; label setText:title];
+        release(env, title);
+        () = msg![env; label setTextAlignment:UITextAlignmentCenter];
+        () = msg![env; label setBackgroundColor:key_color];
+        // Touches should go to the keyboard itself.
+        () = msg![env; label setUserInteractionEnabled:false];
+        () = msg![env; keyboard addSubview:label];
+        release(env, label);
+    }
+
+    let host_object = env.objc.borrow_mut::<UIKeyboardHostObject>(keyboard);
+    host_object.layout = layout;
+    host_object.keys = keys;
+}
+
+/// Show or hide the keyboard as appropriate for the current first responder.
+/// Called by `UIResponder` whenever the first responder changes.
+pub(in crate::frameworks::uikit) fn update_for_first_responder(env: &mut Environment) {
+    let state = &env.framework_state.uikit.ui_view.ui_keyboard;
+    if state.switching_responders {
+        return;
+    }
+
+    let responder = env.framework_state.uikit.ui_responder.first_responder;
+    let wants_keyboard = responder != nil && {
+        let sel = env
+            .objc
+            .register_host_selector("insertText:".to_string(), &mut env.mem);
+        msg![env; responder respondsToSelector:sel]
+    };
+    if wants_keyboard {
+        show(env, responder);
+    } else {
+        hide(env);
+    }
+}
+
+fn show(env: &mut Environment, responder: id) {
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let screen_bounds: CGRect = msg![env; screen bounds];
+    // TODO: landscape keyboard
+    let frame = CGRect {
+        origin: CGPoint {
+            x: 0.0,
+            y: screen_bounds.size.height - KEYBOARD_HEIGHT,
+        },
+        size: CGSize {
+            width: screen_bounds.size.width,
+            height: KEYBOARD_HEIGHT,
+        },
+    };
+
+    let keyboard = if let Some(keyboard) = env.framework_state.uikit.ui_view.ui_keyboard.keyboard {
+        keyboard
+    } else {
+        let keyboard: id = msg_class![env; UIKeyboard alloc];
+        let keyboard: id = msg![env; keyboard initWithFrame:frame];
+        env.framework_state.uikit.ui_view.ui_keyboard.keyboard = Some(keyboard);
+        keyboard
+    };
+
+    let keyboard_type: UIKeyboardType = {
+        let sel = env
+            .objc
+            .register_host_selector("keyboardType".to_string(), &mut env.mem);
+        if msg![env; responder respondsToSelector:sel] {
+            msg![env; responder keyboardType]
+        } else {
+            UIKeyboardTypeDefault
+        }
+    };
+    let (layout, base_layout) = layouts_for_keyboard_type(keyboard_type);
+    env.objc
+        .borrow_mut::<UIKeyboardHostObject>(keyboard)
+        .base_layout = base_layout;
+    if env.objc.borrow::<UIKeyboardHostObject>(keyboard).layout != layout {
+        set_layout(env, keyboard, layout);
+    }
+
+    // The keyboard goes on top of everything else in the responder's window.
+    // Views that aren't in a window use the root of their hierarchy instead.
+    let ui_view_class = env.objc.get_known_class("UIView", &mut env.mem);
+    let responder_class: Class = msg![env; responder class];
+    let mut container = if env
+        .objc
+        .class_is_subclass_of(responder_class, ui_view_class)
+    {
+        responder
+    } else {
+        env.framework_state.uikit.ui_view.ui_window.key_window
+    };
+    if container == nil {
+        log!(
+            "Warning: nowhere to show the keyboard for {:?}, ignoring",
+            responder
+        );
+        return;
+    }
+    loop {
+        let superview: id = msg![env; container superview];
+        if superview == nil {
+            break;
+        }
+        container = superview;
+    }
+
+    let superview: id = msg![env; keyboard superview];
+    if superview == container {
+        () = msg![env; container bringSubviewToFront:keyboard];
+        return;
+    }
+    let was_visible = superview != nil;
+    if was_visible {
+        () = msg![env; keyboard removeFromSuperview];
+    }
+
+    let user_info = keyboard_user_info(env, frame, /* showing: */ true);
+    if !was_visible {
+        post_notification(env, UIKeyboardWillShowNotification, user_info);
+    }
+    () = msg![env; container addSubview:keyboard];
+    if !was_visible {
+        post_notification(env, UIKeyboardDidShowNotification, user_info);
+    }
+    release(env, user_info);
+}
+
+fn hide(env: &mut Environment) {
+    let Some(keyboard) = env.framework_state.uikit.ui_view.ui_keyboard.keyboard else {
+        return;
+    };
+    let superview: id = msg![env; keyboard superview];
+    if superview == nil {
+        return;
+    }
+
+    let frame: CGRect = msg![env; keyboard frame];
+    let user_info = keyboard_user_info(env, frame, /* showing: */ false);
+    post_notification(env, UIKeyboardWillHideNotification, user_info);
+    () = msg![env; keyboard removeFromSuperview];
+    post_notification(env, UIKeyboardDidHideNotification, user_info);
+    release(env, user_info);
+}
+
+/// Create the `userInfo` dictionary (+1 reference) for the keyboard
+/// notifications. The keyboard slides in from the bottom of the screen.
+fn keyboard_user_info(env: &mut Environment, frame: CGRect, showing: bool) -> id {
+    let off_screen = CGRect {
+        origin: CGPoint {
+            x: frame.origin.x,
+            y: frame.origin.y + frame.size.height,
+        },
+        size: frame.size,
+    };
+    let (begin, end) = if showing {
+        (off_screen, frame)
+    } else {
+        (frame, off_screen)
+    };
+    let center = |rect: CGRect| CGPoint {
+        x: rect.origin.x + rect.size.width / 2.0,
+        y: rect.origin.y + rect.size.height / 2.0,
+    };
+    let bounds = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: frame.size,
+    };
+
+    let frame_begin: id = msg_class![env; NSValue valueWithCGRect:begin];
+    let frame_end: id = msg_class![env; NSValue valueWithCGRect:end];
+    let bounds: id = msg_class![env; NSValue valueWithCGRect:bounds];
+    let center_begin: id = msg_class![env; NSValue valueWithCGPoint:(center(begin))];
+    let center_end: id = msg_class![env; NSValue valueWithCGPoint:(center(end))];
+    let keys_and_objects = [
+        (UIKeyboardFrameBeginUserInfoKey, frame_begin),
+        (UIKeyboardFrameEndUserInfoKey, frame_end),
+        (UIKeyboardBoundsUserInfoKey, bounds),
+        (UIKeyboardCenterBeginUserInfoKey, center_begin),
+        (UIKeyboardCenterEndUserInfoKey, center_end),
+    ]
+    .map(|(key, object)| (get_static_str(env, key), object));
+    dict_from_keys_and_objects(env, &keys_and_objects)
+}
+
+fn post_notification(env: &mut Environment, name: &'static str, user_info: id) {
+    let name = get_static_str(env, name);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    () = msg![env; center postNotificationName:name object:nil userInfo:user_info];
+}
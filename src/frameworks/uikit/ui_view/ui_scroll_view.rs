@@ -0,0 +1,199 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIScrollView`.
+//!
+//! The content offset is the origin of the view's bounds, so scrolling moves
+//! the subviews (and whatever the view draws for itself) without any extra
+//! work in the compositor.
+
+pub mod ui_text_view;
+
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::uikit::ui_geometry::UIEdgeInsets;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, nil, objc_classes, ClassExports, NSZonePtr, SEL,
+};
+use crate::Environment;
+
+pub struct UIScrollViewHostObject {
+    superclass: super::UIViewHostObject,
+    content_size: CGSize,
+    content_inset: UIEdgeInsets,
+    scroll_enabled: bool,
+    /// Weak reference.
+    delegate: id,
+    dragging: bool,
+}
+impl_HostObject_with_superclass!(UIScrollViewHostObject);
+impl Default for UIScrollViewHostObject {
+    fn default() -> Self {
+        UIScrollViewHostObject {
+            superclass: Default::default(),
+            content_size: Default::default(),
+            content_inset: Default::default(),
+            scroll_enabled: true,
+            delegate: nil,
+            dragging: false,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIScrollView: UIView
+
+// TODO: deceleration, bouncing, paging, zooming, scroll indicators, clipping
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIScrollViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// weak/non-retaining
+- (id)delegate {
+    env.objc.borrow::<UIScrollViewHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing UIScrollViewDelegate
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).delegate = delegate;
+}
+
+- (CGSize)contentSize {
+    env.objc.borrow::<UIScrollViewHostObject>(this).content_size
+}
+- (())setContentSize:(CGSize)size {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).content_size = size;
+}
+
+- (UIEdgeInsets)contentInset {
+    env.objc.borrow::<UIScrollViewHostObject>(this).content_inset
+}
+- (())setContentInset:(UIEdgeInsets)inset {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).content_inset = inset;
+}
+
+- (CGPoint)contentOffset {
+    let bounds: CGRect = msg![env; this bounds];
+    bounds.origin
+}
+- (())setContentOffset:(CGPoint)offset {
+    let mut bounds: CGRect = msg![env; this bounds];
+    if bounds.origin == offset {
+        return;
+    }
+    bounds.origin = offset;
+    () = msg![env; this setBounds:bounds];
+
+    let delegate = env.objc.borrow::<UIScrollViewHostObject>(this).delegate;
+    let sel: SEL = env
+        .objc
+        .register_host_selector("scrollViewDidScroll:".to_string(), &mut env.mem);
+    if delegate != nil && msg![env; delegate respondsToSelector:sel] {
+        () = msg![env; delegate scrollViewDidScroll:this];
+    }
+}
+- (())setContentOffset:(CGPoint)offset animated:(bool)_animated {
+    // TODO: animation
+    () = msg![env; this setContentOffset:offset];
+}
+
+- (())scrollRectToVisible:(CGRect)rect animated:(bool)animated {
+    let bounds: CGRect = msg![env; this bounds];
+    let mut offset = bounds.origin;
+    if rect.origin.x < offset.x {
+        offset.x = rect.origin.x;
+    } else if rect.origin.x + rect.size.width > offset.x + bounds.size.width {
+        offset.x = rect.origin.x + rect.size.width - bounds.size.width;
+    }
+    if rect.origin.y < offset.y {
+        offset.y = rect.origin.y;
+    } else if rect.origin.y + rect.size.height > offset.y + bounds.size.height {
+        offset.y = rect.origin.y + rect.size.height - bounds.size.height;
+    }
+    let offset = clamp_content_offset(env, this, offset);
+    () = msg![env; this setContentOffset:offset animated:animated];
+}
+
+- (bool)isScrollEnabled {
+    env.objc.borrow::<UIScrollViewHostObject>(this).scroll_enabled
+}
+- (())setScrollEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).scroll_enabled = enabled;
+}
+
+- (bool)isDragging {
+    env.objc.borrow::<UIScrollViewHostObject>(this).dragging
+}
+
+- (())setShowsHorizontalScrollIndicator:(bool)_shows {
+    // Scroll indicators aren't drawn yet.
+}
+- (())setShowsVerticalScrollIndicator:(bool)_shows {
+    // Scroll indicators aren't drawn yet.
+}
+
+- (())touchesBegan:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    // Dragging only starts once the touch moves.
+}
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    if !env.objc.borrow::<UIScrollViewHostObject>(this).scroll_enabled {
+        return;
+    }
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).dragging = true;
+
+    // Window co-ordinates are used because the view's own co-ordinate space
+    // moves while scrolling.
+    let touch: id = msg![env; touches anyObject];
+    let location: CGPoint = msg![env; touch locationInView:nil];
+    let previous: CGPoint = msg![env; touch previousLocationInView:nil];
+    let offset: CGPoint = msg![env; this contentOffset];
+    let offset = CGPoint {
+        x: offset.x + previous.x - location.x,
+        y: offset.y + previous.y - location.y,
+    };
+    let offset = clamp_content_offset(env, this, offset);
+    () = msg![env; this setContentOffset:offset];
+}
+- (())touchesEnded:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).dragging = false;
+}
+- (())touchesCancelled:(id)_touches // NSSet* of UITouch*
+             withEvent:(id)_event { // UIEvent*
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).dragging = false;
+}
+
+@end
+
+};
+
+/// Limit a content offset so that the view can't be scrolled past the edges
+/// of the content (plus the content inset).
+fn clamp_content_offset(env: &mut Environment, scroll_view: id, offset: CGPoint) -> CGPoint {
+    let &UIScrollViewHostObject {
+        content_size,
+        content_inset: inset,
+        ..
+    } = env.objc.borrow(scroll_view);
+    let bounds: CGRect = msg![env; scroll_view bounds];
+
+    let clamp = |value: CGFloat, min: CGFloat, max: CGFloat| value.min(max.max(min)).max(min);
+    CGPoint {
+        x: clamp(
+            offset.x,
+            -inset.left,
+            content_size.width + inset.right - bounds.size.width,
+        ),
+        y: clamp(
+            offset.y,
+            -inset.top,
+            content_size.height + inset.bottom - bounds.size.height,
+        ),
+    }
+}
@@ -0,0 +1,451 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITextView`.
+//!
+//! Useful resources:
+//! - [UITextViewDelegate overview](https://developer.apple.com/documentation/uikit/uitextviewdelegate?language=objc)
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::cg_context::CGContextSetRGBFillColor;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::frameworks::foundation::{NSRange, NSUInteger};
+use crate::frameworks::uikit::ui_color;
+use crate::frameworks::uikit::ui_font::{
+    UILineBreakModeWordWrap, UITextAlignment, UITextAlignmentLeft,
+};
+use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::frameworks::uikit::ui_view::ui_keyboard::{UIKeyboardType, UIKeyboardTypeDefault};
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_class, msg_send, msg_super, nil, objc_classes,
+    release, retain, ClassExports, NSZonePtr, SEL,
+};
+use crate::Environment;
+
+pub const UITextViewTextDidBeginEditingNotification: &str =
+    "UITextViewTextDidBeginEditingNotification";
+pub const UITextViewTextDidChangeNotification: &str = "UITextViewTextDidChangeNotification";
+pub const UITextViewTextDidEndEditingNotification: &str = "UITextViewTextDidEndEditingNotification";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UITextViewTextDidBeginEditingNotification",
+        HostConstant::NSString(UITextViewTextDidBeginEditingNotification),
+    ),
+    (
+        "_UITextViewTextDidChangeNotification",
+        HostConstant::NSString(UITextViewTextDidChangeNotification),
+    ),
+    (
+        "_UITextViewTextDidEndEditingNotification",
+        HostConstant::NSString(UITextViewTextDidEndEditingNotification),
+    ),
+];
+
+type UIDataDetectorTypes = NSUInteger;
+
+/// Space between the edges of the view and the text.
+const TEXT_INSET: CGFloat = 8.0;
+
+struct UITextViewHostObject {
+    superclass: super::UIScrollViewHostObject,
+    /// `NSString*`
+    text: id,
+    /// `UIFont*`
+    font: id,
+    /// `UIColor*`
+    text_color: id,
+    text_alignment: UITextAlignment,
+    editable: bool,
+    selectable: bool,
+    data_detector_types: UIDataDetectorTypes,
+    keyboard_type: UIKeyboardType,
+    editing: bool,
+}
+impl_HostObject_with_superclass!(UITextViewHostObject);
+impl Default for UITextViewHostObject {
+    fn default() -> Self {
+        UITextViewHostObject {
+            superclass: Default::default(),
+            text: nil,
+            font: nil,
+            text_color: nil,
+            text_alignment: UITextAlignmentLeft,
+            editable: true,
+            selectable: true,
+            data_detector_types: 0,
+            keyboard_type: UIKeyboardTypeDefault,
+            editing: false,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITextView: UIScrollView
+
+// TODO: selection, caret, scrolling to the caret, data detectors
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UITextViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithFrame:(CGRect)frame {
+    let this: id = msg_super![env; this initWithFrame:frame];
+    // These aren't redundant, the setters fetch the real defaults.
+    () = msg![env; this setText:nil];
+    () = msg![env; this setFont:nil];
+    () = msg![env; this setTextColor:nil];
+    let bg_color: id = msg_class![env; UIColor whiteColor];
+    () = msg![env; this setBackgroundColor:bg_color];
+    this
+}
+
+- (id)initWithCoder:(id)coder {
+    let this: id = msg_super![env; this initWithCoder:coder];
+    // TODO: Decode the actual property values from the coder
+    () = msg![env; this setText:nil];
+    () = msg![env; this setFont:nil];
+    () = msg![env; this setTextColor:nil];
+    this
+}
+
+- (())dealloc {
+    let &UITextViewHostObject {
+        text,
+        font,
+        text_color,
+        ..
+    } = env.objc.borrow(this);
+    release(env, text);
+    release(env, font);
+    release(env, text_color);
+    msg_super![env; this dealloc]
+}
+
+- (id)text {
+    env.objc.borrow::<UITextViewHostObject>(this).text
+}
+- (())setText:(id)new_text { // NSString*
+    let new_text: id = if new_text == nil {
+        get_static_str(env, "")
+    } else {
+        new_text
+    };
+    let new_text: id = msg![env; new_text copy];
+    let old_text = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextViewHostObject>(this).text,
+        new_text
+    );
+    release(env, old_text);
+
+    update_content_size(env, this);
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (id)font {
+    env.objc.borrow::<UITextViewHostObject>(this).font
+}
+- (())setFont:(id)new_font { // UIFont*
+    let new_font: id = if new_font == nil {
+        // reset to default
+        let size: CGFloat = 17.0;
+        msg_class![env; UIFont systemFontOfSize:size]
+    } else {
+        new_font
+    };
+    retain(env, new_font);
+    let old_font = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextViewHostObject>(this).font,
+        new_font
+    );
+    release(env, old_font);
+
+    update_content_size(env, this);
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (id)textColor {
+    env.objc.borrow::<UITextViewHostObject>(this).text_color
+}
+- (())setTextColor:(id)new_text_color { // UIColor*
+    let new_text_color: id = if new_text_color == nil {
+        msg_class![env; UIColor blackColor]
+    } else {
+        new_text_color
+    };
+    retain(env, new_text_color);
+    let old_text_color = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextViewHostObject>(this).text_color,
+        new_text_color
+    );
+    release(env, old_text_color);
+
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (UITextAlignment)textAlignment {
+    env.objc.borrow::<UITextViewHostObject>(this).text_alignment
+}
+- (())setTextAlignment:(UITextAlignment)text_alignment {
+    env.objc.borrow_mut::<UITextViewHostObject>(this).text_alignment = text_alignment;
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (bool)isEditable {
+    env.objc.borrow::<UITextViewHostObject>(this).editable
+}
+- (())setEditable:(bool)editable {
+    env.objc.borrow_mut::<UITextViewHostObject>(this).editable = editable;
+    if !editable && msg![env; this isFirstResponder] {
+        let _: bool = msg![env; this resignFirstResponder];
+    }
+}
+
+- (bool)isSelectable {
+    env.objc.borrow::<UITextViewHostObject>(this).selectable
+}
+- (())setSelectable:(bool)selectable {
+    env.objc.borrow_mut::<UITextViewHostObject>(this).selectable = selectable;
+}
+
+- (UIDataDetectorTypes)dataDetectorTypes {
+    env.objc.borrow::<UITextViewHostObject>(this).data_detector_types
+}
+- (())setDataDetectorTypes:(UIDataDetectorTypes)types {
+    if types != 0 {
+        log!("TODO: UITextView data detectors ({:#x}) are ignored", types);
+    }
+    env.objc.borrow_mut::<UITextViewHostObject>(this).data_detector_types = types;
+}
+
+- (bool)isEditing {
+    env.objc.borrow::<UITextViewHostObject>(this).editing
+}
+
+- (())setFrame:(CGRect)frame {
+    () = msg_super![env; this setFrame:frame];
+    update_content_size(env, this);
+}
+- (())setBounds:(CGRect)bounds {
+    let old_bounds: CGRect = msg![env; this bounds];
+    () = msg_super![env; this setBounds:bounds];
+    if old_bounds.size != bounds.size {
+        update_content_size(env, this);
+    }
+    // Scrolling changes which part of the text is visible.
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (())drawRect:(CGRect)_rect {
+    let bounds: CGRect = msg![env; this bounds];
+    let content_size: CGSize = msg![env; this contentSize];
+    let context = UIGraphicsGetCurrentContext(env);
+
+    let &UITextViewHostObject {
+        text,
+        font,
+        text_color,
+        text_alignment,
+        ..
+    } = env.objc.borrow(this);
+
+    let (r, g, b, a) = ui_color::get_rgba(&env.objc, text_color);
+    CGContextSetRGBFillColor(env, context, r, g, b, a);
+
+    // The text is laid out in content co-ordinates, so it doesn't need to be
+    // offset by the content offset (the bounds origin).
+    let rect = CGRect {
+        origin: CGPoint {
+            x: TEXT_INSET,
+            y: TEXT_INSET,
+        },
+        size: CGSize {
+            width: bounds.size.width - TEXT_INSET * 2.0,
+            height: content_size.height - TEXT_INSET * 2.0,
+        },
+    };
+    let _size: CGSize = msg![env; text drawInRect:rect
+                                         withFont:font
+                                    lineBreakMode:UILineBreakModeWordWrap
+                                        alignment:text_alignment];
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    let was_dragging: bool = msg![env; this isDragging];
+    () = msg_super![env; this touchesEnded:touches withEvent:event];
+    if !was_dragging && env.objc.borrow::<UITextViewHostObject>(this).editable {
+        let _: bool = msg![env; this becomeFirstResponder];
+    }
+}
+
+- (bool)canBecomeFirstResponder {
+    env.objc.borrow::<UITextViewHostObject>(this).editable
+}
+
+- (bool)becomeFirstResponder {
+    if env.objc.borrow::<UITextViewHostObject>(this).editing {
+        return true;
+    }
+
+    let delegate: id = msg![env; this delegate];
+    if !delegate_allows(env, delegate, "textViewShouldBeginEditing:", this) {
+        return false;
+    }
+
+    // This resigns the previous first responder, if any.
+    if !msg_super![env; this becomeFirstResponder] {
+        return false;
+    }
+    env.objc.borrow_mut::<UITextViewHostObject>(this).editing = true;
+
+    notify_delegate(env, delegate, "textViewDidBeginEditing:", this);
+    post_notification(env, UITextViewTextDidBeginEditingNotification, this);
+
+    true
+}
+
+- (bool)resignFirstResponder {
+    let delegate: id = msg![env; this delegate];
+    if !delegate_allows(env, delegate, "textViewShouldEndEditing:", this) {
+        return false;
+    }
+
+    let _: bool = msg_super![env; this resignFirstResponder];
+    env.objc.borrow_mut::<UITextViewHostObject>(this).editing = false;
+
+    notify_delegate(env, delegate, "textViewDidEndEditing:", this);
+    post_notification(env, UITextViewTextDidEndEditingNotification, this);
+
+    true
+}
+
+// UITextInputTraits implementation
+- (UIKeyboardType)keyboardType {
+    env.objc.borrow::<UITextViewHostObject>(this).keyboard_type
+}
+- (())setKeyboardType:(UIKeyboardType)type_ {
+    env.objc.borrow_mut::<UITextViewHostObject>(this).keyboard_type = type_;
+}
+
+// UIKeyInput implementation
+- (bool)hasText {
+    let text = env.objc.borrow::<UITextViewHostObject>(this).text;
+    let length: NSUInteger = msg![env; text length];
+    length != 0
+}
+- (())insertText:(id)new_text { // NSString*
+    // Unlike a text field, a text view takes newlines as ordinary text.
+    let text = env.objc.borrow::<UITextViewHostObject>(this).text;
+    let length: NSUInteger = msg![env; text length];
+    let range = NSRange {
+        location: length,
+        length: 0,
+    };
+    if !should_change_text(env, this, range, new_text) {
+        return;
+    }
+    // TODO: insert at the caret once there's a selection.
+    let new_text: id = msg![env; text stringByAppendingString:new_text];
+    () = msg![env; this setText:new_text];
+    release(env, new_text);
+    did_change_text(env, this);
+}
+- (())deleteBackward {
+    let text = env.objc.borrow::<UITextViewHostObject>(this).text;
+    let length: NSUInteger = msg![env; text length];
+    if length == 0 {
+        return;
+    }
+    let range = NSRange {
+        location: length - 1,
+        length: 1,
+    };
+    let empty = get_static_str(env, "");
+    if !should_change_text(env, this, range, empty) {
+        return;
+    }
+    let new_text: id = msg![env; text substringToIndex:(length - 1)];
+    () = msg![env; this setText:new_text];
+    did_change_text(env, this);
+}
+
+@end
+
+};
+
+/// Recompute the content size so the wrapped text can be scrolled through.
+fn update_content_size(env: &mut Environment, text_view: id) {
+    let &UITextViewHostObject { text, font, .. } = env.objc.borrow(text_view);
+    if text == nil || font == nil {
+        // Still being initialized.
+        return;
+    }
+    let bounds: CGRect = msg![env; text_view bounds];
+    let constraint = CGSize {
+        width: bounds.size.width - TEXT_INSET * 2.0,
+        height: CGFloat::MAX,
+    };
+    let text_size: CGSize = msg![env; text sizeWithFont:font
+                                      constrainedToSize:constraint
+                                          lineBreakMode:UILineBreakModeWordWrap];
+    let content_size = CGSize {
+        width: bounds.size.width,
+        height: text_size.height + TEXT_INSET * 2.0,
+    };
+    () = msg![env; text_view setContentSize:content_size];
+}
+
+fn should_change_text(env: &mut Environment, text_view: id, range: NSRange, text: id) -> bool {
+    let delegate: id = msg![env; text_view delegate];
+    let sel: SEL = env.objc.register_host_selector(
+        "textView:shouldChangeTextInRange:replacementText:".to_string(),
+        &mut env.mem,
+    );
+    delegate == nil
+        || !msg![env; delegate respondsToSelector:sel]
+        || msg![env; delegate textView:text_view
+               shouldChangeTextInRange:range
+                       replacementText:text]
+}
+
+fn did_change_text(env: &mut Environment, text_view: id) {
+    let delegate: id = msg![env; text_view delegate];
+    notify_delegate(env, delegate, "textViewDidChange:", text_view);
+    post_notification(env, UITextViewTextDidChangeNotification, text_view);
+}
+
+/// Ask an optional `textViewShould...:` delegate method. Missing delegates and
+/// methods count as a yes.
+fn delegate_allows(env: &mut Environment, delegate: id, selector: &str, text_view: id) -> bool {
+    let sel: SEL = env
+        .objc
+        .register_host_selector(selector.to_string(), &mut env.mem);
+    if delegate == nil || !msg![env; delegate respondsToSelector:sel] {
+        return true;
+    }
+    msg_send(env, (delegate, sel, text_view))
+}
+
+/// Call an optional `textViewDid...:` delegate method.
+fn notify_delegate(env: &mut Environment, delegate: id, selector: &str, text_view: id) {
+    let sel: SEL = env
+        .objc
+        .register_host_selector(selector.to_string(), &mut env.mem);
+    if delegate != nil && msg![env; delegate respondsToSelector:sel] {
+        let _: () = msg_send(env, (delegate, sel, text_view));
+    }
+}
+
+fn post_notification(env: &mut Environment, name: &'static str, text_view: id) {
+    let name = get_static_str(env, name);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    () = msg![env; center postNotificationName:name object:text_view];
+}
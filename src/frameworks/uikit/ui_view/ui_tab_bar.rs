@@ -0,0 +1,267 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITabBar`.
+//!
+//! Each item is shown as its image above its title, with the items spread
+//! evenly across the bar.
+
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_array, NSInteger, NSUInteger};
+use crate::frameworks::uikit::ui_font::UITextAlignmentCenter;
+use crate::objc::{
+    autorelease, id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes,
+    release, retain, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+const IMAGE_HEIGHT: CGFloat = 32.0;
+const TITLE_FONT_SIZE: CGFloat = 10.0;
+
+struct UITabBarHostObject {
+    superclass: super::UIViewHostObject,
+    /// `UITabBarItem*`s
+    items: Vec<id>,
+    /// `UITabBarItem*`, weak reference (it's one of the items)
+    selected_item: id,
+    /// Weak reference.
+    delegate: id,
+}
+impl_HostObject_with_superclass!(UITabBarHostObject);
+impl Default for UITabBarHostObject {
+    fn default() -> Self {
+        UITabBarHostObject {
+            superclass: Default::default(),
+            items: Vec::new(),
+            selected_item: nil,
+            delegate: nil,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITabBar: UIView
+
+// TODO: customization, badges, "More" item
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UITabBarHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithFrame:(CGRect)frame {
+    let this: id = msg_super![env; this initWithFrame:frame];
+    let background: id = msg_class![env; UIColor blackColor];
+    () = msg![env; this setBackgroundColor:background];
+    this
+}
+
+- (())dealloc {
+    let items = std::mem::take(&mut env.objc.borrow_mut::<UITabBarHostObject>(this).items);
+    for item in items {
+        release(env, item);
+    }
+    msg_super![env; this dealloc]
+}
+
+// weak/non-retaining
+- (id)delegate {
+    env.objc.borrow::<UITabBarHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing UITabBarDelegate
+    env.objc.borrow_mut::<UITabBarHostObject>(this).delegate = delegate;
+}
+
+- (id)items {
+    let items = env.objc.borrow::<UITabBarHostObject>(this).items.clone();
+    for &item in &items {
+        retain(env, item);
+    }
+    let items = ns_array::from_vec(env, items);
+    autorelease(env, items)
+}
+- (())setItems:(id)items { // NSArray* of UITabBarItem*
+    let count: NSUInteger = msg![env; items count];
+    let mut new_items = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let item: id = msg![env; items objectAtIndex:i];
+        new_items.push(retain(env, item));
+    }
+    let host_object = env.objc.borrow_mut::<UITabBarHostObject>(this);
+    let old_items = std::mem::replace(&mut host_object.items, new_items);
+    if !host_object.items.contains(&host_object.selected_item) {
+        host_object.selected_item = nil;
+    }
+    for item in old_items {
+        release(env, item);
+    }
+    update_item_views(env, this);
+}
+- (())setItems:(id)items // NSArray* of UITabBarItem*
+      animated:(bool)_animated {
+    () = msg![env; this setItems:items];
+}
+
+- (id)selectedItem {
+    env.objc.borrow::<UITabBarHostObject>(this).selected_item
+}
+- (())setSelectedItem:(id)item { // UITabBarItem*
+    let host_object = env.objc.borrow_mut::<UITabBarHostObject>(this);
+    if item != nil && !host_object.items.contains(&item) {
+        log!("Warning: [(UITabBar*){:?} setSelectedItem:{:?}] for an item not in the bar, ignoring.", this, item);
+        return;
+    }
+    host_object.selected_item = item;
+    update_item_views(env, this);
+}
+
+- (())layoutSubviews {
+    update_item_views(env, this);
+}
+
+- (())touchesBegan:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    // Items are selected when the touch ends.
+}
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    let location: CGPoint = msg![env; touch locationInView:this];
+    let _: NSInteger = msg![env; this pressItemAtPoint:location];
+}
+
+// Not public API. Returns the index of the item that was pressed, or -1 if
+// there was no item at the point.
+- (NSInteger)pressItemAtPoint:(CGPoint)point {
+    let items = env.objc.borrow::<UITabBarHostObject>(this).items.clone();
+    let index = item_frames(env, this, items.len())
+        .into_iter()
+        .position(|frame| {
+            (frame.origin.x..frame.origin.x + frame.size.width).contains(&point.x)
+                && (frame.origin.y..frame.origin.y + frame.size.height).contains(&point.y)
+        });
+    let Some(index) = index else {
+        return -1;
+    };
+    let item = items[index];
+    let enabled: bool = msg![env; item isEnabled];
+    if !enabled {
+        return -1;
+    }
+    log_dbg!("Tab bar {:?} item {} pressed", this, index);
+
+    () = msg![env; this setSelectedItem:item];
+    let delegate = env.objc.borrow::<UITabBarHostObject>(this).delegate;
+    if delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, "tabBar:didSelectItem:")
+    {
+        () = msg![env; delegate tabBar:this didSelectItem:item];
+    }
+    index.try_into().unwrap()
+}
+
+@end
+
+};
+
+/// The area of the bar that each item occupies.
+fn item_frames(env: &mut Environment, tab_bar: id, count: usize) -> Vec<CGRect> {
+    let bounds: CGRect = msg![env; tab_bar bounds];
+    let width = bounds.size.width / count.max(1) as CGFloat;
+    (0..count)
+        .map(|i| CGRect {
+            origin: CGPoint {
+                x: bounds.origin.x + i as CGFloat * width,
+                y: bounds.origin.y,
+            },
+            size: CGSize {
+                width,
+                height: bounds.size.height,
+            },
+        })
+        .collect()
+}
+
+/// Recreate the views that show the items.
+fn update_item_views(env: &mut Environment, tab_bar: id) {
+    let subviews = env
+        .objc
+        .borrow::<super::UIViewHostObject>(tab_bar)
+        .subviews
+        .clone();
+    for subview in subviews {
+        () = msg![env; subview removeFromSuperview];
+    }
+
+    let items = env.objc.borrow::<UITabBarHostObject>(tab_bar).items.clone();
+    let selected_item = env.objc.borrow::<UITabBarHostObject>(tab_bar).selected_item;
+    let frames = item_frames(env, tab_bar, items.len());
+    for (&item, frame) in items.iter().zip(frames) {
+        let background: id = if item == selected_item {
+            msg_class![env; UIColor darkGrayColor]
+        } else {
+            msg_class![env; UIColor clearColor]
+        };
+        let item_view: id = msg_class![env; UIView alloc];
+        let item_view: id = msg![env; item_view initWithFrame:frame];
+        () = msg![env; item_view setBackgroundColor:background];
+        // Touches should go to the bar itself.
+        () = msg![env; item_view setUserInteractionEnabled:false];
+        () = msg![env; tab_bar addSubview:item_view];
+        release(env, item_view);
+
+        let image: id = msg![env; item image];
+        if image != nil {
+            let image_view: id = msg_class![env; UIImageView alloc];
+            let image_view: id = msg![env; image_view initWithImage:image];
+            let image_size: CGSize = msg![env; image size];
+            let image_frame = CGRect {
+                origin: CGPoint {
+                    x: ((frame.size.width - image_size.width) / 2.0).round(),
+                    y: ((IMAGE_HEIGHT - image_size.height) / 2.0).round(),
+                },
+                size: image_size,
+            };
+            () = msg![env; image_view setFrame:image_frame];
+            () = msg![env; item_view addSubview:image_view];
+            release(env, image_view);
+        }
+
+        let title: id = msg![env; item title];
+        if title != nil {
+            let title_frame = CGRect {
+                origin: CGPoint {
+                    x: 0.0,
+                    y: IMAGE_HEIGHT,
+                },
+                size: CGSize {
+                    width: frame.size.width,
+                    height: (frame.size.height - IMAGE_HEIGHT).max(0.0),
+                },
+            };
+            let label: id = msg_class![env; UILabel alloc];
+            let label: id = msg![env; label initWithFrame:title_frame];
+            () = msg![env; label setText:title];
+            let font: id = msg_class![env; UIFont boldSystemFontOfSize:TITLE_FONT_SIZE];
+            () = msg![env; label setFont:font];
+            () = msg![env; label setTextAlignment:UITextAlignmentCenter];
+            let clear: id = msg_class![env; UIColor clearColor];
+            () = msg![env; label setBackgroundColor:clear];
+            let text_color: id = if item == selected_item {
+                msg_class![env; UIColor whiteColor]
+            } else {
+                msg_class![env; UIColor lightGrayColor]
+            };
+            () = msg![env; label setTextColor:text_color];
+            () = msg![env; item_view addSubview:label];
+            release(env, label);
+        }
+    }
+}
@@ -6,7 +6,14 @@
 //! `UIWindow`.
 
 use crate::frameworks::core_graphics::CGRect;
-use crate::objc::{id, msg, msg_super, objc_classes, ClassExports};
+use crate::frameworks::foundation::NSUInteger;
+use crate::frameworks::uikit::ui_touch::{
+    UITouchPhase, UITouchPhaseBegan, UITouchPhaseEnded, UITouchPhaseMoved,
+};
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes, release,
+    retain, ClassExports, NSZonePtr,
+};
 
 #[derive(Default)]
 pub struct State {
@@ -14,7 +21,17 @@ pub struct State {
     ///
     /// This is public because Core Animation also uses it.
     pub visible_windows: Vec<id>,
+    /// The key window, if any. Non-retaining!
+    pub key_window: id,
+}
+
+#[derive(Default)]
+struct UIWindowHostObject {
+    superclass: super::UIViewHostObject,
+    /// `UIViewController*`, strong reference
+    root_view_controller: id,
 }
+impl_HostObject_with_superclass!(UIWindowHostObject);
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -22,6 +39,11 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UIWindow: UIView
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIWindowHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
 // TODO: more?
 
 - (id)initWithFrame:(CGRect)frame {
@@ -64,11 +86,27 @@ pub const CLASSES: ClassExports = objc_classes! {
             visible_list,
         );
     }
+    let state = &mut env.framework_state.uikit.ui_view.ui_window;
+    if state.key_window == this {
+        state.key_window = nil;
+    }
+    let root_view_controller = env.objc.borrow::<UIWindowHostObject>(this).root_view_controller;
+    release(env, root_view_controller);
     msg_super![env; this dealloc]
 }
 
 - (())setHidden:(bool)is_hidden {
     let was_hidden: bool = msg![env; this isHidden];
+    // The root view controller's view appears and disappears with the window.
+    let root_view_controller = env.objc.borrow::<UIWindowHostObject>(this).root_view_controller;
+    let appearing = root_view_controller != nil && was_hidden && !is_hidden;
+    let disappearing = root_view_controller != nil && !was_hidden && is_hidden;
+    if appearing {
+        () = msg![env; root_view_controller viewWillAppear:false];
+    } else if disappearing {
+        () = msg![env; root_view_controller viewWillDisappear:false];
+    }
+
     () = msg_super![env; this setHidden:is_hidden];
 
     let visible_list = &mut env.framework_state.uikit.ui_view.ui_window.visible_windows;
@@ -88,15 +126,137 @@ pub const CLASSES: ClassExports = objc_classes! {
             visible_list,
         );
     }
+
+    if appearing {
+        () = msg![env; root_view_controller viewDidAppear:false];
+    } else if disappearing {
+        () = msg![env; root_view_controller viewDidDisappear:false];
+    }
 }
 
-- (())makeKeyAndVisible {
-    // TODO: Set the "key" window once it's relevant. We don't currently have
-    // send any non-touch events to windows, so there's no meaning in it yet.
+- (id)nextResponder {
+    msg_class![env; UIApplication sharedApplication]
+}
 
+- (bool)isKeyWindow {
+    env.framework_state.uikit.ui_view.ui_window.key_window == this
+}
+- (())makeKeyWindow {
+    let old_key_window = std::mem::replace(
+        &mut env.framework_state.uikit.ui_view.ui_window.key_window,
+        this,
+    );
+    if old_key_window == this {
+        return;
+    }
+    if old_key_window != nil {
+        () = msg![env; old_key_window resignKeyWindow];
+    }
+    () = msg![env; this becomeKeyWindow];
+}
+- (())becomeKeyWindow {
+    // Nothing to do. Subclasses may override this.
+}
+- (())resignKeyWindow {
+    // Nothing to do. Subclasses may override this.
+}
+
+- (())makeKeyAndVisible {
+    () = msg![env; this makeKeyWindow];
     msg![env; this setHidden:false]
 }
 
+- (id)rootViewController {
+    env.objc.borrow::<UIWindowHostObject>(this).root_view_controller
+}
+- (())setRootViewController:(id)new_controller { // UIViewController*
+    let old_controller = env.objc.borrow::<UIWindowHostObject>(this).root_view_controller;
+    if new_controller == old_controller {
+        return;
+    }
+    retain(env, new_controller);
+    env.objc.borrow_mut::<UIWindowHostObject>(this).root_view_controller = new_controller;
+
+    let visible = !msg![env; this isHidden];
+
+    if old_controller != nil {
+        let old_view: id = msg![env; old_controller view];
+        if visible {
+            () = msg![env; old_controller viewWillDisappear:false];
+        }
+        () = msg![env; old_view removeFromSuperview];
+        if visible {
+            () = msg![env; old_controller viewDidDisappear:false];
+        }
+        release(env, old_controller);
+    }
+
+    if new_controller != nil {
+        let new_view: id = msg![env; new_controller view];
+        // The view fills the area not covered by the status bar. This assumes
+        // the window covers the whole screen.
+        let screen: id = msg_class![env; UIScreen mainScreen];
+        let frame: CGRect = msg![env; screen applicationFrame];
+        () = msg![env; new_view setFrame:frame];
+        if visible {
+            () = msg![env; new_controller viewWillAppear:false];
+        }
+        () = msg![env; this addSubview:new_view];
+        if visible {
+            () = msg![env; new_controller viewDidAppear:false];
+        }
+    }
+}
+
+// Event handling
+
+- (())sendEvent:(id)event { // UIEvent*
+    // Deliver each touch belonging to this window to the view it was
+    // hit-tested to, grouped by view.
+    let touches: id = msg![env; event allTouches];
+    let touches: id = msg![env; touches allObjects];
+    let count: NSUInteger = msg![env; touches count];
+    // view, phase and set of touches, in order of first appearance
+    let mut view_touches: Vec<(id, UITouchPhase, id)> = Vec::new();
+    for i in 0..count {
+        let touch: id = msg![env; touches objectAtIndex:i];
+        let window: id = msg![env; touch window];
+        let view: id = msg![env; touch view];
+        let phase: UITouchPhase = msg![env; touch phase];
+        if window != this || view == nil {
+            continue;
+        }
+        let set = match view_touches.iter().find(|&&(v, p, _)| v == view && p == phase) {
+            Some(&(_, _, set)) => set,
+            None => {
+                let set: id = msg_class![env; NSMutableSet new];
+                view_touches.push((view, phase, set));
+                set
+            }
+        };
+        () = msg![env; set addObject:touch];
+    }
+
+    for (view, phase, touches) in view_touches {
+        match phase {
+            UITouchPhaseBegan => {
+                log_dbg!("Sending [{:?} touchesBegan:{:?} withEvent:{:?}]", view, touches, event);
+                () = msg![env; view touchesBegan:touches withEvent:event];
+            }
+            UITouchPhaseMoved => {
+                log_dbg!("Sending [{:?} touchesMoved:{:?} withEvent:{:?}]", view, touches, event);
+                () = msg![env; view touchesMoved:touches withEvent:event];
+            }
+            UITouchPhaseEnded => {
+                log_dbg!("Sending [{:?} touchesEnded:{:?} withEvent:{:?}]", view, touches, event);
+                () = msg![env; view touchesEnded:touches withEvent:event];
+            }
+            _ => (),
+        }
+        release(env, touches);
+    }
+}
+
 @end
 
 };
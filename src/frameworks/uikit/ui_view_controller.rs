@@ -5,14 +5,40 @@
  */
 //! `UIViewController`.
 
+pub mod ui_tab_bar_controller;
+
+use super::ui_view::UIViewHostObject;
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::frameworks::core_graphics::{CGPoint, CGRect};
 use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::frameworks::foundation::NSInteger;
 use crate::objc::{
-    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+    get_block_invoke, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports,
+    HostObject, NSZonePtr,
 };
+use crate::Environment;
+
+type UIModalTransitionStyle = NSInteger;
+const UIModalTransitionStyleCoverVertical: UIModalTransitionStyle = 0;
+const UIModalTransitionStyleCrossDissolve: UIModalTransitionStyle = 2;
+
+/// Roughly how long the real modal transitions take.
+const MODAL_TRANSITION_DURATION: CFTimeInterval = 0.3;
 
 #[derive(Default)]
-struct UIViewControllerHostObject {
+pub struct UIViewControllerHostObject {
     view: id,
+    /// `NSString*`
+    title: id,
+    /// `UITabBarItem*`, created on first use.
+    tab_bar_item: id,
+    /// Weak reference. Set by container view controllers.
+    parent_view_controller: id,
+    /// Strong reference. The controller this one is presenting modally.
+    presented_view_controller: id,
+    /// Weak reference. The controller presenting this one modally.
+    presenting_view_controller: id,
+    modal_transition_style: UIModalTransitionStyle,
 }
 impl HostObject for UIViewControllerHostObject {}
 
@@ -37,9 +63,26 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())dealloc {
-    let &UIViewControllerHostObject { view } = env.objc.borrow(this);
+    let &UIViewControllerHostObject {
+        view,
+        title,
+        tab_bar_item,
+        presented_view_controller,
+        ..
+    } = env.objc.borrow(this);
 
+    if view != nil {
+        env.objc.borrow_mut::<UIViewHostObject>(view).view_controller = nil;
+    }
     release(env, view);
+    release(env, title);
+    release(env, tab_bar_item);
+    if presented_view_controller != nil {
+        env.objc
+            .borrow_mut::<UIViewControllerHostObject>(presented_view_controller)
+            .presenting_view_controller = nil;
+    }
+    release(env, presented_view_controller);
 
     env.objc.dealloc_object(this, &mut env.mem);
 }
@@ -54,6 +97,12 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())setView:(id)new_view { // UIView*
     let host_obj = env.objc.borrow_mut::<UIViewControllerHostObject>(this);
     let old_view = std::mem::replace(&mut host_obj.view, new_view);
+    if old_view != nil {
+        env.objc.borrow_mut::<UIViewHostObject>(old_view).view_controller = nil;
+    }
+    if new_view != nil {
+        env.objc.borrow_mut::<UIViewHostObject>(new_view).view_controller = this;
+    }
     retain(env, new_view);
     release(env, old_view);
 }
@@ -68,14 +117,290 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (id)nextResponder {
+    // The view's superview, not the view itself: the view's next responder is
+    // this controller.
+    let view = env.objc.borrow::<UIViewControllerHostObject>(this).view;
+    if view == nil {
+        nil
+    } else {
+        msg![env; view superview]
+    }
+}
+
+- (id)title {
+    env.objc.borrow::<UIViewControllerHostObject>(this).title
+}
+- (())setTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    let old_title = std::mem::replace(
+        &mut env.objc.borrow_mut::<UIViewControllerHostObject>(this).title,
+        title,
+    );
+    release(env, old_title);
+    // The tab bar item follows the title.
+    let tab_bar_item = env.objc.borrow::<UIViewControllerHostObject>(this).tab_bar_item;
+    if tab_bar_item != nil {
+        () = msg![env; tab_bar_item setTitle:title];
+    }
+}
+
+- (id)tabBarItem {
+    let tab_bar_item = env.objc.borrow::<UIViewControllerHostObject>(this).tab_bar_item;
+    if tab_bar_item != nil {
+        return tab_bar_item;
+    }
+    let title: id = msg![env; this title];
+    let tab_bar_item: id = msg_class![env; UITabBarItem alloc];
+    let tab_bar_item: id = msg![env; tab_bar_item initWithTitle:title image:nil tag:0];
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).tab_bar_item = tab_bar_item;
+    tab_bar_item
+}
+- (())setTabBarItem:(id)tab_bar_item { // UITabBarItem*
+    retain(env, tab_bar_item);
+    let old_tab_bar_item = std::mem::replace(
+        &mut env.objc.borrow_mut::<UIViewControllerHostObject>(this).tab_bar_item,
+        tab_bar_item,
+    );
+    release(env, old_tab_bar_item);
+}
+
+- (id)parentViewController {
+    env.objc.borrow::<UIViewControllerHostObject>(this).parent_view_controller
+}
+- (id)tabBarController {
+    let tab_bar_controller_class = env.objc.get_known_class("UITabBarController", &mut env.mem);
+    let mut controller = env.objc.borrow::<UIViewControllerHostObject>(this).parent_view_controller;
+    while controller != nil {
+        if msg![env; controller isKindOfClass:tab_bar_controller_class] {
+            return controller;
+        }
+        controller = env.objc.borrow::<UIViewControllerHostObject>(controller).parent_view_controller;
+    }
+    nil
+}
+
+// Appearance notifications, sent by UIWindow for its root view controller.
+// Subclasses override these.
+- (())viewWillAppear:(bool)_animated {}
+- (())viewDidAppear:(bool)_animated {}
+- (())viewWillDisappear:(bool)_animated {}
+- (())viewDidDisappear:(bool)_animated {}
+
 - (())setEditing:(bool)editing {
     log!("TODO: [(UIViewController*){:?} setEditing:{}]", this, editing); // TODO
 }
 
+- (UIModalTransitionStyle)modalTransitionStyle {
+    env.objc.borrow::<UIViewControllerHostObject>(this).modal_transition_style
+}
+- (())setModalTransitionStyle:(UIModalTransitionStyle)style {
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).modal_transition_style = style;
+}
+
+- (id)presentedViewController {
+    env.objc.borrow::<UIViewControllerHostObject>(this).presented_view_controller
+}
+- (id)modalViewController {
+    msg![env; this presentedViewController]
+}
+- (id)presentingViewController {
+    env.objc.borrow::<UIViewControllerHostObject>(this).presenting_view_controller
+}
+
+- (())presentViewController:(id)controller // UIViewController*
+                   animated:(bool)animated
+                 completion:(id)completion { // void (^)(void)
+    present_view_controller(env, this, controller, animated);
+    call_completion(env, completion);
+}
+- (())presentModalViewController:(id)controller // UIViewController*
+                        animated:(bool)animated {
+    msg![env; this presentViewController:controller animated:animated completion:nil]
+}
+
+- (())dismissViewControllerAnimated:(bool)animated
+                         completion:(id)completion { // void (^)(void)
+    dismiss_view_controller(env, this, animated);
+    call_completion(env, completion);
+}
 - (())dismissModalViewControllerAnimated:(bool)animated {
-    log!("TODO: [(UIViewController*){:?} dismissModalViewControllerAnimated:{}]", this, animated); // TODO
+    msg![env; this dismissViewControllerAnimated:animated completion:nil]
 }
 
 @end
 
 };
+
+/// Present a view controller modally. Its view covers the presenter's.
+fn present_view_controller(env: &mut Environment, presenter: id, presented: id, animated: bool) {
+    if env
+        .objc
+        .borrow::<UIViewControllerHostObject>(presented)
+        .presenting_view_controller
+        != nil
+    {
+        log!(
+            "Warning: {:?} is already being presented, can't present it again",
+            presented
+        );
+        return;
+    }
+
+    // A controller can only present one controller at a time, so presenting
+    // from a controller that is already presenting adds to the end of the
+    // chain.
+    let mut presenter = presenter;
+    loop {
+        let next = env
+            .objc
+            .borrow::<UIViewControllerHostObject>(presenter)
+            .presented_view_controller;
+        if next == nil {
+            break;
+        }
+        presenter = next;
+    }
+
+    retain(env, presented);
+    env.objc
+        .borrow_mut::<UIViewControllerHostObject>(presenter)
+        .presented_view_controller = presented;
+    env.objc
+        .borrow_mut::<UIViewControllerHostObject>(presented)
+        .presenting_view_controller = presenter;
+
+    let presenter_view: id = msg![env; presenter view];
+    let window: id = msg![env; presenter_view window];
+    let view: id = msg![env; presented view];
+
+    () = msg![env; presenter viewWillDisappear:animated];
+    () = msg![env; presented viewWillAppear:animated];
+    if window != nil {
+        // Like the root view controller's view, this fills the area not
+        // covered by the status bar. The presenter's view stays underneath.
+        let screen: id = msg_class![env; UIScreen mainScreen];
+        let frame: CGRect = msg![env; screen applicationFrame];
+        () = msg![env; view setFrame:frame];
+        () = msg![env; window addSubview:view];
+        if animated {
+            animate_presentation(env, presented, view);
+        }
+    } else {
+        log!(
+            "Warning: {:?} presented {:?}, but its view isn't in a window",
+            presenter,
+            presented
+        );
+    }
+    () = msg![env; presenter viewDidDisappear:animated];
+    () = msg![env; presented viewDidAppear:animated];
+}
+
+/// Start the animation for a modal view controller's view appearing,
+/// according to the controller's `modalTransitionStyle`.
+fn animate_presentation(env: &mut Environment, controller: id, view: id) {
+    let style = env
+        .objc
+        .borrow::<UIViewControllerHostObject>(controller)
+        .modal_transition_style;
+    let layer: id = msg![env; view layer];
+    let (key_path, from_value): (&str, id) = match style {
+        UIModalTransitionStyleCrossDissolve => {
+            let from_value: id = msg_class![env; NSNumber numberWithFloat:0f32];
+            ("opacity", from_value)
+        }
+        _ => {
+            if style != UIModalTransitionStyleCoverVertical {
+                log!("TODO: modal transition style {}, sliding up instead", style);
+            }
+            // Slide up from just below the bottom of the screen.
+            let screen: id = msg_class![env; UIScreen mainScreen];
+            let bounds: CGRect = msg![env; screen bounds];
+            let position: CGPoint = msg![env; layer position];
+            let from = CGPoint {
+                x: position.x,
+                y: position.y + bounds.size.height,
+            };
+            let from_value: id = msg_class![env; NSValue valueWithCGPoint:from];
+            ("position", from_value)
+        }
+    };
+    let key_path = get_static_str(env, key_path);
+    let animation: id = msg_class![env; CABasicAnimation animationWithKeyPath:key_path];
+    () = msg![env; animation setFromValue:from_value];
+    () = msg![env; animation setDuration:MODAL_TRANSITION_DURATION];
+    () = msg![env; layer addAnimation:animation forKey:key_path];
+}
+
+/// Dismiss the view controller `controller` is presenting, or if it isn't
+/// presenting one, `controller` itself. Anything presented by the dismissed
+/// controller is dismissed too.
+fn dismiss_view_controller(env: &mut Environment, controller: id, animated: bool) {
+    let &UIViewControllerHostObject {
+        presented_view_controller,
+        presenting_view_controller,
+        ..
+    } = env.objc.borrow(controller);
+    let (presenter, presented) = if presented_view_controller != nil {
+        (controller, presented_view_controller)
+    } else if presenting_view_controller != nil {
+        (presenting_view_controller, controller)
+    } else {
+        log!(
+            "Warning: {:?} has no presented view controller to dismiss",
+            controller
+        );
+        return;
+    };
+
+    let mut chain = vec![presented];
+    loop {
+        let next = env
+            .objc
+            .borrow::<UIViewControllerHostObject>(*chain.last().unwrap())
+            .presented_view_controller;
+        if next == nil {
+            break;
+        }
+        chain.push(next);
+    }
+    env.objc
+        .borrow_mut::<UIViewControllerHostObject>(presenter)
+        .presented_view_controller = nil;
+
+    // TODO: animate dismissal
+    () = msg![env; presenter viewWillAppear:animated];
+    // Topmost first.
+    for &controller in chain.iter().rev() {
+        () = msg![env; controller viewWillDisappear:animated];
+        let view = env
+            .objc
+            .borrow::<UIViewControllerHostObject>(controller)
+            .view;
+        if view != nil {
+            () = msg![env; view removeFromSuperview];
+        }
+        () = msg![env; controller viewDidDisappear:animated];
+        let host_obj = env
+            .objc
+            .borrow_mut::<UIViewControllerHostObject>(controller);
+        host_obj.presented_view_controller = nil;
+        host_obj.presenting_view_controller = nil;
+    }
+    () = msg![env; presenter viewDidAppear:animated];
+
+    // Each controller in the chain was retained by its presenter.
+    for controller in chain {
+        release(env, controller);
+    }
+}
+
+/// Call a `void (^)(void)` completion block, if there is one.
+fn call_completion(env: &mut Environment, completion: id) {
+    if completion == nil {
+        return;
+    }
+    let invoke = get_block_invoke(&env.mem, completion);
+    () = invoke.call_from_host(env, (completion,));
+}
@@ -0,0 +1,360 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITabBarController`.
+
+use super::UIViewControllerHostObject;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_array, NSNotFound, NSUInteger};
+use crate::frameworks::uikit::ui_view::{
+    UIViewAutoresizingFlexibleHeight, UIViewAutoresizingFlexibleTopMargin,
+    UIViewAutoresizingFlexibleWidth,
+};
+use crate::objc::{
+    autorelease, id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes,
+    release, retain, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+const TAB_BAR_HEIGHT: CGFloat = 49.0;
+
+pub struct UITabBarControllerHostObject {
+    superclass: UIViewControllerHostObject,
+    /// `UIViewController*`s
+    view_controllers: Vec<id>,
+    selected_index: NSUInteger,
+    /// Weak reference.
+    delegate: id,
+    /// `UITabBar*`, created along with the view.
+    tab_bar: id,
+}
+impl_HostObject_with_superclass!(UITabBarControllerHostObject);
+impl Default for UITabBarControllerHostObject {
+    fn default() -> Self {
+        UITabBarControllerHostObject {
+            superclass: Default::default(),
+            view_controllers: Vec::new(),
+            selected_index: NSNotFound as NSUInteger,
+            delegate: nil,
+            tab_bar: nil,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITabBarController: UIViewController
+
+// TODO: "More" navigation controller, customizable view controllers
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UITabBarControllerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<UITabBarControllerHostObject>(this);
+    let view_controllers = std::mem::take(&mut host_object.view_controllers);
+    let tab_bar = std::mem::replace(&mut host_object.tab_bar, nil);
+    for controller in view_controllers {
+        env.objc.borrow_mut::<UIViewControllerHostObject>(controller).parent_view_controller = nil;
+        release(env, controller);
+    }
+    if tab_bar != nil {
+        () = msg![env; tab_bar setDelegate:nil];
+    }
+    release(env, tab_bar);
+    msg_super![env; this dealloc]
+}
+
+- (())loadView {
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let frame: CGRect = msg![env; screen applicationFrame];
+    let view: id = msg_class![env; UIView alloc];
+    let view: id = msg![env; view initWithFrame:frame];
+    () = msg![env; this setView:view];
+    release(env, view);
+
+    let tab_bar_frame = CGRect {
+        origin: CGPoint {
+            x: 0.0,
+            y: frame.size.height - TAB_BAR_HEIGHT,
+        },
+        size: CGSize {
+            width: frame.size.width,
+            height: TAB_BAR_HEIGHT,
+        },
+    };
+    let tab_bar: id = msg_class![env; UITabBar alloc];
+    let tab_bar: id = msg![env; tab_bar initWithFrame:tab_bar_frame];
+    () = msg![env; tab_bar setAutoresizingMask:(
+        UIViewAutoresizingFlexibleWidth | UIViewAutoresizingFlexibleTopMargin
+    )];
+    () = msg![env; tab_bar setDelegate:this];
+    () = msg![env; view addSubview:tab_bar];
+    env.objc.borrow_mut::<UITabBarControllerHostObject>(this).tab_bar = tab_bar;
+
+    update_tab_bar(env, this);
+    let selected: id = msg![env; this selectedViewController];
+    if selected != nil {
+        show_child(env, this, selected);
+    }
+}
+
+- (id)tabBar {
+    // The tab bar is part of the view.
+    let _: id = msg![env; this view];
+    env.objc.borrow::<UITabBarControllerHostObject>(this).tab_bar
+}
+
+// weak/non-retaining
+- (id)delegate {
+    env.objc.borrow::<UITabBarControllerHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing UITabBarControllerDelegate
+    env.objc.borrow_mut::<UITabBarControllerHostObject>(this).delegate = delegate;
+}
+
+- (id)viewControllers {
+    let view_controllers = env.objc.borrow::<UITabBarControllerHostObject>(this).view_controllers.clone();
+    for &controller in &view_controllers {
+        retain(env, controller);
+    }
+    let view_controllers = ns_array::from_vec(env, view_controllers);
+    autorelease(env, view_controllers)
+}
+- (())setViewControllers:(id)view_controllers { // NSArray* of UIViewController*
+    () = msg![env; this setViewControllers:view_controllers animated:false];
+}
+- (())setViewControllers:(id)view_controllers // NSArray* of UIViewController*
+                animated:(bool)_animated {
+    let old_selected: id = msg![env; this selectedViewController];
+
+    let count: NSUInteger = if view_controllers == nil {
+        0
+    } else {
+        msg![env; view_controllers count]
+    };
+    let mut new_view_controllers = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let controller: id = msg![env; view_controllers objectAtIndex:i];
+        new_view_controllers.push(retain(env, controller));
+    }
+    for &controller in &new_view_controllers {
+        env.objc.borrow_mut::<UIViewControllerHostObject>(controller).parent_view_controller = this;
+    }
+
+    let host_object = env.objc.borrow_mut::<UITabBarControllerHostObject>(this);
+    let old_view_controllers = std::mem::replace(&mut host_object.view_controllers, new_view_controllers);
+    // Keep the same controller selected if possible, otherwise select the
+    // first one.
+    host_object.selected_index = match host_object.view_controllers.iter().position(|&c| c == old_selected) {
+        Some(index) => index as NSUInteger,
+        None if count > 0 => 0,
+        None => NSNotFound as NSUInteger,
+    };
+    let new_selected: id = msg![env; this selectedViewController];
+
+    if env.objc.borrow::<UITabBarControllerHostObject>(this).tab_bar != nil {
+        update_tab_bar(env, this);
+        if new_selected != old_selected {
+            swap_children(env, this, old_selected, new_selected);
+        }
+    }
+
+    let new_view_controllers = env.objc.borrow::<UITabBarControllerHostObject>(this).view_controllers.clone();
+    for controller in old_view_controllers {
+        if !new_view_controllers.contains(&controller) {
+            env.objc.borrow_mut::<UIViewControllerHostObject>(controller).parent_view_controller = nil;
+        }
+        release(env, controller);
+    }
+}
+
+- (NSUInteger)selectedIndex {
+    env.objc.borrow::<UITabBarControllerHostObject>(this).selected_index
+}
+- (())setSelectedIndex:(NSUInteger)index {
+    let host_object = env.objc.borrow::<UITabBarControllerHostObject>(this);
+    if index as usize >= host_object.view_controllers.len() {
+        log!("Warning: [(UITabBarController*){:?} setSelectedIndex:{}] is out of range, ignoring.", this, index);
+        return;
+    }
+    let old_selected: id = msg![env; this selectedViewController];
+    env.objc.borrow_mut::<UITabBarControllerHostObject>(this).selected_index = index;
+    let new_selected: id = msg![env; this selectedViewController];
+
+    if env.objc.borrow::<UITabBarControllerHostObject>(this).tab_bar != nil {
+        let item: id = msg![env; new_selected tabBarItem];
+        let tab_bar = env.objc.borrow::<UITabBarControllerHostObject>(this).tab_bar;
+        () = msg![env; tab_bar setSelectedItem:item];
+        if new_selected != old_selected {
+            swap_children(env, this, old_selected, new_selected);
+        }
+    }
+}
+
+- (id)selectedViewController {
+    let host_object = env.objc.borrow::<UITabBarControllerHostObject>(this);
+    host_object
+        .view_controllers
+        .get(host_object.selected_index as usize)
+        .copied()
+        .unwrap_or(nil)
+}
+- (())setSelectedViewController:(id)controller { // UIViewController*
+    let index = env
+        .objc
+        .borrow::<UITabBarControllerHostObject>(this)
+        .view_controllers
+        .iter()
+        .position(|&c| c == controller);
+    let Some(index) = index else {
+        log!("Warning: [(UITabBarController*){:?} setSelectedViewController:{:?}] for a controller not in the tab bar controller, ignoring.", this, controller);
+        return;
+    };
+    () = msg![env; this setSelectedIndex:(index as NSUInteger)];
+}
+
+// UITabBarDelegate implementation
+- (())tabBar:(id)tab_bar
+didSelectItem:(id)item { // UITabBarItem*
+    let view_controllers = env.objc.borrow::<UITabBarControllerHostObject>(this).view_controllers.clone();
+    let mut index = None;
+    for (i, &controller) in view_controllers.iter().enumerate() {
+        let controller_item: id = msg![env; controller tabBarItem];
+        if controller_item == item {
+            index = Some(i);
+            break;
+        }
+    }
+    let Some(index) = index else {
+        return;
+    };
+    let controller = view_controllers[index];
+
+    let delegate = env.objc.borrow::<UITabBarControllerHostObject>(this).delegate;
+    if delegate_implements(env, delegate, "tabBarController:shouldSelectViewController:") {
+        let should_select: bool = msg![env; delegate tabBarController:this
+                                              shouldSelectViewController:controller];
+        if !should_select {
+            // Put the highlight back where it was.
+            let selected: id = msg![env; this selectedViewController];
+            let selected_item: id = msg![env; selected tabBarItem];
+            () = msg![env; tab_bar setSelectedItem:selected_item];
+            return;
+        }
+    }
+
+    () = msg![env; this setSelectedIndex:(index as NSUInteger)];
+
+    if delegate_implements(env, delegate, "tabBarController:didSelectViewController:") {
+        () = msg![env; delegate tabBarController:this didSelectViewController:controller];
+    }
+}
+
+// The selected controller's view appears and disappears along with ours.
+- (())viewWillAppear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    () = msg![env; selected viewWillAppear:animated];
+}
+- (())viewDidAppear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    () = msg![env; selected viewDidAppear:animated];
+}
+- (())viewWillDisappear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    () = msg![env; selected viewWillDisappear:animated];
+}
+- (())viewDidDisappear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    () = msg![env; selected viewDidDisappear:animated];
+}
+
+@end
+
+};
+
+fn delegate_implements(env: &mut Environment, delegate: id, selector: &str) -> bool {
+    delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, selector)
+}
+
+/// Give the tab bar one item for each view controller.
+fn update_tab_bar(env: &mut Environment, tab_bar_controller: id) {
+    let &UITabBarControllerHostObject {
+        tab_bar,
+        selected_index,
+        ..
+    } = env.objc.borrow(tab_bar_controller);
+    let view_controllers = env
+        .objc
+        .borrow::<UITabBarControllerHostObject>(tab_bar_controller)
+        .view_controllers
+        .clone();
+
+    let mut items = Vec::with_capacity(view_controllers.len());
+    for controller in view_controllers {
+        let item: id = msg![env; controller tabBarItem];
+        items.push(retain(env, item));
+    }
+    let selected_item = items.get(selected_index as usize).copied().unwrap_or(nil);
+    let items = ns_array::from_vec(env, items);
+    () = msg![env; tab_bar setItems:items];
+    release(env, items);
+    () = msg![env; tab_bar setSelectedItem:selected_item];
+}
+
+/// Put a child view controller's view into the area above the tab bar.
+fn show_child(env: &mut Environment, tab_bar_controller: id, controller: id) {
+    let view: id = msg![env; tab_bar_controller view];
+    let tab_bar = env
+        .objc
+        .borrow::<UITabBarControllerHostObject>(tab_bar_controller)
+        .tab_bar;
+    let bounds: CGRect = msg![env; view bounds];
+    let child_frame = CGRect {
+        origin: bounds.origin,
+        size: CGSize {
+            width: bounds.size.width,
+            height: (bounds.size.height - TAB_BAR_HEIGHT).max(0.0),
+        },
+    };
+    let child_view: id = msg![env; controller view];
+    () = msg![env; child_view setFrame:child_frame];
+    () = msg![env; child_view setAutoresizingMask:(
+        UIViewAutoresizingFlexibleWidth | UIViewAutoresizingFlexibleHeight
+    )];
+    () = msg![env; view addSubview:child_view];
+    () = msg![env; view bringSubviewToFront:tab_bar];
+}
+
+/// Replace the old selected child's view with the new one's, sending the
+/// appearance notifications if the tab bar controller is on-screen.
+fn swap_children(env: &mut Environment, tab_bar_controller: id, old: id, new: id) {
+    let view: id = msg![env; tab_bar_controller view];
+    let window: id = msg![env; view window];
+    let on_screen = window != nil;
+
+    if on_screen {
+        () = msg![env; old viewWillDisappear:false];
+        () = msg![env; new viewWillAppear:false];
+    }
+    if old != nil {
+        let old_view: id = msg![env; old view];
+        () = msg![env; old_view removeFromSuperview];
+    }
+    if new != nil {
+        show_child(env, tab_bar_controller, new);
+    }
+    if on_screen {
+        () = msg![env; old viewDidDisappear:false];
+        () = msg![env; new viewDidAppear:false];
+    }
+}
@@ -0,0 +1,225 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Mapping of game controller input to touches, so that touch-only games can
+//! be played with a controller. See the `--button-to-touch=` and
+//! `--left-stick=` options.
+//!
+//! Co-ordinates here are the same as in those options, i.e. in the app's
+//! co-ordinate space. The window converts them to real touch events. The
+//! right analog stick's virtual cursor is handled separately by the window.
+
+use crate::options::{Button, LeftStickMode, Options};
+use crate::window::{Coords, FingerId};
+use std::collections::HashSet;
+
+/// How far the left stick has to be pushed in a direction for it to count as
+/// pressing that direction on the D-pad, in `--left-stick=dpad` mode.
+const DPAD_THRESHOLD: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TouchPhase {
+    Down,
+    Move,
+    Up,
+}
+
+pub type MappedTouch = (TouchPhase, FingerId, Coords);
+
+#[derive(Default)]
+pub struct GamepadToTouch {
+    /// Buttons that are held down on the controller.
+    buttons_held: HashSet<Button>,
+    /// D-pad directions that are held with the left stick.
+    stick_dpad_held: HashSet<Button>,
+    /// Where the left stick's touch currently is, if it is touching.
+    stick_touch_at: Option<Coords>,
+}
+
+impl GamepadToTouch {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Handle a button being pressed or released. Returns the touch this
+    /// produces, if the button is mapped to one.
+    pub fn button_changed(
+        &mut self,
+        options: &Options,
+        button: Button,
+        pressed: bool,
+    ) -> Option<MappedTouch> {
+        let was_held = self.is_held(button);
+        if pressed {
+            self.buttons_held.insert(button);
+        } else {
+            self.buttons_held.remove(&button);
+        }
+        self.touch_for_button(options, button, was_held)
+    }
+
+    /// Handle the current position of the left stick (each axis in the range
+    /// [-1, 1], with the dead zone already applied). Returns the touches this
+    /// produces, which may be none.
+    pub fn left_stick_moved(&mut self, options: &Options, (x, y): (f32, f32)) -> Vec<MappedTouch> {
+        match options.left_stick {
+            LeftStickMode::Tilt => Vec::new(),
+            LeftStickMode::DPad => {
+                let mut touches = Vec::new();
+                for (button, amount) in [
+                    (Button::DPadLeft, -x),
+                    (Button::DPadRight, x),
+                    (Button::DPadUp, -y),
+                    (Button::DPadDown, y),
+                ] {
+                    let was_held = self.is_held(button);
+                    if amount >= DPAD_THRESHOLD {
+                        self.stick_dpad_held.insert(button);
+                    } else {
+                        self.stick_dpad_held.remove(&button);
+                    }
+                    touches.extend(self.touch_for_button(options, button, was_held));
+                }
+                touches
+            }
+            LeftStickMode::Drag {
+                center: (center_x, center_y),
+                radius,
+            } => {
+                let touching = x != 0.0 || y != 0.0;
+                let new_at = (center_x + x * radius, center_y + y * radius);
+                match (self.stick_touch_at, touching) {
+                    (None, false) => Vec::new(),
+                    (None, true) => {
+                        // Touch the middle of the joystick first, like a real
+                        // finger would, so the app sees the drag.
+                        self.stick_touch_at = Some(new_at);
+                        vec![
+                            (TouchPhase::Down, FingerId::LeftStick, (center_x, center_y)),
+                            (TouchPhase::Move, FingerId::LeftStick, new_at),
+                        ]
+                    }
+                    (Some(old_at), true) if old_at == new_at => Vec::new(),
+                    (Some(_), true) => {
+                        self.stick_touch_at = Some(new_at);
+                        vec![(TouchPhase::Move, FingerId::LeftStick, new_at)]
+                    }
+                    (Some(old_at), false) => {
+                        self.stick_touch_at = None;
+                        vec![(TouchPhase::Up, FingerId::LeftStick, old_at)]
+                    }
+                }
+            }
+        }
+    }
+
+    /// A button counts as held if it's held on the controller or with the
+    /// left stick, so that using both at once doesn't produce extra touches.
+    fn is_held(&self, button: Button) -> bool {
+        self.buttons_held.contains(&button) || self.stick_dpad_held.contains(&button)
+    }
+
+    fn touch_for_button(
+        &self,
+        options: &Options,
+        button: Button,
+        was_held: bool,
+    ) -> Option<MappedTouch> {
+        let held = self.is_held(button);
+        if held == was_held {
+            return None;
+        }
+        let &coords = options.button_to_touch.get(&button)?;
+        let phase = if held {
+            TouchPhase::Down
+        } else {
+            TouchPhase::Up
+        };
+        Some((phase, FingerId::ButtonToTouch(button), coords))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::get_options_from_file;
+
+    fn load_options(config: &str) -> Options {
+        let mut options = Options::default();
+        let options_string = get_options_from_file(config.as_bytes(), "com.example.game")
+            .unwrap()
+            .unwrap();
+        for arg in options_string.split_ascii_whitespace() {
+            assert_eq!(options.parse_argument(arg), Ok(true));
+        }
+        options
+    }
+
+    #[test]
+    fn button_to_touch() {
+        let options = load_options(
+            "com.example.other: --button-to-touch=A,1,2\n\
+             com.example.game: --button-to-touch=A,100,400 --landscape-left\n",
+        );
+        let mut mapper = GamepadToTouch::new();
+        let finger = FingerId::ButtonToTouch(Button::A);
+        assert_eq!(
+            mapper.button_changed(&options, Button::A, true),
+            Some((TouchPhase::Down, finger, (100.0, 400.0)))
+        );
+        assert_eq!(
+            mapper.button_changed(&options, Button::A, false),
+            Some((TouchPhase::Up, finger, (100.0, 400.0)))
+        );
+        assert_eq!(mapper.button_changed(&options, Button::B, true), None);
+    }
+
+    #[test]
+    fn left_stick_as_dpad() {
+        let options =
+            load_options("com.example.game: --left-stick=dpad --button-to-touch=DPadLeft,20,300\n");
+        let mut mapper = GamepadToTouch::new();
+        let finger = FingerId::ButtonToTouch(Button::DPadLeft);
+        assert_eq!(mapper.left_stick_moved(&options, (-0.2, 0.0)), vec![]);
+        assert_eq!(
+            mapper.left_stick_moved(&options, (-0.9, 0.1)),
+            vec![(TouchPhase::Down, finger, (20.0, 300.0))]
+        );
+        // Pressing the real D-pad button at the same time changes nothing.
+        assert_eq!(
+            mapper.button_changed(&options, Button::DPadLeft, true),
+            None
+        );
+        assert_eq!(mapper.left_stick_moved(&options, (0.0, 0.0)), vec![]);
+        assert_eq!(
+            mapper.button_changed(&options, Button::DPadLeft, false),
+            Some((TouchPhase::Up, finger, (20.0, 300.0)))
+        );
+    }
+
+    #[test]
+    fn left_stick_as_drag() {
+        let options = load_options("com.example.game: --left-stick=drag,80,240,50\n");
+        let mut mapper = GamepadToTouch::new();
+        let finger = FingerId::LeftStick;
+        assert_eq!(mapper.left_stick_moved(&options, (0.0, 0.0)), vec![]);
+        assert_eq!(
+            mapper.left_stick_moved(&options, (1.0, 0.0)),
+            vec![
+                (TouchPhase::Down, finger, (80.0, 240.0)),
+                (TouchPhase::Move, finger, (130.0, 240.0)),
+            ]
+        );
+        assert_eq!(mapper.left_stick_moved(&options, (1.0, 0.0)), vec![]);
+        assert_eq!(
+            mapper.left_stick_moved(&options, (0.0, -0.5)),
+            vec![(TouchPhase::Move, finger, (80.0, 215.0))]
+        );
+        assert_eq!(
+            mapper.left_stick_moved(&options, (0.0, 0.0)),
+            vec![(TouchPhase::Up, finger, (80.0, 215.0))]
+        );
+    }
+}
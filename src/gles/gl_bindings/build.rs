@@ -44,6 +44,7 @@ fn main() {
             // Part of the OpenGL ES 1.1 common profile.
             "GL_OES_compressed_paletted_texture",
             "GL_OES_matrix_palette",
+            "GL_OES_read_format",
         ],
     )
     .write_bindings(GlobalGenerator, &mut file)
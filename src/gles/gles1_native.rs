@@ -98,6 +98,9 @@ impl GLES for GLES1Native {
     unsafe fn Flush(&mut self) {
         gles11::Flush()
     }
+    unsafe fn Finish(&mut self) {
+        gles11::Finish()
+    }
     unsafe fn GetString(&mut self, name: GLenum) -> *const GLubyte {
         gles11::GetString(name)
     }
@@ -160,6 +163,18 @@ impl GLES for GLES1Native {
     unsafe fn LineWidthx(&mut self, val: GLfixed) {
         gles11::LineWidthx(val)
     }
+    unsafe fn LogicOp(&mut self, opcode: GLenum) {
+        gles11::LogicOp(opcode)
+    }
+    unsafe fn StencilFunc(&mut self, func: GLenum, ref_: GLint, mask: GLuint) {
+        gles11::StencilFunc(func, ref_, mask)
+    }
+    unsafe fn StencilOp(&mut self, sfail: GLenum, dpfail: GLenum, dppass: GLenum) {
+        gles11::StencilOp(sfail, dpfail, dppass)
+    }
+    unsafe fn StencilMask(&mut self, mask: GLuint) {
+        gles11::StencilMask(mask)
+    }
 
     // Points
     unsafe fn PointSize(&mut self, size: GLfloat) {
@@ -677,6 +692,15 @@ impl GLES for GLES1Native {
     ) {
         gles11::GetRenderbufferParameterivOES(target, pname, params)
     }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gles11::GetFramebufferAttachmentParameterivOES(target, attachment, pname, params)
+    }
     unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
         gles11::CheckFramebufferStatusOES(target)
     }
@@ -84,6 +84,35 @@ pub struct ArrayInfo {
     pub pointer: GLenum,
 }
 
+/// Stencil comparison functions shared by OpenGL ES 1.1 and OpenGL 2.1.
+const STENCIL_FUNCS: &[GLenum] = &[
+    gl21::NEVER,
+    gl21::LESS,
+    gl21::EQUAL,
+    gl21::LEQUAL,
+    gl21::GREATER,
+    gl21::NOTEQUAL,
+    gl21::GEQUAL,
+    gl21::ALWAYS,
+];
+
+/// Stencil operations shared by OpenGL ES 1.1 and OpenGL 2.1. (The wrapping
+/// `INCR_WRAP` and `DECR_WRAP` are OpenGL ES 2.0 additions.)
+const STENCIL_OPS: &[GLenum] = &[
+    gl21::KEEP,
+    gl21::ZERO,
+    gl21::REPLACE,
+    gl21::INCR,
+    gl21::DECR,
+    gl21::INVERT,
+];
+
+/// Whether an operation is one of the 16 logical operations, which are the
+/// same in OpenGL ES 1.1 and OpenGL 2.1.
+fn is_logic_op(opcode: GLenum) -> bool {
+    (gl21::CLEAR..=gl21::SET).contains(&opcode)
+}
+
 struct ArrayStateBackup {
     size: Option<GLint>,
     stride: GLsizei,
@@ -173,8 +202,8 @@ const GET_PARAMS: ParamTable = ParamTable(&[
     (gl21::FOG_END, ParamType::Float, 1),
     (gl21::FRONT_FACE, ParamType::Int, 1),
     (gl21::GREEN_BITS, ParamType::Int, 1),
-    // TODO: IMPLEMENTATION_COLOR_READ_FORMAT_OES? (not shared)
-    // TODO: IMPLEMENTATION_COLOR_READ_TYPE_OES? (not shared)
+    // IMPLEMENTATION_COLOR_READ_FORMAT_OES and IMPLEMENTATION_COLOR_READ_TYPE_OES
+    // aren't shared, see GetIntegerv.
     // TODO: LIGHT_MODEL_AMBIENT (has special type conversion behavior)
     (gl21::LIGHT_MODEL_TWO_SIDE, ParamType::Boolean, 1),
     // TODO: arbitrary number of lights?
@@ -625,6 +654,19 @@ impl GLES for GLES1OnGL2 {
         gl21::GetFloatv(pname, params);
     }
     unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint) {
+        // OpenGL 2.1 can read pixels in any format, so report BGRA, which is
+        // what the iPhone's framebuffer uses.
+        match pname {
+            gles11::IMPLEMENTATION_COLOR_READ_FORMAT_OES => {
+                params.write(gles11::BGRA_EXT as _);
+                return;
+            }
+            gles11::IMPLEMENTATION_COLOR_READ_TYPE_OES => {
+                params.write(gles11::UNSIGNED_BYTE as _);
+                return;
+            }
+            _ => (),
+        }
         let (type_, _count) = GET_PARAMS.get_type_info(pname);
         // TODO: type conversion
         assert!(type_ == ParamType::Int);
@@ -660,6 +702,9 @@ impl GLES for GLES1OnGL2 {
     unsafe fn Flush(&mut self) {
         gl21::Flush();
     }
+    unsafe fn Finish(&mut self) {
+        gl21::Finish();
+    }
     unsafe fn GetString(&mut self, name: GLenum) -> *const GLubyte {
         gl21::GetString(name)
     }
@@ -777,6 +822,23 @@ impl GLES for GLES1OnGL2 {
     unsafe fn LineWidthx(&mut self, val: GLfixed) {
         gl21::LineWidth(fixed_to_float(val))
     }
+    unsafe fn LogicOp(&mut self, opcode: GLenum) {
+        assert!(is_logic_op(opcode));
+        gl21::LogicOp(opcode)
+    }
+    unsafe fn StencilFunc(&mut self, func: GLenum, ref_: GLint, mask: GLuint) {
+        assert!(STENCIL_FUNCS.contains(&func));
+        gl21::StencilFunc(func, ref_, mask)
+    }
+    unsafe fn StencilOp(&mut self, sfail: GLenum, dpfail: GLenum, dppass: GLenum) {
+        for op in [sfail, dpfail, dppass] {
+            assert!(STENCIL_OPS.contains(&op));
+        }
+        gl21::StencilOp(sfail, dpfail, dppass)
+    }
+    unsafe fn StencilMask(&mut self, mask: GLuint) {
+        gl21::StencilMask(mask)
+    }
 
     // Points
     unsafe fn PointSize(&mut self, size: GLfloat) {
@@ -1737,6 +1799,15 @@ impl GLES for GLES1OnGL2 {
     ) {
         gl21::GetRenderbufferParameterivEXT(target, pname, params)
     }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetFramebufferAttachmentParameterivEXT(target, attachment, pname, params)
+    }
     unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
         gl21::CheckFramebufferStatusEXT(target)
     }
@@ -1750,3 +1821,117 @@ impl GLES for GLES1OnGL2 {
         gl21::GenerateMipmapEXT(target)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gles::GLESImplementation;
+    use crate::options::Options;
+
+    /// Draw a triangle strip from 2D vertices in normalized device
+    /// coordinates.
+    unsafe fn draw_quad(gles: &mut GLES1OnGL2, vertices: &[GLfloat; 8]) {
+        gles.VertexPointer(2, gl21::FLOAT, 0, vertices.as_ptr().cast());
+        gles.DrawArrays(gl21::TRIANGLE_STRIP, 0, 4);
+    }
+
+    unsafe fn read_pixels(gles: &mut GLES1OnGL2) -> [u8; 8] {
+        let mut pixels = [0u8; 8];
+        gles.ReadPixels(
+            0,
+            0,
+            2,
+            1,
+            gl21::RGBA,
+            gl21::UNSIGNED_BYTE,
+            pixels.as_mut_ptr().cast(),
+        );
+        pixels
+    }
+
+    #[test]
+    fn stencil_and_logic_op_rendering() {
+        // This needs a real OpenGL 2.1 context, which isn't available when
+        // there's no display (e.g. in CI).
+        if let Err(e) = sdl2::init().and_then(|sdl| sdl.video()) {
+            eprintln!("Skipping test, no video available: {}", e);
+            return;
+        }
+        let options = Options {
+            gles1_implementation: Some(GLESImplementation::GLES1OnGL2),
+            ..Default::default()
+        };
+        let mut window = Window::new("stencil_and_logic_op_rendering", None, None, &options);
+        let mut gles = GLES1OnGL2::new(&mut window).unwrap();
+        gles.make_current(&window);
+
+        const LEFT_HALF: [GLfloat; 8] = [-1.0, -1.0, 0.0, -1.0, -1.0, 1.0, 0.0, 1.0];
+        const EVERYTHING: [GLfloat; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+
+        unsafe {
+            // A 2x1 framebuffer with a stencil buffer.
+            let mut framebuffer = 0;
+            let mut renderbuffers = [0; 2];
+            gles.GenFramebuffersOES(1, &mut framebuffer);
+            gles.GenRenderbuffersOES(2, renderbuffers.as_mut_ptr());
+            gles.BindFramebufferOES(gl21::FRAMEBUFFER_EXT, framebuffer);
+            for (renderbuffer, format, attachment) in [
+                (renderbuffers[0], gl21::RGBA8, gl21::COLOR_ATTACHMENT0_EXT),
+                (
+                    renderbuffers[1],
+                    gl21::STENCIL_INDEX8_EXT,
+                    gl21::STENCIL_ATTACHMENT_EXT,
+                ),
+            ] {
+                gles.BindRenderbufferOES(gl21::RENDERBUFFER_EXT, renderbuffer);
+                gles.RenderbufferStorageOES(gl21::RENDERBUFFER_EXT, format, 2, 1);
+                gles.FramebufferRenderbufferOES(
+                    gl21::FRAMEBUFFER_EXT,
+                    attachment,
+                    gl21::RENDERBUFFER_EXT,
+                    renderbuffer,
+                );
+            }
+            assert_eq!(
+                gles.CheckFramebufferStatusOES(gl21::FRAMEBUFFER_EXT),
+                gl21::FRAMEBUFFER_COMPLETE_EXT
+            );
+            gles.Viewport(0, 0, 2, 1);
+            gles.EnableClientState(gl21::VERTEX_ARRAY);
+
+            gles.ClearColor(0.0, 0.0, 0.0, 0.0);
+            gles.ClearStencil(0);
+            gles.Clear(gl21::COLOR_BUFFER_BIT | gl21::STENCIL_BUFFER_BIT);
+
+            // Mark the left pixel in the stencil buffer only.
+            gles.Enable(gl21::STENCIL_TEST);
+            gles.StencilFunc(gl21::ALWAYS, 1, 0xff);
+            gles.StencilOp(gl21::KEEP, gl21::KEEP, gl21::REPLACE);
+            gles.ColorMask(gl21::FALSE, gl21::FALSE, gl21::FALSE, gl21::FALSE);
+            draw_quad(&mut gles, &LEFT_HALF);
+            gles.ColorMask(gl21::TRUE, gl21::TRUE, gl21::TRUE, gl21::TRUE);
+
+            // Drawing everything only affects the marked pixel.
+            gles.StencilFunc(gl21::EQUAL, 1, 0xff);
+            gles.StencilOp(gl21::KEEP, gl21::KEEP, gl21::KEEP);
+            gles.Color4f(1.0, 0.0, 0.0, 1.0);
+            draw_quad(&mut gles, &EVERYTHING);
+            assert_eq!(read_pixels(&mut gles), [255, 0, 0, 255, 0, 0, 0, 0]);
+            gles.Disable(gl21::STENCIL_TEST);
+
+            // XORing with white inverts both pixels.
+            gles.Enable(gl21::COLOR_LOGIC_OP);
+            gles.LogicOp(gl21::XOR);
+            gles.Color4f(1.0, 1.0, 1.0, 1.0);
+            draw_quad(&mut gles, &EVERYTHING);
+            assert_eq!(read_pixels(&mut gles), [0, 255, 255, 0, 255, 255, 255, 255]);
+            gles.Disable(gl21::COLOR_LOGIC_OP);
+
+            assert_eq!(gles.GetError(), gl21::NO_ERROR);
+            gles.DisableClientState(gl21::VERTEX_ARRAY);
+            gles.BindFramebufferOES(gl21::FRAMEBUFFER_EXT, 0);
+            gles.DeleteRenderbuffersOES(2, renderbuffers.as_ptr());
+            gles.DeleteFramebuffersOES(1, &framebuffer);
+        }
+    }
+}
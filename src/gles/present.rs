@@ -8,7 +8,9 @@
 
 use super::gles11_raw as gles11; // constants and types only
 use super::GLES;
+use crate::debug_overlay::OverlayImage;
 use crate::matrix::Matrix;
+use crate::options::ScalingFilter;
 use std::time::{Duration, Instant};
 
 pub struct FpsCounter {
@@ -40,15 +42,19 @@ impl FpsCounter {
 
 /// Present the the latest frame (e.g. the app's splash screen or rendering
 /// output), provided as a texture bound to `GL_TEXTURE_2D`, by drawing it on
-/// the window. It may be rotated, scaled and/or letterboxed as necessary. The
-/// virtual cursor is also drawn if it should be currently visible.
+/// the window. It may be rotated, scaled and/or letterboxed as necessary, using
+/// the provided filter (this changes the texture's filter parameters). The
+/// virtual cursor is also drawn if it should be currently visible, and so is
+/// the debug overlay (see [crate::debug_overlay]) if provided.
 ///
 /// The provided context must be current.
 pub unsafe fn present_frame(
     gles: &mut dyn GLES,
     viewport: (u32, u32, u32, u32),
     rotation_matrix: Matrix<2>,
+    scaling_filter: ScalingFilter,
     virtual_cursor_visible_at: Option<(f32, f32, bool)>,
+    debug_overlay: Option<&OverlayImage>,
 ) {
     // While this is a generic utility, it is closely tied to
     // crate::frameworks::opengles::eagl::present_renderbuffer, which handles
@@ -57,6 +63,15 @@ pub unsafe fn present_frame(
 
     use gles11::types::*;
 
+    // The texture will not have any mip levels so we must ensure the filter
+    // does not use them, else rendering will fail.
+    let filter = match scaling_filter {
+        ScalingFilter::Linear => gles11::LINEAR,
+        ScalingFilter::Nearest => gles11::NEAREST,
+    };
+    gles.TexParameteri(gles11::TEXTURE_2D, gles11::TEXTURE_MIN_FILTER, filter as _);
+    gles.TexParameteri(gles11::TEXTURE_2D, gles11::TEXTURE_MAG_FILTER, filter as _);
+
     // Draw the quad
     gles.Viewport(
         viewport.0 as _,
@@ -106,4 +121,56 @@ pub unsafe fn present_frame(
         gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
         gles.DrawArrays(gles11::TRIANGLES, 0, 6);
     }
+
+    // Display debug overlay in the top-left corner, unrotated
+    if let Some(overlay) = debug_overlay {
+        let (_, _, vw, vh) = viewport;
+        let margin = 8.0;
+
+        let mut texture = 0;
+        gles.GenTextures(1, &mut texture);
+        gles.BindTexture(gles11::TEXTURE_2D, texture);
+        gles.TexImage2D(
+            gles11::TEXTURE_2D,
+            0,
+            gles11::RGBA as _,
+            overlay.width as _,
+            overlay.height as _,
+            0,
+            gles11::RGBA,
+            gles11::UNSIGNED_BYTE,
+            overlay.pixels.as_ptr() as *const GLvoid,
+        );
+        gles.TexParameteri(
+            gles11::TEXTURE_2D,
+            gles11::TEXTURE_MIN_FILTER,
+            gles11::NEAREST as _,
+        );
+        gles.TexParameteri(
+            gles11::TEXTURE_2D,
+            gles11::TEXTURE_MAG_FILTER,
+            gles11::NEAREST as _,
+        );
+
+        gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
+        gles.Enable(gles11::TEXTURE_2D);
+        gles.Enable(gles11::BLEND);
+        gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
+        gles.Color4f(1.0, 1.0, 1.0, 1.0);
+
+        // The first row of the image is the top, so the texture co-ordinates
+        // can be used to find the position in the image, in pixels.
+        let mut vertices = tex_coords;
+        for i in (0..vertices.len()).step_by(2) {
+            let x = margin + vertices[i] * overlay.width as f32;
+            let y = margin + vertices[i + 1] * overlay.height as f32;
+            vertices[i] = x / (vw as f32 / 2.0) - 1.0;
+            vertices[i + 1] = 1.0 - y / (vh as f32 / 2.0);
+        }
+        gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+        gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
+        gles.DrawArrays(gles11::TRIANGLES, 0, 6);
+
+        gles.DeleteTextures(1, &texture);
+    }
 }
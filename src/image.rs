@@ -3,16 +3,18 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Image decoding. Currently only supports PNG files (treated as 8-bit sRGB).
+//! Image decoding and encoding. Currently only supports decoding PNG files
+//! (treated as 8-bit sRGB), and encoding PNG and JPEG files.
 //!
 //! Implemented as a wrapper around the C library stb_image, since it supports
 //! "CgBI" PNG files (an Apple proprietary extension used in iPhone OS apps).
+//! Encoding uses its sibling library stb_image_write.
 //!
 //! This module also exposes decompression for Imagination Technologies' PVRTC
 //! format, implementing as a wrapper around their decoder from the PowerVR
 //! SDK.
 
-use std::ffi::{c_int, c_uchar, CStr};
+use std::ffi::{c_int, c_uchar, c_void, CStr};
 
 use touchHLE_pvrt_decompress_wrapper::*;
 use touchHLE_stb_image_wrapper::*;
@@ -94,6 +96,79 @@ impl Image {
         }
     }
 
+    /// Encode the image as a PNG file. The alpha is un-premultiplied, since
+    /// that's what PNG expects.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut pixels = self.pixels().to_vec();
+        for rgba in pixels.chunks_exact_mut(4) {
+            let alpha = rgba[3] as u32;
+            if alpha != 0 && alpha != 255 {
+                for channel in &mut rgba[..3] {
+                    *channel = ((*channel as u32 * 255) / alpha).min(255) as u8;
+                }
+            }
+        }
+
+        let (width, height) = self.dimensions;
+        let mut png = Vec::new();
+        let success = unsafe {
+            stbi_write_png_to_func(
+                write_to_vec,
+                &mut png as *mut Vec<u8> as *mut c_void,
+                width.try_into().unwrap(),
+                height.try_into().unwrap(),
+                /* comp: */ 4,
+                pixels.as_ptr() as *const c_void,
+                (width * 4).try_into().unwrap(),
+            )
+        };
+        assert!(success != 0);
+        png
+    }
+
+    /// Like [Image::to_png], but also records the pixel density in a `pHYs`
+    /// chunk, where `scale` is the number of pixels per point (72 DPI).
+    pub fn to_png_with_scale(&self, scale: f32) -> Vec<u8> {
+        let png = self.to_png();
+
+        let pixels_per_metre = (scale * 72.0 / 0.0254).round() as u32;
+        let mut chunk = Vec::with_capacity(21);
+        chunk.extend_from_slice(&9u32.to_be_bytes());
+        chunk.extend_from_slice(b"pHYs");
+        chunk.extend_from_slice(&pixels_per_metre.to_be_bytes());
+        chunk.extend_from_slice(&pixels_per_metre.to_be_bytes());
+        chunk.push(1); // unit is the metre
+        let crc = crc32(&chunk[4..]);
+        chunk.extend_from_slice(&crc.to_be_bytes());
+
+        // stb_image_write always puts IHDR first, right after the signature,
+        // and pHYs must come before IDAT.
+        const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+        assert!(&png[12..16] == b"IHDR");
+        [&png[..IHDR_END], &chunk, &png[IHDR_END..]].concat()
+    }
+
+    /// Encode the image as a JPEG file. `quality` is in the range 1 to 100.
+    /// JPEG has no alpha channel, so the image is effectively composited onto
+    /// black.
+    pub fn to_jpeg(&self, quality: u8) -> Vec<u8> {
+        let (width, height) = self.dimensions;
+        let mut jpeg = Vec::new();
+        let success = unsafe {
+            stbi_write_jpg_to_func(
+                write_to_vec,
+                &mut jpeg as *mut Vec<u8> as *mut c_void,
+                width.try_into().unwrap(),
+                height.try_into().unwrap(),
+                /* comp: */ 4,
+                self.pixels().as_ptr() as *const c_void,
+                quality.clamp(1, 100).into(),
+            )
+        };
+        assert!(success != 0);
+        jpeg
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         self.dimensions
     }
@@ -176,6 +251,29 @@ impl Image {
     }
 }
 
+/// Callback for stb_image_write that appends to a `Vec<u8>`.
+extern "C" fn write_to_vec(context: *mut c_void, data: *mut c_void, size: c_int) {
+    let vec = unsafe { &mut *(context as *mut Vec<u8>) };
+    let data = unsafe { std::slice::from_raw_parts(data as *const u8, size as usize) };
+    vec.extend_from_slice(data);
+}
+
+/// CRC-32 as used by PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
 impl Drop for Image {
     fn drop(&mut self) {
         match self.pixels {
@@ -224,3 +322,48 @@ pub fn decode_pvrtc(pvrtc_data: &[u8], is_2bit: bool, width: u32, height: u32) -
     };
     rgba8_data
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+    }
+
+    #[test]
+    fn to_png_round_trip() {
+        // Premultiplied alpha: the second pixel is half-transparent red.
+        #[rustfmt::skip]
+        let pixels = vec![
+            255, 0, 0, 255,  128, 0, 0, 128,
+            0, 0, 0, 0,  10, 20, 30, 255,
+        ];
+        let image = Image::from_pixel_vec(pixels.clone(), (2, 2));
+        let png = image.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let decoded = Image::from_bytes(&png).unwrap();
+        assert_eq!(decoded.dimensions(), (2, 2));
+        // Un-premultiplying and re-premultiplying can be off by one.
+        for (&a, &b) in decoded.pixels().iter().zip(pixels.iter()) {
+            assert!(a.abs_diff(b) <= 1, "{:?} != {:?}", decoded.pixels(), pixels);
+        }
+    }
+
+    #[test]
+    fn to_png_with_scale_adds_phys_chunk() {
+        let image = Image::from_pixel_vec(vec![0; 4], (1, 1));
+        let png = image.to_png_with_scale(2.0);
+        let chunk = &png[33..33 + 21];
+        assert_eq!(&chunk[4..8], b"pHYs");
+        // 144 DPI is 5669.29... pixels per metre.
+        assert_eq!(chunk[8..12], 5669u32.to_be_bytes());
+        assert_eq!(chunk[12..16], 5669u32.to_be_bytes());
+        assert_eq!(chunk[16], 1);
+        assert_eq!(chunk[17..21], crc32(&chunk[4..17]).to_be_bytes());
+        assert!(Image::from_bytes(&png).is_ok());
+    }
+}
@@ -27,3 +27,28 @@ extern "C" {
     pub fn stbi_image_free(retval_from_stbi_load: *mut c_void);
     pub fn stbi_failure_reason() -> *const c_char;
 }
+
+#[allow(non_camel_case_types)]
+pub type stbi_write_func = extern "C" fn(context: *mut c_void, data: *mut c_void, size: c_int);
+
+// See build.rs, lib.c and ../../../vendor/stb/stb_image_write.h
+extern "C" {
+    pub fn stbi_write_png_to_func(
+        func: stbi_write_func,
+        context: *mut c_void,
+        w: c_int,
+        h: c_int,
+        comp: c_int,
+        data: *const c_void,
+        stride_in_bytes: c_int,
+    ) -> c_int;
+    pub fn stbi_write_jpg_to_func(
+        func: stbi_write_func,
+        context: *mut c_void,
+        x: c_int,
+        y: c_int,
+        comp: c_int,
+        data: *const c_void,
+        quality: c_int,
+    ) -> c_int;
+}
@@ -0,0 +1,451 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Recording and replaying of input events, for reproducing bugs and
+//! regression-testing apps. See the `--record-input=` and `--replay-input=`
+//! options.
+//!
+//! A recording is a text file with one event per line. Each line starts with
+//! the number of frames presented since the app called `UIApplicationMain()`,
+//! followed by the kind of event and its parameters, for example:
+//!
+//! ```text
+//! 90 down 0 160 240
+//! 93 move 0 161.5 242
+//! 96 up 0 161.5 242
+//! 120 text 68656c6c6f
+//! 126 backspace
+//! 132 return
+//! 138 accel 0 0 -1
+//! 150 turbo
+//! ```
+//!
+//! Touch events have one `<finger> <x> <y>` triple per touch. Fingers are
+//! numbered in the order they first appear, so it doesn't matter what kind of
+//! input device was used. Text is UTF-8 encoded as hexadecimal. The keys for
+//! taking a screenshot and toggling turbo mode, the debug overlay and the
+//! scaling filter are recorded too, since they can affect what happens next,
+//! and so are the window losing or gaining focus and being minimized or
+//! restored (`focus-lost`, `focus-gained`, `minimized` and `restored`), which
+//! move the app between the active, inactive and background states.
+//!
+//! Events are replayed on the same frame as they were recorded, so the app sees
+//! them at the same point even if it runs at a different speed. In headless
+//! mode, where nothing is presented, frames are counted at 60Hz (see
+//! [crate::frameworks::core_animation::frame_clock]). Accelerometer readings
+//! are replayed in the same order as they were made, regardless of timing.
+//! Real input of the recorded kinds is ignored during a replay. So long as the
+//! app behaves the same way, it will see the same input as it did during
+//! recording.
+
+use crate::options::Options;
+use crate::window::{Coords, Event, FingerId, TextInputEvent, Window};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+type Acceleration = (f32, f32, f32);
+
+/// Whether an event is the kind of event that gets recorded and replayed.
+pub fn is_input_event(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::FocusLost
+            | Event::FocusGained
+            | Event::Minimized
+            | Event::Restored
+            | Event::TouchesDown(_)
+            | Event::TouchesMove(_)
+            | Event::TouchesUp(_)
+            | Event::TextInput(_)
+            | Event::Screenshot
+            | Event::ToggleTurbo
+            | Event::ToggleDebugOverlay
+            | Event::ToggleScalingFilter
+    )
+}
+
+/// Input recording and replay state. Neither is active until [State::start] is
+/// called.
+#[derive(Default)]
+pub struct State {
+    recorder: Option<InputRecorder>,
+    replayer: Option<InputReplayer>,
+}
+impl State {
+    /// Begin recording or replaying input, if the options ask for it.
+    /// `frame` is the current frame count, which recorded frame numbers are
+    /// relative to.
+    pub fn start(&mut self, options: &Options, frame: u64) {
+        if let Some(ref path) = options.record_input_path {
+            match InputRecorder::new(path, frame) {
+                Ok(recorder) => {
+                    echo!("Recording input to {}.", path.display());
+                    self.recorder = Some(recorder);
+                }
+                Err(e) => log!("Warning: not recording input: {}", e),
+            }
+        }
+        if let Some(ref path) = options.replay_input_path {
+            match InputReplayer::new(path, frame) {
+                Ok(replayer) => {
+                    echo!(
+                        "Replaying input from {}, real input will be ignored.",
+                        path.display()
+                    );
+                    self.replayer = Some(replayer);
+                }
+                Err(e) => log!("Warning: not replaying input: {}", e),
+            }
+        }
+    }
+
+    /// Get the next event, if any: a replayed event that is due on this frame,
+    /// or otherwise an event from the window (if there is one). Real input
+    /// events are recorded, or discarded when replaying.
+    pub fn pop_event(&mut self, window: Option<&mut Window>, frame: u64) -> Option<Event> {
+        if let Some(event) = self
+            .replayer
+            .as_mut()
+            .and_then(|replayer| replayer.pop_due_event(frame))
+        {
+            return Some(event);
+        }
+        let window = window?;
+        loop {
+            let event = window.pop_event()?;
+            if is_input_event(&event) {
+                if self.replayer.is_some() {
+                    continue;
+                }
+                if let Some(ref mut recorder) = self.recorder {
+                    recorder.record_event(frame, &event);
+                }
+            }
+            return Some(event);
+        }
+    }
+
+    /// Get the real, simulated or replayed accelerometer output.
+    pub fn get_acceleration(
+        &mut self,
+        window: &Window,
+        options: &Options,
+        frame: u64,
+    ) -> Acceleration {
+        if let Some(acceleration) = self
+            .replayer
+            .as_mut()
+            .and_then(|replayer| replayer.next_acceleration())
+        {
+            return acceleration;
+        }
+        let acceleration = window.get_acceleration(options);
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.record_acceleration(frame, acceleration);
+        }
+        acceleration
+    }
+}
+
+/// Writes input events to a file as they happen.
+struct InputRecorder {
+    file: File,
+    start_frame: u64,
+    finger_numbers: HashMap<FingerId, u32>,
+}
+impl InputRecorder {
+    fn new(path: &Path, start_frame: u64) -> Result<InputRecorder, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Couldn't create {}: {}", path.display(), e))?;
+        Ok(InputRecorder {
+            file,
+            start_frame,
+            finger_numbers: HashMap::new(),
+        })
+    }
+
+    fn write_line(&mut self, frame: u64, line: String) {
+        let frame = frame.saturating_sub(self.start_frame);
+        if let Err(e) = writeln!(self.file, "{} {}", frame, line) {
+            log!("Warning: couldn't write input recording: {}", e);
+        }
+    }
+
+    /// Record an event, if it's an input event.
+    fn record_event(&mut self, frame: u64, event: &Event) {
+        if let Some(line) = format_event(&mut self.finger_numbers, event) {
+            self.write_line(frame, line);
+        }
+    }
+
+    fn record_acceleration(&mut self, frame: u64, (x, y, z): Acceleration) {
+        self.write_line(frame, format!("accel {} {} {}", x, y, z));
+    }
+}
+
+/// Feeds input events from a recording back to the app.
+struct InputReplayer {
+    start_frame: u64,
+    events: VecDeque<(u64, Event)>,
+    accelerations: VecDeque<Acceleration>,
+    /// The last acceleration replayed, which is repeated once the recorded
+    /// readings run out.
+    last_acceleration: Option<Acceleration>,
+    announced_end: bool,
+}
+impl InputReplayer {
+    fn new(path: &Path, start_frame: u64) -> Result<InputReplayer, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        Self::from_recording(&text, start_frame)
+    }
+
+    fn from_recording(text: &str, start_frame: u64) -> Result<InputReplayer, String> {
+        let mut events = VecDeque::new();
+        let mut accelerations = VecDeque::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_line(line).map_err(|e| format!("Line {}: {}", i + 1, e))? {
+                (frame, RecordedInput::Event(event)) => events.push_back((frame, event)),
+                (_frame, RecordedInput::Acceleration(acceleration)) => {
+                    accelerations.push_back(acceleration)
+                }
+            }
+        }
+        Ok(InputReplayer {
+            start_frame,
+            events,
+            accelerations,
+            last_acceleration: None,
+            announced_end: false,
+        })
+    }
+
+    /// Get the next recorded event, if it is due on or before `frame`.
+    fn pop_due_event(&mut self, frame: u64) -> Option<Event> {
+        let &(due_frame, _) = self.events.front()?;
+        if frame.saturating_sub(self.start_frame) < due_frame {
+            return None;
+        }
+        let (_, event) = self.events.pop_front().unwrap();
+        if self.events.is_empty() && !self.announced_end {
+            self.announced_end = true;
+            echo!("Input replay: last recorded event replayed.");
+        }
+        Some(event)
+    }
+
+    /// Get the next recorded accelerometer reading. Returns [None] if nothing
+    /// was recorded.
+    fn next_acceleration(&mut self) -> Option<Acceleration> {
+        if let Some(acceleration) = self.accelerations.pop_front() {
+            self.last_acceleration = Some(acceleration);
+        }
+        self.last_acceleration
+    }
+}
+
+enum RecordedInput {
+    Event(Event),
+    Acceleration(Acceleration),
+}
+
+fn format_event(finger_numbers: &mut HashMap<FingerId, u32>, event: &Event) -> Option<String> {
+    let mut format_touches = |kind: &str, touches: &HashMap<FingerId, Coords>| {
+        let mut touches: Vec<(u32, Coords)> = touches
+            .iter()
+            .map(|(&finger_id, &coords)| {
+                let next_number = finger_numbers.len() as u32;
+                (
+                    *finger_numbers.entry(finger_id).or_insert(next_number),
+                    coords,
+                )
+            })
+            .collect();
+        // HashMap order is random, but the recording should be stable.
+        touches.sort_by_key(|&(number, _)| number);
+        let mut line = kind.to_string();
+        for (number, (x, y)) in touches {
+            line.push_str(&format!(" {} {} {}", number, x, y));
+        }
+        line
+    };
+    Some(match event {
+        Event::FocusLost => "focus-lost".to_string(),
+        Event::FocusGained => "focus-gained".to_string(),
+        Event::Minimized => "minimized".to_string(),
+        Event::Restored => "restored".to_string(),
+        Event::TouchesDown(touches) => format_touches("down", touches),
+        Event::TouchesMove(touches) => format_touches("move", touches),
+        Event::TouchesUp(touches) => format_touches("up", touches),
+        Event::TextInput(TextInputEvent::Text(text)) => {
+            let hex: String = text.bytes().map(|byte| format!("{:02x}", byte)).collect();
+            format!("text {}", hex)
+        }
+        Event::TextInput(TextInputEvent::Backspace) => "backspace".to_string(),
+        Event::TextInput(TextInputEvent::Return) => "return".to_string(),
+        Event::Screenshot => "screenshot".to_string(),
+        Event::ToggleTurbo => "turbo".to_string(),
+        Event::ToggleDebugOverlay => "debug-overlay".to_string(),
+        Event::ToggleScalingFilter => "scaling-filter".to_string(),
+        _ => return None,
+    })
+}
+
+fn parse_line(line: &str) -> Result<(u64, RecordedInput), String> {
+    let mut parts = line.split_ascii_whitespace();
+    let frame: u64 = parts
+        .next()
+        .unwrap()
+        .parse()
+        .map_err(|_| "Invalid frame number".to_string())?;
+    let kind = parts
+        .next()
+        .ok_or_else(|| "Missing event kind".to_string())?;
+    let params: Vec<&str> = parts.collect();
+    let parse_f32 = |param: &str| -> Result<f32, String> {
+        param
+            .parse()
+            .map_err(|_| format!("Invalid number {:?}", param))
+    };
+
+    let parse_touches = || -> Result<HashMap<FingerId, Coords>, String> {
+        if params.is_empty() || params.len() % 3 != 0 {
+            return Err("Touch events need one or more finger, x, y triples".to_string());
+        }
+        params
+            .chunks(3)
+            .map(|triple| {
+                let number: i64 = triple[0]
+                    .parse()
+                    .map_err(|_| format!("Invalid finger number {:?}", triple[0]))?;
+                let coords = (parse_f32(triple[1])?, parse_f32(triple[2])?);
+                Ok((FingerId::Touch(number), coords))
+            })
+            .collect()
+    };
+
+    let input = match kind {
+        "focus-lost" => RecordedInput::Event(Event::FocusLost),
+        "focus-gained" => RecordedInput::Event(Event::FocusGained),
+        "minimized" => RecordedInput::Event(Event::Minimized),
+        "restored" => RecordedInput::Event(Event::Restored),
+        "down" => RecordedInput::Event(Event::TouchesDown(parse_touches()?)),
+        "move" => RecordedInput::Event(Event::TouchesMove(parse_touches()?)),
+        "up" => RecordedInput::Event(Event::TouchesUp(parse_touches()?)),
+        "text" => {
+            let hex = params.first().copied().unwrap_or("");
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or(""), 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| format!("Invalid hex text {:?}", hex))?;
+            let text = String::from_utf8(bytes).map_err(|_| "Text isn't UTF-8".to_string())?;
+            RecordedInput::Event(Event::TextInput(TextInputEvent::Text(text)))
+        }
+        "backspace" => RecordedInput::Event(Event::TextInput(TextInputEvent::Backspace)),
+        "return" => RecordedInput::Event(Event::TextInput(TextInputEvent::Return)),
+        "screenshot" => RecordedInput::Event(Event::Screenshot),
+        "turbo" => RecordedInput::Event(Event::ToggleTurbo),
+        "debug-overlay" => RecordedInput::Event(Event::ToggleDebugOverlay),
+        "scaling-filter" => RecordedInput::Event(Event::ToggleScalingFilter),
+        "accel" => {
+            let &[x, y, z] = params.as_slice() else {
+                return Err("Accelerometer readings need x, y and z".to_string());
+            };
+            RecordedInput::Acceleration((parse_f32(x)?, parse_f32(y)?, parse_f32(z)?))
+        }
+        _ => return Err(format!("Unknown event kind {:?}", kind)),
+    };
+    Ok((frame, input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_replay() {
+        let recorded = [
+            Event::TouchesDown(HashMap::from([(FingerId::Mouse, (160.0, 240.0))])),
+            Event::TouchesDown(HashMap::from([
+                (FingerId::Mouse, (161.5, 242.0)),
+                (FingerId::VirtualCursor, (10.0, 20.0)),
+            ])),
+            Event::TouchesUp(HashMap::from([
+                (FingerId::Mouse, (161.5, 242.0)),
+                (FingerId::VirtualCursor, (10.0, 20.0)),
+            ])),
+            Event::TextInput(TextInputEvent::Text("héllo".to_string())),
+            Event::TextInput(TextInputEvent::Return),
+            Event::ToggleTurbo,
+            Event::Minimized,
+            // Not an input event, shouldn't be recorded.
+            Event::EnterDebugger,
+        ];
+
+        let mut finger_numbers = HashMap::new();
+        let recording: String = recorded
+            .iter()
+            .enumerate()
+            .filter_map(|(i, event)| {
+                let line = format_event(&mut finger_numbers, event)?;
+                Some(format!("{} {}\n", i * 100, line))
+            })
+            .chain(std::iter::once("800 accel 0 0 -1\n".to_string()))
+            .collect();
+
+        let replayer = InputReplayer::from_recording(&recording, 0).unwrap();
+        let replayed: Vec<(u64, Event)> = replayer.events.into_iter().collect();
+        let expected = [
+            Event::TouchesDown(HashMap::from([(FingerId::Touch(0), (160.0, 240.0))])),
+            Event::TouchesDown(HashMap::from([
+                (FingerId::Touch(0), (161.5, 242.0)),
+                (FingerId::Touch(1), (10.0, 20.0)),
+            ])),
+            Event::TouchesUp(HashMap::from([
+                (FingerId::Touch(0), (161.5, 242.0)),
+                (FingerId::Touch(1), (10.0, 20.0)),
+            ])),
+            Event::TextInput(TextInputEvent::Text("héllo".to_string())),
+            Event::TextInput(TextInputEvent::Return),
+            Event::ToggleTurbo,
+            Event::Minimized,
+        ];
+        assert_eq!(replayed.len(), expected.len());
+        for (i, ((frame, event), expected)) in replayed.iter().zip(expected.iter()).enumerate() {
+            assert_eq!(*frame, i as u64 * 100);
+            assert_eq!(event, expected);
+        }
+        assert_eq!(replayer.accelerations, VecDeque::from([(0.0, 0.0, -1.0)]));
+    }
+
+    #[test]
+    fn replay_by_frame() {
+        // Frame numbers are relative to the frame the replay started on.
+        let mut replayer =
+            InputReplayer::from_recording("0 turbo\n2 screenshot\n2 return\n", 10).unwrap();
+        assert_eq!(replayer.pop_due_event(10), Some(Event::ToggleTurbo));
+        assert_eq!(replayer.pop_due_event(11), None);
+        assert_eq!(replayer.pop_due_event(12), Some(Event::Screenshot));
+        // Events for frames that were skipped aren't lost.
+        assert_eq!(
+            replayer.pop_due_event(20),
+            Some(Event::TextInput(TextInputEvent::Return))
+        );
+        assert_eq!(replayer.pop_due_event(21), None);
+    }
+
+    #[test]
+    fn invalid_recording() {
+        assert!(InputReplayer::from_recording("100 down 0 1", 0).is_err());
+        assert!(InputReplayer::from_recording("abc up 0 1 2", 0).is_err());
+        assert!(InputReplayer::from_recording("100 jump", 0).is_err());
+    }
+}
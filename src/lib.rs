@@ -33,14 +33,17 @@ mod audio;
 mod bundle;
 mod cpu;
 mod debug;
+mod debug_overlay;
 mod dyld;
 mod environment;
 mod font;
 mod frameworks;
 mod fs;
+mod gamepad_to_touch;
 mod gdb;
 mod gles;
 mod image;
+mod input_recording;
 mod libc;
 mod licenses;
 mod mach_o;
@@ -49,6 +52,7 @@ mod mem;
 mod objc;
 mod options;
 mod paths;
+mod snapshot;
 mod stack;
 mod window;
 
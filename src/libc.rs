@@ -12,18 +12,22 @@
 mod generic_char;
 
 pub mod clocale;
+pub mod common_crypto;
 pub mod ctype;
 pub mod cxxabi;
 pub mod dirent;
+pub mod dispatch;
 pub mod dlfcn;
 pub mod errno;
 pub mod ifaddrs;
 pub mod keymgr;
 pub mod mach_thread_info;
 pub mod mach_time;
+pub mod malloc;
 pub mod math;
 pub mod mmap;
 pub mod net;
+pub mod netdb;
 pub mod posix_io;
 pub mod pthread;
 pub mod sched;
@@ -37,13 +41,18 @@ pub mod sys;
 pub mod sysctl;
 pub mod time;
 pub mod unistd;
+pub mod unwind;
 pub mod wchar;
 
 /// Container for state of various child modules
 #[derive(Default)]
 pub struct State {
+    common_crypto: common_crypto::State,
     dirent: dirent::State,
+    dispatch: dispatch::State,
     keymgr: keymgr::State,
+    malloc: malloc::State,
+    netdb: netdb::State,
     posix_io: posix_io::State,
     pthread: pthread::State,
     pub semaphore: semaphore::State,
@@ -52,4 +61,5 @@ pub struct State {
     time: time::State,
     errno: errno::State,
     clocale: clocale::State,
+    unwind: unwind::State,
 }
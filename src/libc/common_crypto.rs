@@ -0,0 +1,359 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! CommonCrypto (`CommonDigest.h`, `CommonHMAC.h` and `CommonCryptor.h`).
+//!
+//! The algorithms themselves are implemented on the host, in the child
+//! modules.
+
+#![allow(non_upper_case_globals)] // Apple's constants begin with "k"
+
+mod cryptor;
+mod digests;
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::{ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, SafeRead};
+use crate::Environment;
+use cryptor::{Cryptor, Operation};
+use digests::{Algorithm, Md5Context};
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct State {
+    cryptors: HashMap<CCCryptorRef, Cryptor>,
+}
+impl State {
+    fn get_mut(env: &mut Environment) -> &mut Self {
+        &mut env.libc_state.common_crypto
+    }
+}
+
+#[allow(non_camel_case_types)]
+type CC_LONG = u32;
+
+const CC_MD5_DIGEST_LENGTH: GuestUSize = 16;
+
+/// `CC_MD5_CTX`, which matches OpenSSL's `MD5_CTX`.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct CC_MD5_CTX {
+    state: [CC_LONG; 4],
+    /// Low and high words of the message length in bits
+    Nl: CC_LONG,
+    Nh: CC_LONG,
+    /// Unprocessed input (declared as `CC_LONG data[16]`)
+    data: [u8; 64],
+    num: i32,
+}
+unsafe impl SafeRead for CC_MD5_CTX {}
+
+impl CC_MD5_CTX {
+    fn to_context(self) -> Md5Context {
+        let bit_length = (u64::from(self.Nh) << 32) | u64::from(self.Nl);
+        Md5Context {
+            state: self.state,
+            length: bit_length / 8,
+            buffer: self.data,
+            buffer_len: self.num as usize,
+        }
+    }
+    fn from_context(context: &Md5Context) -> Self {
+        let bit_length = context.length.wrapping_mul(8);
+        CC_MD5_CTX {
+            state: context.state,
+            Nl: bit_length as u32,
+            Nh: (bit_length >> 32) as u32,
+            data: context.buffer,
+            num: context.buffer_len as i32,
+        }
+    }
+}
+
+type CCHmacAlgorithm = u32;
+const kCCHmacAlgSHA1: CCHmacAlgorithm = 0;
+const kCCHmacAlgMD5: CCHmacAlgorithm = 1;
+const kCCHmacAlgSHA256: CCHmacAlgorithm = 2;
+
+/// Mem::bytes_at() panics when the pointer is NULL, but NULL is fine for an
+/// empty input.
+fn input_slice(mem: &Mem, data: ConstVoidPtr, len: GuestUSize) -> &[u8] {
+    if len == 0 {
+        &[]
+    } else {
+        mem.bytes_at(data.cast(), len)
+    }
+}
+
+fn write_digest(env: &mut Environment, md: MutPtr<u8>, digest: &[u8]) -> MutPtr<u8> {
+    let len = digest.len().try_into().unwrap();
+    env.mem.bytes_at_mut(md, len).copy_from_slice(digest);
+    md
+}
+
+fn one_shot(
+    env: &mut Environment,
+    algorithm: Algorithm,
+    data: ConstVoidPtr,
+    len: CC_LONG,
+    md: MutPtr<u8>,
+) -> MutPtr<u8> {
+    let digest = digests::digest(algorithm, input_slice(&env.mem, data, len));
+    write_digest(env, md, &digest)
+}
+
+fn CC_MD5(env: &mut Environment, data: ConstVoidPtr, len: CC_LONG, md: MutPtr<u8>) -> MutPtr<u8> {
+    one_shot(env, Algorithm::Md5, data, len, md)
+}
+fn CC_SHA1(env: &mut Environment, data: ConstVoidPtr, len: CC_LONG, md: MutPtr<u8>) -> MutPtr<u8> {
+    one_shot(env, Algorithm::Sha1, data, len, md)
+}
+fn CC_SHA256(
+    env: &mut Environment,
+    data: ConstVoidPtr,
+    len: CC_LONG,
+    md: MutPtr<u8>,
+) -> MutPtr<u8> {
+    one_shot(env, Algorithm::Sha256, data, len, md)
+}
+
+fn CC_MD5_Init(env: &mut Environment, c: MutPtr<CC_MD5_CTX>) -> i32 {
+    env.mem
+        .write(c, CC_MD5_CTX::from_context(&Md5Context::default()));
+    1
+}
+fn CC_MD5_Update(
+    env: &mut Environment,
+    c: MutPtr<CC_MD5_CTX>,
+    data: ConstVoidPtr,
+    len: CC_LONG,
+) -> i32 {
+    let mut context = env.mem.read(c).to_context();
+    context.update(input_slice(&env.mem, data, len));
+    env.mem.write(c, CC_MD5_CTX::from_context(&context));
+    1
+}
+fn CC_MD5_Final(env: &mut Environment, md: MutPtr<u8>, c: MutPtr<CC_MD5_CTX>) -> i32 {
+    let digest = env.mem.read(c).to_context().finish();
+    assert!(digest.len() == CC_MD5_DIGEST_LENGTH as usize);
+    write_digest(env, md, &digest);
+    1
+}
+
+fn CCHmac(
+    env: &mut Environment,
+    algorithm: CCHmacAlgorithm,
+    key: ConstVoidPtr,
+    key_length: GuestUSize,
+    data: ConstVoidPtr,
+    data_length: GuestUSize,
+    mac_out: MutVoidPtr,
+) {
+    let key = input_slice(&env.mem, key, key_length);
+    let data = input_slice(&env.mem, data, data_length);
+    let algorithm = match algorithm {
+        kCCHmacAlgSHA1 => Algorithm::Sha1,
+        kCCHmacAlgMD5 => Algorithm::Md5,
+        kCCHmacAlgSHA256 => Algorithm::Sha256,
+        _ => unimplemented!("CCHmac() with algorithm {}", algorithm),
+    };
+    let mac = digests::hmac(algorithm, key, data);
+    write_digest(env, mac_out.cast(), &mac);
+}
+
+/// Opaque type, only used as a key for [State::cryptors].
+#[allow(non_camel_case_types)]
+struct _CCCryptor {
+    _unused: u32,
+}
+unsafe impl SafeRead for _CCCryptor {}
+type CCCryptorRef = MutPtr<_CCCryptor>;
+
+type CCCryptorStatus = i32;
+const kCCSuccess: CCCryptorStatus = 0;
+const kCCParamError: CCCryptorStatus = -4300;
+const kCCBufferTooSmall: CCCryptorStatus = -4301;
+const kCCAlignmentError: CCCryptorStatus = -4303;
+const kCCDecodeError: CCCryptorStatus = -4304;
+
+type CCOperation = u32;
+const kCCEncrypt: CCOperation = 0;
+const kCCDecrypt: CCOperation = 1;
+
+type CCAlgorithm = u32;
+const kCCAlgorithmAES128: CCAlgorithm = 0;
+
+type CCOptions = u32;
+const kCCOptionPKCS7Padding: CCOptions = 1 << 0;
+const kCCOptionECBMode: CCOptions = 1 << 1;
+
+fn new_cryptor(
+    env: &mut Environment,
+    op: CCOperation,
+    alg: CCAlgorithm,
+    options: CCOptions,
+    key: ConstVoidPtr,
+    key_length: GuestUSize,
+    iv: ConstVoidPtr,
+) -> Result<Cryptor, CCCryptorStatus> {
+    let operation = match op {
+        kCCEncrypt => Operation::Encrypt,
+        kCCDecrypt => Operation::Decrypt,
+        _ => return Err(kCCParamError),
+    };
+    // kCCAlgorithmAES128 is used for all AES key sizes.
+    if alg != kCCAlgorithmAES128 {
+        unimplemented!("CCCryptor with algorithm {}", alg);
+    }
+    let iv = if options & kCCOptionECBMode != 0 {
+        None
+    } else if iv.is_null() {
+        // A NULL IV means all zeroes.
+        Some([0; cryptor::BLOCK_SIZE])
+    } else {
+        let iv = env
+            .mem
+            .bytes_at(iv.cast(), cryptor::BLOCK_SIZE as GuestUSize);
+        Some(iv.try_into().unwrap())
+    };
+    let key = input_slice(&env.mem, key, key_length);
+    let padding = options & kCCOptionPKCS7Padding != 0;
+    Cryptor::new(operation, key, iv, padding).ok_or(kCCParamError)
+}
+
+/// Write the output of a cryptor operation, or return [kCCBufferTooSmall] if
+/// it doesn't fit. Either way, `data_out_moved` is set to the output length.
+fn write_output(
+    env: &mut Environment,
+    output: &[u8],
+    data_out: MutVoidPtr,
+    data_out_available: GuestUSize,
+    data_out_moved: MutPtr<GuestUSize>,
+) -> CCCryptorStatus {
+    let len: GuestUSize = output.len().try_into().unwrap();
+    if !data_out_moved.is_null() {
+        env.mem.write(data_out_moved, len);
+    }
+    if len > data_out_available {
+        return kCCBufferTooSmall;
+    }
+    if len != 0 {
+        env.mem
+            .bytes_at_mut(data_out.cast(), len)
+            .copy_from_slice(output);
+    }
+    kCCSuccess
+}
+
+#[allow(clippy::too_many_arguments)]
+fn CCCryptorCreate(
+    env: &mut Environment,
+    op: CCOperation,
+    alg: CCAlgorithm,
+    options: CCOptions,
+    key: ConstVoidPtr,
+    key_length: GuestUSize,
+    iv: ConstVoidPtr,
+    cryptor_ref: MutPtr<CCCryptorRef>,
+) -> CCCryptorStatus {
+    let cryptor = match new_cryptor(env, op, alg, options, key, key_length, iv) {
+        Ok(cryptor) => cryptor,
+        Err(status) => return status,
+    };
+    let new = env.mem.alloc_and_write(_CCCryptor { _unused: 0 });
+    State::get_mut(env).cryptors.insert(new, cryptor);
+    env.mem.write(cryptor_ref, new);
+    kCCSuccess
+}
+
+fn CCCryptorUpdate(
+    env: &mut Environment,
+    cryptor_ref: CCCryptorRef,
+    data_in: ConstVoidPtr,
+    data_in_length: GuestUSize,
+    data_out: MutVoidPtr,
+    data_out_available: GuestUSize,
+    data_out_moved: MutPtr<GuestUSize>,
+) -> CCCryptorStatus {
+    // The operation is done on a copy, so that no state is lost if the output
+    // buffer is too small and the caller retries.
+    let mut cryptor = State::get_mut(env).cryptors[&cryptor_ref].clone();
+    let output = cryptor.update(input_slice(&env.mem, data_in, data_in_length));
+    let status = write_output(env, &output, data_out, data_out_available, data_out_moved);
+    if status == kCCSuccess {
+        State::get_mut(env).cryptors.insert(cryptor_ref, cryptor);
+    }
+    status
+}
+
+fn CCCryptorFinal(
+    env: &mut Environment,
+    cryptor_ref: CCCryptorRef,
+    data_out: MutVoidPtr,
+    data_out_available: GuestUSize,
+    data_out_moved: MutPtr<GuestUSize>,
+) -> CCCryptorStatus {
+    let mut cryptor = State::get_mut(env).cryptors[&cryptor_ref].clone();
+    let output = match cryptor.finish() {
+        Ok(output) => output,
+        Err(cryptor::Error::Alignment) => return kCCAlignmentError,
+        Err(cryptor::Error::Decode) => return kCCDecodeError,
+    };
+    let status = write_output(env, &output, data_out, data_out_available, data_out_moved);
+    if status == kCCSuccess {
+        State::get_mut(env).cryptors.insert(cryptor_ref, cryptor);
+    }
+    status
+}
+
+fn CCCryptorRelease(env: &mut Environment, cryptor_ref: CCCryptorRef) -> CCCryptorStatus {
+    State::get_mut(env).cryptors.remove(&cryptor_ref).unwrap();
+    env.mem.free(cryptor_ref.cast());
+    kCCSuccess
+}
+
+/// One-shot form of the `CCCryptor` functions.
+#[allow(clippy::too_many_arguments)]
+fn CCCrypt(
+    env: &mut Environment,
+    op: CCOperation,
+    alg: CCAlgorithm,
+    options: CCOptions,
+    key: ConstVoidPtr,
+    key_length: GuestUSize,
+    iv: ConstVoidPtr,
+    data_in: ConstVoidPtr,
+    data_in_length: GuestUSize,
+    data_out: MutVoidPtr,
+    data_out_available: GuestUSize,
+    data_out_moved: MutPtr<GuestUSize>,
+) -> CCCryptorStatus {
+    let mut cryptor = match new_cryptor(env, op, alg, options, key, key_length, iv) {
+        Ok(cryptor) => cryptor,
+        Err(status) => return status,
+    };
+    let mut output = cryptor.update(input_slice(&env.mem, data_in, data_in_length));
+    match cryptor.finish() {
+        Ok(end) => output.extend_from_slice(&end),
+        Err(cryptor::Error::Alignment) => return kCCAlignmentError,
+        Err(cryptor::Error::Decode) => return kCCDecodeError,
+    }
+    write_output(env, &output, data_out, data_out_available, data_out_moved)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CC_MD5(_, _, _)),
+    export_c_func!(CC_SHA1(_, _, _)),
+    export_c_func!(CC_SHA256(_, _, _)),
+    export_c_func!(CC_MD5_Init(_)),
+    export_c_func!(CC_MD5_Update(_, _, _)),
+    export_c_func!(CC_MD5_Final(_, _)),
+    export_c_func!(CCHmac(_, _, _, _, _, _)),
+    export_c_func!(CCCryptorCreate(_, _, _, _, _, _, _)),
+    export_c_func!(CCCryptorUpdate(_, _, _, _, _, _)),
+    export_c_func!(CCCryptorFinal(_, _, _, _)),
+    export_c_func!(CCCryptorRelease(_)),
+    export_c_func!(CCCrypt(_, _, _, _, _, _, _, _, _, _, _)),
+];
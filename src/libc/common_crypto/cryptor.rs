@@ -0,0 +1,288 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Host implementation of the ECB and CBC block cipher modes with optional
+//! PKCS#7 padding, as used by `CCCryptor`, using the RustCrypto `aes` and `cbc`
+//! crates.
+
+use aes::{Aes128, Aes192, Aes256};
+use cbc::cipher::block_padding::{Padding, Pkcs7};
+use cbc::cipher::consts::U16;
+use cbc::cipher::generic_array::GenericArray;
+use cbc::cipher::{
+    BlockCipher, BlockDecrypt, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, BlockSizeUser,
+    InnerIvInit, KeyInit,
+};
+
+pub const BLOCK_SIZE: usize = 16;
+
+type Block = [u8; BLOCK_SIZE];
+
+/// Encrypts or decrypts one block at a time, keeping any chaining state. The
+/// crates' types are generic over the key size, mode and operation, so this
+/// erases those differences.
+trait BlockProcessor {
+    fn process_block(&mut self, block: &mut Block);
+    fn clone_box(&self) -> Box<dyn BlockProcessor>;
+}
+impl Clone for Box<dyn BlockProcessor> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Clone)]
+struct Encrypt<T>(T);
+impl<T: BlockEncryptMut + BlockSizeUser<BlockSize = U16> + Clone + 'static> BlockProcessor
+    for Encrypt<T>
+{
+    fn process_block(&mut self, block: &mut Block) {
+        self.0
+            .encrypt_block_mut(GenericArray::from_mut_slice(block));
+    }
+    fn clone_box(&self) -> Box<dyn BlockProcessor> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone)]
+struct Decrypt<T>(T);
+impl<T: BlockDecryptMut + BlockSizeUser<BlockSize = U16> + Clone + 'static> BlockProcessor
+    for Decrypt<T>
+{
+    fn process_block(&mut self, block: &mut Block) {
+        self.0
+            .decrypt_block_mut(GenericArray::from_mut_slice(block));
+    }
+    fn clone_box(&self) -> Box<dyn BlockProcessor> {
+        Box::new(self.clone())
+    }
+}
+
+/// ECB mode is just the block cipher applied to each block, so only CBC mode
+/// needs the `cbc` crate.
+fn new_block_processor<C>(
+    operation: Operation,
+    key: &[u8],
+    iv: Option<Block>,
+) -> Box<dyn BlockProcessor>
+where
+    C: BlockCipher
+        + BlockEncrypt
+        + BlockDecrypt
+        + BlockSizeUser<BlockSize = U16>
+        + KeyInit
+        + Clone
+        + 'static,
+{
+    let cipher = C::new_from_slice(key).unwrap();
+    match (operation, iv) {
+        (Operation::Encrypt, None) => Box::new(Encrypt(cipher)),
+        (Operation::Decrypt, None) => Box::new(Decrypt(cipher)),
+        (Operation::Encrypt, Some(iv)) => Box::new(Encrypt(cbc::Encryptor::inner_iv_init(
+            cipher,
+            GenericArray::from_slice(&iv),
+        ))),
+        (Operation::Decrypt, Some(iv)) => Box::new(Decrypt(cbc::Decryptor::inner_iv_init(
+            cipher,
+            GenericArray::from_slice(&iv),
+        ))),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Encrypt,
+    Decrypt,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The input wasn't a multiple of the block size and there's no padding.
+    Alignment,
+    /// The padding of the decrypted data is invalid.
+    Decode,
+}
+
+/// Streaming AES encryption or decryption. Input is buffered until there's a
+/// complete block.
+#[derive(Clone)]
+pub struct Cryptor {
+    operation: Operation,
+    blocks: Box<dyn BlockProcessor>,
+    padding: bool,
+    buffer: Vec<u8>,
+}
+
+impl Cryptor {
+    /// `iv` should be [None] for ECB mode. Returns [None] if the key isn't
+    /// 128, 192 or 256 bits long.
+    pub fn new(operation: Operation, key: &[u8], iv: Option<Block>, padding: bool) -> Option<Self> {
+        let blocks = match key.len() {
+            16 => new_block_processor::<Aes128>(operation, key, iv),
+            24 => new_block_processor::<Aes192>(operation, key, iv),
+            32 => new_block_processor::<Aes256>(operation, key, iv),
+            _ => return None,
+        };
+        Some(Cryptor {
+            operation,
+            blocks,
+            padding,
+            buffer: Vec::new(),
+        })
+    }
+
+    fn process_blocks(&mut self, count: usize) -> Vec<u8> {
+        let mut blocks: Vec<u8> = self.buffer.drain(..count * BLOCK_SIZE).collect();
+        for block in blocks.chunks_exact_mut(BLOCK_SIZE) {
+            self.blocks.process_block(block.try_into().unwrap());
+        }
+        blocks
+    }
+
+    /// Process as many complete blocks as possible. When decrypting with
+    /// padding, the last block is held back, because it might need to be
+    /// unpadded by [Self::finish].
+    pub fn update(&mut self, input: &[u8]) -> Vec<u8> {
+        self.buffer.extend_from_slice(input);
+        let mut count = self.buffer.len() / BLOCK_SIZE;
+        if self.operation == Operation::Decrypt
+            && self.padding
+            && count > 0
+            && self.buffer.len() == count * BLOCK_SIZE
+        {
+            count -= 1;
+        }
+        self.process_blocks(count)
+    }
+
+    /// Process the remaining input, adding or removing the padding.
+    pub fn finish(&mut self) -> Result<Vec<u8>, Error> {
+        if !self.padding {
+            return if self.buffer.is_empty() {
+                Ok(Vec::new())
+            } else {
+                Err(Error::Alignment)
+            };
+        }
+        match self.operation {
+            Operation::Encrypt => {
+                let len = self.buffer.len();
+                let mut block = [0; BLOCK_SIZE];
+                block[..len].copy_from_slice(&self.buffer);
+                Pkcs7::pad(GenericArray::from_mut_slice(&mut block), len);
+                self.buffer = block.to_vec();
+                Ok(self.process_blocks(1))
+            }
+            Operation::Decrypt => {
+                match self.buffer.len() {
+                    0 => return Err(Error::Decode),
+                    BLOCK_SIZE => (),
+                    _ => return Err(Error::Alignment),
+                }
+                let block = self.process_blocks(1);
+                Pkcs7::unpad(GenericArray::from_slice(&block))
+                    .map(|unpadded| unpadded.to_vec())
+                    .map_err(|_| Error::Decode)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..][..2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_key_sizes() {
+        // FIPS 197 appendix C
+        let plaintext = from_hex("00112233445566778899aabbccddeeff");
+        for (key, ciphertext) in [
+            (
+                "000102030405060708090a0b0c0d0e0f",
+                "69c4e0d86a7b0430d8cdb78070b4c55a",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f1011121314151617",
+                "dda97ca4864cdfe06eaf70a0ec0d7191",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "8ea2b7ca516745bfeafc49904b496089",
+            ),
+        ] {
+            let key = from_hex(key);
+            let mut encryptor = Cryptor::new(Operation::Encrypt, &key, None, false).unwrap();
+            assert_eq!(encryptor.update(&plaintext), from_hex(ciphertext));
+            let mut decryptor = Cryptor::new(Operation::Decrypt, &key, None, false).unwrap();
+            assert_eq!(decryptor.update(&from_hex(ciphertext)), plaintext);
+        }
+        assert!(Cryptor::new(Operation::Encrypt, &[0; 15], None, false).is_none());
+    }
+
+    #[test]
+    fn test_modes() {
+        // NIST SP 800-38A F.1.1 and F.2.1
+        let key = from_hex("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv: Block = from_hex("000102030405060708090a0b0c0d0e0f")
+            .try_into()
+            .unwrap();
+        let plaintext =
+            from_hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+
+        let mut ecb = Cryptor::new(Operation::Encrypt, &key, None, false).unwrap();
+        assert_eq!(
+            ecb.update(&plaintext),
+            from_hex("3ad77bb40d7a3660a89ecaf32466ef97f5d3d58503b9699de785895a96fdbaaf")
+        );
+        assert_eq!(ecb.finish(), Ok(Vec::new()));
+
+        let cbc_ciphertext =
+            from_hex("7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2");
+        let mut cbc = Cryptor::new(Operation::Encrypt, &key, Some(iv), false).unwrap();
+        assert_eq!(cbc.update(&plaintext), cbc_ciphertext);
+        let mut cbc = Cryptor::new(Operation::Decrypt, &key, Some(iv), false).unwrap();
+        assert_eq!(cbc.update(&cbc_ciphertext), plaintext);
+
+        let mut unaligned = Cryptor::new(Operation::Encrypt, &key, Some(iv), false).unwrap();
+        unaligned.update(&plaintext[..20]);
+        assert_eq!(unaligned.finish(), Err(Error::Alignment));
+    }
+
+    #[test]
+    fn test_padding() {
+        let key = [7; 32];
+        let iv = Some([3; BLOCK_SIZE]);
+        let plaintext: Vec<u8> = (0..50).collect();
+        for len in 0..plaintext.len() {
+            let mut encryptor = Cryptor::new(Operation::Encrypt, &key, iv, true).unwrap();
+            let mut ciphertext = encryptor.update(&plaintext[..len / 2]);
+            ciphertext.extend(encryptor.update(&plaintext[len / 2..len]));
+            ciphertext.extend(encryptor.finish().unwrap());
+            assert_eq!(ciphertext.len(), (len / BLOCK_SIZE + 1) * BLOCK_SIZE);
+
+            let mut decryptor = Cryptor::new(Operation::Decrypt, &key, iv, true).unwrap();
+            let mut decrypted = decryptor.update(&ciphertext);
+            assert!(decrypted.len() < ciphertext.len());
+            decrypted.extend(decryptor.finish().unwrap());
+            assert_eq!(decrypted, &plaintext[..len]);
+        }
+
+        // Decrypting with the wrong key gives invalid padding (almost always).
+        let mut encryptor = Cryptor::new(Operation::Encrypt, &key, iv, true).unwrap();
+        let mut ciphertext = encryptor.update(b"secret");
+        ciphertext.extend(encryptor.finish().unwrap());
+        let mut decryptor = Cryptor::new(Operation::Decrypt, &[8; 32], iv, true).unwrap();
+        decryptor.update(&ciphertext);
+        assert_eq!(decryptor.finish(), Err(Error::Decode));
+    }
+}
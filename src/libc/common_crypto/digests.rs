@@ -0,0 +1,171 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Host implementations of the message digest algorithms (MD5, SHA-1 and
+//! SHA-256) and HMAC, using the RustCrypto crates.
+//!
+//! The incremental MD5 functions keep their state in a guest struct
+//! (`CC_MD5_CTX`), which the crates' hashers can't be stored in, so
+//! [Md5Context] does its own buffering and padding around the `md-5` crate's
+//! compression function.
+
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use sha1::Sha1;
+use sha2::Sha256;
+
+#[derive(Copy, Clone)]
+pub enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+pub fn digest(algorithm: Algorithm, data: &[u8]) -> Vec<u8> {
+    match algorithm {
+        Algorithm::Md5 => Md5::digest(data).to_vec(),
+        Algorithm::Sha1 => Sha1::digest(data).to_vec(),
+        Algorithm::Sha256 => Sha256::digest(data).to_vec(),
+    }
+}
+
+/// HMAC as defined in RFC 2104.
+pub fn hmac(algorithm: Algorithm, key: &[u8], data: &[u8]) -> Vec<u8> {
+    // HMAC accepts keys of any length.
+    match algorithm {
+        Algorithm::Md5 => Hmac::<Md5>::new_from_slice(key)
+            .unwrap()
+            .chain_update(data)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+        Algorithm::Sha1 => Hmac::<Sha1>::new_from_slice(key)
+            .unwrap()
+            .chain_update(data)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+        Algorithm::Sha256 => Hmac::<Sha256>::new_from_slice(key)
+            .unwrap()
+            .chain_update(data)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+    }
+}
+
+pub const MD5_BLOCK_SIZE: usize = 64;
+
+/// Incremental MD5 hasher. The fields are public so the state can be stored in
+/// and restored from a guest context struct.
+#[derive(Clone)]
+pub struct Md5Context {
+    pub state: [u32; 4],
+    /// Total length of the message so far, in bytes.
+    pub length: u64,
+    pub buffer: [u8; MD5_BLOCK_SIZE],
+    /// Number of bytes in `buffer` that haven't been processed yet.
+    pub buffer_len: usize,
+}
+
+impl Default for Md5Context {
+    fn default() -> Self {
+        Md5Context {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            length: 0,
+            buffer: [0; MD5_BLOCK_SIZE],
+            buffer_len: 0,
+        }
+    }
+}
+
+impl Md5Context {
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let count = (MD5_BLOCK_SIZE - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..][..count].copy_from_slice(&data[..count]);
+            self.buffer_len += count;
+            data = &data[count..];
+            if self.buffer_len == MD5_BLOCK_SIZE {
+                md5::compress(&mut self.state, &[self.buffer]);
+                self.buffer_len = 0;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let bit_length = self.length.wrapping_mul(8);
+        // Pad with a single 1 bit, then zeroes until there's just enough room
+        // for the little-endian length at the end of a block.
+        let zeroes = (MD5_BLOCK_SIZE * 2 - 9 - self.buffer_len) % MD5_BLOCK_SIZE;
+        let mut padding = vec![0x80];
+        padding.resize(1 + zeroes, 0);
+        padding.extend_from_slice(&bit_length.to_le_bytes());
+        self.update(&padding);
+        debug_assert!(self.buffer_len == 0);
+
+        self.state
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_md5_context() {
+        // RFC 1321 test suite
+        for (input, expected) in [
+            (&b""[..], "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ] {
+            let mut context = Md5Context::default();
+            context.update(input);
+            assert_eq!(hex(&context.finish()), expected);
+        }
+    }
+
+    #[test]
+    fn test_md5_context_incremental() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        for split in [0, 1, 55, 56, 63, 64, 65, 999] {
+            let mut context = Md5Context::default();
+            context.update(&data[..split]);
+            context.update(&data[split..]);
+            assert_eq!(context.finish(), digest(Algorithm::Md5, &data));
+        }
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 2202 and RFC 4231 test case 2
+        let key = b"Jefe";
+        let data = b"what do ya want for nothing?";
+        assert_eq!(
+            hex(&hmac(Algorithm::Md5, key, data)),
+            "750c783e6ab0b503eaa86e310a5db738"
+        );
+        assert_eq!(
+            hex(&hmac(Algorithm::Sha1, key, data)),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+        assert_eq!(
+            hex(&hmac(Algorithm::Sha256, key, data)),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,539 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Grand Central Dispatch (libdispatch).
+//!
+//! This is a simple implementation on top of touchHLE's threads: blocks for
+//! the main queue are run by the main thread's run loop, and each block for a
+//! global queue gets a new thread. touchHLE doesn't run threads in parallel,
+//! so this is no slower than using a thread pool.
+
+use crate::abi::CallFromHost;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant, HostFunction};
+use crate::libc::pthread::thread::{
+    pthread_attr_init, pthread_attr_setdetachstate, pthread_attr_t, pthread_create, pthread_t,
+    PTHREAD_CREATE_DETACHED,
+};
+use crate::mem::{guest_size_of, ConstVoidPtr, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::objc::{_Block_copy, _Block_release, get_block_invoke, id};
+use crate::Environment;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Dispatch objects are just a magic number in guest memory saying what kind
+/// of object it is. Any other state is kept on the host.
+#[repr(C, packed)]
+pub struct dispatch_object_s {
+    magic: u32,
+}
+unsafe impl SafeRead for dispatch_object_s {}
+pub type dispatch_object_t = MutPtr<dispatch_object_s>;
+pub type dispatch_queue_t = dispatch_object_t;
+type dispatch_semaphore_t = dispatch_object_t;
+type dispatch_group_t = dispatch_object_t;
+
+const MAGIC_MAIN_QUEUE: u32 = u32::from_be_bytes(*b"MAIN");
+const MAGIC_GLOBAL_QUEUE: u32 = u32::from_be_bytes(*b"GLOB");
+const MAGIC_SEMAPHORE: u32 = u32::from_be_bytes(*b"SEMA");
+const MAGIC_GROUP: u32 = u32::from_be_bytes(*b"GRUP");
+
+/// Nanoseconds on the same clock as `mach_absolute_time()`, or one of the
+/// special values below.
+pub type dispatch_time_t = u64;
+const DISPATCH_TIME_NOW: dispatch_time_t = 0;
+const DISPATCH_TIME_FOREVER: dispatch_time_t = !0;
+
+/// `long`, which is 0 before the block is run and `~0` after.
+type dispatch_once_t = i32;
+/// Value of a `dispatch_once_t` while the block is running.
+const ONCE_RUNNING: dispatch_once_t = 1;
+const ONCE_DONE: dispatch_once_t = !0;
+
+/// Returned by functions that wait if the timeout passed.
+const KERN_OPERATION_TIMED_OUT: i32 = 49;
+
+/// How often waiting threads check whether they can stop waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Default)]
+pub struct State {
+    main_queue: Option<dispatch_queue_t>,
+    global_queue: Option<dispatch_queue_t>,
+    /// Work waiting to be run by the main thread, in the order it was added.
+    main_queue_items: Vec<MainQueueItem>,
+    /// Work waiting to be run by a new background thread, with the time it's
+    /// due, keyed by the ID passed to the thread.
+    background_items: HashMap<u32, (Instant, Work)>,
+    /// IDs of `dispatch_sync()` calls for the main queue that have finished.
+    finished_sync_items: HashSet<u32>,
+    next_id: u32,
+    semaphores: HashMap<dispatch_semaphore_t, Semaphore>,
+    groups: HashMap<dispatch_group_t, Group>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.libc_state.dispatch
+    }
+    fn new_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+/// A block that has been submitted to a queue.
+struct Work {
+    /// Heap copy, released once it's been run.
+    block: id,
+    /// Group to leave and release once the block has been run.
+    group: Option<dispatch_group_t>,
+}
+
+struct MainQueueItem {
+    due: Instant,
+    work: Work,
+    /// For `dispatch_sync()`: ID to add to [State::finished_sync_items] once
+    /// the block has run.
+    sync_id: Option<u32>,
+}
+
+struct Semaphore {
+    refcount: u32,
+    value: i32,
+    /// Number of threads waiting for the value to become positive.
+    waiters: u32,
+}
+
+struct Group {
+    refcount: u32,
+    /// Number of blocks that haven't finished yet.
+    pending: u32,
+    /// Heap copies of blocks to submit to queues once there are no pending
+    /// blocks. Each one holds a reference to the group.
+    notify: Vec<(dispatch_queue_t, id)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Queue {
+    Main,
+    Global,
+}
+
+fn identify_queue(mem: &Mem, queue: dispatch_queue_t) -> Queue {
+    match mem.read(queue).magic {
+        MAGIC_MAIN_QUEUE => Queue::Main,
+        MAGIC_GLOBAL_QUEUE => Queue::Global,
+        magic => panic!("Unknown dispatch queue {:?} (magic {:#x})", queue, magic),
+    }
+}
+
+fn new_object(mem: &mut Mem, magic: u32) -> dispatch_object_t {
+    mem.alloc_and_write(dispatch_object_s { magic })
+}
+
+fn run_block(env: &mut Environment, block: id) {
+    let invoke = get_block_invoke(&env.mem, block);
+    () = invoke.call_from_host(env, (block,));
+}
+
+/// Convert a `dispatch_time_t` to an [Instant], or [None] if it's
+/// [DISPATCH_TIME_FOREVER].
+fn instant_from_dispatch_time(env: &Environment, when: dispatch_time_t) -> Option<Instant> {
+    match when {
+        DISPATCH_TIME_NOW => Some(Instant::now()),
+        DISPATCH_TIME_FOREVER => None,
+        _ => Some(env.startup_time + Duration::from_nanos(when)),
+    }
+}
+
+fn run_work(env: &mut Environment, work: Work) {
+    let Work { block, group } = work;
+    run_block(env, block);
+    _Block_release(env, block);
+    if let Some(group) = group {
+        dispatch_group_leave(env, group);
+        dispatch_release(env, group);
+    }
+}
+
+/// Cooperatively wait until `condition` is true, or until `deadline` if it's
+/// not [None]. Returns [false] if the deadline passed.
+pub(crate) fn wait_until<F>(
+    env: &mut Environment,
+    deadline: Option<Instant>,
+    mut condition: F,
+) -> bool
+where
+    F: FnMut(&mut Environment) -> bool,
+{
+    loop {
+        if condition(env) {
+            return true;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return false;
+        }
+        env.sleep(POLL_INTERVAL, /* tail_call: */ false);
+    }
+}
+
+/// Schedule work to run on a queue at the time `due`.
+fn enqueue(env: &mut Environment, queue: dispatch_queue_t, due: Instant, work: Work) {
+    match identify_queue(&env.mem, queue) {
+        Queue::Main => State::get(env).main_queue_items.push(MainQueueItem {
+            due,
+            work,
+            sync_id: None,
+        }),
+        Queue::Global => {
+            let state = State::get(env);
+            let work_id = state.new_id();
+            state.background_items.insert(work_id, (due, work));
+
+            let symb = "__touchHLE_dispatch_worker";
+            let hf: HostFunction = &(_touchHLE_dispatch_worker as fn(&mut Environment, _));
+            let gf = env.dyld.create_guest_function(&mut env.mem, symb, hf);
+
+            let attr: MutPtr<pthread_attr_t> =
+                env.mem.alloc(guest_size_of::<pthread_attr_t>()).cast();
+            pthread_attr_init(env, attr);
+            pthread_attr_setdetachstate(env, attr, PTHREAD_CREATE_DETACHED);
+            let thread_ptr: MutPtr<pthread_t> = env.mem.alloc(guest_size_of::<pthread_t>()).cast();
+            let user_data: MutVoidPtr = Ptr::from_bits(work_id);
+            pthread_create(env, thread_ptr, attr.cast_const(), gf, user_data);
+            env.mem.free(thread_ptr.cast());
+            env.mem.free(attr.cast());
+        }
+    }
+}
+
+/// Start routine for the threads that run blocks for global queues.
+fn _touchHLE_dispatch_worker(env: &mut Environment, work_id: MutVoidPtr) {
+    let (due, work) = State::get(env)
+        .background_items
+        .remove(&work_id.to_bits())
+        .unwrap();
+    let now = Instant::now();
+    if due > now {
+        env.sleep(due - now, /* tail_call: */ false);
+    }
+    run_work(env, work);
+}
+
+/// For use by `NSRunLoop`: run the main queue's blocks that are due. Returns
+/// the time the next block is due, if any.
+pub fn handle_main_queue(env: &mut Environment) -> Option<Instant> {
+    let now = Instant::now();
+    let (due, not_due): (Vec<_>, Vec<_>) = std::mem::take(&mut State::get(env).main_queue_items)
+        .into_iter()
+        .partition(|item| item.due <= now);
+    State::get(env).main_queue_items.extend(not_due);
+
+    for MainQueueItem { work, sync_id, .. } in due {
+        run_work(env, work);
+        if let Some(sync_id) = sync_id {
+            State::get(env).finished_sync_items.insert(sync_id);
+        }
+    }
+
+    // This includes any blocks that were added while running blocks.
+    State::get(env)
+        .main_queue_items
+        .iter()
+        .map(|item| item.due)
+        .min()
+}
+
+fn dispatch_get_main_queue(env: &mut Environment) -> dispatch_queue_t {
+    if let Some(queue) = State::get(env).main_queue {
+        return queue;
+    }
+    let queue = new_object(&mut env.mem, MAGIC_MAIN_QUEUE);
+    State::get(env).main_queue = Some(queue);
+    queue
+}
+
+fn dispatch_get_global_queue(
+    env: &mut Environment,
+    _priority: i32,
+    flags: u32,
+) -> dispatch_queue_t {
+    // Reserved, must be zero.
+    assert_eq!(flags, 0);
+    // There's no point in different queues for different priorities when
+    // every block gets its own thread.
+    if let Some(queue) = State::get(env).global_queue {
+        return queue;
+    }
+    let queue = new_object(&mut env.mem, MAGIC_GLOBAL_QUEUE);
+    State::get(env).global_queue = Some(queue);
+    queue
+}
+
+fn dispatch_async(env: &mut Environment, queue: dispatch_queue_t, block: id) {
+    let block = _Block_copy(env, block);
+    enqueue(env, queue, Instant::now(), Work { block, group: None });
+}
+
+fn dispatch_sync(env: &mut Environment, queue: dispatch_queue_t, block: id) {
+    match identify_queue(&env.mem, queue) {
+        // Global queues can run the block on the calling thread.
+        Queue::Global => return run_block(env, block),
+        Queue::Main if env.current_thread == 0 => {
+            // This would deadlock on a real device.
+            log!("Warning: dispatch_sync() for the main queue on the main thread");
+            return run_block(env, block);
+        }
+        Queue::Main => (),
+    }
+
+    let block = _Block_copy(env, block);
+    let state = State::get(env);
+    let sync_id = state.new_id();
+    state.main_queue_items.push(MainQueueItem {
+        due: Instant::now(),
+        work: Work { block, group: None },
+        sync_id: Some(sync_id),
+    });
+    wait_until(env, None, |env| {
+        State::get(env).finished_sync_items.remove(&sync_id)
+    });
+}
+
+fn dispatch_time(env: &mut Environment, when: dispatch_time_t, delta: i64) -> dispatch_time_t {
+    let when = match when {
+        DISPATCH_TIME_FOREVER => return DISPATCH_TIME_FOREVER,
+        DISPATCH_TIME_NOW => Instant::now()
+            .duration_since(env.startup_time)
+            .as_nanos()
+            .try_into()
+            .unwrap(),
+        _ => when,
+    };
+    // DISPATCH_TIME_NOW can't be the result.
+    when.saturating_add_signed(delta).max(1)
+}
+
+fn dispatch_after(
+    env: &mut Environment,
+    when: dispatch_time_t,
+    queue: dispatch_queue_t,
+    block: id,
+) {
+    let Some(due) = instant_from_dispatch_time(env, when) else {
+        log!("Warning: dispatch_after() with DISPATCH_TIME_FOREVER, ignoring");
+        return;
+    };
+    let block = _Block_copy(env, block);
+    enqueue(env, queue, due, Work { block, group: None });
+}
+
+fn dispatch_once(env: &mut Environment, predicate: MutPtr<dispatch_once_t>, block: id) {
+    match env.mem.read(predicate) {
+        0 => {
+            env.mem.write(predicate, ONCE_RUNNING);
+            run_block(env, block);
+            env.mem.write(predicate, ONCE_DONE);
+        }
+        ONCE_RUNNING => {
+            // Another thread is running the block, wait for it to finish.
+            wait_until(env, None, |env| env.mem.read(predicate) == ONCE_DONE);
+        }
+        _ => (),
+    }
+}
+
+fn dispatch_retain(env: &mut Environment, object: dispatch_object_t) {
+    let state = State::get(env);
+    if let Some(semaphore) = state.semaphores.get_mut(&object) {
+        semaphore.refcount += 1;
+    } else if let Some(group) = state.groups.get_mut(&object) {
+        group.refcount += 1;
+    } else {
+        // Queues are never destroyed.
+        identify_queue(&env.mem, object);
+    }
+}
+
+fn dispatch_release(env: &mut Environment, object: dispatch_object_t) {
+    let state = State::get(env);
+    let refcount = if let Some(semaphore) = state.semaphores.get_mut(&object) {
+        semaphore.refcount -= 1;
+        semaphore.refcount
+    } else if let Some(group) = state.groups.get_mut(&object) {
+        group.refcount -= 1;
+        group.refcount
+    } else {
+        // Queues are never destroyed.
+        identify_queue(&env.mem, object);
+        return;
+    };
+    if refcount == 0 {
+        state.semaphores.remove(&object);
+        state.groups.remove(&object);
+        env.mem.free(object.cast());
+    }
+}
+
+fn dispatch_semaphore_create(env: &mut Environment, value: i32) -> dispatch_semaphore_t {
+    if value < 0 {
+        return Ptr::null();
+    }
+    let semaphore = new_object(&mut env.mem, MAGIC_SEMAPHORE);
+    State::get(env).semaphores.insert(
+        semaphore,
+        Semaphore {
+            refcount: 1,
+            value,
+            waiters: 0,
+        },
+    );
+    semaphore
+}
+
+fn dispatch_semaphore_wait(
+    env: &mut Environment,
+    semaphore: dispatch_semaphore_t,
+    timeout: dispatch_time_t,
+) -> i32 {
+    let deadline = instant_from_dispatch_time(env, timeout);
+    State::get(env)
+        .semaphores
+        .get_mut(&semaphore)
+        .unwrap()
+        .waiters += 1;
+    let acquired = wait_until(env, deadline, |env| {
+        let semaphore = State::get(env).semaphores.get_mut(&semaphore).unwrap();
+        if semaphore.value > 0 {
+            semaphore.value -= 1;
+            true
+        } else {
+            false
+        }
+    });
+    State::get(env)
+        .semaphores
+        .get_mut(&semaphore)
+        .unwrap()
+        .waiters -= 1;
+    if acquired {
+        0
+    } else {
+        KERN_OPERATION_TIMED_OUT
+    }
+}
+
+/// Returns non-zero if a thread is woken.
+fn dispatch_semaphore_signal(env: &mut Environment, semaphore: dispatch_semaphore_t) -> i32 {
+    let semaphore = State::get(env).semaphores.get_mut(&semaphore).unwrap();
+    semaphore.value += 1;
+    (semaphore.waiters > 0).into()
+}
+
+fn dispatch_group_create(env: &mut Environment) -> dispatch_group_t {
+    let group = new_object(&mut env.mem, MAGIC_GROUP);
+    State::get(env).groups.insert(
+        group,
+        Group {
+            refcount: 1,
+            pending: 0,
+            notify: Vec::new(),
+        },
+    );
+    group
+}
+
+fn dispatch_group_enter(env: &mut Environment, group: dispatch_group_t) {
+    State::get(env).groups.get_mut(&group).unwrap().pending += 1;
+}
+
+fn dispatch_group_leave(env: &mut Environment, group: dispatch_group_t) {
+    let group_obj = State::get(env).groups.get_mut(&group).unwrap();
+    assert!(group_obj.pending > 0); // Unbalanced call
+    group_obj.pending -= 1;
+    if group_obj.pending > 0 {
+        return;
+    }
+    for (queue, block) in std::mem::take(&mut group_obj.notify) {
+        enqueue(env, queue, Instant::now(), Work { block, group: None });
+        dispatch_release(env, group);
+    }
+}
+
+fn dispatch_group_async(
+    env: &mut Environment,
+    group: dispatch_group_t,
+    queue: dispatch_queue_t,
+    block: id,
+) {
+    dispatch_group_enter(env, group);
+    dispatch_retain(env, group);
+    let block = _Block_copy(env, block);
+    let work = Work {
+        block,
+        group: Some(group),
+    };
+    enqueue(env, queue, Instant::now(), work);
+}
+
+fn dispatch_group_wait(
+    env: &mut Environment,
+    group: dispatch_group_t,
+    timeout: dispatch_time_t,
+) -> i32 {
+    let deadline = instant_from_dispatch_time(env, timeout);
+    let finished = wait_until(env, deadline, |env| {
+        State::get(env).groups[&group].pending == 0
+    });
+    if finished {
+        0
+    } else {
+        KERN_OPERATION_TIMED_OUT
+    }
+}
+
+fn dispatch_group_notify(
+    env: &mut Environment,
+    group: dispatch_group_t,
+    queue: dispatch_queue_t,
+    block: id,
+) {
+    let block = _Block_copy(env, block);
+    if State::get(env).groups[&group].pending == 0 {
+        enqueue(env, queue, Instant::now(), Work { block, group: None });
+        return;
+    }
+    dispatch_retain(env, group);
+    let group_obj = State::get(env).groups.get_mut(&group).unwrap();
+    group_obj.notify.push((queue, block));
+}
+
+pub const CONSTANTS: ConstantExports = &[(
+    "__dispatch_main_q",
+    // dispatch_get_main_queue() is a macro that gets the address of this.
+    HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+        new_object(mem, MAGIC_MAIN_QUEUE).cast().cast_const()
+    }),
+)];
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(dispatch_get_main_queue()),
+    export_c_func!(dispatch_get_global_queue(_, _)),
+    export_c_func!(dispatch_async(_, _)),
+    export_c_func!(dispatch_sync(_, _)),
+    export_c_func!(dispatch_time(_, _)),
+    export_c_func!(dispatch_after(_, _, _)),
+    export_c_func!(dispatch_once(_, _)),
+    export_c_func!(dispatch_retain(_)),
+    export_c_func!(dispatch_release(_)),
+    export_c_func!(dispatch_semaphore_create(_)),
+    export_c_func!(dispatch_semaphore_wait(_, _)),
+    export_c_func!(dispatch_semaphore_signal(_)),
+    export_c_func!(dispatch_group_create()),
+    export_c_func!(dispatch_group_enter(_)),
+    export_c_func!(dispatch_group_leave(_)),
+    export_c_func!(dispatch_group_async(_, _, _)),
+    export_c_func!(dispatch_group_wait(_, _)),
+    export_c_func!(dispatch_group_notify(_, _, _)),
+];
@@ -12,9 +12,28 @@ use crate::Environment;
 use std::io::Write;
 
 pub const EPERM: i32 = 1;
+pub const ENOENT: i32 = 2;
+pub const EIO: i32 = 5;
 pub const EDEADLK: i32 = 11;
+pub const ENOMEM: i32 = 12;
 pub const EBUSY: i32 = 16;
 pub const EINVAL: i32 = 22;
+pub const EPIPE: i32 = 32;
+pub const EAGAIN: i32 = 35;
+pub const EINPROGRESS: i32 = 36;
+pub const EALREADY: i32 = 37;
+pub const ENOTSOCK: i32 = 38;
+pub const ENOPROTOOPT: i32 = 42;
+pub const EPROTONOSUPPORT: i32 = 43;
+pub const EAFNOSUPPORT: i32 = 47;
+pub const EADDRINUSE: i32 = 48;
+pub const EADDRNOTAVAIL: i32 = 49;
+pub const ECONNABORTED: i32 = 53;
+pub const ECONNRESET: i32 = 54;
+pub const EISCONN: i32 = 56;
+pub const ENOTCONN: i32 = 57;
+pub const ETIMEDOUT: i32 = 60;
+pub const ECONNREFUSED: i32 = 61;
 
 #[derive(Default)]
 pub struct State {
@@ -28,7 +47,7 @@ impl State {
     ) -> MutPtr<i32> {
         *self.errnos.entry(thread).or_insert_with(|| {
             log!(
-                "TODO: errno accessed on thread {} (will always be 0)",
+                "TODO: errno accessed on thread {} (only some functions set it)",
                 thread
             );
             mem.alloc_and_write(0i32)
@@ -42,6 +61,12 @@ fn __error(env: &mut Environment) -> MutPtr<i32> {
         .errno_for_thread(&mut env.mem, env.current_thread)
 }
 
+/// Set `errno` for the current thread.
+pub fn set_errno(env: &mut Environment, value: i32) {
+    let ptr = __error(env);
+    env.mem.write(ptr, value);
+}
+
 fn perror(env: &mut Environment, s: ConstPtr<u8>) {
     // TODO: errno mapping
     let errno_msg = "<TODO: errno>\n";
@@ -38,6 +38,9 @@ fn mach_timebase_info(
 /// The result of this function, multiplied by the constant from
 /// [mach_timebase_info], should be the absolute time in nanoseconds.
 /// The absolute time is a monotonic clock with an arbitrary starting point.
+///
+/// This is based on the host's monotonic clock, like timers and
+/// `dispatch_time()`, so neither the CPU throttle nor turbo mode affect it.
 fn mach_absolute_time(env: &mut Environment) -> u64 {
     let now = Instant::now();
     now.duration_since(env.startup_time)
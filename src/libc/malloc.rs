@@ -0,0 +1,104 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `malloc/malloc.h`
+//!
+//! touchHLE has only one allocator, so there is only one zone, and the zone
+//! functions just forward to the `stdlib.h` ones.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::errno::{set_errno, EINVAL};
+use crate::libc::stdlib::{calloc, free, malloc, realloc};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr};
+use crate::Environment;
+
+/// Size of Apple's `malloc_zone_t`, which is a table of function pointers plus
+/// a few other fields. Ours is all zeroes apart from the name.
+const MALLOC_ZONE_T_SIZE: GuestUSize = 14 * 4;
+/// Offset of `const char *zone_name` within `malloc_zone_t`.
+const ZONE_NAME_OFFSET: GuestUSize = 9 * 4;
+
+#[derive(Default)]
+pub struct State {
+    /// `malloc_zone_t*`
+    default_zone: Option<MutVoidPtr>,
+}
+
+fn malloc_size(env: &mut Environment, ptr: ConstVoidPtr) -> GuestUSize {
+    // Pointers not from malloc have a size of zero.
+    env.mem.allocation_size(ptr).unwrap_or(0)
+}
+
+fn malloc_good_size(_env: &mut Environment, size: GuestUSize) -> GuestUSize {
+    Mem::rounded_alloc_size(size)
+}
+
+fn malloc_default_zone(env: &mut Environment) -> MutVoidPtr {
+    if let Some(zone) = env.libc_state.malloc.default_zone {
+        return zone;
+    }
+    let zone = env.mem.alloc(MALLOC_ZONE_T_SIZE);
+    let name = env.mem.alloc_and_write_cstr(b"DefaultMallocZone");
+    let name_field: MutPtr<ConstPtr<u8>> = (zone.cast::<u8>() + ZONE_NAME_OFFSET).cast();
+    env.mem.write(name_field, name.cast_const());
+    env.libc_state.malloc.default_zone = Some(zone);
+    zone
+}
+
+/// Not actually part of `malloc/malloc.h` on Apple platforms, but some code
+/// ported from other platforms expects it.
+fn memalign(env: &mut Environment, alignment: GuestUSize, size: GuestUSize) -> MutVoidPtr {
+    if !alignment.is_power_of_two() {
+        set_errno(env, EINVAL);
+        return Ptr::null();
+    }
+    env.mem.alloc_aligned(size, alignment)
+}
+
+fn malloc_zone_from_ptr(env: &mut Environment, ptr: ConstVoidPtr) -> MutVoidPtr {
+    if env.mem.allocation_size(ptr).is_some() {
+        malloc_default_zone(env)
+    } else {
+        Ptr::null()
+    }
+}
+
+fn malloc_zone_malloc(env: &mut Environment, _zone: MutVoidPtr, size: GuestUSize) -> MutVoidPtr {
+    malloc(env, size)
+}
+
+fn malloc_zone_calloc(
+    env: &mut Environment,
+    _zone: MutVoidPtr,
+    count: GuestUSize,
+    size: GuestUSize,
+) -> MutVoidPtr {
+    calloc(env, count, size)
+}
+
+fn malloc_zone_realloc(
+    env: &mut Environment,
+    _zone: MutVoidPtr,
+    ptr: MutVoidPtr,
+    size: GuestUSize,
+) -> MutVoidPtr {
+    realloc(env, ptr, size)
+}
+
+fn malloc_zone_free(env: &mut Environment, _zone: MutVoidPtr, ptr: MutVoidPtr) {
+    free(env, ptr)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(malloc_size(_)),
+    export_c_func!(malloc_good_size(_)),
+    export_c_func!(malloc_default_zone()),
+    export_c_func!(memalign(_, _)),
+    export_c_func!(malloc_zone_from_ptr(_)),
+    export_c_func!(malloc_zone_malloc(_, _)),
+    export_c_func!(malloc_zone_calloc(_, _, _)),
+    export_c_func!(malloc_zone_realloc(_, _, _)),
+    export_c_func!(malloc_zone_free(_, _)),
+];
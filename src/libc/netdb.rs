@@ -0,0 +1,279 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `netdb.h` (name resolution)
+//!
+//! Names are resolved using the host's resolver.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::sys::socket::{
+    alloc_sockaddr, socklen_t, AF_INET, AF_INET6, AF_UNSPEC, IPPROTO_TCP, IPPROTO_UDP, SOCK_DGRAM,
+    SOCK_STREAM,
+};
+use crate::mem::{ConstPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::Environment;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+
+const AI_PASSIVE: i32 = 0x1;
+const AI_CANONNAME: i32 = 0x2;
+const AI_NUMERICHOST: i32 = 0x4;
+const AI_NUMERICSERV: i32 = 0x1000;
+
+const EAI_FAMILY: i32 = 5;
+const EAI_NONAME: i32 = 8;
+const EAI_SERVICE: i32 = 9;
+const EAI_SOCKTYPE: i32 = 10;
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct addrinfo {
+    ai_flags: i32,
+    ai_family: i32,
+    ai_socktype: i32,
+    ai_protocol: i32,
+    ai_addrlen: socklen_t,
+    ai_canonname: MutPtr<u8>,
+    ai_addr: MutVoidPtr,
+    ai_next: MutPtr<addrinfo>,
+}
+unsafe impl SafeRead for addrinfo {}
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct hostent {
+    h_name: MutPtr<u8>,
+    h_aliases: MutPtr<MutPtr<u8>>,
+    h_addrtype: i32,
+    h_length: i32,
+    h_addr_list: MutPtr<MutPtr<u8>>,
+}
+unsafe impl SafeRead for hostent {}
+
+#[derive(Default)]
+pub struct State {
+    /// The allocations making up the result of the last `gethostbyname` call,
+    /// which stays valid until the next call.
+    hostent_allocations: Vec<MutVoidPtr>,
+}
+
+/// Resolve a host name using the host's resolver. IPv4 addresses come first.
+fn resolve(name: &str, numeric_only: bool) -> Option<Vec<IpAddr>> {
+    if let Ok(addr) = name.parse::<IpAddr>() {
+        return Some(vec![addr]);
+    }
+    if numeric_only {
+        return None;
+    }
+    let mut addrs: Vec<IpAddr> = (name, 0)
+        .to_socket_addrs()
+        .ok()?
+        .map(|addr| addr.ip())
+        .collect();
+    addrs.sort_by_key(|addr| addr.is_ipv6());
+    addrs.dedup();
+    if addrs.is_empty() {
+        None
+    } else {
+        Some(addrs)
+    }
+}
+
+fn getaddrinfo(
+    env: &mut Environment,
+    hostname: ConstPtr<u8>,
+    servname: ConstPtr<u8>,
+    hints: ConstPtr<addrinfo>,
+    res: MutPtr<MutPtr<addrinfo>>,
+) -> i32 {
+    let (flags, family, socktype, protocol) = if hints.is_null() {
+        (0, AF_UNSPEC, 0, 0)
+    } else {
+        let hints = env.mem.read(hints);
+        (
+            hints.ai_flags,
+            hints.ai_family,
+            hints.ai_socktype,
+            hints.ai_protocol,
+        )
+    };
+    let hostname =
+        (!hostname.is_null()).then(|| env.mem.cstr_at_utf8(hostname).unwrap().to_string());
+    let servname =
+        (!servname.is_null()).then(|| env.mem.cstr_at_utf8(servname).unwrap().to_string());
+    log_dbg!(
+        "getaddrinfo({:?}, {:?}, flags {:#x}, family {}, socktype {}, protocol {})",
+        hostname,
+        servname,
+        flags,
+        family,
+        socktype,
+        protocol
+    );
+
+    if ![AF_UNSPEC, AF_INET, AF_INET6].contains(&family) {
+        return EAI_FAMILY;
+    }
+    if ![0, SOCK_STREAM, SOCK_DGRAM].contains(&socktype) {
+        return EAI_SOCKTYPE;
+    }
+    if hostname.is_none() && servname.is_none() {
+        return EAI_NONAME;
+    }
+
+    let port: u16 = match servname.as_deref() {
+        None => 0,
+        Some(servname) => match servname.parse() {
+            Ok(port) => port,
+            Err(_) => {
+                if flags & AI_NUMERICSERV == 0 {
+                    log!("TODO: getaddrinfo() service name {:?}", servname);
+                }
+                return EAI_SERVICE;
+            }
+        },
+    };
+
+    let addrs = match hostname.as_deref() {
+        Some(hostname) => {
+            let Some(addrs) = resolve(hostname, flags & AI_NUMERICHOST != 0) else {
+                log!("getaddrinfo(): could not resolve {:?}", hostname);
+                return EAI_NONAME;
+            };
+            addrs
+        }
+        None if flags & AI_PASSIVE != 0 => vec![
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        ],
+        None => vec![
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ],
+    };
+    let addrs: Vec<IpAddr> = addrs
+        .into_iter()
+        .filter(|addr| match family {
+            AF_INET => addr.is_ipv4(),
+            AF_INET6 => addr.is_ipv6(),
+            _ => true,
+        })
+        .collect();
+    if addrs.is_empty() {
+        return EAI_NONAME;
+    }
+
+    // When no socket type is specified, there is a result for each one.
+    let types: &[(i32, i32)] = match socktype {
+        SOCK_STREAM => &[(SOCK_STREAM, IPPROTO_TCP)],
+        SOCK_DGRAM => &[(SOCK_DGRAM, IPPROTO_UDP)],
+        _ => &[(SOCK_STREAM, IPPROTO_TCP), (SOCK_DGRAM, IPPROTO_UDP)],
+    };
+
+    // Build the linked list back to front.
+    let mut next: MutPtr<addrinfo> = Ptr::null();
+    for (i, &addr) in addrs.iter().enumerate().rev() {
+        for &(socktype, default_protocol) in types.iter().rev() {
+            let (ai_addr, ai_addrlen) = alloc_sockaddr(&mut env.mem, SocketAddr::new(addr, port));
+            // Only the first result has the canonical name.
+            let ai_canonname = match hostname.as_deref() {
+                Some(hostname) if flags & AI_CANONNAME != 0 && i == 0 && socktype == types[0].0 => {
+                    env.mem.alloc_and_write_cstr(hostname.as_bytes())
+                }
+                _ => Ptr::null(),
+            };
+            next = env.mem.alloc_and_write(addrinfo {
+                ai_flags: flags,
+                ai_family: if addr.is_ipv4() { AF_INET } else { AF_INET6 },
+                ai_socktype: socktype,
+                ai_protocol: if protocol != 0 {
+                    protocol
+                } else {
+                    default_protocol
+                },
+                ai_addrlen,
+                ai_canonname,
+                ai_addr,
+                ai_next: next,
+            });
+        }
+    }
+    env.mem.write(res, next);
+    0 // success
+}
+
+fn freeaddrinfo(env: &mut Environment, ai: MutPtr<addrinfo>) {
+    let mut ai = ai;
+    while !ai.is_null() {
+        let addrinfo {
+            ai_canonname,
+            ai_addr,
+            ai_next,
+            ..
+        } = env.mem.read(ai);
+        if !ai_canonname.is_null() {
+            env.mem.free(ai_canonname.cast());
+        }
+        env.mem.free(ai_addr);
+        env.mem.free(ai.cast());
+        ai = ai_next;
+    }
+}
+
+/// Legacy IPv4-only resolution. The result is only valid until the next call.
+fn gethostbyname(env: &mut Environment, name: ConstPtr<u8>) -> MutPtr<hostent> {
+    for allocation in std::mem::take(&mut env.libc_state.netdb.hostent_allocations) {
+        env.mem.free(allocation);
+    }
+
+    let name_str = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    let addrs: Vec<Ipv4Addr> = resolve(&name_str, false)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|addr| match addr {
+            IpAddr::V4(addr) => Some(addr),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    log_dbg!("gethostbyname({:?}) => {:?}", name_str, addrs);
+    if addrs.is_empty() {
+        // TODO: set h_errno
+        return Ptr::null();
+    }
+
+    let mut allocations = Vec::new();
+    let h_name = env.mem.alloc_and_write_cstr(name_str.as_bytes());
+    allocations.push(h_name.cast());
+    let h_aliases = env.mem.alloc_and_write(Ptr::null());
+    allocations.push(h_aliases.cast());
+    let addr_count: GuestUSize = addrs.len().try_into().unwrap();
+    let h_addr_list: MutPtr<MutPtr<u8>> = env.mem.alloc((addr_count + 1) * 4).cast();
+    allocations.push(h_addr_list.cast());
+    for (i, addr) in addrs.iter().enumerate() {
+        // The address is in network byte order.
+        let addr_ptr: MutPtr<u8> = env.mem.alloc(4).cast();
+        env.mem
+            .bytes_at_mut(addr_ptr, 4)
+            .copy_from_slice(&addr.octets());
+        allocations.push(addr_ptr.cast());
+        env.mem.write(h_addr_list + i as GuestUSize, addr_ptr);
+    }
+    env.mem.write(h_addr_list + addr_count, Ptr::null());
+    let result = env.mem.alloc_and_write(hostent {
+        h_name,
+        h_aliases,
+        h_addrtype: AF_INET,
+        h_length: 4,
+        h_addr_list,
+    });
+    allocations.push(result.cast());
+    env.libc_state.netdb.hostent_allocations = allocations;
+    result
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(getaddrinfo(_, _, _, _)),
+    export_c_func!(freeaddrinfo(_)),
+    export_c_func!(gethostbyname(_)),
+];
@@ -10,6 +10,8 @@ pub mod stat;
 use crate::abi::DotDotDot;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::fs::{GuestFile, GuestOpenOptions, GuestPath};
+use crate::libc::errno::{set_errno, EINVAL};
+use crate::libc::sys::socket::{self, Socket};
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, MutPtr, MutVoidPtr, Ptr};
 use crate::Environment;
 use std::io::{Read, Seek, SeekFrom, Write};
@@ -17,14 +19,41 @@ use std::io::{Read, Seek, SeekFrom, Write};
 #[derive(Default)]
 pub struct State {
     /// File descriptors _other than stdin, stdout, and stderr_
-    files: Vec<Option<PosixFileHostObject>>,
+    files: Vec<Option<OpenFile>>,
 }
 impl State {
     fn file_for_fd(&mut self, fd: FileDescriptor) -> Option<&mut PosixFileHostObject> {
-        self.files
-            .get_mut(fd_to_file_idx(fd))
-            .and_then(|file_or_none| file_or_none.as_mut())
+        match self.files.get_mut(fd_to_file_idx(fd)) {
+            Some(Some(OpenFile::File(file))) => Some(file),
+            _ => None,
+        }
+    }
+    pub(super) fn socket_for_fd(&mut self, fd: FileDescriptor) -> Option<&mut Socket> {
+        match self.files.get_mut(fd_to_file_idx(fd)) {
+            Some(Some(OpenFile::Socket(socket))) => Some(socket),
+            _ => None,
+        }
+    }
+    fn add_open_file(&mut self, open_file: OpenFile) -> FileDescriptor {
+        let idx = if let Some(free_idx) = self.files.iter().position(|f| f.is_none()) {
+            self.files[free_idx] = Some(open_file);
+            free_idx
+        } else {
+            let idx = self.files.len();
+            self.files.push(Some(open_file));
+            idx
+        };
+        file_idx_to_fd(idx)
     }
+    pub(super) fn add_socket(&mut self, socket: Socket) -> FileDescriptor {
+        self.add_open_file(OpenFile::Socket(socket))
+    }
+}
+
+/// Sockets share the file descriptor space with files.
+enum OpenFile {
+    File(PosixFileHostObject),
+    Socket(Socket),
 }
 
 struct PosixFileHostObject {
@@ -148,21 +177,9 @@ pub fn open_direct(env: &mut Environment, path: ConstPtr<u8>, flags: i32) -> Fil
                 reached_eof: false,
             };
 
-            let idx = if let Some(free_idx) = env
-                .libc_state
+            env.libc_state
                 .posix_io
-                .files
-                .iter()
-                .position(|f| f.is_none())
-            {
-                env.libc_state.posix_io.files[free_idx] = Some(host_object);
-                free_idx
-            } else {
-                let idx = env.libc_state.posix_io.files.len();
-                env.libc_state.posix_io.files.push(Some(host_object));
-                idx
-            };
-            file_idx_to_fd(idx)
+                .add_open_file(OpenFile::File(host_object))
         }
         Err(()) => {
             // TODO: set errno
@@ -189,6 +206,10 @@ pub fn read(
     buffer: MutVoidPtr,
     size: GuestUSize,
 ) -> GuestISize {
+    if env.libc_state.posix_io.socket_for_fd(fd).is_some() {
+        return socket::recv(env, fd, buffer, size, 0);
+    }
+
     // TODO: error handling for unknown fd?
     let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
 
@@ -248,6 +269,10 @@ pub fn write(
     buffer: ConstVoidPtr,
     size: GuestUSize,
 ) -> GuestISize {
+    if env.libc_state.posix_io.socket_for_fd(fd).is_some() {
+        return socket::send(env, fd, buffer, size, 0);
+    }
+
     // TODO: error handling for unknown fd?
     let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
 
@@ -327,7 +352,7 @@ pub fn close(env: &mut Environment, fd: FileDescriptor) -> i32 {
     }
 
     match env.libc_state.posix_io.files[fd_to_file_idx(fd)].take() {
-        Some(file) => {
+        Some(OpenFile::File(file)) => {
             // The actual closing of the file happens implicitly when `file`
             // falls out of scope. The return value is about whether flushing
             // succeeds.
@@ -343,6 +368,11 @@ pub fn close(env: &mut Environment, fd: FileDescriptor) -> i32 {
                 }
             }
         }
+        Some(OpenFile::Socket(_)) => {
+            // The host socket is closed when it's dropped.
+            log_dbg!("close({:?}) => 0", fd);
+            0
+        }
         None => {
             // TODO: set errno
             log!("Warning: close({:?}) failed, returning -1", fd);
@@ -434,6 +464,53 @@ fn ftruncate(env: &mut Environment, fd: FileDescriptor, len: off_t) -> i32 {
     }
 }
 
+pub const F_GETFD: i32 = 1;
+pub const F_SETFD: i32 = 2;
+pub const F_GETFL: i32 = 3;
+pub const F_SETFL: i32 = 4;
+
+pub const FD_CLOEXEC: i32 = 1;
+
+fn fcntl(env: &mut Environment, fd: FileDescriptor, cmd: i32, args: DotDotDot) -> i32 {
+    let res = match cmd {
+        // There's no exec(), so close-on-exec has no effect and the flag
+        // isn't remembered.
+        F_GETFD => 0,
+        F_SETFD => {
+            let flags: i32 = args.start().next(env);
+            if flags & !FD_CLOEXEC != 0 {
+                log!("TODO: fcntl({:?}, F_SETFD, {:#x})", fd, flags);
+            }
+            0
+        }
+        F_GETFL => match env.libc_state.posix_io.socket_for_fd(fd) {
+            Some(socket) if socket.non_blocking() => O_RDWR | O_NONBLOCK,
+            Some(_) => O_RDWR,
+            None => {
+                // TODO: remember the flags files were opened with
+                log!("TODO: fcntl({:?}, F_GETFL) on a file", fd);
+                O_RDWR
+            }
+        },
+        F_SETFL => {
+            let flags: i32 = args.start().next(env);
+            match env.libc_state.posix_io.socket_for_fd(fd) {
+                Some(socket) => socket.set_non_blocking(flags & O_NONBLOCK != 0),
+                // O_NONBLOCK has no effect on files, see open_direct().
+                None => log!("TODO: fcntl({:?}, F_SETFL, {:#x}) on a file", fd, flags),
+            }
+            0
+        }
+        _ => {
+            log!("TODO: fcntl({:?}, {:#x}), returning EINVAL", fd, cmd);
+            set_errno(env, EINVAL);
+            -1
+        }
+    };
+    log_dbg!("fcntl({:?}, {:#x}) => {:#x}", fd, cmd, res);
+    res
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(open(_, _, _)),
     export_c_func!(read(_, _, _)),
@@ -444,4 +521,5 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(chdir(_)),
     export_c_func!(flock(_, _)),
     export_c_func!(ftruncate(_, _)),
+    export_c_func!(fcntl(_, _, _)),
 ];
@@ -0,0 +1,103 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Condition variables.
+//!
+//! These have no host-side state: the threads waiting on a condition variable
+//! are tracked by the thread scheduler, keyed by the guest address.
+
+use super::mutex::{check_or_register_mutex, pthread_mutex_t};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::{ConstVoidPtr, MutPtr, Ptr, SafeRead};
+use crate::Environment;
+
+/// Apple's implementation is a 4-byte magic number followed by a 24-byte
+/// opaque region. We only have to match the size theirs has.
+#[repr(C, packed)]
+pub struct pthread_cond_t {
+    /// Magic number (must be [MAGIC_COND])
+    magic: u32,
+    _unused: [u32; 6],
+}
+unsafe impl SafeRead for pthread_cond_t {}
+
+/// Arbitrarily-chosen magic number for `pthread_cond_t` (not Apple's).
+const MAGIC_COND: u32 = u32::from_be_bytes(*b"COND");
+/// Magic number used by `PTHREAD_COND_INITIALIZER`. This is part of the ABI!
+const MAGIC_COND_STATIC: u32 = 0x3CB0B1BB;
+
+fn pthread_cond_init(
+    env: &mut Environment,
+    cond: MutPtr<pthread_cond_t>,
+    // Attributes only affect process-shared condition variables and which
+    // clock timed waits use, neither of which matter here.
+    _attr: ConstVoidPtr, // const pthread_condattr_t *
+) -> i32 {
+    env.mem.write(
+        cond,
+        pthread_cond_t {
+            magic: MAGIC_COND,
+            _unused: Default::default(),
+        },
+    );
+    0 // success
+}
+
+fn check_or_register_cond(env: &mut Environment, cond: MutPtr<pthread_cond_t>) {
+    let magic: u32 = env.mem.read(cond.cast());
+    if magic == MAGIC_COND_STATIC {
+        log_dbg!(
+            "Detected statically-initialized condition variable at {:?}.",
+            cond
+        );
+        pthread_cond_init(env, cond, Ptr::null());
+    } else {
+        // See check_or_register_mutex.
+        assert_eq!(magic, MAGIC_COND);
+    }
+}
+
+fn pthread_cond_wait(
+    env: &mut Environment,
+    cond: MutPtr<pthread_cond_t>,
+    mutex: MutPtr<pthread_mutex_t>,
+) -> i32 {
+    check_or_register_cond(env, cond);
+    check_or_register_mutex(env, mutex);
+    let mutex_id = env.mem.read(mutex).mutex_id;
+    env.wait_on_condition(cond, mutex_id).err().unwrap_or(0)
+}
+
+fn pthread_cond_signal(env: &mut Environment, cond: MutPtr<pthread_cond_t>) -> i32 {
+    check_or_register_cond(env, cond);
+    env.signal_condition(cond, /* broadcast: */ false);
+    0 // success
+}
+
+fn pthread_cond_broadcast(env: &mut Environment, cond: MutPtr<pthread_cond_t>) -> i32 {
+    check_or_register_cond(env, cond);
+    env.signal_condition(cond, /* broadcast: */ true);
+    0 // success
+}
+
+fn pthread_cond_destroy(env: &mut Environment, cond: MutPtr<pthread_cond_t>) -> i32 {
+    check_or_register_cond(env, cond);
+    env.mem.write(
+        cond,
+        pthread_cond_t {
+            magic: 0,
+            _unused: Default::default(),
+        },
+    );
+    0 // success
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(pthread_cond_init(_, _)),
+    export_c_func!(pthread_cond_wait(_, _)),
+    export_c_func!(pthread_cond_signal(_)),
+    export_c_func!(pthread_cond_broadcast(_)),
+    export_c_func!(pthread_cond_destroy(_)),
+];
@@ -5,8 +5,9 @@
  */
 //! Thread-specific data keys.
 
-use crate::abi::GuestFunction;
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::errno::EINVAL;
 use crate::mem::{ConstVoidPtr, MutPtr, MutVoidPtr, Ptr};
 use crate::{Environment, ThreadId};
 use std::collections::HashMap;
@@ -15,8 +16,8 @@ use std::collections::HashMap;
 pub struct State {
     /// The `pthread_key_t` value, with 1 subtracted, is the index into this
     /// vector. The tuple contains the map of thread-specific data pointers plus
-    /// the destructor pointer.
-    keys: Vec<(HashMap<ThreadId, MutVoidPtr>, GuestFunction)>,
+    /// the destructor pointer. Deleted keys are [None] (keys aren't reused).
+    keys: Vec<Option<(HashMap<ThreadId, MutVoidPtr>, GuestFunction)>>,
 }
 
 fn get_state(env: &mut Environment) -> &mut State {
@@ -25,6 +26,20 @@ fn get_state(env: &mut Environment) -> &mut State {
 
 type pthread_key_t = u32;
 
+/// Number of times destructors are run on thread exit if they keep setting new
+/// values, matching Apple's `PTHREAD_DESTRUCTOR_ITERATIONS`.
+const PTHREAD_DESTRUCTOR_ITERATIONS: u32 = 4;
+
+/// Get the data for a key. Use of an invalid key is undefined, so panicking is
+/// fine.
+fn get_key(
+    env: &mut Environment,
+    key: pthread_key_t,
+) -> &mut (HashMap<ThreadId, MutVoidPtr>, GuestFunction) {
+    let idx: usize = key.checked_sub(1).unwrap().try_into().unwrap();
+    get_state(env).keys[idx].as_mut().unwrap()
+}
+
 fn pthread_key_create(
     env: &mut Environment,
     key_ptr: MutPtr<pthread_key_t>,
@@ -32,16 +47,28 @@ fn pthread_key_create(
 ) -> i32 {
     let idx = get_state(env).keys.len();
     let key: pthread_key_t = (idx + 1).try_into().unwrap();
-    get_state(env).keys.push((HashMap::new(), destructor));
+    get_state(env).keys.push(Some((HashMap::new(), destructor)));
     env.mem.write(key_ptr, key);
     0 // success
 }
 
+fn pthread_key_delete(env: &mut Environment, key: pthread_key_t) -> i32 {
+    let Some(idx) = key.checked_sub(1) else {
+        return EINVAL;
+    };
+    match get_state(env).keys.get_mut(idx as usize) {
+        // Destructors are not called when a key is deleted.
+        Some(key_data @ Some(_)) => {
+            *key_data = None;
+            0 // success
+        }
+        _ => EINVAL,
+    }
+}
+
 fn pthread_getspecific(env: &mut Environment, key: pthread_key_t) -> MutVoidPtr {
-    // Use of invalid key is undefined, panicking is fine.
-    let idx: usize = key.checked_sub(1).unwrap().try_into().unwrap();
     let current_thread = env.current_thread;
-    get_state(env).keys[idx]
+    get_key(env, key)
         .0
         .get(&current_thread)
         .copied()
@@ -50,16 +77,58 @@ fn pthread_getspecific(env: &mut Environment, key: pthread_key_t) -> MutVoidPtr
 
 fn pthread_setspecific(env: &mut Environment, key: pthread_key_t, value: ConstVoidPtr) -> i32 {
     // TODO: return error instead of panicking if key is invalid?
-    let idx: usize = key.checked_sub(1).unwrap().try_into().unwrap();
     let current_thread = env.current_thread;
-    get_state(env).keys[idx]
-        .0
-        .insert(current_thread, value.cast_mut());
+    get_key(env, key).0.insert(current_thread, value.cast_mut());
     0 // success
 }
 
+/// Call the destructors for the current thread's non-NULL thread-specific data
+/// values and forget them. This should be called when a thread exits.
+pub fn run_destructors(env: &mut Environment) {
+    let current_thread = env.current_thread;
+    // Destructors can set new values, so this may need to be repeated.
+    for _ in 0..PTHREAD_DESTRUCTOR_ITERATIONS {
+        let to_call: Vec<(GuestFunction, MutVoidPtr)> = get_state(env)
+            .keys
+            .iter_mut()
+            .flatten()
+            .filter_map(|(values, destructor)| {
+                // The value is set to NULL before calling the destructor.
+                let value = values.remove(&current_thread)?;
+                (!value.is_null() && !destructor.to_ptr().is_null()).then_some((*destructor, value))
+            })
+            .collect();
+        if to_call.is_empty() {
+            return;
+        }
+        for (destructor, value) in to_call {
+            log_dbg!(
+                "Calling thread-specific data destructor {:?} with {:?} for thread {}",
+                destructor,
+                value,
+                current_thread
+            );
+            () = destructor.call_from_host(env, (value,));
+        }
+    }
+    let mut leftover = false;
+    for (values, _) in get_state(env).keys.iter_mut().flatten() {
+        leftover |= values
+            .remove(&current_thread)
+            .is_some_and(|value| !value.is_null());
+    }
+    if leftover {
+        log!(
+            "Warning: thread {} still has thread-specific data after {} rounds of destructors, ignoring.",
+            current_thread,
+            PTHREAD_DESTRUCTOR_ITERATIONS
+        );
+    }
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(pthread_key_create(_, _)),
+    export_c_func!(pthread_key_delete(_)),
     export_c_func!(pthread_getspecific(_)),
     export_c_func!(pthread_setspecific(_, _)),
 ];
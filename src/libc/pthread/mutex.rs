@@ -33,7 +33,7 @@ pub struct pthread_mutex_t {
     /// Magic number (must be [MAGIC_MUTEX])
     magic: u32,
     /// Unique mutex identifier, used in matching the mutex to it's host object.
-    mutex_id: MutexId,
+    pub(super) mutex_id: MutexId,
 }
 unsafe impl SafeRead for pthread_mutex_t {}
 
@@ -108,7 +108,7 @@ pub fn pthread_mutex_init(
     0 // success
 }
 
-fn check_or_register_mutex(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) {
+pub(super) fn check_or_register_mutex(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) {
     let magic: u32 = env.mem.read(mutex.cast());
     // This is a statically-initialized mutex, we need to register it, and
     // change the magic number in the process.
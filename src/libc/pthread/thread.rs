@@ -217,6 +217,17 @@ fn pthread_join(env: &mut Environment, thread: pthread_t, retval: MutPtr<MutVoid
     env.join_with_thread(joinee_thread, retval);
     0
 }
+fn pthread_detach(env: &mut Environment, thread: pthread_t) -> i32 {
+    let host_obj = State::get(env).threads.get_mut(&thread).unwrap();
+    if host_obj._attr.detachstate == PTHREAD_CREATE_DETACHED {
+        log_dbg!("Thread attempted to detach already detached thread, returning EINVAL!");
+        return EINVAL;
+    }
+    // TODO: Joining is the only thing this affects at the moment. The thread's
+    // resources are already freed when it exits regardless.
+    host_obj._attr.detachstate = PTHREAD_CREATE_DETACHED;
+    0 // success
+}
 fn pthread_setcanceltype(_env: &mut Environment, _type: i32, _oldtype: MutPtr<i32>) -> i32 {
     // TODO
     0
@@ -238,6 +249,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(pthread_create(_, _, _, _)),
     export_c_func!(pthread_self()),
     export_c_func!(pthread_join(_, _)),
+    export_c_func!(pthread_detach(_)),
     export_c_func!(pthread_setcanceltype(_, _)),
     export_c_func!(pthread_mach_thread_np(_)),
 ];
@@ -8,8 +8,8 @@
 //! We don't have a real implementation for this right now. It could be quite
 //! tricky to write one, considering that we would need to unwind through host
 //! code, and somehow do so selectively since we have a mix of stack frames from
-//! different guest threads. For the moment, we simply pray the app never throws
-//! exceptions.
+//! different guest threads. For the moment, we simply pray the app never uses
+//! these. (Exceptions don't use them, see [super::unwind].)
 //!
 //! Note that `setjmp` and `longjmp` are defined as macros in the C standard,
 //! but it seems like the implementation of these on iPhone OS uses real
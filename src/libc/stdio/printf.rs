@@ -22,6 +22,18 @@ use std::io::Write;
 const INTEGER_SPECIFIERS: [u8; 6] = [b'd', b'i', b'o', b'u', b'x', b'X'];
 const FLOAT_SPECIFIERS: [u8; 3] = [b'f', b'e', b'g'];
 
+#[derive(Copy, Clone)]
+enum LengthModifier {
+    /// `hh`
+    Char,
+    /// `h`
+    Short,
+    /// `l`, `z` or `t`
+    Long,
+    /// `ll`, `q` or `j`
+    LongLong,
+}
+
 /// String formatting implementation for `printf` and `NSLog` function families.
 ///
 /// `NS_LOG` is [true] for the `NSLog` format string type, or [false] for the
@@ -50,18 +62,31 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
             continue;
         }
 
-        let pad_char = if get_format_char(&env.mem, format_char_idx) == b'0' {
+        let mut left_justify = false;
+        let mut plus_sign = false;
+        let mut space_sign = false;
+        let mut alternate_form = false;
+        let mut pad_char = ' ';
+        loop {
+            match get_format_char(&env.mem, format_char_idx) {
+                b'-' => left_justify = true,
+                b'+' => plus_sign = true,
+                b' ' => space_sign = true,
+                b'#' => alternate_form = true,
+                b'0' => pad_char = '0',
+                _ => break,
+            }
             format_char_idx += 1;
-            '0'
-        } else {
-            ' '
-        };
+        }
 
-        let pad_width = if get_format_char(&env.mem, format_char_idx) == b'*' {
+        let mut pad_width = if get_format_char(&env.mem, format_char_idx) == b'*' {
             let pad_width = args.next::<i32>(env);
-            assert!(pad_width >= 0); // TODO: Implement right-padding
             format_char_idx += 1;
-            pad_width
+            // A negative width is taken as a '-' flag.
+            if pad_width < 0 {
+                left_justify = true;
+            }
+            pad_width.saturating_abs()
         } else {
             let mut pad_width: i32 = 0;
             while let c @ b'0'..=b'9' = get_format_char(&env.mem, format_char_idx) {
@@ -73,21 +98,51 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
 
         let precision = if get_format_char(&env.mem, format_char_idx) == b'.' {
             format_char_idx += 1;
-            let mut precision = 0;
-            while let c @ b'0'..=b'9' = get_format_char(&env.mem, format_char_idx) {
-                precision = precision * 10 + (c - b'0') as usize;
+            if get_format_char(&env.mem, format_char_idx) == b'*' {
                 format_char_idx += 1;
+                // A negative precision is taken as if it were omitted.
+                usize::try_from(args.next::<i32>(env)).ok()
+            } else {
+                let mut precision = 0;
+                while let c @ b'0'..=b'9' = get_format_char(&env.mem, format_char_idx) {
+                    precision = precision * 10 + (c - b'0') as usize;
+                    format_char_idx += 1;
+                }
+                Some(precision)
             }
-            Some(precision)
         } else {
             None
         };
 
-        let length_modifier = if get_format_char(&env.mem, format_char_idx) == b'l' {
-            format_char_idx += 1;
-            Some(b'l')
-        } else {
-            None
+        let length_modifier = match get_format_char(&env.mem, format_char_idx) {
+            b'h' => {
+                format_char_idx += 1;
+                if get_format_char(&env.mem, format_char_idx) == b'h' {
+                    format_char_idx += 1;
+                    Some(LengthModifier::Char)
+                } else {
+                    Some(LengthModifier::Short)
+                }
+            }
+            b'l' => {
+                format_char_idx += 1;
+                if get_format_char(&env.mem, format_char_idx) == b'l' {
+                    format_char_idx += 1;
+                    Some(LengthModifier::LongLong)
+                } else {
+                    Some(LengthModifier::Long)
+                }
+            }
+            b'q' | b'j' => {
+                format_char_idx += 1;
+                Some(LengthModifier::LongLong)
+            }
+            // On a 32-bit system these are all the same size as int.
+            b'z' | b't' => {
+                format_char_idx += 1;
+                Some(LengthModifier::Long)
+            }
+            _ => None,
         };
 
         let specifier = get_format_char(&env.mem, format_char_idx);
@@ -101,17 +156,28 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
 
         if precision.is_some() {
             assert!(
-                INTEGER_SPECIFIERS.contains(&specifier) || FLOAT_SPECIFIERS.contains(&specifier)
+                INTEGER_SPECIFIERS.contains(&specifier)
+                    || FLOAT_SPECIFIERS.contains(&specifier.to_ascii_lowercase())
+                    || specifier == b's'
             )
         }
 
+        // Left-justified fields are padded after the conversion, so the
+        // individual conversions only need to handle right-justification.
+        let field_start = res.len();
+        let field_width = pad_width as usize;
+        if left_justify {
+            pad_char = ' ';
+            pad_width = 0;
+        }
+
         match specifier {
             // Integer specifiers
             b'c' => {
                 // TODO: support length modifier
                 assert!(length_modifier.is_none());
                 let c: u8 = args.next(env);
-                assert!(pad_char == ' ' && pad_width == 0); // TODO
+                res.resize(res.len() + (pad_width as usize).saturating_sub(1), b' ');
                 res.push(c);
             }
             // Apple extension? Seemingly works in both NSLog and printf.
@@ -129,40 +195,78 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
                 // TODO: support length modifier
                 assert!(length_modifier.is_none());
                 let c_string: ConstPtr<u8> = args.next(env);
-                assert!(pad_char == ' ' && pad_width == 0); // TODO
-                if !c_string.is_null() {
-                    res.extend_from_slice(env.mem.cstr_at(c_string));
+                let string = if !c_string.is_null() {
+                    env.mem.cstr_at(c_string)
                 } else {
-                    res.extend_from_slice("(null)".as_bytes());
-                }
+                    "(null)".as_bytes()
+                };
+                let string = &string[..precision.unwrap_or(usize::MAX).min(string.len())];
+                res.resize(
+                    res.len() + (pad_width as usize).saturating_sub(string.len()),
+                    b' ',
+                );
+                res.extend_from_slice(string);
             }
-            b'd' | b'i' | b'u' => {
-                // Note: on 32-bit system int and long are i32,
-                // so length_modifier is ignored
-                let int: i64 = if specifier == b'u' {
-                    let uint: u32 = args.next(env);
-                    uint.into()
+            b'd' | b'i' | b'u' | b'o' | b'x' | b'X' => {
+                let (negative, magnitude): (bool, u64) = if matches!(specifier, b'd' | b'i') {
+                    let int: i64 = match length_modifier {
+                        Some(LengthModifier::LongLong) => args.next(env),
+                        Some(LengthModifier::Short) => args.next::<i16>(env).into(),
+                        Some(LengthModifier::Char) => args.next::<i8>(env).into(),
+                        // Note: on 32-bit system int and long are i32
+                        Some(LengthModifier::Long) | None => args.next::<i32>(env).into(),
+                    };
+                    (int < 0, int.unsigned_abs())
                 } else {
-                    let int: i32 = args.next(env);
-                    int.into()
+                    let uint: u64 = match length_modifier {
+                        Some(LengthModifier::LongLong) => args.next(env),
+                        Some(LengthModifier::Short) => args.next::<u16>(env).into(),
+                        Some(LengthModifier::Char) => args.next::<u8>(env).into(),
+                        Some(LengthModifier::Long) | None => args.next::<u32>(env).into(),
+                    };
+                    (false, uint)
+                };
+
+                let mut digits = match specifier {
+                    b'o' => format!("{:o}", magnitude),
+                    b'x' => format!("{:x}", magnitude),
+                    b'X' => format!("{:X}", magnitude),
+                    _ => format!("{}", magnitude),
                 };
+                if let Some(precision) = precision {
+                    if precision == 0 && magnitude == 0 {
+                        digits.clear();
+                    } else if digits.len() < precision {
+                        digits.insert_str(0, &"0".repeat(precision - digits.len()));
+                    }
+                }
 
-                let int_with_precision = if precision.is_some_and(|value| value > 0) {
-                    format!("{:01$}", int, precision.unwrap())
+                let prefix = if negative {
+                    "-"
+                } else if matches!(specifier, b'd' | b'i') && plus_sign {
+                    "+"
+                } else if matches!(specifier, b'd' | b'i') && space_sign {
+                    " "
+                } else if alternate_form && specifier == b'o' && !digits.starts_with('0') {
+                    "0"
+                } else if alternate_form && specifier == b'x' && magnitude != 0 {
+                    "0x"
+                } else if alternate_form && specifier == b'X' && magnitude != 0 {
+                    "0X"
                 } else {
-                    format!("{}", int)
+                    ""
                 };
 
-                if pad_width > 0 {
-                    let pad_width = pad_width as usize;
-                    if pad_char == '0' && precision.is_none() {
-                        write!(&mut res, "{:0>1$}", int_with_precision, pad_width).unwrap();
-                    } else {
-                        write!(&mut res, "{:>1$}", int_with_precision, pad_width).unwrap();
-                    }
+                let padding = (pad_width as usize).saturating_sub(prefix.len() + digits.len());
+                // The 0 flag is ignored when a precision is given.
+                if pad_char == '0' && precision.is_none() {
+                    res.extend_from_slice(prefix.as_bytes());
+                    res.resize(res.len() + padding, b'0');
                 } else {
-                    res.extend_from_slice(int_with_precision.as_bytes());
+                    res.resize(res.len() + padding, b' ');
+                    res.extend_from_slice(prefix.as_bytes());
                 }
+                res.extend_from_slice(digits.as_bytes());
             }
             b'@' if NS_LOG => {
                 assert!(length_modifier.is_none());
@@ -178,39 +282,29 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
                     write!(&mut res, "(null)").unwrap();
                 }
             }
-            b'x' => {
-                // Note: on 32-bit system unsigned int and unsigned long
-                // are u32, so length_modifier is ignored
-                let uint: u32 = args.next(env);
-                res.extend_from_slice(format!("{:x}", uint).as_bytes());
-            }
-            b'X' => {
-                // Note: on 32-bit system unsigned int and unsigned long
-                // are u32, so length_modifier is ignored
-                let uint: u32 = args.next(env);
-                res.extend_from_slice(format!("{:X}", uint).as_bytes());
-            }
             b'p' => {
                 assert!(length_modifier.is_none());
                 let ptr: MutVoidPtr = args.next(env);
                 res.extend_from_slice(format!("{:?}", ptr).as_bytes());
             }
             // Float specifiers
-            b'f' => {
+            b'f' | b'F' => {
                 let float: f64 = args.next(env);
-                let pad_width = pad_width as usize;
+                let mut pad_width = pad_width as usize;
                 let precision = precision.unwrap_or(6);
-                if pad_char == '0' {
-                    res.extend_from_slice(
-                        format!("{:01$.2$}", float, pad_width, precision).as_bytes(),
-                    );
-                } else {
-                    res.extend_from_slice(
-                        format!("{:1$.2$}", float, pad_width, precision).as_bytes(),
-                    );
+                if space_sign && !plus_sign && !float.is_sign_negative() {
+                    res.push(b' ');
+                    pad_width = pad_width.saturating_sub(1);
                 }
+                let formatted = match (pad_char == '0', plus_sign) {
+                    (true, true) => format!("{:+01$.2$}", float, pad_width, precision),
+                    (true, false) => format!("{:01$.2$}", float, pad_width, precision),
+                    (false, true) => format!("{:+1$.2$}", float, pad_width, precision),
+                    (false, false) => format!("{:1$.2$}", float, pad_width, precision),
+                };
+                res.extend_from_slice(formatted.as_bytes());
             }
-            b'e' => {
+            b'e' | b'E' => {
                 let float: f64 = args.next(env);
                 let pad_width = pad_width as usize;
                 let precision = precision.unwrap_or(6);
@@ -238,7 +332,7 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
                     );
                 }
             }
-            b'g' => {
+            b'g' | b'G' => {
                 let float: f64 = args.next(env);
                 let pad_width = pad_width as usize;
 
@@ -332,6 +426,14 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
                 format_char_idx
             ),
         }
+
+        if matches!(specifier, b'F' | b'E' | b'G') {
+            res[field_start..].make_ascii_uppercase();
+        }
+        if left_justify {
+            let field_len = res.len() - field_start;
+            res.resize(field_start + field_width.max(field_len), b' ');
+        }
     }
 
     log_dbg!("=> {:?}", std::str::from_utf8(&res));
@@ -377,15 +479,16 @@ fn vsnprintf(
     );
 
     let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), arg);
-    let middle = if ((n - 1) as usize) < res.len() {
-        &res[..(n - 1) as usize]
-    } else {
-        &res[..]
-    };
-
-    let dest_slice = env.mem.bytes_at_mut(dest, n);
-    for (i, &byte) in middle.iter().chain(b"\0".iter()).enumerate() {
-        dest_slice[i] = byte;
+
+    // Nothing is written if the size is zero, and the destination may even be
+    // NULL in that case. This is used to find out how big the buffer needs to
+    // be.
+    if n > 0 {
+        let middle = &res[..((n - 1) as usize).min(res.len())];
+        let dest_slice = env.mem.bytes_at_mut(dest, n);
+        for (i, &byte) in middle.iter().chain(b"\0".iter()).enumerate() {
+            dest_slice[i] = byte;
+        }
     }
 
     res.len().try_into().unwrap()
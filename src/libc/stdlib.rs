@@ -9,6 +9,7 @@ use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, export_c_func_aliased, FunctionExports};
 use crate::fs::{resolve_path, GuestPath};
 use crate::libc::clocale::{setlocale, LC_CTYPE};
+use crate::libc::errno::EINVAL;
 use crate::libc::string::strlen;
 use crate::libc::wchar::wchar_t;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr};
@@ -20,9 +21,10 @@ pub mod qsort;
 
 #[derive(Default)]
 pub struct State {
-    rand: u32,
-    random: u32,
-    arc4random: u32,
+    /// Generator states, which are `None` until first used or seeded.
+    rand: Option<u32>,
+    random: Option<u32>,
+    arc4random: Option<u32>,
     env: HashMap<Vec<u8>, MutPtr<u8>>,
 }
 
@@ -30,23 +32,38 @@ pub struct State {
 // an allocation for any of these, so presumably iPhone OS does too.
 // (touchHLE's allocator will round up allocations to at least 16 bytes.)
 
-fn malloc(env: &mut Environment, size: GuestUSize) -> MutVoidPtr {
+pub fn malloc(env: &mut Environment, size: GuestUSize) -> MutVoidPtr {
     env.mem.alloc(size)
 }
 
-fn calloc(env: &mut Environment, count: GuestUSize, size: GuestUSize) -> MutVoidPtr {
+pub fn calloc(env: &mut Environment, count: GuestUSize, size: GuestUSize) -> MutVoidPtr {
     let total = size.checked_mul(count).unwrap();
     env.mem.alloc(total)
 }
 
-fn realloc(env: &mut Environment, ptr: MutVoidPtr, size: GuestUSize) -> MutVoidPtr {
+pub fn realloc(env: &mut Environment, ptr: MutVoidPtr, size: GuestUSize) -> MutVoidPtr {
     if ptr.is_null() {
         return malloc(env, size);
     }
     env.mem.realloc(ptr, size)
 }
 
-fn free(env: &mut Environment, ptr: MutVoidPtr) {
+fn posix_memalign(
+    env: &mut Environment,
+    memptr: MutPtr<MutVoidPtr>,
+    alignment: GuestUSize,
+    size: GuestUSize,
+) -> i32 {
+    // The alignment must be a power of two multiple of sizeof(void *).
+    if !alignment.is_power_of_two() || alignment % 4 != 0 {
+        return EINVAL;
+    }
+    let ptr = env.mem.alloc_aligned(size, alignment);
+    env.mem.write(memptr, ptr);
+    0 // success
+}
+
+pub fn free(env: &mut Environment, ptr: MutVoidPtr) {
     if ptr.is_null() {
         // "If ptr is a NULL pointer, no operation is performed."
         return;
@@ -117,27 +134,88 @@ fn prng(state: u32) -> u32 {
 const RAND_MAX: i32 = i32::MAX;
 const ULONG_MAX: u32 = u32::MAX;
 
+/// The seed to use for `rand()` or `random()` when the app asks for `seed`.
+/// With `--random-seed=`, the app's choice is ignored so that runs can be
+/// reproduced even if the app seeds with the time.
+fn effective_seed(env: &Environment, seed: u32) -> u32 {
+    if let Some(fixed_seed) = env.options.random_seed {
+        if fixed_seed != seed {
+            log_dbg!(
+                "Ignoring seed {} in favor of --random-seed={}",
+                seed,
+                fixed_seed
+            );
+        }
+        fixed_seed
+    } else {
+        seed
+    }
+}
+
 fn srand(env: &mut Environment, seed: u32) {
-    env.libc_state.stdlib.rand = seed;
+    env.libc_state.stdlib.rand = Some(effective_seed(env, seed));
 }
 fn rand(env: &mut Environment) -> i32 {
-    env.libc_state.stdlib.rand = prng(env.libc_state.stdlib.rand);
-    (env.libc_state.stdlib.rand as i32) & RAND_MAX
+    let state = match env.libc_state.stdlib.rand {
+        Some(state) => state,
+        None => effective_seed(env, 1),
+    };
+    let state = prng(state);
+    env.libc_state.stdlib.rand = Some(state);
+    (state as i32) & RAND_MAX
 }
 
 // BSD's "better" random number generator, with an implementation that is not
 // actually better.
 fn srandom(env: &mut Environment, seed: u32) {
-    env.libc_state.stdlib.random = seed;
+    env.libc_state.stdlib.random = Some(effective_seed(env, seed));
 }
 fn random(env: &mut Environment) -> i32 {
-    env.libc_state.stdlib.random = prng(env.libc_state.stdlib.random);
-    (env.libc_state.stdlib.random as i32) & RAND_MAX
+    let state = match env.libc_state.stdlib.random {
+        Some(state) => state,
+        None => effective_seed(env, 1),
+    };
+    let state = prng(state);
+    env.libc_state.stdlib.random = Some(state);
+    (state as i32) & RAND_MAX
 }
 
+// arc4random() can't be seeded by the app, and is meant to be unpredictable,
+// so it gets a seed from the host unless --random-seed= is used.
 fn arc4random(env: &mut Environment) -> u32 {
-    env.libc_state.stdlib.arc4random = prng(env.libc_state.stdlib.arc4random);
-    env.libc_state.stdlib.arc4random
+    let state = match env.libc_state.stdlib.arc4random {
+        Some(state) => state,
+        None => env.options.random_seed.unwrap_or_else(|| {
+            use std::hash::{BuildHasher, Hasher};
+            // RandomState is randomly keyed by the standard library.
+            std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish() as u32
+        }),
+    };
+    let state = prng(state);
+    env.libc_state.stdlib.arc4random = Some(state);
+    state
+}
+fn arc4random_uniform(env: &mut Environment, upper_bound: u32) -> u32 {
+    if upper_bound < 2 {
+        return 0;
+    }
+    // Taking the result modulo the bound would favor small numbers, unless
+    // results below 2**32 % upper_bound are rejected.
+    let min = upper_bound.wrapping_neg() % upper_bound;
+    loop {
+        let value = arc4random(env);
+        if value >= min {
+            return value % upper_bound;
+        }
+    }
+}
+fn arc4random_buf(env: &mut Environment, buf: MutVoidPtr, nbytes: GuestUSize) {
+    for i in 0..nbytes {
+        let byte = arc4random(env) as u8;
+        env.mem.write(buf.cast::<u8>() + i, byte);
+    }
 }
 
 fn getenv(env: &mut Environment, name: ConstPtr<u8>) -> MutPtr<u8> {
@@ -183,9 +261,9 @@ fn setenv(env: &mut Environment, name: ConstPtr<u8>, value: ConstPtr<u8>, overwr
     0 // success
 }
 
-fn exit(_env: &mut Environment, exit_code: i32) {
+fn exit(env: &mut Environment, exit_code: i32) {
     echo!("App called exit(), exiting.");
-    std::process::exit(exit_code);
+    env.exit(exit_code);
 }
 
 fn bsearch(
@@ -327,6 +405,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(calloc(_, _)),
     export_c_func!(realloc(_, _)),
     export_c_func!(free(_)),
+    export_c_func!(posix_memalign(_, _, _)),
     export_c_func!(atexit(_)),
     export_c_func!(atoi(_)),
     export_c_func!(atol(_)),
@@ -337,6 +416,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(srandom(_)),
     export_c_func!(random()),
     export_c_func!(arc4random()),
+    export_c_func!(arc4random_uniform(_)),
+    export_c_func!(arc4random_buf(_, _)),
     export_c_func!(getenv(_)),
     export_c_func!(setenv(_, _, _)),
     export_c_func!(exit(_)),
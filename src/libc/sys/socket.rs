@@ -0,0 +1,860 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `sys/socket.h`, plus `select()` (`sys/select.h`) and `poll()` (`poll.h`).
+//!
+//! Sockets are backed by host sockets. Only TCP is supported for now.
+//!
+//! The host sockets are always non-blocking. When the guest makes a blocking
+//! call, we poll the host socket and let other guest threads run in between,
+//! so that one thread waiting for the network doesn't hang the whole app.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::errno::{
+    set_errno, EADDRINUSE, EADDRNOTAVAIL, EAFNOSUPPORT, EAGAIN, EALREADY, ECONNABORTED,
+    ECONNREFUSED, ECONNRESET, EINPROGRESS, EINVAL, EIO, EISCONN, ENOPROTOOPT, ENOTCONN, ENOTSOCK,
+    EPIPE, EPROTONOSUPPORT, ETIMEDOUT,
+};
+use crate::libc::posix_io::FileDescriptor;
+use crate::mem::{ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr, MutVoidPtr, SafeRead};
+use crate::Environment;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, TcpListener, TcpStream,
+};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+#[allow(non_camel_case_types)]
+pub type socklen_t = u32;
+
+pub const AF_UNSPEC: i32 = 0;
+pub const AF_INET: i32 = 2;
+pub const AF_INET6: i32 = 30;
+
+pub const SOCK_STREAM: i32 = 1;
+pub const SOCK_DGRAM: i32 = 2;
+
+/// From `netinet/in.h`.
+pub const IPPROTO_TCP: i32 = 6;
+/// From `netinet/in.h`.
+pub const IPPROTO_UDP: i32 = 17;
+
+const SOL_SOCKET: i32 = 0xffff;
+const SO_REUSEADDR: i32 = 0x4;
+const SO_KEEPALIVE: i32 = 0x8;
+const SO_SNDTIMEO: i32 = 0x1005;
+const SO_RCVTIMEO: i32 = 0x1006;
+const SO_ERROR: i32 = 0x1007;
+const SO_NOSIGPIPE: i32 = 0x1022;
+
+/// From `netinet/tcp.h`.
+const TCP_NODELAY: i32 = 0x1;
+
+const MSG_PEEK: i32 = 0x2;
+const MSG_DONTWAIT: i32 = 0x80;
+
+const POLLIN: i16 = 0x1;
+const POLLOUT: i16 = 0x4;
+const POLLERR: i16 = 0x8;
+
+/// Size of `struct sockaddr_in` (`netinet/in.h`).
+const SOCKADDR_IN_SIZE: GuestUSize = 16;
+/// Size of `struct sockaddr_in6` (`netinet6/in6.h`).
+const SOCKADDR_IN6_SIZE: GuestUSize = 28;
+
+/// How often to check a host socket when a guest thread is blocked on it.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct timeval {
+    tv_sec: i32,
+    tv_usec: i32,
+}
+unsafe impl SafeRead for timeval {}
+
+/// Convert a `timeval` to a [Duration]. Returns [None] if it's negative or has
+/// more than a second's worth of microseconds, which is `EINVAL`.
+fn timeval_to_duration(timeval { tv_sec, tv_usec }: timeval) -> Option<Duration> {
+    let secs: u64 = tv_sec.try_into().ok()?;
+    let micros: u64 = tv_usec.try_into().ok()?;
+    if micros >= 1_000_000 {
+        return None;
+    }
+    Some(Duration::from_secs(secs) + Duration::from_micros(micros))
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct pollfd {
+    fd: FileDescriptor,
+    events: i16,
+    revents: i16,
+}
+unsafe impl SafeRead for pollfd {}
+
+/// The host object for a socket file descriptor, see
+/// [crate::libc::posix_io].
+pub struct Socket {
+    state: SocketState,
+    non_blocking: bool,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    no_delay: bool,
+}
+
+enum SocketState {
+    Unconnected,
+    /// The host doesn't let us make a connection without blocking, so it's
+    /// done on a separate host thread.
+    Connecting(Receiver<std::io::Result<TcpStream>>),
+    Connected(TcpStream),
+    /// `std::net` has no separate `bind()` and `listen()`, so a bound socket is
+    /// already listening. To find out if `accept()` would block, we have to
+    /// try it, so the connection might be waiting here.
+    Listening {
+        listener: TcpListener,
+        pending: Option<TcpStream>,
+    },
+    /// A connection attempt failed with this `errno`, which will be reported
+    /// to the guest by the next call that uses the socket.
+    Failed(i32),
+}
+
+impl Socket {
+    pub fn set_non_blocking(&mut self, non_blocking: bool) {
+        self.non_blocking = non_blocking;
+    }
+    pub fn non_blocking(&self) -> bool {
+        self.non_blocking
+    }
+
+    /// Check on progress that happens in the background.
+    fn update(&mut self) {
+        match self.state {
+            SocketState::Connecting(ref receiver) => {
+                self.state = match receiver.try_recv() {
+                    Ok(Ok(stream)) => {
+                        stream.set_nonblocking(true).unwrap();
+                        let _ = stream.set_nodelay(self.no_delay);
+                        SocketState::Connected(stream)
+                    }
+                    Ok(Err(err)) => SocketState::Failed(errno_for_error(&err)),
+                    Err(TryRecvError::Empty) => return,
+                    Err(TryRecvError::Disconnected) => SocketState::Failed(ECONNABORTED),
+                };
+            }
+            SocketState::Listening {
+                ref listener,
+                ref mut pending,
+            } if pending.is_none() => {
+                if let Ok((stream, _)) = listener.accept() {
+                    *pending = Some(stream);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns whether the socket is readable, writable and has an error, in
+    /// the sense used by `select()` and `poll()`.
+    fn readiness(&mut self) -> (bool, bool, bool) {
+        self.update();
+        match self.state {
+            SocketState::Unconnected | SocketState::Connecting(_) => (false, false, false),
+            SocketState::Connected(ref stream) => {
+                // End-of-file and errors also count as readable, since they
+                // mean a read wouldn't block.
+                let readable = !matches!(
+                    stream.peek(&mut [0u8]),
+                    Err(ref err) if err.kind() == ErrorKind::WouldBlock
+                );
+                (readable, true, false)
+            }
+            SocketState::Listening { ref pending, .. } => (pending.is_some(), false, false),
+            SocketState::Failed(_) => (true, true, true),
+        }
+    }
+}
+
+fn errno_for_error(err: &std::io::Error) -> i32 {
+    match err.kind() {
+        ErrorKind::WouldBlock => EAGAIN,
+        ErrorKind::ConnectionRefused => ECONNREFUSED,
+        ErrorKind::ConnectionReset => ECONNRESET,
+        ErrorKind::ConnectionAborted => ECONNABORTED,
+        ErrorKind::NotConnected => ENOTCONN,
+        ErrorKind::AddrInUse => EADDRINUSE,
+        ErrorKind::AddrNotAvailable => EADDRNOTAVAIL,
+        ErrorKind::BrokenPipe => EPIPE,
+        ErrorKind::TimedOut => ETIMEDOUT,
+        _ => {
+            log!("Warning: unexpected host socket error {:?}", err);
+            EIO
+        }
+    }
+}
+
+/// Get the bytes of the `struct sockaddr_in` or `struct sockaddr_in6` for an
+/// address. The port and address are in network byte order.
+fn sockaddr_bytes(addr: SocketAddr) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match addr {
+        SocketAddr::V4(addr) => {
+            bytes.extend_from_slice(&[SOCKADDR_IN_SIZE as u8, AF_INET as u8]);
+            bytes.extend_from_slice(&addr.port().to_be_bytes());
+            bytes.extend_from_slice(&addr.ip().octets());
+            bytes.extend_from_slice(&[0; 8]);
+        }
+        SocketAddr::V6(addr) => {
+            bytes.extend_from_slice(&[SOCKADDR_IN6_SIZE as u8, AF_INET6 as u8]);
+            bytes.extend_from_slice(&addr.port().to_be_bytes());
+            bytes.extend_from_slice(&addr.flowinfo().to_be_bytes());
+            bytes.extend_from_slice(&addr.ip().octets());
+            bytes.extend_from_slice(&addr.scope_id().to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Allocate a `struct sockaddr_in` or `struct sockaddr_in6` for an address.
+/// Returns the pointer and the size of the structure.
+pub fn alloc_sockaddr(mem: &mut Mem, addr: SocketAddr) -> (MutVoidPtr, socklen_t) {
+    let bytes = sockaddr_bytes(addr);
+    let size: GuestUSize = bytes.len().try_into().unwrap();
+    let ptr: MutVoidPtr = mem.alloc(size);
+    mem.bytes_at_mut(ptr.cast(), size).copy_from_slice(&bytes);
+    (ptr, size)
+}
+
+/// Write an address to a guest `struct sockaddr` buffer, truncating it if
+/// necessary, and update the length, as done by e.g. `accept()`.
+fn write_sockaddr(
+    mem: &mut Mem,
+    addr: SocketAddr,
+    buffer: MutVoidPtr,
+    buffer_len: MutPtr<socklen_t>,
+) {
+    if buffer.is_null() || buffer_len.is_null() {
+        return;
+    }
+    let bytes = sockaddr_bytes(addr);
+    let size: GuestUSize = bytes.len().try_into().unwrap();
+    let copy_size = mem.read(buffer_len).min(size);
+    mem.bytes_at_mut(buffer.cast(), copy_size)
+        .copy_from_slice(&bytes[..copy_size as usize]);
+    mem.write(buffer_len, size);
+}
+
+/// Read a guest `struct sockaddr_in` or `struct sockaddr_in6`.
+fn read_sockaddr(mem: &Mem, addr: ConstVoidPtr, len: socklen_t) -> Option<SocketAddr> {
+    if addr.is_null() || len < 2 {
+        return None;
+    }
+    let family = mem.read(addr.cast::<u8>() + 1);
+    match i32::from(family) {
+        AF_INET if len >= SOCKADDR_IN_SIZE => {
+            let bytes = mem.bytes_at(addr.cast(), SOCKADDR_IN_SIZE);
+            let port = u16::from_be_bytes(bytes[2..4].try_into().unwrap());
+            let ip: [u8; 4] = bytes[4..8].try_into().unwrap();
+            Some(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(ip), port)))
+        }
+        AF_INET6 if len >= SOCKADDR_IN6_SIZE => {
+            let bytes = mem.bytes_at(addr.cast(), SOCKADDR_IN6_SIZE);
+            let port = u16::from_be_bytes(bytes[2..4].try_into().unwrap());
+            let flowinfo = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
+            let ip: [u8; 16] = bytes[8..24].try_into().unwrap();
+            let scope_id = u32::from_le_bytes(bytes[24..28].try_into().unwrap());
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(ip),
+                port,
+                flowinfo,
+                scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+fn socket_for_fd(env: &mut Environment, fd: FileDescriptor) -> Result<&mut Socket, i32> {
+    env.libc_state.posix_io.socket_for_fd(fd).ok_or(ENOTSOCK)
+}
+
+/// Repeat an operation on a socket until it no longer fails with [EAGAIN],
+/// letting other threads run in between, unless the socket is non-blocking or
+/// the timeout has expired.
+fn retry_while_blocked<T, F>(
+    env: &mut Environment,
+    non_blocking: bool,
+    timeout: Option<Duration>,
+    mut operation: F,
+) -> Result<T, i32>
+where
+    F: FnMut(&mut Environment) -> Result<T, i32>,
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        match operation(env) {
+            Err(EAGAIN)
+                if !non_blocking
+                    && !deadline.is_some_and(|deadline| Instant::now() >= deadline) =>
+            {
+                env.sleep(POLL_INTERVAL, /* tail_call: */ false);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Convert the result of a socket function to the C convention: `-1` and
+/// `errno` on failure.
+fn to_c_result<T: From<i8>>(env: &mut Environment, result: Result<T, i32>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(errno) => {
+            set_errno(env, errno);
+            T::from(-1)
+        }
+    }
+}
+
+fn socket(env: &mut Environment, domain: i32, type_: i32, protocol: i32) -> FileDescriptor {
+    let result = if domain != AF_INET && domain != AF_INET6 {
+        Err(EAFNOSUPPORT)
+    } else if type_ != SOCK_STREAM || (protocol != 0 && protocol != IPPROTO_TCP) {
+        log!(
+            "TODO: socket() with type {} and protocol {}",
+            type_,
+            protocol
+        );
+        Err(EPROTONOSUPPORT)
+    } else {
+        Ok(env.libc_state.posix_io.add_socket(Socket {
+            state: SocketState::Unconnected,
+            non_blocking: false,
+            recv_timeout: None,
+            send_timeout: None,
+            no_delay: false,
+        }))
+    };
+    log_dbg!(
+        "socket({}, {}, {}) => {:?}",
+        domain,
+        type_,
+        protocol,
+        result
+    );
+    to_c_result(env, result)
+}
+
+/// For use by `NSStream`: create a socket and start connecting it to `addr`.
+/// The connection is made in the background, like a non-blocking
+/// [connect], but the socket is blocking.
+pub fn connect_in_background(env: &mut Environment, addr: SocketAddr) -> FileDescriptor {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(TcpStream::connect(addr));
+    });
+    let fd = env.libc_state.posix_io.add_socket(Socket {
+        state: SocketState::Connecting(receiver),
+        non_blocking: false,
+        recv_timeout: None,
+        send_timeout: None,
+        no_delay: false,
+    });
+    log_dbg!("connect_in_background({:?}) => {}", addr, fd);
+    fd
+}
+
+fn connect(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    addr: ConstVoidPtr,
+    addr_len: socklen_t,
+) -> i32 {
+    let result = (|| -> Result<i32, i32> {
+        let addr = read_sockaddr(&env.mem, addr, addr_len).ok_or(EINVAL)?;
+        log_dbg!("connect({}, {:?})", fd, addr);
+        let socket = socket_for_fd(env, fd)?;
+        socket.update();
+        match socket.state {
+            SocketState::Unconnected => (),
+            SocketState::Connecting(_) => return Err(EALREADY),
+            SocketState::Connected(_) => return Err(EISCONN),
+            SocketState::Listening { .. } => return Err(EINVAL),
+            SocketState::Failed(_) => socket.state = SocketState::Unconnected,
+        }
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(TcpStream::connect(addr));
+        });
+        socket.state = SocketState::Connecting(receiver);
+        if socket.non_blocking {
+            return Err(EINPROGRESS);
+        }
+
+        retry_while_blocked(env, false, None, |env| {
+            let socket = socket_for_fd(env, fd)?;
+            socket.update();
+            match socket.state {
+                SocketState::Connecting(_) => Err(EAGAIN),
+                SocketState::Failed(errno) => {
+                    socket.state = SocketState::Unconnected;
+                    Err(errno)
+                }
+                _ => Ok(0),
+            }
+        })
+    })();
+    log_dbg!("connect({}, ...) => {:?}", fd, result);
+    to_c_result(env, result)
+}
+
+fn bind(env: &mut Environment, fd: FileDescriptor, addr: ConstVoidPtr, addr_len: socklen_t) -> i32 {
+    let result = (|| -> Result<i32, i32> {
+        let addr = read_sockaddr(&env.mem, addr, addr_len).ok_or(EINVAL)?;
+        let socket = socket_for_fd(env, fd)?;
+        if !matches!(socket.state, SocketState::Unconnected) {
+            return Err(EINVAL);
+        }
+        let listener = TcpListener::bind(addr).map_err(|err| errno_for_error(&err))?;
+        listener.set_nonblocking(true).unwrap();
+        socket.state = SocketState::Listening {
+            listener,
+            pending: None,
+        };
+        Ok(0)
+    })();
+    log_dbg!("bind({}, ...) => {:?}", fd, result);
+    to_c_result(env, result)
+}
+
+fn listen(env: &mut Environment, fd: FileDescriptor, backlog: i32) -> i32 {
+    // The host socket is already listening, see SocketState::Listening.
+    let result = socket_for_fd(env, fd).and_then(|socket| match socket.state {
+        SocketState::Listening { .. } => Ok(0),
+        _ => {
+            log!("TODO: listen() on a socket that wasn't bound");
+            Err(EINVAL)
+        }
+    });
+    log_dbg!("listen({}, {}) => {:?}", fd, backlog, result);
+    to_c_result(env, result)
+}
+
+fn accept(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    addr: MutVoidPtr,
+    addr_len: MutPtr<socklen_t>,
+) -> FileDescriptor {
+    let result = (|| -> Result<FileDescriptor, i32> {
+        let non_blocking = socket_for_fd(env, fd)?.non_blocking;
+        let stream = retry_while_blocked(env, non_blocking, None, |env| {
+            let socket = socket_for_fd(env, fd)?;
+            socket.update();
+            match socket.state {
+                SocketState::Listening {
+                    ref mut pending, ..
+                } => pending.take().ok_or(EAGAIN),
+                _ => Err(EINVAL),
+            }
+        })?;
+        stream.set_nonblocking(true).unwrap();
+        if let Ok(peer_addr) = stream.peer_addr() {
+            write_sockaddr(&mut env.mem, peer_addr, addr, addr_len);
+        }
+        Ok(env.libc_state.posix_io.add_socket(Socket {
+            state: SocketState::Connected(stream),
+            non_blocking: false,
+            recv_timeout: None,
+            send_timeout: None,
+            no_delay: false,
+        }))
+    })();
+    log_dbg!("accept({}, ...) => {:?}", fd, result);
+    to_c_result(env, result)
+}
+
+fn getsockname(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    addr: MutVoidPtr,
+    addr_len: MutPtr<socklen_t>,
+) -> i32 {
+    let result = socket_for_fd(env, fd).map(|socket| {
+        let local_addr = match socket.state {
+            SocketState::Connected(ref stream) => stream.local_addr().ok(),
+            SocketState::Listening { ref listener, .. } => listener.local_addr().ok(),
+            _ => None,
+        };
+        local_addr.unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))
+    });
+    log_dbg!("getsockname({}, ...) => {:?}", fd, result);
+    let result = result.map(|local_addr| {
+        write_sockaddr(&mut env.mem, local_addr, addr, addr_len);
+        0
+    });
+    to_c_result(env, result)
+}
+
+fn getpeername(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    addr: MutVoidPtr,
+    addr_len: MutPtr<socklen_t>,
+) -> i32 {
+    let result = socket_for_fd(env, fd).and_then(|socket| match socket.state {
+        SocketState::Connected(ref stream) => {
+            stream.peer_addr().map_err(|err| errno_for_error(&err))
+        }
+        _ => Err(ENOTCONN),
+    });
+    log_dbg!("getpeername({}, ...) => {:?}", fd, result);
+    let result = result.map(|peer_addr| {
+        write_sockaddr(&mut env.mem, peer_addr, addr, addr_len);
+        0
+    });
+    to_c_result(env, result)
+}
+
+fn setsockopt(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    level: i32,
+    option_name: i32,
+    option_value: ConstVoidPtr,
+    option_len: socklen_t,
+) -> i32 {
+    let result = (|| -> Result<i32, i32> {
+        match (level, option_name) {
+            (SOL_SOCKET, SO_RCVTIMEO | SO_SNDTIMEO) => {
+                if option_len < 8 {
+                    return Err(EINVAL);
+                }
+                let timeout =
+                    timeval_to_duration(env.mem.read(option_value.cast())).ok_or(EINVAL)?;
+                // Zero means there's no timeout.
+                let timeout = (!timeout.is_zero()).then_some(timeout);
+                let socket = socket_for_fd(env, fd)?;
+                if option_name == SO_RCVTIMEO {
+                    socket.recv_timeout = timeout;
+                } else {
+                    socket.send_timeout = timeout;
+                }
+            }
+            (IPPROTO_TCP, TCP_NODELAY) => {
+                if option_len < 4 {
+                    return Err(EINVAL);
+                }
+                let no_delay = env.mem.read(option_value.cast::<i32>()) != 0;
+                let socket = socket_for_fd(env, fd)?;
+                socket.no_delay = no_delay;
+                if let SocketState::Connected(ref stream) = socket.state {
+                    let _ = stream.set_nodelay(no_delay);
+                }
+            }
+            // These have no effect for us: the host handles address reuse and
+            // keep-alive itself, and there are no signals for broken pipes.
+            (SOL_SOCKET, SO_REUSEADDR | SO_KEEPALIVE | SO_NOSIGPIPE) => {
+                socket_for_fd(env, fd)?;
+            }
+            _ => {
+                log!(
+                    "TODO: setsockopt() level {:#x} option {:#x}",
+                    level,
+                    option_name
+                );
+                return Err(ENOPROTOOPT);
+            }
+        }
+        Ok(0)
+    })();
+    log_dbg!(
+        "setsockopt({}, {:#x}, {:#x}, ...) => {:?}",
+        fd,
+        level,
+        option_name,
+        result
+    );
+    to_c_result(env, result)
+}
+
+fn getsockopt(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    level: i32,
+    option_name: i32,
+    option_value: MutVoidPtr,
+    option_len: MutPtr<socklen_t>,
+) -> i32 {
+    let result = (|| -> Result<i32, i32> {
+        match (level, option_name) {
+            // Used to find out the result of a non-blocking connect().
+            (SOL_SOCKET, SO_ERROR) => {
+                let socket = socket_for_fd(env, fd)?;
+                socket.update();
+                let errno = match socket.state {
+                    SocketState::Failed(errno) => {
+                        socket.state = SocketState::Unconnected;
+                        errno
+                    }
+                    _ => 0,
+                };
+                if env.mem.read(option_len) < 4 {
+                    return Err(EINVAL);
+                }
+                env.mem.write(option_value.cast(), errno);
+                env.mem.write(option_len, 4);
+                Ok(0)
+            }
+            _ => {
+                log!(
+                    "TODO: getsockopt() level {:#x} option {:#x}",
+                    level,
+                    option_name
+                );
+                Err(ENOPROTOOPT)
+            }
+        }
+    })();
+    log_dbg!(
+        "getsockopt({}, {:#x}, {:#x}, ...) => {:?}",
+        fd,
+        level,
+        option_name,
+        result
+    );
+    to_c_result(env, result)
+}
+
+/// `send()`, also used for `write()` on sockets.
+pub fn send(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    buffer: ConstVoidPtr,
+    length: GuestUSize,
+    flags: i32,
+) -> GuestISize {
+    if flags & !MSG_DONTWAIT != 0 {
+        log!("TODO: send() flags {:#x}", flags);
+    }
+    let result = (|| -> Result<GuestISize, i32> {
+        let socket = socket_for_fd(env, fd)?;
+        let non_blocking = socket.non_blocking || flags & MSG_DONTWAIT != 0;
+        let timeout = socket.send_timeout;
+        retry_while_blocked(env, non_blocking, timeout, |env| {
+            let socket = env.libc_state.posix_io.socket_for_fd(fd).ok_or(ENOTSOCK)?;
+            socket.update();
+            let SocketState::Connected(ref mut stream) = socket.state else {
+                return Err(ENOTCONN);
+            };
+            let buffer = env.mem.bytes_at(buffer.cast(), length);
+            match stream.write(buffer) {
+                Ok(written) => Ok(written.try_into().unwrap()),
+                Err(err) => Err(errno_for_error(&err)),
+            }
+        })
+    })();
+    log_dbg!(
+        "send({}, {:?}, {:#x}, {:#x}) => {:?}",
+        fd,
+        buffer,
+        length,
+        flags,
+        result
+    );
+    to_c_result(env, result)
+}
+
+/// `recv()`, also used for `read()` on sockets.
+pub fn recv(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    buffer: MutVoidPtr,
+    length: GuestUSize,
+    flags: i32,
+) -> GuestISize {
+    if flags & !(MSG_DONTWAIT | MSG_PEEK) != 0 {
+        log!("TODO: recv() flags {:#x}", flags);
+    }
+    let result = (|| -> Result<GuestISize, i32> {
+        let socket = socket_for_fd(env, fd)?;
+        let non_blocking = socket.non_blocking || flags & MSG_DONTWAIT != 0;
+        let timeout = socket.recv_timeout;
+        retry_while_blocked(env, non_blocking, timeout, |env| {
+            let socket = env.libc_state.posix_io.socket_for_fd(fd).ok_or(ENOTSOCK)?;
+            socket.update();
+            let SocketState::Connected(ref mut stream) = socket.state else {
+                return Err(ENOTCONN);
+            };
+            let buffer = env.mem.bytes_at_mut(buffer.cast(), length);
+            let result = if flags & MSG_PEEK != 0 {
+                stream.peek(buffer)
+            } else {
+                stream.read(buffer)
+            };
+            match result {
+                Ok(read) => Ok(read.try_into().unwrap()),
+                Err(err) => Err(errno_for_error(&err)),
+            }
+        })
+    })();
+    log_dbg!(
+        "recv({}, {:?}, {:#x}, {:#x}) => {:?}",
+        fd,
+        buffer,
+        length,
+        flags,
+        result
+    );
+    to_c_result(env, result)
+}
+
+/// Readiness of a file descriptor for [select], [poll] and `NSStream`. Other
+/// kinds of file are always ready, since reading and writing them doesn't
+/// block.
+pub fn readiness(env: &mut Environment, fd: FileDescriptor) -> (bool, bool, bool) {
+    match env.libc_state.posix_io.socket_for_fd(fd) {
+        Some(socket) => socket.readiness(),
+        None => (true, true, false),
+    }
+}
+
+/// Call `check` until it returns something other than zero or the timeout
+/// expires (if there is one), letting other threads run in between.
+fn wait_for_readiness<F>(env: &mut Environment, timeout: Option<Duration>, mut check: F) -> i32
+where
+    F: FnMut(&mut Environment) -> i32,
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        let ready_count = check(env);
+        if ready_count != 0 || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return ready_count;
+        }
+        env.sleep(POLL_INTERVAL, /* tail_call: */ false);
+    }
+}
+
+fn select(
+    env: &mut Environment,
+    nfds: i32,
+    readfds: MutPtr<u32>, // fd_set *
+    writefds: MutPtr<u32>,
+    errorfds: MutPtr<u32>,
+    timeout: MutPtr<timeval>,
+) -> i32 {
+    let timeout = if timeout.is_null() {
+        None
+    } else {
+        let Some(timeout) = timeval_to_duration(env.mem.read(timeout)) else {
+            log_dbg!("select({}, ...) with invalid timeout => -1", nfds);
+            set_errno(env, EINVAL);
+            return -1;
+        };
+        Some(timeout)
+    };
+    log_dbg!("select({}, ...) with timeout {:?}", nfds, timeout);
+
+    // An fd_set is a bitfield with one bit per file descriptor.
+    let sets = [readfds, writefds, errorfds];
+    let bit = |fd: FileDescriptor| (fd as GuestUSize / 32, 1u32 << (fd % 32));
+    let requested: Vec<[bool; 3]> = (0..nfds)
+        .map(|fd| {
+            let (word, mask) = bit(fd);
+            sets.map(|set| !set.is_null() && env.mem.read(set + word) & mask != 0)
+        })
+        .collect();
+
+    let mut ready: Vec<[bool; 3]> = Vec::new();
+    let ready_count = wait_for_readiness(env, timeout, |env| {
+        ready = (0..nfds)
+            .zip(requested.iter())
+            .map(|(fd, requested)| {
+                if !requested.contains(&true) {
+                    return [false; 3];
+                }
+                let (readable, writable, error) = readiness(env, fd);
+                [
+                    requested[0] && readable,
+                    requested[1] && writable,
+                    requested[2] && error,
+                ]
+            })
+            .collect();
+        ready
+            .iter()
+            .map(|ready| ready.iter().filter(|&&ready| ready).count() as i32)
+            .sum()
+    });
+
+    // Only the ready file descriptors are left in the sets.
+    for (fd, ready) in (0..nfds).zip(ready.iter()) {
+        let (word, mask) = bit(fd);
+        for (&set, &is_ready) in sets.iter().zip(ready.iter()) {
+            if !set.is_null() && !is_ready {
+                let value = env.mem.read(set + word);
+                env.mem.write(set + word, value & !mask);
+            }
+        }
+    }
+    log_dbg!("select({}, ...) => {}", nfds, ready_count);
+    ready_count
+}
+
+fn poll(env: &mut Environment, fds: MutPtr<pollfd>, nfds: u32, timeout: i32) -> i32 {
+    // A negative timeout means there is none.
+    let timeout = (timeout >= 0).then(|| Duration::from_millis(timeout as u64));
+    log_dbg!("poll({:?}, {}) with timeout {:?}", fds, nfds, timeout);
+    let ready_count = wait_for_readiness(env, timeout, |env| {
+        let mut ready_count = 0;
+        for i in 0..nfds {
+            let pollfd { fd, events, .. } = env.mem.read(fds + i);
+            let (readable, writable, error) = readiness(env, fd);
+            let mut revents = 0;
+            if readable {
+                revents |= events & POLLIN;
+            }
+            if writable {
+                revents |= events & POLLOUT;
+            }
+            if error {
+                revents |= POLLERR;
+            }
+            env.mem.write(
+                fds + i,
+                pollfd {
+                    fd,
+                    events,
+                    revents,
+                },
+            );
+            if revents != 0 {
+                ready_count += 1;
+            }
+        }
+        ready_count
+    });
+    log_dbg!("poll({:?}, {}) => {}", fds, nfds, ready_count);
+    ready_count
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(socket(_, _, _)),
+    export_c_func!(connect(_, _, _)),
+    export_c_func!(bind(_, _, _)),
+    export_c_func!(listen(_, _)),
+    export_c_func!(accept(_, _, _)),
+    export_c_func!(getsockname(_, _, _)),
+    export_c_func!(getpeername(_, _, _)),
+    export_c_func!(setsockopt(_, _, _, _, _)),
+    export_c_func!(getsockopt(_, _, _, _, _)),
+    export_c_func!(send(_, _, _, _)),
+    export_c_func!(recv(_, _, _, _)),
+    export_c_func!(select(_, _, _, _, _)),
+    export_c_func!(poll(_, _, _)),
+];
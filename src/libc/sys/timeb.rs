@@ -6,7 +6,7 @@
 //! `sys/timeb.h`
 
 use crate::dyld::FunctionExports;
-use crate::libc::time::time_t;
+use crate::libc::time::{time_t, wall_clock_now};
 use crate::mem::{MutPtr, SafeRead};
 use crate::{export_c_func, Environment};
 use std::time::SystemTime;
@@ -27,7 +27,7 @@ struct timeb {
 unsafe impl SafeRead for timeb {}
 
 fn ftime(env: &mut Environment, tb: MutPtr<timeb>) -> i32 {
-    let epoch_duration = SystemTime::now()
+    let epoch_duration = wall_clock_now(env)
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
     let time64 = epoch_duration.as_secs();
@@ -4,11 +4,108 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `sys/sysctl.h`
+//!
+//! Only the read-only hardware information that apps use to identify the
+//! device is supported. The values are those of the original iPhone, which is
+//! what touchHLE emulates.
 
 use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::errno::{set_errno, ENOENT, ENOMEM, EPERM};
 use crate::mem::{ConstPtr, GuestUSize, MutPtr, MutVoidPtr};
 use crate::Environment;
 
+const CTL_HW: i32 = 6;
+const HW_MACHINE: i32 = 1;
+const HW_MODEL: i32 = 2;
+const HW_NCPU: i32 = 3;
+const HW_PHYSMEM: i32 = 5;
+const HW_PAGESIZE: i32 = 7;
+const HW_CPU_FREQ: i32 = 15;
+const HW_MEMSIZE: i32 = 24;
+
+/// Device identifier, as also used in e.g. iTunes.
+const MACHINE: &str = "iPhone1,1";
+/// Internal board name.
+const MODEL: &str = "M68AP";
+const NCPU: i32 = 1;
+/// 128MiB of RAM.
+const MEMSIZE: u64 = 128 * 1024 * 1024;
+const PAGESIZE: i32 = 4096;
+/// 412MHz.
+const CPU_FREQ: i32 = 412_000_000;
+
+/// Maps between the names used by `sysctlbyname` and the numbers used by
+/// `sysctl`. The second level numbers are all within [CTL_HW].
+const HW_NAMES: &[(&str, i32)] = &[
+    ("hw.machine", HW_MACHINE),
+    ("hw.model", HW_MODEL),
+    ("hw.ncpu", HW_NCPU),
+    ("hw.activecpu", HW_NCPU),
+    ("hw.physmem", HW_PHYSMEM),
+    ("hw.pagesize", HW_PAGESIZE),
+    ("hw.cpufrequency", HW_CPU_FREQ),
+    ("hw.memsize", HW_MEMSIZE),
+];
+
+/// Get the value of a second-level [CTL_HW] entry, in the form it is copied to
+/// the guest.
+fn hw_value(hw_number: i32) -> Option<Vec<u8>> {
+    fn str_value(value: &str) -> Vec<u8> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(b'\0');
+        bytes
+    }
+    Some(match hw_number {
+        HW_MACHINE => str_value(MACHINE),
+        HW_MODEL => str_value(MODEL),
+        HW_NCPU => NCPU.to_le_bytes().to_vec(),
+        // This is an int, so it can't hold more than 2GiB.
+        HW_PHYSMEM => (MEMSIZE as i32).to_le_bytes().to_vec(),
+        HW_PAGESIZE => PAGESIZE.to_le_bytes().to_vec(),
+        HW_CPU_FREQ => CPU_FREQ.to_le_bytes().to_vec(),
+        HW_MEMSIZE => MEMSIZE.to_le_bytes().to_vec(),
+        _ => return None,
+    })
+}
+
+/// Shared implementation of `sysctl` and `sysctlbyname` once the entry has
+/// been looked up. If `oldp` is null, only the size is returned.
+fn get_value(
+    env: &mut Environment,
+    value: Option<Vec<u8>>,
+    oldp: MutVoidPtr,
+    oldlenp: MutPtr<GuestUSize>,
+    newp: MutVoidPtr,
+) -> i32 {
+    let Some(value) = value else {
+        set_errno(env, ENOENT);
+        return -1;
+    };
+    if !newp.is_null() {
+        // All the supported entries are read-only.
+        set_errno(env, EPERM);
+        return -1;
+    }
+    if oldlenp.is_null() {
+        return 0;
+    }
+    let size: GuestUSize = value.len().try_into().unwrap();
+    if oldp.is_null() {
+        env.mem.write(oldlenp, size);
+        return 0;
+    }
+    if env.mem.read(oldlenp) < size {
+        env.mem.write(oldlenp, size);
+        set_errno(env, ENOMEM);
+        return -1;
+    }
+    env.mem
+        .bytes_at_mut(oldp.cast(), size)
+        .copy_from_slice(&value);
+    env.mem.write(oldlenp, size);
+    0 // success
+}
+
 fn sysctl(
     env: &mut Environment,
     name: MutPtr<i32>,
@@ -18,19 +115,23 @@ fn sysctl(
     newp: MutVoidPtr,
     newlen: GuestUSize,
 ) -> i32 {
-    log!(
-        "TODO: sysctl({:?}, {:#x}, {:?}, {:?}, {:?}, {:x})",
-        name,
-        name_len,
+    let name_parts: Vec<i32> = (0..name_len).map(|i| env.mem.read(name + i)).collect();
+    log_dbg!(
+        "sysctl({:?}, {:?}, {:?}, {:?}, {:x})",
+        name_parts,
         oldp,
         oldlenp,
         newp,
         newlen
     );
-    assert!(!oldp.is_null() && !oldlenp.is_null()); // TODO
-    assert!(newp.is_null()); // TODO
-    env.mem.write(oldlenp, 0);
-    0 // success
+    let value = match name_parts[..] {
+        [CTL_HW, hw_number] => hw_value(hw_number),
+        _ => None,
+    };
+    if value.is_none() {
+        log!("TODO: sysctl() with unknown name {:?}", name_parts);
+    }
+    get_value(env, value, oldp, oldlenp, newp)
 }
 
 fn sysctlbyname(
@@ -43,27 +144,21 @@ fn sysctlbyname(
 ) -> i32 {
     let name_str = env.mem.cstr_at_utf8(name).unwrap();
     log_dbg!(
-        "TODO: sysctlbyname({:?}, {:?}, {:?}, {:?}, {:x})",
+        "sysctlbyname({:?}, {:?}, {:?}, {:?}, {:x})",
         name_str,
         oldp,
         oldlenp,
         newp,
         newlen
     );
-    assert_eq!(name_str, "hw.machine");
-    if oldp.is_null() && newp.is_null() {
-        // "iPhone1,1"
-        env.mem.write(oldlenp, 10);
-        return 0;
+    let value = HW_NAMES
+        .iter()
+        .find(|&&(hw_name, _)| hw_name == name_str)
+        .and_then(|&(_, hw_number)| hw_value(hw_number));
+    if value.is_none() {
+        log!("TODO: sysctlbyname() with unknown name {:?}", name_str);
     }
-    assert!(!oldp.is_null() && !oldlenp.is_null());
-    assert!(newp.is_null());
-    let hw_machine_str = env.mem.alloc_and_write_cstr(b"iPhone1,1");
-    assert_eq!(env.mem.read(oldlenp), 10);
-    env.mem
-        .memmove(oldp, hw_machine_str.cast().cast_const(), 10);
-    env.mem.free(hw_machine_str.cast());
-    0 // success
+    get_value(env, value, oldp, oldlenp, newp)
 }
 
 pub const FUNCTIONS: FunctionExports = &[
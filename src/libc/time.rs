@@ -10,6 +10,18 @@ use crate::mem::{guest_size_of, ConstPtr, MutPtr, Ptr, SafeRead};
 use crate::Environment;
 use std::time::{Duration, Instant, SystemTime};
 
+/// The current wall-clock time as the app sees it, which may be offset from
+/// the host's by the `--date-offset=` option.
+pub fn wall_clock_now(env: &Environment) -> SystemTime {
+    let now = SystemTime::now();
+    let offset = env.options.date_offset;
+    if offset >= 0.0 {
+        now + Duration::from_secs_f64(offset)
+    } else {
+        now - Duration::from_secs_f64(-offset)
+    }
+}
+
 #[derive(Default)]
 pub struct State {
     y2k38_warned: bool,
@@ -37,7 +49,7 @@ fn clock(env: &mut Environment) -> clock_t {
 }
 
 fn time(env: &mut Environment, out: MutPtr<time_t>) -> time_t {
-    let time64 = SystemTime::now()
+    let time64 = wall_clock_now(env)
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
@@ -319,7 +331,7 @@ fn gettimeofday(
         return 0; // success
     }
 
-    let time = SystemTime::now()
+    let time = wall_clock_now(env)
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
 
@@ -0,0 +1,386 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `unwind.h` (exception unwinding).
+//!
+//! iPhone OS apps use "SjLj" (setjmp/longjmp) exception handling: every
+//! function that can catch an exception or needs to clean up while one passes
+//! through registers a function context with the unwinder on entry, and keeps
+//! a call site index in it up to date. The context also contains a jump buffer
+//! for returning to the function's landing pad dispatcher, and a pointer to
+//! the function's language-specific data area (LSDA), which describes what
+//! happens at each call site.
+//!
+//! Since all of this lives in guest memory, the unwinder can be implemented in
+//! host code. The language-specific part (deciding whether an exception is an
+//! instance of a type) is left to the caller of [raise_exception].
+//!
+//! Unwinding through host code is not possible (see also the [setjmp module
+//! docs](super::setjmp)), so exceptions can only be caught by guest code that
+//! was called by the same host function as the code raising them.
+//!
+//! Resources:
+//! - [Itanium C++ ABI: Exception Handling](https://itanium-cxx-abi.github.io/cxx-abi/abi-eh.html)
+//! - LLVM's `libunwind` (`Unwind-sjlj.c`) and `libcxxabi`
+//!   (`cxa_personality.cpp`), which have the details of the SjLj variant and
+//!   the LSDA format.
+
+use crate::abi::{GuestFunction, FRAME_POINTER};
+use crate::cpu::Cpu;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, Ptr, SafeRead};
+use crate::{Environment, ThreadId};
+use std::collections::HashMap;
+
+/// The header of an exception object, which the language runtime embeds in
+/// its own exception object. `private_1` and `private_2` belong to the
+/// unwinder.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+pub struct _Unwind_Exception {
+    pub _exception_class: u64,
+    pub _exception_cleanup: GuestFunction,
+    /// The type filter index of the handler found by the search phase.
+    pub private_1: u32,
+    /// The function context of the handler found by the search phase.
+    pub private_2: MutPtr<_Unwind_FunctionContext>,
+}
+unsafe impl SafeRead for _Unwind_Exception {}
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+pub struct _Unwind_FunctionContext {
+    prev: MutPtr<_Unwind_FunctionContext>,
+    /// 1-based index into the LSDA's call site table, or -1 for calls that
+    /// don't need any action. The unwinder replaces this with the index of the
+    /// landing pad to go to.
+    call_site: i32,
+    /// Set by the unwinder for the landing pad: the exception object and the
+    /// type filter index ("selector").
+    _resume_parameters: [u32; 4],
+    personality: GuestFunction,
+    lsda: ConstPtr<u8>,
+    /// Frame pointer, resume address and stack pointer, in that order. The
+    /// other two entries are unused on ARM.
+    jbuf: [u32; 5],
+}
+unsafe impl SafeRead for _Unwind_FunctionContext {}
+
+#[derive(Default)]
+pub struct State {
+    /// The most recently registered function context of each thread.
+    function_contexts: HashMap<ThreadId, MutPtr<_Unwind_FunctionContext>>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.libc_state.unwind
+    }
+}
+
+fn top_function_context(env: &mut Environment) -> MutPtr<_Unwind_FunctionContext> {
+    let thread = env.current_thread;
+    State::get(env)
+        .function_contexts
+        .get(&thread)
+        .copied()
+        .unwrap_or(Ptr::null())
+}
+fn set_top_function_context(env: &mut Environment, context: MutPtr<_Unwind_FunctionContext>) {
+    let thread = env.current_thread;
+    State::get(env).function_contexts.insert(thread, context);
+}
+
+fn _Unwind_SjLj_Register(env: &mut Environment, context: MutPtr<_Unwind_FunctionContext>) {
+    let mut function_context = env.mem.read(context);
+    function_context.prev = top_function_context(env);
+    env.mem.write(context, function_context);
+    set_top_function_context(env, context);
+}
+
+fn _Unwind_SjLj_Unregister(env: &mut Environment, context: MutPtr<_Unwind_FunctionContext>) {
+    let prev = env.mem.read(context).prev;
+    set_top_function_context(env, prev);
+}
+
+/// Called at the end of a landing pad that only cleaned up, to continue
+/// unwinding to the handler.
+fn _Unwind_SjLj_Resume(env: &mut Environment, exception: MutPtr<_Unwind_Exception>) {
+    unwind_to_handler(env, exception);
+}
+
+/// Raise an exception. `catches` is called with the exception object and the
+/// type info of each catch clause that is found, and decides whether that
+/// clause handles the exception. A null type info is a catch-all clause.
+///
+/// If there's a handler, the guest CPU state is changed so that execution
+/// continues at the first landing pad once the current host function returns,
+/// and [true] is returned. Otherwise, [false] is returned and nothing happens.
+pub fn raise_exception(
+    env: &mut Environment,
+    exception: MutPtr<_Unwind_Exception>,
+    catches: &mut dyn FnMut(&mut Environment, MutPtr<_Unwind_Exception>, ConstVoidPtr) -> bool,
+) -> bool {
+    // Search phase: find the handler, but don't unwind anything yet.
+    let mut context = top_function_context(env);
+    while !context.is_null() {
+        let function_context = env.mem.read(context);
+        let [fp, _, _, _, _] = { function_context.jbuf };
+        if crosses_host_function(env, fp) {
+            break;
+        }
+        if let Some(call_site) = find_call_site(env, &function_context) {
+            for &type_index in &call_site.type_indices {
+                if type_index <= 0 {
+                    // Cleanup, or an exception specification (which
+                    // Objective-C doesn't have).
+                    continue;
+                }
+                let type_info = call_site.type_info(env, type_index);
+                if catches(env, exception, type_info) {
+                    let mut header = env.mem.read(exception);
+                    header.private_1 = type_index as u32;
+                    header.private_2 = context;
+                    env.mem.write(exception, header);
+                    unwind_to_handler(env, exception);
+                    return true;
+                }
+            }
+        }
+        context = function_context.prev;
+    }
+    false
+}
+
+/// Cleanup phase: go to the next landing pad between the top of the stack and
+/// the handler found by [raise_exception].
+fn unwind_to_handler(env: &mut Environment, exception: MutPtr<_Unwind_Exception>) {
+    let header = env.mem.read(exception);
+    let handler = header.private_2;
+    let mut context = top_function_context(env);
+    loop {
+        assert!(!context.is_null(), "Handler went missing while unwinding");
+        let function_context = env.mem.read(context);
+        if let Some(call_site) = find_call_site(env, &function_context) {
+            if context == handler {
+                resume_at_landing_pad(env, context, &call_site, exception, header.private_1);
+                return;
+            }
+            if call_site.type_indices.contains(&0) {
+                resume_at_landing_pad(env, context, &call_site, exception, 0);
+                return;
+            }
+        }
+        assert!(context != handler);
+        context = function_context.prev;
+    }
+}
+
+fn resume_at_landing_pad(
+    env: &mut Environment,
+    context: MutPtr<_Unwind_FunctionContext>,
+    call_site: &CallSite,
+    exception: MutPtr<_Unwind_Exception>,
+    selector: u32,
+) {
+    let mut function_context = env.mem.read(context);
+    function_context.call_site = call_site.landing_pad as i32;
+    function_context._resume_parameters = [exception.to_bits(), selector, 0, 0];
+    let [fp, resume_address, sp, _, _] = { function_context.jbuf };
+    env.mem.write(context, function_context);
+    set_top_function_context(env, context);
+
+    log_dbg!(
+        "Unwinding to landing pad {} of function context {:?} (selector {})",
+        call_site.landing_pad,
+        context,
+        selector
+    );
+    // This is what __builtin_longjmp() does.
+    let regs = env.cpu.regs_mut();
+    regs[FRAME_POINTER] = fp;
+    regs[Cpu::SP] = sp;
+    env.cpu
+        .branch(GuestFunction::from_addr_with_thumb_bit(resume_address));
+}
+
+/// Check whether a host function is between the current guest stack frame and
+/// the one with frame pointer `target_fp`.
+fn crosses_host_function(env: &Environment, target_fp: u32) -> bool {
+    let return_to_host_routine = env.dyld.return_to_host_routine().addr_with_thumb_bit();
+    let mut fp: ConstPtr<u32> = Ptr::from_bits(env.cpu.regs()[FRAME_POINTER]);
+    // The stack grows downwards.
+    while !fp.is_null() && fp.to_bits() < target_fp {
+        if env.mem.read(fp + 1) == return_to_host_routine {
+            return true;
+        }
+        fp = Ptr::from_bits(env.mem.read(fp));
+    }
+    false
+}
+
+/// The LSDA's entry for the call site a function context is at.
+struct CallSite {
+    landing_pad: u32,
+    /// Type filter indices from the action table, in order. 0 is a cleanup.
+    type_indices: Vec<i32>,
+    type_info_encoding: u8,
+    /// The end of the type info table.
+    type_info_base: ConstPtr<u8>,
+}
+impl CallSite {
+    fn type_info(&self, env: &Environment, type_index: i32) -> ConstVoidPtr {
+        let size = encoded_size(self.type_info_encoding);
+        let entry = self.type_info_base - (type_index as u32) * size;
+        read_encoded(env, entry, self.type_info_encoding)
+    }
+}
+
+const DW_EH_PE_OMIT: u8 = 0xff;
+
+fn find_call_site(env: &Environment, context: &_Unwind_FunctionContext) -> Option<CallSite> {
+    let (personality, lsda) = (context.personality, context.lsda);
+    let call_site_index = match context.call_site {
+        -1 => return None,
+        0 => panic!(
+            "Exception raised through a function that can't propagate it (LSDA {:?})",
+            lsda
+        ),
+        index => index as u32,
+    };
+    // The personality routine is the language-specific part that the caller
+    // of raise_exception() handles, but a function without one doesn't want
+    // anything to do with exceptions.
+    if personality.addr_with_thumb_bit() == 0 || lsda.is_null() {
+        return None;
+    }
+
+    let mut ptr = lsda;
+    let landing_pad_start_encoding = read_u8(env, &mut ptr);
+    if landing_pad_start_encoding != DW_EH_PE_OMIT {
+        // Landing pads are indices for SjLj, so this isn't needed.
+        ptr += encoded_size(landing_pad_start_encoding);
+    }
+    let type_info_encoding = read_u8(env, &mut ptr);
+    let type_info_base = if type_info_encoding != DW_EH_PE_OMIT {
+        let offset = read_uleb128(env, &mut ptr);
+        ptr + offset
+    } else {
+        Ptr::null()
+    };
+    // The call site encoding is ignored: SjLj call site tables always use
+    // ULEB128, whatever the LSDA claims.
+    let _call_site_encoding = read_u8(env, &mut ptr);
+    let call_site_table_length = read_uleb128(env, &mut ptr);
+    let action_table = ptr + call_site_table_length;
+
+    let mut remaining = call_site_index;
+    while ptr.to_bits() < action_table.to_bits() {
+        let landing_pad = read_uleb128(env, &mut ptr);
+        let action = read_uleb128(env, &mut ptr);
+        remaining -= 1;
+        if remaining != 0 {
+            continue;
+        }
+
+        let mut type_indices = Vec::new();
+        if action == 0 {
+            type_indices.push(0);
+        } else {
+            let mut action_ptr = action_table + (action - 1);
+            loop {
+                type_indices.push(read_sleb128(env, &mut action_ptr));
+                let next_ptr = action_ptr;
+                let next_offset = read_sleb128(env, &mut action_ptr);
+                if next_offset == 0 {
+                    break;
+                }
+                action_ptr = Ptr::from_bits(next_ptr.to_bits().wrapping_add(next_offset as u32));
+            }
+        }
+        return Some(CallSite {
+            landing_pad,
+            type_indices,
+            type_info_encoding,
+            type_info_base,
+        });
+    }
+    None
+}
+
+fn read_u8(env: &Environment, ptr: &mut ConstPtr<u8>) -> u8 {
+    let value = env.mem.read(*ptr);
+    *ptr += 1;
+    value
+}
+
+fn read_uleb128(env: &Environment, ptr: &mut ConstPtr<u8>) -> u32 {
+    let mut value = 0u32;
+    let mut shift = 0;
+    loop {
+        let byte = read_u8(env, ptr);
+        value |= ((byte & 0x7f) as u32) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+    }
+}
+
+fn read_sleb128(env: &Environment, ptr: &mut ConstPtr<u8>) -> i32 {
+    let mut value = 0i32;
+    let mut shift = 0;
+    loop {
+        let byte = read_u8(env, ptr);
+        value |= ((byte & 0x7f) as i32) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            if shift < 32 && byte & 0x40 != 0 {
+                value |= -1 << shift;
+            }
+            return value;
+        }
+    }
+}
+
+fn encoded_size(encoding: u8) -> u32 {
+    match encoding & 0x0f {
+        // absptr, udata4, sdata4
+        0x00 | 0x03 | 0x0b => 4,
+        // udata2, sdata2
+        0x02 | 0x0a => 2,
+        _ => unimplemented!("Pointer encoding {:#x}", encoding),
+    }
+}
+
+/// Read a pointer in one of the DWARF exception handling encodings.
+fn read_encoded(env: &Environment, ptr: ConstPtr<u8>, encoding: u8) -> ConstVoidPtr {
+    let value: u32 = match encoding & 0x0f {
+        0x00 | 0x03 | 0x0b => env.mem.read(ptr.cast()),
+        0x02 => env.mem.read::<u16>(ptr.cast()).into(),
+        0x0a => env.mem.read::<i16>(ptr.cast()) as u32,
+        _ => unimplemented!("Pointer encoding {:#x}", encoding),
+    };
+    if value == 0 {
+        return Ptr::null();
+    }
+    let value = match encoding & 0x70 {
+        0x00 => value,
+        // pcrel
+        0x10 => ptr.to_bits().wrapping_add(value),
+        _ => unimplemented!("Pointer encoding {:#x}", encoding),
+    };
+    // indirect
+    if encoding & 0x80 != 0 {
+        env.mem.read(Ptr::from_bits(value))
+    } else {
+        Ptr::from_bits(value)
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(_Unwind_SjLj_Register(_)),
+    export_c_func!(_Unwind_SjLj_Unregister(_)),
+    export_c_func!(_Unwind_SjLj_Resume(_)),
+];
@@ -112,9 +112,9 @@ or any later version.
 ";
 
 const STB_IMAGE: &str = "
-touchHLE, and therefore this executable, incorporates the library stb_image,
-which is available either as Public Domain or under the terms of the MIT
-license.
+touchHLE, and therefore this executable, incorporates the libraries stb_image
+and stb_image_write, which are available either as Public Domain or under the
+terms of the MIT license.
 ";
 
 const PVRTD_DESCRIPTION: &str = "
@@ -135,6 +135,12 @@ from the library minimp3, which is licensed under the Creative Commons CC0 1.0
 Universal Public Domain Dedication.
 ";
 
+const RUST_CRYPTO: &str = "
+touchHLE, and therefore this executable, incorporates the RustCrypto libraries
+md-5, sha1, sha2, hmac, aes and cbc, which are available under the terms of
+either the MIT license or the Apache License, Version 2.0.
+";
+
 // When resource files are bundled with touchHLE in such a way that the user can
 // read their license files directly, use this caveat.
 
@@ -217,6 +223,8 @@ fn print(out: &mut String, resources_are_external_files: bool) -> Result<(), std
     writeln!(out, "{}", PVRTD_LICENSE.trim_end())?;
     divider(out)?;
     writeln!(out, "{}", DR_MP3)?;
+    divider(out)?;
+    writeln!(out, "{}", RUST_CRYPTO)?;
     if !resources_are_external_files {
         divider(out)?;
         writeln!(out, "{}", INTERNAL_DYLIBS_DESCRIPTION)?;
@@ -46,6 +46,10 @@ pub struct MachO {
     /// can look things up quickly. Thumb function symbols always have the Thumb
     /// bit set.
     pub exported_symbols: HashMap<String, u32>,
+    /// All named symbols defined by the binary, including non-exported ones,
+    /// sorted by address (without the Thumb bit). This is only used for
+    /// symbolication of addresses in debug output.
+    pub symbols: Vec<(u32, String)>,
     /// List of addresses and names of external relocations for the dynamic
     /// linker to resolve.
     pub external_relocations: Vec<(u32, String)>,
@@ -306,6 +310,7 @@ impl MachO {
         // Info used for the result
         let mut dynamic_libraries = Vec::new();
         let mut exported_symbols = HashMap::new();
+        let mut symbols = Vec::new();
         let mut indirect_undef_symbols: Vec<Option<String>> = Vec::new();
         let mut external_relocations: Vec<(u32, String)> = Vec::new();
         let mut entry_point_pc: Option<u32> = None;
@@ -397,6 +402,15 @@ impl MachO {
                             if let Symbol::Debug { .. } = symbol {
                                 continue;
                             }
+                            if let Symbol::Defined {
+                                name: Some(name),
+                                entry,
+                                ..
+                            } = symbol
+                            {
+                                let entry: u32 = entry.try_into().unwrap();
+                                symbols.push((entry, name.to_string()));
+                            }
                             if let Symbol::Defined {
                                 name: Some(name),
                                 external: true,
@@ -606,11 +620,14 @@ impl MachO {
             })
             .collect();
 
+        symbols.sort();
+
         Ok(MachO {
             name,
             dynamic_libraries,
             sections,
             exported_symbols,
+            symbols,
             external_relocations,
             entry_point_pc,
         })
@@ -637,4 +654,21 @@ impl MachO {
     pub fn get_section<P: SectionPredicate>(&self, by: P) -> Option<&Section> {
         self.sections.iter().find(|section| by.test(section))
     }
+
+    /// Find the symbol an address belongs to, if any, and return its name and
+    /// the offset of the address from it. The Thumb bit is ignored.
+    pub fn symbol_for_address(&self, addr: u32) -> Option<(&str, u32)> {
+        let addr = addr & !GuestFunction::THUMB_BIT;
+        let section = self
+            .sections
+            .iter()
+            .find(|section| (section.addr..section.addr + section.size).contains(&addr))?;
+        let index = self.symbols.partition_point(|&(symbol, _)| symbol <= addr);
+        let (symbol, name) = self.symbols[..index].last()?;
+        // A symbol from an earlier section isn't the one we want.
+        if *symbol < section.addr {
+            return None;
+        }
+        Some((name, addr - symbol))
+    }
 }
@@ -201,6 +201,19 @@ impl<T: SafeRead> SafeWrite for T {}
 
 type Bytes = [u8; 1 << 32];
 
+/// Copy of the guest memory in use and the allocator's state, see
+/// [Mem::snapshot] and [crate::snapshot].
+pub struct MemSnapshot {
+    pub null_segment_size: VAddr,
+    pub chunks: Vec<MemSnapshotChunk>,
+}
+/// An allocated or reserved region of memory in a [MemSnapshot].
+pub struct MemSnapshotChunk {
+    pub base: VAddr,
+    pub reserved: bool,
+    pub bytes: Vec<u8>,
+}
+
 /// The type that owns the guest memory and provides accessors for it.
 pub struct Mem {
     /// This array is 4GiB in size so that it can cover the entire 32-bit
@@ -233,6 +246,10 @@ pub struct Mem {
     /// range.
     null_segment_size: VAddr,
 
+    /// Whether every access is checked against the allocator's record of used
+    /// memory. See [Self::set_strict_checking].
+    strict_checking: bool,
+
     allocator: allocator::Allocator,
 }
 
@@ -270,6 +287,7 @@ impl Mem {
         Mem {
             bytes,
             null_segment_size: 0,
+            strict_checking: false,
             allocator,
         }
     }
@@ -283,6 +301,7 @@ impl Mem {
         let Mem {
             bytes: _,
             null_segment_size: _,
+            strict_checking: _,
             ref mut allocator,
         } = mem;
         let used_chunks = allocator.reset_and_drain_used_chunks();
@@ -290,6 +309,7 @@ impl Mem {
             mem.bytes_mut()[base as usize..][..size.get() as usize].fill(0);
         }
         mem.null_segment_size = 0;
+        mem.strict_checking = false;
         mem
     }
 
@@ -313,6 +333,19 @@ impl Mem {
         self.null_segment_size
     }
 
+    /// Enable or disable strict memory checking. When enabled, every access
+    /// made via [Self::bytes_at] and friends must fall entirely within an
+    /// allocation or a reserved region (e.g. a binary's segments or a stack),
+    /// otherwise there is a panic with a diagnostic naming the nearest
+    /// allocation.
+    ///
+    /// This only covers accesses made by the CPU if direct memory access is
+    /// disabled. Allocations are rounded up to [allocator::MIN_CHUNK_SIZE], so
+    /// small overruns into that padding aren't caught.
+    pub fn set_strict_checking(&mut self, enabled: bool) {
+        self.strict_checking = enabled;
+    }
+
     /// Get a pointer to the full 4GiB of memory. This is only for use when
     /// setting up the CPU, never call this otherwise.
     ///
@@ -340,6 +373,41 @@ impl Mem {
         )
     }
 
+    #[inline]
+    fn strict_check(&self, at: VAddr, size: GuestUSize, access: &str) {
+        if size != 0 && !self.allocator.is_in_use(at, size) {
+            self.strict_check_fail(at, size, access)
+        }
+    }
+    #[cold]
+    fn strict_check_fail(&self, at: VAddr, size: GuestUSize, access: &str) {
+        let nearest = match self.allocator.nearest_used_chunk(at) {
+            Some(chunk) => {
+                let last_byte = chunk.base + (chunk.size.get() - 1);
+                if at > last_byte {
+                    format!(
+                        "{:#x} bytes after the end of {:?}",
+                        at - last_byte - 1,
+                        chunk
+                    )
+                } else if at < chunk.base {
+                    format!(
+                        "{:#x} bytes before the start of {:?}",
+                        chunk.base - at,
+                        chunk
+                    )
+                } else {
+                    format!("overrunning the end of {:?}", chunk)
+                }
+            }
+            None => "no allocations".to_string(),
+        };
+        panic!(
+            "Strict memory checking: {} of {:#x} bytes at {:#x} is outside of any allocation ({})",
+            access, size, at, nearest
+        )
+    }
+
     /// Special version of [Self::bytes_at] that returns [None] rather than
     /// panicking on failure. Only for use by [crate::gdb::GdbServer].
     pub fn get_bytes_fallible(&self, addr: ConstVoidPtr, count: GuestUSize) -> Option<&[u8]> {
@@ -376,6 +444,9 @@ impl Mem {
         if ptr.to_bits() < self.null_segment_size {
             Self::null_check_fail(ptr.to_bits(), count)
         }
+        if self.strict_checking {
+            self.strict_check(ptr.to_bits(), count, "read")
+        }
         &self.bytes()[ptr.to_bits() as usize..][..count as usize]
     }
     /// Get a slice for reading or writing `count` bytes. This is the basic
@@ -389,6 +460,9 @@ impl Mem {
         if ptr.to_bits() < self.null_segment_size {
             Self::null_check_fail(ptr.to_bits(), count)
         }
+        if self.strict_checking {
+            self.strict_check(ptr.to_bits(), count, "read/write")
+        }
         &mut self.bytes_mut()[ptr.to_bits() as usize..][..count as usize]
     }
 
@@ -471,6 +545,10 @@ impl Mem {
 
     /// C-style `memmove`.
     pub fn memmove(&mut self, dest: MutVoidPtr, src: ConstVoidPtr, size: GuestUSize) {
+        if self.strict_checking {
+            self.strict_check(src.to_bits(), size, "read");
+            self.strict_check(dest.to_bits(), size, "write");
+        }
         let src = src.to_bits() as usize;
         let dest = dest.to_bits() as usize;
         let size = size as usize;
@@ -485,6 +563,32 @@ impl Mem {
         ptr
     }
 
+    /// Allocate `size` bytes at an address that is a multiple of `align`,
+    /// which must be a power of two. The allocation can be freed like any
+    /// other.
+    pub fn alloc_aligned(&mut self, size: GuestUSize, align: GuestUSize) -> MutVoidPtr {
+        let ptr = Ptr::from_bits(self.allocator.alloc_aligned(size, align));
+        log_dbg!(
+            "Allocated {:?} ({:#x} bytes, aligned to {:#x})",
+            ptr,
+            size,
+            align
+        );
+        ptr
+    }
+
+    /// Get the size that an allocation of `size` bytes made with
+    /// [Self::alloc] will actually have, which is at least `size`.
+    pub fn rounded_alloc_size(size: GuestUSize) -> GuestUSize {
+        allocator::Allocator::rounded_size(size)
+    }
+
+    /// Get the actual size of an allocation made with one of the `alloc`
+    /// methods on this type, or [None] if `ptr` isn't the start of one.
+    pub fn allocation_size(&self, ptr: ConstVoidPtr) -> Option<GuestUSize> {
+        self.allocator.allocated_size(ptr.to_bits())
+    }
+
     pub fn realloc(&mut self, old_ptr: MutVoidPtr, size: GuestUSize) -> MutVoidPtr {
         // TODO: for a moment we always assume that we do not have enough size
         //       to realloc inplace
@@ -498,10 +602,65 @@ impl Mem {
         new_ptr
     }
 
+    /// Copy the contents of all allocated and reserved memory, along with the
+    /// allocator's state. Everything else is zero, so this is enough to
+    /// reconstruct the guest memory with [Self::restore_snapshot].
+    pub fn snapshot(&self) -> MemSnapshot {
+        let chunks = self
+            .allocator
+            .used_chunks()
+            .map(|(chunk, reserved)| MemSnapshotChunk {
+                base: chunk.base,
+                reserved,
+                bytes: self.bytes()[chunk.base as usize..][..chunk.size.get() as usize].to_vec(),
+            })
+            .collect();
+        MemSnapshot {
+            null_segment_size: self.null_segment_size,
+            chunks,
+        }
+    }
+
+    /// Replace the contents of guest memory and the allocator's state with
+    /// those from a snapshot made by [Self::snapshot].
+    pub fn restore_snapshot(&mut self, snapshot: &MemSnapshot) {
+        let old_chunks: Vec<_> = self.allocator.used_chunks().collect();
+        for (allocator::Chunk { base, size }, _reserved) in old_chunks {
+            self.bytes_mut()[base as usize..][..size.get() as usize].fill(0);
+        }
+
+        let chunks: Vec<_> = snapshot
+            .chunks
+            .iter()
+            .map(|chunk| {
+                let size = chunk.bytes.len().try_into().unwrap();
+                (allocator::Chunk::new(chunk.base, size), chunk.reserved)
+            })
+            .collect();
+        self.allocator = allocator::Allocator::from_used_chunks(&chunks);
+        for chunk in &snapshot.chunks {
+            self.bytes_mut()[chunk.base as usize..][..chunk.bytes.len()]
+                .copy_from_slice(&chunk.bytes);
+        }
+        self.null_segment_size = snapshot.null_segment_size;
+    }
+
+    /// Get the number of allocations made with the `alloc` methods that haven't
+    /// been freed yet, and their total size in bytes. This is for the leak
+    /// report (see [crate::Environment::exit]) and the debug overlay.
+    pub fn outstanding_allocations(&self) -> (usize, u64) {
+        self.allocator
+            .allocated_chunks()
+            .fold((0, 0), |(count, bytes), chunk| {
+                (count + 1, bytes + u64::from(chunk.size.get()))
+            })
+    }
+
     /// Free an allocation made with one of the `alloc` methods on this type.
     pub fn free(&mut self, ptr: MutVoidPtr) {
         let size = self.allocator.free(ptr.to_bits());
-        self.bytes_at_mut(ptr.cast(), size).fill(0);
+        // The chunk is no longer in use, so this must bypass strict checking.
+        self.bytes_mut()[ptr.to_bits() as usize..][..size as usize].fill(0);
         log_dbg!("Freed {:?} ({:#x} bytes)", ptr, size);
     }
 
@@ -562,3 +721,38 @@ impl Mem {
         self.allocator.reserve(allocator::Chunk::new(base, size));
     }
 }
+
+#[cfg(test)]
+mod strict_checking_tests {
+    use super::*;
+
+    #[test]
+    fn in_bounds_access() {
+        let mut mem = Mem::new();
+        mem.set_strict_checking(true);
+        let ptr: MutPtr<u32> = mem.alloc(16).cast();
+        mem.write(ptr + 3, 0xdeadbeef);
+        assert_eq!(mem.read(ptr + 3), 0xdeadbeef);
+        let stack_ptr: MutPtr<u32> = Ptr::from_bits(Mem::MAIN_THREAD_STACK_LOW_END);
+        mem.write(stack_ptr, 1);
+        mem.free(ptr.cast());
+    }
+
+    #[test]
+    #[should_panic(expected = "read of 0x4 bytes")]
+    fn out_of_bounds_read() {
+        let mut mem = Mem::new();
+        mem.set_strict_checking(true);
+        let ptr: MutPtr<u32> = mem.alloc(16).cast();
+        let _: u32 = mem.read(ptr + 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "bytes after the end of Chunk")]
+    fn out_of_bounds_diagnostic() {
+        let mut mem = Mem::new();
+        mem.set_strict_checking(true);
+        let ptr: MutPtr<u8> = mem.alloc(16).cast();
+        mem.write(ptr + 32, 0u8);
+    }
+}
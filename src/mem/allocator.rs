@@ -4,7 +4,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 use super::{GuestUSize, Mem, VAddr};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::num::NonZeroU32;
 
 /// iPhone OS's allocator always aligns to 16 bytes at minimum, and this
@@ -125,6 +125,12 @@ mod collections {
             Some(self.remove_with_base(chunk.base).unwrap())
         }
         #[inline(always)]
+        pub fn iter(&self) -> impl Iterator<Item = Chunk> + '_ {
+            self.chunks
+                .iter()
+                .map(|(&base, &size)| Chunk { base, size })
+        }
+        #[inline(always)]
         pub fn drain(self) -> impl Iterator<Item = Chunk> {
             self.chunks
                 .into_iter()
@@ -134,6 +140,20 @@ mod collections {
         pub fn get_size_with_base(&self, base: VAddr) -> Option<NonZeroU32> {
             self.chunks.get(&base).copied()
         }
+        /// Find the chunk with the highest base address that is at or below
+        /// `addr`, and the chunk with the lowest base address above it.
+        #[inline(always)]
+        pub fn neighbors(&self, addr: VAddr) -> (Option<Chunk>, Option<Chunk>) {
+            use std::ops::Bound::{Excluded, Unbounded};
+            let to_chunk = |(&base, &size)| Chunk { base, size };
+            (
+                self.chunks.range(..=addr).next_back().map(to_chunk),
+                self.chunks
+                    .range((Excluded(addr), Unbounded))
+                    .next()
+                    .map(to_chunk),
+            )
+        }
     }
 
     #[derive(Default, Debug)]
@@ -255,6 +275,9 @@ use collections::{ChunkMap, SizeBucketedChunkMap};
 pub struct Allocator {
     used_chunks: ChunkMap,
     unused_chunks: SizeBucketedChunkMap,
+    /// Base addresses of the used chunks that are reserved rather than
+    /// allocated.
+    reserved_bases: BTreeSet<VAddr>,
 }
 
 impl Allocator {
@@ -269,12 +292,60 @@ impl Allocator {
         let mut unused_chunks: SizeBucketedChunkMap = Default::default();
         unused_chunks.insert(rest);
 
+        let reserved_bases = BTreeSet::from([main_thread_stack.base]);
+
         Allocator {
             used_chunks,
             unused_chunks,
+            reserved_bases,
         }
     }
 
+    /// Reconstruct an allocator from a list of used chunks and whether each
+    /// one is reserved. All the other memory is considered unused. This is for
+    /// restoring snapshots.
+    pub fn from_used_chunks(chunks: &[(Chunk, bool)]) -> Allocator {
+        let mut chunks = chunks.to_vec();
+        chunks.sort_by_key(|&(chunk, _)| chunk.base);
+
+        let mut used_chunks: ChunkMap = Default::default();
+        let mut unused_chunks: SizeBucketedChunkMap = Default::default();
+        let mut reserved_bases = BTreeSet::new();
+        // u64 so that the end of the address space can be represented.
+        let mut next_unused: u64 = 0;
+        for (chunk, reserved) in chunks {
+            assert!(u64::from(chunk.base) >= next_unused);
+            if u64::from(chunk.base) > next_unused {
+                let gap = u64::from(chunk.base) - next_unused;
+                unused_chunks.insert(Chunk::new(next_unused as VAddr, gap as GuestUSize));
+            }
+            used_chunks.insert(chunk);
+            if reserved {
+                reserved_bases.insert(chunk.base);
+            }
+            next_unused = u64::from(chunk.last_byte()) + 1;
+        }
+        // The main thread stack is at the top of the address space, so there
+        // shouldn't be anything left over, but just in case.
+        if next_unused < 1 << 32 {
+            let size = ((1 << 32) - next_unused).min(u64::from(GuestUSize::MAX));
+            unused_chunks.insert(Chunk::new(next_unused as VAddr, size as GuestUSize));
+        }
+
+        Allocator {
+            used_chunks,
+            unused_chunks,
+            reserved_bases,
+        }
+    }
+
+    /// Iterate over the used chunks and whether each one is reserved.
+    pub fn used_chunks(&self) -> impl Iterator<Item = (Chunk, bool)> + '_ {
+        self.used_chunks
+            .iter()
+            .map(|chunk| (chunk, self.reserved_bases.contains(&chunk.base)))
+    }
+
     pub fn reserve(&mut self, chunk: Chunk) {
         let mut to_trisect = None;
         for unused_chunk in self.unused_chunks.iter() {
@@ -297,15 +368,21 @@ impl Allocator {
             self.unused_chunks.insert(after);
         }
         self.used_chunks.insert(chunk);
+        self.reserved_bases.insert(chunk.base);
     }
 
-    pub fn alloc(&mut self, size: GuestUSize) -> VAddr {
+    /// Get the size that an allocation of `size` bytes will actually have.
+    pub fn rounded_size(size: GuestUSize) -> GuestUSize {
         let size = size.max(MIN_CHUNK_SIZE);
-        let size = if size % MIN_CHUNK_SIZE != 0 {
+        if size % MIN_CHUNK_SIZE != 0 {
             size + MIN_CHUNK_SIZE - (size % MIN_CHUNK_SIZE)
         } else {
             size
-        };
+        }
+    }
+
+    pub fn alloc(&mut self, size: GuestUSize) -> VAddr {
+        let size = Self::rounded_size(size);
 
         let Some(alloc) = self.unused_chunks.allocate(size) else {
             panic!(
@@ -318,6 +395,33 @@ impl Allocator {
         alloc.base
     }
 
+    /// Like [Self::alloc], but the address returned is a multiple of `align`,
+    /// which must be a power of two.
+    pub fn alloc_aligned(&mut self, size: GuestUSize, align: GuestUSize) -> VAddr {
+        assert!(align.is_power_of_two());
+        if align <= MIN_CHUNK_SIZE {
+            return self.alloc(size);
+        }
+
+        // Over-allocate so there's guaranteed to be an aligned address inside
+        // the chunk, then give back the part before it. The part after it is
+        // left as slack at the end of the allocation.
+        let padded_size = Self::rounded_size(size)
+            .checked_add(align - MIN_CHUNK_SIZE)
+            .unwrap();
+        let base = self.alloc(padded_size);
+        let aligned = base.next_multiple_of(align);
+        if aligned != base {
+            let whole = self.used_chunks.remove_with_base(base).unwrap();
+            let leading_size = aligned - base;
+            self.used_chunks.insert(Chunk::new(base, leading_size));
+            self.used_chunks
+                .insert(Chunk::new(aligned, whole.size.get() - leading_size));
+            let _ = self.free(base);
+        }
+        aligned
+    }
+
     /// This is used for realloc
     pub fn find_allocated_size(&mut self, base: VAddr) -> GuestUSize {
         let Some(size) = self.used_chunks.get_size_with_base(base) else {
@@ -326,6 +430,17 @@ impl Allocator {
         size.get()
     }
 
+    /// Get the size of an allocation (not a reserved chunk) with a given base
+    /// address, if there is one.
+    pub fn allocated_size(&self, base: VAddr) -> Option<GuestUSize> {
+        if self.reserved_bases.contains(&base) {
+            return None;
+        }
+        self.used_chunks
+            .get_size_with_base(base)
+            .map(|size| size.get())
+    }
+
     /// Returns the size of the freed chunk so it can be zeroed if desired
     #[must_use]
     pub fn free(&mut self, base: VAddr) -> GuestUSize {
@@ -351,9 +466,73 @@ impl Allocator {
         freed.size.get()
     }
 
+    /// Iterate over the chunks that are currently allocated (not reserved).
+    pub fn allocated_chunks(&self) -> impl Iterator<Item = Chunk> + '_ {
+        self.used_chunks
+            .iter()
+            .filter(|chunk| !self.reserved_bases.contains(&chunk.base))
+    }
+
+    /// Check whether every byte in a range is part of some used chunk, i.e.
+    /// an allocation or a reserved region. This is for strict memory checking.
+    pub fn is_in_use(&self, base: VAddr, size: GuestUSize) -> bool {
+        assert!(size > 0);
+        let Some(last_byte) = base.checked_add(size - 1) else {
+            return false;
+        };
+        // The range might span several adjacent chunks.
+        let mut addr = base;
+        loop {
+            let (Some(chunk), _) = self.used_chunks.neighbors(addr) else {
+                return false;
+            };
+            if !chunk.contains(addr) {
+                return false;
+            }
+            if chunk.contains(last_byte) {
+                return true;
+            }
+            addr = chunk.last_byte() + 1;
+        }
+    }
+
+    /// Find the used chunk closest to an address, if any. This is for
+    /// diagnostics.
+    pub fn nearest_used_chunk(&self, addr: VAddr) -> Option<Chunk> {
+        match self.used_chunks.neighbors(addr) {
+            (Some(before), Some(after)) => {
+                if addr.saturating_sub(before.last_byte()) <= after.base - addr {
+                    Some(before)
+                } else {
+                    Some(after)
+                }
+            }
+            (before, after) => before.or(after),
+        }
+    }
+
     pub(super) fn reset_and_drain_used_chunks(&mut self) -> impl Iterator<Item = Chunk> {
         let chunks = std::mem::take(&mut self.used_chunks);
         *self = Allocator::new();
         chunks.drain()
     }
 }
+
+#[cfg(test)]
+mod allocator_tests {
+    use super::Allocator;
+    #[test]
+    fn test_alloc_aligned() {
+        let mut allocator = Allocator::new();
+        // Make sure the next free address isn't already aligned.
+        let unaligned = allocator.alloc(16);
+        for size in [1, 64, 100, 4096] {
+            let aligned = allocator.alloc_aligned(size, 256);
+            assert_eq!(aligned % 256, 0);
+            assert!(allocator.allocated_size(aligned).unwrap() >= size);
+            let _ = allocator.free(aligned);
+        }
+        let _ = allocator.free(unaligned);
+        assert_eq!(allocator.allocated_chunks().count(), 0);
+    }
+}
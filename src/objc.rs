@@ -18,11 +18,14 @@
 //! classes that are both (considering Objective-C's support for inheritance,
 //! categories and dynamic class editing).
 
-use crate::dyld::{export_c_func, FunctionExports};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::ns_object::Observation;
 use crate::MutexId;
 use std::collections::HashMap;
 
+mod blocks;
 mod classes;
+mod exceptions;
 mod messages;
 mod methods;
 mod objects;
@@ -30,21 +33,28 @@ mod properties;
 mod selectors;
 mod synchronization;
 
+pub use blocks::{_Block_copy, _Block_release, class_name_for_block_isa_symbol, get_block_invoke};
 pub use classes::{objc_classes, Class, ClassExports, ClassTemplate};
+pub use exceptions::{alloc_ehtype, objc_exception_throw};
 pub use messages::{
     autorelease, msg, msg_class, msg_send, msg_send_super2, msg_super, objc_super, release, retain,
 };
 pub use methods::{HostIMP, IMP};
 pub use objects::{
-    id, impl_HostObject_with_superclass, nil, AnyHostObject, HostObject, TrivialHostObject,
+    id, impl_HostObject_with_superclass, nil, AnyHostObject, HostObject, LiveObjects,
+    TrivialHostObject,
 };
 pub use selectors::{selector, SEL};
 
+use blocks::{_Block_object_assign, _Block_object_dispose};
 use classes::{ClassHostObject, FakeClass, UnimplementedClass, CLASS_LISTS};
+use exceptions::{
+    __objc_personality_v0, objc_begin_catch, objc_end_catch, objc_exception, objc_exception_rethrow,
+};
 use messages::{
     objc_msgSend, objc_msgSendSuper2, objc_msgSend_stret, MsgSendSignature, MsgSendSuperSignature,
 };
-use methods::method_list_t;
+use methods::{class_addMethod, method_list_t};
 use objects::{objc_object, HostObjectEntry};
 use properties::{objc_copyStruct, objc_setProperty};
 use selectors::sel_registerName;
@@ -74,6 +84,16 @@ pub struct ObjC {
     /// Mutexes used in @synchronized blocks (objc_sync_enter/exit).
     sync_mutexes: HashMap<id, MutexId>,
 
+    /// Exceptions being handled by `@catch` clauses on each thread, innermost
+    /// last, and whether they have been rethrown.
+    caught_exceptions: HashMap<crate::ThreadId, Vec<(crate::mem::MutPtr<objc_exception>, bool)>>,
+
+    /// Key-value observations of each object, see
+    /// [crate::frameworks::foundation::ns_object]. These are kept here so that
+    /// they go away with the object, even if its class doesn't call
+    /// `-[NSObject dealloc]`.
+    key_value_observations: HashMap<id, Vec<Observation>>,
+
     /// Temporary storage for optional type information when sending a message.
     /// Type information isn't part of the `objc_msgSend` ABI, so an alternative
     /// channel is needed.
@@ -87,6 +107,8 @@ impl ObjC {
             objects: HashMap::new(),
             classes: HashMap::new(),
             sync_mutexes: HashMap::new(),
+            caught_exceptions: HashMap::new(),
+            key_value_observations: HashMap::new(),
             message_type_info: None,
         }
     }
@@ -100,5 +122,20 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(objc_copyStruct(_, _, _, _, _)),
     export_c_func!(objc_sync_enter(_)),
     export_c_func!(objc_sync_exit(_)),
+    export_c_func!(objc_exception_throw(_)),
+    export_c_func!(objc_exception_rethrow()),
+    export_c_func!(objc_begin_catch(_)),
+    export_c_func!(objc_end_catch()),
+    export_c_func!(__objc_personality_v0()),
     export_c_func!(sel_registerName(_)),
+    export_c_func!(class_addMethod(_, _, _, _)),
+    export_c_func!(_Block_copy(_)),
+    export_c_func!(_Block_release(_)),
+    export_c_func!(_Block_object_assign(_, _, _)),
+    export_c_func!(_Block_object_dispose(_, _)),
 ];
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_OBJC_EHTYPE_id",
+    HostConstant::Custom(|mem| alloc_ehtype(mem, "id")),
+)];
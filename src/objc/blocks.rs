@@ -0,0 +1,320 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Blocks, the closure extension to C that many Objective-C APIs use.
+//!
+//! Resources:
+//! - Clang's [Block Implementation Specification](https://clang.llvm.org/docs/Block-ABI-Apple.html)
+
+use super::{id, objc_classes, release, retain, Class, ClassExports, NSZonePtr};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, SafeRead};
+use crate::Environment;
+
+/// The layout of a block literal in guest memory. Blocks are Objective-C
+/// objects, so pointers to them are normally passed around as [id].
+///
+/// The name and field names are from the Block ABI specification.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct Block_layout {
+    _isa: Class,
+    flags: i32,
+    _reserved: i32,
+    /// The function implementing the block. Its first argument is always a
+    /// pointer to the block itself, followed by the block's own arguments.
+    invoke: GuestFunction,
+    descriptor: ConstPtr<Block_descriptor>,
+}
+unsafe impl SafeRead for Block_layout {}
+
+/// The part of a block descriptor that is always present. If the block has
+/// [BLOCK_HAS_COPY_DISPOSE] set, it is followed by the copy and dispose
+/// helpers.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct Block_descriptor {
+    _reserved: GuestUSize,
+    size: GuestUSize,
+}
+unsafe impl SafeRead for Block_descriptor {}
+
+/// The header of the structure a `__block` variable is stored in. If
+/// [BLOCK_HAS_COPY_DISPOSE] is set, it is followed by the keep and destroy
+/// helpers, and then by the variable itself.
+///
+/// Accesses to the variable always go through `forwarding`, so that once the
+/// structure has been moved to the heap, the stack and heap blocks share it.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct Block_byref {
+    _isa: ConstVoidPtr,
+    forwarding: MutPtr<Block_byref>,
+    flags: i32,
+    size: GuestUSize,
+}
+unsafe impl SafeRead for Block_byref {}
+
+const BLOCK_REFCOUNT_MASK: i32 = 0xfffe;
+/// The refcount is stored in the flags, in units of this.
+const BLOCK_REFCOUNT_ONE: i32 = 2;
+const BLOCK_NEEDS_FREE: i32 = 1 << 24;
+const BLOCK_HAS_COPY_DISPOSE: i32 = 1 << 25;
+const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+/// Flags for `_Block_object_assign` and `_Block_object_dispose`, which tell
+/// them what kind of captured variable they're handling.
+const BLOCK_FIELD_IS_OBJECT: i32 = 3;
+const BLOCK_FIELD_IS_BLOCK: i32 = 7;
+const BLOCK_FIELD_IS_BYREF: i32 = 8;
+const BLOCK_FIELD_IS_WEAK: i32 = 16;
+/// Set when called from a `__block` variable's keep or destroy helper.
+const BLOCK_BYREF_CALLER: i32 = 128;
+
+/// Get the function to call to invoke a block. The block must be passed as the
+/// first argument, e.g. `invoke.call_from_host(env, (block, foo, bar))`.
+pub fn get_block_invoke(mem: &Mem, block: id) -> GuestFunction {
+    assert!(!block.is_null());
+    mem.read(block.cast::<Block_layout>()).invoke
+}
+
+/// For use by the linker: get the name of the class that a block `isa` symbol
+/// (e.g. `_NSConcreteStackBlock`) refers to, if it is one.
+pub fn class_name_for_block_isa_symbol(symbol: &str) -> Option<&'static str> {
+    match symbol {
+        "__NSConcreteStackBlock" => Some("__NSStackBlock__"),
+        "__NSConcreteMallocBlock" => Some("__NSMallocBlock__"),
+        "__NSConcreteGlobalBlock" => Some("__NSGlobalBlock__"),
+        _ => None,
+    }
+}
+
+/// Get a block's copy and dispose helpers, if it has them.
+fn get_helpers(mem: &Mem, block: id) -> Option<(GuestFunction, GuestFunction)> {
+    let Block_layout {
+        flags, descriptor, ..
+    } = mem.read(block.cast());
+    if flags & BLOCK_HAS_COPY_DISPOSE == 0 {
+        return None;
+    }
+    let helpers: ConstPtr<GuestFunction> = (descriptor + 1).cast();
+    Some((mem.read(helpers), mem.read(helpers + 1)))
+}
+
+fn set_flags(mem: &mut Mem, block: id, flags: i32) {
+    let flags_ptr: MutPtr<i32> = block.cast::<i32>() + 1;
+    mem.write(flags_ptr, flags);
+}
+
+fn set_byref_flags(mem: &mut Mem, byref: MutPtr<Block_byref>, flags: i32) {
+    let flags_ptr: MutPtr<i32> = byref.cast::<i32>() + 2;
+    mem.write(flags_ptr, flags);
+}
+
+fn set_byref_forwarding(
+    mem: &mut Mem,
+    byref: MutPtr<Block_byref>,
+    forwarding: MutPtr<Block_byref>,
+) {
+    let forwarding_ptr: MutPtr<MutPtr<Block_byref>> = byref.cast() + 1;
+    mem.write(forwarding_ptr, forwarding);
+}
+
+/// Get a `__block` variable's keep and destroy helpers, if it has them.
+fn get_byref_helpers(
+    mem: &Mem,
+    byref: MutPtr<Block_byref>,
+) -> Option<(GuestFunction, GuestFunction)> {
+    if mem.read(byref).flags & BLOCK_HAS_COPY_DISPOSE == 0 {
+        return None;
+    }
+    let helpers: ConstPtr<GuestFunction> = (byref + 1).cast().cast_const();
+    Some((mem.read(helpers), mem.read(helpers + 1)))
+}
+
+/// Move a `__block` variable to the heap, or retain it if it's already there.
+/// Both the original and the copy are made to forward to the heap version.
+fn byref_copy(env: &mut Environment, byref: MutPtr<Block_byref>) -> MutPtr<Block_byref> {
+    let byref = env.mem.read(byref).forwarding;
+    let Block_byref { flags, size, .. } = env.mem.read(byref);
+    if flags & BLOCK_NEEDS_FREE != 0 {
+        set_byref_flags(&mut env.mem, byref, flags + BLOCK_REFCOUNT_ONE);
+        return byref;
+    }
+
+    let new_byref: MutPtr<Block_byref> = env.mem.alloc(size).cast();
+    env.mem
+        .memmove(new_byref.cast(), byref.cast().cast_const(), size);
+    // One reference for the heap block, one for the stack frame.
+    set_byref_flags(
+        &mut env.mem,
+        new_byref,
+        (flags & !BLOCK_REFCOUNT_MASK) | BLOCK_NEEDS_FREE | (BLOCK_REFCOUNT_ONE * 2),
+    );
+    set_byref_forwarding(&mut env.mem, new_byref, new_byref);
+    set_byref_forwarding(&mut env.mem, byref, new_byref);
+    log_dbg!("Moved __block variable {:?} to {:?}", byref, new_byref);
+
+    // The keep helper retains the variable if it's an object or block.
+    if let Some((keep_helper, _)) = get_byref_helpers(&env.mem, new_byref) {
+        () = keep_helper.call_from_host(env, (new_byref, byref));
+    }
+    new_byref
+}
+
+/// Release a `__block` variable, freeing it if it's on the heap and this was
+/// the last reference.
+fn byref_release(env: &mut Environment, byref: MutPtr<Block_byref>) {
+    let byref = env.mem.read(byref).forwarding;
+    let flags = env.mem.read(byref).flags;
+    if flags & BLOCK_NEEDS_FREE == 0 {
+        return;
+    }
+    let flags = flags - BLOCK_REFCOUNT_ONE;
+    set_byref_flags(&mut env.mem, byref, flags);
+    if flags & BLOCK_REFCOUNT_MASK != 0 {
+        return;
+    }
+
+    log_dbg!("Freeing __block variable {:?}", byref);
+    if let Some((_, destroy_helper)) = get_byref_helpers(&env.mem, byref) {
+        () = destroy_helper.call_from_host(env, (byref,));
+    }
+    env.mem.free(byref.cast());
+}
+
+/// Copy a block to the heap, or retain it if it's already there. The result
+/// must eventually be released with [_Block_release].
+pub fn _Block_copy(env: &mut Environment, block: id) -> id {
+    if block.is_null() {
+        return block;
+    }
+    let Block_layout {
+        flags, descriptor, ..
+    } = env.mem.read(block.cast());
+    if flags & BLOCK_NEEDS_FREE != 0 {
+        set_flags(&mut env.mem, block, flags + BLOCK_REFCOUNT_ONE);
+        return block;
+    }
+    if flags & BLOCK_IS_GLOBAL != 0 {
+        return block;
+    }
+
+    let size = env.mem.read(descriptor).size;
+    let new_block: id = env.mem.alloc(size).cast();
+    env.mem
+        .memmove(new_block.cast(), block.cast().cast_const(), size);
+    let isa = env.objc.get_known_class("__NSMallocBlock__", &mut env.mem);
+    env.mem.write(new_block.cast(), isa);
+    set_flags(
+        &mut env.mem,
+        new_block,
+        (flags & !BLOCK_REFCOUNT_MASK) | BLOCK_NEEDS_FREE | BLOCK_REFCOUNT_ONE,
+    );
+    log_dbg!("_Block_copy: copied {:?} to {:?}", block, new_block);
+
+    // The copy helper copies or retains the captured variables that need it.
+    if let Some((copy_helper, _)) = get_helpers(&env.mem, new_block) {
+        () = copy_helper.call_from_host(env, (new_block, block));
+    }
+    new_block
+}
+
+/// Release a block that was copied with [_Block_copy]. Blocks that aren't on
+/// the heap are unaffected.
+pub fn _Block_release(env: &mut Environment, block: id) {
+    if block.is_null() {
+        return;
+    }
+    let flags = env.mem.read(block.cast::<Block_layout>()).flags;
+    if flags & BLOCK_NEEDS_FREE == 0 {
+        return;
+    }
+    let flags = flags - BLOCK_REFCOUNT_ONE;
+    set_flags(&mut env.mem, block, flags);
+    if flags & BLOCK_REFCOUNT_MASK != 0 {
+        return;
+    }
+
+    log_dbg!("_Block_release: freeing {:?}", block);
+    if let Some((_, dispose_helper)) = get_helpers(&env.mem, block) {
+        () = dispose_helper.call_from_host(env, (block,));
+    }
+    env.mem.free(block.cast());
+}
+
+/// Used by blocks' copy helpers to copy a captured variable, and by
+/// `__block` variables' keep helpers to copy their contents.
+pub(super) fn _Block_object_assign(
+    env: &mut Environment,
+    dest: MutPtr<id>,
+    object: id,
+    flags: i32,
+) {
+    // Objects and blocks stored in `__block` variables are not retained.
+    let new = if flags & BLOCK_BYREF_CALLER != 0 {
+        object
+    } else {
+        match flags & !BLOCK_FIELD_IS_WEAK {
+            BLOCK_FIELD_IS_OBJECT => retain(env, object),
+            BLOCK_FIELD_IS_BLOCK => _Block_copy(env, object),
+            BLOCK_FIELD_IS_BYREF => byref_copy(env, object.cast()).cast(),
+            _ => unimplemented!("_Block_object_assign() with flags {:#x}", flags),
+        }
+    };
+    env.mem.write(dest, new);
+}
+
+/// Used by blocks' dispose helpers to release a captured variable, by
+/// `__block` variables' destroy helpers, and by compiled code when a
+/// `__block` variable goes out of scope.
+pub(super) fn _Block_object_dispose(env: &mut Environment, object: id, flags: i32) {
+    if flags & BLOCK_BYREF_CALLER != 0 {
+        return;
+    }
+    match flags & !BLOCK_FIELD_IS_WEAK {
+        BLOCK_FIELD_IS_OBJECT => release(env, object),
+        BLOCK_FIELD_IS_BLOCK => _Block_release(env, object),
+        BLOCK_FIELD_IS_BYREF => byref_release(env, object.cast()),
+        _ => unimplemented!("_Block_object_dispose() with flags {:#x}", flags),
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// Blocks' `isa` points to one of the subclasses of this, depending on where
+// the block is stored. Their memory management methods have the same effect
+// as the block runtime functions.
+@implementation NSBlock: NSObject
+
+- (id)copyWithZone:(NSZonePtr)_zone {
+    _Block_copy(env, this)
+}
+
+- (id)retain {
+    let flags = env.mem.read(this.cast::<Block_layout>()).flags;
+    if flags & BLOCK_NEEDS_FREE != 0 {
+        set_flags(&mut env.mem, this, flags + BLOCK_REFCOUNT_ONE);
+    }
+    this
+}
+- (())release {
+    _Block_release(env, this);
+}
+
+@end
+
+@implementation __NSStackBlock__: NSBlock
+@end
+
+@implementation __NSMallocBlock__: NSBlock
+@end
+
+@implementation __NSGlobalBlock__: NSBlock
+@end
+
+};
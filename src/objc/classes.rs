@@ -702,6 +702,26 @@ impl ObjC {
         }
     }
 
+    /// Get the superclass of a class or metaclass. This is [nil] for root
+    /// classes, and for classes we have no implementation of.
+    pub fn get_superclass(&self, class: Class) -> Class {
+        match self.get_host_object(class).unwrap().as_any().downcast_ref() {
+            Some(&ClassHostObject { superclass, .. }) => superclass,
+            None => nil,
+        }
+    }
+
+    /// Check whether an object is a class or metaclass.
+    pub(super) fn is_class(&self, object: id) -> bool {
+        let Some(host_object) = self.get_host_object(object) else {
+            return false;
+        };
+        let host_object = host_object.as_any();
+        host_object.is::<ClassHostObject>()
+            || host_object.is::<UnimplementedClass>()
+            || host_object.is::<FakeClass>()
+    }
+
     pub fn get_class_name(&self, class: Class) -> &str {
         let host_object = self.get_host_object(class).unwrap();
         if let Some(ClassHostObject { name, .. }) = host_object.as_any().downcast_ref() {
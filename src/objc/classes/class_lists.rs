@@ -7,23 +7,39 @@
 //! very long and frequently-updated list.
 
 use crate::frameworks::{
-    av_audio, core_animation, core_foundation, core_graphics, foundation, media_player, opengles,
-    store_kit, uikit,
+    address_book, av_audio, core_animation, core_foundation, core_graphics, core_location,
+    foundation, game_kit, map_kit, media_player, message_ui, opengles, store_kit,
+    system_configuration, uikit,
 };
 
 /// All the lists of classes that the runtime should search through.
 pub const CLASS_LISTS: &[super::ClassExports] = &[
-    crate::app_picker::CLASSES, // Not a framework! Special internal classes.
+    crate::app_picker::CLASSES,   // Not a framework! Special internal classes.
+    crate::objc::blocks::CLASSES, // Not a framework! Part of the runtime.
+    address_book::ab_address_book::CLASSES, // Special internal classes.
+    address_book::ab_multi_value::CLASSES, // Special internal classes.
+    address_book::ab_person::CLASSES, // Special internal classes.
+    core_animation::ca_animation::CLASSES,
     core_animation::ca_eagl_layer::CLASSES,
+    core_animation::ca_gradient_layer::CLASSES,
     core_animation::ca_layer::CLASSES,
+    core_animation::ca_shape_layer::CLASSES,
+    core_animation::ca_transaction::CLASSES,
     core_graphics::cg_data_provider::CLASSES,
+    core_graphics::cg_color::CLASSES,
     core_graphics::cg_color_space::CLASSES,
+    core_graphics::cg_gradient::CLASSES,
     core_graphics::cg_context::CLASSES,
     core_graphics::cg_image::CLASSES,
+    core_graphics::cg_path::CLASSES,
+    core_foundation::cf_run_loop::CLASSES, // Special internal classes.
     core_foundation::cf_run_loop_timer::CLASSES, // Special internal classes.
+    core_location::cl_location::CLASSES,
+    core_location::cl_location_manager::CLASSES,
     foundation::ns_array::CLASSES,
     foundation::ns_autorelease_pool::CLASSES,
     foundation::ns_bundle::CLASSES,
+    foundation::ns_cache::CLASSES,
     foundation::ns_character_set::CLASSES,
     foundation::ns_coder::CLASSES,
     foundation::ns_data::CLASSES,
@@ -32,8 +48,11 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_dictionary::CLASSES,
     foundation::ns_enumerator::CLASSES,
     foundation::ns_error::CLASSES,
+    foundation::ns_exception::CLASSES,
     foundation::ns_file_handle::CLASSES,
     foundation::ns_file_manager::CLASSES,
+    foundation::ns_http_cookie_storage::CLASSES,
+    foundation::ns_index_set::CLASSES,
     foundation::ns_keyed_unarchiver::CLASSES,
     foundation::ns_locale::CLASSES,
     foundation::ns_lock::CLASSES,
@@ -44,21 +63,37 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_process_info::CLASSES,
     foundation::ns_run_loop::CLASSES,
     foundation::ns_set::CLASSES,
+    foundation::ns_stream::CLASSES,
     foundation::ns_string::CLASSES,
     foundation::ns_thread::CLASSES,
+    foundation::ns_time_zone::CLASSES,
     foundation::ns_timer::CLASSES,
     foundation::ns_url::CLASSES,
+    foundation::ns_url_connection::CLASSES,
+    foundation::ns_url_request::CLASSES,
+    foundation::ns_url_response::CLASSES,
     foundation::ns_user_defaults::CLASSES,
     foundation::ns_value::CLASSES,
     av_audio::av_audio_player::CLASSES,
+    game_kit::gk_leaderboard::CLASSES,
+    game_kit::gk_local_player::CLASSES,
+    game_kit::gk_score::CLASSES,
+    map_kit::mk_annotation_view::CLASSES,
+    map_kit::mk_map_view::CLASSES,
+    map_kit::mk_shape::CLASSES,
     media_player::movie_player::CLASSES,
     media_player::music_player::CLASSES,
     media_player::media_query::CLASSES,
+    message_ui::mf_mail_compose_view_controller::CLASSES,
     opengles::eagl::CLASSES,
+    store_kit::sk_payment::CLASSES,
+    store_kit::sk_payment_queue::CLASSES,
     store_kit::sk_product::CLASSES,
+    system_configuration::sc_network_reachability::CLASSES,
     uikit::ui_accelerometer::CLASSES,
     uikit::ui_activity_indicator_view::CLASSES,
     uikit::ui_application::CLASSES,
+    uikit::ui_bezier_path::CLASSES,
     uikit::ui_color::CLASSES,
     uikit::ui_device::CLASSES,
     uikit::ui_event::CLASSES,
@@ -66,16 +101,24 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     uikit::ui_image::CLASSES,
     uikit::ui_image_picker_controller::CLASSES,
     uikit::ui_nib::CLASSES,
+    uikit::ui_pasteboard::CLASSES,
     uikit::ui_responder::CLASSES,
     uikit::ui_screen::CLASSES,
+    uikit::ui_tab_bar_item::CLASSES,
     uikit::ui_touch::CLASSES,
     uikit::ui_view::CLASSES,
+    uikit::ui_view::ui_action_sheet::CLASSES,
     uikit::ui_view::ui_alert_view::CLASSES,
     uikit::ui_view::ui_control::CLASSES,
     uikit::ui_view::ui_control::ui_button::CLASSES,
     uikit::ui_view::ui_control::ui_text_field::CLASSES,
     uikit::ui_view::ui_image_view::CLASSES,
+    uikit::ui_view::ui_keyboard::CLASSES,
     uikit::ui_view::ui_label::CLASSES,
+    uikit::ui_view::ui_scroll_view::CLASSES,
+    uikit::ui_view::ui_scroll_view::ui_text_view::CLASSES,
+    uikit::ui_view::ui_tab_bar::CLASSES,
     uikit::ui_view::ui_window::CLASSES,
     uikit::ui_view_controller::CLASSES,
+    uikit::ui_view_controller::ui_tab_bar_controller::CLASSES,
 ];
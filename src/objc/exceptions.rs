@@ -0,0 +1,158 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Objective-C exceptions (`@throw`, `@try`, `@catch`).
+//!
+//! On iPhone OS, Objective-C exceptions use the same mechanism as C++
+//! exceptions. `@try` blocks are compiled to the same kind of landing pads and
+//! exception tables, and `@catch` clauses refer to type info structures named
+//! `OBJC_EHTYPE_$_ClassName`. The unwinding itself is done by
+//! [crate::libc::unwind]; this module provides the Objective-C part.
+//!
+//! Resources:
+//! - Apple's [source code for `objc-exception.mm`](https://opensource.apple.com/source/objc4/objc4-551.1/runtime/objc-exception.mm.auto.html)
+
+use super::{id, nil, Class, ObjC};
+use crate::abi::GuestFunction;
+use crate::frameworks::foundation::ns_exception;
+use crate::libc::unwind::{self, _Unwind_Exception};
+use crate::mem::{ConstPtr, ConstVoidPtr, Mem, MutPtr, Ptr, SafeRead};
+use crate::Environment;
+
+/// What [objc_exception_throw] allocates: a header for the unwinder, followed
+/// by the thrown object.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+pub(super) struct objc_exception {
+    _header: _Unwind_Exception,
+    object: id,
+}
+unsafe impl SafeRead for objc_exception {}
+
+/// Type info for a `@catch` clause.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct objc_typeinfo {
+    _vtable: ConstVoidPtr,
+    name: ConstPtr<u8>,
+    /// May be nil, in which case the class is looked up by name.
+    class: Class,
+}
+unsafe impl SafeRead for objc_typeinfo {}
+
+/// The same exception class C++ exceptions have, since that's what Apple's
+/// runtime uses.
+const EXCEPTION_CLASS: u64 = u64::from_be_bytes(*b"GNUCC++\0");
+
+/// Allocate an `OBJC_EHTYPE_$_...` constant for a class, for use in constant
+/// lists.
+pub fn alloc_ehtype(mem: &mut Mem, class_name: &str) -> ConstVoidPtr {
+    let name = mem.alloc_and_write_cstr(class_name.as_bytes()).cast_const();
+    mem.alloc_and_write(objc_typeinfo {
+        _vtable: Ptr::null(),
+        name,
+        class: nil,
+    })
+    .cast()
+    .cast_const()
+}
+
+/// Throw an Objective-C exception. This doesn't return to the caller: once the
+/// current host function returns, execution continues in the handler.
+pub fn objc_exception_throw(env: &mut Environment, object: id) {
+    log_dbg!("objc_exception_throw({:?})", object);
+    let exception = env.mem.alloc_and_write(objc_exception {
+        _header: _Unwind_Exception {
+            _exception_class: EXCEPTION_CLASS,
+            _exception_cleanup: GuestFunction::from_addr_with_thumb_bit(0),
+            private_1: 0,
+            private_2: Ptr::null(),
+        },
+        object,
+    });
+    throw(env, exception);
+}
+
+fn throw(env: &mut Environment, exception: MutPtr<objc_exception>) {
+    if !unwind::raise_exception(env, exception.cast(), &mut catches) {
+        // Apple's runtime calls Foundation's uncaught exception handler here.
+        let object = env.mem.read(exception).object;
+        ns_exception::uncaught_exception(env, object);
+    }
+}
+
+/// Rethrow the exception currently being handled (`@throw;`).
+pub(super) fn objc_exception_rethrow(env: &mut Environment) {
+    let thread = env.current_thread;
+    let caught = env.objc.caught_exceptions.entry(thread).or_default();
+    let (exception, rethrown) = caught.last_mut().expect("No exception to rethrow");
+    *rethrown = true;
+    let exception = *exception;
+    log_dbg!("objc_exception_rethrow() => {:?}", exception);
+    throw(env, exception);
+}
+
+/// Called at the start of a `@catch` clause to get the thrown object.
+pub(super) fn objc_begin_catch(env: &mut Environment, exception: MutPtr<objc_exception>) -> id {
+    let thread = env.current_thread;
+    env.objc
+        .caught_exceptions
+        .entry(thread)
+        .or_default()
+        .push((exception, false));
+    env.mem.read(exception).object
+}
+
+/// Called at the end of a `@catch` clause.
+pub(super) fn objc_end_catch(env: &mut Environment) {
+    let thread = env.current_thread;
+    let caught = env.objc.caught_exceptions.entry(thread).or_default();
+    let (exception, rethrown) = caught.pop().expect("No exception being handled");
+    // A rethrown exception is still in flight.
+    if !rethrown {
+        env.mem.free(exception.cast());
+    }
+}
+
+/// Only the address of this is used by apps: the work of the personality
+/// routine is done by [crate::libc::unwind] together with [catches].
+pub(super) fn __objc_personality_v0(_env: &mut Environment) -> i32 {
+    log!("Warning: __objc_personality_v0() was called directly, ignoring");
+    // _URC_FATAL_PHASE1_ERROR
+    3
+}
+
+/// Decide whether a `@catch` clause handles an exception.
+fn catches(
+    env: &mut Environment,
+    exception: MutPtr<_Unwind_Exception>,
+    type_info: ConstVoidPtr,
+) -> bool {
+    // catch (...)
+    if type_info.is_null() {
+        return true;
+    }
+    let object = env.mem.read(exception.cast::<objc_exception>()).object;
+    let objc_typeinfo { name, class, .. } = env.mem.read(type_info.cast());
+    let class = if class != nil && env.objc.get_host_object(class).is_some() {
+        class
+    } else {
+        let Ok(name) = env.mem.cstr_at_utf8(name) else {
+            return false;
+        };
+        if name == "id" {
+            return true;
+        }
+        match env.objc.classes.get(name) {
+            Some(&class) => class,
+            // Not a class we know about, perhaps a C++ type.
+            None => return false,
+        }
+    };
+    object != nil
+        && env
+            .objc
+            .class_is_subclass_of(ObjC::read_isa(object, &env.mem), class)
+}
@@ -220,3 +220,35 @@ impl ObjC {
         }
     }
 }
+
+/// Standard Objective-C runtime function for adding a method to a class. Like
+/// with methods in the app binary, the type encoding is ignored.
+pub(super) fn class_addMethod(
+    env: &mut Environment,
+    class: Class,
+    name: SEL,
+    imp: GuestIMP,
+    _types: ConstPtr<u8>,
+) -> bool {
+    // Classes touchHLE has no implementation of can't have methods added.
+    let is_implemented = env
+        .objc
+        .get_host_object(class)
+        .is_some_and(|host_object| host_object.as_any().is::<ClassHostObject>());
+    if !is_implemented {
+        log!(
+            "Warning: class_addMethod() for unimplemented class {:?}, ignoring",
+            class
+        );
+        return false;
+    }
+
+    let host_object = env.objc.borrow_mut::<ClassHostObject>(class);
+    // This doesn't replace an existing method, but it can override a method
+    // of a superclass.
+    if host_object.methods.contains_key(&name) {
+        return false;
+    }
+    host_object.methods.insert(name, IMP::Guest(imp));
+    true
+}
@@ -21,8 +21,10 @@
 //! See also: [crate::frameworks::foundation::ns_object].
 
 use super::{Class, ClassHostObject};
+use crate::frameworks::foundation::ns_object::Observation;
 use crate::mem::{guest_size_of, GuestUSize, Mem, MutPtr, Ptr, SafeRead};
 use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
 use std::num::NonZeroU32;
 
 /// Memory layout of a minimal Objective-C object. See [id].
@@ -64,6 +66,16 @@ pub(super) struct HostObjectEntry {
     refcount: Option<NonZeroU32>,
 }
 
+/// Counts of objects that are still alive, by class name. See
+/// [super::ObjC::live_objects].
+#[derive(Default, Debug)]
+pub struct LiveObjects {
+    /// Reference-counted objects. At exit, these are probably leaks.
+    pub refcounted: BTreeMap<String, usize>,
+    /// Static-lifetime objects, e.g. singletons. These are leaked on purpose.
+    pub static_lifetime: BTreeMap<String, usize>,
+}
+
 /// Type for host objects.
 pub trait HostObject: Any + 'static {
     /// Upcast to the superclass's host object type, if any.
@@ -275,6 +287,17 @@ impl super::ObjC {
         }
     }
 
+    /// Get the refcount of an object, or [None] if it has a static duration.
+    pub fn get_refcount(&self, object: id) -> Option<NonZeroU32> {
+        let Some(entry) = self.objects.get(&object) else {
+            panic!(
+                "No entry found for object {:?}, it may have already been deallocated",
+                object
+            );
+        };
+        entry.refcount
+    }
+
     /// Increase the refcount of a reference-counted object. Do not call this
     /// directly unless you're implementing `release` on `NSObject`. That method
     /// may be overridden.
@@ -325,6 +348,32 @@ impl super::ObjC {
         }
     }
 
+    /// Count the objects that are still alive, by class. Classes and
+    /// metaclasses are not included. This is for the leak report (see
+    /// [crate::Environment::exit]).
+    pub fn live_objects(&self, mem: &Mem) -> LiveObjects {
+        let mut live_objects = LiveObjects::default();
+        for (&object, entry) in &self.objects {
+            if self.is_class(object) {
+                continue;
+            }
+            let class_name = self.get_class_name(Self::read_isa(object, mem));
+            let counts = if entry.refcount.is_some() {
+                &mut live_objects.refcounted
+            } else {
+                &mut live_objects.static_lifetime
+            };
+            *counts.entry(class_name.to_string()).or_default() += 1;
+        }
+        live_objects
+    }
+
+    /// Get the key-value observations of each object. This is for use by
+    /// [crate::frameworks::foundation::ns_object] only.
+    pub fn key_value_observations(&mut self) -> &mut HashMap<id, Vec<Observation>> {
+        &mut self.key_value_observations
+    }
+
     /// Deallocate an object. Do not call this directly unless you're
     /// implementing `dealloc` and are sure you don't need to do a super-call.
     pub fn dealloc_object(&mut self, object: id, mem: &mut Mem) {
@@ -346,6 +395,37 @@ impl super::ObjC {
 
         std::mem::drop(host_object);
 
+        // A new object could get the same address.
+        self.key_value_observations.remove(&object);
+
         mem.free(object.cast());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objc::ObjC;
+
+    #[test]
+    fn live_objects() {
+        let mut mem = Mem::new();
+        let mut objc = ObjC::new();
+        let class = objc.get_known_class("NSObject", &mut mem);
+
+        let leaked: Vec<id> = (0..3)
+            .map(|_| objc.alloc_object(class, Box::new(TrivialHostObject), &mut mem))
+            .collect();
+        let freed = objc.alloc_object(class, Box::new(TrivialHostObject), &mut mem);
+        objc.alloc_static_object(class, Box::new(TrivialHostObject), &mut mem);
+        assert!(objc.decrement_refcount(freed));
+        objc.dealloc_object(freed, &mut mem);
+
+        let live_objects = objc.live_objects(&mem);
+        assert_eq!(live_objects.refcounted.get("NSObject"), Some(&leaked.len()));
+        assert_eq!(live_objects.static_lifetime.get("NSObject"), Some(&1));
+        // Classes aren't counted.
+        assert_eq!(live_objects.refcounted.len(), 1);
+        assert_eq!(live_objects.static_lifetime.len(), 1);
+    }
+}
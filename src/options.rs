@@ -11,6 +11,7 @@ use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read};
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::num::NonZeroU32;
+use std::path::PathBuf;
 
 pub const OPTIONS_HELP: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/OPTIONS_HELP.txt"));
@@ -35,20 +36,107 @@ pub struct Options {
     pub fullscreen: bool,
     pub initial_orientation: DeviceOrientation,
     pub scale_hack: NonZeroU32,
+    pub scaling_filter: ScalingFilter,
+    /// Whether the app's output is only scaled up by whole-number factors when
+    /// it's bigger than the window.
+    pub integer_scaling: bool,
     pub deadzone: f32,
     pub x_tilt_range: f32,
     pub y_tilt_range: f32,
     pub x_tilt_offset: f32,
     pub y_tilt_offset: f32,
     pub button_to_touch: HashMap<Button, (f32, f32)>,
+    pub left_stick: LeftStickMode,
     pub stabilize_virtual_cursor: Option<(f32, f32)>,
     pub gles1_implementation: Option<GLESImplementation>,
     pub direct_memory_access: bool,
+    pub strict_memory_checking: bool,
+    pub leak_report: bool,
+    pub record_input_path: Option<PathBuf>,
+    pub replay_input_path: Option<PathBuf>,
+    /// Fixed seed for all the random number generators, if set.
+    pub random_seed: Option<u32>,
     pub gdb_listen_addrs: Option<Vec<SocketAddr>>,
     pub preferred_languages: Option<Vec<String>>,
     pub headless: bool,
     pub print_fps: bool,
+    pub debug_overlay: bool,
     pub fps_limit: Option<f64>,
+    /// Target CPU speed in millions of ticks (roughly, instructions) per
+    /// second, if throttled.
+    pub cpu_speed: Option<f64>,
+    pub turbo: bool,
+    /// Simulated location as (latitude, longitude) in degrees. If there is
+    /// more than one point, the location moves along the path they form.
+    pub location_path: Vec<(f64, f64)>,
+    /// Speed in meters per second at which the location follows the path.
+    pub location_speed: f64,
+    /// Alias of the simulated Game Center local player.
+    pub game_center_alias: String,
+    /// People in the simulated address book, as (name, phone number).
+    pub contacts: Vec<(String, Option<String>)>,
+    /// Whether simulated in-app purchases succeed.
+    pub in_app_purchases_succeed: bool,
+    /// What the image picker gives the app.
+    pub image_picker: ImagePickerChoice,
+    /// Network connectivity reported to the app.
+    pub network: NetworkConnectivity,
+    /// Offset in seconds added to the wall-clock time the app sees.
+    pub date_offset: f64,
+}
+
+/// What the left analog stick does, see the `--left-stick=` option.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LeftStickMode {
+    /// Simulate tilting the device (accelerometer).
+    #[default]
+    Tilt,
+    /// Act like the D-pad, for use with `--button-to-touch=`.
+    DPad,
+    /// Drag a touch around a point, like an on-screen joystick.
+    Drag { center: (f32, f32), radius: f32 },
+}
+
+/// How the app's output is filtered when it is scaled to fit the window.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ScalingFilter {
+    /// Bilinear filtering, which is smooth but blurry.
+    #[default]
+    Linear,
+    /// Nearest-neighbor filtering, which keeps pixels sharp.
+    Nearest,
+}
+impl ScalingFilter {
+    pub fn toggled(self) -> Self {
+        match self {
+            ScalingFilter::Linear => ScalingFilter::Nearest,
+            ScalingFilter::Nearest => ScalingFilter::Linear,
+        }
+    }
+}
+
+/// See [Options::image_picker].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ImagePickerChoice {
+    /// A built-in sample image.
+    #[default]
+    Sample,
+    /// An image file on the host.
+    File(PathBuf),
+    /// The user cancels.
+    Cancel,
+}
+
+/// See [Options::network].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NetworkConnectivity {
+    /// Reachable via a local network (WiFi).
+    #[default]
+    WiFi,
+    /// Reachable via a cellular network (WWAN).
+    Cellular,
+    /// Not reachable.
+    None,
 }
 
 impl Default for Options {
@@ -57,20 +145,41 @@ impl Default for Options {
             fullscreen: false,
             initial_orientation: DeviceOrientation::Portrait,
             scale_hack: NonZeroU32::new(1).unwrap(),
+            scaling_filter: ScalingFilter::Linear,
+            integer_scaling: false,
             deadzone: 0.1,
             x_tilt_range: 60.0,
             y_tilt_range: 60.0,
             x_tilt_offset: 0.0,
             y_tilt_offset: 0.0,
             button_to_touch: HashMap::new(),
+            left_stick: LeftStickMode::Tilt,
             stabilize_virtual_cursor: None,
             gles1_implementation: None,
             direct_memory_access: true,
+            strict_memory_checking: false,
+            leak_report: false,
+            record_input_path: None,
+            replay_input_path: None,
+            random_seed: None,
             gdb_listen_addrs: None,
             preferred_languages: None,
             headless: false,
             print_fps: false,
+            debug_overlay: false,
             fps_limit: Some(60.0), // Original iPhone is 60Hz and uses v-sync
+            cpu_speed: None,
+            turbo: false,
+            // Same as the iOS Simulator
+            location_path: vec![(37.331705, -122.030237)],
+            // Walking pace
+            location_speed: 1.4,
+            game_center_alias: "touchHLE".to_string(),
+            contacts: Vec::new(),
+            in_app_purchases_succeed: false,
+            image_picker: ImagePickerChoice::Sample,
+            network: NetworkConnectivity::WiFi,
+            date_offset: 0.0,
         }
     }
 }
@@ -100,6 +209,16 @@ impl Options {
             self.scale_hack = value
                 .parse()
                 .map_err(|_| "Invalid scale hack factor".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--scaling-filter=") {
+            self.scaling_filter = match value {
+                "linear" => ScalingFilter::Linear,
+                "nearest" => ScalingFilter::Nearest,
+                _ => {
+                    return Err("Value for --scaling-filter= must be linear or nearest".to_string())
+                }
+            };
+        } else if arg == "--integer-scaling" {
+            self.integer_scaling = true;
         } else if let Some(value) = arg.strip_prefix("--deadzone=") {
             self.deadzone = parse_degrees(value, "deadzone")?;
         } else if let Some(value) = arg.strip_prefix("--x-tilt-range=") {
@@ -137,6 +256,31 @@ impl Options {
                 .parse()
                 .map_err(|_| "Invalid Y co-ordinate for --button-to-touch=".to_string())?;
             self.button_to_touch.insert(button, (x, y));
+        } else if let Some(value) = arg.strip_prefix("--left-stick=") {
+            self.left_stick = match value {
+                "tilt" => LeftStickMode::Tilt,
+                "dpad" => LeftStickMode::DPad,
+                _ => {
+                    let drag_values = value.strip_prefix("drag,").ok_or_else(|| {
+                        "Value for --left-stick= must be tilt, dpad or drag".to_string()
+                    })?;
+                    let mut drag_values = drag_values.split(',').map(|value| value.parse::<f32>());
+                    let (Some(Ok(x)), Some(Ok(y)), Some(Ok(radius)), None) = (
+                        drag_values.next(),
+                        drag_values.next(),
+                        drag_values.next(),
+                        drag_values.next(),
+                    ) else {
+                        return Err(
+                            "--left-stick=drag requires three numbers: X, Y and radius".to_string()
+                        );
+                    };
+                    LeftStickMode::Drag {
+                        center: (x, y),
+                        radius,
+                    }
+                }
+            };
         } else if let Some(value) = arg.strip_prefix("--stabilize-virtual-cursor=") {
             let (smoothing_strength, sticky_radius) = value
                 .split_once(',')
@@ -163,6 +307,20 @@ impl Options {
             );
         } else if arg == "--disable-direct-memory-access" {
             self.direct_memory_access = false;
+        } else if arg == "--strict-memory" {
+            self.strict_memory_checking = true;
+        } else if arg == "--leak-report" {
+            self.leak_report = true;
+        } else if let Some(value) = arg.strip_prefix("--record-input=") {
+            self.record_input_path = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--replay-input=") {
+            self.replay_input_path = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--random-seed=") {
+            self.random_seed = Some(
+                value
+                    .parse()
+                    .map_err(|_| "Invalid value for --random-seed=".to_string())?,
+            );
         } else if let Some(address) = arg.strip_prefix("--gdb=") {
             let addrs = address
                 .to_socket_addrs()
@@ -175,6 +333,8 @@ impl Options {
             self.headless = true;
         } else if arg == "--print-fps" {
             self.print_fps = true;
+        } else if arg == "--debug-overlay" {
+            self.debug_overlay = true;
         } else if let Some(value) = arg.strip_prefix("--fps-limit=") {
             if value == "off" {
                 self.fps_limit = None;
@@ -186,6 +346,92 @@ impl Options {
                     .ok_or_else(|| "Invalid value for --fps-limit=".to_string())?;
                 self.fps_limit = Some(limit);
             }
+        } else if let Some(value) = arg.strip_prefix("--cpu-speed=") {
+            if value == "off" {
+                self.cpu_speed = None;
+            } else {
+                let speed: f64 = value
+                    .parse()
+                    .ok()
+                    .and_then(|v| if v <= 0.0 { None } else { Some(v) })
+                    .ok_or_else(|| "Invalid value for --cpu-speed=".to_string())?;
+                self.cpu_speed = Some(speed);
+            }
+        } else if arg == "--turbo" {
+            self.turbo = true;
+        } else if let Some(value) = arg.strip_prefix("--location=") {
+            self.location_path = value
+                .split(';')
+                .map(|point| {
+                    let (latitude, longitude) = point.split_once(',')?;
+                    let latitude: f64 = latitude.trim().parse().ok()?;
+                    let longitude: f64 = longitude.trim().parse().ok()?;
+                    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+                        .then_some((latitude, longitude))
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| "Invalid value for --location=".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--location-speed=") {
+            self.location_speed = value
+                .parse()
+                .ok()
+                .and_then(|v: f64| {
+                    if v.is_finite() && v >= 0.0 {
+                        Some(v)
+                    } else {
+                        None
+                    }
+                })
+                .ok_or_else(|| "Invalid value for --location-speed=".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--game-center-alias=") {
+            if value.is_empty() {
+                return Err("Value for --game-center-alias= must not be empty".to_string());
+            }
+            self.game_center_alias = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--contacts=") {
+            self.contacts = value
+                .split(';')
+                .map(|contact| {
+                    let (name, phone) = match contact.split_once(',') {
+                        Some((name, phone)) => (
+                            name.trim(),
+                            Some(phone.trim()).filter(|phone| !phone.is_empty()),
+                        ),
+                        None => (contact.trim(), None),
+                    };
+                    (!name.is_empty()).then(|| (name.to_string(), phone.map(str::to_string)))
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| "Invalid value for --contacts=".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--in-app-purchases=") {
+            self.in_app_purchases_succeed = match value {
+                "succeed" => true,
+                "fail" => false,
+                _ => {
+                    return Err("Value for --in-app-purchases= must be succeed or fail".to_string())
+                }
+            };
+        } else if let Some(value) = arg.strip_prefix("--image-picker=") {
+            self.image_picker = match value {
+                "sample" => ImagePickerChoice::Sample,
+                "cancel" => ImagePickerChoice::Cancel,
+                "" => return Err("Value for --image-picker= must not be empty".to_string()),
+                path => ImagePickerChoice::File(PathBuf::from(path)),
+            };
+        } else if let Some(value) = arg.strip_prefix("--network=") {
+            self.network = match value {
+                "wifi" => NetworkConnectivity::WiFi,
+                "cellular" => NetworkConnectivity::Cellular,
+                "none" => NetworkConnectivity::None,
+                _ => return Err("Value for --network= must be wifi, cellular or none".to_string()),
+            };
+        } else if let Some(value) = arg.strip_prefix("--date-offset=") {
+            let days: f64 = value
+                .parse()
+                .ok()
+                .filter(|v: &f64| v.is_finite())
+                .ok_or_else(|| "Invalid value for --date-offset=".to_string())?;
+            self.date_offset = days * 24.0 * 60.0 * 60.0;
         } else {
             return Ok(false);
         };
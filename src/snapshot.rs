@@ -0,0 +1,192 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Snapshots of emulator state, the foundation for save states.
+//!
+//! So far, a snapshot contains the guest memory that is in use, the memory
+//! allocator's state, and the CPU registers. That isn't yet enough to resume
+//! an app: the host side of the Objective-C runtime (host objects and
+//! refcounts), open files, run loop timers, and host resources like audio and
+//! OpenGL contexts aren't covered. Host resources in particular will need to
+//! be re-created on restore, since they can't be serialized. Until all of that
+//! is done, this isn't exposed as a user-facing feature.
+//!
+//! The file format is a simple little-endian binary format, starting with a
+//! magic number and a format version. Newer versions of touchHLE should keep
+//! being able to read older versions of the format.
+#![allow(dead_code)] // TODO: remove once save states are exposed
+
+use crate::mem::{GuestUSize, MemSnapshot, MemSnapshotChunk};
+use crate::Environment;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 8] = b"THLESNAP";
+
+/// Current version of the snapshot format. Increase this whenever the format
+/// changes, and keep support for reading older versions.
+pub const FORMAT_VERSION: u32 = 1;
+
+pub struct Snapshot {
+    /// Registers of the current thread.
+    /// TODO: VFP registers, other threads
+    regs: [u32; 16],
+    cpsr: u32,
+    mem: MemSnapshot,
+}
+
+impl Snapshot {
+    /// Capture the state of the emulator. This fails if there's state that
+    /// can't be captured yet.
+    pub fn capture(env: &Environment) -> Result<Snapshot, String> {
+        if env.threads.len() > 1 {
+            return Err("Snapshots of apps with multiple threads aren't supported yet".to_string());
+        }
+        Ok(Snapshot {
+            regs: *env.cpu.regs(),
+            cpsr: env.cpu.cpsr(),
+            mem: env.mem.snapshot(),
+        })
+    }
+
+    /// Restore the state of the emulator from a snapshot.
+    pub fn restore(&self, env: &mut Environment) {
+        env.mem.restore_snapshot(&self.mem);
+        // Recompiled code might be stale now.
+        env.cpu.invalidate_cache_range(0, GuestUSize::MAX);
+        *env.cpu.regs_mut() = self.regs;
+        env.cpu.set_cpsr(self.cpsr);
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(writer, FORMAT_VERSION)?;
+
+        for &reg in &self.regs {
+            write_u32(writer, reg)?;
+        }
+        write_u32(writer, self.cpsr)?;
+
+        write_u32(writer, self.mem.null_segment_size)?;
+        write_u32(writer, self.mem.chunks.len().try_into().unwrap())?;
+        for chunk in &self.mem.chunks {
+            write_u32(writer, chunk.base)?;
+            write_u32(writer, chunk.bytes.len().try_into().unwrap())?;
+            writer.write_all(&[chunk.reserved.into()])?;
+            writer.write_all(&chunk.bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Snapshot, String> {
+        let io_error = |e: std::io::Error| format!("Couldn't read snapshot: {}", e);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic).map_err(io_error)?;
+        if &magic != MAGIC {
+            return Err("Not a touchHLE snapshot".to_string());
+        }
+        let version = read_u32(reader).map_err(io_error)?;
+        if version > FORMAT_VERSION {
+            return Err(format!(
+                "Snapshot format version {} is too new, this version of touchHLE only supports up to {}",
+                version, FORMAT_VERSION
+            ));
+        }
+
+        let mut regs = [0u32; 16];
+        for reg in &mut regs {
+            *reg = read_u32(reader).map_err(io_error)?;
+        }
+        let cpsr = read_u32(reader).map_err(io_error)?;
+
+        let null_segment_size = read_u32(reader).map_err(io_error)?;
+        let chunk_count = read_u32(reader).map_err(io_error)?;
+        let mut chunks = Vec::new();
+        for _ in 0..chunk_count {
+            let base = read_u32(reader).map_err(io_error)?;
+            let size = read_u32(reader).map_err(io_error)?;
+            let mut reserved = [0u8];
+            reader.read_exact(&mut reserved).map_err(io_error)?;
+            let mut bytes = vec![0u8; size as usize];
+            reader.read_exact(&mut bytes).map_err(io_error)?;
+            chunks.push(MemSnapshotChunk {
+                base,
+                reserved: reserved[0] != 0,
+                bytes,
+            });
+        }
+
+        Ok(Snapshot {
+            regs,
+            cpsr,
+            mem: MemSnapshot {
+                null_segment_size,
+                chunks,
+            },
+        })
+    }
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::{Mem, MutPtr};
+
+    #[test]
+    fn snapshot_and_restore_heap() {
+        let mut mem = Mem::new();
+        let a: MutPtr<u32> = mem.alloc(16).cast();
+        let b: MutPtr<u32> = mem.alloc(64).cast();
+        mem.write(a, 0x12345678);
+        mem.write(b + 15, 0xcafebabe);
+
+        let snapshot = Snapshot {
+            regs: [7; 16],
+            cpsr: 0x10,
+            mem: mem.snapshot(),
+        };
+        let mut file = Vec::new();
+        snapshot.write_to(&mut file).unwrap();
+        let allocations_at_snapshot = mem.outstanding_allocations();
+
+        // Mutate the state.
+        mem.write(a, 0);
+        mem.free(b.cast());
+        let c: MutPtr<u32> = mem.alloc(256).cast();
+        mem.write(c, 0xdeadbeef);
+        let e: MutPtr<u32> = mem.alloc(16).cast();
+        mem.write(e, 1);
+        assert_ne!(mem.outstanding_allocations(), allocations_at_snapshot);
+
+        let restored = Snapshot::read_from(&mut file.as_slice()).unwrap();
+        assert_eq!(restored.regs, [7; 16]);
+        assert_eq!(restored.cpsr, 0x10);
+        mem.restore_snapshot(&restored.mem);
+
+        assert_eq!(mem.outstanding_allocations(), allocations_at_snapshot);
+        assert_eq!(mem.read(a), 0x12345678);
+        assert_eq!(mem.read(b + 15), 0xcafebabe);
+        // The allocator must be consistent with the restored heap.
+        mem.free(b.cast());
+        let d: MutPtr<u32> = mem.alloc(16).cast();
+        assert_ne!(d, a);
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(Snapshot::read_from(&mut file.as_slice()).is_err());
+    }
+}
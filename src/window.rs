@@ -12,11 +12,13 @@
 //! window system interaction in general, because it is assumed only one window
 //! will be needed for the runtime of the app.
 
+use crate::gamepad_to_touch::{GamepadToTouch, TouchPhase};
 use crate::gles::present::present_frame;
 use crate::gles::{create_gles1_ctx, GLES};
 use crate::image::Image;
 use crate::matrix::Matrix;
-use crate::options::Options;
+use crate::options::{LeftStickMode, Options, ScalingFilter};
+use sdl2::event::WindowEvent;
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::surface::Surface;
@@ -40,6 +42,38 @@ fn size_for_orientation(orientation: DeviceOrientation, scale_hack: NonZeroU32)
         DeviceOrientation::LandscapeRight => (480 * scale_hack, 320 * scale_hack),
     }
 }
+/// Find the region of the screen (x, y, width, height) that content of a
+/// particular size should be scaled to, preserving its aspect ratio and
+/// centering it. If `integer_scaling` is [true], content that fits on the
+/// screen is only scaled up by whole-number factors.
+fn fit_to_screen(
+    (app_width, app_height): (u32, u32),
+    (screen_width, screen_height): (u32, u32),
+    integer_scaling: bool,
+) -> (u32, u32, u32, u32) {
+    let integer_scale = (screen_width / app_width).min(screen_height / app_height);
+    let (scaled_width, scaled_height) = if integer_scaling && integer_scale >= 1 {
+        (app_width * integer_scale, app_height * integer_scale)
+    } else {
+        let app_aspect = app_width as f32 / app_height as f32;
+        let screen_aspect = screen_width as f32 / screen_height as f32;
+        if app_aspect < screen_aspect {
+            (
+                (screen_height as f32 * app_aspect).round() as u32,
+                screen_height,
+            )
+        } else {
+            (
+                screen_width,
+                (screen_width as f32 / app_aspect).round() as u32,
+            )
+        }
+    };
+    let x = (screen_width - scaled_width) / 2;
+    let y = (screen_height - scaled_height) / 2;
+    (x, y, scaled_width, scaled_height)
+}
+
 fn rotate_fullscreen_size(orientation: DeviceOrientation, screen_size: (u32, u32)) -> (u32, u32) {
     let (short_side, long_side) = if screen_size.0 < screen_size.1 {
         (screen_size.0, screen_size.1)
@@ -74,17 +108,18 @@ pub enum FingerId {
     Touch(i64),
     VirtualCursor,
     ButtonToTouch(crate::options::Button),
+    LeftStick,
 }
 pub type Coords = (f32, f32);
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum TextInputEvent {
     Text(String),
     Backspace,
     Return,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Event {
     /// User requested quit.
     Quit,
@@ -94,12 +129,33 @@ pub enum Event {
     /// OS has informed touchHLE it will soon terminate.
     /// (iOS `applicationWillTerminate:`, Android `onDestroy()`)
     AppWillTerminate,
+    /// The window lost input focus, e.g. because the user switched to another
+    /// window. (iOS `applicationWillResignActive:`)
+    FocusLost,
+    /// The window regained input focus. (iOS `applicationDidBecomeActive:`)
+    FocusGained,
+    /// The window was minimized, so the app can't be seen.
+    /// (iOS `applicationDidEnterBackground:`)
+    Minimized,
+    /// The window was restored after being minimized.
+    /// (iOS `applicationWillEnterForeground:` and
+    /// `applicationDidBecomeActive:`)
+    Restored,
     TouchesDown(HashMap<FingerId, Coords>),
     TouchesMove(HashMap<FingerId, Coords>),
     TouchesUp(HashMap<FingerId, Coords>),
     /// User pressed F12, requesting that execution be paused and the debugger
     /// take over.
     EnterDebugger,
+    /// User pressed F10, requesting a screenshot of the app's OpenGL ES
+    /// framebuffer.
+    Screenshot,
+    /// User pressed F8, requesting that turbo mode be toggled.
+    ToggleTurbo,
+    /// User pressed F7, requesting that the debug overlay be toggled.
+    ToggleDebugOverlay,
+    /// User pressed F6, requesting that the scaling filter be toggled.
+    ToggleScalingFilter,
     TextInput(TextInputEvent),
 }
 
@@ -152,6 +208,9 @@ pub struct Window {
     /// [Self::rotatable_fullscreen] returns [true].
     fullscreen: bool,
     scale_hack: NonZeroU32,
+    scaling_filter: ScalingFilter,
+    /// Copy of `integer_scaling` on [Options].
+    integer_scaling: bool,
     internal_gl_ctx: Option<Box<dyn GLES>>,
     splash_image: Option<Image>,
     device_orientation: DeviceOrientation,
@@ -162,6 +221,7 @@ pub struct Window {
     accelerometer: Option<sdl2::sensor::Sensor>,
     virtual_cursor_last: Option<(f32, f32, bool, bool)>,
     virtual_cursor_last_unsticky: Option<(f32, f32, Instant)>,
+    gamepad_to_touch: GamepadToTouch,
 }
 impl Window {
     /// Returns [true] if touchHLE is running on a device where we should always
@@ -291,6 +351,8 @@ impl Window {
             viewport_y_offset: 0,
             fullscreen,
             scale_hack,
+            scaling_filter: options.scaling_filter,
+            integer_scaling: options.integer_scaling,
             internal_gl_ctx: None,
             splash_image: launch_image,
             device_orientation,
@@ -301,6 +363,7 @@ impl Window {
             accelerometer,
             virtual_cursor_last: None,
             virtual_cursor_last_unsticky: None,
+            gamepad_to_touch: GamepadToTouch::new(),
         };
 
         // Set up OpenGL ES context used for splash screen and app UI rendering
@@ -375,6 +438,14 @@ impl Window {
                 _ => None,
             }
         }
+        fn touch_event(phase: TouchPhase, finger: FingerId, coords: Coords) -> Event {
+            let touches = HashMap::from([(finger, coords)]);
+            match phase {
+                TouchPhase::Down => Event::TouchesDown(touches),
+                TouchPhase::Move => Event::TouchesMove(touches),
+                TouchPhase::Up => Event::TouchesUp(touches),
+            }
+        }
         fn finger_absolute_coords(window: &Window, (x, y): (f32, f32)) -> (f32, f32) {
             let (screen_width, screen_height) = window.window.drawable_size();
             (screen_width as f32 * x, screen_height as f32 * y)
@@ -446,26 +517,15 @@ impl Window {
                     let Some(button) = translate_button(button) else {
                         continue;
                     };
-                    let Some(&(x, y)) = options.button_to_touch.get(&button) else {
+                    let pressed = matches!(event, E::ControllerButtonDown { .. });
+                    let Some((phase, finger, coords)) = self
+                        .gamepad_to_touch
+                        .button_changed(options, button, pressed)
+                    else {
                         continue;
                     };
-                    match event {
-                        E::ControllerButtonUp { .. } => {
-                            let coords = transform_input_coords(self, (x, y), true);
-                            Event::TouchesUp(HashMap::from([(
-                                FingerId::ButtonToTouch(button),
-                                coords,
-                            )]))
-                        }
-                        E::ControllerButtonDown { .. } => {
-                            let coords = transform_input_coords(self, (x, y), true);
-                            Event::TouchesDown(HashMap::from([(
-                                FingerId::ButtonToTouch(button),
-                                coords,
-                            )]))
-                        }
-                        _ => unreachable!(),
-                    }
+                    let coords = transform_input_coords(self, coords, true);
+                    touch_event(phase, finger, coords)
                 }
                 E::ControllerAxisMotion { .. } => {
                     controller_updated = true;
@@ -482,6 +542,13 @@ impl Window {
                     self.enable_event_polling = false;
                     continue;
                 }
+                E::Window { win_event, .. } => match win_event {
+                    WindowEvent::FocusLost => Event::FocusLost,
+                    WindowEvent::FocusGained => Event::FocusGained,
+                    WindowEvent::Minimized => Event::Minimized,
+                    WindowEvent::Restored => Event::Restored,
+                    _ => continue,
+                },
                 E::AppTerminating { .. } => {
                     log!("Received app-will-terminate event.");
                     assert!(self.high_priority_event.is_none());
@@ -583,6 +650,22 @@ impl Window {
                     echo!("F12 pressed, EnterDebugger event queued.");
                     Event::EnterDebugger
                 }
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F10),
+                    ..
+                } => Event::Screenshot,
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F8),
+                    ..
+                } => Event::ToggleTurbo,
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F7),
+                    ..
+                } => Event::ToggleDebugOverlay,
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F6),
+                    ..
+                } => Event::ToggleScalingFilter,
                 E::KeyDown {
                     keycode: Some(sdl2::keyboard::Keycode::Backspace),
                     ..
@@ -605,6 +688,15 @@ impl Window {
             })
         }
 
+        if controller_updated && options.left_stick != LeftStickMode::Tilt {
+            let (x, y, _) = self.get_controller_stick(options, true);
+            for (phase, finger, coords) in self.gamepad_to_touch.left_stick_moved(options, (x, y)) {
+                let coords = transform_input_coords(self, coords, true);
+                self.event_queue
+                    .push_back(touch_event(phase, finger, coords));
+            }
+        }
+
         if controller_updated {
             let (new_x, new_y, pressed, pressed_changed, moved) =
                 self.update_virtual_cursor(options);
@@ -693,8 +785,14 @@ impl Window {
             }
         }
 
-        // Get left analog stick input. The range is [-1, 1] on each axis.
-        let (x, y, _) = self.get_controller_stick(options, true);
+        // Get left analog stick input. The range is [-1, 1] on each axis. If
+        // the stick is being used for touch input instead, the device is held
+        // level.
+        let (x, y, _) = if options.left_stick == LeftStickMode::Tilt {
+            self.get_controller_stick(options, true)
+        } else {
+            (0.0, 0.0, false)
+        };
 
         // Correct for window rotation
         let [x, y] = self.rotation_matrix().transform([x, y]);
@@ -963,19 +1061,14 @@ impl Window {
                 gles11::UNSIGNED_BYTE,
                 image.pixels().as_ptr() as *const _,
             );
-            gl_ctx.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MIN_FILTER,
-                gles11::LINEAR as _,
-            );
-            gl_ctx.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MAG_FILTER,
-                gles11::LINEAR as _,
-            );
 
             present_frame(
-                gl_ctx, viewport, matrix, /* virtual_cursor_visible_at: */ None,
+                gl_ctx,
+                viewport,
+                matrix,
+                self.scaling_filter,
+                /* virtual_cursor_visible_at: */ None,
+                /* debug_overlay: */ None,
             );
 
             gl_ctx.DeleteTextures(1, &texture);
@@ -1090,24 +1183,24 @@ impl Window {
             return (0, 0, app_width, app_height);
         }
 
-        let (screen_width, screen_height) = self.window.drawable_size();
+        fit_to_screen(
+            (app_width, app_height),
+            self.window.drawable_size(),
+            self.integer_scaling,
+        )
+    }
 
-        let app_aspect = app_width as f32 / app_height as f32;
-        let screen_aspect = screen_width as f32 / screen_height as f32;
-        let (scaled_width, scaled_height) = if app_aspect < screen_aspect {
-            (
-                (screen_height as f32 * app_aspect).round() as u32,
-                screen_height,
-            )
-        } else {
-            (
-                screen_width,
-                (screen_width as f32 / app_aspect).round() as u32,
-            )
-        };
-        let x = (screen_width - scaled_width) / 2;
-        let y = (screen_height - scaled_height) / 2;
-        (x, y, scaled_width, scaled_height)
+    /// Get the filter used when scaling the app's output to the viewport.
+    pub fn scaling_filter(&self) -> ScalingFilter {
+        self.scaling_filter
+    }
+    /// Switch between linear and nearest-neighbor scaling.
+    pub fn toggle_scaling_filter(&mut self) {
+        self.scaling_filter = self.scaling_filter.toggled();
+        echo!("Scaling filter is now {:?}.", self.scaling_filter);
+        if self.splash_image.is_some() {
+            self.display_splash();
+        }
     }
 
     /// Special offset to add to y co-ordinates, only when drawing to screen.
@@ -1130,6 +1223,21 @@ impl Window {
         }
     }
 
+    /// Get the text on the host clipboard, if there is any.
+    pub fn clipboard_text(&self) -> Option<String> {
+        let clipboard = self.video_ctx.clipboard();
+        if !clipboard.has_clipboard_text() {
+            return None;
+        }
+        clipboard.clipboard_text().ok()
+    }
+    /// Put text on the host clipboard.
+    pub fn set_clipboard_text(&self, text: &str) {
+        if let Err(e) = self.video_ctx.clipboard().set_clipboard_text(text) {
+            log!("Warning: couldn't set host clipboard text: {}", e);
+        }
+    }
+
     pub fn is_screen_saver_enabled(&self) -> bool {
         self.video_ctx.is_screen_saver_enabled()
     }
@@ -1144,3 +1252,59 @@ impl Window {
 pub fn open_url(url: &str) -> Result<(), String> {
     sdl2::url::open_url(url).map_err(|e| e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Which texel nearest-neighbor sampling picks for an output pixel, using
+    /// the rule from the OpenGL ES 1.1 spec (sampling at the pixel center).
+    fn nearest_texel(output_pixel: u32, output_size: u32, texture_size: u32) -> u32 {
+        let u = (output_pixel as f32 + 0.5) / output_size as f32;
+        ((u * texture_size as f32).floor() as u32).min(texture_size - 1)
+    }
+
+    #[test]
+    fn fit_to_screen_letterboxes() {
+        assert_eq!(
+            fit_to_screen((320, 480), (1920, 1080), false),
+            (600, 0, 720, 1080)
+        );
+        assert_eq!(
+            fit_to_screen((480, 320), (480, 640), false),
+            (0, 160, 480, 320)
+        );
+    }
+
+    #[test]
+    fn integer_scaling() {
+        // 1080 / 480 = 2.25, so only 2× fits.
+        assert_eq!(
+            fit_to_screen((320, 480), (1920, 1080), true),
+            (640, 60, 640, 960)
+        );
+        // Too small for even 1×, so it has to be scaled down normally.
+        assert_eq!(
+            fit_to_screen((320, 480), (200, 300), true),
+            (0, 0, 200, 300)
+        );
+    }
+
+    #[test]
+    fn integer_scaling_gives_solid_pixel_blocks() {
+        let app_size = (320, 480);
+        let (_, _, width, height) = fit_to_screen(app_size, (1920, 1440), true);
+        assert_eq!((width, height), (960, 1440));
+        // Every source pixel becomes exactly a 3×3 block, with no output pixel
+        // sampling from a neighbor at the edges.
+        for (output_size, texture_size) in [(width, app_size.0), (height, app_size.1)] {
+            let mut counts = vec![0; texture_size as usize];
+            for output_pixel in 0..output_size {
+                let texel = nearest_texel(output_pixel, output_size, texture_size);
+                assert_eq!(texel, output_pixel / 3);
+                counts[texel as usize] += 1;
+            }
+            assert!(counts.iter().all(|&count| count == 3));
+        }
+    }
+}
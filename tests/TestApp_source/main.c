@@ -38,8 +38,12 @@ typedef __builtin_va_list va_list;
 typedef struct FILE FILE;
 FILE *fopen(const char *, const char *);
 int fclose(FILE *);
+size_t fread(void *, size_t, size_t, FILE *);
+int remove(const char *);
 int sscanf(const char *, const char *, ...);
 int printf(const char *, ...);
+int sprintf(char *, const char *, ...);
+int snprintf(char *, size_t, const char *, ...);
 int vsnprintf(char *, size_t, const char *, va_list);
 int swprintf(wchar_t *, size_t, const wchar_t *, ...);
 
@@ -51,12 +55,28 @@ void free(void *);
 void *malloc(size_t);
 void qsort(void *, size_t, size_t, int (*)(const void *, const void *));
 void *realloc(void *, size_t);
+int posix_memalign(void **, size_t, size_t);
+#define EINVAL 22
 double atof(const char *);
 float strtof(const char *, char **);
 unsigned long strtoul(const char *, char **, int);
+
+// <malloc/malloc.h>
+typedef struct _malloc_zone_t malloc_zone_t;
+size_t malloc_size(const void *);
+size_t malloc_good_size(size_t);
+malloc_zone_t *malloc_default_zone(void);
+malloc_zone_t *malloc_zone_from_ptr(const void *);
+void *malloc_zone_malloc(malloc_zone_t *, size_t);
 char *realpath(const char *, char *);
 size_t mbstowcs(wchar_t *, const char *, size_t);
 size_t wcstombs(char *, const wchar_t *, size_t);
+int rand(void);
+void srand(unsigned);
+long random(void);
+void srandom(unsigned);
+unsigned arc4random(void);
+unsigned arc4random_uniform(unsigned);
 
 // <string.h>
 void *memset(void *, int, size_t);
@@ -69,6 +89,7 @@ size_t strlcpy(char *, const char *, size_t);
 char *strchr(const char *s, int c);
 char *strrchr(const char *s, int c);
 size_t strlen(const char *);
+char *strstr(const char *, const char *);
 int strncmp(const char *, const char *, size_t);
 size_t strcspn(const char *, const char *);
 
@@ -79,8 +100,91 @@ int chdir(const char *);
 char *getcwd(char *, size_t);
 int usleep(useconds_t);
 
+// <mach/mach_time.h>
+typedef struct {
+  unsigned int numer;
+  unsigned int denom;
+} mach_timebase_info_data_t;
+int mach_timebase_info(mach_timebase_info_data_t *);
+unsigned long long mach_absolute_time(void);
+
+// <sys/sysctl.h>
+#define CTL_HW 6
+#define HW_NCPU 3
+int sysctl(int *, unsigned int, void *, size_t *, void *, size_t);
+int sysctlbyname(const char *, void *, size_t *, void *, size_t);
+
+// <netdb.h>
+#define AF_INET 2
+#define SOCK_STREAM 1
+#define AI_NUMERICHOST 0x4
+#define EAI_NONAME 8
+typedef unsigned int socklen_t;
+struct sockaddr_in {
+  unsigned char sin_len;
+  unsigned char sin_family;
+  unsigned short sin_port;
+  unsigned char sin_addr[4];
+  char sin_zero[8];
+};
+struct addrinfo {
+  int ai_flags;
+  int ai_family;
+  int ai_socktype;
+  int ai_protocol;
+  socklen_t ai_addrlen;
+  char *ai_canonname;
+  void *ai_addr;
+  struct addrinfo *ai_next;
+};
+struct hostent {
+  char *h_name;
+  char **h_aliases;
+  int h_addrtype;
+  int h_length;
+  char **h_addr_list;
+};
+int getaddrinfo(const char *, const char *, const struct addrinfo *,
+                struct addrinfo **);
+void freeaddrinfo(struct addrinfo *);
+struct hostent *gethostbyname(const char *);
+
+// <sys/socket.h>
+typedef long ssize_t;
+int socket(int, int, int);
+int bind(int, const void *, socklen_t);
+int listen(int, int);
+int accept(int, void *, socklen_t *);
+int connect(int, const void *, socklen_t);
+int getsockname(int, void *, socklen_t *);
+ssize_t send(int, const void *, size_t, int);
+ssize_t recv(int, void *, size_t, int);
+int close(int);
+#define EAGAIN 35
+
+// <sys/select.h>
+struct timeval {
+  long tv_sec;
+  int tv_usec;
+};
+typedef struct {
+  unsigned int fds_bits[32];
+} fd_set;
+int select(int, fd_set *, fd_set *, fd_set *, struct timeval *);
+
+// <sys/time.h> and <time.h>
+typedef long time_t;
+int gettimeofday(struct timeval *, void *);
+time_t time(time_t *);
+
 // <fcntl.h>
 #define O_CREAT 0x00000200
+#define O_NONBLOCK 0x00000004
+#define F_SETFD 2
+#define F_GETFL 3
+#define F_SETFL 4
+#define FD_CLOEXEC 1
+int fcntl(int, int, ...);
 
 // <pthread.h>
 typedef struct opaque_pthread_t opaque_pthread_t;
@@ -91,6 +195,54 @@ typedef struct opaque_pthread_attr_t *__pthread_attr_t;
 typedef __pthread_attr_t pthread_attr_t;
 int pthread_create(pthread_t *, const pthread_attr_t *, void *(*)(void *),
                    void *);
+pthread_t pthread_self(void);
+int pthread_join(pthread_t, void **);
+int pthread_detach(pthread_t);
+typedef struct {
+  long __sig;
+  char __opaque[40];
+} pthread_mutex_t;
+#define PTHREAD_MUTEX_INITIALIZER {0x32AAABA7, {0}}
+int pthread_mutex_lock(pthread_mutex_t *);
+int pthread_mutex_unlock(pthread_mutex_t *);
+typedef struct {
+  long __sig;
+  char __opaque[24];
+} pthread_cond_t;
+#define PTHREAD_COND_INITIALIZER {0x3CB0B1BB, {0}}
+int pthread_cond_wait(pthread_cond_t *, pthread_mutex_t *);
+int pthread_cond_broadcast(pthread_cond_t *);
+typedef unsigned long pthread_key_t;
+int pthread_key_create(pthread_key_t *, void (*)(void *));
+int pthread_key_delete(pthread_key_t);
+void *pthread_getspecific(pthread_key_t);
+int pthread_setspecific(pthread_key_t, const void *);
+
+// <Block.h>
+void *_Block_copy(const void *);
+void _Block_release(const void *);
+
+// <dispatch/dispatch.h>
+typedef struct dispatch_queue_s *dispatch_queue_t;
+typedef long dispatch_once_t;
+dispatch_queue_t dispatch_get_main_queue(void);
+void dispatch_async(dispatch_queue_t, void (^)(void));
+void dispatch_once(dispatch_once_t *, void (^)(void));
+typedef unsigned long long dispatch_time_t;
+#define DISPATCH_TIME_NOW 0ull
+#define DISPATCH_TIME_FOREVER (~0ull)
+dispatch_time_t dispatch_time(dispatch_time_t, long long);
+void dispatch_release(void *);
+dispatch_queue_t dispatch_get_global_queue(long, unsigned long);
+typedef struct dispatch_semaphore_s *dispatch_semaphore_t;
+dispatch_semaphore_t dispatch_semaphore_create(long);
+long dispatch_semaphore_wait(dispatch_semaphore_t, dispatch_time_t);
+long dispatch_semaphore_signal(dispatch_semaphore_t);
+typedef struct dispatch_group_s *dispatch_group_t;
+dispatch_group_t dispatch_group_create(void);
+void dispatch_group_async(dispatch_group_t, dispatch_queue_t, void (^)(void));
+long dispatch_group_wait(dispatch_group_t, dispatch_time_t);
+void dispatch_group_notify(dispatch_group_t, dispatch_queue_t, void (^)(void));
 
 // <semaphore.h>
 #define SEM_FAILED ((sem_t *)-1)
@@ -124,6 +276,43 @@ DIR *opendir(const char *);
 struct dirent *readdir(DIR *);
 int closedir(DIR *);
 
+// <CommonCrypto/CommonDigest.h>, <CommonCrypto/CommonHMAC.h> and
+// <CommonCrypto/CommonCryptor.h>
+typedef unsigned int CC_LONG;
+typedef struct {
+  CC_LONG A, B, C, D;
+  CC_LONG Nl, Nh;
+  CC_LONG data[16];
+  int num;
+} CC_MD5_CTX;
+unsigned char *CC_MD5(const void *, CC_LONG, unsigned char *);
+unsigned char *CC_SHA1(const void *, CC_LONG, unsigned char *);
+unsigned char *CC_SHA256(const void *, CC_LONG, unsigned char *);
+int CC_MD5_Init(CC_MD5_CTX *);
+int CC_MD5_Update(CC_MD5_CTX *, const void *, CC_LONG);
+int CC_MD5_Final(unsigned char *, CC_MD5_CTX *);
+#define kCCHmacAlgSHA1 0
+#define kCCHmacAlgMD5 1
+#define kCCHmacAlgSHA256 2
+void CCHmac(unsigned int, const void *, size_t, const void *, size_t, void *);
+typedef struct _CCCryptor *CCCryptorRef;
+#define kCCSuccess 0
+#define kCCBufferTooSmall -4301
+#define kCCDecodeError -4304
+#define kCCEncrypt 0
+#define kCCDecrypt 1
+#define kCCAlgorithmAES128 0
+#define kCCOptionPKCS7Padding 1
+#define kCCOptionECBMode 2
+int CCCrypt(unsigned int, unsigned int, unsigned int, const void *, size_t,
+            const void *, const void *, size_t, void *, size_t, size_t *);
+int CCCryptorCreate(unsigned int, unsigned int, unsigned int, const void *,
+                    size_t, const void *, CCCryptorRef *);
+int CCCryptorUpdate(CCCryptorRef, const void *, size_t, void *, size_t,
+                    size_t *);
+int CCCryptorFinal(CCCryptorRef, void *, size_t, size_t *);
+int CCCryptorRelease(CCCryptorRef);
+
 // `CFBase.h`
 
 typedef const struct _CFAllocator *CFAllocatorRef;
@@ -137,6 +326,9 @@ typedef unsigned long CFOptionFlags;
 typedef const struct _CFDictionary *CFDictionaryRef;
 typedef const struct _CFString *CFStringRef;
 typedef const struct _CFString *CFMutableStringRef;
+typedef const void *CFTypeRef;
+CFTypeRef CFRetain(CFTypeRef);
+void CFRelease(CFTypeRef);
 
 // `CFString.h`
 
@@ -152,6 +344,295 @@ CFComparisonResult CFStringCompare(CFStringRef a, CFStringRef b,
                                    CFStringCompareFlags flags);
 CFRange CFStringFind(CFStringRef theString, CFStringRef stringToFind,
                      CFOptionFlags compareOptions);
+CFIndex CFStringGetLength(CFStringRef theString);
+int CFStringGetCString(CFStringRef theString, char *buffer,
+                       CFIndex bufferSize, CFStringEncoding encoding);
+const char *CFStringGetCStringPtr(CFStringRef theString,
+                                  CFStringEncoding encoding);
+
+// `CFArray.h`
+
+typedef const struct _CFArray *CFArrayRef;
+typedef struct {
+  CFIndex version;
+  const void *retain;
+  const void *release;
+  const void *copyDescription;
+  const void *equal;
+} CFArrayCallBacks;
+extern const CFArrayCallBacks kCFTypeArrayCallBacks;
+
+CFArrayRef CFArrayCreate(CFAllocatorRef allocator, const void **values,
+                         CFIndex numValues, const CFArrayCallBacks *callBacks);
+CFArrayRef CFArrayCreateMutable(CFAllocatorRef allocator, CFIndex capacity,
+                                const CFArrayCallBacks *callBacks);
+CFIndex CFArrayGetCount(CFArrayRef theArray);
+const void *CFArrayGetValueAtIndex(CFArrayRef theArray, CFIndex idx);
+
+// `CFDictionary.h`
+
+typedef const struct _CFDictionary *CFMutableDictionaryRef;
+typedef struct {
+  CFIndex version;
+  const void *retain;
+  const void *release;
+  const void *copyDescription;
+  const void *equal;
+  const void *hash;
+} CFDictionaryKeyCallBacks;
+typedef struct {
+  CFIndex version;
+  const void *retain;
+  const void *release;
+  const void *copyDescription;
+  const void *equal;
+} CFDictionaryValueCallBacks;
+extern const CFDictionaryKeyCallBacks kCFTypeDictionaryKeyCallBacks;
+extern const CFDictionaryValueCallBacks kCFTypeDictionaryValueCallBacks;
+
+CFDictionaryRef
+CFDictionaryCreate(CFAllocatorRef allocator, const void **keys,
+                   const void **values, CFIndex numValues,
+                   const CFDictionaryKeyCallBacks *keyCallBacks,
+                   const CFDictionaryValueCallBacks *valueCallBacks);
+CFMutableDictionaryRef
+CFDictionaryCreateMutable(CFAllocatorRef allocator, CFIndex capacity,
+                          const CFDictionaryKeyCallBacks *keyCallBacks,
+                          const CFDictionaryValueCallBacks *valueCallBacks);
+CFIndex CFDictionaryGetCount(CFDictionaryRef theDict);
+const void *CFDictionaryGetValue(CFDictionaryRef theDict, const void *key);
+void CFDictionarySetValue(CFMutableDictionaryRef theDict, const void *key,
+                          const void *value);
+
+// `CFRunLoop.h`
+
+typedef double CFTimeInterval;
+typedef CFTimeInterval CFAbsoluteTime;
+typedef struct __CFRunLoop *CFRunLoopRef;
+typedef CFStringRef CFRunLoopMode;
+typedef struct __CFRunLoopTimer *CFRunLoopTimerRef;
+typedef struct __CFRunLoopSource *CFRunLoopSourceRef;
+typedef struct {
+  CFIndex version;
+  void *info;
+  const void *(*retain)(const void *);
+  void (*release)(const void *);
+  CFStringRef (*copyDescription)(const void *);
+} CFRunLoopTimerContext;
+typedef struct {
+  CFIndex version;
+  void *info;
+  const void *(*retain)(const void *);
+  void (*release)(const void *);
+  CFStringRef (*copyDescription)(const void *);
+  int (*equal)(const void *, const void *);
+  unsigned long (*hash)(const void *);
+  void (*schedule)(void *, CFRunLoopRef, CFRunLoopMode);
+  void (*cancel)(void *, CFRunLoopRef, CFRunLoopMode);
+  void (*perform)(void *);
+} CFRunLoopSourceContext;
+#define kCFRunLoopRunStopped 2
+#define kCFRunLoopRunTimedOut 3
+#define kCFRunLoopRunHandledSource 4
+CFAbsoluteTime CFAbsoluteTimeGetCurrent(void);
+CFRunLoopRef CFRunLoopGetCurrent(void);
+int CFRunLoopRunInMode(CFRunLoopMode, CFTimeInterval, int);
+void CFRunLoopStop(CFRunLoopRef);
+CFRunLoopTimerRef CFRunLoopTimerCreate(CFAllocatorRef, CFAbsoluteTime,
+                                       CFTimeInterval, CFOptionFlags, CFIndex,
+                                       void (*)(CFRunLoopTimerRef, void *),
+                                       CFRunLoopTimerContext *);
+void CFRunLoopAddTimer(CFRunLoopRef, CFRunLoopTimerRef, CFRunLoopMode);
+CFRunLoopSourceRef CFRunLoopSourceCreate(CFAllocatorRef, CFIndex,
+                                         CFRunLoopSourceContext *);
+void CFRunLoopAddSource(CFRunLoopRef, CFRunLoopSourceRef, CFRunLoopMode);
+void CFRunLoopRemoveSource(CFRunLoopRef, CFRunLoopSourceRef, CFRunLoopMode);
+void CFRunLoopSourceSignal(CFRunLoopSourceRef);
+
+// `CFDate.h` and `CFTimeZone.h`
+
+typedef const struct __CFTimeZone *CFTimeZoneRef;
+typedef struct {
+  int year;
+  signed char month;
+  signed char day;
+  signed char hours;
+  signed char minutes;
+  double seconds;
+} CFGregorianDate;
+CFGregorianDate CFAbsoluteTimeGetGregorianDate(CFAbsoluteTime, CFTimeZoneRef);
+
+// `CFURL.h`
+
+typedef const struct __CFURL *CFURLRef;
+typedef CFIndex CFURLPathStyle;
+CFStringRef CFURLCopyFileSystemPath(CFURLRef anURL, CFURLPathStyle pathStyle);
+
+// `CFBundle.h`
+
+typedef struct __CFBundle *CFBundleRef;
+CFBundleRef CFBundleGetMainBundle(void);
+CFURLRef CFBundleCopyResourceURL(CFBundleRef bundle, CFStringRef resourceName,
+                                 CFStringRef resourceType,
+                                 CFStringRef subDirName);
+CFTypeRef CFBundleGetValueForInfoDictionaryKey(CFBundleRef bundle,
+                                               CFStringRef key);
+CFStringRef CFBundleGetIdentifier(CFBundleRef bundle);
+
+// `CFPreferences.h`
+
+extern const CFStringRef kCFPreferencesCurrentApplication;
+CFTypeRef CFPreferencesCopyAppValue(CFStringRef key,
+                                    CFStringRef applicationID);
+void CFPreferencesSetAppValue(CFStringRef key, CFTypeRef value,
+                              CFStringRef applicationID);
+unsigned char CFPreferencesAppSynchronize(CFStringRef applicationID);
+
+// `CGColorSpace.h` and `CGColor.h`
+
+typedef struct CGColorSpace *CGColorSpaceRef;
+typedef struct CGColor *CGColorRef;
+CGColorSpaceRef CGColorSpaceCreateDeviceRGB(void);
+void CGColorSpaceRelease(CGColorSpaceRef);
+size_t CGColorSpaceGetNumberOfComponents(CGColorSpaceRef);
+CGColorRef CGColorCreate(CGColorSpaceRef, const CGFloat *);
+void CGColorRelease(CGColorRef);
+size_t CGColorGetNumberOfComponents(CGColorRef);
+const CGFloat *CGColorGetComponents(CGColorRef);
+CGFloat CGColorGetAlpha(CGColorRef);
+CGColorSpaceRef CGColorGetColorSpace(CGColorRef);
+
+// `CGContext.h` and `CGBitmapContext.h`
+
+typedef struct CGContext *CGContextRef;
+CGContextRef CGBitmapContextCreate(void *data, size_t width, size_t height,
+                                   size_t bitsPerComponent, size_t bytesPerRow,
+                                   CGColorSpaceRef space,
+                                   unsigned int bitmapInfo);
+void CGContextRelease(CGContextRef);
+void CGContextSetRGBFillColor(CGContextRef, CGFloat, CGFloat, CGFloat,
+                              CGFloat);
+void CGContextFillRect(CGContextRef, CGRect);
+void CGContextSaveGState(CGContextRef);
+void CGContextRestoreGState(CGContextRef);
+void CGContextClipToRect(CGContextRef, CGRect);
+void CGContextSetAlpha(CGContextRef, CGFloat);
+void CGContextTranslateCTM(CGContextRef, CGFloat, CGFloat);
+void CGContextScaleCTM(CGContextRef, CGFloat, CGFloat);
+void CGContextConcatCTM(CGContextRef, CGAffineTransform);
+CGAffineTransform CGContextGetCTM(CGContextRef);
+typedef struct CGImage *CGImageRef;
+CGImageRef CGBitmapContextCreateImage(CGContextRef);
+void CGImageRelease(CGImageRef);
+void CGContextDrawImage(CGContextRef, CGRect, CGImageRef);
+void CGContextDrawTiledImage(CGContextRef, CGRect, CGImageRef);
+void *CGBitmapContextGetData(CGContextRef);
+void CGContextFillPath(CGContextRef);
+
+// `CGGradient.h`
+
+typedef struct CGGradient *CGGradientRef;
+CGGradientRef CGGradientCreateWithColorComponents(CGColorSpaceRef,
+                                                  const CGFloat *,
+                                                  const CGFloat *, size_t);
+void CGGradientRelease(CGGradientRef);
+void CGContextDrawLinearGradient(CGContextRef, CGGradientRef, CGPoint, CGPoint,
+                                 unsigned int);
+
+// `CGPath.h`
+
+typedef struct CGPath *CGMutablePathRef;
+typedef const struct CGPath *CGPathRef;
+CGMutablePathRef CGPathCreateMutable(void);
+void CGPathRelease(CGPathRef);
+void CGPathMoveToPoint(CGMutablePathRef, const CGAffineTransform *, CGFloat,
+                       CGFloat);
+void CGPathAddLineToPoint(CGMutablePathRef, const CGAffineTransform *, CGFloat,
+                          CGFloat);
+void CGPathAddRect(CGMutablePathRef, const CGAffineTransform *, CGRect);
+void CGPathAddEllipseInRect(CGMutablePathRef, const CGAffineTransform *,
+                            CGRect);
+void CGPathAddArc(CGMutablePathRef, const CGAffineTransform *, CGFloat,
+                  CGFloat, CGFloat, CGFloat, CGFloat, bool);
+void CGPathCloseSubpath(CGMutablePathRef);
+void CGPathAddPath(CGMutablePathRef, const CGAffineTransform *, CGPathRef);
+bool CGPathIsEmpty(CGPathRef);
+bool CGPathContainsPoint(CGPathRef, const CGAffineTransform *, CGPoint, bool);
+CGRect CGPathGetBoundingBox(CGPathRef);
+void CGContextAddPath(CGContextRef, CGPathRef);
+
+// `UIGraphics.h` and `UIGeometry.h`
+
+void UIGraphicsBeginImageContext(CGSize);
+CGContextRef UIGraphicsGetCurrentContext(void);
+id UIGraphicsGetImageFromCurrentImageContext(void);
+void UIGraphicsEndImageContext(void);
+id UIImagePNGRepresentation(id);
+id UIImageJPEGRepresentation(id, CGFloat);
+bool UIGraphicsBeginPDFContextToFile(id, CGRect, id);
+void UIGraphicsBeginPDFPage(void);
+void UIGraphicsEndPDFContext(void);
+typedef struct {
+  CGFloat top, left, bottom, right;
+} UIEdgeInsets;
+
+// `SCNetworkReachability.h`
+
+typedef const struct __SCNetworkReachability *SCNetworkReachabilityRef;
+typedef unsigned int SCNetworkReachabilityFlags;
+#define kSCNetworkReachabilityFlagsReachable (1 << 1)
+SCNetworkReachabilityRef SCNetworkReachabilityCreateWithName(CFAllocatorRef,
+                                                             const char *);
+bool SCNetworkReachabilityGetFlags(SCNetworkReachabilityRef,
+                                   SCNetworkReachabilityFlags *);
+
+// `CTFontManager.h`
+
+// `ABAddressBook.h`
+
+typedef CFTypeRef ABAddressBookRef;
+typedef CFTypeRef CFErrorRef;
+ABAddressBookRef ABAddressBookCreate(void);
+CFIndex ABAddressBookGetAuthorizationStatus(void);
+void ABAddressBookRequestAccessWithCompletion(ABAddressBookRef,
+                                              void (^)(bool, CFErrorRef));
+CFIndex ABAddressBookGetPersonCount(ABAddressBookRef);
+CFArrayRef ABAddressBookCopyArrayOfAllPeople(ABAddressBookRef);
+
+// `CLLocation.h` and `MKGeometry.h`
+
+typedef struct {
+  double latitude, longitude;
+} CLLocationCoordinate2D;
+typedef struct {
+  double latitudeDelta, longitudeDelta;
+} MKCoordinateSpan;
+typedef struct {
+  CLLocationCoordinate2D center;
+  MKCoordinateSpan span;
+} MKCoordinateRegion;
+
+typedef unsigned int CTFontManagerScope;
+bool CTFontManagerRegisterFontsForURL(CFURLRef fontURL,
+                                      CTFontManagerScope scope, void *error);
+
+// `objc.h`, `message.h` and `NSObjCRuntime.h`
+// Objective-C can't be compiled for the TestApp (see README.md), so messages
+// must be sent by calling objc_msgSend() directly.
+
+typedef struct objc_object *id;
+typedef struct objc_selector *SEL;
+typedef unsigned int NSUInteger;
+typedef struct {
+  NSUInteger location;
+  NSUInteger length;
+} NSRange;
+id objc_msgSend(id, SEL, ...);
+void objc_msgSend_stret(void *, id, SEL, ...);
+SEL sel_registerName(const char *);
+typedef id (*IMP)(id, SEL, ...);
+bool class_addMethod(id, SEL, IMP, const char *);
+id NSClassFromString(CFStringRef);
 
 // === Main code ===
 
@@ -299,6 +780,42 @@ int test_vsnprintf() {
   return res;
 }
 
+int test_sprintf() {
+  char buf[32];
+  int len = sprintf(buf, "%d-%s-%.2f", 42, "abc", 3.14159);
+  if (len != 11 || strcmp(buf, "42-abc-3.14") != 0) {
+    return -1;
+  }
+
+  // Truncated output is still null-terminated, and the return value is the
+  // length the whole output would have had.
+  memset(buf, 'x', sizeof(buf));
+  len = snprintf(buf, 5, "%d-%s-%.2f", 42, "abc", 3.14159);
+  if (len != 11 || strcmp(buf, "42-a") != 0 || buf[5] != 'x') {
+    return -2;
+  }
+  // A size of zero writes nothing, so the buffer may be NULL.
+  if (snprintf(NULL, 0, "%d", 12345) != 5) {
+    return -3;
+  }
+
+  // Flags, length modifiers and field widths.
+  sprintf(buf, "[%-4d|%+d|% d|%-6s|%.2s|%3c]", 7, 7, 7, "ab", "xyz", 'q');
+  if (strcmp(buf, "[7   |+7| 7|ab    |xy|  q]") != 0) {
+    return -4;
+  }
+  sprintf(buf, "%#x|%#o|%08X|%-5x|", 255, 8, 0xBEEF, 0xa);
+  if (strcmp(buf, "0xff|010|0000BEEF|a    |") != 0) {
+    return -5;
+  }
+  sprintf(buf, "%lld|%llu|%hd|%05d", -5000000000LL, 5000000000ULL, 70000,
+          -42);
+  if (strcmp(buf, "-5000000000|5000000000|4464|-0042") != 0) {
+    return -6;
+  }
+  return 0;
+}
+
 int test_sscanf() {
   int a, b;
   short c, d;
@@ -344,6 +861,72 @@ int test_realloc() {
   return res == 0 ? 0 : -1;
 }
 
+int test_malloc_size() {
+  char *ptr = malloc(100);
+  size_t size = malloc_size(ptr);
+  if (size < 100 || size != malloc_good_size(100)) {
+    return -1;
+  }
+  // The whole reported size is usable.
+  memset(ptr, 'a', size);
+  free(ptr);
+
+  // Pointers that weren't allocated by malloc have no size.
+  int not_malloced;
+  if (malloc_size(&not_malloced) != 0 ||
+      malloc_zone_from_ptr(&not_malloced) != NULL) {
+    return -2;
+  }
+
+  malloc_zone_t *zone = malloc_default_zone();
+  if (!zone) {
+    return -3;
+  }
+  ptr = malloc_zone_malloc(zone, 10);
+  if (malloc_size(ptr) < 10 || malloc_zone_from_ptr(ptr) != zone) {
+    return -4;
+  }
+  free(ptr);
+  return 0;
+}
+
+int test_posix_memalign() {
+  size_t sizes[] = {1, 16, 63, 64, 100, 4096};
+  void *ptrs[6];
+  for (int i = 0; i < 6; i++) {
+    if (posix_memalign(&ptrs[i], 64, sizes[i]) != 0) {
+      return -1;
+    }
+    if ((unsigned long)ptrs[i] % 64 != 0) {
+      return -2;
+    }
+    memset(ptrs[i], 0xAB, sizes[i]);
+  }
+  for (int i = 0; i < 6; i++) {
+    free(ptrs[i]);
+  }
+
+  // Reallocating keeps the contents, though not necessarily the alignment.
+  void *ptr;
+  if (posix_memalign(&ptr, 256, 8) != 0 || (unsigned long)ptr % 256 != 0) {
+    return -3;
+  }
+  memmove(ptr, "abcdefg", 8);
+  ptr = realloc(ptr, 1000);
+  if (memcmp(ptr, "abcdefg", 8) != 0) {
+    return -4;
+  }
+  free(ptr);
+
+  // The alignment must be a power of two multiple of sizeof(void *).
+  ptr = NULL;
+  if (posix_memalign(&ptr, 48, 16) != EINVAL ||
+      posix_memalign(&ptr, 2, 16) != EINVAL || ptr != NULL) {
+    return -5;
+  }
+  return 0;
+}
+
 int test_atof() {
   if (atof("1") != 1)
     return -1;
@@ -893,6 +1476,162 @@ int test_mbstowcs() {
   return 0;
 }
 
+// Compare a digest against its hexadecimal form.
+int digest_matches(const unsigned char *digest, const char *hex) {
+  char buffer[3];
+  for (size_t i = 0; i < strlen(hex) / 2; i++) {
+    snprintf(buffer, sizeof(buffer), "%02x", digest[i]);
+    if (strncmp(buffer, hex + i * 2, 2) != 0) {
+      return 0;
+    }
+  }
+  return 1;
+}
+
+int test_CommonCrypto() {
+  const char *abc = "abc";
+  const char *long_input =
+      "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+  unsigned char md[32];
+
+  // Known vectors from RFC 1321 and FIPS 180.
+  if (CC_MD5(abc, 3, md) != md ||
+      !digest_matches(md, "900150983cd24fb0d6963f7d28e17f72")) {
+    return -1;
+  }
+  CC_MD5("", 0, md);
+  if (!digest_matches(md, "d41d8cd98f00b204e9800998ecf8427e")) {
+    return -2;
+  }
+  CC_SHA1(long_input, strlen(long_input), md);
+  if (!digest_matches(md, "84983e441c3bd26ebaae4aa1f95129e5e54670f1")) {
+    return -3;
+  }
+  CC_SHA256(abc, 3, md);
+  if (!digest_matches(md, "ba7816bf8f01cfea414140de5dae2223"
+                          "b00361a396177a9cb410ff61f20015ad")) {
+    return -4;
+  }
+
+  // Streaming in uneven pieces gives the same result as one-shot.
+  const char *digits = "1234567890";
+  CC_MD5_CTX ctx;
+  CC_MD5_Init(&ctx);
+  for (int i = 0; i < 8; i++) {
+    CC_MD5_Update(&ctx, digits, 3);
+    CC_MD5_Update(&ctx, digits + 3, 7);
+  }
+  CC_MD5_Final(md, &ctx);
+  if (!digest_matches(md, "57edf4a22be3c955ac49da2e2107b67a")) {
+    return -5;
+  }
+
+  // RFC 2202 and RFC 4231 test case 2.
+  const char *key = "Jefe";
+  const char *data = "what do ya want for nothing?";
+  CCHmac(kCCHmacAlgMD5, key, 4, data, strlen(data), md);
+  if (!digest_matches(md, "750c783e6ab0b503eaa86e310a5db738")) {
+    return -6;
+  }
+  CCHmac(kCCHmacAlgSHA1, key, 4, data, strlen(data), md);
+  if (!digest_matches(md, "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79")) {
+    return -7;
+  }
+  CCHmac(kCCHmacAlgSHA256, key, 4, data, strlen(data), md);
+  if (!digest_matches(md, "5bdcc146bf60754e6a042426089575c7"
+                          "5a003f089d2739839dec58b964ec3843")) {
+    return -8;
+  }
+  return 0;
+}
+
+int test_CCCrypt() {
+  // NIST SP 800-38A F.2.1 (AES-128-CBC)
+  const unsigned char key[16] = {0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae,
+                                 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88,
+                                 0x09, 0xcf, 0x4f, 0x3c};
+  unsigned char iv[16];
+  for (int i = 0; i < 16; i++) {
+    iv[i] = i;
+  }
+  const unsigned char plaintext[32] = {
+      0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e,
+      0x11, 0x73, 0x93, 0x17, 0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03,
+      0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf, 0x8e, 0x51};
+  unsigned char encrypted[48];
+  unsigned char decrypted[48];
+  size_t moved = 0;
+
+  int status = CCCrypt(kCCEncrypt, kCCAlgorithmAES128, 0, key, 16, iv,
+                       plaintext, 32, encrypted, sizeof(encrypted), &moved);
+  if (status != kCCSuccess || moved != 32 ||
+      !digest_matches(encrypted, "7649abac8119b246cee98e9b12e9197d"
+                                 "5086cb9b507219ee95db113a917678b2")) {
+    return -1;
+  }
+  status = CCCrypt(kCCDecrypt, kCCAlgorithmAES128, 0, key, 16, iv, encrypted,
+                   32, decrypted, sizeof(decrypted), &moved);
+  if (status != kCCSuccess || moved != 32 ||
+      memcmp(decrypted, plaintext, 32) != 0) {
+    return -2;
+  }
+
+  // With padding, a whole extra block is added, and the output buffer must
+  // have room for it.
+  status = CCCrypt(kCCEncrypt, kCCAlgorithmAES128, kCCOptionPKCS7Padding, key,
+                   16, iv, plaintext, 32, encrypted, 32, &moved);
+  if (status != kCCBufferTooSmall || moved != 48) {
+    return -3;
+  }
+  status = CCCrypt(kCCEncrypt, kCCAlgorithmAES128, kCCOptionPKCS7Padding, key,
+                   16, iv, plaintext, 32, encrypted, 48, &moved);
+  if (status != kCCSuccess || moved != 48 ||
+      !digest_matches(encrypted, "7649abac8119b246cee98e9b12e9197d")) {
+    return -4;
+  }
+
+  // Streaming decryption in uneven pieces.
+  CCCryptorRef cryptor = NULL;
+  status = CCCryptorCreate(kCCDecrypt, kCCAlgorithmAES128,
+                           kCCOptionPKCS7Padding, key, 16, iv, &cryptor);
+  if (status != kCCSuccess || cryptor == NULL) {
+    return -5;
+  }
+  size_t total = 0;
+  size_t pieces[3] = {5, 27, 16};
+  const unsigned char *in = encrypted;
+  for (int i = 0; i < 3; i++) {
+    status = CCCryptorUpdate(cryptor, in, pieces[i], decrypted + total,
+                             sizeof(decrypted) - total, &moved);
+    if (status != kCCSuccess) {
+      return -6;
+    }
+    in += pieces[i];
+    total += moved;
+    // The last complete block is held back, because it might be padding.
+    if (i == 1 && total != 16) {
+      return -7;
+    }
+  }
+  status = CCCryptorFinal(cryptor, decrypted + total,
+                          sizeof(decrypted) - total, &moved);
+  CCCryptorRelease(cryptor);
+  if (status != kCCSuccess || total != 32 || moved != 0 ||
+      memcmp(decrypted, plaintext, 32) != 0) {
+    return -8;
+  }
+
+  // Decrypting with the wrong key fails on the padding.
+  unsigned char wrong_key[32] = {0};
+  status = CCCrypt(kCCDecrypt, kCCAlgorithmAES128, kCCOptionPKCS7Padding,
+                   wrong_key, 32, iv, encrypted, 48, decrypted,
+                   sizeof(decrypted), &moved);
+  if (status != kCCDecodeError) {
+    return -9;
+  }
+  return 0;
+}
+
 int test_CFMutableString() {
   CFMutableStringRef mut_str = CFStringCreateMutable(NULL, 0);
   CFStringRef fmt = CFStringCreateWithCString(NULL, "%d %.2f", 0x0600);
@@ -904,60 +1643,4998 @@ int test_CFMutableString() {
   return 0;
 }
 
-#define FUNC_DEF(func)                                                         \
-  { &func, #func }
-struct {
-  int (*func)();
-  const char *name;
-} test_func_array[] = {
-    FUNC_DEF(test_qsort),
-    FUNC_DEF(test_vsnprintf),
-    FUNC_DEF(test_sscanf),
-    FUNC_DEF(test_errno),
-    FUNC_DEF(test_realloc),
-    FUNC_DEF(test_atof),
-    FUNC_DEF(test_strtof),
-    FUNC_DEF(test_getcwd_chdir),
-    FUNC_DEF(test_sem),
-    FUNC_DEF(test_CGAffineTransform),
-    FUNC_DEF(test_strncpy),
-    FUNC_DEF(test_strncat),
-    FUNC_DEF(test_strlcpy),
-    FUNC_DEF(test_setlocale),
-    FUNC_DEF(test_strtoul),
-    FUNC_DEF(test_dirent),
-    FUNC_DEF(test_strchr),
-    FUNC_DEF(test_swprintf),
-    FUNC_DEF(test_realpath),
-    FUNC_DEF(test_CFStringFind),
-    FUNC_DEF(test_strcspn),
-    FUNC_DEF(test_mbstowcs),
-    FUNC_DEF(test_CFMutableString),
-};
+id msg0(id receiver, const char *selector) {
+  return objc_msgSend(receiver, sel_registerName(selector));
+}
 
-// Because no libc is linked into this executable, there is no libc entry point
-// to call main. Instead, integration.rs tells Clang to set the _main symbol
-// as the entry point. (It has to be _main because a C compiler will throw
-// away stuff not called by main().) Since this is the true entry point, there's
-// no argc or argv and we must call exit() ourselves.
-int main() {
-  int tests_run = 0;
-  int tests_passed = 0;
+id new_object(const char *class_name) {
+  CFStringRef name = CFStringCreateWithCString(NULL, class_name, 0x0600);
+  return msg0(msg0(NSClassFromString(name), "alloc"), "init");
+}
 
-  int n = sizeof(test_func_array) / sizeof(test_func_array[0]);
-  int i;
-  for (i = 0; i < n; i++) {
-    printf("%s: ", test_func_array[i].name);
-    tests_run++;
-    int latest_test_result = test_func_array[i].func();
-    if (latest_test_result == 0) {
-      printf("OK\n");
-      tests_passed++;
-    } else {
-      printf("FAIL (%d)\n", latest_test_result);
+id new_string(const char *c_string) {
+  return (id)CFStringCreateWithCString(NULL, c_string, 0x0600);
+}
+
+id new_view(CGFloat x, CGFloat y, CGFloat width, CGFloat height) {
+  CGRect frame = {{x, y}, {width, height}};
+  CFStringRef name = CFStringCreateWithCString(NULL, "UIView", 0x0600);
+  id view = msg0(NSClassFromString(name), "alloc");
+  return ((id(*)(id, SEL, CGRect))objc_msgSend)(
+      view, sel_registerName("initWithFrame:"), frame);
+}
+
+int test_NSCache() {
+  id cache = new_object("NSCache");
+  ((void (*)(id, SEL, NSUInteger))objc_msgSend)(
+      cache, sel_registerName("setCountLimit:"), 2);
+  SEL set_sel = sel_registerName("setObject:forKey:");
+  SEL get_sel = sel_registerName("objectForKey:");
+  id a = new_object("NSObject");
+  id b = new_object("NSObject");
+  id c = new_object("NSObject");
+  id d = new_object("NSObject");
+
+  // Past the count limit, the oldest entry is evicted.
+  objc_msgSend(cache, set_sel, a, new_string("a"));
+  objc_msgSend(cache, set_sel, b, new_string("b"));
+  objc_msgSend(cache, set_sel, c, new_string("c"));
+  if (objc_msgSend(cache, get_sel, new_string("a")) != NULL ||
+      objc_msgSend(cache, get_sel, new_string("b")) != b ||
+      objc_msgSend(cache, get_sel, new_string("c")) != c) {
+    return -1;
+  }
+
+  // Looking up an entry makes it recently used, so "c" is evicted next.
+  objc_msgSend(cache, get_sel, new_string("b"));
+  objc_msgSend(cache, set_sel, d, new_string("d"));
+  if (objc_msgSend(cache, get_sel, new_string("c")) != NULL ||
+      objc_msgSend(cache, get_sel, new_string("b")) != b ||
+      objc_msgSend(cache, get_sel, new_string("d")) != d) {
+    return -2;
+  }
+
+  // The cost limit works the same way.
+  msg0(cache, "removeAllObjects");
+  ((void (*)(id, SEL, NSUInteger))objc_msgSend)(
+      cache, sel_registerName("setTotalCostLimit:"), 10);
+  SEL set_cost_sel = sel_registerName("setObject:forKey:cost:");
+  ((void (*)(id, SEL, id, id, NSUInteger))objc_msgSend)(
+      cache, set_cost_sel, a, new_string("a"), 6);
+  ((void (*)(id, SEL, id, id, NSUInteger))objc_msgSend)(
+      cache, set_cost_sel, b, new_string("b"), 6);
+  if (objc_msgSend(cache, get_sel, new_string("a")) != NULL ||
+      objc_msgSend(cache, get_sel, new_string("b")) != b) {
+    return -3;
+  }
+
+  objc_msgSend(cache, sel_registerName("removeObjectForKey:"),
+               new_string("b"));
+  if (objc_msgSend(cache, get_sel, new_string("b")) != NULL) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_NSMutableDictionary() {
+  id dict = new_object("NSMutableDictionary");
+  id other = new_object("NSMutableDictionary");
+  id v1 = new_object("NSObject");
+  id v2 = new_object("NSObject");
+  id v3 = new_object("NSObject");
+  id v4 = new_object("NSObject");
+  SEL set_sel = sel_registerName("setObject:forKey:");
+  SEL get_sel = sel_registerName("objectForKey:");
+  objc_msgSend(dict, set_sel, v1, new_string("a"));
+  objc_msgSend(dict, set_sel, v2, new_string("b"));
+  objc_msgSend(other, set_sel, v3, new_string("b"));
+  objc_msgSend(other, set_sel, v4, new_string("c"));
+  objc_msgSend(dict, sel_registerName("addEntriesFromDictionary:"), other);
+  // Lookups use separate but equal strings, so this checks hashing too.
+  if ((NSUInteger)msg0(dict, "count") != 3 ||
+      objc_msgSend(dict, get_sel, new_string("a")) != v1 ||
+      objc_msgSend(dict, get_sel, new_string("b")) != v3 ||
+      objc_msgSend(dict, get_sel, new_string("c")) != v4) {
+    return -1;
+  }
+  // The replaced object should have been released.
+  if ((NSUInteger)msg0(v2, "retainCount") != 1) {
+    return -2;
+  }
+  id enumerator = msg0(dict, "keyEnumerator");
+  int visited = 0;
+  id key;
+  while ((key = msg0(enumerator, "nextObject"))) {
+    id value = objc_msgSend(dict, get_sel, key);
+    if (value != v1 && value != v3 && value != v4) {
+      return -3;
     }
+    visited++;
+  }
+  if (visited != 3) {
+    return -4;
+  }
+  objc_msgSend(dict, sel_registerName("removeObjectForKey:"), new_string("a"));
+  if ((NSUInteger)msg0(dict, "count") != 2 ||
+      objc_msgSend(dict, get_sel, new_string("a")) != NULL) {
+    return -5;
   }
+  msg0(dict, "release");
+  msg0(other, "release");
+  return 0;
+}
 
-  printf("Passed %d out of %d tests\n", tests_passed, tests_run);
-  exit(tests_run == tests_passed ? 0 : 1);
+id open_url_test_url;
+
+bool open_url_test_handle(id self, SEL _cmd, id app, id url) {
+  open_url_test_url = url;
+  return true;
+}
+
+int test_UIApplication_openURL() {
+  CFStringRef ui_application_name =
+      CFStringCreateWithCString(NULL, "UIApplication", 0x0600);
+  CFStringRef ns_url_name = CFStringCreateWithCString(NULL, "NSURL", 0x0600);
+  id app = msg0(NSClassFromString(ui_application_name), "sharedApplication");
+  id ns_url = NSClassFromString(ns_url_name);
+  SEL url_sel = sel_registerName("URLWithString:");
+  SEL can_open_sel = sel_registerName("canOpenURL:");
+  // TestApp's Info.plist registers this scheme.
+  id own_url = objc_msgSend(ns_url, url_sel, new_string("TouchHLETest://x"));
+  id web_url =
+      objc_msgSend(ns_url, url_sel, new_string("https://touchhle.org/"));
+  id unknown_url = objc_msgSend(ns_url, url_sel, new_string("nonexistent://x"));
+  if (!(int)objc_msgSend(app, can_open_sel, own_url)) {
+    return -1;
+  }
+  if (!(int)objc_msgSend(app, can_open_sel, web_url)) {
+    return -2;
+  }
+  if ((int)objc_msgSend(app, can_open_sel, unknown_url)) {
+    return -3;
+  }
+
+  // Opening a URL with the app's own scheme goes back to its delegate.
+  class_addMethod(msg0(msg0(app, "delegate"), "class"),
+                  sel_registerName("application:handleOpenURL:"),
+                  (IMP)open_url_test_handle, "c@:@@");
+  open_url_test_url = NULL;
+  SEL open_sel = sel_registerName("openURL:");
+  if (!(int)objc_msgSend(app, open_sel, own_url) ||
+      open_url_test_url != own_url) {
+    return -4;
+  }
+  open_url_test_url = NULL;
+  if ((int)objc_msgSend(app, open_sel, unknown_url) ||
+      open_url_test_url != NULL) {
+    return -5;
+  }
+  return 0;
+}
+
+int test_UIPasteboard() {
+  CFStringRef ui_pasteboard_name =
+      CFStringCreateWithCString(NULL, "UIPasteboard", 0x0600);
+  id pasteboard =
+      msg0(NSClassFromString(ui_pasteboard_name), "generalPasteboard");
+  id str = new_string("touchHLE pasteboard test");
+  objc_msgSend(pasteboard, sel_registerName("setString:"), str);
+  id result = msg0(pasteboard, "string");
+  if (result == NULL) {
+    return -1;
+  }
+  if (!(int)objc_msgSend(result, sel_registerName("isEqualToString:"), str)) {
+    return -2;
+  }
+  return 0;
+}
+
+int mail_test_result = -1;
+
+void mail_test_did_finish(id self, SEL _cmd, id composer, int result,
+                          id error) {
+  mail_test_result = result;
+  objc_msgSend(composer,
+               sel_registerName("dismissModalViewControllerAnimated:"), 0);
+}
+
+int test_MFMailComposeViewController() {
+  CFStringRef composer_name =
+      CFStringCreateWithCString(NULL, "MFMailComposeViewController", 0x0600);
+  id composer_class = NSClassFromString(composer_name);
+  if (!(int)msg0(composer_class, "canSendMail")) {
+    return -1;
+  }
+  id composer = new_object("MFMailComposeViewController");
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIResponder", 0x0600)),
+      sel_registerName("mailComposeController:didFinishWithResult:error:"),
+      (IMP)mail_test_did_finish, "v@:@i@");
+  id delegate = new_object("UIResponder");
+  objc_msgSend(composer, sel_registerName("setMailComposeDelegate:"),
+               delegate);
+  if (msg0(composer, "mailComposeDelegate") != delegate) {
+    return -2;
+  }
+  id recipient = new_string("test@example.com");
+  id recipients = new_object("NSMutableArray");
+  objc_msgSend(recipients, sel_registerName("addObject:"), recipient);
+  objc_msgSend(composer, sel_registerName("setToRecipients:"), recipients);
+  objc_msgSend(composer, sel_registerName("setSubject:"), new_string("Hi"));
+  objc_msgSend(composer, sel_registerName("setMessageBody:isHTML:"),
+               new_string("Hello & goodbye"), 0);
+  // The delegate is told the result as soon as the composer appears, and it
+  // dismisses the composer like a real app would.
+  id presenter = new_object("UIViewController");
+  objc_msgSend(presenter,
+               sel_registerName("presentModalViewController:animated:"),
+               composer, 1);
+  if (mail_test_result == -1) {
+    return -3;
+  }
+  if (msg0(presenter, "presentedViewController") != NULL ||
+      msg0(composer, "presentingViewController") != NULL) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_CLLocationManager() {
+  CFStringRef manager_name =
+      CFStringCreateWithCString(NULL, "CLLocationManager", 0x0600);
+  id manager_class = NSClassFromString(manager_name);
+  // kCLAuthorizationStatusAuthorized
+  if ((int)msg0(manager_class, "authorizationStatus") != 3) {
+    return -1;
+  }
+  id manager = new_object("CLLocationManager");
+  if (msg0(manager, "location") != NULL) {
+    return -2;
+  }
+  msg0(manager, "startUpdatingLocation");
+  id location = msg0(manager, "location");
+  if (location == NULL) {
+    return -3;
+  }
+  // integration.rs sets this simulated location.
+  CFStringRef location_name =
+      CFStringCreateWithCString(NULL, "CLLocation", 0x0600);
+  id expected = msg0(NSClassFromString(location_name), "alloc");
+  expected = ((id(*)(id, SEL, double, double))objc_msgSend)(
+      expected, sel_registerName("initWithLatitude:longitude:"), 51.5007,
+      -0.1246);
+  double distance = ((double (*)(id, SEL, id))objc_msgSend)(
+      location, sel_registerName("distanceFromLocation:"), expected);
+  msg0(manager, "stopUpdatingLocation");
+  if (!(distance >= 0.0 && distance < 1.0)) {
+    return -4;
+  }
+  return 0;
+}
+
+static int gk_handler_calls;
+static id gk_loaded_scores;
+
+int test_GameKit() {
+  CFStringRef player_name =
+      CFStringCreateWithCString(NULL, "GKLocalPlayer", 0x0600);
+  CFStringRef score_name = CFStringCreateWithCString(NULL, "GKScore", 0x0600);
+  id category = new_string("touchHLE.test");
+
+  id player = msg0(NSClassFromString(player_name), "localPlayer");
+  gk_handler_calls = 0;
+  objc_msgSend(player, sel_registerName("authenticateWithCompletionHandler:"),
+               ^(id error) {
+                 if (error == NULL) {
+                   gk_handler_calls++;
+                 }
+               });
+  if (gk_handler_calls != 1 || !(int)msg0(player, "isAuthenticated")) {
+    return -1;
+  }
+
+  id score = msg0(NSClassFromString(score_name), "alloc");
+  score = objc_msgSend(score, sel_registerName("initWithCategory:"), category);
+  ((void (*)(id, SEL, long long))objc_msgSend)(
+      score, sel_registerName("setValue:"), 1234);
+  objc_msgSend(score, sel_registerName("reportScoreWithCompletionHandler:"),
+               ^(id error) {
+                 if (error == NULL) {
+                   gk_handler_calls++;
+                 }
+               });
+  if (gk_handler_calls != 2) {
+    return -2;
+  }
+
+  id leaderboard = new_object("GKLeaderboard");
+  objc_msgSend(leaderboard, sel_registerName("setCategory:"), category);
+  gk_loaded_scores = NULL;
+  SEL load_sel = sel_registerName("loadScoresWithCompletionHandler:");
+  objc_msgSend(leaderboard, load_sel, ^(id scores, id error) {
+    if (error == NULL) {
+      gk_loaded_scores = scores;
+    }
+  });
+  if (gk_loaded_scores == NULL ||
+      (NSUInteger)msg0(gk_loaded_scores, "count") != 1) {
+    return -3;
+  }
+  id loaded_score = objc_msgSend(gk_loaded_scores,
+                                 sel_registerName("objectAtIndex:"), 0);
+  long long value = ((long long (*)(id, SEL))objc_msgSend)(
+      loaded_score, sel_registerName("value"));
+  if (value != 1234) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_StoreKit() {
+  CFStringRef set_name = CFStringCreateWithCString(NULL, "NSSet", 0x0600);
+  CFStringRef queue_name =
+      CFStringCreateWithCString(NULL, "SKPaymentQueue", 0x0600);
+  CFStringRef payment_name =
+      CFStringCreateWithCString(NULL, "SKPayment", 0x0600);
+  CFStringRef request_name =
+      CFStringCreateWithCString(NULL, "SKProductsRequest", 0x0600);
+  id identifier = new_string("touchHLE.test.product");
+
+  id identifiers = objc_msgSend(NSClassFromString(set_name),
+                                sel_registerName("setWithObject:"), identifier);
+  id request = msg0(NSClassFromString(request_name), "alloc");
+  request = objc_msgSend(request,
+                         sel_registerName("initWithProductIdentifiers:"),
+                         identifiers);
+  msg0(request, "start");
+  msg0(request, "release");
+
+  id queue = msg0(NSClassFromString(queue_name), "defaultQueue");
+  if (!(int)msg0(NSClassFromString(queue_name), "canMakePayments")) {
+    return -1;
+  }
+  // Test apps can't define an observer class, so the queue's list of
+  // unfinished transactions is checked instead.
+  id observer = new_object("NSObject");
+  objc_msgSend(queue, sel_registerName("addTransactionObserver:"), observer);
+  id payment = objc_msgSend(NSClassFromString(payment_name),
+                            sel_registerName("paymentWithProductIdentifier:"),
+                            identifier);
+  objc_msgSend(queue, sel_registerName("addPayment:"), payment);
+
+  id transactions = msg0(queue, "transactions");
+  if ((NSUInteger)msg0(transactions, "count") != 1) {
+    return -2;
+  }
+  id transaction =
+      objc_msgSend(transactions, sel_registerName("objectAtIndex:"), 0);
+  // SKPaymentTransactionStatePurchased
+  if ((int)msg0(transaction, "transactionState") != 1 ||
+      msg0(transaction, "transactionIdentifier") == NULL) {
+    return -3;
+  }
+  id bought = msg0(msg0(transaction, "payment"), "productIdentifier");
+  if (!(int)objc_msgSend(bought, sel_registerName("isEqualToString:"),
+                         identifier)) {
+    return -4;
+  }
+
+  objc_msgSend(queue, sel_registerName("finishTransaction:"), transaction);
+  if ((NSUInteger)msg0(msg0(queue, "transactions"), "count") != 0) {
+    return -5;
+  }
+
+  msg0(queue, "restoreCompletedTransactions");
+  transactions = msg0(queue, "transactions");
+  if ((NSUInteger)msg0(transactions, "count") != 1) {
+    return -6;
+  }
+  transaction =
+      objc_msgSend(transactions, sel_registerName("objectAtIndex:"), 0);
+  // SKPaymentTransactionStateRestored
+  if ((int)msg0(transaction, "transactionState") != 3) {
+    return -7;
+  }
+  objc_msgSend(queue, sel_registerName("finishTransaction:"), transaction);
+  objc_msgSend(queue, sel_registerName("removeTransactionObserver:"),
+               observer);
+  msg0(observer, "release");
+  return 0;
+}
+
+int test_UIWindow() {
+  CFStringRef app_name =
+      CFStringCreateWithCString(NULL, "UIApplication", 0x0600);
+  id window = new_object("UIWindow");
+  id first = new_object("UIViewController");
+  id second = new_object("UIViewController");
+
+  // Test apps can't define a view controller subclass to observe
+  // viewDidAppear:, so the resulting view hierarchy is checked instead.
+  objc_msgSend(window, sel_registerName("setRootViewController:"), first);
+  msg0(window, "makeKeyAndVisible");
+  // There's no shared application unless UIApplicationMain() was called.
+  id app = msg0(NSClassFromString(app_name), "sharedApplication");
+  if (app != NULL && msg0(app, "keyWindow") != window) {
+    return -1;
+  }
+  if (!(int)msg0(window, "isKeyWindow") || (int)msg0(window, "isHidden")) {
+    return -2;
+  }
+  id subviews = msg0(window, "subviews");
+  if ((NSUInteger)msg0(subviews, "count") != 1 ||
+      objc_msgSend(subviews, sel_registerName("objectAtIndex:"), 0) !=
+          msg0(first, "view")) {
+    return -3;
+  }
+
+  // Replacing the root view controller replaces its view.
+  objc_msgSend(window, sel_registerName("setRootViewController:"), second);
+  subviews = msg0(window, "subviews");
+  if ((NSUInteger)msg0(subviews, "count") != 1 ||
+      objc_msgSend(subviews, sel_registerName("objectAtIndex:"), 0) !=
+          msg0(second, "view") ||
+      msg0(msg0(first, "view"), "superview") != NULL) {
+    return -4;
+  }
+  if (msg0(window, "rootViewController") != second) {
+    return -5;
+  }
+
+  msg0(window, "release");
+  msg0(first, "release");
+  msg0(second, "release");
+  return 0;
+}
+
+int test_UIView_hitTest() {
+  SEL hit_test = sel_registerName("hitTest:withEvent:");
+  id (*hit_test_fn)(id, SEL, CGPoint, id) =
+      (id(*)(id, SEL, CGPoint, id))objc_msgSend;
+  SEL add_subview = sel_registerName("addSubview:");
+
+  id root = new_view(0, 0, 100, 100);
+  id back = new_view(0, 0, 100, 100);
+  id front = new_view(50, 50, 50, 50);
+  id child = new_view(10, 10, 20, 20);
+  objc_msgSend(root, add_subview, back);
+  objc_msgSend(root, add_subview, front);
+  objc_msgSend(front, add_subview, child);
+
+  CGPoint in_child = {65, 65};
+  CGPoint in_front = {95, 95};
+  CGPoint in_back = {25, 25};
+  CGPoint outside = {150, 25};
+  if (hit_test_fn(root, hit_test, in_child, NULL) != child ||
+      hit_test_fn(root, hit_test, in_front, NULL) != front ||
+      hit_test_fn(root, hit_test, in_back, NULL) != back ||
+      hit_test_fn(root, hit_test, outside, NULL) != NULL) {
+    return -1;
+  }
+
+  // A view that doesn't accept interaction is skipped, along with its
+  // subviews, in favor of the view behind it.
+  objc_msgSend(front, sel_registerName("setUserInteractionEnabled:"), 0);
+  if (hit_test_fn(root, hit_test, in_child, NULL) != back ||
+      hit_test_fn(root, hit_test, in_front, NULL) != back) {
+    return -2;
+  }
+  objc_msgSend(front, sel_registerName("setUserInteractionEnabled:"), 1);
+
+  // Likewise for hidden views.
+  objc_msgSend(child, sel_registerName("setHidden:"), 1);
+  if (hit_test_fn(root, hit_test, in_child, NULL) != front) {
+    return -3;
+  }
+
+  msg0(root, "release");
+  msg0(back, "release");
+  msg0(front, "release");
+  msg0(child, "release");
+  return 0;
+}
+
+int test_UIView_convert() {
+  CGPoint (*convert_point)(id, SEL, CGPoint, id) =
+      (CGPoint(*)(id, SEL, CGPoint, id))objc_msgSend_stret;
+  CGRect (*convert_rect)(id, SEL, CGRect, id) =
+      (CGRect(*)(id, SEL, CGRect, id))objc_msgSend_stret;
+  SEL add_subview = sel_registerName("addSubview:");
+
+  id window = new_object("UIWindow");
+  id a = new_view(10, 20, 200, 200);
+  id b = new_view(30, 40, 100, 100);
+  id c = new_view(1, 2, 50, 50);
+  objc_msgSend(window, add_subview, a);
+  objc_msgSend(a, add_subview, b);
+  objc_msgSend(b, add_subview, c);
+
+  // nil means the window's co-ordinate space.
+  CGPoint point = {5, 5};
+  CGPoint in_window =
+      convert_point(c, sel_registerName("convertPoint:toView:"), point, NULL);
+  CGPoint expected = {46, 67};
+  if (!CGPointEqualToPoint(in_window, expected)) {
+    return -1;
+  }
+  CGPoint round_trip = convert_point(
+      c, sel_registerName("convertPoint:fromView:"), in_window, NULL);
+  if (!CGPointEqualToPoint(round_trip, point)) {
+    return -2;
+  }
+  // Between views in the same hierarchy.
+  CGPoint in_a =
+      convert_point(c, sel_registerName("convertPoint:toView:"), point, a);
+  CGPoint expected_in_a = {36, 47};
+  if (!CGPointEqualToPoint(in_a, expected_in_a)) {
+    return -3;
+  }
+
+  CGRect rect = {{5, 5}, {10, 10}};
+  CGRect rect_in_window =
+      convert_rect(c, sel_registerName("convertRect:toView:"), rect, window);
+  CGRect expected_rect = {{46, 67}, {10, 10}};
+  if (!CGRectEqualToRect(rect_in_window, expected_rect)) {
+    return -4;
+  }
+  CGRect rect_round_trip = convert_rect(
+      c, sel_registerName("convertRect:fromView:"), rect_in_window, NULL);
+  if (!CGRectEqualToRect(rect_round_trip, rect)) {
+    return -5;
+  }
+
+  msg0(window, "release");
+  msg0(a, "release");
+  msg0(b, "release");
+  msg0(c, "release");
+  return 0;
+}
+
+int test_UIView_autoresizing() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  void (*set_rect)(id, SEL, CGRect) = (void (*)(id, SEL, CGRect))objc_msgSend;
+  SEL add_subview = sel_registerName("addSubview:");
+  SEL set_mask = sel_registerName("setAutoresizingMask:");
+
+  id superview = new_view(0, 0, 100, 100);
+  id flexible_width = new_view(10, 10, 80, 20);
+  id flexible_left = new_view(60, 40, 30, 20);
+  id fixed = new_view(10, 70, 30, 20);
+  objc_msgSend(superview, add_subview, flexible_width);
+  objc_msgSend(superview, add_subview, flexible_left);
+  objc_msgSend(superview, add_subview, fixed);
+  objc_msgSend(flexible_width, set_mask, 1 << 1); // FlexibleWidth
+  objc_msgSend(flexible_left, set_mask, 1 << 0);  // FlexibleLeftMargin
+
+  CGRect new_frame = {{0, 0}, {200, 100}};
+  set_rect(superview, sel_registerName("setFrame:"), new_frame);
+
+  CGRect expected_width = {{10, 10}, {180, 20}};
+  CGRect expected_left = {{160, 40}, {30, 20}};
+  CGRect expected_fixed = {{10, 70}, {30, 20}};
+  if (!CGRectEqualToRect(get_rect(flexible_width, sel_registerName("frame")),
+                         expected_width) ||
+      !CGRectEqualToRect(get_rect(flexible_left, sel_registerName("frame")),
+                         expected_left) ||
+      !CGRectEqualToRect(get_rect(fixed, sel_registerName("frame")),
+                         expected_fixed)) {
+    return -1;
+  }
+
+  // Subviews aren't resized if autoresizesSubviews is off.
+  objc_msgSend(superview, sel_registerName("setAutoresizesSubviews:"), 0);
+  CGRect new_bounds = {{0, 0}, {100, 100}};
+  set_rect(superview, sel_registerName("setBounds:"), new_bounds);
+  if (!CGRectEqualToRect(get_rect(flexible_width, sel_registerName("frame")),
+                         expected_width)) {
+    return -2;
+  }
+
+  msg0(superview, "release");
+  msg0(flexible_width, "release");
+  msg0(flexible_left, "release");
+  msg0(fixed, "release");
+  return 0;
+}
+
+int test_UIView_transform() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  CGAffineTransform (*get_transform)(id, SEL) =
+      (CGAffineTransform(*)(id, SEL))objc_msgSend_stret;
+  void (*set_transform)(id, SEL, CGAffineTransform) =
+      (void (*)(id, SEL, CGAffineTransform))objc_msgSend;
+  SEL hit_test = sel_registerName("hitTest:withEvent:");
+  id (*hit_test_fn)(id, SEL, CGPoint, id) =
+      (id(*)(id, SEL, CGPoint, id))objc_msgSend;
+
+  id root = new_view(0, 0, 100, 100);
+  id view = new_view(0, 40, 100, 20);
+  objc_msgSend(root, sel_registerName("addSubview:"), view);
+
+  // A 90° rotation, written out exactly to avoid rounding errors.
+  CGAffineTransform rotation = CGAffineTransformMake(0, 1, -1, 0, 0, 0);
+  set_transform(view, sel_registerName("setTransform:"), rotation);
+  if (!CGAffineTransformEqualToTransform(
+          get_transform(view, sel_registerName("transform")), rotation)) {
+    return -1;
+  }
+
+  // The bounds are unchanged, but the frame is the rotated bounding box.
+  CGRect bounds = {{0, 0}, {100, 20}};
+  CGRect frame = {{40, 0}, {20, 100}};
+  if (!CGRectEqualToRect(get_rect(view, sel_registerName("bounds")),
+                         bounds) ||
+      !CGRectEqualToRect(get_rect(view, sel_registerName("frame")), frame)) {
+    return -2;
+  }
+
+  // A point outside the untransformed frame now hits the view, and a point
+  // inside it no longer does.
+  CGPoint rotated_in = {50, 10};
+  CGPoint rotated_out = {10, 50};
+  if (hit_test_fn(root, hit_test, rotated_in, NULL) != view ||
+      hit_test_fn(root, hit_test, rotated_out, NULL) != root) {
+    return -3;
+  }
+
+  msg0(root, "release");
+  msg0(view, "release");
+  return 0;
+}
+
+int test_UIResponder() {
+  SEL become = sel_registerName("becomeFirstResponder");
+  SEL resign = sel_registerName("resignFirstResponder");
+  SEL is_first = sel_registerName("isFirstResponder");
+  SEL target_for_action = sel_registerName("targetForAction:withSender:");
+  CFStringRef field_name =
+      CFStringCreateWithCString(NULL, "UITextField", 0x0600);
+  CGRect frame = {{10, 10}, {100, 20}};
+  id (*init_with_frame)(id, SEL, CGRect) =
+      (id(*)(id, SEL, CGRect))objc_msgSend;
+  id first = init_with_frame(msg0(NSClassFromString(field_name), "alloc"),
+                             sel_registerName("initWithFrame:"), frame);
+  id second = init_with_frame(msg0(NSClassFromString(field_name), "alloc"),
+                              sel_registerName("initWithFrame:"), frame);
+  id window = new_object("UIWindow");
+  id controller = new_object("UIViewController");
+  objc_msgSend(window, sel_registerName("setRootViewController:"),
+               controller);
+  id view = msg0(controller, "view");
+  objc_msgSend(view, sel_registerName("addSubview:"), first);
+  objc_msgSend(view, sel_registerName("addSubview:"), second);
+
+  // The chain goes from a view to its controller, then the controller's
+  // view's superview.
+  if (msg0(first, "nextResponder") != view ||
+      msg0(view, "nextResponder") != controller ||
+      msg0(controller, "nextResponder") != window) {
+    return -1;
+  }
+
+  // Only one responder can be the first responder at a time.
+  if (!(int)objc_msgSend(first, become) ||
+      !(int)objc_msgSend(first, is_first)) {
+    return -2;
+  }
+  if (!(int)objc_msgSend(second, become) ||
+      (int)objc_msgSend(first, is_first) ||
+      !(int)objc_msgSend(second, is_first)) {
+    return -3;
+  }
+  if (!(int)objc_msgSend(second, resign) ||
+      (int)objc_msgSend(second, is_first)) {
+    return -4;
+  }
+  // Plain views can't become the first responder.
+  if ((int)objc_msgSend(view, become) || (int)objc_msgSend(view, is_first)) {
+    return -5;
+  }
+
+  // An action the text field can't handle goes up the chain to the window.
+  SEL action = sel_registerName("makeKeyWindow");
+  if (objc_msgSend(first, target_for_action, action, NULL) != window) {
+    return -6;
+  }
+  action = sel_registerName("someActionNobodyHandles:");
+  if (objc_msgSend(first, target_for_action, action, NULL) != NULL) {
+    return -7;
+  }
+
+  msg0(window, "release");
+  msg0(controller, "release");
+  msg0(first, "release");
+  msg0(second, "release");
+  return 0;
+}
+
+int test_UIKeyboard() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  bool (*press_key)(id, SEL, CGPoint) =
+      (bool (*)(id, SEL, CGPoint))objc_msgSend;
+  SEL press = sel_registerName("pressKeyAtPoint:");
+  CFStringRef center_name =
+      CFStringCreateWithCString(NULL, "NSNotificationCenter", 0x0600);
+  id did_show = new_string("UIKeyboardDidShowNotification");
+  id center = msg0(NSClassFromString(center_name), "defaultCenter");
+  // Every notification gets added to the array.
+  id notifications = new_object("NSMutableArray");
+  ((void (*)(id, SEL, id, SEL, id, id))objc_msgSend)(
+      center, sel_registerName("addObserver:selector:name:object:"),
+      notifications, sel_registerName("addObject:"), did_show, NULL);
+
+  id window = new_object("UIWindow");
+  CFStringRef field_name =
+      CFStringCreateWithCString(NULL, "UITextField", 0x0600);
+  CGRect field_frame = {{10, 10}, {100, 20}};
+  id field = ((id(*)(id, SEL, CGRect))objc_msgSend)(
+      msg0(NSClassFromString(field_name), "alloc"),
+      sel_registerName("initWithFrame:"), field_frame);
+  objc_msgSend(window, sel_registerName("addSubview:"), field);
+
+  // The keyboard appears on top when the text field starts editing.
+  msg0(field, "becomeFirstResponder");
+  if ((NSUInteger)msg0(notifications, "count") != 1) {
+    return -1;
+  }
+  id keyboard = msg0(msg0(window, "subviews"), "lastObject");
+  if (keyboard == field) {
+    return -2;
+  }
+  id notification =
+      objc_msgSend(notifications, sel_registerName("objectAtIndex:"), 0);
+  id frame_value =
+      objc_msgSend(msg0(notification, "userInfo"),
+                   sel_registerName("objectForKey:"),
+                   new_string("UIKeyboardFrameEndUserInfoKey"));
+  CGRect frame = get_rect(frame_value, sel_registerName("CGRectValue"));
+  if (frame.size.width <= 0 || frame.size.height <= 0 ||
+      !CGRectEqualToRect(get_rect(keyboard, sel_registerName("frame")),
+                         frame)) {
+    return -3;
+  }
+
+  // Type "qw", then delete the "w". The default layout starts with a row of
+  // ten keys with "q" and "w" first, and the third row ends with backspace.
+  CGFloat key_width = frame.size.width / 10;
+  CGFloat row_height = frame.size.height / 4;
+  CGPoint q = {key_width / 2, row_height / 2};
+  CGPoint w = {key_width * 1.5, row_height / 2};
+  CGPoint backspace = {frame.size.width - 1, row_height * 2.5};
+  if (!press_key(keyboard, press, q) || !press_key(keyboard, press, w)) {
+    return -4;
+  }
+  if (!(int)objc_msgSend(msg0(field, "text"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("qw"))) {
+    return -5;
+  }
+  press_key(keyboard, press, backspace);
+  if (!(int)objc_msgSend(msg0(field, "text"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("q"))) {
+    return -6;
+  }
+
+  // The keyboard goes away when the text field stops editing.
+  msg0(field, "resignFirstResponder");
+  if (msg0(keyboard, "superview") != NULL) {
+    return -7;
+  }
+
+  ((void (*)(id, SEL, id, id, id))objc_msgSend)(
+      center, sel_registerName("removeObserver:name:object:"), notifications,
+      did_show, NULL);
+  msg0(notifications, "release");
+  msg0(window, "release");
+  msg0(field, "release");
+  return 0;
+}
+
+int test_UITextView() {
+  CGSize (*get_size)(id, SEL) = (CGSize(*)(id, SEL))objc_msgSend_stret;
+  SEL content_size = sel_registerName("contentSize");
+  SEL insert = sel_registerName("insertText:");
+  CFStringRef center_name =
+      CFStringCreateWithCString(NULL, "NSNotificationCenter", 0x0600);
+  id did_change = new_string("UITextViewTextDidChangeNotification");
+  id center = msg0(NSClassFromString(center_name), "defaultCenter");
+  // Every notification gets added to the array.
+  id notifications = new_object("NSMutableArray");
+  ((void (*)(id, SEL, id, SEL, id, id))objc_msgSend)(
+      center, sel_registerName("addObserver:selector:name:object:"),
+      notifications, sel_registerName("addObject:"), did_change, NULL);
+
+  CFStringRef view_name =
+      CFStringCreateWithCString(NULL, "UITextView", 0x0600);
+  CGRect frame = {{0, 0}, {100, 100}};
+  id view = ((id(*)(id, SEL, CGRect))objc_msgSend)(
+      msg0(NSClassFromString(view_name), "alloc"),
+      sel_registerName("initWithFrame:"), frame);
+  if ((int)msg0(view, "hasText") || !(int)msg0(view, "isEditable")) {
+    return -1;
+  }
+
+  objc_msgSend(view, insert, new_string("Hi"));
+  CGSize one_line = get_size(view, content_size);
+  if (one_line.width != 100 || one_line.height <= 0) {
+    return -2;
+  }
+
+  // This is far too long to fit on one line, so it must wrap.
+  objc_msgSend(view, insert,
+               new_string(" the quick brown fox jumps over the lazy dog"));
+  CGSize wrapped = get_size(view, content_size);
+  if (wrapped.width != 100 || wrapped.height <= one_line.height * 2) {
+    return -3;
+  }
+  if ((NSUInteger)msg0(notifications, "count") != 2) {
+    return -4;
+  }
+
+  msg0(view, "deleteBackward");
+  if ((NSUInteger)msg0(msg0(view, "text"), "length") != 45 ||
+      (NSUInteger)msg0(notifications, "count") != 3) {
+    return -5;
+  }
+
+  ((void (*)(id, SEL, id, id, id))objc_msgSend)(
+      center, sel_registerName("removeObserver:name:object:"), notifications,
+      did_change, NULL);
+  msg0(notifications, "release");
+  msg0(view, "release");
+  return 0;
+}
+
+void movie_test_run_loop() {
+  id run_loop = msg0(NSClassFromString(CFStringCreateWithCString(
+                         NULL, "NSRunLoop", 0x0600)),
+                     "currentRunLoop");
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSDate", 0x0600)),
+      sel_registerName("dateWithTimeIntervalSinceNow:"), 0.01);
+  objc_msgSend(run_loop, sel_registerName("runUntilDate:"), date);
+}
+
+int test_MPMoviePlayerController() {
+  double (*get_time)(id, SEL) = (double (*)(id, SEL))objc_msgSend;
+  CFStringRef bundle_name = CFStringCreateWithCString(NULL, "NSBundle", 0x0600);
+  CFStringRef url_name = CFStringCreateWithCString(NULL, "NSURL", 0x0600);
+  CFStringRef player_name =
+      CFStringCreateWithCString(NULL, "MPMoviePlayerController", 0x0600);
+  id bundle = msg0(NSClassFromString(bundle_name), "mainBundle");
+  id path = ((id(*)(id, SEL, id, id))objc_msgSend)(
+      bundle, sel_registerName("pathForResource:ofType:"), new_string("clip"),
+      new_string("mp4"));
+  id url = objc_msgSend(NSClassFromString(url_name),
+                        sel_registerName("fileURLWithPath:"), path);
+  id player = objc_msgSend(msg0(NSClassFromString(player_name), "alloc"),
+                           sel_registerName("initWithContentURL:"), url);
+
+  // The clip's movie header says it's 900 units long at 600 units a second.
+  if (get_time(player, sel_registerName("duration")) != 1.5) {
+    return -1;
+  }
+  if ((int)msg0(player, "playbackState") != 0) {
+    return -2;
+  }
+  msg0(player, "play");
+  if ((int)msg0(player, "playbackState") != 1) {
+    return -3;
+  }
+  msg0(player, "pause");
+  if ((int)msg0(player, "playbackState") != 2) {
+    return -4;
+  }
+  // Seeking can't go past the end.
+  ((void (*)(id, SEL, double))objc_msgSend)(
+      player, sel_registerName("setCurrentPlaybackTime:"), 10.0);
+  if (get_time(player, sel_registerName("currentPlaybackTime")) != 1.5) {
+    return -5;
+  }
+  msg0(player, "stop");
+  if ((int)msg0(player, "playbackState") != 0 ||
+      get_time(player, sel_registerName("currentPlaybackTime")) != 0) {
+    return -6;
+  }
+
+  // Video can't be decoded, so playback finishes once the app returns to the
+  // run loop.
+  CFStringRef center_name =
+      CFStringCreateWithCString(NULL, "NSNotificationCenter", 0x0600);
+  id did_finish = new_string("MPMoviePlayerPlaybackDidFinishNotification");
+  id center = msg0(NSClassFromString(center_name), "defaultCenter");
+  id notifications = new_object("NSMutableArray");
+  ((void (*)(id, SEL, id, SEL, id, id))objc_msgSend)(
+      center, sel_registerName("addObserver:selector:name:object:"),
+      notifications, sel_registerName("addObject:"), did_finish, player);
+  // The first play's notification is still pending.
+  movie_test_run_loop();
+  if ((NSUInteger)msg0(notifications, "count") != 1) {
+    return -7;
+  }
+  msg0(player, "play");
+  movie_test_run_loop();
+  if ((NSUInteger)msg0(notifications, "count") != 2 ||
+      (int)msg0(player, "playbackState") != 0 ||
+      get_time(player, sel_registerName("currentPlaybackTime")) != 1.5) {
+    return -8;
+  }
+  ((void (*)(id, SEL, id, id, id))objc_msgSend)(
+      center, sel_registerName("removeObserver:name:object:"), notifications,
+      did_finish, player);
+  msg0(notifications, "release");
+
+  // Apps often stop the player when they hear it finished.
+  ((void (*)(id, SEL, id, SEL, id, id))objc_msgSend)(
+      center, sel_registerName("addObserver:selector:name:object:"), player,
+      sel_registerName("stop"), did_finish, player);
+  msg0(player, "play");
+  movie_test_run_loop();
+  if ((int)msg0(player, "playbackState") != 0 ||
+      get_time(player, sel_registerName("currentPlaybackTime")) != 0) {
+    return -9;
+  }
+  ((void (*)(id, SEL, id, id, id))objc_msgSend)(
+      center, sel_registerName("removeObserver:name:object:"), player,
+      did_finish, player);
+
+  msg0(player, "release");
+  return 0;
+}
+
+int test_UIImagePickerController() {
+  CFStringRef picker_name =
+      CFStringCreateWithCString(NULL, "UIImagePickerController", 0x0600);
+  id picker_class = NSClassFromString(picker_name);
+  SEL available = sel_registerName("isSourceTypeAvailable:");
+  // The photo library is simulated, but there's no camera.
+  if (!(int)objc_msgSend(picker_class, available, 0) ||
+      (int)objc_msgSend(picker_class, available, 1)) {
+    return -1;
+  }
+  id picker = new_object("UIImagePickerController");
+  id delegate = new_object("NSObject");
+  objc_msgSend(picker, sel_registerName("setDelegate:"), delegate);
+  objc_msgSend(picker, sel_registerName("setSourceType:"), 2);
+  objc_msgSend(picker, sel_registerName("setAllowsEditing:"), 1);
+  if (msg0(picker, "delegate") != delegate ||
+      (int)msg0(picker, "sourceType") != 2 ||
+      !(int)msg0(picker, "allowsImageEditing")) {
+    return -2;
+  }
+  // Presenting must not block or crash, even though the delegate doesn't
+  // implement the callbacks. Nothing dismisses the picker, so it stays.
+  id presenter = new_object("UIViewController");
+  objc_msgSend(presenter,
+               sel_registerName("presentModalViewController:animated:"),
+               picker, 1);
+  if (msg0(presenter, "presentedViewController") != picker) {
+    return -3;
+  }
+  msg0(presenter, "release");
+  msg0(delegate, "release");
+  msg0(picker, "release");
+  return 0;
+}
+
+int test_UIActionSheet() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  CFStringRef sheet_name =
+      CFStringCreateWithCString(NULL, "UIActionSheet", 0x0600);
+  id sheet = ((id(*)(id, SEL, id, id, id, id, id, ...))objc_msgSend)(
+      msg0(NSClassFromString(sheet_name), "alloc"),
+      sel_registerName("initWithTitle:delegate:cancelButtonTitle:"
+                       "destructiveButtonTitle:otherButtonTitles:"),
+      new_string("Title"), NULL, new_string("Cancel"), new_string("Delete"),
+      new_string("A"), new_string("B"), NULL);
+  // The destructive button comes first and the cancel button comes last.
+  if ((int)msg0(sheet, "numberOfButtons") != 4 ||
+      (int)msg0(sheet, "destructiveButtonIndex") != 0 ||
+      (int)msg0(sheet, "firstOtherButtonIndex") != 1 ||
+      (int)msg0(sheet, "cancelButtonIndex") != 3) {
+    return -1;
+  }
+  if (!(int)objc_msgSend(
+          objc_msgSend(sheet, sel_registerName("buttonTitleAtIndex:"), 2),
+          sel_registerName("isEqualToString:"), new_string("B"))) {
+    return -2;
+  }
+
+  id window = new_object("UIWindow");
+  id view = new_view(0, 0, 320, 480);
+  objc_msgSend(window, sel_registerName("addSubview:"), view);
+  objc_msgSend(sheet, sel_registerName("showInView:"), view);
+  if (msg0(sheet, "superview") != window || !(int)msg0(sheet, "isVisible")) {
+    return -3;
+  }
+
+  // Tap the middle of the "B" button. The sheet's subviews are the title,
+  // then the buttons in order.
+  id b_label = objc_msgSend(msg0(sheet, "subviews"),
+                            sel_registerName("objectAtIndex:"), 3);
+  CGRect b_frame = get_rect(b_label, sel_registerName("frame"));
+  CGPoint b_center = {b_frame.origin.x + b_frame.size.width / 2,
+                      b_frame.origin.y + b_frame.size.height / 2};
+  int pressed = ((int (*)(id, SEL, CGPoint))objc_msgSend)(
+      sheet, sel_registerName("pressButtonAtPoint:"), b_center);
+  if (pressed != 2) {
+    return -4;
+  }
+  // Pressing a button dismisses the sheet.
+  if (msg0(sheet, "superview") != NULL) {
+    return -5;
+  }
+
+  msg0(sheet, "release");
+  msg0(view, "release");
+  msg0(window, "release");
+  return 0;
+}
+
+int test_UITabBarController() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  id first = new_object("UIViewController");
+  id second = new_object("UIViewController");
+  objc_msgSend(first, sel_registerName("setTitle:"), new_string("First"));
+  objc_msgSend(second, sel_registerName("setTitle:"), new_string("Second"));
+  id array = new_object("NSMutableArray");
+  objc_msgSend(array, sel_registerName("addObject:"), first);
+  objc_msgSend(array, sel_registerName("addObject:"), second);
+
+  id tabs = new_object("UITabBarController");
+  objc_msgSend(tabs, sel_registerName("setViewControllers:"), array);
+  if ((int)msg0(tabs, "selectedIndex") != 0 ||
+      msg0(tabs, "selectedViewController") != first ||
+      msg0(second, "tabBarController") != tabs) {
+    return -1;
+  }
+  // Each tab's item takes its title from the view controller.
+  if (!(int)objc_msgSend(msg0(msg0(second, "tabBarItem"), "title"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("Second"))) {
+    return -2;
+  }
+
+  id view = msg0(tabs, "view");
+  id tab_bar = msg0(tabs, "tabBar");
+  if (msg0(tab_bar, "superview") != view ||
+      msg0(msg0(first, "view"), "superview") != view ||
+      msg0(msg0(second, "view"), "superview") != NULL) {
+    return -3;
+  }
+
+  // Tap the middle of the second tab.
+  CGRect bar_bounds = get_rect(tab_bar, sel_registerName("bounds"));
+  CGPoint point = {bar_bounds.size.width * 3 / 4,
+                   bar_bounds.size.height / 2};
+  int pressed = ((int (*)(id, SEL, CGPoint))objc_msgSend)(
+      tab_bar, sel_registerName("pressItemAtPoint:"), point);
+  if (pressed != 1 || (int)msg0(tabs, "selectedIndex") != 1 ||
+      msg0(tabs, "selectedViewController") != second ||
+      msg0(tab_bar, "selectedItem") != msg0(second, "tabBarItem")) {
+    return -4;
+  }
+  // The second tab's view replaces the first's.
+  if (msg0(msg0(second, "view"), "superview") != view ||
+      msg0(msg0(first, "view"), "superview") != NULL) {
+    return -5;
+  }
+
+  msg0(tabs, "release");
+  msg0(array, "release");
+  msg0(second, "release");
+  msg0(first, "release");
+  return 0;
+}
+
+// What the NSLocalizedString() macro expands to.
+id localized_string(const char *key) {
+  id bundle = msg0(NSClassFromString(CFStringCreateWithCString(
+                       NULL, "NSBundle", 0x0600)),
+                   "mainBundle");
+  return objc_msgSend(bundle,
+                      sel_registerName("localizedStringForKey:value:table:"),
+                      new_string(key), new_string(""), NULL);
+}
+
+int test_NSLocalizedString() {
+  // These are in en.lproj/Localizable.strings.
+  if (!(int)objc_msgSend(localized_string("Hello"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("Hello, world!"))) {
+    return -1;
+  }
+  if (!(int)objc_msgSend(localized_string("Quoted"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("\"Quoted\"\tvalue"))) {
+    return -2;
+  }
+  if (!(int)objc_msgSend(localized_string("Unquoted"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("Unquoted value"))) {
+    return -3;
+  }
+  // A missing key falls back to the key itself.
+  if (!(int)objc_msgSend(localized_string("Missing"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("Missing"))) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_UIFont() {
+  id font_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIFont", 0x0600));
+  id (*font_with_name)(id, SEL, id, float) =
+      (id(*)(id, SEL, id, float))objc_msgSend;
+  SEL font_with_name_sel = sel_registerName("fontWithName:size:");
+
+  // TestFont.ttf is listed in UIAppFonts, so it's available from launch.
+  id font = font_with_name(font_class, font_with_name_sel,
+                           new_string("touchHLETest-Regular"), 12.0);
+  if (!(int)objc_msgSend(msg0(font, "familyName"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("touchHLE Test"))) {
+    return -1;
+  }
+
+  // TestFontBold.ttf has to be registered first.
+  id bundle = msg0(NSClassFromString(CFStringCreateWithCString(
+                       NULL, "NSBundle", 0x0600)),
+                   "mainBundle");
+  CFURLRef url = (CFURLRef)objc_msgSend(
+      bundle, sel_registerName("URLForResource:withExtension:"),
+      new_string("TestFontBold"), new_string("ttf"));
+  if (!CTFontManagerRegisterFontsForURL(url, 1, NULL)) {
+    return -2;
+  }
+  font = font_with_name(font_class, font_with_name_sel,
+                        new_string("touchHLETest-Bold"), 12.0);
+  if (!(int)objc_msgSend(msg0(font, "familyName"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("touchHLE Test")) ||
+      !(int)objc_msgSend(msg0(font, "fontName"),
+                         sel_registerName("isEqualToString:"),
+                         new_string("touchHLETest-Bold"))) {
+    return -3;
+  }
+  // Registering the same font twice fails.
+  if (CTFontManagerRegisterFontsForURL(url, 1, NULL)) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_CGColor() {
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  if (CGColorSpaceGetNumberOfComponents(space) != 3) {
+    return -1;
+  }
+  CGColorRef color = CGColorCreate(space, (CGFloat[]){0.25, 0.5, 0.75, 0.5});
+  CGColorSpaceRelease(space);
+  if (CGColorGetNumberOfComponents(color) != 4) {
+    return -2;
+  }
+  const CGFloat *components = CGColorGetComponents(color);
+  if (components[0] != 0.25 || components[1] != 0.5 ||
+      components[2] != 0.75 || components[3] != 0.5) {
+    return -3;
+  }
+  if (CGColorGetAlpha(color) != 0.5) {
+    return -4;
+  }
+  // The color keeps its color space alive.
+  if (CGColorSpaceGetNumberOfComponents(CGColorGetColorSpace(color)) != 3) {
+    return -5;
+  }
+  CGColorRelease(color);
+
+  // UIColor bridges to CGColor.
+  id ui_color_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIColor", 0x0600));
+  CGColorRef red =
+      (CGColorRef)msg0(msg0(ui_color_class, "redColor"), "CGColor");
+  components = CGColorGetComponents(red);
+  if (components[0] != 1.0 || components[1] != 0.0 ||
+      CGColorGetAlpha(red) != 1.0) {
+    return -6;
+  }
+  return 0;
+}
+
+int test_CGContextClip() {
+  // 4×4 RGBA, kCGImageAlphaPremultipliedLast
+  unsigned char pixels[4 * 4 * 4] = {0};
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  CGContextRef context = CGBitmapContextCreate(pixels, 4, 4, 8, 16, space, 1);
+  CGColorSpaceRelease(space);
+
+  CGContextSaveGState(context);
+  CGContextClipToRect(context, (CGRect){{1, 1}, {2, 2}});
+  CGContextSetRGBFillColor(context, 1.0, 0.0, 0.0, 1.0);
+  CGContextFillRect(context, (CGRect){{0, 0}, {4, 4}});
+  // Only the 2×2 square in the middle should have changed.
+  for (int y = 0; y < 4; y++) {
+    for (int x = 0; x < 4; x++) {
+      int inside = x >= 1 && x < 3 && y >= 1 && y < 3;
+      unsigned char *pixel = &pixels[(y * 4 + x) * 4];
+      if (pixel[0] != (inside ? 255 : 0) || pixel[3] != (inside ? 255 : 0)) {
+        return -1;
+      }
+    }
+  }
+
+  // Restoring the state removes the clip. The fill color and alpha set
+  // afterwards apply.
+  CGContextRestoreGState(context);
+  CGContextSetRGBFillColor(context, 0.0, 0.0, 1.0, 1.0);
+  CGContextSetAlpha(context, 0.0);
+  CGContextFillRect(context, (CGRect){{0, 0}, {4, 4}});
+  if (pixels[0] != 0 || pixels[3] != 0) {
+    return -2;
+  }
+  CGContextSetAlpha(context, 1.0);
+  CGContextFillRect(context, (CGRect){{0, 0}, {4, 4}});
+  if (pixels[0] != 0 || pixels[2] != 255 || pixels[3] != 255) {
+    return -3;
+  }
+  CGContextRelease(context);
+  return 0;
+}
+
+int test_CGGradient() {
+  // 8×1 RGBA, kCGImageAlphaPremultipliedLast
+  unsigned char pixels[8 * 4] = {0};
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  CGContextRef context = CGBitmapContextCreate(pixels, 8, 1, 8, 32, space, 1);
+  // Red to blue.
+  CGFloat components[] = {1, 0, 0, 1, 0, 0, 1, 1};
+  CGFloat locations[] = {0, 1};
+  CGGradientRef gradient =
+      CGGradientCreateWithColorComponents(space, components, locations, 2);
+  CGColorSpaceRelease(space);
+
+  // The gradient goes from the center of the second pixel to the center of
+  // the second-last pixel, and it's clipped to those pixels and the ones in
+  // between.
+  CGContextSaveGState(context);
+  CGContextClipToRect(context, (CGRect){{1, 0}, {6, 1}});
+  // kCGGradientDrawsBeforeStartLocation | kCGGradientDrawsAfterEndLocation
+  CGContextDrawLinearGradient(context, gradient, (CGPoint){1.5, 0.5},
+                              (CGPoint){6.5, 0.5}, 3);
+  CGContextRestoreGState(context);
+  unsigned char *first = &pixels[0], *start = &pixels[4];
+  unsigned char *end = &pixels[6 * 4], *last = &pixels[7 * 4];
+  if (first[3] != 0 || last[3] != 0) {
+    return -1;
+  }
+  if (start[0] != 255 || start[1] != 0 || start[2] != 0 || start[3] != 255 ||
+      end[0] != 0 || end[1] != 0 || end[2] != 255 || end[3] != 255) {
+    return -2;
+  }
+  // The pixels in between are a mix of the two.
+  unsigned char *middle = &pixels[3 * 4];
+  if (middle[0] == 0 || middle[0] == 255 || middle[2] == 0 ||
+      middle[2] == 255 || middle[3] != 255) {
+    return -3;
+  }
+
+  // Without the options, nothing is drawn beyond the start and end.
+  memset(pixels, 0, sizeof(pixels));
+  CGContextDrawLinearGradient(context, gradient, (CGPoint){1.5, 0.5},
+                              (CGPoint){6.5, 0.5}, 0);
+  if (first[3] != 0 || last[3] != 0 || start[0] != 255 || end[2] != 255) {
+    return -4;
+  }
+  CGContextDrawLinearGradient(context, gradient, (CGPoint){1.5, 0.5},
+                              (CGPoint){6.5, 0.5}, 3);
+  if (first[0] != 255 || first[3] != 255 || last[2] != 255 ||
+      last[3] != 255) {
+    return -5;
+  }
+
+  CGGradientRelease(gradient);
+  CGContextRelease(context);
+  return 0;
+}
+
+int test_CGContextCTM() {
+  // 4×4 RGBA, kCGImageAlphaPremultipliedLast
+  unsigned char pixels[4 * 4 * 4] = {0};
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  CGContextRef context = CGBitmapContextCreate(pixels, 4, 4, 8, 16, space, 1);
+  CGColorSpaceRelease(space);
+
+  if (!CGAffineTransformIsIdentity(CGContextGetCTM(context))) {
+    return -1;
+  }
+
+  CGContextSaveGState(context);
+  CGContextTranslateCTM(context, 2, 1);
+  CGAffineTransform ctm = CGContextGetCTM(context);
+  if (ctm.a != 1 || ctm.d != 1 || ctm.tx != 2 || ctm.ty != 1) {
+    return -2;
+  }
+  CGContextSetRGBFillColor(context, 1.0, 0.0, 0.0, 1.0);
+  CGContextFillRect(context, (CGRect){{0, 0}, {1, 1}});
+  // Only the pixel at (2, 1) should have changed. The origin is in the
+  // bottom-left corner, but rows are stored top-to-bottom.
+  for (int y = 0; y < 4; y++) {
+    for (int x = 0; x < 4; x++) {
+      int filled = x == 2 && y == 4 - 1 - 1;
+      unsigned char *pixel = &pixels[(y * 4 + x) * 4];
+      if (pixel[0] != (filled ? 255 : 0) || pixel[3] != (filled ? 255 : 0)) {
+        return -3;
+      }
+    }
+  }
+
+  // Scaling composes with the existing translation.
+  CGContextScaleCTM(context, 2, 2);
+  ctm = CGContextGetCTM(context);
+  if (ctm.a != 2 || ctm.d != 2 || ctm.tx != 2 || ctm.ty != 1) {
+    return -4;
+  }
+  CGContextSetRGBFillColor(context, 0.0, 1.0, 0.0, 1.0);
+  // Covers (2, 3) to (4, 5) in device space, clipped to the bitmap.
+  CGContextFillRect(context, (CGRect){{0, 1}, {1, 1}});
+  if (pixels[(0 * 4 + 2) * 4 + 1] != 255 ||
+      pixels[(0 * 4 + 3) * 4 + 1] != 255 ||
+      pixels[(0 * 4 + 1) * 4 + 1] != 0 || pixels[(1 * 4 + 2) * 4 + 1] != 0) {
+    return -5;
+  }
+
+  // Restoring the state restores the CTM.
+  CGContextRestoreGState(context);
+  if (!CGAffineTransformIsIdentity(CGContextGetCTM(context))) {
+    return -6;
+  }
+  CGContextConcatCTM(context, CGAffineTransformMakeScale(1, -1));
+  ctm = CGContextGetCTM(context);
+  if (ctm.a != 1 || ctm.d != -1 || ctm.tx != 0 || ctm.ty != 0) {
+    return -7;
+  }
+  CGContextRelease(context);
+  return 0;
+}
+
+int test_CGContextDrawImage() {
+  // 2×2 RGBA, kCGImageAlphaPremultipliedLast. Rows are top-to-bottom.
+  unsigned char image_pixels[2 * 2 * 4] = {
+      255, 0,   0,   255, 0,   255, 0,   255, // red, green
+      0,   0,   255, 255, 255, 255, 255, 255, // blue, white
+  };
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  CGContextRef image_context =
+      CGBitmapContextCreate(image_pixels, 2, 2, 8, 8, space, 1);
+  CGImageRef image = CGBitmapContextCreateImage(image_context);
+  CGContextRelease(image_context);
+
+  // 4×4 RGBA, kCGImageAlphaPremultipliedLast
+  unsigned char pixels[4 * 4 * 4] = {0};
+  CGContextRef context = CGBitmapContextCreate(pixels, 4, 4, 8, 16, space, 1);
+  CGColorSpaceRelease(space);
+
+  // Scaling up should repeat each pixel in a 2×2 block, and the image should
+  // be the right way up.
+  CGContextDrawImage(context, (CGRect){{0, 0}, {4, 4}}, image);
+  for (int y = 0; y < 4; y++) {
+    for (int x = 0; x < 4; x++) {
+      unsigned char *expected = &image_pixels[((y / 2) * 2 + x / 2) * 4];
+      unsigned char *pixel = &pixels[(y * 4 + x) * 4];
+      if (memcmp(pixel, expected, 4) != 0) {
+        return -1;
+      }
+    }
+  }
+
+  // Tiling at the original size should repeat the whole image.
+  memset(pixels, 0, sizeof(pixels));
+  CGContextDrawTiledImage(context, (CGRect){{0, 0}, {2, 2}}, image);
+  for (int y = 0; y < 4; y++) {
+    for (int x = 0; x < 4; x++) {
+      unsigned char *expected = &image_pixels[((y % 2) * 2 + x % 2) * 4];
+      unsigned char *pixel = &pixels[(y * 4 + x) * 4];
+      if (memcmp(pixel, expected, 4) != 0) {
+        return -2;
+      }
+    }
+  }
+
+  CGContextRelease(context);
+  CGImageRelease(image);
+  return 0;
+}
+
+int test_UIImage_draw() {
+  // 2×2 RGBA, kCGImageAlphaPremultipliedLast. Rows are top-to-bottom.
+  unsigned char image_pixels[2 * 2 * 4] = {
+      255, 0,   0,   255, 0,   255, 0,   255, // red, green
+      0,   0,   255, 255, 255, 255, 255, 255, // blue, white
+  };
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  CGContextRef image_context =
+      CGBitmapContextCreate(image_pixels, 2, 2, 8, 8, space, 1);
+  CGColorSpaceRelease(space);
+  CGImageRef cg_image = CGBitmapContextCreateImage(image_context);
+  CGContextRelease(image_context);
+  CFStringRef name = CFStringCreateWithCString(NULL, "UIImage", 0x0600);
+  id image = ((id(*)(id, SEL, CGImageRef))objc_msgSend)(
+      NSClassFromString(name), sel_registerName("imageWithCGImage:"),
+      cg_image);
+  CGImageRelease(cg_image);
+
+  void (*draw_in_rect)(id, SEL, CGRect) =
+      (void (*)(id, SEL, CGRect))objc_msgSend;
+  SEL draw_in_rect_sel = sel_registerName("drawInRect:");
+
+  UIGraphicsBeginImageContext((CGSize){4, 4});
+  unsigned char *pixels = CGBitmapContextGetData(UIGraphicsGetCurrentContext());
+
+  // The image should be scaled up and the right way up, with its top row at
+  // the top of the image context.
+  draw_in_rect(image, draw_in_rect_sel, (CGRect){{0, 0}, {4, 4}});
+  for (int y = 0; y < 4; y++) {
+    for (int x = 0; x < 4; x++) {
+      unsigned char *expected = &image_pixels[((y / 2) * 2 + x / 2) * 4];
+      if (memcmp(&pixels[(y * 4 + x) * 4], expected, 4) != 0) {
+        return -1;
+      }
+    }
+  }
+
+  // With cap insets, the top-left corner keeps its size and the rest of the
+  // image is stretched.
+  UIEdgeInsets insets = {1, 1, 0, 0};
+  id resizable = ((id(*)(id, SEL, UIEdgeInsets))objc_msgSend)(
+      image, sel_registerName("resizableImageWithCapInsets:"), insets);
+  draw_in_rect(resizable, draw_in_rect_sel, (CGRect){{0, 0}, {4, 4}});
+  for (int y = 0; y < 4; y++) {
+    for (int x = 0; x < 4; x++) {
+      int part = (y == 0 ? 0 : 2) + (x == 0 ? 0 : 1);
+      unsigned char *expected = &image_pixels[part * 4];
+      if (memcmp(&pixels[(y * 4 + x) * 4], expected, 4) != 0) {
+        return -2;
+      }
+    }
+  }
+
+  id result = UIGraphicsGetImageFromCurrentImageContext();
+  UIGraphicsEndImageContext();
+  if (result == NULL) {
+    return -3;
+  }
+  CGSize (*get_size)(id, SEL) = (CGSize(*)(id, SEL))objc_msgSend_stret;
+  CGSize size = get_size(result, sel_registerName("size"));
+  if (size.width != 4 || size.height != 4) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_UIImage_PNG() {
+  UIGraphicsBeginImageContext((CGSize){6, 3});
+  CGContextRef context = UIGraphicsGetCurrentContext();
+  CGContextSetRGBFillColor(context, 0.0, 0.0, 1.0, 1.0);
+  CGContextFillRect(context, (CGRect){{0, 0}, {3, 3}});
+  id image = UIGraphicsGetImageFromCurrentImageContext();
+  UIGraphicsEndImageContext();
+
+  id png = UIImagePNGRepresentation(image);
+  if (png == NULL) {
+    return -1;
+  }
+  NSUInteger length = (NSUInteger)msg0(png, "length");
+  const unsigned char *bytes = (const unsigned char *)msg0(png, "bytes");
+  const unsigned char signature[8] = {0x89, 'P', 'N', 'G', '\r', '\n', 0x1a,
+                                      '\n'};
+  if (length < 8 || memcmp(bytes, signature, 8) != 0) {
+    return -2;
+  }
+
+  // The encoded data should decode back to an image of the same size.
+  CFStringRef name = CFStringCreateWithCString(NULL, "UIImage", 0x0600);
+  id decoded = objc_msgSend(NSClassFromString(name),
+                            sel_registerName("imageWithData:"), png);
+  if (decoded == NULL) {
+    return -3;
+  }
+  CGSize (*get_size)(id, SEL) = (CGSize(*)(id, SEL))objc_msgSend_stret;
+  CGSize size = get_size(decoded, sel_registerName("size"));
+  if (size.width != 6 || size.height != 3) {
+    return -4;
+  }
+
+  // The left half should still be blue and the right half transparent.
+  UIGraphicsBeginImageContext((CGSize){6, 3});
+  unsigned char *pixels = CGBitmapContextGetData(UIGraphicsGetCurrentContext());
+  ((void (*)(id, SEL, CGPoint))objc_msgSend)(
+      decoded, sel_registerName("drawAtPoint:"), (CGPoint){0, 0});
+  bool left_ok = pixels[2] == 255 && pixels[3] == 255;
+  bool right_ok = pixels[5 * 4 + 3] == 0;
+  UIGraphicsEndImageContext();
+  if (!left_ok || !right_ok) {
+    return -5;
+  }
+
+  // JPEG data starts with a start-of-image marker.
+  id jpeg = UIImageJPEGRepresentation(image, 0.8);
+  if (jpeg == NULL || (NSUInteger)msg0(jpeg, "length") < 2) {
+    return -6;
+  }
+  const unsigned char *jpeg_bytes =
+      (const unsigned char *)msg0(jpeg, "bytes");
+  if (jpeg_bytes[0] != 0xFF || jpeg_bytes[1] != 0xD8) {
+    return -7;
+  }
+  return 0;
+}
+
+int test_UIView_snapshot() {
+  id ui_color_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIColor", 0x0600));
+  SEL set_background_color = sel_registerName("setBackgroundColor:");
+
+  // A blue view that clips a red subview hanging off its bottom-right corner.
+  id view = new_view(0, 0, 4, 4);
+  objc_msgSend(view, set_background_color, msg0(ui_color_class, "blueColor"));
+  ((void (*)(id, SEL, bool))objc_msgSend)(
+      view, sel_registerName("setClipsToBounds:"), true);
+  id subview = new_view(2, 2, 4, 4);
+  objc_msgSend(subview, set_background_color,
+               msg0(ui_color_class, "redColor"));
+  objc_msgSend(view, sel_registerName("addSubview:"), subview);
+
+  UIGraphicsBeginImageContext((CGSize){6, 6});
+  unsigned char *pixels = CGBitmapContextGetData(UIGraphicsGetCurrentContext());
+  bool drawn = ((bool (*)(id, SEL, CGRect, bool))objc_msgSend)(
+      view, sel_registerName("drawViewHierarchyInRect:afterScreenUpdates:"),
+      (CGRect){{0, 0}, {4, 4}}, false);
+  id image = UIGraphicsGetImageFromCurrentImageContext();
+  UIGraphicsEndImageContext();
+  if (!drawn || image == NULL) {
+    return -1;
+  }
+
+  unsigned char *top_left = &pixels[(1 * 6 + 1) * 4];
+  unsigned char *bottom_right = &pixels[(3 * 6 + 3) * 4];
+  unsigned char *clipped = &pixels[(5 * 6 + 5) * 4];
+  if (top_left[0] != 0 || top_left[2] != 255 || top_left[3] != 255) {
+    return -2;
+  }
+  if (bottom_right[0] != 255 || bottom_right[2] != 0 ||
+      bottom_right[3] != 255) {
+    return -3;
+  }
+  if (clipped[3] != 0) {
+    return -4;
+  }
+
+  // Transforms are respected too: scaling the subview about its center
+  // makes it cover the whole view.
+  ((void (*)(id, SEL, CGAffineTransform))objc_msgSend)(
+      subview, sel_registerName("setTransform:"),
+      CGAffineTransformMakeScale(2, 2));
+  UIGraphicsBeginImageContext((CGSize){4, 4});
+  pixels = CGBitmapContextGetData(UIGraphicsGetCurrentContext());
+  objc_msgSend(msg0(view, "layer"), sel_registerName("renderInContext:"),
+               UIGraphicsGetCurrentContext());
+  bool all_red = pixels[0] == 255 && pixels[2] == 0;
+  UIGraphicsEndImageContext();
+  if (!all_red) {
+    return -5;
+  }
+  return 0;
+}
+
+int test_UIView_alpha_hidden() {
+  id ui_color_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIColor", 0x0600));
+  SEL set_background_color = sel_registerName("setBackgroundColor:");
+  SEL add_subview = sel_registerName("addSubview:");
+  void (*set_alpha)(id, SEL, CGFloat) =
+      (void (*)(id, SEL, CGFloat))objc_msgSend;
+
+  // An opaque red view inside a half-transparent container covers the whole
+  // black view, and a half-transparent blue view covers the right half.
+  id view = new_view(0, 0, 4, 4);
+  objc_msgSend(view, set_background_color,
+               msg0(ui_color_class, "blackColor"));
+  id container = new_view(0, 0, 4, 4);
+  set_alpha(container, sel_registerName("setAlpha:"), 0.5);
+  objc_msgSend(view, add_subview, container);
+  id red = new_view(0, 0, 4, 4);
+  objc_msgSend(red, set_background_color, msg0(ui_color_class, "redColor"));
+  objc_msgSend(container, add_subview, red);
+  id blue = new_view(2, 0, 2, 4);
+  id translucent_blue = ((id(*)(id, SEL, CGFloat, CGFloat, CGFloat, CGFloat))
+                             objc_msgSend)(
+      ui_color_class, sel_registerName("colorWithRed:green:blue:alpha:"), 0.0,
+      0.0, 1.0, 0.5);
+  objc_msgSend(blue, set_background_color, translucent_blue);
+  objc_msgSend(view, add_subview, blue);
+  // A hidden view hides its subviews too.
+  id hidden = new_view(0, 0, 4, 4);
+  ((void (*)(id, SEL, bool))objc_msgSend)(
+      hidden, sel_registerName("setHidden:"), true);
+  objc_msgSend(view, add_subview, hidden);
+  id green = new_view(0, 0, 4, 4);
+  objc_msgSend(green, set_background_color,
+               msg0(ui_color_class, "greenColor"));
+  objc_msgSend(hidden, add_subview, green);
+
+  UIGraphicsBeginImageContext((CGSize){4, 4});
+  unsigned char *pixels = CGBitmapContextGetData(UIGraphicsGetCurrentContext());
+  objc_msgSend(msg0(view, "layer"), sel_registerName("renderInContext:"),
+               UIGraphicsGetCurrentContext());
+  unsigned char left[4], right[4];
+  for (int i = 0; i < 4; i++) {
+    left[i] = pixels[(1 * 4 + 0) * 4 + i];
+    right[i] = pixels[(1 * 4 + 3) * 4 + i];
+  }
+  UIGraphicsEndImageContext();
+
+  // Half-transparent red over black.
+  if (left[0] == 0 || left[0] == 255 || left[1] != 0 || left[2] != 0 ||
+      left[3] != 255) {
+    return -1;
+  }
+  // Half-transparent blue over that.
+  if (right[0] == 0 || right[0] >= left[0] || right[1] != 0 ||
+      right[2] == 0 || right[2] == 255 || right[3] != 255) {
+    return -2;
+  }
+
+  msg0(green, "release");
+  msg0(hidden, "release");
+  msg0(blue, "release");
+  msg0(red, "release");
+  msg0(container, "release");
+  msg0(view, "release");
+  return 0;
+}
+
+id layout_test_view;
+int layout_test_count;
+
+void layout_test_layout_subviews(id self, SEL _cmd) {
+  if (self == layout_test_view) {
+    layout_test_count++;
+  }
+}
+
+void layout_test_run_frame() {
+  id run_loop = msg0(NSClassFromString(CFStringCreateWithCString(
+                         NULL, "NSRunLoop", 0x0600)),
+                     "currentRunLoop");
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSDate", 0x0600)),
+      sel_registerName("dateWithTimeIntervalSinceNow:"), 0.01);
+  objc_msgSend(run_loop, sel_registerName("runUntilDate:"), date);
+}
+
+int test_UIView_layout() {
+  // UIImageView only inherits layoutSubviews, so it can be overridden.
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIImageView", 0x0600)),
+      sel_registerName("layoutSubviews"), (IMP)layout_test_layout_subviews,
+      "v@:");
+  id window = new_object("UIWindow");
+  layout_test_view = new_object("UIImageView");
+  objc_msgSend(window, sel_registerName("addSubview:"), layout_test_view);
+
+  // New views are laid out once before they are first drawn.
+  layout_test_run_frame();
+  if (layout_test_count != 1) {
+    return -1;
+  }
+  layout_test_run_frame();
+  if (layout_test_count != 1) {
+    return -2;
+  }
+
+  // Layout is lazy: however often it's requested, it happens once, in the
+  // next frame.
+  msg0(layout_test_view, "setNeedsLayout");
+  msg0(layout_test_view, "setNeedsLayout");
+  if (layout_test_count != 1) {
+    return -3;
+  }
+  layout_test_run_frame();
+  if (layout_test_count != 2) {
+    return -4;
+  }
+
+  // ...unless it's forced with layoutIfNeeded.
+  msg0(layout_test_view, "setNeedsLayout");
+  msg0(layout_test_view, "layoutIfNeeded");
+  msg0(layout_test_view, "layoutIfNeeded");
+  layout_test_run_frame();
+  if (layout_test_count != 3) {
+    return -5;
+  }
+
+  msg0(layout_test_view, "removeFromSuperview");
+  msg0(layout_test_view, "release");
+  msg0(window, "release");
+  layout_test_view = NULL;
+  return 0;
+}
+
+int test_UIView_tag() {
+  SEL set_tag = sel_registerName("setTag:");
+  SEL view_with_tag = sel_registerName("viewWithTag:");
+  id root = new_object("UIView");
+  id child1 = new_object("UIView");
+  id child2 = new_object("UIView");
+  id grandchild = new_object("UIView");
+  id grandchild2 = new_object("UIView");
+  objc_msgSend(root, sel_registerName("addSubview:"), child1);
+  objc_msgSend(root, sel_registerName("addSubview:"), child2);
+  objc_msgSend(child1, sel_registerName("addSubview:"), grandchild);
+  objc_msgSend(child2, sel_registerName("addSubview:"), grandchild2);
+
+  // Zero is the default tag, so the receiver itself matches it.
+  if ((int)msg0(root, "tag") != 0 ||
+      objc_msgSend(root, view_with_tag, 0) != root) {
+    return -1;
+  }
+
+  objc_msgSend(root, set_tag, 1);
+  objc_msgSend(child1, set_tag, 2);
+  objc_msgSend(child2, set_tag, 3);
+  objc_msgSend(grandchild, set_tag, 3);
+  objc_msgSend(grandchild2, set_tag, 4);
+  if ((int)msg0(child1, "tag") != 2 ||
+      objc_msgSend(root, view_with_tag, 1) != root ||
+      objc_msgSend(root, view_with_tag, 2) != child1 ||
+      objc_msgSend(root, view_with_tag, 4) != grandchild2 ||
+      objc_msgSend(child2, view_with_tag, 3) != child2 ||
+      objc_msgSend(root, view_with_tag, 5) != NULL) {
+    return -2;
+  }
+  // The search is depth-first, so the first child's subviews come before the
+  // second child.
+  if (objc_msgSend(root, view_with_tag, 3) != grandchild) {
+    return -3;
+  }
+  // The superview isn't searched.
+  if (objc_msgSend(child1, view_with_tag, 1) != NULL) {
+    return -4;
+  }
+
+  msg0(grandchild2, "release");
+  msg0(grandchild, "release");
+  msg0(child2, "release");
+  msg0(child1, "release");
+  msg0(root, "release");
+  return 0;
+}
+
+int test_CATransaction() {
+  CFStringRef name = CFStringCreateWithCString(NULL, "CATransaction", 0x0600);
+  id transaction = NSClassFromString(name);
+  id layer = new_object("CALayer");
+  void (*set_opacity)(id, SEL, float) = (void (*)(id, SEL, float))objc_msgSend;
+  float (*get_opacity)(id, SEL) = (float (*)(id, SEL))objc_msgSend;
+  SEL set_opacity_sel = sel_registerName("setOpacity:");
+  SEL get_opacity_sel = sel_registerName("opacity");
+
+  // The change is animated over the transaction's duration. The model value
+  // changes immediately, but the presentation value is interpolated.
+  msg0(transaction, "begin");
+  ((void (*)(id, SEL, double))objc_msgSend)(
+      transaction, sel_registerName("setAnimationDuration:"), 2.0);
+  set_opacity(layer, set_opacity_sel, 0.0);
+  msg0(transaction, "commit");
+  if (get_opacity(layer, get_opacity_sel) != 0.0) {
+    return -1;
+  }
+  usleep(500000);
+  float opacity =
+      get_opacity(msg0(layer, "presentationLayer"), get_opacity_sel);
+  if (!(opacity > 0.0 && opacity < 1.0)) {
+    return -2;
+  }
+
+  // With actions disabled, the change is instant.
+  msg0(transaction, "begin");
+  ((void (*)(id, SEL, bool))objc_msgSend)(
+      transaction, sel_registerName("setDisableActions:"), true);
+  set_opacity(layer, set_opacity_sel, 1.0);
+  msg0(transaction, "commit");
+  opacity = get_opacity(msg0(layer, "presentationLayer"), get_opacity_sel);
+  if (opacity != 1.0) {
+    return -3;
+  }
+
+  msg0(layer, "release");
+  return 0;
+}
+
+int test_CABasicAnimation() {
+  CFStringRef name = CFStringCreateWithCString(NULL, "NSNumber", 0x0600);
+  id number = NSClassFromString(name);
+  name = CFStringCreateWithCString(NULL, "CABasicAnimation", 0x0600);
+  id animation_class = NSClassFromString(name);
+  id (*number_with_float)(id, SEL, float) =
+      (id(*)(id, SEL, float))objc_msgSend;
+  SEL number_with_float_sel = sel_registerName("numberWithFloat:");
+  float (*get_opacity)(id, SEL) = (float (*)(id, SEL))objc_msgSend;
+  SEL get_opacity_sel = sel_registerName("opacity");
+  id layer = new_object("CALayer");
+
+  id animation =
+      objc_msgSend(animation_class, sel_registerName("animationWithKeyPath:"),
+                   new_string("opacity"));
+  objc_msgSend(animation, sel_registerName("setFromValue:"),
+               number_with_float(number, number_with_float_sel, 0.2));
+  objc_msgSend(animation, sel_registerName("setToValue:"),
+               number_with_float(number, number_with_float_sel, 0.8));
+  ((void (*)(id, SEL, double))objc_msgSend)(
+      animation, sel_registerName("setDuration:"), 1.0);
+  objc_msgSend(layer, sel_registerName("addAnimation:forKey:"), animation,
+               new_string("fade"));
+
+  // The model value is unaffected, but the presentation value is
+  // interpolated.
+  if (get_opacity(layer, get_opacity_sel) != 1.0) {
+    return -1;
+  }
+  usleep(500000);
+  float opacity =
+      get_opacity(msg0(layer, "presentationLayer"), get_opacity_sel);
+  if (!(opacity > 0.2 && opacity < 0.8)) {
+    return -2;
+  }
+  if (objc_msgSend(layer, sel_registerName("animationForKey:"),
+                   new_string("fade")) == NULL) {
+    return -3;
+  }
+
+  // Once finished, the animation is removed (the delegate would be told at
+  // this point) and the model value is displayed again.
+  usleep(700000);
+  if (objc_msgSend(layer, sel_registerName("animationForKey:"),
+                   new_string("fade")) != NULL) {
+    return -4;
+  }
+  opacity = get_opacity(msg0(layer, "presentationLayer"), get_opacity_sel);
+  if (opacity != 1.0) {
+    return -5;
+  }
+
+  msg0(layer, "release");
+  return 0;
+}
+
+int test_UIBezierPath() {
+  CFStringRef name = CFStringCreateWithCString(NULL, "UIBezierPath", 0x0600);
+  id path = msg0(NSClassFromString(name), "bezierPath");
+  void (*add_point)(id, SEL, CGPoint) =
+      (void (*)(id, SEL, CGPoint))objc_msgSend;
+  SEL line_sel = sel_registerName("addLineToPoint:");
+  add_point(path, sel_registerName("moveToPoint:"), (CGPoint){0, 0});
+  add_point(path, line_sel, (CGPoint){8, 0});
+  add_point(path, line_sel, (CGPoint){0, 8});
+  msg0(path, "closePath");
+
+  bool (*contains_point)(id, SEL, CGPoint) =
+      (bool (*)(id, SEL, CGPoint))objc_msgSend;
+  SEL contains_point_sel = sel_registerName("containsPoint:");
+  if (!contains_point(path, contains_point_sel, (CGPoint){2, 2}) ||
+      contains_point(path, contains_point_sel, (CGPoint){7, 7})) {
+    return -1;
+  }
+
+  // Only pixels inside the triangle are filled.
+  UIGraphicsBeginImageContext((CGSize){8, 8});
+  CGContextRef context = UIGraphicsGetCurrentContext();
+  unsigned char *pixels = CGBitmapContextGetData(context);
+  CGContextSetRGBFillColor(context, 1.0, 0.0, 0.0, 1.0);
+  msg0(path, "fill");
+  for (int y = 0; y < 8; y++) {
+    for (int x = 0; x < 8; x++) {
+      unsigned char *pixel = &pixels[(y * 8 + x) * 4];
+      bool inside = x + y < 7;
+      bool outside = x + y > 7;
+      if ((inside && (pixel[0] != 255 || pixel[3] != 255)) ||
+          (outside && pixel[3] != 0)) {
+        return -2;
+      }
+    }
+  }
+  UIGraphicsEndImageContext();
+  return 0;
+}
+
+static bool rect_nearly_equal(CGRect a, CGRect b) {
+  CGFloat values[4] = {a.origin.x - b.origin.x, a.origin.y - b.origin.y,
+                       a.size.width - b.size.width,
+                       a.size.height - b.size.height};
+  for (int i = 0; i < 4; i++) {
+    if (values[i] > 0.001 || values[i] < -0.001) {
+      return false;
+    }
+  }
+  return true;
+}
+
+int test_CGPath() {
+  CGMutablePathRef path = CGPathCreateMutable();
+  if (!CGPathIsEmpty(path)) {
+    return -1;
+  }
+  // A square, moved by the transform, and an ellipse next to it.
+  CGAffineTransform transform = CGAffineTransformMakeTranslation(5, 5);
+  CGPathAddRect(path, &transform, (CGRect){{0, 0}, {10, 10}});
+  CGPathAddEllipseInRect(path, NULL, (CGRect){{20, 0}, {10, 4}});
+  if (CGPathIsEmpty(path) ||
+      !rect_nearly_equal(CGPathGetBoundingBox(path),
+                         (CGRect){{5, 0}, {25, 15}})) {
+    return -2;
+  }
+  if (!CGPathContainsPoint(path, NULL, (CGPoint){10, 10}, false) ||
+      !CGPathContainsPoint(path, NULL, (CGPoint){25, 2}, false) ||
+      CGPathContainsPoint(path, NULL, (CGPoint){2, 2}, false) ||
+      CGPathContainsPoint(path, NULL, (CGPoint){16, 10}, false) ||
+      CGPathContainsPoint(path, NULL, (CGPoint){20.5, 0.5}, false)) {
+    return -3;
+  }
+  // The transform applies to the point being tested.
+  if (!CGPathContainsPoint(path, &transform, (CGPoint){0, 0}, false)) {
+    return -4;
+  }
+
+  // A square inside the first one is a hole only with the even-odd rule.
+  CGMutablePathRef inner = CGPathCreateMutable();
+  CGPathMoveToPoint(inner, NULL, 8, 8);
+  CGPathAddLineToPoint(inner, NULL, 12, 8);
+  CGPathAddLineToPoint(inner, NULL, 12, 12);
+  CGPathAddLineToPoint(inner, NULL, 8, 12);
+  CGPathCloseSubpath(inner);
+  CGPathAddPath(path, NULL, inner);
+  CGPathRelease(inner);
+  if (!CGPathContainsPoint(path, NULL, (CGPoint){10, 10}, false) ||
+      CGPathContainsPoint(path, NULL, (CGPoint){10, 10}, true) ||
+      !CGPathContainsPoint(path, NULL, (CGPoint){6, 6}, true)) {
+    return -5;
+  }
+  CGPathRelease(path);
+
+  // A half circle, closed by its diameter.
+  CGMutablePathRef arc = CGPathCreateMutable();
+  CGPathAddArc(arc, NULL, 0, 0, 4, 0, 3.14159265f, false);
+  CGPathCloseSubpath(arc);
+  if (!rect_nearly_equal(CGPathGetBoundingBox(arc),
+                         (CGRect){{-4, 0}, {8, 4}}) ||
+      !CGPathContainsPoint(arc, NULL, (CGPoint){0, 2}, false) ||
+      CGPathContainsPoint(arc, NULL, (CGPoint){0, -2}, false)) {
+    return -6;
+  }
+  CGPathRelease(arc);
+
+  // Drawing a path with a context.
+  CGMutablePathRef square = CGPathCreateMutable();
+  CGPathAddRect(square, NULL, (CGRect){{2, 2}, {4, 4}});
+  UIGraphicsBeginImageContext((CGSize){8, 8});
+  CGContextRef context = UIGraphicsGetCurrentContext();
+  unsigned char *pixels = CGBitmapContextGetData(context);
+  CGContextSetRGBFillColor(context, 1.0, 0.0, 0.0, 1.0);
+  CGContextAddPath(context, square);
+  CGContextFillPath(context);
+  CGPathRelease(square);
+  for (int y = 0; y < 8; y++) {
+    for (int x = 0; x < 8; x++) {
+      unsigned char *pixel = &pixels[(y * 8 + x) * 4];
+      bool inside = x >= 2 && x < 6 && y >= 2 && y < 6;
+      if (pixel[3] != (inside ? 255 : 0)) {
+        return -7;
+      }
+    }
+  }
+  UIGraphicsEndImageContext();
+  return 0;
+}
+
+int test_CAShapeLayer() {
+  id layer = new_object("CAShapeLayer");
+  ((void (*)(id, SEL, CGRect))objc_msgSend)(
+      layer, sel_registerName("setBounds:"), (CGRect){{0, 0}, {8, 8}});
+  CGMutablePathRef path = CGPathCreateMutable();
+  CGPathMoveToPoint(path, NULL, 0, 0);
+  CGPathAddLineToPoint(path, NULL, 8, 0);
+  CGPathAddLineToPoint(path, NULL, 0, 8);
+  CGPathCloseSubpath(path);
+  objc_msgSend(layer, sel_registerName("setPath:"), path);
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  CGColorRef red = CGColorCreate(space, (CGFloat[]){1.0, 0.0, 0.0, 1.0});
+  objc_msgSend(layer, sel_registerName("setFillColor:"), red);
+  CGColorRelease(red);
+  CGColorSpaceRelease(space);
+  // The layer keeps its own references.
+  CGPathRelease(path);
+  if ((CGPathRef)msg0(layer, "path") != path ||
+      (CGColorRef)msg0(layer, "fillColor") != red) {
+    return -1;
+  }
+
+  // Only pixels inside the triangle are filled.
+  UIGraphicsBeginImageContext((CGSize){8, 8});
+  CGContextRef context = UIGraphicsGetCurrentContext();
+  unsigned char *pixels = CGBitmapContextGetData(context);
+  objc_msgSend(layer, sel_registerName("renderInContext:"), context);
+  for (int y = 0; y < 8; y++) {
+    for (int x = 0; x < 8; x++) {
+      unsigned char *pixel = &pixels[(y * 8 + x) * 4];
+      bool inside = x + y < 7;
+      bool outside = x + y > 7;
+      if ((inside && (pixel[0] != 255 || pixel[3] != 255)) ||
+          (outside && pixel[3] != 0)) {
+        return -2;
+      }
+    }
+  }
+  UIGraphicsEndImageContext();
+  msg0(layer, "release");
+  return 0;
+}
+
+int test_CAGradientLayer() {
+  id layer = new_object("CAGradientLayer");
+  ((void (*)(id, SEL, CGRect))objc_msgSend)(
+      layer, sel_registerName("setBounds:"), (CGRect){{0, 0}, {4, 8}});
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  const void *colors[2] = {
+      CGColorCreate(space, (CGFloat[]){1.0, 0.0, 0.0, 1.0}),
+      CGColorCreate(space, (CGFloat[]){0.0, 0.0, 1.0, 1.0}),
+  };
+  CFArrayRef array = CFArrayCreate(NULL, colors, 2, &kCFTypeArrayCallBacks);
+  objc_msgSend(layer, sel_registerName("setColors:"), array);
+  CFRelease(array);
+  CGColorRelease((CGColorRef)colors[0]);
+  CGColorRelease((CGColorRef)colors[1]);
+  CGColorSpaceRelease(space);
+  if ((NSUInteger)msg0(msg0(layer, "colors"), "count") != 2) {
+    return -1;
+  }
+
+  // By default the gradient goes from the top to the bottom.
+  UIGraphicsBeginImageContext((CGSize){4, 8});
+  CGContextRef context = UIGraphicsGetCurrentContext();
+  unsigned char *pixels = CGBitmapContextGetData(context);
+  objc_msgSend(layer, sel_registerName("renderInContext:"), context);
+  for (int y = 0; y < 8; y++) {
+    for (int x = 0; x < 4; x++) {
+      unsigned char *pixel = &pixels[(y * 4 + x) * 4];
+      if (pixel[1] != 0 || pixel[3] != 255) {
+        return -2;
+      }
+      bool top = y == 0 && (pixel[0] < 224 || pixel[2] > 32);
+      bool bottom = y == 7 && (pixel[0] > 32 || pixel[2] < 224);
+      bool middle = (y == 3 || y == 4) && (pixel[0] < 64 || pixel[0] > 192 ||
+                                           pixel[2] < 64 || pixel[2] > 192);
+      if (top || bottom || middle) {
+        return -3;
+      }
+    }
+  }
+  // The colors change smoothly from top to bottom.
+  for (int y = 1; y < 8; y++) {
+    if (pixels[y * 16] >= pixels[(y - 1) * 16]) {
+      return -4;
+    }
+  }
+  UIGraphicsEndImageContext();
+  msg0(layer, "release");
+  return 0;
+}
+
+int test_CALayer_border() {
+  id layer = new_object("CALayer");
+  ((void (*)(id, SEL, CGRect))objc_msgSend)(
+      layer, sel_registerName("setBounds:"), (CGRect){{0, 0}, {16, 16}});
+
+  // The shadow and border colors default to opaque black.
+  const CGFloat *shadow = CGColorGetComponents(msg0(layer, "shadowColor"));
+  const CGFloat *border = CGColorGetComponents(msg0(layer, "borderColor"));
+  if (shadow[0] != 0.0 || shadow[3] != 1.0 || border[0] != 0.0 ||
+      border[3] != 1.0) {
+    return -1;
+  }
+  CGSize offset = ((CGSize(*)(id, SEL))objc_msgSend_stret)(
+      layer, sel_registerName("shadowOffset"));
+  if (offset.width != 0.0 || offset.height != -3.0) {
+    return -2;
+  }
+
+  CGColorSpaceRef space = CGColorSpaceCreateDeviceRGB();
+  CGColorRef red = CGColorCreate(space, (CGFloat[]){1.0, 0.0, 0.0, 1.0});
+  CGColorRef blue = CGColorCreate(space, (CGFloat[]){0.0, 0.0, 1.0, 1.0});
+  objc_msgSend(layer, sel_registerName("setBackgroundColor:"), red);
+  objc_msgSend(layer, sel_registerName("setBorderColor:"), blue);
+  CGColorRelease(red);
+  CGColorRelease(blue);
+  CGColorSpaceRelease(space);
+  ((void (*)(id, SEL, CGFloat))objc_msgSend)(
+      layer, sel_registerName("setCornerRadius:"), 6.0);
+  ((void (*)(id, SEL, CGFloat))objc_msgSend)(
+      layer, sel_registerName("setBorderWidth:"), 2.0);
+  ((void (*)(id, SEL, bool))objc_msgSend)(
+      layer, sel_registerName("setMasksToBounds:"), true);
+  if ((CGColorRef)msg0(layer, "borderColor") != blue ||
+      ((CGFloat(*)(id, SEL))objc_msgSend)(
+          layer, sel_registerName("cornerRadius")) != 6.0) {
+    return -3;
+  }
+
+  UIGraphicsBeginImageContext((CGSize){16, 16});
+  CGContextRef context = UIGraphicsGetCurrentContext();
+  unsigned char *pixels = CGBitmapContextGetData(context);
+  objc_msgSend(layer, sel_registerName("renderInContext:"), context);
+  // The corners are cut off...
+  int corners[4][2] = {{0, 0}, {15, 0}, {0, 15}, {15, 15}};
+  for (int i = 0; i < 4; i++) {
+    if (pixels[(corners[i][1] * 16 + corners[i][0]) * 4 + 3] != 0) {
+      return -4;
+    }
+  }
+  // ...the border runs along the edges...
+  int edges[4][2] = {{8, 0}, {0, 8}, {15, 8}, {8, 15}};
+  for (int i = 0; i < 4; i++) {
+    unsigned char *pixel = &pixels[(edges[i][1] * 16 + edges[i][0]) * 4];
+    if (pixel[0] != 0 || pixel[2] != 255 || pixel[3] != 255) {
+      return -5;
+    }
+  }
+  // ...and the background fills the rest.
+  unsigned char *inside = &pixels[(8 * 16 + 3) * 4];
+  unsigned char *center = &pixels[(8 * 16 + 8) * 4];
+  if (inside[0] != 255 || inside[2] != 0 || center[0] != 255 ||
+      center[2] != 0 || center[3] != 255) {
+    return -6;
+  }
+  UIGraphicsEndImageContext();
+  msg0(layer, "release");
+  return 0;
+}
+
+int test_UIGraphics_PDF() {
+  const char *path = "/var/mobile/Applications/"
+                     "00000000-0000-0000-0000-000000000000/Documents/test.pdf";
+  if (!UIGraphicsBeginPDFContextToFile(new_string(path),
+                                       (CGRect){{0, 0}, {100, 50}}, NULL)) {
+    return -1;
+  }
+  UIGraphicsBeginPDFPage();
+  CGContextRef context = UIGraphicsGetCurrentContext();
+  if (context == NULL) {
+    return -2;
+  }
+  CGContextSetRGBFillColor(context, 0.0, 0.0, 1.0, 1.0);
+  CGContextFillRect(context, (CGRect){{10, 10}, {20, 20}});
+  UIGraphicsEndPDFContext();
+  if (UIGraphicsGetCurrentContext() != NULL) {
+    return -3;
+  }
+
+  FILE *file = fopen(path, "r");
+  if (!file) {
+    return -4;
+  }
+  char header[257];
+  size_t read = fread(header, 1, 256, file);
+  fclose(file);
+  remove(path);
+  header[read] = '\0';
+  // The page dictionary comes before any binary data.
+  if (read < 8 || memcmp(header, "%PDF-", 5) != 0 ||
+      !strstr(header, "/MediaBox [0 0 100 50]")) {
+    return -5;
+  }
+  return 0;
+}
+
+int test_SCNetworkReachability() {
+  SCNetworkReachabilityRef reachability =
+      SCNetworkReachabilityCreateWithName(NULL, "www.example.com");
+  if (reachability == NULL) {
+    return -1;
+  }
+  SCNetworkReachabilityFlags flags = 0;
+  bool success = SCNetworkReachabilityGetFlags(reachability, &flags);
+  CFRelease(reachability);
+  if (!success) {
+    return -2;
+  }
+  // The default is to be reachable via WiFi.
+  if (!(flags & kSCNetworkReachabilityFlagsReachable)) {
+    return -3;
+  }
+  return 0;
+}
+
+pthread_t background_test_thread;
+int background_test_result;
+
+void background_test_action(id self, SEL _cmd, id arg) {
+  background_test_thread = pthread_self();
+  // The argument must still be alive even though the caller released it.
+  bool is_equal = ((bool (*)(id, SEL, id))objc_msgSend)(
+      arg, sel_registerName("isEqualToString:"), new_string("background"));
+  background_test_result = is_equal ? 1 : -1;
+}
+
+int test_performSelectorInBackground() {
+  SEL action = sel_registerName("backgroundTestAction:");
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSObject", 0x0600)),
+      action, (IMP)background_test_action, "v@:@");
+  id target = new_object("NSObject");
+  id arg = msg0(new_string("background"), "mutableCopy");
+  ((void (*)(id, SEL, SEL, id))objc_msgSend)(
+      target, sel_registerName("performSelectorInBackground:withObject:"),
+      action, arg);
+  msg0(arg, "release");
+  msg0(target, "release");
+
+  for (int i = 0; i < 100 && background_test_result == 0; i++) {
+    usleep(10000);
+  }
+  if (background_test_result != 1) {
+    return -1;
+  }
+  if (background_test_thread == pthread_self()) {
+    return -2;
+  }
+  return 0;
+}
+
+int dispatch_test_result;
+dispatch_once_t dispatch_test_once;
+int dispatch_test_once_count;
+
+void *dispatch_test_thread(void *main_thread) {
+  // Capturing a variable makes this a stack block, which must be copied.
+  dispatch_async(dispatch_get_main_queue(), ^{
+    dispatch_test_result = pthread_self() == main_thread ? 1 : -1;
+  });
+  return NULL;
+}
+
+int test_dispatch_async() {
+  dispatch_test_result = 0;
+  pthread_t thread;
+  pthread_create(&thread, NULL, dispatch_test_thread, pthread_self());
+
+  // The block can only run once the main thread runs its run loop.
+  id run_loop = msg0(NSClassFromString(CFStringCreateWithCString(
+                         NULL, "NSRunLoop", 0x0600)),
+                     "currentRunLoop");
+  id date_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSDate", 0x0600));
+  int i;
+  for (i = 0; i < 20 && dispatch_test_result == 0; i++) {
+    id date = ((id(*)(id, SEL, double))objc_msgSend)(
+        date_class, sel_registerName("dateWithTimeIntervalSinceNow:"), 0.05);
+    ((void (*)(id, SEL, id))objc_msgSend)(
+        run_loop, sel_registerName("runUntilDate:"), date);
+  }
+  if (dispatch_test_result != 1) {
+    return -1;
+  }
+
+  for (i = 0; i < 2; i++) {
+    dispatch_once(&dispatch_test_once, ^{
+      dispatch_test_once_count++;
+    });
+  }
+  if (dispatch_test_once_count != 1) {
+    return -2;
+  }
+  return 0;
+}
+
+int dispatch_test_produced;
+int dispatch_test_group_count;
+int dispatch_test_group_notified;
+
+void *dispatch_test_producer(void *semaphore) {
+  int i;
+  for (i = 0; i < 3; i++) {
+    usleep(1000);
+    dispatch_test_produced++;
+    dispatch_semaphore_signal(semaphore);
+  }
+  return NULL;
+}
+
+int test_dispatch_semaphore_group() {
+  dispatch_semaphore_t semaphore = dispatch_semaphore_create(0);
+  // Nothing has been produced yet, so a wait with a timeout fails.
+  if (dispatch_semaphore_wait(semaphore, dispatch_time(DISPATCH_TIME_NOW,
+                                                       1000000)) == 0) {
+    return -1;
+  }
+  dispatch_test_produced = 0;
+  pthread_t thread;
+  pthread_create(&thread, NULL, dispatch_test_producer, semaphore);
+  int i;
+  for (i = 0; i < 3; i++) {
+    if (dispatch_semaphore_wait(semaphore, DISPATCH_TIME_FOREVER) != 0) {
+      return -2;
+    }
+  }
+  if (dispatch_test_produced != 3) {
+    return -3;
+  }
+
+  dispatch_test_group_count = 0;
+  dispatch_test_group_notified = 0;
+  dispatch_group_t group = dispatch_group_create();
+  dispatch_queue_t queue = dispatch_get_global_queue(0, 0);
+  for (i = 0; i < 3; i++) {
+    dispatch_group_async(group, queue, ^{
+      usleep(1000);
+      dispatch_test_group_count++;
+    });
+  }
+  dispatch_group_notify(group, queue, ^{
+    dispatch_test_group_notified = dispatch_test_group_count;
+    dispatch_semaphore_signal(semaphore);
+  });
+  if (dispatch_group_wait(group, DISPATCH_TIME_FOREVER) != 0) {
+    return -4;
+  }
+  if (dispatch_test_group_count != 3) {
+    return -5;
+  }
+  // The notification block runs after all the others.
+  if (dispatch_semaphore_wait(semaphore, dispatch_time(DISPATCH_TIME_NOW,
+                                                       1000000000)) != 0 ||
+      dispatch_test_group_notified != 3) {
+    return -6;
+  }
+  dispatch_release(group);
+  dispatch_release(semaphore);
+  return 0;
+}
+
+int condition_test_ready;
+int condition_test_value;
+
+void *condition_test_producer(void *condition) {
+  usleep(10000);
+  msg0(condition, "lock");
+  condition_test_value = 42;
+  condition_test_ready = 1;
+  msg0(condition, "signal");
+  msg0(condition, "unlock");
+  return NULL;
+}
+
+void *condition_lock_test_producer(void *lock) {
+  usleep(10000);
+  msg0(lock, "lock");
+  condition_test_value = 43;
+  ((void (*)(id, SEL, int))objc_msgSend)(
+      lock, sel_registerName("unlockWithCondition:"), 1);
+  return NULL;
+}
+
+int test_NSCondition() {
+  id date_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSDate", 0x0600));
+  id condition = new_object("NSCondition");
+  condition_test_ready = 0;
+  condition_test_value = 0;
+
+  // Nobody signals, so a wait with a timeout fails.
+  msg0(condition, "lock");
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(
+      date_class, sel_registerName("dateWithTimeIntervalSinceNow:"), 0.01);
+  if (((bool (*)(id, SEL, id))objc_msgSend)(
+          condition, sel_registerName("waitUntilDate:"), date)) {
+    return -1;
+  }
+  msg0(condition, "unlock");
+
+  // The consumer waits until the producer thread signals.
+  pthread_t thread;
+  pthread_create(&thread, NULL, condition_test_producer, condition);
+  msg0(condition, "lock");
+  while (!condition_test_ready) {
+    msg0(condition, "wait");
+  }
+  int value = condition_test_value;
+  msg0(condition, "unlock");
+  if (value != 42) {
+    return -2;
+  }
+
+  id lock = ((id(*)(id, SEL, int))objc_msgSend)(
+      msg0(NSClassFromString(
+               CFStringCreateWithCString(NULL, "NSConditionLock", 0x0600)),
+           "alloc"),
+      sel_registerName("initWithCondition:"), 0);
+  if (((bool (*)(id, SEL, int))objc_msgSend)(
+          lock, sel_registerName("tryLockWhenCondition:"), 1)) {
+    return -3;
+  }
+  pthread_create(&thread, NULL, condition_lock_test_producer, lock);
+  ((void (*)(id, SEL, int))objc_msgSend)(
+      lock, sel_registerName("lockWhenCondition:"), 1);
+  value = condition_test_value;
+  int lock_condition = (int)msg0(lock, "condition");
+  ((void (*)(id, SEL, int))objc_msgSend)(
+      lock, sel_registerName("unlockWithCondition:"), 0);
+  if (value != 43 || lock_condition != 1) {
+    return -4;
+  }
+  return 0;
+}
+
+pthread_mutex_t pthread_test_mutex = PTHREAD_MUTEX_INITIALIZER;
+pthread_cond_t pthread_test_cond = PTHREAD_COND_INITIALIZER;
+int pthread_test_counter;
+int pthread_test_finished;
+
+void *pthread_test_thread(void *arg) {
+  int increments = *(int *)arg;
+  for (int i = 0; i < increments; i++) {
+    pthread_mutex_lock(&pthread_test_mutex);
+    pthread_test_counter++;
+    pthread_mutex_unlock(&pthread_test_mutex);
+  }
+  pthread_mutex_lock(&pthread_test_mutex);
+  pthread_test_finished++;
+  pthread_cond_broadcast(&pthread_test_cond);
+  pthread_mutex_unlock(&pthread_test_mutex);
+  return (void *)(long)(increments * 2);
+}
+
+int test_pthread() {
+  int increments = 1000;
+  pthread_test_counter = 0;
+  pthread_test_finished = 0;
+
+  pthread_t threads[2];
+  for (int i = 0; i < 2; i++) {
+    if (pthread_create(&threads[i], NULL, pthread_test_thread, &increments)) {
+      return -1;
+    }
+  }
+
+  // Wait for both threads to signal they are done.
+  pthread_mutex_lock(&pthread_test_mutex);
+  while (pthread_test_finished < 2) {
+    pthread_cond_wait(&pthread_test_cond, &pthread_test_mutex);
+  }
+  int counter = pthread_test_counter;
+  pthread_mutex_unlock(&pthread_test_mutex);
+  if (counter != 2000) {
+    return -2;
+  }
+
+  for (int i = 0; i < 2; i++) {
+    void *result;
+    if (pthread_join(threads[i], &result) || result != (void *)2000) {
+      return -3;
+    }
+  }
+
+  // A detached thread still runs, but can't be joined.
+  pthread_t detached;
+  pthread_create(&detached, NULL, pthread_test_thread, &increments);
+  if (pthread_detach(detached)) {
+    return -4;
+  }
+  pthread_mutex_lock(&pthread_test_mutex);
+  while (pthread_test_finished < 3) {
+    pthread_cond_wait(&pthread_test_cond, &pthread_test_mutex);
+  }
+  counter = pthread_test_counter;
+  pthread_mutex_unlock(&pthread_test_mutex);
+  if (counter != 3000) {
+    return -5;
+  }
+  return 0;
+}
+
+pthread_key_t pthread_key_test_key;
+int pthread_key_test_destructed[2];
+
+void pthread_key_test_destructor(void *value) {
+  // The value is cleared before the destructor is called.
+  if (pthread_getspecific(pthread_key_test_key) == NULL) {
+    *(int *)value += 1;
+  }
+}
+
+void *pthread_key_test_thread(void *arg) {
+  if (pthread_getspecific(pthread_key_test_key) != NULL) {
+    return (void *)-1;
+  }
+  pthread_setspecific(pthread_key_test_key, arg);
+  // Give the other thread a chance to set its value.
+  usleep(10000);
+  return pthread_getspecific(pthread_key_test_key);
+}
+
+int test_pthread_key() {
+  if (pthread_key_create(&pthread_key_test_key, pthread_key_test_destructor)) {
+    return -1;
+  }
+  pthread_key_test_destructed[0] = 0;
+  pthread_key_test_destructed[1] = 0;
+  pthread_setspecific(pthread_key_test_key, &pthread_key_test_key);
+
+  pthread_t threads[2];
+  for (int i = 0; i < 2; i++) {
+    pthread_create(&threads[i], NULL, pthread_key_test_thread,
+                   &pthread_key_test_destructed[i]);
+  }
+  for (int i = 0; i < 2; i++) {
+    void *result;
+    pthread_join(threads[i], &result);
+    // Each thread sees its own value, and the destructor ran when it exited.
+    if (result != &pthread_key_test_destructed[i] ||
+        pthread_key_test_destructed[i] != 1) {
+      return -2;
+    }
+  }
+
+  // The other threads' values don't affect this one.
+  if (pthread_getspecific(pthread_key_test_key) != &pthread_key_test_key) {
+    return -3;
+  }
+  if (pthread_key_delete(pthread_key_test_key)) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_Block_copy() {
+  __block int counter = 0;
+  void (^block)(int) = ^(int amount) {
+    counter += amount;
+  };
+  // Copying the block moves the __block variable to the heap, but both the
+  // copy and this function must still see the same variable.
+  void (^copy)(int) = _Block_copy(block);
+  copy(2);
+  copy(3);
+  if (counter != 5) {
+    return -1;
+  }
+  block(1);
+  if (counter != 6) {
+    return -2;
+  }
+  // Copying a heap block just retains it.
+  if (_Block_copy(copy) != copy) {
+    return -3;
+  }
+  _Block_release(copy);
+  _Block_release(copy);
+  return 0;
+}
+
+int test_sysctl() {
+  // First query the size, then get the value.
+  size_t size = 0;
+  if (sysctlbyname("hw.machine", NULL, &size, NULL, 0) != 0 ||
+      size != sizeof("iPhone1,1")) {
+    return -1;
+  }
+  char machine[16];
+  size = sizeof(machine);
+  if (sysctlbyname("hw.machine", machine, &size, NULL, 0) != 0 ||
+      size != sizeof("iPhone1,1") || strcmp(machine, "iPhone1,1") != 0) {
+    return -2;
+  }
+  // A buffer that is too small is an error.
+  size = 4;
+  if (sysctlbyname("hw.machine", machine, &size, NULL, 0) != -1) {
+    return -3;
+  }
+  int mib[2] = {CTL_HW, HW_NCPU};
+  int ncpu = 0;
+  size = sizeof(ncpu);
+  if (sysctl(mib, 2, &ncpu, &size, NULL, 0) != 0 || ncpu != 1) {
+    return -4;
+  }
+  if (sysctlbyname("hw.nonexistent", NULL, &size, NULL, 0) != -1) {
+    return -5;
+  }
+  return 0;
+}
+
+int test_getaddrinfo() {
+  struct addrinfo hints;
+  memset(&hints, 0, sizeof(hints));
+  hints.ai_family = AF_INET;
+  hints.ai_socktype = SOCK_STREAM;
+  struct addrinfo *res = NULL;
+  if (getaddrinfo("localhost", "80", &hints, &res) != 0 || res == NULL) {
+    return -1;
+  }
+  struct sockaddr_in *addr = res->ai_addr;
+  // The port and address are in network byte order.
+  if (res->ai_family != AF_INET || res->ai_socktype != SOCK_STREAM ||
+      res->ai_addrlen != sizeof(struct sockaddr_in) ||
+      addr->sin_family != AF_INET ||
+      memcmp(&addr->sin_port, "\x00\x50", 2) != 0 ||
+      memcmp(addr->sin_addr, "\x7f\x00\x00\x01", 4) != 0) {
+    freeaddrinfo(res);
+    return -2;
+  }
+  freeaddrinfo(res);
+  // Resolution failure is reported.
+  hints.ai_flags = AI_NUMERICHOST;
+  if (getaddrinfo("localhost", NULL, &hints, &res) != EAI_NONAME) {
+    return -3;
+  }
+
+  struct hostent *host = gethostbyname("localhost");
+  if (host == NULL || host->h_addrtype != AF_INET || host->h_length != 4 ||
+      memcmp(host->h_addr_list[0], "\x7f\x00\x00\x01", 4) != 0) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_sockets() {
+  // The echo server is a socket in this same thread, so the test doesn't
+  // need a network connection.
+  int server = socket(AF_INET, SOCK_STREAM, 0);
+  struct sockaddr_in addr;
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_len = sizeof(addr);
+  addr.sin_family = AF_INET;
+  memmove(addr.sin_addr, "\x7f\x00\x00\x01", 4);
+  // Port 0 means any free port.
+  if (server < 0 || bind(server, &addr, sizeof(addr)) != 0 ||
+      listen(server, 1) != 0) {
+    return -1;
+  }
+  socklen_t addr_len = sizeof(addr);
+  if (getsockname(server, &addr, &addr_len) != 0 || addr.sin_port == 0) {
+    return -2;
+  }
+
+  int client = socket(AF_INET, SOCK_STREAM, 0);
+  if (client < 0 || connect(client, &addr, sizeof(addr)) != 0) {
+    return -3;
+  }
+  int connection = accept(server, NULL, NULL);
+  if (connection < 0) {
+    return -4;
+  }
+
+  // Nothing has been sent yet, so a non-blocking receive fails.
+  fcntl(connection, F_SETFL, fcntl(connection, F_GETFL) | O_NONBLOCK);
+  char buf[8];
+  if (recv(connection, buf, sizeof(buf), 0) != -1 || errno != EAGAIN) {
+    return -5;
+  }
+  // Close-on-exec is accepted, and an invalid timeout is an error.
+  struct timeval invalid_timeout = {-1, 0};
+  if (fcntl(connection, F_SETFD, FD_CLOEXEC) != 0 ||
+      select(0, NULL, NULL, NULL, &invalid_timeout) != -1 || errno != EINVAL) {
+    return -10;
+  }
+  if (send(client, "hello", 5, 0) != 5) {
+    return -6;
+  }
+  fd_set readfds;
+  memset(&readfds, 0, sizeof(readfds));
+  readfds.fds_bits[connection / 32] |= 1u << (connection % 32);
+  struct timeval timeout = {5, 0};
+  if (select(connection + 1, &readfds, NULL, NULL, &timeout) != 1) {
+    return -7;
+  }
+  ssize_t received = recv(connection, buf, sizeof(buf), 0);
+  if (received != 5 || send(connection, buf, received, 0) != 5) {
+    return -8;
+  }
+  // This receive blocks until the echoed data arrives.
+  memset(buf, 0, sizeof(buf));
+  if (recv(client, buf, sizeof(buf), 0) != 5 || memcmp(buf, "hello", 5) != 0) {
+    return -9;
+  }
+
+  close(client);
+  close(connection);
+  close(server);
+  return 0;
+}
+
+void CFRunLoop_test_timer_callback(CFRunLoopTimerRef timer, void *info) {
+  (*(int *)info)++;
+  CFRunLoopStop(CFRunLoopGetCurrent());
+}
+
+void CFRunLoop_test_source_perform(void *info) { (*(int *)info)++; }
+
+int test_CFRunLoop() {
+  CFRunLoopMode mode =
+      CFStringCreateWithCString(NULL, "kCFRunLoopDefaultMode", 0x0600);
+  CFRunLoopRef run_loop = CFRunLoopGetCurrent();
+
+  // The timer stops the run loop when it fires.
+  int timer_count = 0;
+  CFRunLoopTimerContext timer_context = {0, &timer_count, NULL, NULL, NULL};
+  CFRunLoopTimerRef timer = CFRunLoopTimerCreate(
+      NULL, CFAbsoluteTimeGetCurrent() + 0.01, 0, 0, 0,
+      CFRunLoop_test_timer_callback, &timer_context);
+  CFRunLoopAddTimer(run_loop, timer, mode);
+  if (CFRunLoopRunInMode(mode, 5.0, 0) != kCFRunLoopRunStopped ||
+      timer_count != 1) {
+    return -1;
+  }
+
+  int source_count = 0;
+  CFRunLoopSourceContext source_context;
+  memset(&source_context, 0, sizeof(source_context));
+  source_context.info = &source_count;
+  source_context.perform = CFRunLoop_test_source_perform;
+  CFRunLoopSourceRef source = CFRunLoopSourceCreate(NULL, 0, &source_context);
+  CFRunLoopAddSource(run_loop, source, mode);
+  // Nothing happens until the source is signalled.
+  if (CFRunLoopRunInMode(mode, 0.01, 1) != kCFRunLoopRunTimedOut ||
+      source_count != 0) {
+    return -2;
+  }
+  CFRunLoopSourceSignal(source);
+  if (CFRunLoopRunInMode(mode, 5.0, 1) != kCFRunLoopRunHandledSource ||
+      source_count != 1) {
+    return -3;
+  }
+  CFRunLoopRemoveSource(run_loop, source, mode);
+  CFRelease(source);
+  CFRelease(mode);
+  return 0;
+}
+
+int test_CFString_bridging() {
+  // A CFString is an NSString.
+  CFStringRef cf_str = CFStringCreateWithCString(NULL, "bridged", 0x0600);
+  if (CFStringGetLength(cf_str) != 7 ||
+      (NSUInteger)msg0((id)cf_str, "length") != 7) {
+    return -1;
+  }
+  if (!objc_msgSend((id)cf_str, sel_registerName("isEqualToString:"),
+                    new_string("bridged"))) {
+    return -2;
+  }
+  // An NSString is a CFString.
+  id ns_str = msg0(NSClassFromString(new_string("NSString")), "alloc");
+  ns_str = objc_msgSend(ns_str, sel_registerName("initWithUTF8String:"),
+                        "toll-free");
+  if ((NSUInteger)msg0(ns_str, "length") != 9 ||
+      CFStringGetLength((CFStringRef)ns_str) != 9) {
+    return -3;
+  }
+  char buf[16];
+  // 0x08000100 is kCFStringEncodingUTF8
+  if (!CFStringGetCString((CFStringRef)ns_str, buf, sizeof(buf), 0x08000100) ||
+      strcmp(buf, "toll-free") != 0) {
+    return -4;
+  }
+  // Too small a buffer must fail.
+  if (CFStringGetCString((CFStringRef)ns_str, buf, 4, 0x08000100)) {
+    return -5;
+  }
+  // This is allowed to return NULL, but the result must be right otherwise.
+  const char *ptr = CFStringGetCStringPtr(cf_str, 0x0600);
+  if (ptr && strcmp(ptr, "bridged") != 0) {
+    return -6;
+  }
+  if (CFRetain(cf_str) != cf_str) {
+    return -7;
+  }
+  CFRelease(cf_str);
+  CFRelease(cf_str);
+  CFRelease((CFStringRef)ns_str);
+  return 0;
+}
+
+int test_CFDictionary_CFArray_bridging() {
+  const void *keys[2] = {new_string("a"), new_string("b")};
+  const void *values[2] = {new_object("NSObject"), new_object("NSObject")};
+  CFDictionaryRef dict =
+      CFDictionaryCreate(NULL, keys, values, 2, &kCFTypeDictionaryKeyCallBacks,
+                         &kCFTypeDictionaryValueCallBacks);
+  if (CFDictionaryGetCount(dict) != 2 ||
+      (NSUInteger)msg0((id)dict, "count") != 2) {
+    return -1;
+  }
+  SEL get_sel = sel_registerName("objectForKey:");
+  if (CFDictionaryGetValue(dict, keys[0]) != values[0] ||
+      objc_msgSend((id)dict, get_sel, keys[1]) != values[1] ||
+      CFDictionaryGetValue(dict, new_string("b")) != values[1]) {
+    return -2;
+  }
+  // The values are retained.
+  if ((NSUInteger)msg0((id)values[0], "retainCount") != 2) {
+    return -3;
+  }
+
+  CFMutableDictionaryRef mut_dict =
+      CFDictionaryCreateMutable(NULL, 0, &kCFTypeDictionaryKeyCallBacks,
+                                &kCFTypeDictionaryValueCallBacks);
+  CFDictionarySetValue(mut_dict, keys[0], values[1]);
+  objc_msgSend((id)mut_dict, sel_registerName("setObject:forKey:"), values[0],
+               keys[1]);
+  if (CFDictionaryGetCount(mut_dict) != 2 ||
+      objc_msgSend((id)mut_dict, get_sel, keys[0]) != values[1] ||
+      CFDictionaryGetValue(mut_dict, keys[1]) != values[0]) {
+    return -4;
+  }
+
+  CFArrayRef array = CFArrayCreate(NULL, values, 2, &kCFTypeArrayCallBacks);
+  SEL index_sel = sel_registerName("objectAtIndex:");
+  if (CFArrayGetCount(array) != 2 ||
+      (NSUInteger)msg0((id)array, "count") != 2 ||
+      CFArrayGetValueAtIndex(array, 0) != values[0] ||
+      objc_msgSend((id)array, index_sel, (NSUInteger)1) != values[1]) {
+    return -5;
+  }
+  // With no callbacks, the values can be arbitrary pointers.
+  const void *raw_values[2] = {(const void *)1, (const void *)2};
+  CFArrayRef raw_array = CFArrayCreate(NULL, raw_values, 2, NULL);
+  if (CFArrayGetCount(raw_array) != 2 ||
+      CFArrayGetValueAtIndex(raw_array, 1) != raw_values[1]) {
+    return -6;
+  }
+
+  CFRelease(raw_array);
+  CFRelease(array);
+  CFRelease(mut_dict);
+  CFRelease(dict);
+  if ((NSUInteger)msg0((id)values[0], "retainCount") != 1) {
+    return -7;
+  }
+  CFRelease(values[0]);
+  CFRelease(values[1]);
+  return 0;
+}
+
+int test_CFBundle() {
+  CFBundleRef cf_bundle = CFBundleGetMainBundle();
+  id ns_bundle = msg0(NSClassFromString(new_string("NSBundle")), "mainBundle");
+  // CFBundle and NSBundle are the same thing.
+  if ((id)cf_bundle != ns_bundle) {
+    return -1;
+  }
+
+  CFStringRef name = (CFStringRef)new_string("clip");
+  CFStringRef type = (CFStringRef)new_string("mp4");
+  CFURLRef url = CFBundleCopyResourceURL(cf_bundle, name, type, NULL);
+  if (!url) {
+    return -2;
+  }
+  CFStringRef path = CFURLCopyFileSystemPath(url, 0); // kCFURLPOSIXPathStyle
+  id ns_path = objc_msgSend(
+      ns_bundle, sel_registerName("pathForResource:ofType:"), name, type);
+  if (CFStringCompare(path, (CFStringRef)ns_path, 0) != 0) {
+    return -3;
+  }
+  CFRelease(path);
+  CFRelease(url);
+
+  // Type and subdirectory must both match.
+  name = (CFStringRef)new_string("Localizable");
+  type = (CFStringRef)new_string("strings");
+  url = CFBundleCopyResourceURL(cf_bundle, name, type,
+                                (CFStringRef)new_string("en.lproj"));
+  if (!url) {
+    return -4;
+  }
+  CFRelease(url);
+  url = CFBundleCopyResourceURL(cf_bundle, name,
+                                (CFStringRef)new_string("plist"),
+                                (CFStringRef)new_string("en.lproj"));
+  if (url) {
+    return -5;
+  }
+  url = CFBundleCopyResourceURL(cf_bundle, name, type,
+                                (CFStringRef)new_string("nonexistent"));
+  if (url) {
+    return -6;
+  }
+
+  CFStringRef identifier = (CFStringRef)CFBundleGetValueForInfoDictionaryKey(
+      cf_bundle, (CFStringRef)new_string("CFBundleIdentifier"));
+  if (!identifier ||
+      CFStringCompare(identifier,
+                      (CFStringRef)new_string("com.yourcompany.TestApp"),
+                      0) != 0 ||
+      CFBundleGetIdentifier(cf_bundle) != identifier) {
+    return -7;
+  }
+  return 0;
+}
+
+int test_CFPreferences() {
+  CFStringRef app = kCFPreferencesCurrentApplication;
+  CFStringRef key = (CFStringRef)new_string("TestCFPreferencesKey");
+  id defaults = msg0(NSClassFromString(new_string("NSUserDefaults")),
+                     "standardUserDefaults");
+
+  // CFPreferences and NSUserDefaults share the same store.
+  CFPreferencesSetAppValue(key, (CFTypeRef)new_string("from CF"), app);
+  id value = objc_msgSend(defaults, sel_registerName("objectForKey:"), key);
+  if (!value || !(int)objc_msgSend(value, sel_registerName("isEqualToString:"),
+                                   new_string("from CF"))) {
+    return -1;
+  }
+
+  objc_msgSend(defaults, sel_registerName("setObject:forKey:"),
+               new_string("from NS"), key);
+  CFStringRef copied = (CFStringRef)CFPreferencesCopyAppValue(key, app);
+  if (!copied ||
+      CFStringCompare(copied, (CFStringRef)new_string("from NS"), 0) != 0) {
+    return -2;
+  }
+  CFRelease(copied);
+
+  if (!CFPreferencesAppSynchronize(app)) {
+    return -3;
+  }
+
+  // Setting NULL removes the value.
+  CFPreferencesSetAppValue(key, NULL, app);
+  if (objc_msgSend(defaults, sel_registerName("objectForKey:"), key) ||
+      CFPreferencesCopyAppValue(key, app)) {
+    return -4;
+  }
+  if (!CFPreferencesAppSynchronize(app)) {
+    return -5;
+  }
+  return 0;
+}
+
+int test_mach_absolute_time() {
+  mach_timebase_info_data_t timebase;
+  if (mach_timebase_info(&timebase) != 0 || timebase.numer == 0 ||
+      timebase.denom == 0) {
+    return -1;
+  }
+  unsigned long long start = mach_absolute_time();
+  usleep(20000);
+  unsigned long long end = mach_absolute_time();
+  if (end <= start) {
+    return -2;
+  }
+  unsigned long long elapsed_ns = (end - start) * timebase.numer /
+                                  timebase.denom;
+  // The sleep can overshoot by a lot on a busy machine, but not undershoot.
+  if (elapsed_ns < 20000000ULL || elapsed_ns > 5000000000ULL) {
+    return -3;
+  }
+  return 0;
+}
+
+int test_wall_clock_time() {
+  struct timeval tv;
+  if (gettimeofday(&tv, NULL) != 0) {
+    return -1;
+  }
+  time_t now = time(NULL);
+  // Sanity check: after 2020-01-01 and consistent with time().
+  if (tv.tv_sec < 1577836800 || now < tv.tv_sec || now - tv.tv_sec > 5 ||
+      tv.tv_usec < 0 || tv.tv_usec >= 1000000) {
+    return -2;
+  }
+
+  // CFAbsoluteTime counts from 2001-01-01, not 1970-01-01.
+  CFAbsoluteTime cf_now = CFAbsoluteTimeGetCurrent();
+  double unix_now = tv.tv_sec + tv.tv_usec / 1000000.0;
+  double diff = cf_now + 978307200.0 - unix_now;
+  if (diff < -1.0 || diff > 5.0) {
+    return -3;
+  }
+
+  id date = msg0(NSClassFromString(new_string("NSDate")), "date");
+  double ns_now = ((double (*)(id, SEL))objc_msgSend)(
+      date, sel_registerName("timeIntervalSinceReferenceDate"));
+  diff = ns_now - cf_now;
+  if (diff < -1.0 || diff > 5.0) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_random() {
+  // Seeding again must reproduce the sequence.
+  int rand_values[4];
+  long random_values[4];
+  srand(1);
+  srandom(1);
+  for (int i = 0; i < 4; i++) {
+    rand_values[i] = rand();
+    random_values[i] = random();
+  }
+  // The integration test uses --random-seed=, so the app's seed is ignored,
+  // even if it is based on the time.
+  srand(time(NULL));
+  srandom(time(NULL));
+  for (int i = 0; i < 4; i++) {
+    if (rand() != rand_values[i] || random() != random_values[i]) {
+      return -1;
+    }
+  }
+
+  if (arc4random_uniform(0) != 0 || arc4random_uniform(1) != 0) {
+    return -2;
+  }
+  int seen[6] = {0};
+  for (int i = 0; i < 600; i++) {
+    unsigned value = arc4random_uniform(6);
+    if (value >= 6) {
+      return -3;
+    }
+    seen[value]++;
+  }
+  for (int i = 0; i < 6; i++) {
+    if (seen[i] == 0) {
+      return -4;
+    }
+  }
+  // Large bounds, where the bias would be worst without rejection.
+  for (int i = 0; i < 100; i++) {
+    if (arc4random_uniform(0x80000001) > 0x80000000) {
+      return -5;
+    }
+  }
+  return 0;
+}
+
+int test_UIStatusBar() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  void (*set_bool)(id, SEL, bool) = (void (*)(id, SEL, bool))objc_msgSend;
+  SEL status_bar_frame = sel_registerName("statusBarFrame");
+  SEL application_frame = sel_registerName("applicationFrame");
+  SEL set_hidden = sel_registerName("setStatusBarHidden:");
+  CFStringRef app_name =
+      CFStringCreateWithCString(NULL, "UIApplication", 0x0600);
+  CFStringRef screen_name = CFStringCreateWithCString(NULL, "UIScreen", 0x0600);
+  CFStringRef center_name =
+      CFStringCreateWithCString(NULL, "NSNotificationCenter", 0x0600);
+  id app = msg0(NSClassFromString(app_name), "sharedApplication");
+  id screen = msg0(NSClassFromString(screen_name), "mainScreen");
+  id did_change =
+      new_string("UIApplicationDidChangeStatusBarFrameNotification");
+  id center = msg0(NSClassFromString(center_name), "defaultCenter");
+  // Every notification gets added to the array.
+  id notifications = new_object("NSMutableArray");
+  ((void (*)(id, SEL, id, SEL, id, id))objc_msgSend)(
+      center, sel_registerName("addObserver:selector:name:object:"),
+      notifications, sel_registerName("addObject:"), did_change, NULL);
+
+  bool was_hidden = (bool)msg0(app, "isStatusBarHidden");
+  set_bool(app, set_hidden, false);
+  CGRect bar_frame = get_rect(app, status_bar_frame);
+  CGRect shown_frame = get_rect(screen, application_frame);
+  if (bar_frame.size.height != 20 || shown_frame.origin.y != 20) {
+    return -1;
+  }
+
+  // Hiding the status bar gives its space to the app.
+  NSUInteger count = (NSUInteger)msg0(notifications, "count");
+  set_bool(app, set_hidden, true);
+  if (!(bool)msg0(app, "isStatusBarHidden")) {
+    return -2;
+  }
+  CGRect hidden_frame = get_rect(screen, application_frame);
+  if (hidden_frame.origin.y != 0 ||
+      hidden_frame.size.height != shown_frame.size.height + 20) {
+    return -3;
+  }
+  CGRect empty = {{0, 0}, {0, 0}};
+  if (!CGRectEqualToRect(get_rect(app, status_bar_frame), empty)) {
+    return -4;
+  }
+  // The notification has the old frame.
+  if ((NSUInteger)msg0(notifications, "count") != count + 1) {
+    return -5;
+  }
+  id notification = msg0(notifications, "lastObject");
+  id frame_value =
+      objc_msgSend(msg0(notification, "userInfo"),
+                   sel_registerName("objectForKey:"),
+                   new_string("UIApplicationStatusBarFrameUserInfoKey"));
+  if (!CGRectEqualToRect(get_rect(frame_value, sel_registerName("CGRectValue")),
+                         bar_frame)) {
+    return -6;
+  }
+
+  int old_style = (int)msg0(app, "statusBarStyle");
+  SEL set_style = sel_registerName("setStatusBarStyle:");
+  objc_msgSend(app, set_style, 2); // UIStatusBarStyleBlackOpaque
+  if ((int)msg0(app, "statusBarStyle") != 2) {
+    return -7;
+  }
+  objc_msgSend(app, set_style, old_style);
+
+  set_bool(app, set_hidden, was_hidden);
+  ((void (*)(id, SEL, id, id, id))objc_msgSend)(
+      center, sel_registerName("removeObserver:name:object:"), notifications,
+      did_change, NULL);
+  msg0(notifications, "release");
+  return 0;
+}
+
+int test_UIViewController_modal() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  void (*present)(id, SEL, id, bool, id) =
+      (void (*)(id, SEL, id, bool, id))objc_msgSend;
+  void (*dismiss)(id, SEL, bool, id) =
+      (void (*)(id, SEL, bool, id))objc_msgSend;
+  SEL present_sel =
+      sel_registerName("presentViewController:animated:completion:");
+  SEL dismiss_sel =
+      sel_registerName("dismissViewControllerAnimated:completion:");
+  SEL frame = sel_registerName("frame");
+  id window = new_object("UIWindow");
+  id root = new_object("UIViewController");
+  id modal = new_object("UIViewController");
+  id second_modal = new_object("UIViewController");
+  objc_msgSend(window, sel_registerName("setRootViewController:"), root);
+  msg0(window, "makeKeyAndVisible");
+
+  // Test apps can't define a view controller subclass to observe
+  // viewDidAppear:, so the resulting view hierarchy is checked instead.
+  objc_msgSend(modal, sel_registerName("setModalTransitionStyle:"),
+               2); // UIModalTransitionStyleCrossDissolve
+  present(root, present_sel, modal, true, NULL);
+  if (msg0(root, "presentedViewController") != modal ||
+      msg0(modal, "presentingViewController") != root) {
+    return -1;
+  }
+  id modal_view = msg0(modal, "view");
+  if (msg0(modal_view, "superview") != window ||
+      msg0(msg0(window, "subviews"), "lastObject") != modal_view ||
+      !CGRectEqualToRect(get_rect(modal_view, frame),
+                         get_rect(msg0(root, "view"), frame))) {
+    return -2;
+  }
+
+  // Presenting from a controller that is already presenting something puts
+  // the new controller on top.
+  present(root, present_sel, second_modal, false, NULL);
+  if (msg0(modal, "presentedViewController") != second_modal ||
+      msg0(second_modal, "presentingViewController") != modal ||
+      msg0(msg0(window, "subviews"), "lastObject") !=
+          msg0(second_modal, "view")) {
+    return -3;
+  }
+
+  // Dismissing the first modal also dismisses the one on top of it.
+  dismiss(modal, dismiss_sel, false, NULL);
+  if (msg0(root, "presentedViewController") != NULL ||
+      msg0(modal, "presentingViewController") != NULL ||
+      msg0(second_modal, "presentingViewController") != NULL) {
+    return -4;
+  }
+  if (msg0(modal_view, "superview") != NULL ||
+      msg0(msg0(second_modal, "view"), "superview") != NULL ||
+      msg0(msg0(root, "view"), "superview") != window) {
+    return -5;
+  }
+
+  // The older API does the same thing.
+  objc_msgSend(root, sel_registerName("presentModalViewController:animated:"),
+               modal, 0);
+  if (msg0(root, "modalViewController") != modal) {
+    return -6;
+  }
+  objc_msgSend(root, sel_registerName("dismissModalViewControllerAnimated:"),
+               0);
+  if (msg0(root, "modalViewController") != NULL) {
+    return -7;
+  }
+
+  msg0(window, "release");
+  msg0(root, "release");
+  msg0(modal, "release");
+  msg0(second_modal, "release");
+  return 0;
+}
+
+int test_NSStream() {
+  long (*read_bytes)(id, SEL, unsigned char *, NSUInteger) =
+      (long (*)(id, SEL, unsigned char *, NSUInteger))objc_msgSend;
+  long (*write_bytes)(id, SEL, const unsigned char *, NSUInteger) =
+      (long (*)(id, SEL, const unsigned char *, NSUInteger))objc_msgSend;
+  SEL read_sel = sel_registerName("read:maxLength:");
+  SEL write_sel = sel_registerName("write:maxLength:");
+  CFStringRef bundle_name =
+      CFStringCreateWithCString(NULL, "NSBundle", 0x0600);
+  CFStringRef data_name = CFStringCreateWithCString(NULL, "NSData", 0x0600);
+  CFStringRef input_name =
+      CFStringCreateWithCString(NULL, "NSInputStream", 0x0600);
+  CFStringRef output_name =
+      CFStringCreateWithCString(NULL, "NSOutputStream", 0x0600);
+  id bundle = msg0(NSClassFromString(bundle_name), "mainBundle");
+  id path = objc_msgSend(bundle, sel_registerName("pathForResource:ofType:"),
+                         new_string("TestFont"), new_string("ttf"));
+  id data = objc_msgSend(NSClassFromString(data_name),
+                         sel_registerName("dataWithContentsOfFile:"), path);
+  NSUInteger length = (NSUInteger)msg0(data, "length");
+  const unsigned char *bytes = (const unsigned char *)msg0(data, "bytes");
+
+  // Read the file in chunks that don't divide its size evenly.
+  id input =
+      objc_msgSend(NSClassFromString(input_name),
+                   sel_registerName("inputStreamWithFileAtPath:"), path);
+  if ((int)msg0(input, "streamStatus") != 0) { // NSStreamStatusNotOpen
+    return -1;
+  }
+  msg0(input, "open");
+  unsigned char *assembled = malloc(length + 100);
+  NSUInteger total = 0;
+  while ((bool)msg0(input, "hasBytesAvailable")) {
+    long count = read_bytes(input, read_sel, assembled + total, 100);
+    if (count < 0 || total + count > length) {
+      return -2;
+    }
+    total += count;
+  }
+  if (total != length || memcmp(assembled, bytes, length) != 0) {
+    return -3;
+  }
+  if (read_bytes(input, read_sel, assembled, 100) != 0) {
+    return -4;
+  }
+  msg0(input, "close");
+  if ((int)msg0(input, "streamStatus") != 6) { // NSStreamStatusClosed
+    return -5;
+  }
+  free(assembled);
+
+  // Copy from a data stream to a memory stream.
+  input = objc_msgSend(NSClassFromString(input_name),
+                       sel_registerName("inputStreamWithData:"), data);
+  id output = msg0(NSClassFromString(output_name), "outputStreamToMemory");
+  msg0(input, "open");
+  msg0(output, "open");
+  unsigned char buffer[256];
+  long count;
+  while ((count = read_bytes(input, read_sel, buffer, sizeof(buffer))) > 0) {
+    if (write_bytes(output, write_sel, buffer, count) != count) {
+      return -6;
+    }
+  }
+  id written =
+      objc_msgSend(output, sel_registerName("propertyForKey:"),
+                   new_string("NSStreamDataWrittenToMemoryStreamKey"));
+  if ((NSUInteger)msg0(written, "length") != length ||
+      memcmp(msg0(written, "bytes"), bytes, length) != 0) {
+    return -7;
+  }
+  msg0(input, "close");
+  msg0(output, "close");
+  return 0;
+}
+
+int cookie_test_server;
+char cookie_test_requests[2][1024];
+
+void *cookie_test_server_thread(void *unused) {
+  const char *responses[2] = {
+      "HTTP/1.1 200 OK\r\n"
+      "Set-Cookie: session=abc123; Path=/; Max-Age=3600\r\n"
+      "Content-Length: 5\r\n\r\nhello",
+      "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+  };
+  for (int i = 0; i < 2; i++) {
+    int connection = accept(cookie_test_server, NULL, NULL);
+    if (connection < 0) {
+      return NULL;
+    }
+    // Read until the end of the request headers.
+    char *request = cookie_test_requests[i];
+    size_t length = 0;
+    while (length < sizeof(cookie_test_requests[i]) - 1 &&
+           !strstr(request, "\r\n\r\n")) {
+      ssize_t received = recv(connection, request + length,
+                              sizeof(cookie_test_requests[i]) - 1 - length, 0);
+      if (received <= 0) {
+        break;
+      }
+      length += received;
+    }
+    send(connection, responses[i], strlen(responses[i]), 0);
+    close(connection);
+  }
+  return NULL;
+}
+
+int test_NSURLConnection_cookies() {
+  // The mock server runs on another thread, so the test doesn't need a
+  // network connection.
+  cookie_test_server = socket(AF_INET, SOCK_STREAM, 0);
+  struct sockaddr_in addr;
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_len = sizeof(addr);
+  addr.sin_family = AF_INET;
+  memmove(addr.sin_addr, "\x7f\x00\x00\x01", 4);
+  socklen_t addr_len = sizeof(addr);
+  if (cookie_test_server < 0 ||
+      bind(cookie_test_server, &addr, sizeof(addr)) != 0 ||
+      listen(cookie_test_server, 2) != 0 ||
+      getsockname(cookie_test_server, &addr, &addr_len) != 0) {
+    return -1;
+  }
+  memset(cookie_test_requests, 0, sizeof(cookie_test_requests));
+  pthread_t thread;
+  pthread_create(&thread, NULL, cookie_test_server_thread, NULL);
+
+  char url_string[64];
+  unsigned short port = (addr.sin_port >> 8) | ((addr.sin_port & 0xff) << 8);
+  snprintf(url_string, sizeof(url_string), "http://127.0.0.1:%u/login", port);
+  id url_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSURL", 0x0600));
+  id request_class = NSClassFromString(
+      CFStringCreateWithCString(NULL, "NSURLRequest", 0x0600));
+  id connection_class = NSClassFromString(
+      CFStringCreateWithCString(NULL, "NSURLConnection", 0x0600));
+  id storage = msg0(NSClassFromString(CFStringCreateWithCString(
+                        NULL, "NSHTTPCookieStorage", 0x0600)),
+                    "sharedHTTPCookieStorage");
+  SEL url_sel = sel_registerName("URLWithString:");
+  SEL request_sel = sel_registerName("requestWithURL:");
+  SEL send_sel =
+      sel_registerName("sendSynchronousRequest:returningResponse:error:");
+
+  // The first response sets a cookie.
+  id url = objc_msgSend(url_class, url_sel, new_string(url_string));
+  id response = NULL;
+  id error = NULL;
+  id data = objc_msgSend(connection_class, send_sel,
+                         objc_msgSend(request_class, request_sel, url),
+                         &response, &error);
+  if (data == NULL || error != NULL ||
+      (NSUInteger)msg0(data, "length") != 5 ||
+      memcmp(msg0(data, "bytes"), "hello", 5) != 0 ||
+      (int)msg0(response, "statusCode") != 200) {
+    return -2;
+  }
+  if (strstr(cookie_test_requests[0], "Cookie:") != NULL) {
+    return -3;
+  }
+
+  // The stored cookie matches what the server sent.
+  id cookies = objc_msgSend(storage, sel_registerName("cookiesForURL:"), url);
+  if ((NSUInteger)msg0(cookies, "count") != 1) {
+    return -4;
+  }
+  id cookie = objc_msgSend(cookies, sel_registerName("objectAtIndex:"), 0);
+  if (strcmp((char *)msg0(msg0(cookie, "name"), "UTF8String"), "session") !=
+          0 ||
+      strcmp((char *)msg0(msg0(cookie, "value"), "UTF8String"), "abc123") !=
+          0 ||
+      strcmp((char *)msg0(msg0(cookie, "domain"), "UTF8String"),
+             "127.0.0.1") != 0 ||
+      strcmp((char *)msg0(msg0(cookie, "path"), "UTF8String"), "/") != 0 ||
+      (bool)msg0(cookie, "isSessionOnly")) {
+    return -5;
+  }
+
+  // A later request to the same server sends the cookie back.
+  snprintf(url_string, sizeof(url_string), "http://127.0.0.1:%u/profile",
+           port);
+  url = objc_msgSend(url_class, url_sel, new_string(url_string));
+  data = objc_msgSend(connection_class, send_sel,
+                      objc_msgSend(request_class, request_sel, url), &response,
+                      &error);
+  if (data == NULL || (NSUInteger)msg0(data, "length") != 0 ||
+      strstr(cookie_test_requests[1], "\r\nCookie: session=abc123\r\n") ==
+          NULL) {
+    return -6;
+  }
+
+  // Don't leave the cookie behind for next time.
+  objc_msgSend(storage, sel_registerName("deleteCookie:"), cookie);
+  cookies = objc_msgSend(storage, sel_registerName("cookiesForURL:"), url);
+  close(cookie_test_server);
+  if ((NSUInteger)msg0(cookies, "count") != 0) {
+    return -7;
+  }
+  return 0;
+}
+
+int post_test_server;
+char post_test_request[1024];
+
+void *post_test_server_thread(void *body_length) {
+  int connection = accept(post_test_server, NULL, NULL);
+  if (connection < 0) {
+    return NULL;
+  }
+  // Read until the end of the request headers and the body after them.
+  size_t length = 0;
+  while (length < sizeof(post_test_request) - 1) {
+    char *end = strstr(post_test_request, "\r\n\r\n");
+    if (end && (size_t)(post_test_request + length - (end + 4)) >=
+                   (size_t)body_length) {
+      break;
+    }
+    ssize_t received =
+        recv(connection, post_test_request + length,
+             sizeof(post_test_request) - 1 - length, 0);
+    if (received <= 0) {
+      break;
+    }
+    length += received;
+  }
+  const char *response = "HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n";
+  send(connection, response, strlen(response), 0);
+  close(connection);
+  return NULL;
+}
+
+int test_NSMutableURLRequest_POST() {
+  post_test_server = socket(AF_INET, SOCK_STREAM, 0);
+  struct sockaddr_in addr;
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_len = sizeof(addr);
+  addr.sin_family = AF_INET;
+  memmove(addr.sin_addr, "\x7f\x00\x00\x01", 4);
+  socklen_t addr_len = sizeof(addr);
+  if (post_test_server < 0 ||
+      bind(post_test_server, &addr, sizeof(addr)) != 0 ||
+      listen(post_test_server, 1) != 0 ||
+      getsockname(post_test_server, &addr, &addr_len) != 0) {
+    return -1;
+  }
+
+  char url_string[64];
+  unsigned short port = (addr.sin_port >> 8) | ((addr.sin_port & 0xff) << 8);
+  snprintf(url_string, sizeof(url_string), "http://127.0.0.1:%u/submit", port);
+  id url = objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSURL", 0x0600)),
+      sel_registerName("URLWithString:"), new_string(url_string));
+  id request = objc_msgSend(NSClassFromString(CFStringCreateWithCString(
+                                NULL, "NSMutableURLRequest", 0x0600)),
+                            sel_registerName("requestWithURL:"), url);
+  SEL set_sel = sel_registerName("setValue:forHTTPHeaderField:");
+  SEL add_sel = sel_registerName("addValue:forHTTPHeaderField:");
+  const char *json = "{\"score\":42}";
+  id body = objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSData", 0x0600)),
+      sel_registerName("dataWithBytes:length:"), json, strlen(json));
+  objc_msgSend(request, sel_registerName("setHTTPMethod:"),
+               new_string("POST"));
+  objc_msgSend(request, set_sel, new_string("text/plain"),
+               new_string("Content-Type"));
+  objc_msgSend(request, set_sel, new_string("application/json"),
+               new_string("content-type"));
+  objc_msgSend(request, add_sel, new_string("a"), new_string("X-Test"));
+  objc_msgSend(request, add_sel, new_string("b"), new_string("X-Test"));
+  objc_msgSend(request, sel_registerName("setHTTPBody:"), body);
+  ((void (*)(id, SEL, double))objc_msgSend)(
+      request, sel_registerName("setTimeoutInterval:"), 10.0);
+  id x_test = objc_msgSend(request, sel_registerName("valueForHTTPHeaderField:"),
+                           new_string("x-test"));
+  if (strcmp((char *)msg0(msg0(request, "HTTPMethod"), "UTF8String"),
+             "POST") != 0 ||
+      strcmp((char *)msg0(x_test, "UTF8String"), "a, b") != 0 ||
+      ((double (*)(id, SEL))objc_msgSend)(
+          request, sel_registerName("timeoutInterval")) != 10.0) {
+    return -2;
+  }
+
+  memset(post_test_request, 0, sizeof(post_test_request));
+  pthread_t thread;
+  pthread_create(&thread, NULL, post_test_server_thread,
+                 (void *)strlen(json));
+  id response = NULL;
+  id error = NULL;
+  id data = objc_msgSend(
+      NSClassFromString(
+          CFStringCreateWithCString(NULL, "NSURLConnection", 0x0600)),
+      sel_registerName("sendSynchronousRequest:returningResponse:error:"),
+      request, &response, &error);
+  close(post_test_server);
+  if (data == NULL || error != NULL ||
+      (int)msg0(response, "statusCode") != 201) {
+    return -3;
+  }
+
+  // The server received the method, headers and body.
+  char *body_start = strstr(post_test_request, "\r\n\r\n");
+  if (strncmp(post_test_request, "POST /submit HTTP/1.1\r\n", 23) != 0 ||
+      strstr(post_test_request, "\r\nContent-Type: application/json\r\n") ==
+          NULL ||
+      strstr(post_test_request, "\r\nX-Test: a, b\r\n") == NULL ||
+      strstr(post_test_request, "\r\nContent-Length: 12\r\n") == NULL ||
+      body_start == NULL || strcmp(body_start + 4, json) != 0) {
+    return -4;
+  }
+  return 0;
+}
+
+int async_test_server;
+char async_test_events[8];
+int async_test_status;
+char async_test_body[16];
+int async_test_error_code;
+
+void *async_test_server_thread(void *unused) {
+  int connection = accept(async_test_server, NULL, NULL);
+  if (connection < 0) {
+    return NULL;
+  }
+  char request[1024] = {0};
+  size_t length = 0;
+  while (length < sizeof(request) - 1 && !strstr(request, "\r\n\r\n")) {
+    ssize_t received =
+        recv(connection, request + length, sizeof(request) - 1 - length, 0);
+    if (received <= 0) {
+      break;
+    }
+    length += received;
+  }
+  const char *response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+  send(connection, response, strlen(response), 0);
+  close(connection);
+  return NULL;
+}
+
+void async_test_add_event(char event) {
+  size_t length = strlen(async_test_events);
+  if (length < sizeof(async_test_events) - 1) {
+    async_test_events[length] = event;
+  }
+}
+void async_test_did_receive_response(id self, SEL _cmd, id connection,
+                                     id response) {
+  async_test_status = (int)msg0(response, "statusCode");
+  async_test_add_event('R');
+}
+void async_test_did_receive_data(id self, SEL _cmd, id connection, id data) {
+  NSUInteger length = (NSUInteger)msg0(data, "length");
+  if (length < sizeof(async_test_body)) {
+    memmove(async_test_body, msg0(data, "bytes"), length);
+  }
+  async_test_add_event('D');
+}
+void async_test_did_finish_loading(id self, SEL _cmd, id connection) {
+  async_test_add_event('F');
+}
+void async_test_did_fail(id self, SEL _cmd, id connection, id error) {
+  async_test_error_code = (int)msg0(error, "code");
+  async_test_add_event('E');
+}
+
+// Run the run loop until the delegate gets its last message.
+void async_test_wait() {
+  id run_loop = msg0(NSClassFromString(CFStringCreateWithCString(
+                         NULL, "NSRunLoop", 0x0600)),
+                     "currentRunLoop");
+  id date_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSDate", 0x0600));
+  for (int i = 0;
+       i < 500 && async_test_events[strcspn(async_test_events, "FE")] == '\0';
+       i++) {
+    id date = ((id(*)(id, SEL, double))objc_msgSend)(
+        date_class, sel_registerName("dateWithTimeIntervalSinceNow:"), 0.01);
+    objc_msgSend(run_loop, sel_registerName("runUntilDate:"), date);
+  }
+}
+
+int test_NSURLConnection_async() {
+  id object_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSObject", 0x0600));
+  class_addMethod(object_class,
+                  sel_registerName("connection:didReceiveResponse:"),
+                  (IMP)async_test_did_receive_response, "v@:@@");
+  class_addMethod(object_class, sel_registerName("connection:didReceiveData:"),
+                  (IMP)async_test_did_receive_data, "v@:@@");
+  class_addMethod(object_class,
+                  sel_registerName("connectionDidFinishLoading:"),
+                  (IMP)async_test_did_finish_loading, "v@:@");
+  class_addMethod(object_class,
+                  sel_registerName("connection:didFailWithError:"),
+                  (IMP)async_test_did_fail, "v@:@@");
+
+  async_test_server = socket(AF_INET, SOCK_STREAM, 0);
+  struct sockaddr_in addr;
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_len = sizeof(addr);
+  addr.sin_family = AF_INET;
+  memmove(addr.sin_addr, "\x7f\x00\x00\x01", 4);
+  socklen_t addr_len = sizeof(addr);
+  if (async_test_server < 0 ||
+      bind(async_test_server, &addr, sizeof(addr)) != 0 ||
+      listen(async_test_server, 1) != 0 ||
+      getsockname(async_test_server, &addr, &addr_len) != 0) {
+    return -1;
+  }
+  pthread_t thread;
+  pthread_create(&thread, NULL, async_test_server_thread, NULL);
+
+  char url_string[64];
+  unsigned short port = (addr.sin_port >> 8) | ((addr.sin_port & 0xff) << 8);
+  snprintf(url_string, sizeof(url_string), "http://127.0.0.1:%u/", port);
+  id url_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSURL", 0x0600));
+  id request_class = NSClassFromString(
+      CFStringCreateWithCString(NULL, "NSURLRequest", 0x0600));
+  id connection_class = NSClassFromString(
+      CFStringCreateWithCString(NULL, "NSURLConnection", 0x0600));
+  SEL url_sel = sel_registerName("URLWithString:");
+  SEL request_sel = sel_registerName("requestWithURL:");
+  SEL connection_sel = sel_registerName("connectionWithRequest:delegate:");
+  id delegate = new_object("NSObject");
+
+  // The delegate gets the response, then the data, then the end.
+  memset(async_test_events, 0, sizeof(async_test_events));
+  memset(async_test_body, 0, sizeof(async_test_body));
+  id url = objc_msgSend(url_class, url_sel, new_string(url_string));
+  id connection = objc_msgSend(connection_class, connection_sel,
+                               objc_msgSend(request_class, request_sel, url),
+                               delegate);
+  if (connection == NULL || async_test_events[0] != '\0') {
+    return -2;
+  }
+  async_test_wait();
+  close(async_test_server);
+  if (strcmp(async_test_events, "RDF") != 0 || async_test_status != 200 ||
+      strcmp(async_test_body, "hello") != 0) {
+    return -3;
+  }
+
+  // Errors are reported to the delegate too.
+  memset(async_test_events, 0, sizeof(async_test_events));
+  url = objc_msgSend(url_class, url_sel, new_string("ftp://127.0.0.1/"));
+  objc_msgSend(connection_class, connection_sel,
+               objc_msgSend(request_class, request_sel, url), delegate);
+  async_test_wait();
+  if (strcmp(async_test_events, "E") != 0 ||
+      async_test_error_code != -1002) { // NSURLErrorUnsupportedURL
+    return -4;
+  }
+
+  // A cancelled connection doesn't message its delegate.
+  memset(async_test_events, 0, sizeof(async_test_events));
+  connection = objc_msgSend(connection_class, connection_sel,
+                            objc_msgSend(request_class, request_sel, url),
+                            delegate);
+  msg0(connection, "cancel");
+  movie_test_run_loop();
+  msg0(delegate, "release");
+  if (async_test_events[0] != '\0') {
+    return -5;
+  }
+  return 0;
+}
+
+// Objective-C exceptions. This file is C, so the SjLj exception handling code
+// the compiler would generate for @try/@catch is written out by hand here.
+struct _Unwind_FunctionContext {
+  struct _Unwind_FunctionContext *prev;
+  int call_site;
+  unsigned int data[4];
+  void *personality;
+  const void *lsda;
+  void *jbuf[5];
+};
+void _Unwind_SjLj_Register(struct _Unwind_FunctionContext *);
+void _Unwind_SjLj_Unregister(struct _Unwind_FunctionContext *);
+id objc_begin_catch(void *);
+void objc_end_catch(void);
+int __objc_personality_v0();
+extern char OBJC_EHTYPE_id;
+extern char OBJC_EHTYPE_$_NSException;
+
+// An LSDA with a single call site, whose landing pad (0) catches one type.
+struct exception_test_lsda {
+  unsigned char table[12];
+  const void *type_info;
+};
+
+// Equivalent to `@try { raise(arg); } @catch (type *e) { return e; }`, where
+// `type_info` is the type's OBJC_EHTYPE. Returns NULL if nothing was thrown.
+id exception_test_catch(void (*raise)(void *), void *arg,
+                        const void *type_info) {
+  struct exception_test_lsda lsda = {
+      {
+          0xff,     // no landing pad base
+          0x00, 13, // absolute type info pointers, offset of table end
+          0x01, 2,  // call site table length
+          0, 1,     // call site 1: landing pad 0, action 1
+          1, 0,     // action 1: type index 1, no next action
+      },
+      type_info,
+  };
+  struct _Unwind_FunctionContext fc;
+  memset(&fc, 0, sizeof(fc));
+  fc.personality = &__objc_personality_v0;
+  fc.lsda = &lsda;
+  id caught = NULL;
+  if (__builtin_setjmp(fc.jbuf) == 0) {
+    _Unwind_SjLj_Register(&fc);
+    fc.call_site = 1;
+    raise(arg);
+    fc.call_site = -1;
+  } else if (fc.call_site == 0 && fc.data[1] == 1) {
+    caught = objc_begin_catch((void *)fc.data[0]);
+    objc_end_catch();
+  }
+  _Unwind_SjLj_Unregister(&fc);
+  return caught;
+}
+
+void exception_test_raise(void *reason) {
+  objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSException", 0x0600)),
+      sel_registerName("raise:format:"), new_string("TestException"),
+      new_string("reason: %s"), reason);
+}
+
+void exception_test_out_of_bounds(void *array) {
+  objc_msgSend((id)array, sel_registerName("objectAtIndex:"), 3);
+}
+
+void exception_test_remove_last(void *array) {
+  msg0((id)array, "removeLastObject");
+}
+
+int test_NSException() {
+  id exception = exception_test_catch(exception_test_raise, "foo",
+                                      &OBJC_EHTYPE_$_NSException);
+  if (exception == NULL ||
+      strcmp((char *)msg0(msg0(exception, "name"), "UTF8String"),
+             "TestException") != 0 ||
+      strcmp((char *)msg0(msg0(exception, "reason"), "UTF8String"),
+             "reason: foo") != 0) {
+    return -1;
+  }
+
+  // Host code raises exceptions too.
+  id array = objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSArray", 0x0600)),
+      sel_registerName("arrayWithObjects:"), new_string("a"), new_string("b"),
+      NULL);
+  exception = exception_test_catch(exception_test_out_of_bounds, array,
+                                   &OBJC_EHTYPE_id);
+  if (exception == NULL ||
+      strcmp((char *)msg0(msg0(exception, "name"), "UTF8String"),
+             "NSRangeException") != 0) {
+    return -2;
+  }
+
+  // Including arrays that don't retain their contents.
+  array = (id)CFArrayCreateMutable(NULL, 0, NULL);
+  exception = exception_test_catch(exception_test_remove_last, array,
+                                   &OBJC_EHTYPE_id);
+  msg0(array, "release");
+  if (exception == NULL ||
+      strcmp((char *)msg0(msg0(exception, "name"), "UTF8String"),
+             "NSRangeException") != 0) {
+    return -3;
+  }
+  return 0;
+}
+
+NSUInteger background_test_run(id run_loop, id firings) {
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSDate", 0x0600)),
+      sel_registerName("dateWithTimeIntervalSinceNow:"), 0.05);
+  msg0(firings, "removeAllObjects");
+  ((void (*)(id, SEL, id))objc_msgSend)(
+      run_loop, sel_registerName("runUntilDate:"), date);
+  return (NSUInteger)msg0(firings, "count");
+}
+
+// Run the run loop until a replayed lifecycle event (see input_replay.txt)
+// moves the app to the given state.
+bool background_test_wait_for_state(id app, id run_loop, id firings,
+                                    int state) {
+  for (int i = 0; i < 100; i++) {
+    if ((int)msg0(app, "applicationState") == state) {
+      return true;
+    }
+    background_test_run(run_loop, firings);
+  }
+  return false;
+}
+
+int test_UIApplication_background() {
+  CFStringRef app_name =
+      CFStringCreateWithCString(NULL, "UIApplication", 0x0600);
+  id app = msg0(NSClassFromString(app_name), "sharedApplication");
+  id run_loop = msg0(NSClassFromString(CFStringCreateWithCString(
+                         NULL, "NSRunLoop", 0x0600)),
+                     "currentRunLoop");
+  id timer_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSTimer", 0x0600));
+  // The timer adds itself to the array each time it fires.
+  id firings = new_object("NSMutableArray");
+  id timer = ((id(*)(id, SEL, double, id, SEL, id, bool))objc_msgSend)(
+      timer_class,
+      sel_registerName(
+          "scheduledTimerWithTimeInterval:target:selector:userInfo:repeats:"),
+      0.01, firings, sel_registerName("addObject:"), NULL, true);
+
+  NSUInteger foreground_count = background_test_run(run_loop, firings);
+
+  // The timer doesn't fire while the app is in the background, unless there's
+  // a background task. The window is minimized on frame 20.
+  // 2 is UIApplicationStateBackground.
+  bool backgrounded = background_test_wait_for_state(app, run_loop, firings, 2);
+  NSUInteger background_count = background_test_run(run_loop, firings);
+  NSUInteger task = (NSUInteger)objc_msgSend(
+      app, sel_registerName("beginBackgroundTaskWithExpirationHandler:"),
+      NULL);
+  double remaining = ((double (*)(id, SEL))objc_msgSend)(
+      app, sel_registerName("backgroundTimeRemaining"));
+  NSUInteger task_count = background_test_run(run_loop, firings);
+  ((void (*)(id, SEL, NSUInteger))objc_msgSend)(
+      app, sel_registerName("endBackgroundTask:"), task);
+  NSUInteger ended_count = background_test_run(run_loop, firings);
+
+  // The timer fires again once the app is back in the foreground. The window
+  // is restored on frame 60. 0 is UIApplicationStateActive.
+  bool resumed = background_test_wait_for_state(app, run_loop, firings, 0);
+  NSUInteger resumed_count = background_test_run(run_loop, firings);
+
+  msg0(timer, "invalidate");
+  msg0(firings, "removeAllObjects");
+  msg0(firings, "release");
+
+  if (foreground_count == 0) {
+    return -1;
+  }
+  if (!backgrounded || background_count != 0) {
+    return -2;
+  }
+  if (task == 0 || remaining <= 0 || remaining > 600 || task_count == 0) {
+    return -3;
+  }
+  if (ended_count != 0) {
+    return -4;
+  }
+  if (!resumed || resumed_count == 0) {
+    return -5;
+  }
+  return 0;
+}
+
+// Delegate messages are logged in upper case, notifications in lower case.
+char lifecycle_test_log[32];
+
+void lifecycle_test_append(char event) {
+  size_t length = strlen(lifecycle_test_log);
+  if (length < sizeof(lifecycle_test_log) - 1) {
+    lifecycle_test_log[length] = event;
+  }
+}
+void lifecycle_test_will_resign_active(id self, SEL _cmd, id app) {
+  lifecycle_test_append('R');
+}
+void lifecycle_test_did_enter_background(id self, SEL _cmd, id app) {
+  lifecycle_test_append('B');
+}
+void lifecycle_test_will_enter_foreground(id self, SEL _cmd, id app) {
+  lifecycle_test_append('F');
+}
+void lifecycle_test_did_become_active(id self, SEL _cmd, id app) {
+  lifecycle_test_append('A');
+}
+const char *lifecycle_test_notifications[4] = {
+    "UIApplicationWillResignActiveNotification",
+    "UIApplicationDidEnterBackgroundNotification",
+    "UIApplicationWillEnterForegroundNotification",
+    "UIApplicationDidBecomeActiveNotification",
+};
+void lifecycle_test_notification(id self, SEL _cmd, id notification) {
+  const char *name = (char *)msg0(msg0(notification, "name"), "UTF8String");
+  for (int i = 0; i < 4; i++) {
+    if (strcmp(name, lifecycle_test_notifications[i]) == 0) {
+      lifecycle_test_append("rbfa"[i]);
+    }
+  }
+}
+
+// integration.rs replays window focus and minimize events (see
+// input_replay.txt), which this checks are passed on to the app delegate and
+// as notifications in the right order.
+int test_UIApplication_lifecycle() {
+  id app = msg0(NSClassFromString(CFStringCreateWithCString(
+                    NULL, "UIApplication", 0x0600)),
+                "sharedApplication");
+  id delegate_class = msg0(msg0(app, "delegate"), "class");
+  class_addMethod(delegate_class,
+                  sel_registerName("applicationWillResignActive:"),
+                  (IMP)lifecycle_test_will_resign_active, "v@:@");
+  class_addMethod(delegate_class,
+                  sel_registerName("applicationDidEnterBackground:"),
+                  (IMP)lifecycle_test_did_enter_background, "v@:@");
+  class_addMethod(delegate_class,
+                  sel_registerName("applicationWillEnterForeground:"),
+                  (IMP)lifecycle_test_will_enter_foreground, "v@:@");
+  class_addMethod(delegate_class,
+                  sel_registerName("applicationDidBecomeActive:"),
+                  (IMP)lifecycle_test_did_become_active, "v@:@");
+  SEL notification_sel = sel_registerName("lifecycleTestNotification:");
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSObject", 0x0600)),
+      notification_sel, (IMP)lifecycle_test_notification, "v@:@");
+  id center = msg0(NSClassFromString(CFStringCreateWithCString(
+                       NULL, "NSNotificationCenter", 0x0600)),
+                   "defaultCenter");
+  id observer = new_object("NSObject");
+  for (int i = 0; i < 4; i++) {
+    ((void (*)(id, SEL, id, SEL, id, id))objc_msgSend)(
+        center, sel_registerName("addObserver:selector:name:object:"),
+        observer, notification_sel,
+        new_string(lifecycle_test_notifications[i]), app);
+  }
+
+  // The replayed events are: focus lost, focus gained, focus lost, minimized,
+  // focus lost (which does nothing in the background), restored.
+  memset(lifecycle_test_log, 0, sizeof(lifecycle_test_log));
+  const char *expected = "RrAaRrBbFfAa";
+  for (int i = 0; i < 300 && strlen(lifecycle_test_log) < strlen(expected);
+       i++) {
+    layout_test_run_frame();
+  }
+  objc_msgSend(center, sel_registerName("removeObserver:"), observer);
+  msg0(observer, "release");
+  if (strcmp(lifecycle_test_log, expected) != 0) {
+    return -1;
+  }
+  if ((int)msg0(app, "applicationState") != 0) { // UIApplicationStateActive
+    return -2;
+  }
+  return 0;
+}
+
+int test_NSObject_introspection() {
+  id object_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSObject", 0x0600));
+  id array_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSArray", 0x0600));
+  id string_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSString", 0x0600));
+  SEL is_subclass = sel_registerName("isSubclassOfClass:");
+  SEL is_kind = sel_registerName("isKindOfClass:");
+  id array = new_object("NSMutableArray");
+
+  if (msg0(object_class, "superclass") != NULL) {
+    return -1;
+  }
+  if (msg0(array, "superclass") != msg0(msg0(array, "class"), "superclass")) {
+    return -2;
+  }
+  if ((bool)msg0(array, "isProxy")) {
+    return -3;
+  }
+
+  // Walk up to the root class. Every class on the way is one the instance is
+  // a kind of, and a subclass of NSObject.
+  id class = msg0(array, "class");
+  id last = NULL;
+  int depth = 0;
+  while (class != NULL && depth < 10) {
+    if (!(bool)objc_msgSend(array, is_kind, class) ||
+        !(bool)objc_msgSend(msg0(array, "class"), is_subclass, class) ||
+        !(bool)objc_msgSend(class, is_subclass, object_class)) {
+      return -4;
+    }
+    last = class;
+    class = msg0(class, "superclass");
+    depth++;
+  }
+  if (last != object_class) {
+    return -5;
+  }
+
+  // isSubclassOfClass: agrees with isKindOfClass:.
+  id array_class_of_instance = msg0(array, "class");
+  if ((bool)objc_msgSend(array_class_of_instance, is_subclass, array_class) !=
+          (bool)objc_msgSend(array, is_kind, array_class) ||
+      (bool)objc_msgSend(array_class_of_instance, is_subclass, string_class) !=
+          (bool)objc_msgSend(array, is_kind, string_class) ||
+      (bool)objc_msgSend(array, is_kind, string_class)) {
+    return -6;
+  }
+  if ((bool)objc_msgSend(object_class, is_subclass, array_class)) {
+    return -7;
+  }
+
+  // Key-value coding finds simple getters.
+  id view = new_object("UIView");
+  id layer = objc_msgSend(view, sel_registerName("valueForKey:"),
+                          new_string("layer"));
+  if (layer == NULL || layer != msg0(view, "layer")) {
+    return -8;
+  }
+  msg0(view, "release");
+  msg0(array, "release");
+  return 0;
+}
+
+id kvo_test_change;
+id kvo_test_key_path;
+id kvo_test_object;
+void *kvo_test_context;
+
+void kvo_test_observe(id self, SEL _cmd, id key_path, id object, id change,
+                      void *context) {
+  kvo_test_change = msg0(change, "retain");
+  kvo_test_key_path = key_path;
+  kvo_test_object = object;
+  kvo_test_context = context;
+}
+
+id kvo_test_value_for_undefined_key(id self, SEL _cmd, id key) { return key; }
+
+int test_KVO() {
+  // There's no way to define a class in C, but a method can be added to an
+  // existing one.
+  SEL observe_sel =
+      sel_registerName("observeValueForKeyPath:ofObject:change:context:");
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSObject", 0x0600)),
+      observe_sel, (IMP)kvo_test_observe, "v@:@@@^v");
+  SEL set_value = sel_registerName("setValue:forKey:");
+  SEL object_for_key = sel_registerName("objectForKey:");
+  SEL is_equal = sel_registerName("isEqualToString:");
+  id observer = new_object("NSObject");
+  id controller = new_object("UIViewController");
+  id key = new_string("title");
+  objc_msgSend(controller, sel_registerName("setTitle:"), new_string("old"));
+
+  // NSKeyValueObservingOptionNew | NSKeyValueObservingOptionOld
+  ((void (*)(id, SEL, id, id, NSUInteger, void *))objc_msgSend)(
+      controller, sel_registerName("addObserver:forKeyPath:options:context:"),
+      observer, key, 3, (void *)0x1234);
+  kvo_test_change = NULL;
+  objc_msgSend(controller, set_value, new_string("new"), key);
+  if (kvo_test_change == NULL) {
+    return -1;
+  }
+  if (kvo_test_object != controller || kvo_test_context != (void *)0x1234 ||
+      !(bool)objc_msgSend(kvo_test_key_path, is_equal, key)) {
+    return -2;
+  }
+  id old_value =
+      objc_msgSend(kvo_test_change, object_for_key, new_string("old"));
+  id new_value =
+      objc_msgSend(kvo_test_change, object_for_key, new_string("new"));
+  if (old_value == NULL || new_value == NULL ||
+      !(bool)objc_msgSend(old_value, is_equal, new_string("old")) ||
+      !(bool)objc_msgSend(new_value, is_equal, new_string("new"))) {
+    return -3;
+  }
+  msg0(kvo_test_change, "release");
+
+  // No more notifications once the observer is removed.
+  objc_msgSend(controller, sel_registerName("removeObserver:forKeyPath:"),
+               observer, key);
+  kvo_test_change = NULL;
+  objc_msgSend(controller, set_value, new_string("newer"), key);
+  if (kvo_test_change != NULL) {
+    return -4;
+  }
+
+  // Key paths with more than one key are ignored rather than rejected.
+  ((void (*)(id, SEL, id, id, NSUInteger, void *))objc_msgSend)(
+      controller, sel_registerName("addObserver:forKeyPath:options:context:"),
+      observer, new_string("title.length"), 3, NULL);
+  objc_msgSend(controller, set_value, new_string("newest"), key);
+  if (kvo_test_change != NULL) {
+    return -5;
+  }
+
+  // Keys without a getter go to valueForUndefinedKey:, which raises an
+  // exception unless it's overridden.
+  class_addMethod(NSClassFromString(CFStringCreateWithCString(
+                      NULL, "UIViewController", 0x0600)),
+                  sel_registerName("valueForUndefinedKey:"),
+                  (IMP)kvo_test_value_for_undefined_key, "@@:@");
+  id undefined_key = new_string("noSuchKey");
+  if (objc_msgSend(controller, sel_registerName("valueForKey:"),
+                   undefined_key) != undefined_key) {
+    return -6;
+  }
+
+  msg0(controller, "release");
+  msg0(observer, "release");
+  return 0;
+}
+
+id control_test_targets[4];
+id control_test_senders[4];
+int control_test_count;
+
+void control_test_action(id self, SEL _cmd, id sender) {
+  if (control_test_count < 4) {
+    control_test_targets[control_test_count] = self;
+    control_test_senders[control_test_count] = sender;
+  }
+  control_test_count++;
+}
+
+// The actions go through the shared UIApplication, so this is an app test.
+int test_UIControl() {
+  SEL action = sel_registerName("controlTestAction:");
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSObject", 0x0600)),
+      action, (IMP)control_test_action, "v@:@");
+  SEL add_sel = sel_registerName("addTarget:action:forControlEvents:");
+  SEL send_sel = sel_registerName("sendActionsForControlEvents:");
+  id control = new_object("UIControl");
+  id target1 = new_object("NSObject");
+  id target2 = new_object("NSObject");
+  id target3 = new_object("NSObject");
+  NSUInteger touch_up_inside = 1 << 6;
+  NSUInteger touch_down = 1 << 0;
+  objc_msgSend(control, add_sel, target1, action, touch_up_inside);
+  objc_msgSend(control, add_sel, target2, action, touch_up_inside);
+  objc_msgSend(control, add_sel, target3, action, touch_down);
+
+  // Both targets for the event get the action, with the control as the
+  // sender.
+  control_test_count = 0;
+  objc_msgSend(control, send_sel, touch_up_inside);
+  if (control_test_count != 2 || control_test_targets[0] != target1 ||
+      control_test_targets[1] != target2 ||
+      control_test_senders[0] != control ||
+      control_test_senders[1] != control) {
+    return -1;
+  }
+
+  if ((NSUInteger)msg0(msg0(control, "allTargets"), "count") != 3) {
+    return -2;
+  }
+  id actions = ((id(*)(id, SEL, id, NSUInteger))objc_msgSend)(
+      control, sel_registerName("actionsForTarget:forControlEvent:"), target3,
+      touch_down);
+  if ((NSUInteger)msg0(actions, "count") != 1 ||
+      strcmp((char *)msg0(objc_msgSend(actions,
+                                       sel_registerName("objectAtIndex:"), 0),
+                          "UTF8String"),
+             "controlTestAction:") != 0 ||
+      ((id(*)(id, SEL, id, NSUInteger))objc_msgSend)(
+          control, sel_registerName("actionsForTarget:forControlEvent:"),
+          target3, touch_up_inside) != NULL) {
+    return -3;
+  }
+
+  // A removed target no longer gets the action.
+  objc_msgSend(control,
+               sel_registerName("removeTarget:action:forControlEvents:"),
+               target1, action, touch_up_inside);
+  control_test_count = 0;
+  objc_msgSend(control, send_sel, touch_up_inside | touch_down);
+  if (control_test_count != 2 || control_test_targets[0] != target2 ||
+      control_test_targets[1] != target3) {
+    return -4;
+  }
+
+  msg0(control, "release");
+  msg0(target1, "release");
+  msg0(target2, "release");
+  msg0(target3, "release");
+  return 0;
+}
+
+// integration.rs replays a tap in the middle of the screen on the first frames
+// after UIApplicationMain() is called (see input_replay.txt), so this must be
+// the first app test that runs the run loop.
+int test_UIControl_touches() {
+  SEL action = sel_registerName("controlTestAction:");
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSObject", 0x0600)),
+      action, (IMP)control_test_action, "v@:@");
+  id (*init_with_frame)(id, SEL, CGRect) =
+      (id(*)(id, SEL, CGRect))objc_msgSend;
+  SEL init_sel = sel_registerName("initWithFrame:");
+  CGRect window_frame = {{0, 0}, {320, 480}};
+  id window = init_with_frame(
+      msg0(NSClassFromString(
+               CFStringCreateWithCString(NULL, "UIWindow", 0x0600)),
+           "alloc"),
+      init_sel, window_frame);
+  CGRect control_frame = {{100, 200}, {120, 80}};
+  id control = init_with_frame(
+      msg0(NSClassFromString(
+               CFStringCreateWithCString(NULL, "UIControl", 0x0600)),
+           "alloc"),
+      init_sel, control_frame);
+  id target = new_object("NSObject");
+  objc_msgSend(control, sel_registerName("addTarget:action:forControlEvents:"),
+               target, action, (NSUInteger)1 << 6); // TouchUpInside
+  objc_msgSend(window, sel_registerName("addSubview:"), control);
+  msg0(window, "makeKeyAndVisible");
+
+  control_test_count = 0;
+  for (int i = 0; i < 100 && control_test_count == 0; i++) {
+    layout_test_run_frame();
+  }
+  int result = 0;
+  if (control_test_count != 1 || control_test_targets[0] != target ||
+      control_test_senders[0] != control) {
+    result = -1;
+  }
+
+  ((void (*)(id, SEL, bool))objc_msgSend)(
+      window, sel_registerName("setHidden:"), true);
+  msg0(control, "release");
+  msg0(window, "release");
+  msg0(target, "release");
+  return result;
+}
+
+int test_UIButton() {
+  CGRect (*get_rect)(id, SEL) = (CGRect(*)(id, SEL))objc_msgSend_stret;
+  SEL set_title = sel_registerName("setTitle:forState:");
+  SEL utf8 = sel_registerName("UTF8String");
+  id button = objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIButton", 0x0600)),
+      sel_registerName("buttonWithType:"), 0); // UIButtonTypeCustom
+  id label = msg0(button, "titleLabel");
+  objc_msgSend(button, set_title, new_string("Normal"), 0);
+  objc_msgSend(button, set_title, new_string("Pressed"), 1); // Highlighted
+  if (strcmp((char *)objc_msgSend(msg0(label, "text"), utf8), "Normal") != 0) {
+    return -1;
+  }
+
+  // Touching down highlights the button, which shows the other title.
+  id touch = new_object("UITouch");
+  id touches = objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSSet", 0x0600)),
+      sel_registerName("setWithObject:"), touch);
+  objc_msgSend(button, sel_registerName("touchesBegan:withEvent:"), touches,
+               NULL);
+  if (!(bool)msg0(button, "isHighlighted") ||
+      strcmp((char *)objc_msgSend(msg0(label, "text"), utf8), "Pressed") !=
+          0) {
+    return -2;
+  }
+  objc_msgSend(button, sel_registerName("setHighlighted:"), false);
+  // There's no title for the disabled state, so the normal one is used.
+  objc_msgSend(button, sel_registerName("setEnabled:"), false);
+  if (strcmp((char *)objc_msgSend(msg0(label, "text"), utf8), "Normal") != 0) {
+    return -3;
+  }
+
+  // sizeToFit fits the title and the content insets.
+  UIEdgeInsets insets = {5, 10, 5, 10};
+  ((void (*)(id, SEL, UIEdgeInsets))objc_msgSend)(
+      button, sel_registerName("setContentEdgeInsets:"), insets);
+  msg0(button, "sizeToFit");
+  CGSize title_size = ((CGSize(*)(id, SEL, id))objc_msgSend_stret)(
+      new_string("Normal"), sel_registerName("sizeWithFont:"),
+      msg0(label, "font"));
+  CGRect frame = get_rect(button, sel_registerName("frame"));
+  CGFloat width_error = frame.size.width - (title_size.width + 20);
+  CGFloat height_error = frame.size.height - (title_size.height + 10);
+  if (title_size.width <= 0 || width_error < -0.01 || width_error > 0.01 ||
+      height_error < -0.01 || height_error > 0.01) {
+    return -4;
+  }
+  msg0(touch, "release");
+  return 0;
+}
+
+int test_NSTimeZone() {
+  id tz_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSTimeZone", 0x0600));
+  id date_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSDate", 0x0600));
+  SEL with_name = sel_registerName("timeZoneWithName:");
+  SEL with_interval =
+      sel_registerName("dateWithTimeIntervalSinceReferenceDate:");
+  SEL is_equal = sel_registerName("isEqualToString:");
+  id london = objc_msgSend(tz_class, with_name, new_string("Europe/London"));
+  id tokyo = objc_msgSend(tz_class, with_name, new_string("Asia/Tokyo"));
+  id new_york =
+      objc_msgSend(tz_class, with_name, new_string("America/New_York"));
+  if (london == NULL || tokyo == NULL || new_york == NULL) {
+    return -1;
+  }
+  if (objc_msgSend(tz_class, with_name, new_string("Nowhere/Special")) !=
+      NULL) {
+    return -2;
+  }
+
+  // 2010-01-15 12:00:00 GMT
+  CFAbsoluteTime winter = 285249600;
+  CFGregorianDate london_date =
+      CFAbsoluteTimeGetGregorianDate(winter, (CFTimeZoneRef)london);
+  CFGregorianDate tokyo_date =
+      CFAbsoluteTimeGetGregorianDate(winter, (CFTimeZoneRef)tokyo);
+  if (london_date.hours != 12 || tokyo_date.hours != 21 ||
+      london_date.day != 15 || tokyo_date.day != 15) {
+    return -3;
+  }
+
+  // Daylight saving time applies in New York in July.
+  CFAbsoluteTime summer = winter + 181 * 24 * 60 * 60;
+  id winter_date = ((id(*)(id, SEL, double))objc_msgSend)(
+      date_class, with_interval, winter);
+  id summer_date = ((id(*)(id, SEL, double))objc_msgSend)(
+      date_class, with_interval, summer);
+  SEL seconds_for_date = sel_registerName("secondsFromGMTForDate:");
+  if ((int)objc_msgSend(new_york, seconds_for_date, winter_date) !=
+          -5 * 60 * 60 ||
+      (int)objc_msgSend(new_york, seconds_for_date, summer_date) !=
+          -4 * 60 * 60) {
+    return -4;
+  }
+  SEL is_dst = sel_registerName("isDaylightSavingTimeForDate:");
+  if ((bool)objc_msgSend(new_york, is_dst, winter_date) ||
+      !(bool)objc_msgSend(new_york, is_dst, summer_date)) {
+    return -5;
+  }
+  id abbreviation = objc_msgSend(
+      new_york, sel_registerName("abbreviationForDate:"), summer_date);
+  if (!(bool)objc_msgSend(abbreviation, is_equal, new_string("EDT"))) {
+    return -6;
+  }
+
+  id fixed = ((id(*)(id, SEL, int))objc_msgSend)(
+      tz_class, sel_registerName("timeZoneForSecondsFromGMT:"), 3600);
+  if ((int)msg0(fixed, "secondsFromGMT") != 3600 ||
+      !(bool)objc_msgSend(msg0(fixed, "name"), is_equal,
+                          new_string("GMT+0100"))) {
+    return -7;
+  }
+
+  // Date formatters use their time zone.
+  id formatter = new_object("NSDateFormatter");
+  SEL set_time_zone = sel_registerName("setTimeZone:");
+  SEL string_from_date = sel_registerName("stringFromDate:");
+  objc_msgSend(formatter, sel_registerName("setDateFormat:"),
+               new_string("HH:mm"));
+  objc_msgSend(formatter, set_time_zone, tokyo);
+  id tokyo_string = objc_msgSend(formatter, string_from_date, winter_date);
+  objc_msgSend(formatter, set_time_zone, london);
+  id london_string = objc_msgSend(formatter, string_from_date, winter_date);
+  msg0(formatter, "release");
+  if (!(bool)objc_msgSend(tokyo_string, is_equal, new_string("21:00")) ||
+      !(bool)objc_msgSend(london_string, is_equal, new_string("12:00"))) {
+    return -8;
+  }
+  return 0;
+}
+
+int test_MKMapView() {
+  CFStringRef name = CFStringCreateWithCString(NULL, "MKMapView", 0x0600);
+  id map_view = msg0(NSClassFromString(name), "alloc");
+  CGRect frame = {{0, 0}, {320, 480}};
+  map_view = ((id(*)(id, SEL, CGRect))objc_msgSend)(
+      map_view, sel_registerName("initWithFrame:"), frame);
+
+  MKCoordinateRegion region = {{51.5, -0.125}, {0.1, 0.25}};
+  ((void (*)(id, SEL, MKCoordinateRegion, bool))objc_msgSend)(
+      map_view, sel_registerName("setRegion:animated:"), region, 1);
+  MKCoordinateRegion got = ((MKCoordinateRegion(*)(id, SEL))objc_msgSend_stret)(
+      map_view, sel_registerName("region"));
+  if (got.center.latitude != 51.5 || got.center.longitude != -0.125 ||
+      got.span.latitudeDelta != 0.1 || got.span.longitudeDelta != 0.25) {
+    return -1;
+  }
+
+  id annotation = new_object("MKPointAnnotation");
+  CLLocationCoordinate2D coordinate = {51.5, -0.125};
+  ((void (*)(id, SEL, CLLocationCoordinate2D))objc_msgSend)(
+      annotation, sel_registerName("setCoordinate:"), coordinate);
+  objc_msgSend(map_view, sel_registerName("addAnnotation:"), annotation);
+  id annotations = msg0(map_view, "annotations");
+  if ((int)msg0(annotations, "count") != 1 ||
+      objc_msgSend(annotations, sel_registerName("objectAtIndex:"), 0) !=
+          annotation) {
+    return -2;
+  }
+  SEL view_for = sel_registerName("viewForAnnotation:");
+  id view = objc_msgSend(map_view, view_for, annotation);
+  if (view == NULL || msg0(view, "annotation") != annotation) {
+    return -3;
+  }
+
+  // The annotation is at the center of the region.
+  CGPoint point =
+      ((CGPoint(*)(id, SEL, CLLocationCoordinate2D, id))objc_msgSend_stret)(
+          map_view, sel_registerName("convertCoordinate:toPointToView:"),
+          coordinate, map_view);
+  if (point.x != 160 || point.y != 240) {
+    return -4;
+  }
+
+  ((void (*)(id, SEL, bool))objc_msgSend)(
+      map_view, sel_registerName("setShowsUserLocation:"), 1);
+  if (!(bool)msg0(map_view, "showsUserLocation")) {
+    return -5;
+  }
+
+  objc_msgSend(map_view, sel_registerName("removeAnnotation:"), annotation);
+  bool removed = (int)msg0(msg0(map_view, "annotations"), "count") == 0;
+  msg0(annotation, "release");
+  msg0(map_view, "release");
+  return removed ? 0 : -6;
+}
+
+static int ab_access_granted;
+
+int test_AddressBook() {
+  ABAddressBookRef book = ABAddressBookCreate();
+  if (book == NULL) {
+    return -1;
+  }
+  ab_access_granted = 0;
+  ABAddressBookRequestAccessWithCompletion(book, ^(bool granted,
+                                                   CFErrorRef error) {
+    if (granted && error == NULL) {
+      ab_access_granted++;
+    }
+  });
+  // kABAuthorizationStatusAuthorized
+  if (ab_access_granted != 1 || ABAddressBookGetAuthorizationStatus() != 3) {
+    CFRelease(book);
+    return -2;
+  }
+  // integration.rs doesn't set --contacts=, so there's nobody in it.
+  CFArrayRef people = ABAddressBookCopyArrayOfAllPeople(book);
+  bool empty = people != NULL && CFArrayGetCount(people) == 0 &&
+               ABAddressBookGetPersonCount(book) == 0;
+  if (people != NULL) {
+    CFRelease(people);
+  }
+  CFRelease(book);
+  return empty ? 0 : -3;
+}
+
+int test_NSMutableArray() {
+  id arr = new_object("NSMutableArray");
+  id a = new_object("NSObject");
+  id b = new_object("NSObject");
+  id c = new_object("NSObject");
+  objc_msgSend(arr, sel_registerName("addObject:"), a);
+  objc_msgSend(arr, sel_registerName("addObject:"), c);
+  objc_msgSend(arr, sel_registerName("insertObject:atIndex:"), b, 1);
+  if ((NSUInteger)msg0(arr, "count") != 3 ||
+      objc_msgSend(arr, sel_registerName("objectAtIndex:"), 0) != a ||
+      objc_msgSend(arr, sel_registerName("objectAtIndex:"), 1) != b ||
+      objc_msgSend(arr, sel_registerName("objectAtIndex:"), 2) != c) {
+    return -1;
+  }
+  if ((NSUInteger)msg0(b, "retainCount") != 2) {
+    return -2;
+  }
+  objc_msgSend(arr, sel_registerName("removeObject:"), b);
+  if ((NSUInteger)msg0(arr, "count") != 2 ||
+      objc_msgSend(arr, sel_registerName("objectAtIndex:"), 0) != a ||
+      objc_msgSend(arr, sel_registerName("objectAtIndex:"), 1) != c) {
+    return -3;
+  }
+  if ((NSUInteger)msg0(b, "retainCount") != 1) {
+    return -4;
+  }
+  objc_msgSend(arr,
+               sel_registerName("exchangeObjectAtIndex:withObjectAtIndex:"), 0,
+               1);
+  if (objc_msgSend(arr, sel_registerName("objectAtIndex:"), 0) != c ||
+      objc_msgSend(arr, sel_registerName("objectAtIndex:"), 1) != a) {
+    return -5;
+  }
+  msg0(arr, "removeAllObjects");
+  if ((NSUInteger)msg0(arr, "count") != 0 ||
+      (NSUInteger)msg0(a, "retainCount") != 1) {
+    return -6;
+  }
+  msg0(arr, "release");
+  msg0(a, "release");
+  msg0(b, "release");
+  msg0(c, "release");
+  return 0;
+}
+
+int test_NSArray_concat_and_search() {
+  id array_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSArray", 0x0600));
+  id a = objc_msgSend(array_class, sel_registerName("arrayWithObjects:"),
+                      new_string("a"), new_string("b"), NULL);
+  id b = objc_msgSend(array_class, sel_registerName("arrayWithObjects:"),
+                      new_string("c"), new_string("d"), NULL);
+  id ab = objc_msgSend(a, sel_registerName("arrayByAddingObjectsFromArray:"),
+                       b);
+  ab = objc_msgSend(ab, sel_registerName("arrayByAddingObject:"),
+                    new_string("e"));
+  id joined = objc_msgSend(ab, sel_registerName("componentsJoinedByString:"),
+                           new_string(""));
+  if (strcmp((char *)msg0(joined, "UTF8String"), "abcde") != 0 ||
+      (NSUInteger)msg0(a, "count") != 2) {
+    return -1;
+  }
+
+  id mutable = new_object("NSMutableArray");
+  objc_msgSend(mutable, sel_registerName("addObjectsFromArray:"), ab);
+  objc_msgSend(mutable, sel_registerName("addObjectsFromArray:"), mutable);
+  if ((NSUInteger)msg0(mutable, "count") != 10) {
+    return -2;
+  }
+
+  id empty = new_object("NSArray");
+  id sub = ((id(*)(id, SEL, NSRange))objc_msgSend)(
+      ab, sel_registerName("subarrayWithRange:"), (NSRange){1, 3});
+  joined = objc_msgSend(sub, sel_registerName("componentsJoinedByString:"),
+                        new_string(""));
+  if (strcmp((char *)msg0(joined, "UTF8String"), "bcd") != 0 ||
+      strcmp((char *)msg0(msg0(sub, "firstObject"), "UTF8String"), "b") != 0 ||
+      msg0(empty, "firstObject") != NULL) {
+    return -3;
+  }
+
+  // The search uses isEqual:, so a different string object still matches.
+  SEL index_of = sel_registerName("indexOfObject:");
+  SEL contains = sel_registerName("containsObject:");
+  if ((NSUInteger)objc_msgSend(ab, index_of, new_string("d")) != 3 ||
+      (NSUInteger)objc_msgSend(ab, index_of, new_string("z")) != 0x7fffffff ||
+      !(bool)objc_msgSend(mutable, contains, new_string("e")) ||
+      (bool)objc_msgSend(sub, contains, new_string("a"))) {
+    return -4;
+  }
+
+  msg0(empty, "release");
+  msg0(mutable, "release");
+  return 0;
+}
+
+int test_NSArray_componentsJoinedByString() {
+  id number = objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSNumber", 0x0600)),
+      sel_registerName("numberWithInt:"), 42);
+  id arr = objc_msgSend(
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSArray", 0x0600)),
+      sel_registerName("arrayWithObjects:"), new_string("a"), number,
+      new_string("b c"), NULL);
+  id line = objc_msgSend(arr, sel_registerName("componentsJoinedByString:"),
+                         new_string(","));
+  if (strcmp((char *)msg0(line, "UTF8String"), "a,42,b c") != 0) {
+    return -1;
+  }
+  id empty_arr = new_object("NSArray");
+  id empty = objc_msgSend(empty_arr,
+                          sel_registerName("componentsJoinedByString:"),
+                          new_string(","));
+  msg0(empty_arr, "release");
+  if ((NSUInteger)msg0(empty, "length") != 0) {
+    return -2;
+  }
+  return 0;
+}
+
+int test_NSString_path_components() {
+  SEL append_component = sel_registerName("stringByAppendingPathComponent:");
+  SEL append_extension = sel_registerName("stringByAppendingPathExtension:");
+  id path = new_string("/tmp//");
+  path = objc_msgSend(path, append_component, new_string("dir/"));
+  path = objc_msgSend(path, append_component, new_string("file"));
+  path = objc_msgSend(path, append_extension, new_string("png"));
+  if (strcmp((char *)msg0(path, "UTF8String"), "/tmp/dir/file.png") != 0) {
+    return -1;
+  }
+  if (strcmp((char *)msg0(msg0(path, "lastPathComponent"), "UTF8String"),
+             "file.png") != 0 ||
+      strcmp((char *)msg0(msg0(path, "pathExtension"), "UTF8String"),
+             "png") != 0) {
+    return -2;
+  }
+  path = msg0(path, "stringByDeletingPathExtension");
+  path = msg0(path, "stringByDeletingLastPathComponent");
+  path = msg0(path, "stringByDeletingLastPathComponent");
+  if (strcmp((char *)msg0(path, "UTF8String"), "/tmp") != 0) {
+    return -3;
+  }
+  path = msg0(path, "stringByDeletingLastPathComponent");
+  if (strcmp((char *)msg0(path, "UTF8String"), "/") != 0 ||
+      strcmp((char *)msg0(objc_msgSend(path, append_component,
+                                       new_string("a")),
+                          "UTF8String"),
+             "/a") != 0) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_NSString_numeric_values() {
+  if ((int)msg0(new_string("42abc"), "intValue") != 42 ||
+      (int)msg0(new_string("  -17"), "integerValue") != -17 ||
+      (int)msg0(new_string("99999999999"), "intValue") != 2147483647) {
+    return -1;
+  }
+  long long ll = ((long long (*)(id, SEL))objc_msgSend)(
+      new_string("99999999999"), sel_registerName("longLongValue"));
+  if (ll != 99999999999LL) {
+    return -2;
+  }
+  double d = ((double (*)(id, SEL))objc_msgSend)(
+      new_string(" -3.14"), sel_registerName("doubleValue"));
+  float f = ((float (*)(id, SEL))objc_msgSend)(new_string("2.5e2x"),
+                                                sel_registerName("floatValue"));
+  if (d != -3.14 || f != 250.0f) {
+    return -3;
+  }
+  if (!(bool)msg0(new_string("YES"), "boolValue") ||
+      !(bool)msg0(new_string("true"), "boolValue") ||
+      !(bool)msg0(new_string(" 007"), "boolValue") ||
+      (bool)msg0(new_string("NO"), "boolValue") ||
+      (bool)msg0(new_string("0"), "boolValue")) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_NSString_rangeOfString() {
+  NSRange (*range_of_string)(id, SEL, id, NSUInteger) =
+      (NSRange(*)(id, SEL, id, NSUInteger))objc_msgSend_stret;
+  NSRange (*range_of_string_in_range)(id, SEL, id, NSUInteger, NSRange) =
+      (NSRange(*)(id, SEL, id, NSUInteger, NSRange))objc_msgSend_stret;
+  SEL sel = sel_registerName("rangeOfString:options:");
+  SEL sel_range = sel_registerName("rangeOfString:options:range:");
+  id str = new_string("Hello, hello world");
+
+  NSRange r = range_of_string(str, sel, new_string("HELLO"), 0);
+  if (r.location != 0x7fffffff || r.length != 0) {
+    return -1;
+  }
+  // NSCaseInsensitiveSearch
+  r = range_of_string(str, sel, new_string("HELLO"), 1);
+  if (r.location != 0 || r.length != 5) {
+    return -2;
+  }
+  // NSCaseInsensitiveSearch | NSBackwardsSearch
+  r = range_of_string(str, sel, new_string("HELLO"), 1 | 4);
+  if (r.location != 7 || r.length != 5) {
+    return -3;
+  }
+  // NSAnchoredSearch only matches at the start (or end, if backwards).
+  r = range_of_string(str, sel, new_string("world"), 8);
+  if (r.location != 0x7fffffff) {
+    return -4;
+  }
+  r = range_of_string(str, sel, new_string("world"), 8 | 4);
+  if (r.location != 13 || r.length != 5) {
+    return -5;
+  }
+  // The search is limited to the range, but the result is relative to the
+  // whole string.
+  r = range_of_string_in_range(str, sel_range, new_string("hello"), 0,
+                               (NSRange){1, 10});
+  if (r.location != 0x7fffffff) {
+    return -6;
+  }
+  r = range_of_string_in_range(str, sel_range, new_string("hello"), 0,
+                               (NSRange){1, 11});
+  if (r.location != 7 || r.length != 5) {
+    return -7;
+  }
+
+  SEL has_prefix = sel_registerName("hasPrefix:");
+  SEL has_suffix = sel_registerName("hasSuffix:");
+  SEL contains = sel_registerName("containsString:");
+  id empty = new_string("");
+  if (!(bool)objc_msgSend(str, has_prefix, new_string("Hello")) ||
+      (bool)objc_msgSend(str, has_prefix, new_string("hello")) ||
+      !(bool)objc_msgSend(str, has_suffix, new_string("world")) ||
+      !(bool)objc_msgSend(str, has_suffix, str) ||
+      !(bool)objc_msgSend(str, contains, new_string("o, h"))) {
+    return -8;
+  }
+  // The empty string is never a prefix, suffix or substring.
+  if ((bool)objc_msgSend(str, has_prefix, empty) ||
+      (bool)objc_msgSend(str, has_suffix, empty) ||
+      (bool)objc_msgSend(str, contains, empty) ||
+      (bool)objc_msgSend(empty, has_prefix, empty)) {
+    return -9;
+  }
+  return 0;
+}
+
+int test_NSData_base64() {
+  id data_class =
+      NSClassFromString(CFStringCreateWithCString(NULL, "NSData", 0x0600));
+  SEL with_bytes_sel = sel_registerName("dataWithBytes:length:");
+  SEL encode_sel = sel_registerName("base64EncodedStringWithOptions:");
+  SEL decode_sel = sel_registerName("initWithBase64EncodedString:options:");
+
+  // Round trip, including lengths that aren't a multiple of 3.
+  unsigned char bytes[256];
+  for (int i = 0; i < 256; i++) {
+    bytes[i] = (unsigned char)(255 - i);
+  }
+  for (NSUInteger len = 0; len <= 7; len++) {
+    id data = objc_msgSend(data_class, with_bytes_sel, bytes, len);
+    id encoded = objc_msgSend(data, encode_sel, (NSUInteger)0);
+    if ((NSUInteger)msg0(encoded, "length") != (len + 2) / 3 * 4) {
+      return -1;
+    }
+    id decoded = objc_msgSend(msg0(data_class, "alloc"), decode_sel, encoded,
+                              (NSUInteger)0);
+    if (decoded == NULL || (NSUInteger)msg0(decoded, "length") != len ||
+        (len && memcmp(msg0(decoded, "bytes"), bytes, len) != 0)) {
+      return -2;
+    }
+    msg0(decoded, "release");
+  }
+
+  // Line breaks with the 64-character line length option, which must be
+  // ignored explicitly when decoding.
+  id data = objc_msgSend(data_class, with_bytes_sel, bytes, 256);
+  id encoded = objc_msgSend(data, encode_sel, (NSUInteger)1);
+  const char *encoded_str = (const char *)msg0(encoded, "UTF8String");
+  if (strlen(encoded_str) != 344 + 5 * 2 || encoded_str[64] != '\r' ||
+      encoded_str[65] != '\n') {
+    return -3;
+  }
+  if (objc_msgSend(msg0(data_class, "alloc"), decode_sel, encoded,
+                   (NSUInteger)0) != NULL) {
+    return -4;
+  }
+  id decoded = objc_msgSend(msg0(data_class, "alloc"), decode_sel, encoded,
+                            (NSUInteger)1);
+  if ((NSUInteger)msg0(decoded, "length") != 256 ||
+      memcmp(msg0(decoded, "bytes"), bytes, 256) != 0) {
+    return -5;
+  }
+  msg0(decoded, "release");
+
+  // Known vector, and the older API.
+  decoded = objc_msgSend(msg0(data_class, "alloc"),
+                         sel_registerName("initWithBase64Encoding:"),
+                         new_string("SGVsbG8sIHdvcmxkIQ=="));
+  if ((NSUInteger)msg0(decoded, "length") != 13 ||
+      memcmp(msg0(decoded, "bytes"), "Hello, world!", 13) != 0) {
+    return -6;
+  }
+  if (strcmp((const char *)msg0(msg0(decoded, "base64Encoding"),
+                                "UTF8String"),
+             "SGVsbG8sIHdvcmxkIQ==") != 0) {
+    return -7;
+  }
+
+  // Hex description.
+  if (strcmp((const char *)msg0(msg0(decoded, "description"), "UTF8String"),
+             "<48656c6c 6f2c2077 6f726c64 21>") != 0) {
+    return -8;
+  }
+  msg0(decoded, "release");
+
+  // Invalid base64: bad padding, and a character outside the alphabet.
+  const char *invalid[] = {"SGVsbG8", "SGVsbG8==", "SG=sbG8=", "SGV*bG8="};
+  for (int i = 0; i < 4; i++) {
+    if (objc_msgSend(msg0(data_class, "alloc"), decode_sel,
+                     new_string(invalid[i]), (NSUInteger)0) != NULL) {
+      return -9 - i;
+    }
+  }
+  return 0;
+}
+
+#define FUNC_DEF(func)                                                         \
+  { &func, #func }
+struct {
+  int (*func)();
+  const char *name;
+} test_func_array[] = {
+    FUNC_DEF(test_qsort),
+    FUNC_DEF(test_vsnprintf),
+    FUNC_DEF(test_sprintf),
+    FUNC_DEF(test_sscanf),
+    FUNC_DEF(test_errno),
+    FUNC_DEF(test_realloc),
+    FUNC_DEF(test_malloc_size),
+    FUNC_DEF(test_posix_memalign),
+    FUNC_DEF(test_atof),
+    FUNC_DEF(test_strtof),
+    FUNC_DEF(test_getcwd_chdir),
+    FUNC_DEF(test_sem),
+    FUNC_DEF(test_pthread),
+    FUNC_DEF(test_pthread_key),
+    FUNC_DEF(test_CGAffineTransform),
+    FUNC_DEF(test_strncpy),
+    FUNC_DEF(test_strncat),
+    FUNC_DEF(test_strlcpy),
+    FUNC_DEF(test_setlocale),
+    FUNC_DEF(test_strtoul),
+    FUNC_DEF(test_dirent),
+    FUNC_DEF(test_strchr),
+    FUNC_DEF(test_swprintf),
+    FUNC_DEF(test_realpath),
+    FUNC_DEF(test_CFStringFind),
+    FUNC_DEF(test_strcspn),
+    FUNC_DEF(test_mbstowcs),
+    FUNC_DEF(test_CommonCrypto),
+    FUNC_DEF(test_CCCrypt),
+    FUNC_DEF(test_CFMutableString),
+    FUNC_DEF(test_NSMutableArray),
+    FUNC_DEF(test_NSArray_componentsJoinedByString),
+    FUNC_DEF(test_NSArray_concat_and_search),
+    FUNC_DEF(test_NSString_path_components),
+    FUNC_DEF(test_NSString_numeric_values),
+    FUNC_DEF(test_NSString_rangeOfString),
+    FUNC_DEF(test_NSData_base64),
+    FUNC_DEF(test_NSMutableDictionary),
+    FUNC_DEF(test_UIPasteboard),
+    FUNC_DEF(test_MFMailComposeViewController),
+    FUNC_DEF(test_CLLocationManager),
+    FUNC_DEF(test_GameKit),
+    FUNC_DEF(test_StoreKit),
+    FUNC_DEF(test_UIWindow),
+    FUNC_DEF(test_UIView_hitTest),
+    FUNC_DEF(test_UIView_convert),
+    FUNC_DEF(test_UIView_autoresizing),
+    FUNC_DEF(test_UIView_transform),
+    FUNC_DEF(test_UIResponder),
+    FUNC_DEF(test_UIKeyboard),
+    FUNC_DEF(test_UITextView),
+    FUNC_DEF(test_MPMoviePlayerController),
+    FUNC_DEF(test_UIImagePickerController),
+    FUNC_DEF(test_UIActionSheet),
+    FUNC_DEF(test_UITabBarController),
+    FUNC_DEF(test_NSLocalizedString),
+    FUNC_DEF(test_UIFont),
+    FUNC_DEF(test_CGColor),
+    FUNC_DEF(test_CGContextClip),
+    FUNC_DEF(test_CGGradient),
+    FUNC_DEF(test_CGContextCTM),
+    FUNC_DEF(test_CGContextDrawImage),
+    FUNC_DEF(test_UIImage_draw),
+    FUNC_DEF(test_UIImage_PNG),
+    FUNC_DEF(test_UIView_snapshot),
+    FUNC_DEF(test_UIView_alpha_hidden),
+    FUNC_DEF(test_UIView_layout),
+    FUNC_DEF(test_UIView_tag),
+    FUNC_DEF(test_CATransaction),
+    FUNC_DEF(test_CABasicAnimation),
+    FUNC_DEF(test_UIBezierPath),
+    FUNC_DEF(test_CGPath),
+    FUNC_DEF(test_CAShapeLayer),
+    FUNC_DEF(test_CAGradientLayer),
+    FUNC_DEF(test_CALayer_border),
+    FUNC_DEF(test_UIGraphics_PDF),
+    FUNC_DEF(test_SCNetworkReachability),
+    FUNC_DEF(test_dispatch_async),
+    FUNC_DEF(test_performSelectorInBackground),
+    FUNC_DEF(test_dispatch_semaphore_group),
+    FUNC_DEF(test_NSCondition),
+    FUNC_DEF(test_NSCache),
+    FUNC_DEF(test_Block_copy),
+    FUNC_DEF(test_sysctl),
+    FUNC_DEF(test_getaddrinfo),
+    FUNC_DEF(test_sockets),
+    FUNC_DEF(test_CFRunLoop),
+    FUNC_DEF(test_CFString_bridging),
+    FUNC_DEF(test_CFDictionary_CFArray_bridging),
+    FUNC_DEF(test_CFBundle),
+    FUNC_DEF(test_CFPreferences),
+    FUNC_DEF(test_mach_absolute_time),
+    FUNC_DEF(test_wall_clock_time),
+    FUNC_DEF(test_random),
+    FUNC_DEF(test_UIViewController_modal),
+    FUNC_DEF(test_NSStream),
+    FUNC_DEF(test_NSURLConnection_cookies),
+    FUNC_DEF(test_NSMutableURLRequest_POST),
+    FUNC_DEF(test_NSURLConnection_async),
+    FUNC_DEF(test_NSException),
+    FUNC_DEF(test_NSObject_introspection),
+    FUNC_DEF(test_KVO),
+    FUNC_DEF(test_UIButton),
+    FUNC_DEF(test_NSTimeZone),
+    FUNC_DEF(test_MKMapView),
+    FUNC_DEF(test_AddressBook),
+};
+
+// These tests need the shared UIApplication, so they are run by the app
+// delegate once UIApplicationMain() has launched the app.
+struct {
+  int (*func)();
+  const char *name;
+} app_test_func_array[] = {
+    FUNC_DEF(test_UIControl_touches),
+    FUNC_DEF(test_UIApplication_openURL),
+    FUNC_DEF(test_UIStatusBar),
+    FUNC_DEF(test_UIApplication_background),
+    FUNC_DEF(test_UIApplication_lifecycle),
+    FUNC_DEF(test_UIControl),
+};
+
+int tests_run = 0;
+int tests_passed = 0;
+
+void run_test(int (*func)(), const char *name) {
+  printf("%s: ", name);
+  tests_run++;
+  int latest_test_result = func();
+  if (latest_test_result == 0) {
+    printf("OK\n");
+    tests_passed++;
+  } else {
+    printf("FAIL (%d)\n", latest_test_result);
+  }
+}
+
+typedef void (*NSUncaughtExceptionHandler)(id);
+NSUncaughtExceptionHandler NSGetUncaughtExceptionHandler(void);
+void NSSetUncaughtExceptionHandler(NSUncaughtExceptionHandler);
+
+int UIApplicationMain(int, char **, id, id);
+
+int uncaught_exception_exit_code = 1;
+void uncaught_exception_handler(id exception) {
+  if (strcmp((char *)msg0(msg0(exception, "name"), "UTF8String"),
+             "TestException") != 0) {
+    exit(1);
+  }
+  exit(uncaught_exception_exit_code);
+}
+
+bool main_test_did_finish_launching(id self, SEL _cmd, id app, id options) {
+  id shared_app = msg0(NSClassFromString(CFStringCreateWithCString(
+                           NULL, "UIApplication", 0x0600)),
+                       "sharedApplication");
+  printf("test_UIApplicationMain: ");
+  tests_run++;
+  if (shared_app == app && msg0(app, "delegate") == self) {
+    printf("OK\n");
+    tests_passed++;
+  } else {
+    printf("FAIL\n");
+  }
+
+  int n = sizeof(app_test_func_array) / sizeof(app_test_func_array[0]);
+  int i;
+  for (i = 0; i < n; i++) {
+    run_test(app_test_func_array[i].func, app_test_func_array[i].name);
+  }
+
+  printf("Passed %d out of %d tests\n", tests_passed, tests_run);
+
+  // Finally, an uncaught exception should be reported (integration.rs checks
+  // this) and then passed to the handler, which exits.
+  uncaught_exception_exit_code = tests_run == tests_passed ? 0 : 1;
+  exception_test_raise("uncaught");
+  // Not reached
+  return true;
+}
+
+// Because no libc is linked into this executable, there is no libc entry point
+// to call main. Instead, integration.rs tells Clang to set the _main symbol
+// as the entry point. (It has to be _main because a C compiler will throw
+// away stuff not called by main().) Since this is the true entry point, there's
+// no argc or argv and we must call exit() ourselves.
+int main() {
+  int n = sizeof(test_func_array) / sizeof(test_func_array[0]);
+  int i;
+  for (i = 0; i < n; i++) {
+    run_test(test_func_array[i].func, test_func_array[i].name);
+  }
+
+  NSSetUncaughtExceptionHandler(uncaught_exception_handler);
+  if (NSGetUncaughtExceptionHandler() != uncaught_exception_handler) {
+    exit(1);
+  }
+
+  // UIApplicationMain() never returns, so it has to be tested last. The
+  // delegate runs the remaining tests once the app has finished launching.
+  class_addMethod(
+      NSClassFromString(CFStringCreateWithCString(NULL, "UIResponder", 0x0600)),
+      sel_registerName("application:didFinishLaunchingWithOptions:"),
+      (IMP)main_test_did_finish_launching, "c@:@@");
+  UIApplicationMain(0, NULL, NULL,
+                    CFStringCreateWithCString(NULL, "UIResponder", 0x0600));
+  // Not reached
+  exit(1);
 }
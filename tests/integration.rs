@@ -61,6 +61,8 @@ fn build_test_app(tests_dir: &Path, test_app_path: &Path) -> Result<(), Box<dyn
         // If enabled, the stack protection causes a null pointer crash in some
         // functions. This is probably because ___stack_chk_guard isn't linked.
         .arg("-fno-stack-protector")
+        // Clang only enables blocks by default for iPhone OS 3.2 and later.
+        .arg("-fblocks")
         // Pass four args to the linker:
         // `-e _main` sets the mangled C main() function as the entry point
         // (normally the libc provides an entry point calling main(), but we
@@ -105,6 +107,20 @@ fn run_test_app() -> Result<(), Box<dyn Error>> {
         // headless mode avoids a distracting window briefly appearing during
         // testing, and works in CI.
         .arg("--headless")
+        // checked by test_CLLocationManager
+        .arg("--location=51.5007,-0.1246")
+        // checked by test_StoreKit
+        .arg("--in-app-purchases=succeed")
+        // checked by test_random
+        .arg("--random-seed=42")
+        // checked by the app tests, starting with test_UIControl_touches
+        .arg(format!(
+            "--replay-input={}",
+            tests_dir
+                .join("TestApp_source")
+                .join("input_replay.txt")
+                .display()
+        ))
         .output()
         .expect("failed to execute touchHLE process");
 
@@ -117,6 +133,14 @@ fn run_test_app() -> Result<(), Box<dyn Error>> {
         find_subsequence(output.stderr.as_slice(), b"CPU emulation begins now."),
         None
     );
+    // checked by the end of main() in TestApp
+    assert_ne!(
+        find_subsequence(
+            output.stderr.as_slice(),
+            b"*** Terminating app due to uncaught exception 'TestException', reason: 'reason: uncaught'"
+        ),
+        None
+    );
 
     Ok(())
 }